@@ -3,12 +3,43 @@ in vec3 v_normal;
 in vec3 v_position;
 in vec3 v_world_position;
 out vec4 color;
+uniform float fog_mode;
+uniform vec4 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+uniform float fog_density;
 uniform vec3 light_pos;
 uniform vec4 ambient_color;
 uniform vec4 diffuse_color;
 uniform vec4 specular_color;
 uniform vec4 rim_color;
 uniform vec3 camera_pos;
+uniform sampler2D environment_map;
+uniform float environment_strength;
+uniform float reflectivity;
+
+const float PI = 3.14159265359;
+
+vec2 equirectangular_uv(vec3 direction) {
+    return vec2(
+        atan(direction.x, -direction.z) / (2.0 * PI) + 0.5,
+        asin(clamp(direction.y, -1.0, 1.0)) / PI + 0.5
+    );
+}
+
+float fog_amount(vec3 world_position) {
+    float distance = length(world_position - camera_pos);
+    float amount = 0.0;
+    if (fog_mode > 2.5) {
+        float d = fog_density * distance;
+        amount = 1.0 - exp(-d * d);
+    } else if (fog_mode > 1.5) {
+        amount = 1.0 - exp(-fog_density * distance);
+    } else if (fog_mode > 0.5) {
+        amount = clamp((distance - fog_start) / max(fog_end - fog_start, 0.0001), 0.0, 1.0);
+    }
+    return amount * fog_color.a;
+}
 
 void main() {
     vec3 normal = normalize(v_normal);
@@ -29,5 +60,16 @@ void main() {
     vec3 rim_light = rim * (rim_color.xyz * rim_color.w);
 
     vec3 final_color = ambient + diffuse + specular + rim_light;
+
+    if (environment_strength > 0.0) {
+        // a very blurry mip of the sky is a cheap stand-in for proper irradiance
+        vec3 sky = textureLod(environment_map, equirectangular_uv(normal), 8.0).rgb;
+        final_color += sky * diffuse_color.rgb * environment_strength;
+
+        vec3 reflected = reflect(-view_dir, normal);
+        vec3 reflection = textureLod(environment_map, equirectangular_uv(reflected), 0.0).rgb;
+        final_color = mix(final_color, reflection * environment_strength, reflectivity);
+    }
     color = vec4(final_color, 1.0);
+    color.rgb = mix(color.rgb, fog_color.rgb, fog_amount(v_world_position));
 }
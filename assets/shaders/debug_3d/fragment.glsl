@@ -0,0 +1,24 @@
+#version 150
+
+in vec3 v_normal;
+in vec2 v_tex_coords;
+out vec4 color;
+
+// same order as DebugRenderMode
+uniform float debug_mode;
+
+void main() {
+    if (debug_mode < 1.5) {
+        color = vec4(0.6, 1.0, 0.3, 1.0);
+    } else if (debug_mode < 2.5) {
+        color = vec4(normalize(v_normal) * 0.5 + 0.5, 1.0);
+    } else if (debug_mode < 3.5) {
+        vec2 cell = floor(v_tex_coords * 8.0);
+        float checker = mod(cell.x + cell.y, 2.0);
+        // tinted so it's clear which way the UVs go
+        vec3 tint = vec3(fract(v_tex_coords), 1.0) * 0.5 + 0.5;
+        color = vec4(mix(vec3(0.15), vec3(0.9), checker) * tint, 1.0);
+    } else {
+        color = vec4(0.12, 0.05, 0.02, 1.0);
+    }
+}
@@ -1,9 +1,31 @@
 #version 140
 
 in vec4 vertex_color;
+in vec3 v_world_position;
 out vec4 color;
+uniform vec3 camera_pos;
+uniform float fog_mode;
+uniform vec4 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+uniform float fog_density;
+
+float fog_amount(vec3 world_position) {
+    float distance = length(world_position - camera_pos);
+    float amount = 0.0;
+    if (fog_mode > 2.5) {
+        float d = fog_density * distance;
+        amount = 1.0 - exp(-d * d);
+    } else if (fog_mode > 1.5) {
+        amount = 1.0 - exp(-fog_density * distance);
+    } else if (fog_mode > 0.5) {
+        amount = clamp((distance - fog_start) / max(fog_end - fog_start, 0.0001), 0.0, 1.0);
+    }
+    return amount * fog_color.a;
+}
 
 void main() {
     // color = vec4(1.0, 0.0, 0.0, 1.0);
     color = vertex_color;
+    color.rgb = mix(color.rgb, fog_color.rgb, fog_amount(v_world_position));
 }
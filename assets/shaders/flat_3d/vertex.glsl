@@ -4,12 +4,15 @@ in vec3 position;
 in vec3 normal;
 in vec2 tex_coords;
 out vec4 vertex_color;
+out vec3 v_world_position;
 
 uniform mat4 model_matrix;
 uniform mat4 view_proj_matrix;
 uniform vec4 color;
 
 void main() {
+    vec4 world_position = model_matrix * vec4(position, 1.0);
+    v_world_position = world_position.xyz;
     vertex_color = color;
-    gl_Position = view_proj_matrix * model_matrix * vec4(position, 1.0);
+    gl_Position = view_proj_matrix * world_position;
 }
@@ -1,13 +1,49 @@
 #version 140
 
 in vec3 v_normal;
+in vec2 v_tex_coords;
+in vec4 v_tangent;
+in vec3 v_world_position;
 out vec4 color;
+uniform vec3 camera_pos;
+uniform float fog_mode;
+uniform vec4 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+uniform float fog_density;
 uniform vec3 light_pos;
 uniform vec4 dark_color;
 uniform vec4 regular_color;
+uniform sampler2D normal_map;
+uniform float use_normal_map;
+uniform float normal_scale;
+
+float fog_amount(vec3 world_position) {
+    float distance = length(world_position - camera_pos);
+    float amount = 0.0;
+    if (fog_mode > 2.5) {
+        float d = fog_density * distance;
+        amount = 1.0 - exp(-d * d);
+    } else if (fog_mode > 1.5) {
+        amount = 1.0 - exp(-fog_density * distance);
+    } else if (fog_mode > 0.5) {
+        amount = clamp((distance - fog_start) / max(fog_end - fog_start, 0.0001), 0.0, 1.0);
+    }
+    return amount * fog_color.a;
+}
 
 void main() {
-    float brightness = dot(normalize(v_normal), normalize(light_pos));
+    vec3 normal = normalize(v_normal);
+    if (use_normal_map > 0.5) {
+        vec3 tangent = normalize(v_tangent.xyz - normal * dot(normal, v_tangent.xyz));
+        vec3 bitangent = cross(normal, tangent) * v_tangent.w;
+        vec3 tangent_normal = texture(normal_map, v_tex_coords).xyz * 2.0 - 1.0;
+        tangent_normal.xy *= normal_scale;
+        normal = normalize(mat3(tangent, bitangent, normal) * tangent_normal);
+    }
+
+    float brightness = dot(normal, normalize(light_pos));
     float value = (brightness + 1) / 2;
     color = mix(dark_color, regular_color, value);
+    color.rgb = mix(color.rgb, fog_color.rgb, fog_amount(v_world_position));
 }
@@ -0,0 +1,28 @@
+#version 150
+
+in vec2 position;
+in vec3 center;
+in float size;
+in float rotation;
+in vec4 color;
+
+out vec2 v_position;
+out vec4 v_color;
+out vec3 v_world_position;
+
+uniform mat4 view_proj_matrix;
+uniform vec3 camera_right;
+uniform vec3 camera_up;
+
+void main() {
+    float s = sin(rotation);
+    float c = cos(rotation);
+    vec2 corner = vec2(position.x * c - position.y * s, position.x * s + position.y * c);
+    vec3 world_position = center + (camera_right * corner.x + camera_up * corner.y) * size * 0.5;
+
+    v_position = position;
+    v_color = color;
+    v_world_position = world_position;
+
+    gl_Position = view_proj_matrix * vec4(world_position, 1.0);
+}
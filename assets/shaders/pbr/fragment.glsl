@@ -0,0 +1,179 @@
+#version 150
+in vec3 v_normal;
+in vec3 v_world_position;
+in vec2 v_tex_coords;
+in vec4 v_tangent;
+out vec4 color;
+uniform float fog_mode;
+uniform vec4 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+uniform float fog_density;
+
+uniform vec4 albedo;
+uniform float metallic;
+uniform float roughness;
+uniform vec4 emissive;
+uniform float normal_scale;
+
+uniform sampler2D albedo_map;
+uniform sampler2D metallic_roughness_map;
+uniform sampler2D normal_map;
+uniform sampler2D emissive_map;
+uniform float use_albedo_map;
+uniform float use_metallic_roughness_map;
+uniform float use_normal_map;
+uniform float use_emissive_map;
+
+uniform vec3 light_pos;
+uniform vec4 light_color;
+uniform float light_intensity;
+uniform vec4 ambient_color;
+uniform vec3 camera_pos;
+uniform sampler2D environment_map;
+uniform float environment_strength;
+// set when the frame is tonemapped at the end instead
+uniform float hdr;
+
+const float PI = 3.14159265359;
+
+vec2 equirectangular_uv(vec3 direction) {
+    return vec2(
+        atan(direction.x, -direction.z) / (2.0 * PI) + 0.5,
+        asin(clamp(direction.y, -1.0, 1.0)) / PI + 0.5
+    );
+}
+
+vec3 to_linear(vec3 c) {
+    return pow(c, vec3(2.2));
+}
+
+vec3 perturb_normal(vec3 normal, vec3 tangent_normal) {
+    vec3 tangent = v_tangent.xyz - normal * dot(normal, v_tangent.xyz);
+    vec3 bitangent = cross(normal, tangent) * v_tangent.w;
+
+    if (dot(tangent, tangent) < 0.000001) {
+        // no tangent, so the frame is worked out from screen space derivatives. V goes down
+        // the image, and normal maps have +Y pointing up it
+        vec3 dp1 = dFdx(v_world_position);
+        vec3 dp2 = dFdy(v_world_position);
+        vec2 duv1 = dFdx(v_tex_coords);
+        vec2 duv2 = dFdy(v_tex_coords);
+
+        vec3 dp2perp = cross(dp2, normal);
+        vec3 dp1perp = cross(normal, dp1);
+        tangent = dp2perp * duv1.x + dp1perp * duv2.x;
+        bitangent = -(dp2perp * duv1.y + dp1perp * duv2.y);
+    }
+
+    float inv_max = inversesqrt(max(dot(tangent, tangent), dot(bitangent, bitangent)));
+    mat3 tbn = mat3(tangent * inv_max, bitangent * inv_max, normal);
+    return normalize(tbn * tangent_normal);
+}
+
+float distribution_ggx(float n_dot_h, float a) {
+    float a2 = a * a;
+    float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * d * d);
+}
+
+float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+    float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+    float gv = n_dot_v / (n_dot_v * (1.0 - k) + k);
+    float gl = n_dot_l / (n_dot_l * (1.0 - k) + k);
+    return gv * gl;
+}
+
+vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(clamp(1.0 - cos_theta, 0.0, 1.0), 5.0);
+}
+
+float fog_amount(vec3 world_position) {
+    float distance = length(world_position - camera_pos);
+    float amount = 0.0;
+    if (fog_mode > 2.5) {
+        float d = fog_density * distance;
+        amount = 1.0 - exp(-d * d);
+    } else if (fog_mode > 1.5) {
+        amount = 1.0 - exp(-fog_density * distance);
+    } else if (fog_mode > 0.5) {
+        amount = clamp((distance - fog_start) / max(fog_end - fog_start, 0.0001), 0.0, 1.0);
+    }
+    return amount * fog_color.a;
+}
+
+void main() {
+    vec4 base = vec4(to_linear(albedo.rgb), albedo.a);
+    if (use_albedo_map > 0.5) {
+        vec4 texel = texture(albedo_map, v_tex_coords);
+        base *= vec4(to_linear(texel.rgb), texel.a);
+    }
+
+    float metal = metallic;
+    float rough = roughness;
+    if (use_metallic_roughness_map > 0.5) {
+        // glTF packing: roughness in green, metallic in blue
+        vec4 texel = texture(metallic_roughness_map, v_tex_coords);
+        rough *= texel.g;
+        metal *= texel.b;
+    }
+    rough = clamp(rough, 0.04, 1.0);
+    metal = clamp(metal, 0.0, 1.0);
+
+    vec3 normal = normalize(v_normal);
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+    if (use_normal_map > 0.5) {
+        vec3 tangent_normal = texture(normal_map, v_tex_coords).xyz * 2.0 - 1.0;
+        tangent_normal.xy *= normal_scale;
+        normal = perturb_normal(normal, normalize(tangent_normal));
+    }
+
+    vec3 view_dir = normalize(camera_pos - v_world_position);
+    vec3 to_light = light_pos - v_world_position;
+    vec3 light_dir = normalize(to_light);
+    vec3 half_dir = normalize(view_dir + light_dir);
+
+    float n_dot_v = max(dot(normal, view_dir), 0.0001);
+    float n_dot_l = max(dot(normal, light_dir), 0.0);
+    float n_dot_h = max(dot(normal, half_dir), 0.0);
+
+    vec3 f0 = mix(vec3(0.04), base.rgb, metal);
+    vec3 f = fresnel_schlick(max(dot(half_dir, view_dir), 0.0), f0);
+    float d = distribution_ggx(n_dot_h, rough * rough);
+    float g = geometry_smith(n_dot_v, n_dot_l, rough);
+    vec3 specular = d * g * f / (4.0 * n_dot_v * max(n_dot_l, 0.0001));
+    vec3 diffuse = (1.0 - f) * (1.0 - metal) * base.rgb / PI;
+
+    vec3 radiance = to_linear(light_color.rgb) * light_intensity;
+    vec3 final_color = (diffuse + specular) * radiance * n_dot_l;
+
+    vec3 ambient_diffuse = to_linear(ambient_color.rgb);
+    vec3 ambient_specular = ambient_diffuse;
+    if (environment_strength > 0.0) {
+        // same cheap stand-in for irradiance as blinn_phong, with rougher surfaces reading
+        // blurrier mips for their reflections
+        ambient_diffuse = textureLod(environment_map, equirectangular_uv(normal), 8.0).rgb
+            * environment_strength;
+        vec3 reflected = reflect(-view_dir, normal);
+        ambient_specular = textureLod(environment_map, equirectangular_uv(reflected), rough * 8.0).rgb
+            * environment_strength;
+    }
+    vec3 ambient_f = fresnel_schlick(n_dot_v, f0);
+    final_color += (1.0 - ambient_f) * (1.0 - metal) * base.rgb * ambient_diffuse;
+    final_color += ambient_f * ambient_specular * (1.0 - rough * 0.7);
+
+    vec3 emission = to_linear(emissive.rgb) * emissive.a;
+    if (use_emissive_map > 0.5) {
+        emission *= to_linear(texture(emissive_map, v_tex_coords).rgb);
+    }
+    final_color += emission;
+
+    // lighting is done in linear space, so it needs tonemapping and gamma before it's shown
+    if (hdr < 0.5) {
+        final_color = final_color / (final_color + vec3(1.0));
+    }
+    color = vec4(pow(final_color, vec3(1.0 / 2.2)), base.a);
+    color.rgb = mix(color.rgb, fog_color.rgb, fog_amount(v_world_position));
+}
@@ -0,0 +1,15 @@
+#version 140
+
+in vec2 v_tex_coords;
+in vec4 v_color;
+out vec4 color;
+
+uniform sampler2D tex;
+
+void main() {
+    // only the solid parts of sprites can be picked
+    if (texture(tex, v_tex_coords).a < 0.5) {
+        discard;
+    }
+    color = v_color;
+}
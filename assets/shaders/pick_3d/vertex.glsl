@@ -0,0 +1,10 @@
+#version 140
+
+in vec3 position;
+
+uniform mat4 model_matrix;
+uniform mat4 view_proj_matrix;
+
+void main() {
+    gl_Position = view_proj_matrix * model_matrix * vec4(position, 1.0);
+}
@@ -0,0 +1,55 @@
+#version 140
+
+in vec2 v_tex_coords;
+in vec4 v_color;
+in vec4 v_outline_color;
+in vec4 v_shadow_color;
+// outline width, shadow softness, shadow offset
+in vec4 v_effects;
+in vec4 v_tex_bounds;
+out vec4 color;
+
+uniform sampler2D tex;
+
+// the edge of the glyph is at 0.5, with the inside above it
+float distance_at(vec2 uv) {
+    if (uv.x < v_tex_bounds.x || uv.y < v_tex_bounds.y || uv.x > v_tex_bounds.z || uv.y > v_tex_bounds.w) {
+        return 0.0;
+    }
+    return texture(tex, uv).a;
+}
+
+// porter-duff over, with premultiplied alpha
+vec4 over(vec4 top, vec4 bottom) {
+    return top + bottom * (1.0 - top.a);
+}
+
+void main() {
+    float outline_width = v_effects.x;
+    float softness = v_effects.y;
+    vec2 shadow_offset = v_effects.zw;
+
+    float dist = distance_at(v_tex_coords);
+    // about a pixel on screen, so edges stay sharp however far the text is zoomed
+    float smoothing = max(fwidth(dist) * 0.75, 0.001);
+
+    float fill = smoothstep(0.5 - smoothing, 0.5 + smoothing, dist);
+    float outer_edge = 0.5 - outline_width;
+    float outline = smoothstep(outer_edge - smoothing, outer_edge + smoothing, dist);
+
+    vec4 fill_color = vec4(v_color.rgb, 1.0) * v_color.a * fill;
+    vec4 outline_color = vec4(v_outline_color.rgb, 1.0) * v_outline_color.a * (outline - fill);
+    vec4 glyph = over(fill_color, outline_color);
+
+    float shadow_dist = distance_at(v_tex_coords - shadow_offset);
+    float shadow_edge = smoothing + softness;
+    float shadow = smoothstep(outer_edge - shadow_edge, outer_edge + shadow_edge, shadow_dist);
+    vec4 shadow_color = vec4(v_shadow_color.rgb, 1.0) * v_shadow_color.a * shadow;
+
+    vec4 result = over(glyph, shadow_color);
+    if (result.a <= 0.0) {
+        discard;
+    }
+    // the sprite blending expects straight alpha
+    color = vec4(result.rgb / result.a, result.a);
+}
@@ -0,0 +1,29 @@
+#version 140
+
+in vec3 position;
+in vec2 tex_coords;
+in vec4 color;
+in vec4 outline_color;
+in vec4 shadow_color;
+in vec4 effects;
+in vec4 tex_bounds;
+
+out vec2 v_tex_coords;
+out vec4 v_color;
+out vec4 v_outline_color;
+out vec4 v_shadow_color;
+out vec4 v_effects;
+out vec4 v_tex_bounds;
+
+uniform mat4 projection;
+
+void main() {
+    v_tex_coords = tex_coords;
+    v_color = color;
+    v_outline_color = outline_color;
+    v_shadow_color = shadow_color;
+    v_effects = effects;
+    v_tex_bounds = tex_bounds;
+
+    gl_Position = projection * vec4(position, 1.0);
+}
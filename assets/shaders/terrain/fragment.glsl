@@ -0,0 +1,50 @@
+#version 150
+
+in vec3 v_normal;
+in vec3 v_world_position;
+in vec2 v_tex_coords;
+out vec4 color;
+uniform vec3 camera_pos;
+uniform float fog_mode;
+uniform vec4 fog_color;
+uniform float fog_start;
+uniform float fog_end;
+uniform float fog_density;
+
+uniform sampler2D splat_map;
+uniform sampler2D base_layer;
+uniform sampler2D layer_r;
+uniform sampler2D layer_g;
+uniform sampler2D layer_b;
+uniform float tiling;
+uniform vec3 light_dir;
+
+float fog_amount(vec3 world_position) {
+    float distance = length(world_position - camera_pos);
+    float amount = 0.0;
+    if (fog_mode > 2.5) {
+        float d = fog_density * distance;
+        amount = 1.0 - exp(-d * d);
+    } else if (fog_mode > 1.5) {
+        amount = 1.0 - exp(-fog_density * distance);
+    } else if (fog_mode > 0.5) {
+        amount = clamp((distance - fog_start) / max(fog_end - fog_start, 0.0001), 0.0, 1.0);
+    }
+    return amount * fog_color.a;
+}
+
+void main() {
+    vec3 splat = texture(splat_map, v_tex_coords).rgb;
+    vec2 uv = v_tex_coords * tiling;
+
+    vec3 albedo = texture(base_layer, uv).rgb;
+    albedo = mix(albedo, texture(layer_r, uv).rgb, splat.r);
+    albedo = mix(albedo, texture(layer_g, uv).rgb, splat.g);
+    albedo = mix(albedo, texture(layer_b, uv).rgb, splat.b);
+
+    vec3 normal = normalize(v_normal);
+    float diffuse = max(dot(normal, normalize(light_dir)), 0.0);
+
+    color = vec4(albedo * (0.3 + 0.7 * diffuse), 1.0);
+    color.rgb = mix(color.rgb, fog_color.rgb, fog_amount(v_world_position));
+}
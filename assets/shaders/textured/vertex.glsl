@@ -6,6 +6,7 @@ in vec2 tex_coords;
 
 out vec3 v_normal;
 out vec2 v_tex_coords;
+out vec3 v_world_position;
 
 uniform mat4 model_matrix;
 uniform mat4 view_proj_matrix;
@@ -15,5 +16,7 @@ void main() {
     // v_normal = normal_matrix * normal;
     v_tex_coords = tex_coords;
 
-    gl_Position = view_proj_matrix * model_matrix * vec4(position, 1.0);
+    vec4 world_position = model_matrix * vec4(position, 1.0);
+    v_world_position = world_position.xyz;
+    gl_Position = view_proj_matrix * world_position;
 }
@@ -0,0 +1,78 @@
+//! Collision and broadphase queries against lots of shapes.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use engine_4::collisions::ray::Ray;
+use engine_4::collisions::spatial_grid::SpatialGrid;
+use engine_4::collisions::{AABB2D, Circle, IntersectsWith};
+use engine_4::prelude::Vec2;
+
+fn circles(count: usize) -> Vec<Circle> {
+    let side = (count as f32).sqrt().ceil() as usize;
+    (0..count)
+        .map(|i| Circle {
+            center: Vec2::new((i % side) as f32 * 12.0, (i / side) as f32 * 12.0),
+            radius: 5.0 + (i % 3) as f32,
+        })
+        .collect()
+}
+
+fn grid_of(circles: &[Circle]) -> SpatialGrid {
+    let mut grid = SpatialGrid::new(16.0);
+    for (i, circle) in circles.iter().enumerate() {
+        grid.insert(
+            i,
+            AABB2D::from_center_size(circle.center, Vec2::splat(circle.radius * 2.0)),
+        );
+    }
+    grid
+}
+
+fn brute_force(c: &mut Criterion) {
+    let circles = circles(1_000);
+
+    c.bench_function("collisions/brute_force_pairs", |b| {
+        b.iter(|| {
+            let mut hits = 0;
+            for (i, a) in circles.iter().enumerate() {
+                for b in &circles[i + 1..] {
+                    hits += a.intersects_with(b) as usize;
+                }
+            }
+            black_box(hits)
+        })
+    });
+}
+
+fn spatial_grid(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spatial_grid");
+
+    for count in [1_000, 10_000] {
+        let circles = circles(count);
+        let grid = grid_of(&circles);
+
+        group.bench_with_input(BenchmarkId::new("build", count), &circles, |b, circles| {
+            b.iter(|| black_box(grid_of(circles)))
+        });
+
+        group.bench_with_input(BenchmarkId::new("pairs", count), &grid, |b, grid| {
+            b.iter(|| black_box(grid.pairs()))
+        });
+
+        group.bench_with_input(BenchmarkId::new("query_aabb", count), &grid, |b, grid| {
+            let area = AABB2D::new(Vec2::splat(100.0), Vec2::splat(300.0));
+            b.iter(|| black_box(grid.query_aabb(&area)))
+        });
+
+        group.bench_with_input(BenchmarkId::new("query_ray", count), &grid, |b, grid| {
+            let ray = Ray::new(Vec2::ZERO, Vec2::new(1.0, 0.7));
+            b.iter(|| black_box(grid.query_ray(&ray, 2_000.0)))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, brute_force, spatial_grid);
+criterion_main!(benches);
@@ -0,0 +1,123 @@
+//! Building 2D draw commands, which is where the CPU time of a busy 2D frame goes. None of this
+//! needs a window, since nothing is sent to the GPU.
+
+use std::hint::black_box;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use engine_4::prelude::*;
+
+fn rects(count: usize) -> Vec<Rect> {
+    (0..count)
+        .map(|i| Rect {
+            top_left: Vec2::new((i % 100) as f32 * 10.0, (i / 100) as f32 * 10.0),
+            size: Vec2::splat(8.0),
+            color: Color::SKY_500,
+        })
+        .collect()
+}
+
+fn polylines(count: usize) -> Vec<Polyline> {
+    (0..count)
+        .map(|i| {
+            let points = (0..32)
+                .map(|j| Vec2::new(j as f32 * 4.0, ((i + j) as f32 * 0.3).sin() * 20.0))
+                .collect();
+            Polyline::new(points, 3.0, Color::AMBER_400)
+        })
+        .collect()
+}
+
+fn shapes_with_holes(count: usize) -> Vec<ShapeWithHoles> {
+    let square = |center: Vec2, half: f32| {
+        vec![
+            center + Vec2::new(-half, -half),
+            center + Vec2::new(half, -half),
+            center + Vec2::new(half, half),
+            center + Vec2::new(-half, half),
+        ]
+    };
+
+    (0..count)
+        .map(|i| {
+            let center = Vec2::new(i as f32 * 30.0, 0.0);
+            ShapeWithHoles {
+                outline: square(center, 12.0),
+                holes: vec![square(center, 4.0)],
+                color: Color::EMERALD_500,
+            }
+        })
+        .collect()
+}
+
+fn draw_queue(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw_queue");
+
+    for count in [1_000, 10_000] {
+        let rects = rects(count);
+        group.bench_with_input(BenchmarkId::new("rects", count), &rects, |b, rects| {
+            b.iter(|| {
+                let mut commands = DrawCommands2D::new();
+                for rect in rects {
+                    commands.draw(rect);
+                }
+                black_box(commands)
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("circles", count), &rects, |b, rects| {
+            b.iter(|| {
+                let mut commands = DrawCommands2D::new();
+                for rect in rects {
+                    commands.draw(&Circle {
+                        center: rect.top_left,
+                        radius: rect.size,
+                        color: rect.color,
+                    });
+                }
+                black_box(commands)
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn tessellation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tessellation");
+    group.sample_size(20);
+
+    let polylines = polylines(1_000);
+    group.bench_function("polylines", |b| {
+        b.iter(|| {
+            let mut commands = DrawCommands2D::new();
+            for polyline in &polylines {
+                commands.draw(polyline);
+            }
+            black_box(commands)
+        })
+    });
+    group.bench_function("polylines_parallel", |b| {
+        b.iter(|| {
+            black_box(DrawCommands2D::record_parallel(
+                &polylines,
+                |polyline, commands| commands.draw(polyline),
+            ))
+        })
+    });
+
+    let shapes = shapes_with_holes(1_000);
+    group.bench_function("shapes_with_holes", |b| {
+        b.iter(|| {
+            let mut commands = DrawCommands2D::new();
+            for shape in &shapes {
+                commands.draw(shape);
+            }
+            black_box(commands)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, draw_queue, tessellation);
+criterion_main!(benches);
@@ -0,0 +1,48 @@
+//! Laying out text, which happens every frame for everything drawn with `draw_text`.
+
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use engine_4::prelude::*;
+
+const PARAGRAPH: &str = "The quick brown fox jumps over the lazy dog. Sphinx of black quartz, \
+    judge my vow! How vexingly quick daft zebras jump. Pack my box with five dozen liquor jugs.";
+
+fn text_layout(c: &mut Criterion) {
+    let font = fontdue::Font::from_bytes(
+        include_bytes!("../assets/fonts/inter.ttf") as &[u8],
+        fontdue::FontSettings::default(),
+    )
+    .unwrap();
+
+    let mut group = c.benchmark_group("text_layout");
+
+    group.bench_function("line", |b| {
+        b.iter(|| black_box(layout_text(&font, "Score: 123456", 24.0)))
+    });
+
+    group.bench_function("paragraph", |b| {
+        b.iter(|| black_box(layout_text(&font, PARAGRAPH, 24.0)))
+    });
+
+    group.bench_function("paragraph_boxed", |b| {
+        let style = TextStyle {
+            wrap: true,
+            ..Default::default()
+        };
+        b.iter(|| {
+            black_box(layout_text_boxed(
+                &[&font],
+                PARAGRAPH,
+                24.0,
+                Vec2::new(300.0, f32::INFINITY),
+                &style,
+            ))
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, text_layout);
+criterion_main!(benches);
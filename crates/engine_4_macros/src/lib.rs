@@ -2,7 +2,8 @@ use proc_macro::TokenStream;
 use proc_macro2::TokenStream as Ts2;
 use quote::quote;
 use syn::parse::Parse;
-use syn::{Expr, Ident, Token, Visibility, parse_macro_input};
+use heck::{ToLowerCamelCase, ToSnakeCase, ToUpperCamelCase};
+use syn::{Data, DeriveInput, Expr, Fields, Ident, LitStr, Token, Visibility, parse_macro_input};
 
 struct Actions {
     actions: Vec<Action>,
@@ -143,59 +144,246 @@ pub fn gen_ref_type(input: TokenStream) -> TokenStream {
     } = parse_macro_input!(input as RefTypeParams);
 
     quote! {
-        #[derive(Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
-        pub struct #ty_ref(pub usize);
+        /// A handle to something in the engine's storage. Stops working once what it points
+        /// to is removed, instead of quietly pointing at whatever gets stored there next.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Ord, PartialOrd, Hash)]
+        pub struct #ty_ref {
+            index: u32,
+            generation: u32,
+        }
 
         impl #ty_ref {
+            /// For things the engine stores at startup, which are never removed.
+            pub(crate) const fn from_raw(index: u32, generation: u32) -> Self {
+                Self { index, generation }
+            }
+
+            pub fn index(&self) -> u32 {
+                self.index
+            }
+
+            pub fn generation(&self) -> u32 {
+                self.generation
+            }
+
+            /// Whether what this points to is still there.
+            pub fn is_valid(&self) -> bool {
+                crate::try_get_state().is_some_and(|state| {
+                    state.storage.#storage_name.contains(self.index, self.generation)
+                })
+            }
+
+            pub fn try_get(&self) -> Option<&'static #ty> {
+                get_state().storage.#storage_name.get(self.index, self.generation)
+            }
+
+            pub fn try_get_mut(&self) -> Option<&'static mut #ty> {
+                get_state().storage.#storage_name.get_mut(self.index, self.generation)
+            }
+
+            /// Takes it out of storage. This handle, and every copy of it, stops being valid.
+            /// The engine's own, like the default font, can't be removed, and trying to is
+            /// reported as an error.
+            pub fn remove(self) -> Option<#ty> {
+                let slots = &mut get_state().storage.#storage_name;
+                if slots.is_builtin(self.index) {
+                    crate::error::report_error(crate::error::EngineError::RemovedBuiltin(
+                        stringify!(#ty_ref),
+                    ));
+                    return None;
+                }
+                slots.remove(self.index, self.generation)
+            }
+
+            #[track_caller]
             pub(crate) fn get(&self) -> &'static #ty {
-                &get_state().storage.#storage_name[self.0]
+                self.try_get().unwrap_or_else(|| Self::stale())
             }
 
+            #[track_caller]
             pub(crate) fn get_mut(&self) -> &'static mut #ty {
-                &mut get_state().storage.#storage_name[self.0]
+                self.try_get_mut().unwrap_or_else(|| Self::stale())
+            }
+
+            /// Like `get`, but a stale handle is reported instead of panicking, for drawing, where
+            /// leaving one thing out is better than stopping the game.
+            pub(crate) fn get_or_report(&self) -> Option<&'static #ty> {
+                let value = self.try_get();
+                if value.is_none() {
+                    Self::report_stale();
+                }
+                value
+            }
+
+            pub(crate) fn get_mut_or_report(&self) -> Option<&'static mut #ty> {
+                let value = self.try_get_mut();
+                if value.is_none() {
+                    Self::report_stale();
+                }
+                value
+            }
+
+            fn report_stale() {
+                crate::error::report_error(crate::error::EngineError::StaleRef(stringify!(#ty_ref)));
             }
 
-            pub fn new() -> Self {
-                let id = get_state().storage.#storage_name.len();
-                Self(id)
+            #[track_caller]
+            fn stale() -> ! {
+                panic!("{}", crate::error::EngineError::StaleRef(stringify!(#ty_ref)))
             }
         }
 
         impl crate::utils::EngineCreate<#ty_ref> for #ty {
             fn create(self) -> #ty_ref {
-                let state = get_state();
-                let id = state.storage.#storage_name.len();
-                state.storage.#storage_name.push(self);
-
-                #ty_ref(id)
+                let (index, generation) = get_state().storage.#storage_name.insert(self);
+                #ty_ref { index, generation }
             }
         }
 
         impl std::ops::Deref for #ty_ref {
             type Target = #ty;
             fn deref(&self) -> &Self::Target {
-                &get_state().storage.#storage_name[self.0]
+                self.get()
             }
         }
 
         impl std::ops::DerefMut for #ty_ref {
             fn deref_mut(&mut self) -> &mut Self::Target {
-                &mut get_state().storage.#storage_name[self.0]
+                self.get_mut()
             }
         }
 
         impl std::ops::Index<#ty_ref> for crate::EngineStorage {
             type Output = #ty;
             fn index(&self, index: #ty_ref) -> &Self::Output {
-                &self.#storage_name[index.0]
+                self.#storage_name
+                    .get(index.index, index.generation)
+                    .unwrap_or_else(|| #ty_ref::stale())
             }
         }
 
         impl std::ops::IndexMut<#ty_ref> for crate::EngineStorage {
             fn index_mut(&mut self, index: #ty_ref) -> &mut Self::Output {
-                &mut self.#storage_name[index.0]
+                self.#storage_name
+                    .get_mut(index.index, index.generation)
+                    .unwrap_or_else(|| #ty_ref::stale())
             }
         }
     }
     .into()
 }
+
+#[derive(Default)]
+struct UniformAttrs {
+    name: Option<String>,
+    rename_all: Option<String>,
+    skip: bool,
+}
+
+fn parse_uniform_attrs(attrs: &[syn::Attribute]) -> syn::Result<UniformAttrs> {
+    let mut out = UniformAttrs::default();
+
+    for attr in attrs.iter().filter(|a| a.path().is_ident("uniform")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                out.skip = true;
+            } else if meta.path.is_ident("name") {
+                out.name = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident("rename_all") {
+                out.rename_all = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else {
+                return Err(meta.error("expected `skip`, `name = \"...\"` or `rename_all = \"...\"`"));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(out)
+}
+
+fn rename(name: &str, case: Option<&str>) -> syn::Result<String> {
+    Ok(match case {
+        None | Some("snake_case") => name.to_snake_case(),
+        Some("camelCase") => name.to_lower_camel_case(),
+        Some("PascalCase") => name.to_upper_camel_case(),
+        Some(other) => {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!("unknown rename_all case `{other}`"),
+            ));
+        }
+    })
+}
+
+/// Generates a `glium::uniforms::Uniforms` implementation for a struct, so it can be passed
+/// straight to a draw call instead of building a `uniform!` block by hand.
+///
+/// Every field must implement `AsUniformValue`. Field names are used as the uniform names;
+/// use `#[uniform(name = "...")]` to override one, `#[uniform(rename_all = "camelCase")]` on
+/// the struct to change all of them, and `#[uniform(skip)]` to leave a field out.
+#[proc_macro_derive(Uniforms, attributes(uniform))]
+pub fn derive_uniforms(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match derive_uniforms_inner(input) {
+        Ok(ts) => ts.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn derive_uniforms_inner(input: DeriveInput) -> syn::Result<Ts2> {
+    let struct_attrs = parse_uniform_attrs(&input.attrs)?;
+    let rename_all = struct_attrs.rename_all.as_deref();
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &input.ident,
+                    "`Uniforms` can only be derived for structs with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`Uniforms` can only be derived for structs",
+            ));
+        }
+    };
+
+    let mut visits = Ts2::new();
+
+    for field in fields {
+        let attrs = parse_uniform_attrs(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+
+        let ident = field.ident.as_ref().unwrap();
+        let name = match attrs.name {
+            Some(name) => name,
+            None => rename(&ident.to_string(), rename_all)?,
+        };
+
+        visits = quote! {
+            #visits
+            add(#name, ::engine_4::prelude::AsUniformValue::as_uniform_value(&self.#ident));
+        };
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::engine_4::prelude::glium::uniforms::Uniforms for #ident #ty_generics #where_clause {
+            fn visit_values<'__a, __F: FnMut(&str, ::engine_4::prelude::glium::uniforms::UniformValue<'__a>)>(
+                &'__a self,
+                mut add: __F,
+            ) {
+                #visits
+            }
+        }
+    })
+}
@@ -22,7 +22,13 @@ fn main() -> anyhow::Result<()> {
         }
 
         if action_pressed(REBIND) {
-            bind(FWD, KeyCode::KeyE);
+            println!("press a key for FWD");
+            start_rebind(FWD);
+        }
+
+        if let Some((_, button)) = rebound() {
+            println!("FWD is now {button}");
+            println!("{}", serialize_binds());
         }
 
         if should_quit() {
@@ -0,0 +1,48 @@
+use engine_4::prelude::*;
+
+const TEXTURES: [&str; 4] = [
+    "assets/textures/guy.jpg",
+    "assets/textures/pasta.jpg",
+    "assets/textures/space.jpg",
+    "assets/textures/missing.png",
+];
+
+fn main() -> anyhow::Result<()> {
+    init("Async textures")?;
+
+    // starts decoding straight away, without holding up the first frame
+    let handles: Vec<TextureHandle> = TEXTURES.iter().map(load_texture_async).collect();
+
+    loop {
+        clear_screen(Color::GRAY_900);
+
+        for (i, handle) in handles.iter().enumerate() {
+            let position = vec2(20.0 + i as f32 * 220.0, 60.0);
+            draw_texture_scaled(
+                handle.texture_or_placeholder(),
+                position,
+                Vec2::splat(200.0),
+            );
+
+            let status = if handle.is_ready() {
+                "Ready"
+            } else {
+                handle.error().unwrap_or("Loading...")
+            };
+            draw_text(status, position + vec2(0.0, 210.0));
+        }
+
+        draw_text(
+            format!("{} still loading", textures_loading()),
+            vec2(20.0, 20.0),
+        );
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
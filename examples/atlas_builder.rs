@@ -0,0 +1,54 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("Atlas builder")?;
+    use_nearest_filtering();
+
+    // a pile of procedurally made sprites, all packed into one texture
+    let colors = [
+        Pixel::RED_500,
+        Pixel::AMBER_400,
+        Pixel::LIME_500,
+        Pixel::SKY_400,
+        Pixel::VIOLET_500,
+    ];
+    let mut builder = AtlasBuilder::new().with_max_size(256);
+    let mut sprites = Vec::new();
+    for i in 0..40 {
+        let size = 8 + (i * 7) % 40;
+        let mut image = Image::empty(size, size);
+        let radius = size as i32 / 2 - 1;
+        image.fill_circle(
+            IVec2::splat(size as i32 / 2),
+            radius,
+            colors[i % colors.len()],
+        );
+        image.draw_circle_outline(IVec2::splat(size as i32 / 2), radius, Pixel::WHITE);
+        sprites.push(builder.add(image));
+    }
+    let atlas = builder.build()?;
+
+    loop {
+        clear_screen(Color::GRAY_900);
+
+        for (i, sprite) in sprites.iter().enumerate() {
+            let region = atlas.region(*sprite);
+            let position = vec2((i % 10) as f32 * 70.0 + 40.0, (i / 10) as f32 * 70.0 + 40.0);
+            region.draw_scaled(position, region.size());
+        }
+
+        // the pages themselves, to see how they were packed
+        for (i, page) in atlas.pages.iter().enumerate() {
+            let size = page.dimensions().as_vec2();
+            draw_texture_scaled(*page, vec2(40.0 + i as f32 * 270.0, 340.0), size);
+        }
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
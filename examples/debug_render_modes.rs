@@ -0,0 +1,62 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("Debug render modes")?;
+    show_debug_info();
+
+    let mut orbit_controller = OrbitCameraController::new(Vec3::ZERO);
+
+    let material = create_gouraud_material(Color::SLATE_300, Color::SLATE_700, Vec3::Y * 5.0);
+    let meshes = [
+        uv_sphere_mesh(0.8, 24, 12)?,
+        icosphere_mesh(0.8, 2)?,
+        torus_mesh(0.6, 0.25, 32, 16)?,
+        cylinder_mesh(0.6, 1.5, 20)?,
+    ];
+    let objects: Vec<_> = meshes
+        .into_iter()
+        .enumerate()
+        .map(|(i, mesh)| {
+            let mut object = Object3D::from_mesh_and_material(mesh.create(), material);
+            object.transform =
+                Transform3D::from_translation(Vec3::new(i as f32 * 2.0 - 3.0, 0.0, 0.0));
+            object
+        })
+        .collect();
+
+    // the torus always shows its wireframe, whatever the global mode is
+    objects[2].set_debug_render_mode(Some(DebugRenderMode::Wireframe));
+
+    loop {
+        clear_screen(Color::GRAY_900);
+
+        orbit_controller.update();
+
+        // the mode can also be picked in the debug window
+        if key_pressed(KeyCode::Space) {
+            let current = debug_render_mode();
+            let index = DebugRenderMode::ALL
+                .iter()
+                .position(|mode| *mode == current)
+                .unwrap();
+            set_debug_render_mode(DebugRenderMode::ALL[(index + 1) % DebugRenderMode::ALL.len()]);
+        }
+
+        for object in &objects {
+            object.draw();
+        }
+
+        draw_text(
+            format!("{} (Space to change)", debug_render_mode().name()),
+            Vec2::new(20.0, 20.0),
+        );
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
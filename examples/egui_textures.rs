@@ -0,0 +1,43 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("egui textures")?;
+
+    let preview = create_empty_render_texture(256, 256)?;
+    let sign = create_empty_render_texture(256, 128)?;
+    let mut spin = 0.0;
+
+    loop {
+        clear_screen(Color::GRAY_900);
+        spin += delta_time();
+
+        // a little scene, shown in an egui window below
+        start_rendering_to_texture(preview);
+        clear_screen(Color::SKY_900);
+        draw_poly(vec2(128.0, 128.0), 5, 80.0, spin, Color::AMBER_400);
+        end_rendering_to_texture();
+
+        // and egui drawn onto a texture, shown in the world
+        run_ui_on_texture(sign, |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.heading("Shop");
+                ui.label(format!("Open for {:.0} seconds", time()));
+            });
+        });
+        draw_texture_world(sign.color_texture, vec2(-128.0, -64.0), 256.0);
+
+        run_ui(|ctx| {
+            egui::Window::new("Preview").show(ctx, |ui| {
+                ui.image((preview.to_egui_texture_id(), egui::vec2(256.0, 256.0)));
+            });
+        });
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("Fog")?;
+
+    let mut orbit_controller = OrbitCameraController::new(Vec3::ZERO);
+
+    let sky = SkyGradient::new(Color::SKY_500, Color::SKY_100, Color::STONE_600);
+    set_sky_gradient(sky);
+
+    let fogs = [
+        Fog::linear(sky.horizon, 5.0, 40.0),
+        Fog::exponential(sky.horizon, 0.08),
+        Fog::exponential_squared(sky.horizon, 0.06),
+    ];
+    let mut current = 0;
+    set_fog(fogs[current]);
+
+    let ground = Object3D::from_mesh_and_material(
+        plane_mesh(Vec2::splat(200.0), 1)?.create(),
+        create_gouraud_material(Color::LIME_600, Color::LIME_900, Vec3::new(1.0, 3.0, 2.0)),
+    );
+
+    // rows of pillars going off into the distance
+    let pillar = Object3D::from_mesh_and_material(
+        cylinder_mesh(0.5, 4.0, 24)?.create(),
+        create_pbr_material(Color::STONE_300, 0.0, 0.8),
+    );
+    let mut pillars = Vec::new();
+    for row in 0..30 {
+        for side in [-3.0, 3.0] {
+            pillars.push(Transform3D::from_translation(Vec3::new(
+                side,
+                2.0,
+                -(row as f32) * 4.0,
+            )));
+        }
+    }
+
+    loop {
+        clear_screen(Color::BLACK);
+
+        orbit_controller.update();
+
+        if key_pressed(KeyCode::Space) {
+            current = (current + 1) % (fogs.len() + 1);
+            match fogs.get(current) {
+                Some(fog) => set_fog(*fog),
+                None => clear_fog(),
+            }
+        }
+
+        ground.draw();
+        pillar.draw_many(pillars.clone());
+
+        draw_text(
+            format!("{:?}\nSpace to change", fog().map(|fog| fog.mode)),
+            Vec2::new(20.0, 20.0),
+        );
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
@@ -4,9 +4,12 @@ fn main() -> anyhow::Result<()> {
     init("Title for the window")?;
 
     let inter = load_font(include_bytes!("../assets/fonts/inter.ttf"))?;
+    // inter doesn't have maths symbols, so borrow them from the default font
+    inter.add_fallback(default_font());
 
     loop {
         inter.draw_text("Hello world, from Inter", Vec2::splat(100.0), 100);
+        inter.draw_text("∀x ∈ ℕ, x ∘ x ∣ x²", vec2(100.0, 250.0), 60);
 
         if should_quit() {
             break;
@@ -0,0 +1,34 @@
+use engine_4::prelude::*;
+
+// runs a little simulation with no window, like a dedicated server would
+fn main() -> anyhow::Result<()> {
+    init_headless_with_config(EngineConfig {
+        target_fps: Some(60.0),
+        ..Default::default()
+    })?;
+
+    let mut world = PhysicsWorld::new();
+    let (ball, _) = world.insert_rigid_body_with_collider(
+        RigidBodyBuilder::dynamic()
+            .translation(vector![0.0, 100.0])
+            .build(),
+        ColliderBuilder::ball(5.0).build(),
+    );
+
+    every(0.5, || println!("{:.1}s", time()));
+
+    while time() < 3.0 {
+        world.step();
+
+        // draw calls are ignored, so rendering code can stay in
+        draw_circle(Vec2::ZERO, 5.0, Color::RED_500);
+
+        next_frame();
+    }
+
+    if let Some(body) = world.get_rigid_body(ball) {
+        println!("ball ended up at {:?}", body.translation());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("Image editing")?;
+    use_nearest_filtering();
+
+    let guy = Image::load(
+        include_bytes!("../assets/textures/guy.jpg"),
+        ImageFormat::Jpeg,
+    )?;
+
+    // a little "wanted" poster, made on the CPU then uploaded once
+    let mut poster = Image::gen_color(160, 200, Pixel::AMBER_100);
+    poster.draw_rect_outline(IVec2::ZERO, IVec2::new(160, 200), 4, Pixel::AMBER_900);
+    let mut face = guy.resize(96, 96, ResizeFilter::Bilinear);
+    face.flip_horizontal();
+    poster.blit(&face, IVec2::new(32, 40));
+    poster.draw_circle_outline(IVec2::new(80, 88), 52, Pixel::RED_600);
+    poster.draw_line(IVec2::new(44, 52), IVec2::new(116, 124), Pixel::RED_600);
+    poster.draw_text(
+        "WANTED",
+        IVec2::new(34, 10),
+        24.0,
+        Pixel::AMBER_900,
+        default_font(),
+    );
+    poster.fill_rect(
+        IVec2::new(20, 150),
+        IVec2::new(120, 30),
+        Pixel::AMBER_900.with_alpha(80),
+    );
+
+    let poster_texture = poster.to_texture()?;
+    let tilted = poster.rotate_90().to_texture()?;
+
+    loop {
+        clear_screen(Color::GRAY_900);
+
+        draw_texture_scaled(poster_texture, vec2(40.0, 40.0), vec2(320.0, 400.0));
+        draw_texture_scaled(tilted, vec2(400.0, 40.0), vec2(400.0, 320.0));
+
+        // reads the texture back from the GPU, like a photo mode would
+        if key_pressed(KeyCode::KeyS) {
+            poster_texture
+                .download()
+                .save("poster.png", ImageFormat::Png)?;
+            println!("Saved poster.png");
+        }
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
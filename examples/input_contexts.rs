@@ -0,0 +1,84 @@
+use engine_4::prelude::*;
+
+actions! {
+    JUMP, PAUSE, CONFIRM, SAVE
+}
+
+const MOVE_X: Axis = Axis::new(0);
+const MOVE_Y: Axis = Axis::new(1);
+
+fn main() -> anyhow::Result<()> {
+    init("Input contexts")?;
+
+    bind! {
+        JUMP => KeyCode::Space;
+        PAUSE => KeyCode::Escape;
+        CONFIRM => KeyCode::Space;
+    }
+    bind_chord(SAVE, [KeyCode::ControlLeft, KeyCode::KeyS]);
+
+    bind_axis(
+        MOVE_X,
+        AxisBinding::buttons(KeyCode::KeyA, KeyCode::KeyD)
+            .with_source(AxisSource::Gamepad(GamepadAxis::LeftStickX)),
+    );
+    bind_axis(
+        MOVE_Y,
+        AxisBinding::buttons(KeyCode::KeyW, KeyCode::KeyS)
+            .with_source(AxisSource::Gamepad(GamepadAxis::LeftStickY)),
+    );
+
+    push_input_context(
+        "gameplay",
+        InputContext::new()
+            .with_actions([JUMP, PAUSE])
+            .with_axes([MOVE_X, MOVE_Y]),
+    );
+
+    let mut position = vec2(200.0, 200.0);
+    let mut paused = false;
+
+    loop {
+        if action_pressed(PAUSE) {
+            paused = true;
+            // the menu blocks gameplay, and has space to itself
+            push_input_context(
+                "menu",
+                InputContext::new().with_actions([CONFIRM]).blocking(),
+            );
+        }
+
+        if action_pressed(CONFIRM) {
+            paused = false;
+            remove_input_context("menu");
+        }
+
+        if action_pressed(SAVE) {
+            println!("saved");
+        }
+
+        if action_pressed(JUMP) {
+            println!("jump");
+        }
+
+        position += axis_vec2(MOVE_X, MOVE_Y) * vec2(1.0, -1.0) * 300.0 * delta_time();
+        draw_circle(position, 20.0, Color::RED_400);
+
+        draw_text(
+            if paused {
+                "Paused, space to carry on"
+            } else {
+                "WASD to move, space to jump, escape to pause, control + S to save"
+            },
+            vec2(10.0, 10.0),
+        );
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
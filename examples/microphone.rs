@@ -0,0 +1,42 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("Microphone")?;
+    start_microphone()?;
+
+    let mut height = 0.0;
+
+    loop {
+        let level = microphone_level();
+        draw_rect(
+            vec2(10.0, 50.0),
+            vec2(level * 2000.0, 20.0),
+            Color::GREEN_400,
+        );
+
+        // sing higher to float up
+        let target = microphone_pitch().map_or(0.0, |pitch| (pitch - 100.0).max(0.0));
+        height += (target - height) * 5.0 * delta_time();
+        draw_circle(
+            vec2(window_width() / 2.0, window_height() - 20.0 - height),
+            20.0,
+            Color::BLUE_400,
+        );
+
+        draw_text(
+            match microphone_pitch() {
+                Some(pitch) => format!("Singing at {pitch:.0} Hz"),
+                None => "Sing or hum into the microphone".to_string(),
+            },
+            vec2(10.0, 10.0),
+        );
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
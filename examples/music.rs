@@ -0,0 +1,51 @@
+use engine_4::prelude::*;
+
+const SONG: &str = "assets/sounds/vine-boom.mp3";
+
+fn main() -> anyhow::Result<()> {
+    init("Music")?;
+
+    play_music(MusicTrack::new(SONG), 1.0);
+    on_music_beat(120.0, 0.0, |beat| {
+        if beat % 4 == 0 {
+            println!("bar {}", beat / 4);
+        }
+    });
+
+    loop {
+        if key_pressed(KeyCode::Digit1) {
+            crossfade_to(MusicTrack::new(SONG), 2.0);
+        }
+        if key_pressed(KeyCode::Digit2) {
+            // the boom once, then just its tail over and over
+            crossfade_to(MusicTrack::new(SONG).with_loop_points(0.5, 1.0), 2.0);
+        }
+        if key_pressed(KeyCode::Digit3) {
+            crossfade_to(MusicTrack::new(SONG).once().with_volume(0.5), 2.0);
+        }
+        if key_pressed(KeyCode::KeyS) {
+            stop_music(1.0);
+        }
+
+        draw_text(
+            "1 to loop it all, 2 to loop part, 3 to play once quietly, S to stop",
+            vec2(10.0, 10.0),
+        );
+        if let Some(position) = music_position() {
+            draw_text(format!("{position:.2}s in"), vec2(10.0, 40.0));
+        }
+        if let Some(beat) = music_beat(120.0, 0.0) {
+            // big on the beat, shrinking until the next one
+            let radius = 20.0 + 30.0 * (1.0 - beat.fract());
+            draw_circle(window_size() / 2.0, radius, Color::RED_400);
+        }
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use std::f32::consts::TAU;
+
+use engine_4::prelude::*;
+
+const SIZE: usize = 256;
+
+fn main() -> anyhow::Result<()> {
+    init("Normal mapping")?;
+
+    let mut orbit_controller = OrbitCameraController::new(Vec3::ZERO);
+
+    // bumps made from a height function, turned into a +Y up tangent space normal map
+    let height = |x: f32, y: f32| {
+        let (x, y) = (x / SIZE as f32 * TAU * 6.0, y / SIZE as f32 * TAU * 3.0);
+        (x.sin() * y.sin()).abs()
+    };
+    let mut normals = Image::empty(SIZE, SIZE);
+    normals.map_pixels(|x, y, _| {
+        let (x, y) = (x as f32, y as f32);
+        let dx = height(x + 1.0, y) - height(x - 1.0, y);
+        let dy = height(x, y + 1.0) - height(x, y - 1.0);
+        // image rows go down, normal maps point up
+        let normal = Vec3::new(-dx, dy, 0.5).normalize() * 0.5 + 0.5;
+        Pixel::from_rgba_f32(normal.x, normal.y, normal.z, 1.0)
+    });
+    let normals = normals.to_texture()?;
+
+    let mesh = uv_sphere_mesh(1.0, 64, 32)?.create();
+
+    let gouraud =
+        create_gouraud_material(Color::SLATE_200, Color::SLATE_700, Vec3::new(1.0, 3.0, 2.0));
+    let mut left = Object3D::from_mesh_and_material(mesh, gouraud);
+    left.material().set_normal_map(normals);
+    left.transform = Transform3D::from_translation(Vec3::X * -1.3);
+
+    let pbr = Material::pbr()
+        .with_albedo(Color::AMBER_400)
+        .with_metallic(1.0)
+        .with_roughness(0.35)
+        .with_normal_map(normals)
+        .create();
+    let mut right = Object3D::from_mesh_and_material(mesh, pbr);
+    right.transform = Transform3D::from_translation(Vec3::X * 1.3);
+
+    let mut enabled = true;
+
+    loop {
+        clear_screen(Color::SLATE_900);
+
+        orbit_controller.update();
+
+        if key_pressed(KeyCode::KeyN) {
+            enabled = !enabled;
+            left.material().set_normal_mapping(enabled);
+            right.material().set_normal_mapping(enabled);
+        }
+
+        left.draw();
+        right.draw();
+
+        draw_text(
+            format!("Normal mapping: {enabled} (N to toggle)"),
+            Vec2::new(20.0, 20.0),
+        );
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("3D particles")?;
+
+    let mut orbit_controller = OrbitCameraController::new(Vec3::Y);
+
+    let mut fire = ParticleEmitter3D::new(EmissionShape3D::Cone {
+        direction: Vec3::Y,
+        angle: 0.25,
+        radius: 0.3,
+    })
+    .with_rate(120.0)
+    .with_blend_mode(BlendMode::Additive)
+    .with_config(
+        ParticleConfig::default()
+            .with_lifetime(0.6, 1.0)
+            .with_speed(1.0, 2.0)
+            .with_size(Curve::eased(0.5, 0.1, Ease::InQuad))
+            .with_color(
+                Curve::linear(Color::AMBER_300, Color::RED_600.with_alpha(0.0)).with_key(
+                    0.3,
+                    Color::ORANGE_500,
+                    Ease::Linear,
+                ),
+            ),
+    );
+
+    let mut smoke = ParticleEmitter3D::new(EmissionShape3D::Cone {
+        direction: Vec3::Y,
+        angle: 0.4,
+        radius: 0.3,
+    })
+    .with_position(Vec3::Y * 1.2)
+    .with_rate(15.0)
+    .with_gravity(Vec3::new(0.4, 0.3, 0.0))
+    .with_config(
+        ParticleConfig::default()
+            .with_lifetime(2.0, 3.0)
+            .with_speed(0.3, 0.6)
+            .with_spin(-1.0, 1.0)
+            .with_size(Curve::linear(0.4, 1.5))
+            .with_color(Curve::linear(
+                Color::GRAY_500.with_alpha(0.5),
+                Color::GRAY_700.with_alpha(0.0),
+            )),
+    );
+
+    let mut sparks = ParticleEmitter3D::new(EmissionShape3D::Sphere { radius: 0.2 })
+        .with_position(Vec3::Y)
+        .with_rate(0.0)
+        .with_gravity(Vec3::NEG_Y * 6.0)
+        .with_blend_mode(BlendMode::Additive)
+        .with_config(
+            ParticleConfig::default()
+                .with_lifetime(0.5, 1.2)
+                .with_speed(3.0, 6.0)
+                .with_drag(0.8)
+                .with_size(Curve::constant(0.08))
+                .with_color(Curve::linear(
+                    Color::YELLOW_200,
+                    Color::AMBER_600.with_alpha(0.0),
+                )),
+        );
+
+    loop {
+        clear_screen(Color::SLATE_950);
+
+        orbit_controller.update();
+
+        if key_pressed(KeyCode::Space) {
+            sparks.burst(200);
+        }
+
+        let dt = delta_time();
+        for emitter in [&mut fire, &mut smoke, &mut sparks] {
+            emitter.update(dt);
+            emitter.draw();
+        }
+
+        draw_text("Space for sparks", Vec2::new(20.0, 20.0));
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("PBR")?;
+
+    let mut orbit_controller = OrbitCameraController::new(Vec3::ZERO);
+
+    // metallic goes up to the right, roughness goes up towards the top
+    let mesh = uv_sphere_mesh(0.4, 48, 24)?.create();
+    let mut spheres = Vec::new();
+    for row in 0..5 {
+        for column in 0..5 {
+            let material = create_pbr_material(
+                Color::RED_500,
+                column as f32 / 4.0,
+                0.05 + row as f32 / 4.0 * 0.95,
+            );
+            let mut sphere = Object3D::from_mesh_and_material(mesh, material);
+            sphere.transform = Transform3D::from_translation(Vec3::new(
+                column as f32 - 2.0,
+                row as f32 - 2.0,
+                0.0,
+            ));
+            spheres.push(sphere);
+        }
+    }
+
+    let glowing = Material::pbr()
+        .with_albedo(Color::GRAY_900)
+        .with_emissive(Color::AMBER_400)
+        .create();
+    let mut lamp = Object3D::from_mesh_and_material(mesh, glowing);
+    lamp.transform = Transform3D::from_translation(Vec3::new(0.0, 3.2, 0.0));
+
+    loop {
+        clear_screen(Color::SLATE_800);
+
+        orbit_controller.update();
+
+        for sphere in &spheres {
+            sphere.draw();
+        }
+        lamp.draw();
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
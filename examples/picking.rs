@@ -0,0 +1,81 @@
+use engine_4::prelude::*;
+
+const RING: PickId = PickId(0);
+const SUZANNE: PickId = PickId(1);
+const FIRST_CIRCLE: u32 = 2;
+
+fn main() -> anyhow::Result<()> {
+    init("Picking")?;
+    use_picking(true);
+
+    // a ring, so clicking the hole in the middle picks what's behind it
+    let mut ring = Image::empty(128, 128);
+    ring.fill_circle(IVec2::splat(64), 60, Pixel::AMBER_400);
+    ring.map_pixels(|x, y, pixel| {
+        let offset = Vec2::new(x as f32 - 64.0, y as f32 - 64.0);
+        if offset.length() < 36.0 {
+            Pixel::TRANSPARENT
+        } else {
+            pixel
+        }
+    });
+    let ring = ring.to_texture()?;
+
+    let material = create_gouraud_material(Color::SLATE_300, Color::SLATE_500, Vec3::Y * 5.0);
+    let suzanne = Object3D::from_obj_bytes_with_material(
+        include_bytes!("../assets/models/suzanne.obj"),
+        material,
+    )?;
+
+    let mut selected = None;
+
+    loop {
+        clear_screen(Color::GRAY_900);
+
+        let hovered = hovered_pick();
+        if mouse_pressed(MouseButton::Left) {
+            selected = hovered;
+        }
+        let highlight = |id: PickId, color: Color| {
+            if selected == Some(id) {
+                Color::WHITE
+            } else if hovered == Some(id) {
+                color.lighten(0.2)
+            } else {
+                color
+            }
+        };
+
+        with_pick_id(SUZANNE, || suzanne.draw());
+
+        for i in 0..5 {
+            let id = PickId(FIRST_CIRCLE + i);
+            let center = Vec2::new(160.0 + i as f32 * 60.0, 200.0);
+            with_pick_id(id, || {
+                draw_circle(center, 40.0, highlight(id, Color::SKY_500))
+            });
+        }
+
+        with_pick_id(RING, || {
+            draw_texture_ex(
+                ring,
+                Transform2D::from_scale_translation(Vec2::splat(256.0), Vec2::new(180.0, 80.0)),
+                highlight(RING, Color::WHITE),
+                None,
+            )
+        });
+
+        draw_text(
+            format!("Hovered: {hovered:?}\nSelected: {selected:?}"),
+            Vec2::new(20.0, 20.0),
+        );
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("Rich text")?;
+
+    let guy = load_texture(
+        include_bytes!("../assets/textures/guy.jpg"),
+        ImageFormat::Jpeg,
+    )?;
+    register_text_icon("guy", guy);
+
+    let inter = load_font(include_bytes!("../assets/fonts/inter.ttf"))?;
+    // no bold font is bundled, so use jetbrains to tell bold text apart
+    inter.set_bold(default_font());
+
+    loop {
+        clear_screen(Color::GRAY_900);
+
+        draw_rich_text(
+            "Talk to [icon=guy] [color=accent][b]the guy[/b][/color] about the \
+             [color=#ff6060][size=40]missing pasta[/size][/color].\n\
+             [color=text_muted][i]He seems nervous.[/i][/color] Use [[E] to talk.",
+            TextDrawParams {
+                font: Some(inter),
+                font_size: 28,
+                position: vec2(40.0, 40.0),
+                ..Default::default()
+            },
+        );
+
+        draw_text_spans(
+            &[
+                TextSpan::new("Spans work too: "),
+                TextSpan::new("+50 gold")
+                    .with_color(Color::AMBER_400)
+                    .bold(),
+            ],
+            TextDrawParams {
+                font: Some(inter),
+                font_size: 28,
+                position: vec2(40.0, 200.0),
+                ..Default::default()
+            },
+        );
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
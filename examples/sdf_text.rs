@@ -0,0 +1,58 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("SDF text")?;
+
+    // scroll to zoom in, the distance field text stays sharp and the normal text doesn't
+    let mut camera_controller = PanningCameraController::new();
+
+    loop {
+        camera_controller.update();
+        clear_screen(Color::GRAY_900);
+
+        draw_text_world_ex(
+            "Bitmap text",
+            TextDrawParams {
+                position: vec2(-200.0, -120.0),
+                font_size: 40,
+                ..Default::default()
+            },
+        );
+
+        draw_text_sdf_world(
+            "Distance field text",
+            SdfTextParams {
+                position: vec2(-200.0, -40.0),
+                font_size: 40.0,
+                ..Default::default()
+            },
+        );
+
+        draw_text_sdf_world(
+            "Outlined",
+            SdfTextParams {
+                position: vec2(-200.0, 40.0),
+                font_size: 40.0,
+                color: Color::AMBER_300,
+                outline: Some(TextOutline {
+                    width: 3.0,
+                    color: Color::GRAY_950,
+                }),
+                shadow: Some(TextShadow {
+                    offset: vec2(3.0, 4.0),
+                    color: Color::BLACK.with_alpha(0.6),
+                    softness: 2.0,
+                }),
+                ..Default::default()
+            },
+        );
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,81 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("Spatial audio")?;
+
+    // built into the executable, so there's no file to ship alongside it
+    load_sound(
+        "boom",
+        include_bytes!("../assets/sounds/vine-boom.mp3"),
+        AudioFormat::Mp3,
+    )?;
+
+    let mut booms = Vec::new();
+    let mut last_boom: Option<SoundHandle> = None;
+
+    loop {
+        if mouse_pressed(MouseButton::Left) {
+            let position = screen_to_world(cursor_pos());
+            last_boom = play_sound_at("boom", position);
+            booms.push(position);
+        }
+
+        if let Some(boom) = last_boom {
+            if key_pressed(KeyCode::KeyP) {
+                if boom.is_paused() {
+                    boom.resume();
+                } else {
+                    boom.pause();
+                }
+            }
+            if key_pressed(KeyCode::KeyR) {
+                boom.seek(0.0);
+            }
+        }
+
+        if key_pressed(KeyCode::KeyU) {
+            let underwater = bus_settings(AudioBus::Sfx).filter.is_none();
+            set_bus_filter(
+                AudioBus::Sfx,
+                underwater.then(|| BusFilter::low_pass(600.0)),
+            );
+        }
+        if key_pressed(KeyCode::ArrowUp) {
+            set_master_volume((master_volume() + 0.1).min(1.0));
+        }
+        if key_pressed(KeyCode::ArrowDown) {
+            set_master_volume(master_volume() - 0.1);
+        }
+
+        let movement = vec2(
+            key_held(KeyCode::KeyD) as i32 as f32 - key_held(KeyCode::KeyA) as i32 as f32,
+            key_held(KeyCode::KeyS) as i32 as f32 - key_held(KeyCode::KeyW) as i32 as f32,
+        );
+        mutate_camera_2d(|camera| camera.translation += movement * 500.0 * delta_time());
+
+        for &boom in &booms {
+            draw_circle_world(boom, 10.0, Color::ORANGE_400);
+        }
+        draw_circle_world(listener_position(), 5.0, Color::WHITE);
+
+        draw_text(
+            "Click to boom, WASD to move the camera away from them, U to go underwater",
+            vec2(10.0, 10.0),
+        );
+        draw_text(
+            format!(
+                "Volume {:.0}%, up and down to change",
+                master_volume() * 100.0
+            ),
+            vec2(10.0, 40.0),
+        );
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
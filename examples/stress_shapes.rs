@@ -0,0 +1,77 @@
+//! Draws more and more spinning shapes that need tessellating, recorded on every core with
+//! `draw_parallel`. Up and down change how many, P turns the threads off, D shows the debug
+//! info.
+
+use engine_4::prelude::*;
+
+fn shape(i: usize, time: f32) -> Polyline {
+    let columns = 60;
+    let center = Vec2::new(
+        (i % columns) as f32 * 20.0 + 10.0,
+        (i / columns) as f32 * 20.0 + 70.0,
+    );
+    let points = (0..12)
+        .map(|j| {
+            let angle = time + j as f32 * std::f32::consts::TAU / 12.0;
+            let radius = if j % 2 == 0 { 8.0 } else { 4.0 };
+            center + Vec2::from_angle(angle) * radius
+        })
+        .collect();
+
+    Polyline::new(points, 1.5, Color::hsl((i * 7 % 360) as f32, 0.7, 0.6)).closed()
+}
+
+fn main() -> anyhow::Result<()> {
+    init("Shape stress test")?;
+
+    let mut count = 1_000;
+    let mut parallel = true;
+
+    loop {
+        clear_screen(Color::GRAY_900);
+
+        if key_pressed(KeyCode::ArrowUp) {
+            count *= 2;
+        }
+        if key_pressed(KeyCode::ArrowDown) {
+            count = (count / 2).max(1);
+        }
+        if key_pressed(KeyCode::KeyP) {
+            parallel = !parallel;
+        }
+        if key_pressed(KeyCode::KeyD) {
+            toggle_debug_info();
+        }
+
+        let time = time();
+        let indices: Vec<usize> = (0..count).collect();
+        if parallel {
+            draw_parallel(&indices, |&i, commands| commands.draw(&shape(i, time)));
+        } else {
+            for &i in &indices {
+                shape(i, time).draw();
+            }
+        }
+
+        draw_rect(
+            Vec2::ZERO,
+            Vec2::new(260.0, 60.0),
+            Color::BLACK.with_alpha(0.7),
+        );
+        let mode = if parallel {
+            "parallel"
+        } else {
+            "single thread"
+        };
+        draw_text(format!("{count} shapes, {mode}"), Vec2::new(10.0, 5.0));
+        draw_text(format!("{:.1} fps", avg_fps()), Vec2::new(10.0, 30.0));
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! Draws more and more bouncing sprites. Up and down change how many, D shows the debug info.
+
+use engine_4::prelude::*;
+
+struct Bouncer {
+    position: Vec2,
+    velocity: Vec2,
+}
+
+impl Bouncer {
+    fn random() -> Self {
+        Self {
+            position: Vec2::new(
+                random_range(0.0..window_width()),
+                random_range(0.0..window_height()),
+            ),
+            velocity: Vec2::new(random_range(-200.0..200.0), random_range(-200.0..200.0)),
+        }
+    }
+
+    fn update(&mut self) {
+        self.position += self.velocity * delta_time();
+
+        let size = Vec2::new(window_width(), window_height());
+        for axis in 0..2 {
+            if self.position[axis] < 0.0 || self.position[axis] > size[axis] {
+                self.velocity[axis] = -self.velocity[axis];
+                self.position[axis] = self.position[axis].clamp(0.0, size[axis]);
+            }
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    init("Sprite stress test")?;
+
+    let texture = load_texture(
+        include_bytes!("../assets/textures/guy.jpg"),
+        ImageFormat::Jpeg,
+    )?;
+    let mut bouncers: Vec<Bouncer> = (0..1_000).map(|_| Bouncer::random()).collect();
+
+    loop {
+        clear_screen(Color::GRAY_900);
+
+        if key_pressed(KeyCode::ArrowUp) {
+            bouncers.extend((0..bouncers.len().max(1_000)).map(|_| Bouncer::random()));
+        }
+        if key_pressed(KeyCode::ArrowDown) {
+            bouncers.truncate(bouncers.len() / 2);
+        }
+        if key_pressed(KeyCode::KeyD) {
+            toggle_debug_info();
+        }
+
+        for bouncer in &mut bouncers {
+            bouncer.update();
+            draw_texture_scaled(texture, bouncer.position, Vec2::splat(16.0));
+        }
+
+        draw_rect(
+            Vec2::ZERO,
+            Vec2::new(220.0, 60.0),
+            Color::BLACK.with_alpha(0.7),
+        );
+        draw_text(format!("{} sprites", bouncers.len()), Vec2::new(10.0, 5.0));
+        draw_text(format!("{:.1} fps", avg_fps()), Vec2::new(10.0, 30.0));
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
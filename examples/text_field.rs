@@ -0,0 +1,32 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("Text field")?;
+
+    set_ime_allowed(true);
+
+    let mut name = TextField::new().with_max_len(24);
+    let mut names = Vec::new();
+
+    loop {
+        if name.update() && !name.is_empty() {
+            names.push(name.take());
+        }
+
+        draw_text("What's your name?", vec2(10.0, 10.0));
+        name.draw(TextDrawParams {
+            position: vec2(10.0, 40.0),
+            font_size: 24,
+            ..Default::default()
+        });
+
+        for (i, name) in names.iter().enumerate() {
+            draw_text(
+                format!("Hello, {name}!"),
+                vec2(10.0, 90.0 + i as f32 * 20.0),
+            );
+        }
+
+        next_frame();
+    }
+}
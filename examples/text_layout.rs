@@ -0,0 +1,47 @@
+use bevy_math::Rect;
+use engine_4::prelude::*;
+
+const TEXT: &str = "The quick brown fox jumps over the lazy dog, then keeps running until it \
+finds somewhere warm to sleep.\nMove the mouse to resize the boxes.";
+
+fn main() -> anyhow::Result<()> {
+    init("Text layout")?;
+
+    let aligns = [
+        TextAlign::Left,
+        TextAlign::Center,
+        TextAlign::Right,
+        TextAlign::Justify,
+    ];
+
+    loop {
+        clear_screen(Color::GRAY_900);
+
+        let size = (cursor_pos() - vec2(20.0, 20.0)).max(vec2(60.0, 40.0)) / 2.0;
+        for (i, align) in aligns.into_iter().enumerate() {
+            let top_left = vec2(20.0, 20.0) + vec2((i % 2) as f32, (i / 2) as f32) * size;
+            let rect = Rect::from_corners(top_left, top_left + size - 10.0);
+
+            draw_rect_outline(rect.min, rect.size(), 1.0, Color::GRAY_700);
+            draw_text_boxed(
+                TEXT,
+                rect,
+                TextStyle {
+                    font_size: 20,
+                    align,
+                    vertical_align: VerticalAlign::Middle,
+                    ellipsis: true,
+                    ..Default::default()
+                },
+            );
+        }
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use engine_4::prelude::*;
+
+const LINES: [&str; 3] = [
+    "Well, well. Another traveller.",
+    "The bridge is out, so you'll have to go through the caves!",
+    "Good luck... you'll need it.",
+];
+
+fn main() -> anyhow::Result<()> {
+    init("Typewriter")?;
+
+    let mut line = 0;
+    let mut typewriter = TypewriterText::new(LINES[0], 30.0);
+
+    loop {
+        clear_screen(Color::GRAY_900);
+
+        if key_pressed(KeyCode::Space) {
+            if typewriter.is_finished() {
+                line = (line + 1) % LINES.len();
+                typewriter.set_text(LINES[line]);
+                // the last line is spooky
+                typewriter.effects = if line == 2 {
+                    vec![TextEffect::shake()]
+                } else {
+                    Vec::new()
+                };
+            } else {
+                typewriter.skip();
+            }
+        }
+
+        typewriter.update();
+        typewriter.draw(TextDrawParams {
+            position: vec2(40.0, 40.0),
+            font_size: 28,
+            ..Default::default()
+        });
+
+        draw_text_with_effects(
+            "Press space",
+            TextDrawParams {
+                position: vec2(40.0, 120.0),
+                font_size: 20,
+                color: Color::GRAY_400,
+                ..Default::default()
+            },
+            &[TextEffect::wave(), TextEffect::rainbow()],
+        );
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("UI")?;
+
+    let mut ui = Ui::new();
+    let mut volume = 0.8;
+    let mut show_fps = true;
+    let mut health = 20.0;
+    let mut clicks = 0;
+
+    // keeps everything a little way in from the edges, like on a TV
+    set_safe_area(SafeArea::uniform(16.0));
+    let minimap = Anchored::new(Anchor::TopRight, UiSize::percent(20.0, 20.0));
+
+    loop {
+        clear_screen(Color::GRAY_900);
+
+        ui.panel(Anchor::TopLeft, vec2(10.0, 10.0), |ui| {
+            ui.label("Settings");
+            ui.slider("Volume", &mut volume, 0.0..=1.0);
+            ui.checkbox("Show FPS", &mut show_fps);
+            if ui.button(format!("Clicked {clicks} times")) {
+                clicks += 1;
+            }
+        });
+
+        ui.panel(Anchor::BottomRight, vec2(10.0, 10.0), |ui| {
+            ui.progress_bar_with_text(health / 20.0, format!("{health:.0} / 20 HP"));
+            if ui.button("Take damage") {
+                health = (health - 3.0f32).max(0.0);
+            }
+            if ui.button("Heal") {
+                health = 20.0;
+            }
+        });
+
+        minimap.draw(|rect| {
+            draw_rect(rect.min, rect.size(), Color::GRAY_800);
+            draw_text("Minimap", rect.min + 8.0);
+        });
+
+        ui.panel(Anchor::Top, vec2(0.0, 10.0), |ui| {
+            ui.label("Anchored to the top")
+        });
+
+        if show_fps {
+            draw_fps();
+        }
+
+        if mouse_pressed(MouseButton::Left) && !ui.wants_mouse() {
+            health = (health - 1.0f32).max(0.0);
+        }
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use engine_4::prelude::*;
+
+fn main() -> anyhow::Result<()> {
+    init("Window")?;
+
+    set_window_min_size(Some(UVec2::new(320, 240)));
+    set_window_icon(Some(&Image::from_bytes(
+        32,
+        32,
+        [239, 68, 68, 255].repeat(32 * 32),
+    )?))?;
+
+    for monitor in monitors() {
+        println!(
+            "{} {}x{} at {:?}Hz ({} video modes)",
+            monitor.name.as_deref().unwrap_or("unnamed"),
+            monitor.size.x,
+            monitor.size.y,
+            monitor.refresh_rate_hz,
+            monitor.video_modes.len(),
+        );
+    }
+
+    let cursors = [
+        CursorIcon::Default,
+        CursorIcon::Crosshair,
+        CursorIcon::Pointer,
+    ];
+    let mut cursor = 0;
+    let mut look = Vec2::ZERO;
+
+    loop {
+        if key_pressed(KeyCode::KeyF) {
+            toggle_fullscreen();
+        }
+
+        if key_pressed(KeyCode::KeyE) {
+            set_window_mode(WindowMode::ExclusiveFullscreen(None));
+        }
+
+        if key_pressed(KeyCode::KeyC) {
+            cursor = (cursor + 1) % cursors.len();
+            set_cursor_icon(cursors[cursor]);
+        }
+
+        if key_pressed(KeyCode::KeyH) {
+            hide_cursor();
+        }
+
+        if key_pressed(KeyCode::KeyS) {
+            show_cursor();
+        }
+
+        if key_pressed(KeyCode::KeyL) {
+            if cursor_grab() == CursorGrabMode::None {
+                lock_cursor();
+            } else {
+                unlock_cursor();
+            }
+        }
+
+        let (dx, dy) = mouse_diff();
+        look += vec2(dx, dy);
+
+        set_window_title(&format!("Window - {:?}", window_mode()));
+        draw_text(
+            "F: fullscreen, E: exclusive, C: cursor, H/S: hide/show cursor, L: lock cursor",
+            vec2(10.0, 10.0),
+        );
+        draw_text(format!("mouse moved {look:.0}"), vec2(10.0, 30.0));
+
+        if should_quit() {
+            break;
+        }
+
+        next_frame();
+    }
+
+    Ok(())
+}
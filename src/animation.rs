@@ -10,6 +10,10 @@ use crate::{
     shapes_2d::Rect,
 };
 
+mod flipbook;
+
+pub use flipbook::{AnimationEvent, Flipbook, FlipbookClip, FlipbookFrame, FlipbookPlayer};
+
 pub trait Animatable {
     /// progress ranges from 0 (animation start) to 1 (animation end)
     fn lerp(a: Self, b: Self, progress: f32) -> Self;
@@ -543,3 +547,78 @@ impl EasingFunction for EaseInOutBounce {
         }
     }
 }
+
+/// Every easing function as one type, for when it's picked at runtime or stored somewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Ease {
+    #[default]
+    Linear,
+    InSine,
+    OutSine,
+    InOutSine,
+    InQuad,
+    OutQuad,
+    InOutQuad,
+    InCubic,
+    OutCubic,
+    InOutCubic,
+    InQuart,
+    OutQuart,
+    InOutQuart,
+    InQuint,
+    OutQuint,
+    InOutQuint,
+    InExpo,
+    OutExpo,
+    InOutExpo,
+    InCirc,
+    OutCirc,
+    InOutCirc,
+    InBack,
+    OutBack,
+    InOutBack,
+    InElastic,
+    OutElastic,
+    InOutElastic,
+    InBounce,
+    OutBounce,
+    InOutBounce,
+}
+
+impl EasingFunction for Ease {
+    fn progress(&self, t: f32) -> f32 {
+        match self {
+            Ease::Linear => LinearEasingFunction.progress(t),
+            Ease::InSine => EaseInSine.progress(t),
+            Ease::OutSine => EaseOutSine.progress(t),
+            Ease::InOutSine => EaseInOutSine.progress(t),
+            Ease::InQuad => EaseInQuad.progress(t),
+            Ease::OutQuad => EaseOutQuad.progress(t),
+            Ease::InOutQuad => EaseInOutQuad.progress(t),
+            Ease::InCubic => EaseInCubic.progress(t),
+            Ease::OutCubic => EaseOutCubic.progress(t),
+            Ease::InOutCubic => EaseInOutCubic.progress(t),
+            Ease::InQuart => EaseInQuart.progress(t),
+            Ease::OutQuart => EaseOutQuart.progress(t),
+            Ease::InOutQuart => EaseInOutQuart.progress(t),
+            Ease::InQuint => EaseInQuint.progress(t),
+            Ease::OutQuint => EaseOutQuint.progress(t),
+            Ease::InOutQuint => EaseInOutQuint.progress(t),
+            Ease::InExpo => EaseInExpo.progress(t),
+            Ease::OutExpo => EaseOutExpo.progress(t),
+            Ease::InOutExpo => EaseInOutExpo.progress(t),
+            Ease::InCirc => EaseInCirc.progress(t),
+            Ease::OutCirc => EaseOutCirc.progress(t),
+            Ease::InOutCirc => EaseInOutCirc.progress(t),
+            Ease::InBack => EaseInBack.progress(t),
+            Ease::OutBack => EaseOutBack.progress(t),
+            Ease::InOutBack => EaseInOutBack.progress(t),
+            Ease::InElastic => EaseInElastic.progress(t),
+            Ease::OutElastic => EaseOutElastic.progress(t),
+            Ease::InOutElastic => EaseInOutElastic.progress(t),
+            Ease::InBounce => EaseInBounce.progress(t),
+            Ease::OutBounce => EaseOutBounce.progress(t),
+            Ease::InOutBounce => EaseInOutBounce.progress(t),
+        }
+    }
+}
@@ -0,0 +1,478 @@
+//! Frame-by-frame sprite animation, loaded from text files so artists can tweak timings without
+//! a rebuild. A file looks like:
+//!
+//! ```toml
+//! image = "player.png"
+//! frame_size = [32, 32]
+//! frame_time = 0.1
+//!
+//! [idle]
+//! frames = [0, 1, 2, 3]
+//!
+//! [walk]
+//! frames = [8, 9, 10, 11, 12, 13]
+//! durations = [0.1, 0.08, 0.1, 0.1, 0.08, 0.1]
+//!
+//! [walk.events]
+//! footstep = [2, 5]
+//!
+//! [attack]
+//! frames = [16, 17, 18]
+//! frame_time = 0.05
+//! looping = false
+//! ```
+//!
+//! Frames are cells of `frame_size` in the sprite sheet, counted left to right then top to
+//! bottom. `durations` is seconds per frame, and without it every frame gets the clip's
+//! `frame_time`, or the file's, or a tenth of a second. Clips loop unless told not to. Events are
+//! sent as an [`AnimationEvent`] when the frames at those positions in the clip come up, for
+//! playing footstep sounds and spawning particles.
+//!
+//! Files can be loaded at runtime with [`Flipbook::load`], or baked into the game with
+//! `include_folder!` and [`Flipbook::parse`], since text files come out of it as `String`s.
+
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, bail, ensure};
+use bevy_math::{Rect, UVec2};
+use serde::{Deserialize, Serialize};
+
+use crate::{api::delta_time, ecs::Entity, events::emit};
+
+const DEFAULT_FRAME_TIME: f32 = 0.1;
+
+/// Sent by [`FlipbookPlayer::update`] for each event on a frame as it comes up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationEvent {
+    pub name: String,
+    pub clip: String,
+    /// Position in the clip, not the cell in the sprite sheet.
+    pub frame: usize,
+    /// Whatever the player was given with [`FlipbookPlayer::with_entity`].
+    pub entity: Option<Entity>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlipbookFrame {
+    /// Which cell of the sprite sheet to show.
+    pub cell: u32,
+    /// In seconds.
+    pub duration: f32,
+    pub events: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlipbookClip {
+    pub frames: Vec<FlipbookFrame>,
+    pub looping: bool,
+}
+
+impl FlipbookClip {
+    /// Every frame at the same speed.
+    pub fn new(cells: impl IntoIterator<Item = u32>, frame_time: f32) -> Self {
+        Self {
+            frames: cells
+                .into_iter()
+                .map(|cell| FlipbookFrame {
+                    cell,
+                    duration: frame_time,
+                    events: Vec::new(),
+                })
+                .collect(),
+            looping: true,
+        }
+    }
+
+    pub fn once(mut self) -> Self {
+        self.looping = false;
+        self
+    }
+
+    /// Adds an event to the frame at `frame` in the clip.
+    pub fn with_event(mut self, frame: usize, name: impl Into<String>) -> Self {
+        self.frames[frame].events.push(name.into());
+        self
+    }
+
+    /// How long one play through takes, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+
+    /// The position of the frame showing `time` seconds in, wrapping if it loops.
+    pub fn frame_at(&self, time: f32) -> usize {
+        let duration = self.duration();
+        if self.frames.is_empty() || duration <= 0.0 {
+            return 0;
+        }
+
+        let mut time = if self.looping {
+            time.rem_euclid(duration)
+        } else {
+            time.max(0.0)
+        };
+        for (i, frame) in self.frames.iter().enumerate() {
+            if time < frame.duration {
+                return i;
+            }
+            time -= frame.duration;
+        }
+        self.frames.len() - 1
+    }
+}
+
+/// A sprite sheet's animations. See the [module docs](self) for the file format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flipbook {
+    /// The sprite sheet the file is for. Not loaded, just kept so games and tools know which
+    /// texture goes with it.
+    pub image: Option<String>,
+    /// Size of each cell in the sprite sheet, in pixels.
+    pub frame_size: UVec2,
+    pub clips: BTreeMap<String, FlipbookClip>,
+}
+
+impl Flipbook {
+    pub fn new(frame_size: UVec2) -> Self {
+        Self {
+            image: None,
+            frame_size,
+            clips: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_clip(mut self, name: impl Into<String>, clip: FlipbookClip) -> Self {
+        self.clips.insert(name.into(), clip);
+        self
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("couldn't read animation file {}", path.display()))?;
+        Self::parse(&text)
+            .with_context(|| format!("couldn't load animation file {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let file: FlipbookFile = toml::from_str(text)?;
+        let Some([width, height]) = file.frame_size else {
+            bail!("frame_size must be set, like `frame_size = [32, 32]`");
+        };
+        let mut flipbook = Self::new(UVec2::new(width, height));
+        ensure!(
+            flipbook.frame_size.cmpgt(UVec2::ZERO).all(),
+            "frame_size must be more than 0"
+        );
+        flipbook.image = file.image;
+
+        let frame_time = file.frame_time.unwrap_or(DEFAULT_FRAME_TIME);
+        ensure!(frame_time > 0.0, "frame_time must be more than 0");
+
+        for (name, clip) in file.clips {
+            let clip = read_clip(clip, frame_time).with_context(|| format!("in [{name}]"))?;
+            flipbook.clips.insert(name, clip);
+        }
+
+        Ok(flipbook)
+    }
+
+    /// The flipbook in the format [`parse`](Self::parse) reads.
+    pub fn to_toml(&self) -> String {
+        let clips = self
+            .clips
+            .iter()
+            .map(|(name, clip)| {
+                let same_durations = clip
+                    .frames
+                    .first()
+                    .filter(|first| clip.frames.iter().all(|f| f.duration == first.duration));
+
+                let mut events: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+                for (i, frame) in clip.frames.iter().enumerate() {
+                    for event in &frame.events {
+                        events.entry(event.clone()).or_default().push(i);
+                    }
+                }
+
+                let file = ClipFile {
+                    frames: clip.frames.iter().map(|frame| frame.cell).collect(),
+                    frame_time: same_durations.map(|first| first.duration),
+                    durations: match same_durations {
+                        Some(_) => None,
+                        None => Some(clip.frames.iter().map(|f| f.duration).collect()),
+                    },
+                    looping: clip.looping,
+                    events,
+                    unknown: BTreeMap::new(),
+                };
+                (name.clone(), file)
+            })
+            .collect();
+
+        let file = FlipbookFile {
+            image: self.image.clone(),
+            frame_size: Some(self.frame_size.to_array()),
+            frame_time: None,
+            clips,
+        };
+        // names and numbers, which toml can always write
+        toml::to_string(&file).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_toml())
+            .with_context(|| format!("couldn't write animation file {}", path.display()))
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&FlipbookClip> {
+        self.clips.get(name)
+    }
+
+    /// Where `cell` is in a sprite sheet `sheet_size` pixels big, as the `region` for
+    /// [`draw_texture_world_ex`](crate::prelude::draw_texture_world_ex).
+    pub fn region(&self, cell: u32, sheet_size: UVec2) -> Rect {
+        let columns = (sheet_size.x / self.frame_size.x).max(1);
+        let min = UVec2::new(cell % columns, cell / columns) * self.frame_size;
+        Rect::from_corners(min.as_vec2(), (min + self.frame_size).as_vec2())
+    }
+}
+
+/// What animation files look like, see the module docs.
+#[derive(Serialize, Deserialize)]
+struct FlipbookFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    #[serde(default)]
+    frame_size: Option<[u32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    frame_time: Option<f32>,
+    #[serde(flatten)]
+    clips: BTreeMap<String, ClipFile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ClipFile {
+    frames: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    frame_time: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    durations: Option<Vec<f32>>,
+    #[serde(default = "looping", skip_serializing_if = "is_looping")]
+    looping: bool,
+    /// Positions in the clip each event is sent at.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    events: BTreeMap<String, Vec<usize>>,
+    #[serde(flatten, skip_serializing)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+fn looping() -> bool {
+    true
+}
+
+fn is_looping(looping: &bool) -> bool {
+    *looping
+}
+
+fn read_clip(file: ClipFile, frame_time: f32) -> anyhow::Result<FlipbookClip> {
+    for key in file.unknown.keys() {
+        log::warn!("unknown animation setting {key}");
+    }
+
+    let frame_time = file.frame_time.unwrap_or(frame_time);
+    ensure!(frame_time > 0.0, "frame_time must be more than 0");
+    if let Some(durations) = &file.durations {
+        ensure!(
+            durations.len() == file.frames.len(),
+            "{} frames but {} durations",
+            file.frames.len(),
+            durations.len()
+        );
+        ensure!(
+            durations.iter().all(|d| *d > 0.0),
+            "durations must be more than 0"
+        );
+    }
+
+    let mut frames: Vec<FlipbookFrame> = file
+        .frames
+        .iter()
+        .enumerate()
+        .map(|(i, cell)| FlipbookFrame {
+            cell: *cell,
+            duration: file.durations.as_ref().map_or(frame_time, |d| d[i]),
+            events: Vec::new(),
+        })
+        .collect();
+
+    for (event, positions) in file.events {
+        for index in positions {
+            let Some(frame) = frames.get_mut(index) else {
+                bail!("in events {event}: frame {index} is past the end of the clip");
+            };
+            frame.events.push(event.clone());
+        }
+    }
+
+    Ok(FlipbookClip {
+        frames,
+        looping: file.looping,
+    })
+}
+
+/// Plays clips from a [`Flipbook`]. Keeps no reference to it, so one flipbook can be shared by
+/// every sprite that uses it.
+///
+/// ```ignore
+/// player.play(if moving { "walk" } else { "idle" });
+/// player.update(&flipbook);
+/// let region = player.region(&flipbook, texture.dimensions);
+/// draw_texture_world_ex(texture, transform, Color::WHITE, region);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlipbookPlayer {
+    /// 2 is double speed.
+    pub speed: f32,
+    pub paused: bool,
+    pub entity: Option<Entity>,
+    clip: String,
+    frame: usize,
+    /// Seconds into the current frame.
+    time: f32,
+    /// Whether the first frame's events have been sent.
+    started: bool,
+    finished: bool,
+}
+
+impl FlipbookPlayer {
+    pub fn new(clip: impl Into<String>) -> Self {
+        Self {
+            speed: 1.0,
+            paused: false,
+            entity: None,
+            clip: clip.into(),
+            frame: 0,
+            time: 0.0,
+            started: false,
+            finished: false,
+        }
+    }
+
+    pub fn with_speed(mut self, speed: f32) -> Self {
+        self.speed = speed;
+        self
+    }
+
+    /// Sent along with its events, to tell which sprite they came from.
+    pub fn with_entity(mut self, entity: Entity) -> Self {
+        self.entity = Some(entity);
+        self
+    }
+
+    pub fn clip(&self) -> &str {
+        &self.clip
+    }
+
+    /// The position in the clip of the frame showing.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Whether a clip that doesn't loop has got to the end.
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Switches to `clip`, from the start. Does nothing if it's already playing, so it can be
+    /// called every frame.
+    pub fn play(&mut self, clip: &str) {
+        if self.clip != clip {
+            self.clip = clip.to_string();
+            self.restart();
+        }
+    }
+
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.time = 0.0;
+        self.started = false;
+        self.finished = false;
+    }
+
+    /// Moves on by this frame's time, and sends any events that came up.
+    pub fn update(&mut self, flipbook: &Flipbook) {
+        for event in self.advance(flipbook, delta_time()) {
+            emit(event);
+        }
+    }
+
+    /// Like [`update`](Self::update), but by `delta_time` seconds, giving back the events
+    /// instead of sending them.
+    pub fn advance(&mut self, flipbook: &Flipbook, delta_time: f32) -> Vec<AnimationEvent> {
+        let mut events = Vec::new();
+        let Some(clip) = flipbook.clip(&self.clip) else {
+            return events;
+        };
+        if clip.frames.is_empty() {
+            return events;
+        }
+        self.frame = self.frame.min(clip.frames.len() - 1);
+
+        if !self.started {
+            self.started = true;
+            self.frame_events(clip, &mut events);
+        }
+        if self.paused || self.finished || clip.duration() <= 0.0 {
+            return events;
+        }
+
+        self.time += delta_time * self.speed.max(0.0);
+        loop {
+            let duration = clip.frames[self.frame].duration;
+            if self.time < duration {
+                break;
+            }
+
+            if self.frame + 1 < clip.frames.len() {
+                self.frame += 1;
+            } else if clip.looping {
+                self.frame = 0;
+            } else {
+                self.time = duration;
+                self.finished = true;
+                break;
+            }
+            self.time -= duration;
+            self.frame_events(clip, &mut events);
+        }
+
+        events
+    }
+
+    fn frame_events(&self, clip: &FlipbookClip, events: &mut Vec<AnimationEvent>) {
+        for name in &clip.frames[self.frame].events {
+            events.push(AnimationEvent {
+                name: name.clone(),
+                clip: self.clip.clone(),
+                frame: self.frame,
+                entity: self.entity,
+            });
+        }
+    }
+
+    /// The sprite sheet cell to show, or `None` if the clip doesn't exist.
+    pub fn cell(&self, flipbook: &Flipbook) -> Option<u32> {
+        let clip = flipbook.clip(&self.clip)?;
+        clip.frames
+            .get(self.frame.min(clip.frames.len().saturating_sub(1)))
+            .map(|frame| frame.cell)
+    }
+
+    /// [`Flipbook::region`] of the frame showing.
+    pub fn region(&self, flipbook: &Flipbook, sheet_size: UVec2) -> Option<Rect> {
+        self.cell(flipbook)
+            .map(|cell| flipbook.region(cell, sheet_size))
+    }
+}
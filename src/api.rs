@@ -4,35 +4,207 @@ use crate::utils::EngineCreate;
 use crate::{
     camera::Camera3D,
     collisions::AABB2D,
-    post_processing::PostProcessingEffect,
+    draw_queue_2d::DrawCommands2D,
+    post_processing::{ColorFilter, PostProcessingEffect, Tonemapping},
     prelude::{FontRef, Transform2D, avg_fps, draw_text},
-    render_pipeline::{RenderTexture, RenderTextureRef},
+    render_pipeline::{PassSettings, RenderPass, RenderTexture, RenderTextureRef},
     shapes_2d::*,
     textures::EngineTexture,
 };
-use bevy_math::{UVec2, Vec2};
+use bevy_math::{Rect, UVec2, Vec2};
 use egui_glium::egui_winit::egui::Context;
 use glium::{
     Texture2d,
-    texture::DepthTexture2d,
+    texture::{DepthStencilTexture2d, MipmapsOption, UncompressedFloatFormat},
     uniforms::{MagnifySamplerFilter, MinifySamplerFilter},
 };
 use rand::{
-    Rng,
+    Rng, SeedableRng,
     distr::{
         Distribution, StandardUniform,
         uniform::{SampleRange, SampleUniform},
     },
 };
+use rand_chacha::ChaCha8Rng;
 use tunes::engine::AudioEngine;
 
-use crate::{camera::Camera2D, color::Color, get_state, textures::TextureRef};
+use crate::{
+    background::BackgroundLayer, camera::Camera2D, color::Color, get_state, materials::BlendMode,
+    textures::TextureRef, try_get_state,
+};
 
 // always sets color alpha to 1.0 to stop some buggyness
 pub fn clear_screen(color: Color) {
     get_state().current_render_pipeline().clear_color = Some(color.with_alpha(1.0));
 }
 
+/// Blend mode for 2D drawing, until the end of the frame or until it's changed again.
+pub fn set_blend_mode(blend_mode: BlendMode) {
+    let state = get_state();
+    state.draw_queue_2d().set_blend_mode(blend_mode);
+    state.world_draw_queue_2d().set_blend_mode(blend_mode);
+}
+
+pub fn blend_mode() -> BlendMode {
+    get_state().draw_queue_2d().blend_mode()
+}
+
+/// Draws everything in `f` with the given blend mode, then goes back to the previous one.
+pub fn with_blend_mode<T>(blend_mode: BlendMode, f: impl FnOnce() -> T) -> T {
+    let previous = self::blend_mode();
+    set_blend_mode(blend_mode);
+    let result = f();
+    set_blend_mode(previous);
+    result
+}
+
+/// Layer for 2D drawing, until the end of the frame or until it's changed again. Higher layers
+/// are drawn on top, and draws on the same layer keep their call order. Layers can be
+/// fractional, so `set_layer(position.y)` gives Y sorting.
+pub fn set_layer(layer: f32) {
+    let state = get_state();
+    state.draw_queue_2d().set_layer(layer);
+    state.world_draw_queue_2d().set_layer(layer);
+}
+
+pub fn layer() -> f32 {
+    get_state().draw_queue_2d().layer()
+}
+
+/// Draws everything in `f` on the given layer, then goes back to the previous one.
+pub fn with_layer<T>(layer: f32, f: impl FnOnce() -> T) -> T {
+    let previous = self::layer();
+    set_layer(layer);
+    let result = f();
+    set_layer(previous);
+    result
+}
+
+/// Draws the screen space things in `f` on the overlay, on top of everything else in the
+/// frame, including the UI.
+pub fn with_overlay<T>(f: impl FnOnce() -> T) -> T {
+    let previous = std::mem::replace(&mut get_state().drawing_overlay, true);
+    let result = f();
+    get_state().drawing_overlay = previous;
+    result
+}
+
+pub fn pass_settings(pass: RenderPass) -> PassSettings {
+    get_state().pass_settings[pass as usize]
+}
+
+/// Picks whether a pass is post processed and shaken. See [`RenderPass::default_settings`].
+pub fn set_pass_settings(pass: RenderPass, settings: PassSettings) {
+    get_state().pass_settings[pass as usize] = settings;
+}
+
+/// Only draws what `f` draws inside of `shape`, using the stencil buffer. Nested masks
+/// intersect, so things inside of them have to be inside of every one.
+pub fn with_mask<T>(shape: &impl Shape2D, f: impl FnOnce() -> T) -> T {
+    get_state().draw_queue_2d().push_mask(shape);
+    let result = f();
+    get_state().draw_queue_2d().pop_mask();
+    result
+}
+
+/// Same as `with_mask`, but for things drawn in the world. `shape` is in world coordinates.
+pub fn with_mask_world<T>(shape: &impl Shape2D, f: impl FnOnce() -> T) -> T {
+    get_state().world_draw_queue_2d().push_mask(shape);
+    let result = f();
+    get_state().world_draw_queue_2d().pop_mask();
+    result
+}
+
+/// Cuts off everything drawn on screen outside of `rect` (in pixels), until `pop_clip_rect`.
+/// Nested clip rects intersect. Useful for scrolling panels and split screen HUDs.
+pub fn push_clip_rect(rect: Rect) {
+    get_state().draw_queue_2d().push_clip_rect(rect);
+}
+
+pub fn pop_clip_rect() {
+    get_state().draw_queue_2d().pop_clip_rect();
+}
+
+pub fn with_clip_rect<T>(rect: Rect, f: impl FnOnce() -> T) -> T {
+    push_clip_rect(rect);
+    let result = f();
+    pop_clip_rect();
+    result
+}
+
+/// Draws everything recorded in `commands` on screen, on top of what's been drawn so far.
+pub fn submit_draw_commands(commands: DrawCommands2D) {
+    get_state().draw_queue_2d().append(commands.into_queue());
+}
+
+/// Same as `submit_draw_commands`, but for things drawn in the world.
+pub fn submit_draw_commands_world(commands: DrawCommands2D) {
+    get_state()
+        .world_draw_queue_2d()
+        .append(commands.into_queue());
+}
+
+/// Calls `f` for every item on a few threads at once, then draws what they recorded on screen in
+/// the same order as `items`. Worth it for lots of shapes that are slow to tessellate, like big
+/// polygons and curves. `f` isn't on the main thread, so it can't use the engine.
+pub fn draw_parallel<T: Sync>(items: &[T], f: impl Fn(&T, &mut DrawCommands2D) + Sync) {
+    for commands in DrawCommands2D::record_parallel(items, f) {
+        submit_draw_commands(commands);
+    }
+}
+
+/// Same as `draw_parallel`, but for things drawn in the world.
+pub fn draw_parallel_world<T: Sync>(items: &[T], f: impl Fn(&T, &mut DrawCommands2D) + Sync) {
+    for commands in DrawCommands2D::record_parallel(items, f) {
+        submit_draw_commands_world(commands);
+    }
+}
+
+pub fn add_background_layer(layer: BackgroundLayer) {
+    get_state().current_render_pipeline().background.push(layer);
+}
+
+/// Fills the screen with a vertical gradient, going from `bottom` to `horizon` at the middle of
+/// the screen, and then to `top`. Drawn behind everything else this frame.
+pub fn draw_sky_gradient(top: Color, horizon: Color, bottom: Color) {
+    add_background_layer(BackgroundLayer::Gradient {
+        top,
+        horizon,
+        bottom,
+        horizon_height: 0.5,
+    });
+}
+
+pub fn draw_background_stars(color: Color, density: f32) {
+    add_background_layer(BackgroundLayer::Stars {
+        color,
+        density,
+        cell_size: 24.0,
+        twinkle_speed: 2.0,
+    });
+}
+
+pub fn draw_background_clouds(color: Color, coverage: f32, speed: f32) {
+    add_background_layer(BackgroundLayer::Clouds {
+        color,
+        scale: 3.0,
+        coverage,
+        speed,
+    });
+}
+
+/// `height` is from 0 (bottom of the screen) to 1 (top). Draw a few of these with different
+/// speeds for parallax.
+pub fn draw_background_hills(color: Color, height: f32, speed: f32) {
+    add_background_layer(BackgroundLayer::Hills {
+        color,
+        height,
+        amplitude: 0.15,
+        frequency: 1.5,
+        speed,
+    });
+}
+
 pub fn draw_tri_outline(a: Vec2, b: Vec2, c: Vec2, thickness: f32, color: Color) {
     draw_line(a, b, thickness, color);
     draw_line(b, c, thickness, color);
@@ -219,16 +391,38 @@ pub fn camera2d_zoom_at(screen_pos: Vec2, zoom_factor: f32) {
 
 pub fn run_ui(mut f: impl FnMut(&Context)) {
     let state = get_state();
+    // there's nothing to show the UI on in headless mode
+    let Some(context) = &mut state.window_context else {
+        return;
+    };
     state.gui_initialized = true;
-    state.gui.run(&state.window, |ctx| {
+    state.ui_ran_this_frame = true;
+    let mut console_line = None;
+    context.gui.run(&context.window, |ctx| {
+        if state.theme_changed {
+            ctx.set_visuals(state.theme.egui_visuals());
+            state.theme_changed = false;
+        }
+        if ctx.zoom_factor() != state.config.ui_scale {
+            ctx.set_zoom_factor(state.config.ui_scale);
+        }
+
         state.debug_info.draw_debug_info(ctx);
+        console_line = state.console.draw(ctx);
 
         f(ctx);
     });
+
+    if let Some(line) = console_line {
+        crate::debugging::console::execute_in_console(&line);
+    }
 }
 
 pub fn draw_texture(texture: TextureRef, position: Vec2, scale: f32) {
-    draw_texture_scaled(texture, position, texture.normalized_dimensions * scale);
+    let Some(data) = texture.get_or_report() else {
+        return;
+    };
+    draw_texture_scaled(texture, position, data.normalized_dimensions * scale);
 }
 
 pub fn draw_texture_scaled(texture: TextureRef, position: Vec2, scale: Vec2) {
@@ -241,7 +435,10 @@ pub fn draw_texture_scaled(texture: TextureRef, position: Vec2, scale: Vec2) {
 }
 
 pub fn draw_texture_world(texture: TextureRef, position: Vec2, scale: f32) {
-    draw_texture_scaled_world(texture, position, texture.normalized_dimensions * scale);
+    let Some(data) = texture.get_or_report() else {
+        return;
+    };
+    draw_texture_scaled_world(texture, position, data.normalized_dimensions * scale);
 }
 
 pub fn draw_texture_scaled_world(texture: TextureRef, position: Vec2, scale: Vec2) {
@@ -298,6 +495,12 @@ pub fn world_to_screen(world_pos: Vec2) -> Vec2 {
     get_state().camera_2d.world_to_screen(world_pos)
 }
 
+/// Makes [`rand`], [`random_range`] and the rest give the same numbers every time they're called
+/// in the same order after this, for replays and tests.
+pub fn seed_rng(seed: u64) {
+    get_state().rng = ChaCha8Rng::seed_from_u64(seed);
+}
+
 pub fn rand<T>() -> T
 where
     StandardUniform: Distribution<T>,
@@ -356,62 +559,104 @@ pub fn set_magnify_filter(filtering: MagnifySamplerFilter) {
     get_state().config.default_magnify_filter = filtering;
 }
 
+// caps the frame rate by sleeping at the end of each frame. `None` to run as fast as possible
+// (or as fast as vsync allows)
+pub fn set_target_fps(fps: Option<f32>) {
+    if let Some(fps) = fps {
+        assert!(fps > 0.0, "target fps must be positive, got {fps}");
+    }
+    get_state().config.target_fps = fps;
+}
+
+pub fn target_fps() -> Option<f32> {
+    get_state().config.target_fps
+}
+
+// whether vsync was asked for in `init_with_config`. it can't be changed once the window is made
+pub fn is_vsync_enabled() -> bool {
+    get_state().config.vsync
+}
+
 #[cfg(feature = "debugging")]
 #[inline]
 pub(crate) fn debugger_add_vertices(vertices: usize) {
-    use crate::prelude::get_debug_info_mut;
-    let debug = get_debug_info_mut();
-    debug.current_frame_mut().vertex_count += vertices;
+    if let Some(state) = try_get_state() {
+        state.debug_info.current_frame_mut().vertex_count += vertices;
+    }
 }
 
 #[cfg(not(feature = "debugging"))]
 #[inline]
-pub(crate) fn debugger_add_vertices(vertices: usize) {}
+pub(crate) fn debugger_add_vertices(_vertices: usize) {}
 
 #[cfg(feature = "debugging")]
 #[inline]
 pub(crate) fn debugger_add_indices(indices: usize) {
-    use crate::prelude::get_debug_info_mut;
-    let debug = get_debug_info_mut();
-    debug.current_frame_mut().index_count += indices;
+    if let Some(state) = try_get_state() {
+        state.debug_info.current_frame_mut().index_count += indices;
+    }
 }
 
 #[cfg(not(feature = "debugging"))]
-#[inline_always]
-pub(crate) fn debugger_add_indices(indices: usize) {}
+#[inline]
+pub(crate) fn debugger_add_indices(_indices: usize) {}
 
 #[cfg(feature = "debugging")]
 #[inline]
 pub(crate) fn debugger_add_draw_calls(count: usize) {
-    use crate::prelude::get_debug_info_mut;
-    let debug = get_debug_info_mut();
-    debug.current_frame_mut().draw_calls += count;
+    if let Some(state) = try_get_state() {
+        state.debug_info.current_frame_mut().draw_calls += count;
+    }
 }
 
 #[cfg(not(feature = "debugging"))]
-#[inline_always]
-pub(crate) fn debugger_add_draw_calls(count: usize) {}
+#[inline]
+pub(crate) fn debugger_add_draw_calls(_count: usize) {}
 
 #[cfg(feature = "debugging")]
 #[inline]
 pub(crate) fn debugger_add_drawn_objects(count: usize) {
-    use crate::prelude::get_debug_info_mut;
-    let debug = get_debug_info_mut();
-    debug.current_frame_mut().drawn_objects += count;
+    if let Some(state) = try_get_state() {
+        state.debug_info.current_frame_mut().drawn_objects += count;
+    }
 }
 
 #[cfg(not(feature = "debugging"))]
-#[inline_always]
-pub(crate) fn debugger_add_drawn_object(count: usize) {}
+#[inline]
+pub(crate) fn debugger_add_drawn_objects(_count: usize) {}
 
 pub fn time() -> f32 {
     get_state().time
 }
 
+/// Seconds since the last frame, sped up or slowed down by [`time_scale`].
 pub fn delta_time() -> f32 {
     get_state().delta_time
 }
 
+/// Seconds since the last frame in real time, for things that shouldn't slow down with the
+/// game, like menus.
+pub fn unscaled_delta_time() -> f32 {
+    get_state().unscaled_delta_time
+}
+
+/// Real time since the engine started, ignoring [`time_scale`].
+pub fn unscaled_time() -> f32 {
+    get_state().unscaled_time
+}
+
+/// How fast game time passes: `0.5` is half speed, and `0.0` freezes it, for slow motion and
+/// hit-stop. Affects [`time`], [`delta_time`], [`physics_time`], animations, tweens, scheduled
+/// tasks and physics steps. Defaults to `1.0`.
+pub fn set_time_scale(scale: f32) {
+    assert!(scale >= 0.0, "time scale can't be negative, got {scale}");
+    get_state().time_scale = scale;
+}
+
+pub fn time_scale() -> f32 {
+    get_state().time_scale
+}
+
 pub fn start_rendering_to_texture(texture: RenderTextureRef) {
     get_state().start_rendering_to_texture(texture);
 }
@@ -422,12 +667,31 @@ pub fn end_rendering_to_texture() {
 
 pub(crate) fn empty_render_texture(width: u32, height: u32) -> anyhow::Result<RenderTexture> {
     let state = get_state();
-    let facade = &state.display;
+    let facade = state.display();
     let texture = Texture2d::empty(facade, width, height)?;
     let texture = EngineTexture::new(texture).create();
     Ok(RenderTexture {
         dimensions: UVec2::new(width, height),
-        depth_texture: DepthTexture2d::empty(facade, width, height)?,
+        depth_texture: DepthStencilTexture2d::empty(facade, width, height)?,
+        color_texture: texture,
+    })
+}
+
+/// Like [`empty_render_texture`], but half float, so colors can go over 1.0.
+pub(crate) fn empty_hdr_render_texture(width: u32, height: u32) -> anyhow::Result<RenderTexture> {
+    let state = get_state();
+    let facade = state.display();
+    let texture = Texture2d::empty_with_format(
+        facade,
+        UncompressedFloatFormat::F16F16F16F16,
+        MipmapsOption::NoMipmap,
+        width,
+        height,
+    )?;
+    let texture = EngineTexture::new(texture).create();
+    Ok(RenderTexture {
+        dimensions: UVec2::new(width, height),
+        depth_texture: DepthStencilTexture2d::empty(facade, width, height)?,
         color_texture: texture,
     })
 }
@@ -436,6 +700,20 @@ pub fn create_empty_render_texture(width: u32, height: u32) -> anyhow::Result<Re
     Ok(empty_render_texture(width, height)?.create())
 }
 
+/// Draws the world in HDR, so colors brighter than white, like emissive materials, lights and
+/// bloom, add up instead of clipping, then tonemaps it down for the screen at the end of the
+/// frame. `None` goes back to drawing straight to the screen. Off by default.
+///
+/// Only the [`RenderPass`]es that are post processed are in HDR, so by default the UI isn't
+/// tonemapped. Render textures are never in HDR.
+pub fn set_hdr(tonemapping: Option<Tonemapping>) {
+    get_state().hdr = tonemapping;
+}
+
+pub fn hdr() -> Option<Tonemapping> {
+    get_state().hdr
+}
+
 pub fn add_post_processing_effect(effect: PostProcessingEffect) {
     get_state().current_render_pipeline().add_effect(effect);
 }
@@ -489,6 +767,18 @@ pub fn chromatic_abberation_screen(strength: f32) {
     add_post_processing_effect(PostProcessingEffect::ChromaticAberration { strength });
 }
 
+/// The [`arcade`](PostProcessingEffect::crt_arcade) CRT preset. Add a
+/// [`PostProcessingEffect::Crt`] for a different look.
+pub fn crt_screen() {
+    add_post_processing_effect(PostProcessingEffect::crt_arcade());
+}
+
+/// For this frame. [`EngineConfig::color_filter`](crate::config::EngineConfig::color_filter)
+/// applies one every frame.
+pub fn color_filter_screen(filter: ColorFilter) {
+    add_post_processing_effect(PostProcessingEffect::ColorFilter(filter));
+}
+
 pub fn window_size() -> Vec2 {
     get_state().window_size()
 }
@@ -505,8 +795,12 @@ pub fn draw_fullscreen_texture(texture: TextureRef) {
     draw_texture_scaled(texture, Vec2::ZERO, window_size());
 }
 
+/// Panics in headless mode, which has no audio.
 pub fn audio() -> &'static mut AudioEngine {
-    &mut get_state().audio_engine
+    get_state()
+        .audio_engine
+        .as_mut()
+        .expect("there's no audio in headless mode")
 }
 
 pub fn cursor_pos() -> Vec2 {
@@ -517,8 +811,15 @@ pub fn dpi_scaling() -> f32 {
     get_state().dpi_scaling()
 }
 
+/// [`dpi_scaling`] times [`EngineConfig::ui_scale`](crate::config::EngineConfig::ui_scale). What
+/// text and UI drawn with DPI scaling are multiplied by.
+pub fn ui_scaling() -> f32 {
+    let state = get_state();
+    state.dpi_scaling() * state.config.ui_scale
+}
+
 pub fn default_font() -> FontRef {
-    FontRef(0)
+    FontRef::from_raw(0, 0)
 }
 
 pub fn frame_count() -> usize {
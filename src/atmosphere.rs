@@ -0,0 +1,188 @@
+use std::sync::OnceLock;
+
+use bevy_math::Mat4;
+use glium::{Surface, uniform};
+
+use crate::{
+    color::Color,
+    get_state,
+    materials::Material,
+    post_processing::{POSTPROCESS_VERTEX_SHADER, render_fullscreen_quad},
+    programs::{ProgramRef, cached_program, load_program},
+};
+
+/// How fog thickens with distance from the camera.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FogMode {
+    /// None before `start`, all fog past `end`.
+    Linear { start: f32, end: f32 },
+    /// Builds up quickly near the camera, then slowly further out.
+    Exponential { density: f32 },
+    /// Clear near the camera, then builds up faster than `Exponential`.
+    ExponentialSquared { density: f32 },
+}
+
+/// Distance fog for 3D. The built in 3D materials fade towards `color` as things get further
+/// from the camera, and particles fade out. The color's alpha is how thick the fog gets at
+/// most.
+///
+/// Custom shaders get `fog_mode` (0 for none, then 1, 2 and 3 in the order of [`FogMode`]),
+/// `fog_color`, `fog_start`, `fog_end` and `fog_density` uniforms to do the same.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Fog {
+    pub color: Color,
+    pub mode: FogMode,
+}
+
+impl Fog {
+    pub fn linear(color: Color, start: f32, end: f32) -> Self {
+        Self {
+            color,
+            mode: FogMode::Linear { start, end },
+        }
+    }
+
+    pub fn exponential(color: Color, density: f32) -> Self {
+        Self {
+            color,
+            mode: FogMode::Exponential { density },
+        }
+    }
+
+    pub fn exponential_squared(color: Color, density: f32) -> Self {
+        Self {
+            color,
+            mode: FogMode::ExponentialSquared { density },
+        }
+    }
+
+    /// How much of something `distance` away is hidden by fog, from 0 to 1. Same as the
+    /// shaders, for things like not bothering with objects that can't be seen.
+    pub fn amount(&self, distance: f32) -> f32 {
+        let amount = match self.mode {
+            FogMode::Linear { start, end } => {
+                ((distance - start) / (end - start).max(f32::EPSILON)).clamp(0.0, 1.0)
+            }
+            FogMode::Exponential { density } => 1.0 - (-density * distance).exp(),
+            FogMode::ExponentialSquared { density } => 1.0 - (-(density * distance).powi(2)).exp(),
+        };
+        amount * self.color.a
+    }
+
+    /// `fog_mode`, `fog_color`, `fog_start`, `fog_end` and `fog_density`, in that order.
+    pub(crate) fn uniform_values(fog: Option<Fog>) -> (f32, [f32; 4], f32, f32, f32) {
+        let Some(fog) = fog else {
+            return (0.0, [0.0; 4], 0.0, 0.0, 0.0);
+        };
+
+        let color = fog.color.for_gpu();
+        match fog.mode {
+            FogMode::Linear { start, end } => (1.0, color, start, end, 0.0),
+            FogMode::Exponential { density } => (2.0, color, 0.0, 0.0, density),
+            FogMode::ExponentialSquared { density } => (3.0, color, 0.0, 0.0, density),
+        }
+    }
+
+    pub(crate) fn set_uniforms(fog: Option<Fog>, material: &mut Material) {
+        let (mode, color, start, end, density) = Self::uniform_values(fog);
+        material.set_float("fog_mode", mode);
+        material.set_vec4("fog_color", color.into());
+        material.set_float("fog_start", start);
+        material.set_float("fog_end", end);
+        material.set_float("fog_density", density);
+    }
+}
+
+/// Sets the fog for all 3D drawing. Stays until replaced or cleared.
+pub fn set_fog(fog: Fog) {
+    get_state().fog = Some(fog);
+}
+
+pub fn clear_fog() {
+    get_state().fog = None;
+}
+
+pub fn fog() -> Option<Fog> {
+    get_state().fog
+}
+
+/// A cheap sky without a texture: `horizon` at eye level, blending to `zenith` straight up
+/// and `ground` straight down. Drawn behind all 3D geometry when there's no
+/// [`Skybox`](crate::prelude::Skybox). Give fog the horizon color so the distance blends
+/// into it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SkyGradient {
+    pub zenith: Color,
+    pub horizon: Color,
+    pub ground: Color,
+}
+
+impl SkyGradient {
+    pub fn new(zenith: Color, horizon: Color, ground: Color) -> Self {
+        Self {
+            zenith,
+            horizon,
+            ground,
+        }
+    }
+
+    pub(crate) fn draw<T: Surface>(&self, target: &mut T, view_proj: Mat4) -> anyhow::Result<()> {
+        let program = cached_program(&SKY_GRADIENT_PROGRAM, || {
+            load_program(POSTPROCESS_VERTEX_SHADER, SKY_GRADIENT_FRAGMENT_SHADER)
+        })?;
+
+        let uniforms = uniform! {
+            inverse_view_proj: view_proj.inverse().to_cols_array_2d(),
+            zenith: self.zenith.for_gpu(),
+            horizon: self.horizon.for_gpu(),
+            ground: self.ground.for_gpu(),
+        };
+
+        render_fullscreen_quad(target, program.get(), &uniforms)
+    }
+}
+
+impl Default for SkyGradient {
+    fn default() -> Self {
+        Self::new(Color::SKY_600, Color::SKY_200, Color::STONE_500)
+    }
+}
+
+/// Sets the sky drawn behind all 3D geometry, unless there's a skybox. Stays until replaced or
+/// cleared.
+pub fn set_sky_gradient(sky: SkyGradient) {
+    get_state().sky_gradient = Some(sky);
+}
+
+pub fn clear_sky_gradient() {
+    get_state().sky_gradient = None;
+}
+
+pub fn sky_gradient() -> Option<SkyGradient> {
+    get_state().sky_gradient
+}
+
+static SKY_GRADIENT_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
+
+const SKY_GRADIENT_FRAGMENT_SHADER: &str = r#"
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+uniform mat4 inverse_view_proj;
+uniform vec4 zenith;
+uniform vec4 horizon;
+uniform vec4 ground;
+
+void main() {
+    vec2 ndc = v_tex_coords * 2.0 - 1.0;
+    vec4 near = inverse_view_proj * vec4(ndc, 0.0, 1.0);
+    vec4 far = inverse_view_proj * vec4(ndc, 1.0, 1.0);
+    vec3 direction = normalize(far.xyz / far.w - near.xyz / near.w);
+
+    // a little curve keeps the horizon color from being a thin line
+    float up = direction.y;
+    vec4 sky = mix(horizon, zenith, pow(max(up, 0.0), 0.6));
+    vec4 below = mix(horizon, ground, pow(max(-up, 0.0), 0.4));
+    color = vec4(up >= 0.0 ? sky.rgb : below.rgb, 1.0);
+}
+"#;
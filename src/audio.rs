@@ -0,0 +1,30 @@
+//! Sound, built on [`tunes`].
+
+pub(crate) mod buses;
+mod microphone;
+pub(crate) mod music;
+pub(crate) mod sounds;
+
+pub use crate::api::audio;
+pub(crate) use buses::BusState;
+pub use buses::{
+    AudioBus, BusFilter, BusReverb, BusSettings, bus_settings, bus_volume, master_volume,
+    set_bus_filter, set_bus_reverb, set_bus_settings, set_bus_volume, set_master_volume,
+};
+pub(crate) use microphone::{MicrophoneState, update_microphone};
+pub use microphone::{
+    detect_pitch, is_microphone_on, microphone_level, microphone_pitch, microphone_sample_rate,
+    microphone_samples, rms, start_microphone, stop_microphone,
+};
+pub use music::{
+    BeatHandle, MusicLoop, MusicTrack, beat_at, cancel_music_beat, crossfade_to, current_music,
+    music_beat, music_position, on_music_beat, play_music, stop_music,
+};
+pub(crate) use music::{MusicState, remove_loop_files, update_music};
+pub use sounds::{
+    AudioFormat, SoundHandle, SpatialFalloff, listener_position, load_sound, play_sound,
+    play_sound_at, set_listener, set_spatial_falloff, spatial_falloff,
+};
+pub(crate) use sounds::{SoundState, update_sounds};
+pub use tunes;
+pub use tunes::engine::AudioEngine;
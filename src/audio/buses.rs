@@ -0,0 +1,183 @@
+//! Groups of sounds with their own volume and effects, so music, sound effects and UI sounds
+//! can be turned up and down separately.
+
+use tunes::synthesis::{effects::Reverb, filter::Filter};
+
+use crate::get_state;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AudioBus {
+    /// Everything played with [`play_music`](crate::audio::play_music) and
+    /// [`crossfade_to`](crate::audio::crossfade_to).
+    Music,
+    /// Sounds in the world, including everything from
+    /// [`play_sound_at`](crate::audio::play_sound_at).
+    Sfx,
+    /// Clicks and beeps from menus, which usually shouldn't be muffled along with the game.
+    Ui,
+}
+
+impl AudioBus {
+    pub const ALL: [AudioBus; 3] = [AudioBus::Music, AudioBus::Sfx, AudioBus::Ui];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BusFilter {
+    /// Cuts frequencies above `cutoff` Hz, for sounding muffled, underwater or through a wall.
+    LowPass { cutoff: f32, resonance: f32 },
+    /// Cuts frequencies below `cutoff` Hz, for sounding tinny, like through a radio.
+    HighPass { cutoff: f32, resonance: f32 },
+}
+
+impl BusFilter {
+    pub fn low_pass(cutoff: f32) -> Self {
+        Self::LowPass {
+            cutoff,
+            resonance: 0.5,
+        }
+    }
+
+    pub fn high_pass(cutoff: f32) -> Self {
+        Self::HighPass {
+            cutoff,
+            resonance: 0.5,
+        }
+    }
+
+    pub(crate) fn to_tunes(self) -> Filter {
+        match self {
+            Self::LowPass { cutoff, resonance } => Filter::low_pass(cutoff, resonance),
+            Self::HighPass { cutoff, resonance } => Filter::high_pass(cutoff, resonance),
+        }
+    }
+}
+
+/// A simple room reverb. Everything is from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BusReverb {
+    pub room_size: f32,
+    /// How quickly high frequencies die away.
+    pub damping: f32,
+    /// How much of the output is the echo rather than the dry sound.
+    pub mix: f32,
+}
+
+impl BusReverb {
+    /// A small room, for indoors.
+    pub fn room() -> Self {
+        Self {
+            room_size: 0.4,
+            damping: 0.5,
+            mix: 0.25,
+        }
+    }
+
+    /// A big echoing space, like a cave or cathedral.
+    pub fn hall() -> Self {
+        Self {
+            room_size: 0.9,
+            damping: 0.3,
+            mix: 0.4,
+        }
+    }
+
+    pub(crate) fn to_tunes(self) -> Reverb {
+        Reverb::new(self.room_size, self.damping, self.mix)
+    }
+}
+
+/// A bus's volume and effects.
+///
+/// The volume changes sounds straight away, including ones already playing. Effects are baked
+/// into each sound when it starts, so changing them only affects sounds started afterwards,
+/// and music is streamed so only ever gets the volume.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BusSettings {
+    /// From 0 to 1.
+    pub volume: f32,
+    pub filter: Option<BusFilter>,
+    pub reverb: Option<BusReverb>,
+}
+
+impl Default for BusSettings {
+    fn default() -> Self {
+        Self {
+            volume: 1.0,
+            filter: None,
+            reverb: None,
+        }
+    }
+}
+
+pub(crate) struct BusState {
+    master_volume: f32,
+    buses: [BusSettings; AudioBus::ALL.len()],
+}
+
+impl Default for BusState {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            buses: Default::default(),
+        }
+    }
+}
+
+impl BusState {
+    /// The bus's volume with the master volume applied.
+    pub(crate) fn gain(&self, bus: AudioBus) -> f32 {
+        self.master_volume * self.buses[bus.index()].volume
+    }
+
+    pub(crate) fn set_master_volume(&mut self, volume: f32) {
+        self.master_volume = volume.max(0.0);
+    }
+
+    pub(crate) fn set_volume(&mut self, bus: AudioBus, volume: f32) {
+        self.buses[bus.index()].volume = volume.max(0.0);
+    }
+}
+
+/// Scales every bus, like the main volume slider in a settings menu.
+pub fn set_master_volume(volume: f32) {
+    get_state().audio_buses.set_master_volume(volume);
+}
+
+pub fn master_volume() -> f32 {
+    get_state().audio_buses.master_volume
+}
+
+pub fn set_bus_volume(bus: AudioBus, volume: f32) {
+    get_state().audio_buses.set_volume(bus, volume);
+}
+
+pub fn bus_volume(bus: AudioBus) -> f32 {
+    get_state().audio_buses.buses[bus.index()].volume
+}
+
+/// Filters sounds started on `bus` from now on, or stops filtering them with `None`.
+pub fn set_bus_filter(bus: AudioBus, filter: Option<BusFilter>) {
+    get_state().audio_buses.buses[bus.index()].filter = filter;
+}
+
+/// Adds reverb to sounds started on `bus` from now on, or stops adding it with `None`.
+pub fn set_bus_reverb(bus: AudioBus, reverb: Option<BusReverb>) {
+    get_state().audio_buses.buses[bus.index()].reverb = reverb;
+}
+
+pub fn bus_settings(bus: AudioBus) -> BusSettings {
+    get_state().audio_buses.buses[bus.index()]
+}
+
+pub fn set_bus_settings(bus: AudioBus, settings: BusSettings) {
+    get_state().audio_buses.buses[bus.index()] = settings;
+}
+
+/// The bus's volume with the master volume applied.
+pub(crate) fn bus_gain(bus: AudioBus) -> f32 {
+    get_state().audio_buses.gain(bus)
+}
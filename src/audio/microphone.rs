@@ -0,0 +1,223 @@
+//! Sound from the microphone, for voice activated or singing games.
+
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, anyhow};
+use cpal::{
+    FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+};
+
+use crate::get_state;
+
+/// How many of the latest samples are kept for [`microphone_pitch`], which needs a few waves
+/// of the lowest note to work with.
+const PITCH_WINDOW: usize = 4096;
+/// Anything quieter than this is treated as silence by [`detect_pitch`].
+const SILENCE: f32 = 0.01;
+
+struct Microphone {
+    /// Stops capturing when dropped.
+    _stream: Stream,
+    sample_rate: u32,
+    /// Filled by the audio thread, and emptied each frame.
+    incoming: Arc<Mutex<Vec<f32>>>,
+    /// What came in during the last frame.
+    frame: Vec<f32>,
+    /// The latest [`PITCH_WINDOW`] samples, oldest first.
+    recent: Vec<f32>,
+}
+
+#[derive(Default)]
+pub(crate) struct MicrophoneState {
+    microphone: Option<Microphone>,
+}
+
+/// Starts listening to the default microphone. Its sound is mixed down to mono and shows up in
+/// [`microphone_samples`] a frame at a time.
+///
+/// Some platforms ask the player for permission the first time, and give silence until they
+/// do.
+pub fn start_microphone() -> anyhow::Result<()> {
+    let device = cpal::default_host()
+        .default_input_device()
+        .ok_or_else(|| anyhow!("there's no microphone"))?;
+    let config = device
+        .default_input_config()
+        .context("couldn't get the microphone's settings")?;
+
+    let incoming = Arc::new(Mutex::new(Vec::new()));
+    let format = config.sample_format();
+    let config: StreamConfig = config.into();
+    let stream = match format {
+        SampleFormat::I8 => build_stream::<i8>(&device, &config, incoming.clone()),
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, incoming.clone()),
+        SampleFormat::I32 => build_stream::<i32>(&device, &config, incoming.clone()),
+        SampleFormat::U8 => build_stream::<u8>(&device, &config, incoming.clone()),
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, incoming.clone()),
+        SampleFormat::U32 => build_stream::<u32>(&device, &config, incoming.clone()),
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, incoming.clone()),
+        SampleFormat::F64 => build_stream::<f64>(&device, &config, incoming.clone()),
+        format => {
+            return Err(anyhow!(
+                "the microphone gives {format} samples, which aren't supported"
+            ));
+        }
+    }?;
+    stream.play().context("couldn't start the microphone")?;
+
+    get_state().microphone.microphone = Some(Microphone {
+        _stream: stream,
+        sample_rate: config.sample_rate.0,
+        incoming,
+        frame: Vec::new(),
+        recent: Vec::new(),
+    });
+    Ok(())
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    incoming: Arc<Mutex<Vec<f32>>>,
+) -> anyhow::Result<Stream>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels as usize;
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _| {
+                let mut incoming = incoming.lock().unwrap();
+                for frame in data.chunks(channels) {
+                    let sum: f32 = frame.iter().map(|sample| sample.to_sample::<f32>()).sum();
+                    incoming.push(sum / channels as f32);
+                }
+            },
+            |e| log::warn!("microphone error: {e}"),
+            None,
+        )
+        .context("couldn't open the microphone")
+}
+
+pub fn stop_microphone() {
+    get_state().microphone.microphone = None;
+}
+
+pub fn is_microphone_on() -> bool {
+    get_state().microphone.microphone.is_some()
+}
+
+/// Mono samples from -1 to 1 captured during the last frame, oldest first. Empty if the
+/// microphone isn't on.
+pub fn microphone_samples() -> &'static [f32] {
+    get_state()
+        .microphone
+        .microphone
+        .as_ref()
+        .map_or(&[], |microphone| &microphone.frame)
+}
+
+/// Samples per second, or `None` if the microphone isn't on.
+pub fn microphone_sample_rate() -> Option<u32> {
+    get_state()
+        .microphone
+        .microphone
+        .as_ref()
+        .map(|microphone| microphone.sample_rate)
+}
+
+/// How loud the microphone was during the last frame, from 0 to about 1. Good for telling
+/// whether anyone's talking.
+pub fn microphone_level() -> f32 {
+    rms(microphone_samples())
+}
+
+/// The note being sung or hummed into the microphone, in Hz, or `None` if it's quiet or there
+/// isn't a clear note.
+pub fn microphone_pitch() -> Option<f32> {
+    let microphone = get_state().microphone.microphone.as_ref()?;
+    detect_pitch(&microphone.recent, microphone.sample_rate)
+}
+
+/// The root mean square of `samples`, which is how loud they sound.
+pub fn rms(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    (samples.iter().map(|sample| sample * sample).sum::<f32>() / samples.len() as f32).sqrt()
+}
+
+/// The strongest note in `samples`, in Hz, between 60 and 1000 Hz, which covers most voices.
+/// Finds how far the wave has to be shifted to line up with itself, so it needs at least a
+/// couple of waves of the note.
+pub fn detect_pitch(samples: &[f32], sample_rate: u32) -> Option<f32> {
+    if rms(samples) < SILENCE {
+        return None;
+    }
+
+    let min_lag = (sample_rate / 1000).max(1) as usize;
+    let max_lag = ((sample_rate / 60) as usize).min(samples.len() / 2);
+    if min_lag >= max_lag {
+        return None;
+    }
+
+    let correlation = |lag: usize| {
+        let (a, b) = (&samples[..samples.len() - lag], &samples[lag..]);
+        let product: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+        let energy = a.iter().map(|x| x * x).sum::<f32>() * b.iter().map(|y| y * y).sum::<f32>();
+        if energy > 0.0 {
+            product / energy.sqrt()
+        } else {
+            0.0
+        }
+    };
+    let correlations: Vec<f32> = (min_lag..=max_lag).map(correlation).collect();
+
+    let best = correlations.iter().copied().fold(f32::MIN, f32::max);
+    if best < 0.5 {
+        return None;
+    }
+
+    // the shortest lag that lines up nearly as well as the best, since the wave also lines up
+    // two and three waves along, which would be an octave or more too low
+    let index = (1..correlations.len() - 1).find(|&i| {
+        let c = correlations[i];
+        c >= best * 0.9 && c >= correlations[i - 1] && c >= correlations[i + 1]
+    })?;
+
+    // fit a parabola through the peak, to get between whole samples
+    let (before, peak, after) = (
+        correlations[index - 1],
+        correlations[index],
+        correlations[index + 1],
+    );
+    let curve = before - 2.0 * peak + after;
+    let shift = if curve != 0.0 {
+        0.5 * (before - after) / curve
+    } else {
+        0.0
+    };
+    let lag = (min_lag + index) as f32 + shift;
+
+    Some(sample_rate as f32 / lag)
+}
+
+/// Moves what the microphone captured since last frame into [`microphone_samples`].
+pub(crate) fn update_microphone() {
+    let Some(microphone) = &mut get_state().microphone.microphone else {
+        return;
+    };
+
+    microphone.frame.clear();
+    std::mem::swap(
+        &mut microphone.frame,
+        &mut microphone.incoming.lock().unwrap(),
+    );
+
+    microphone.recent.extend_from_slice(&microphone.frame);
+    let excess = microphone.recent.len().saturating_sub(PITCH_WINDOW);
+    microphone.recent.drain(..excess);
+}
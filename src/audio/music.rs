@@ -0,0 +1,517 @@
+//! Long tracks streamed from disk, with fading, crossfading and loop points.
+//!
+//! Streams are decoded a bit at a time on a background thread, so a five minute song doesn't
+//! sit in memory. They can't be started part way through though, so a track with
+//! [loop points](MusicTrack::with_loop_points) gets the looping part cut out into its own
+//! temporary file the first time it plays, and switches over to that once the first play
+//! through reaches the loop end. The file is deleted once nothing's playing the track.
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread::JoinHandle,
+};
+
+use anyhow::Context;
+use cpal::traits::{DeviceTrait, HostTrait};
+use tunes::{
+    engine::{AudioEngine, SoundId},
+    synthesis::sample::Sample,
+};
+
+use super::buses::{AudioBus, bus_gain};
+use crate::get_state;
+
+/// Something to play with [`play_music`] or [`crossfade_to`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct MusicTrack {
+    pub path: PathBuf,
+    /// From 0 to 1.
+    pub volume: f32,
+    pub looping: MusicLoop,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MusicLoop {
+    /// Plays through once and stops.
+    Once,
+    /// Starts again from the beginning once it ends.
+    Whole,
+    /// Plays from the beginning to `end` seconds, then loops from `start` to `end` forever.
+    /// Anything after `end` is never heard, which is handy for tracks with an intro or a tail.
+    Points { start: f32, end: f32 },
+}
+
+impl MusicTrack {
+    /// A track that loops the whole file, at full volume.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            volume: 1.0,
+            looping: MusicLoop::Whole,
+        }
+    }
+
+    pub fn once(mut self) -> Self {
+        self.looping = MusicLoop::Once;
+        self
+    }
+
+    /// Loops from `start` to `end`, in seconds, after playing the part before `start` once.
+    pub fn with_loop_points(mut self, start: f32, end: f32) -> Self {
+        self.looping = MusicLoop::Points { start, end };
+        self
+    }
+
+    pub fn with_volume(mut self, volume: f32) -> Self {
+        self.volume = volume;
+        self
+    }
+}
+
+/// A volume going from `from` to `to`, as a fraction of the track's volume.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Fade {
+    from: f32,
+    to: f32,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl Fade {
+    pub(crate) fn new(from: f32, to: f32, duration: f32) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
+        }
+    }
+
+    pub(crate) fn advance(&mut self, dt: f32) {
+        self.elapsed = (self.elapsed + dt).min(self.duration);
+    }
+
+    pub(crate) fn value(&self) -> f32 {
+        if self.duration <= 0.0 {
+            return self.to;
+        }
+        let t = self.elapsed / self.duration;
+        self.from + (self.to - self.from) * t
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// One track that's playing, or fading out.
+struct Voice {
+    track: MusicTrack,
+    /// `None` in headless mode, or if the stream couldn't be started.
+    stream: Option<SoundId>,
+    /// Seconds into the file, as near as the game can tell.
+    position: f32,
+    fade: Fade,
+    /// Has switched over to the cut out loop.
+    in_loop: bool,
+    /// The volume last given to the stream, so it's only sent when it changes.
+    sent_volume: Option<f32>,
+}
+
+/// The cut out loop for a track with loop points, made on a background thread.
+enum LoopBody {
+    Preparing(JoinHandle<anyhow::Result<LoopFile>>),
+    Ready(LoopFile),
+    Failed,
+}
+
+/// A cut out loop in the temp directory, deleted when it's dropped. It's made before the file is
+/// written, so half written ones go too, and if the `LoopBody` is dropped while it's still
+/// being made, it goes once the thread finishes.
+pub(crate) struct LoopFile(pub(crate) PathBuf);
+
+impl Drop for LoopFile {
+    fn drop(&mut self) {
+        std::fs::remove_file(&self.0).ok();
+    }
+}
+
+/// Counts how much audio the sound card has played, which keeps better time than the frame
+/// timer, since that's what the player actually hears.
+pub(crate) struct AudioClock {
+    /// Written by the audio thread, once per buffer.
+    samples: Arc<AtomicU64>,
+    /// Samples per second, counting each channel.
+    rate: f64,
+    last: u64,
+}
+
+impl AudioClock {
+    /// Counts the samples going past `audio`'s monitor callback, so replacing that callback
+    /// stops the clock.
+    fn start(audio: &AudioEngine) -> Option<Self> {
+        // tunes doesn't say what it opened, but it's always the default output
+        let config = cpal::default_host()
+            .default_output_device()?
+            .default_output_config()
+            .ok()?;
+        let rate = config.sample_rate().0 as f64 * config.channels() as f64;
+
+        let samples = Arc::new(AtomicU64::new(0));
+        let counter = samples.clone();
+        audio.set_monitor_callback(Some(Box::new(move |buffer: &[f32]| {
+            counter.fetch_add(buffer.len() as u64, Ordering::Relaxed);
+        })));
+
+        Some(Self {
+            samples,
+            rate,
+            last: 0,
+        })
+    }
+
+    /// Seconds of audio played since this was last called.
+    fn delta(&mut self) -> f32 {
+        let now = self.samples.load(Ordering::Relaxed);
+        let delta = now - self.last;
+        self.last = now;
+        (delta as f64 / self.rate) as f32
+    }
+}
+
+/// Identifies a callback from [`on_music_beat`], to [cancel](cancel_music_beat) it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BeatHandle(u64);
+
+struct BeatListener {
+    handle: BeatHandle,
+    bpm: f32,
+    offset: f32,
+    callback: Box<dyn FnMut(u64)>,
+    /// The last beat the callback was given.
+    last: Option<i64>,
+}
+
+#[derive(Default)]
+pub(crate) struct MusicState {
+    current: Option<Voice>,
+    fading_out: Vec<Voice>,
+    loop_bodies: HashMap<(PathBuf, u32, u32), LoopBody>,
+    /// `None` in headless mode, where the music goes by the frame timer instead.
+    clock: Option<AudioClock>,
+    beat_listeners: Vec<BeatListener>,
+    next_beat_handle: u64,
+}
+
+impl MusicState {
+    pub(crate) fn new(audio: Option<&AudioEngine>) -> Self {
+        Self {
+            clock: audio.and_then(AudioClock::start),
+            ..Default::default()
+        }
+    }
+}
+
+/// Which beat the music is on, as `position * bpm / 60` counted from `offset` seconds in,
+/// which is where the first beat is. Fractional, so `beat.fract()` is how far through the
+/// current beat it is, for pulsing things in time.
+pub fn beat_at(position: f32, bpm: f32, offset: f32) -> f32 {
+    (position - offset) * bpm / 60.0
+}
+
+/// Stops whatever music is playing and starts `track`, fading it in over `fade_in` seconds.
+pub fn play_music(track: MusicTrack, fade_in: f32) {
+    let music = &mut get_state().music;
+    for voice in music.fading_out.drain(..).chain(music.current.take()) {
+        stop_stream(&voice);
+    }
+    music.current = Some(start_voice(track, Fade::new(0.0, 1.0, fade_in)));
+}
+
+/// Fades out what's playing while fading in `track`, both over `duration` seconds. Plays it
+/// straight away if nothing was playing.
+pub fn crossfade_to(track: MusicTrack, duration: f32) {
+    let music = &mut get_state().music;
+    if let Some(mut voice) = music.current.take() {
+        voice.fade = Fade::new(voice.fade.value(), 0.0, duration);
+        music.fading_out.push(voice);
+    }
+    music.current = Some(start_voice(track, Fade::new(0.0, 1.0, duration)));
+}
+
+/// Fades the music out over `fade_out` seconds, then stops it.
+pub fn stop_music(fade_out: f32) {
+    let music = &mut get_state().music;
+    if let Some(mut voice) = music.current.take() {
+        voice.fade = Fade::new(voice.fade.value(), 0.0, fade_out);
+        music.fading_out.push(voice);
+    }
+}
+
+/// The track started by the last [`play_music`] or [`crossfade_to`], unless it was stopped.
+pub fn current_music() -> Option<&'static MusicTrack> {
+    get_state().music.current.as_ref().map(|voice| &voice.track)
+}
+
+/// Roughly how many seconds into its file the current track is. After looping back this
+/// counts from the loop start again.
+pub fn music_position() -> Option<f32> {
+    get_state()
+        .music
+        .current
+        .as_ref()
+        .map(|voice| voice.position)
+}
+
+/// How many beats into the current track it is at `bpm`, with the first beat `offset` seconds
+/// in. Goes by the audio actually played rather than the frame timer, so it stays in sync with
+/// what's heard.
+pub fn music_beat(bpm: f32, offset: f32) -> Option<f32> {
+    music_position().map(|position| beat_at(position, bpm, offset))
+}
+
+/// Calls `callback` with the beat number on every beat of the current music, at `bpm` with the
+/// first beat `offset` seconds in, for rhythm games and things that pulse in time. It's called
+/// during [`next_frame`](crate::next_frame), so it can be up to a frame late; use
+/// [`music_beat`] to place things exactly.
+///
+/// Carries on through track changes, counting from the start of whatever's playing.
+pub fn on_music_beat(bpm: f32, offset: f32, callback: impl FnMut(u64) + 'static) -> BeatHandle {
+    let music = &mut get_state().music;
+    let handle = BeatHandle(music.next_beat_handle);
+    music.next_beat_handle += 1;
+    music.beat_listeners.push(BeatListener {
+        handle,
+        bpm,
+        offset,
+        callback: Box::new(callback),
+        last: None,
+    });
+    handle
+}
+
+/// Returns whether there was a callback to cancel.
+pub fn cancel_music_beat(handle: BeatHandle) -> bool {
+    let listeners = &mut get_state().music.beat_listeners;
+    let len = listeners.len();
+    listeners.retain(|listener| listener.handle != handle);
+    listeners.len() != len
+}
+
+/// The beats to call back with when the music goes from beat `last` to `beat`. Skipped beats
+/// are caught up on, and going backwards, like when the music loops, counts again from there.
+/// With no `last`, only the very first beat is called back with, so one that went past before
+/// anyone was listening isn't.
+pub(crate) fn beats_crossed(last: Option<i64>, beat: f32) -> std::ops::RangeInclusive<i64> {
+    let beat = beat.floor() as i64;
+    match last {
+        Some(last) if last <= beat => last + 1..=beat,
+        Some(_) => beat..=beat,
+        None if beat == 0 => 0..=0,
+        None => beat + 1..=beat,
+    }
+}
+
+fn start_voice(track: MusicTrack, fade: Fade) -> Voice {
+    if let MusicLoop::Points { start, end } = track.looping {
+        prepare_loop_body(&track.path, start, end);
+    }
+    let stream = start_stream(&track.path, track.looping == MusicLoop::Whole);
+    Voice {
+        track,
+        stream,
+        position: 0.0,
+        fade,
+        in_loop: false,
+        sent_volume: None,
+    }
+}
+
+fn start_stream(path: &Path, looping: bool) -> Option<SoundId> {
+    let audio = get_state().audio_engine.as_ref()?;
+    let started = if looping {
+        audio.stream_file_looping(path)
+    } else {
+        audio.stream_file(path)
+    };
+    match started {
+        Ok(id) => {
+            // starts silent, so the first frame of a fade in isn't at full volume
+            audio.set_stream_volume(id, 0.0).ok();
+            Some(id)
+        }
+        Err(e) => {
+            log::warn!("couldn't stream {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+fn stop_stream(voice: &Voice) {
+    if let (Some(audio), Some(id)) = (&get_state().audio_engine, voice.stream) {
+        audio.stop_stream(id).ok();
+    }
+}
+
+fn loop_body_key(path: &Path, start: f32, end: f32) -> (PathBuf, u32, u32) {
+    (path.to_path_buf(), start.to_bits(), end.to_bits())
+}
+
+fn prepare_loop_body(path: &Path, start: f32, end: f32) {
+    let key = loop_body_key(path, start, end);
+    let music = &mut get_state().music;
+    if music.loop_bodies.contains_key(&key) {
+        return;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    // with the process id, so two copies of the game don't delete each other's
+    let destination = std::env::temp_dir().join(format!(
+        "engine_4_music_loop_{}_{:016x}.wav",
+        std::process::id(),
+        hasher.finish()
+    ));
+
+    let source = path.to_path_buf();
+    let handle = std::thread::spawn(move || {
+        let file = LoopFile(destination);
+        Sample::from_file(&source)
+            .and_then(|sample| sample.slice(start, end))
+            .and_then(|body| body.export_wav(&file.0))
+            .with_context(|| format!("couldn't cut the loop out of {}", source.display()))?;
+        Ok(file)
+    });
+    music.loop_bodies.insert(key, LoopBody::Preparing(handle));
+}
+
+/// The cut out loop, if it's finished being made.
+fn loop_body(path: &Path, start: f32, end: f32) -> Option<&'static Path> {
+    let body = get_state()
+        .music
+        .loop_bodies
+        .get_mut(&loop_body_key(path, start, end))?;
+
+    if let LoopBody::Preparing(handle) = body {
+        if !handle.is_finished() {
+            return None;
+        }
+        let LoopBody::Preparing(handle) = std::mem::replace(body, LoopBody::Failed) else {
+            unreachable!()
+        };
+        match handle.join() {
+            Ok(Ok(destination)) => *body = LoopBody::Ready(destination),
+            Ok(Err(e)) => log::warn!("{e:#}"),
+            Err(_) => log::warn!("cutting out the loop of {} panicked", path.display()),
+        }
+    }
+
+    match body {
+        LoopBody::Ready(file) => Some(&file.0),
+        _ => None,
+    }
+}
+
+fn voice_loop_key(voice: &Voice) -> Option<(PathBuf, u32, u32)> {
+    match voice.track.looping {
+        MusicLoop::Points { start, end } => Some(loop_body_key(&voice.track.path, start, end)),
+        _ => None,
+    }
+}
+
+/// Deletes every cut out loop. The engine's state is never dropped, so this is called when the
+/// window closes instead. A loop that's playing keeps going where the OS allows it.
+pub(crate) fn remove_loop_files() {
+    get_state().music.loop_bodies.clear();
+}
+
+fn update_voice(voice: &mut Voice, dt: f32) {
+    voice.position += dt;
+    voice.fade.advance(dt);
+
+    if let MusicLoop::Points { start, end } = voice.track.looping
+        && !voice.in_loop
+        && voice.position >= end
+    {
+        stop_stream(voice);
+        match loop_body(&voice.track.path, start, end) {
+            Some(body) => {
+                voice.stream = start_stream(body, true);
+                voice.in_loop = true;
+            }
+            // not cut out yet, or it couldn't be, so go round the whole thing again instead
+            None => voice.stream = start_stream(&voice.track.path, false),
+        }
+        voice.position = if voice.in_loop { start } else { 0.0 };
+        voice.sent_volume = None;
+    }
+
+    // the cut out loop repeats by itself
+    if voice.in_loop
+        && let MusicLoop::Points { start, end } = voice.track.looping
+        && voice.position >= end
+    {
+        voice.position = start + (voice.position - end);
+    }
+
+    let volume = voice.track.volume * voice.fade.value() * bus_gain(AudioBus::Music);
+    if voice.sent_volume != Some(volume)
+        && let (Some(audio), Some(id)) = (&get_state().audio_engine, voice.stream)
+    {
+        audio.set_stream_volume(id, volume).ok();
+        voice.sent_volume = Some(volume);
+    }
+}
+
+/// Moves fades along, switches tracks with loop points over to their loop, and calls beat
+/// callbacks.
+pub(crate) fn update_music(dt: f32) {
+    let music = &mut get_state().music;
+    let dt = music.clock.as_mut().map_or(dt, AudioClock::delta);
+
+    if let Some(voice) = &mut music.current {
+        update_voice(voice, dt);
+    }
+
+    let position = music.current.as_ref().map(|voice| voice.position);
+    for listener in &mut music.beat_listeners {
+        let Some(position) = position else {
+            listener.last = None;
+            continue;
+        };
+        let beat = beat_at(position, listener.bpm, listener.offset);
+        if beat < 0.0 {
+            listener.last = None;
+            continue;
+        }
+        for n in beats_crossed(listener.last, beat) {
+            (listener.callback)(n as u64);
+        }
+        listener.last = Some(beat.floor() as i64);
+    }
+
+    music.fading_out.retain_mut(|voice| {
+        update_voice(voice, dt);
+        if voice.fade.is_done() {
+            stop_stream(voice);
+            false
+        } else {
+            true
+        }
+    });
+
+    // playing the track again cuts the loop out again, rather than leaving files around
+    let in_use: Vec<_> = music
+        .current
+        .iter()
+        .chain(&music.fading_out)
+        .filter_map(voice_loop_key)
+        .collect();
+    music.loop_bodies.retain(|key, _| in_use.contains(key));
+}
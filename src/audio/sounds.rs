@@ -0,0 +1,497 @@
+//! Sounds played on a [bus](AudioBus), optionally with a position in the 2D world, which makes
+//! them get quieter further from the camera and pan to whichever side of the screen they're on.
+
+use std::{collections::HashMap, time::Instant};
+
+use anyhow::Context;
+use bevy_math::Vec2;
+use tunes::{
+    composition::{Composition, Tempo},
+    engine::SoundId,
+    synthesis::sample::Sample,
+};
+
+use super::buses::{AudioBus, bus_gain, bus_settings};
+use crate::{
+    events::{EventBus, SoundFinished},
+    get_state,
+};
+
+/// How a positioned sound's volume and pan depend on how far it is from the listener, in world
+/// units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpatialFalloff {
+    /// Anything closer than this is at full volume.
+    pub min_distance: f32,
+    /// Anything further than this is silent. Between the two the volume falls off linearly.
+    pub max_distance: f32,
+    /// How far to the side something has to be to come only out of that speaker.
+    pub pan_distance: f32,
+}
+
+impl Default for SpatialFalloff {
+    fn default() -> Self {
+        Self {
+            min_distance: 100.0,
+            max_distance: 1500.0,
+            pan_distance: 600.0,
+        }
+    }
+}
+
+impl SpatialFalloff {
+    /// The volume, from 0 to 1, and pan, from -1 (left) to 1 (right), of a sound `offset` away
+    /// from the listener. `offset` is in the camera's orientation, so positive x is the right
+    /// of the screen.
+    pub fn mix(&self, offset: Vec2) -> (f32, f32) {
+        let distance = offset.length();
+        let volume = if distance <= self.min_distance {
+            1.0
+        } else if distance >= self.max_distance {
+            0.0
+        } else {
+            1.0 - (distance - self.min_distance) / (self.max_distance - self.min_distance)
+        };
+
+        let pan = if self.pan_distance > 0.0 {
+            (offset.x / self.pan_distance).clamp(-1.0, 1.0)
+        } else {
+            offset.x.signum()
+        };
+
+        (volume, pan)
+    }
+}
+
+/// An encoding for [`load_sound`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AudioFormat {
+    Wav,
+    /// Ogg Vorbis.
+    Ogg,
+    Mp3,
+    Flac,
+}
+
+impl AudioFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "wav" | "wave" => Some(Self::Wav),
+            "ogg" | "oga" => Some(Self::Ogg),
+            "mp3" => Some(Self::Mp3),
+            "flac" => Some(Self::Flac),
+            _ => None,
+        }
+    }
+
+    /// Whether `bytes` start like a file in this format.
+    pub fn matches(self, bytes: &[u8]) -> bool {
+        match self {
+            Self::Wav => bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WAVE",
+            Self::Ogg => bytes.starts_with(b"OggS"),
+            Self::Flac => bytes.starts_with(b"fLaC"),
+            // either a tag, or straight into the first frame's sync bits
+            Self::Mp3 => {
+                bytes.starts_with(b"ID3")
+                    || (bytes.len() >= 2 && bytes[0] == 0xFF && bytes[1] & 0xE0 == 0xE0)
+            }
+        }
+    }
+}
+
+/// Decodes a sound from memory, like one from `include_bytes!`, so it can be played by passing
+/// `name` to [`play_sound`] or [`play_sound_at`] instead of a path.
+pub fn load_sound(name: &str, bytes: &[u8], format: AudioFormat) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        format.matches(bytes),
+        "sound {name} isn't {format:?}, or is corrupted"
+    );
+    let sample =
+        Sample::from_bytes(bytes).with_context(|| format!("couldn't decode sound {name}"))?;
+    get_state().sounds.samples.insert(name.to_string(), sample);
+    Ok(())
+}
+
+/// Controls a sound started with [`play_sound`] or [`play_sound_at`]. Does nothing once the
+/// sound has finished.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SoundHandle(pub(crate) u64);
+
+pub(crate) struct PlayingSound {
+    handle: SoundHandle,
+    /// Changes when it's seeked, since that starts it again part way through.
+    id: SoundId,
+    path: String,
+    bus: AudioBus,
+    /// `None` for sounds that aren't anywhere in particular.
+    position: Option<Vec2>,
+    /// Multiplies the bus and distance volume.
+    volume: f32,
+    speed: f32,
+    paused: bool,
+    /// Seconds into the sample, as near as the game can tell.
+    time: f32,
+    started: Instant,
+    /// Whether the audio thread has picked it up yet, since it isn't playing straight away.
+    seen_playing: bool,
+    /// Sends a [`SoundFinished`] when it's done.
+    pub(crate) watched: bool,
+}
+
+impl PlayingSound {
+    pub(crate) fn new(
+        handle: SoundHandle,
+        id: SoundId,
+        path: &str,
+        bus: AudioBus,
+        position: Option<Vec2>,
+    ) -> Self {
+        Self {
+            handle,
+            id,
+            path: path.to_string(),
+            bus,
+            position,
+            volume: 1.0,
+            speed: 1.0,
+            paused: false,
+            time: 0.0,
+            started: Instant::now(),
+            seen_playing: false,
+            watched: false,
+        }
+    }
+
+    /// Switches to a new `tunes` sound, after it's been started again somewhere else.
+    pub(crate) fn restart(&mut self, id: SoundId, time: f32) {
+        self.id = id;
+        self.time = time;
+        self.started = Instant::now();
+        self.seen_playing = false;
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct SoundState {
+    /// Loaded samples by path, or by name for ones [loaded from memory](load_sound), so playing
+    /// one again doesn't read the file.
+    samples: HashMap<String, Sample>,
+    sounds: Vec<PlayingSound>,
+    next_handle: u64,
+    /// Hears instead of the camera, if set.
+    listener: Option<Vec2>,
+    falloff: SpatialFalloff,
+}
+
+/// Plays the sound at `path`, or the one [loaded](load_sound) with that name, on `bus`, with
+/// the bus's volume and effects.
+///
+/// Returns `None` in headless mode, or if the sound couldn't be loaded.
+pub fn play_sound(path: &str, bus: AudioBus) -> Option<SoundHandle> {
+    start_sound(path, bus, None)
+}
+
+/// Plays the sound at `path`, or the one loaded with that name, from `position` in the world, on the [`Sfx`](AudioBus::Sfx) bus.
+/// It's panned and attenuated relative to the 2D camera, or the [listener](set_listener) if
+/// there is one, and kept up to date every frame as either moves.
+///
+/// Returns `None` in headless mode, or if the sound couldn't be loaded.
+pub fn play_sound_at(path: &str, position: Vec2) -> Option<SoundHandle> {
+    start_sound(path, AudioBus::Sfx, Some(position))
+}
+
+fn start_sound(path: &str, bus: AudioBus, position: Option<Vec2>) -> Option<SoundHandle> {
+    let id = start_sample(path, bus, 0.0)?;
+
+    let sounds = &mut get_state().sounds;
+    let handle = SoundHandle(sounds.next_handle);
+    sounds.next_handle += 1;
+
+    let sound = PlayingSound::new(handle, id, path, bus, position);
+    // set straight away, so it doesn't start out at full volume in the middle
+    apply_mix(&sound);
+    sounds.sounds.push(sound);
+
+    Some(handle)
+}
+
+/// Starts the sample at `path` from `offset` seconds in, with `bus`'s effects.
+fn start_sample(path: &str, bus: AudioBus, offset: f32) -> Option<SoundId> {
+    let state = get_state();
+    let audio = state.audio_engine.as_ref()?;
+    let samples = &mut state.sounds.samples;
+
+    let sample = match samples.get(path) {
+        Some(sample) => sample,
+        None => match Sample::from_file(path) {
+            Ok(sample) => samples.entry(path.to_string()).or_insert(sample),
+            Err(e) => {
+                log::warn!("couldn't load sound {path}: {e}");
+                return None;
+            }
+        },
+    };
+    let sliced;
+    let sample = if offset > 0.0 {
+        sliced = sample.slice(offset, sample.duration).ok()?;
+        &sliced
+    } else {
+        sample
+    };
+
+    let settings = bus_settings(bus);
+    let mut composition = Composition::new(Tempo::new(120.0));
+    let mut track = composition.track("sound");
+    if let Some(filter) = settings.filter {
+        track = track.filter(filter.to_tunes());
+    }
+    if let Some(reverb) = settings.reverb {
+        track = track.reverb(reverb.to_tunes());
+    }
+    track.play_sample(sample, 1.0);
+
+    match audio.play_mixer_realtime(&composition.into_mixer()) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            log::warn!("couldn't play sound {path}: {e}");
+            None
+        }
+    }
+}
+
+impl SoundHandle {
+    fn sound(self) -> Option<&'static mut PlayingSound> {
+        get_state()
+            .sounds
+            .sounds
+            .iter_mut()
+            .find(|sound| sound.handle == self)
+    }
+
+    /// The id `tunes` knows it by, for [`AudioEngine`](crate::audio::AudioEngine) methods.
+    /// Changes after a [`seek`](Self::seek).
+    pub fn id(self) -> Option<SoundId> {
+        self.sound().map(|sound| sound.id)
+    }
+
+    pub fn pause(self) {
+        if let Some(sound) = self.sound()
+            && let Some(audio) = &get_state().audio_engine
+        {
+            audio.pause(sound.id).ok();
+            sound.paused = true;
+        }
+    }
+
+    pub fn resume(self) {
+        if let Some(sound) = self.sound()
+            && let Some(audio) = &get_state().audio_engine
+        {
+            audio.resume(sound.id).ok();
+            sound.paused = false;
+        }
+    }
+
+    pub fn is_paused(self) -> bool {
+        self.sound().is_some_and(|sound| sound.paused)
+    }
+
+    pub fn stop(self) {
+        let sounds = &mut get_state().sounds.sounds;
+        if let Some(index) = sounds.iter().position(|sound| sound.handle == self) {
+            let sound = sounds.remove(index);
+            if let Some(audio) = &get_state().audio_engine {
+                audio.stop(sound.id).ok();
+            }
+        }
+    }
+
+    /// From 0 to 1, on top of the bus's volume and how far away it is.
+    pub fn set_volume(self, volume: f32) {
+        if let Some(sound) = self.sound() {
+            sound.volume = volume.max(0.0);
+            apply_mix(sound);
+        }
+    }
+
+    pub fn volume(self) -> f32 {
+        self.sound().map_or(0.0, |sound| sound.volume)
+    }
+
+    /// Changes the speed, and the pitch along with it. 2 is twice as fast and an octave up.
+    pub fn set_speed(self, speed: f32) {
+        if let Some(sound) = self.sound()
+            && let Some(audio) = &get_state().audio_engine
+        {
+            // tunes can't go further than this either way
+            sound.speed = speed.clamp(0.1, 4.0);
+            audio.set_playback_rate(sound.id, sound.speed).ok();
+        }
+    }
+
+    pub fn speed(self) -> f32 {
+        self.sound().map_or(1.0, |sound| sound.speed)
+    }
+
+    /// Jumps to `time` seconds into the sound. Past the end stops it.
+    pub fn seek(self, time: f32) {
+        let Some(sound) = self.sound() else {
+            return;
+        };
+        let Some(audio) = &get_state().audio_engine else {
+            return;
+        };
+
+        audio.stop(sound.id).ok();
+        match start_sample(&sound.path, sound.bus, time.max(0.0)) {
+            Some(id) => {
+                sound.restart(id, time.max(0.0));
+                audio.set_playback_rate(id, sound.speed).ok();
+                if sound.paused {
+                    audio.pause(id).ok();
+                }
+                apply_mix(sound);
+            }
+            None => self.stop(),
+        }
+    }
+
+    /// Roughly how many seconds into the sound it is.
+    pub fn time(self) -> Option<f32> {
+        self.sound().map(|sound| sound.time)
+    }
+
+    /// Moves a positioned sound, like footsteps following whoever's walking. Sounds started
+    /// with [`play_sound`] get positioned from then on.
+    pub fn set_position(self, position: Vec2) {
+        if let Some(sound) = self.sound() {
+            sound.position = Some(position);
+        }
+    }
+
+    /// Sends a [`SoundFinished`] event once it plays to the end, even if it's been seeked.
+    pub fn notify_when_finished(self) {
+        if let Some(sound) = self.sound() {
+            sound.watched = true;
+        }
+    }
+
+    /// Whether it's played to the end or been stopped. Paused sounds aren't finished.
+    pub fn is_finished(self) -> bool {
+        match self.sound() {
+            Some(sound) => {
+                sound.seen_playing
+                    && get_state()
+                        .audio_engine
+                        .as_ref()
+                        .is_none_or(|audio| !audio.is_playing(sound.id))
+            }
+            None => true,
+        }
+    }
+}
+
+/// Hears positioned sounds from `listener` instead of the middle of the camera, like from the
+/// player in a game where the camera runs ahead of them. `None` goes back to the camera.
+pub fn set_listener(listener: Option<Vec2>) {
+    get_state().sounds.listener = listener;
+}
+
+/// Where positioned sounds are heard from, in the world.
+pub fn listener_position() -> Vec2 {
+    let state = get_state();
+    match state.sounds.listener {
+        Some(listener) => listener,
+        None => {
+            let (min, max) = state.camera_2d.visible_bounds();
+            (min + max) / 2.0
+        }
+    }
+}
+
+pub fn set_spatial_falloff(falloff: SpatialFalloff) {
+    get_state().sounds.falloff = falloff;
+}
+
+pub fn spatial_falloff() -> SpatialFalloff {
+    get_state().sounds.falloff
+}
+
+fn apply_mix(sound: &PlayingSound) {
+    let state = get_state();
+    let Some(audio) = &state.audio_engine else {
+        return;
+    };
+
+    let (volume, pan) = match sound.position {
+        Some(position) => {
+            // measured on screen and scaled back, so it follows the camera's rotation
+            let camera = &mut state.camera_2d;
+            let screen_offset =
+                camera.world_to_screen(position) - camera.world_to_screen(listener_position());
+            let offset = screen_offset.normalize_or_zero()
+                * camera.screen_distance_to_world(screen_offset.length());
+            state.sounds.falloff.mix(offset)
+        }
+        None => (1.0, 0.0),
+    };
+
+    audio
+        .set_volume(sound.id, sound.volume * volume * bus_gain(sound.bus))
+        .ok();
+    audio.set_pan(sound.id, pan).ok();
+}
+
+/// How long a sound gets to start playing before it's given up on.
+const START_TIMEOUT: f32 = 1.0;
+
+/// Whether a sound `age` seconds old is still going, from whether the audio thread says it's
+/// `playing`. Sounds aren't playing straight away, so one that hasn't been `seen_playing` yet
+/// gets a moment to start.
+pub(crate) fn still_playing(playing: bool, seen_playing: bool, age: f32) -> bool {
+    playing || (!seen_playing && age < START_TIMEOUT)
+}
+
+/// Drops the sounds that have finished, sending a [`SoundFinished`] for the watched ones.
+pub(crate) fn forget_finished(
+    sounds: &mut Vec<PlayingSound>,
+    is_playing: impl Fn(SoundId) -> bool,
+    events: &mut EventBus,
+) {
+    sounds.retain_mut(|sound| {
+        let playing = is_playing(sound.id);
+        sound.seen_playing |= playing;
+        let still = still_playing(
+            playing,
+            sound.seen_playing,
+            sound.started.elapsed().as_secs_f32(),
+        );
+        if !still && sound.watched {
+            events.emit(SoundFinished {
+                handle: sound.handle,
+            });
+        }
+        still
+    });
+}
+
+/// Forgets sounds that have finished, and updates the rest for where they and the listener
+/// are now, and their bus's volume.
+pub(crate) fn update_sounds(dt: f32) {
+    let state = get_state();
+    let Some(audio) = &state.audio_engine else {
+        return;
+    };
+
+    forget_finished(
+        &mut state.sounds.sounds,
+        |id| audio.is_playing(id),
+        &mut state.events,
+    );
+    for sound in &mut state.sounds.sounds {
+        if !sound.paused {
+            sound.time += dt * sound.speed;
+        }
+        apply_mix(sound);
+    }
+}
@@ -0,0 +1,327 @@
+use std::sync::OnceLock;
+
+use bevy_math::Vec2;
+use glium::{Surface, uniform};
+
+use crate::{
+    color::Color,
+    error::EngineResult,
+    get_state,
+    post_processing::{POSTPROCESS_VERTEX_SHADER, render_fullscreen_quad},
+    programs::{ProgramRef, cached_program, load_program},
+};
+
+/// Cheap shader-driven fillers drawn behind everything else. Layers are drawn in the order
+/// they were added, so put the gradient first and stack stars/clouds/hills on top of it.
+#[derive(Clone, Debug)]
+pub enum BackgroundLayer {
+    Gradient {
+        top: Color,
+        horizon: Color,
+        bottom: Color,
+        /// Where the horizon sits, from 0 (bottom of the screen) to 1 (top).
+        horizon_height: f32,
+    },
+    Stars {
+        color: Color,
+        /// Chance of a star in each cell, 0 to 1.
+        density: f32,
+        /// Size of a cell in pixels. Bigger cells mean fewer, more spread out stars.
+        cell_size: f32,
+        twinkle_speed: f32,
+    },
+    Clouds {
+        color: Color,
+        scale: f32,
+        /// 0 is clear skies, 1 is fully overcast.
+        coverage: f32,
+        speed: f32,
+    },
+    Hills {
+        color: Color,
+        /// Base height of the hills, from 0 (bottom of the screen) to 1 (top).
+        height: f32,
+        amplitude: f32,
+        frequency: f32,
+        speed: f32,
+    },
+}
+
+impl BackgroundLayer {
+    pub(crate) fn draw<T: Surface>(
+        &self,
+        target: &mut T,
+        screen_size: Vec2,
+        flip_y: bool,
+    ) -> anyhow::Result<()> {
+        let time = get_state().time;
+
+        match self {
+            Self::Gradient {
+                top,
+                horizon,
+                bottom,
+                horizon_height,
+            } => {
+                let program = get_or_create_gradient_program()?;
+                let uniforms = uniform! {
+                    top_color: top.for_gpu(),
+                    horizon_color: horizon.for_gpu(),
+                    bottom_color: bottom.for_gpu(),
+                    horizon_height: *horizon_height,
+                    flip_y: flip_y,
+                };
+                render_fullscreen_quad(target, program.get(), &uniforms)?;
+            }
+            Self::Stars {
+                color,
+                density,
+                cell_size,
+                twinkle_speed,
+            } => {
+                let program = get_or_create_stars_program()?;
+                let uniforms = uniform! {
+                    star_color: color.for_gpu(),
+                    density: *density,
+                    cell_size: *cell_size,
+                    twinkle_speed: *twinkle_speed,
+                    time: time,
+                    screen_size: [screen_size.x, screen_size.y],
+                    flip_y: flip_y,
+                };
+                render_fullscreen_quad(target, program.get(), &uniforms)?;
+            }
+            Self::Clouds {
+                color,
+                scale,
+                coverage,
+                speed,
+            } => {
+                let program = get_or_create_clouds_program()?;
+                let uniforms = uniform! {
+                    cloud_color: color.for_gpu(),
+                    scale: *scale,
+                    coverage: *coverage,
+                    speed: *speed,
+                    time: time,
+                    screen_size: [screen_size.x, screen_size.y],
+                    flip_y: flip_y,
+                };
+                render_fullscreen_quad(target, program.get(), &uniforms)?;
+            }
+            Self::Hills {
+                color,
+                height,
+                amplitude,
+                frequency,
+                speed,
+            } => {
+                let program = get_or_create_hills_program()?;
+                let uniforms = uniform! {
+                    hill_color: color.for_gpu(),
+                    height: *height,
+                    amplitude: *amplitude,
+                    frequency: *frequency,
+                    speed: *speed,
+                    time: time,
+                    screen_size: [screen_size.x, screen_size.y],
+                    flip_y: flip_y,
+                };
+                render_fullscreen_quad(target, program.get(), &uniforms)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+static GRADIENT_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
+static STARS_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
+static CLOUDS_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
+static HILLS_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
+
+fn get_or_create_gradient_program() -> EngineResult<ProgramRef> {
+    cached_program(&GRADIENT_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, GRADIENT_FRAGMENT_SHADER)
+    })
+}
+
+fn get_or_create_stars_program() -> EngineResult<ProgramRef> {
+    cached_program(&STARS_PROGRAM, || {
+        load_program(
+            POSTPROCESS_VERTEX_SHADER,
+            &with_noise(STARS_FRAGMENT_SHADER),
+        )
+    })
+}
+
+fn get_or_create_clouds_program() -> EngineResult<ProgramRef> {
+    cached_program(&CLOUDS_PROGRAM, || {
+        load_program(
+            POSTPROCESS_VERTEX_SHADER,
+            &with_noise(CLOUDS_FRAGMENT_SHADER),
+        )
+    })
+}
+
+fn get_or_create_hills_program() -> EngineResult<ProgramRef> {
+    cached_program(&HILLS_PROGRAM, || {
+        load_program(
+            POSTPROCESS_VERTEX_SHADER,
+            &with_noise(HILLS_FRAGMENT_SHADER),
+        )
+    })
+}
+
+fn with_noise(shader: &str) -> String {
+    shader.replacen("// NOISE", NOISE_FUNCTIONS, 1)
+}
+
+const NOISE_FUNCTIONS: &str = r#"
+float hash(vec2 p) {
+    p = fract(p * vec2(123.34, 456.21));
+    p += dot(p, p + 45.32);
+    return fract(p.x * p.y);
+}
+
+float value_noise(vec2 p) {
+    vec2 i = floor(p);
+    vec2 f = fract(p);
+    vec2 u = f * f * (3.0 - 2.0 * f);
+
+    float a = hash(i);
+    float b = hash(i + vec2(1.0, 0.0));
+    float c = hash(i + vec2(0.0, 1.0));
+    float d = hash(i + vec2(1.0, 1.0));
+
+    return mix(mix(a, b, u.x), mix(c, d, u.x), u.y);
+}
+
+float fbm(vec2 p) {
+    float value = 0.0;
+    float amplitude = 0.5;
+    for (int i = 0; i < 5; i++) {
+        value += amplitude * value_noise(p);
+        p *= 2.0;
+        amplitude *= 0.5;
+    }
+    return value;
+}
+"#;
+
+const GRADIENT_FRAGMENT_SHADER: &str = r#"
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+uniform vec4 top_color;
+uniform vec4 horizon_color;
+uniform vec4 bottom_color;
+uniform float horizon_height;
+uniform bool flip_y;
+
+void main() {
+    float y = flip_y ? 1.0 - v_tex_coords.y : v_tex_coords.y;
+
+    if (y > horizon_height) {
+        color = mix(horizon_color, top_color, (y - horizon_height) / (1.0 - horizon_height));
+    } else {
+        color = mix(bottom_color, horizon_color, y / horizon_height);
+    }
+}
+"#;
+
+const STARS_FRAGMENT_SHADER: &str = r#"
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+uniform vec4 star_color;
+uniform float density;
+uniform float cell_size;
+uniform float twinkle_speed;
+uniform float time;
+uniform vec2 screen_size;
+uniform bool flip_y;
+
+// NOISE
+
+void main() {
+    vec2 uv = v_tex_coords;
+    if (flip_y) uv.y = 1.0 - uv.y;
+
+    vec2 pixel = uv * screen_size;
+    vec2 cell = floor(pixel / cell_size);
+
+    if (hash(cell) > density) {
+        color = vec4(0.0);
+        return;
+    }
+
+    vec2 star_pos = (cell + vec2(hash(cell + 17.0), hash(cell + 31.0))) * cell_size;
+    float size = mix(0.5, 1.5, hash(cell + 53.0));
+    float dist = distance(pixel, star_pos);
+    float glow = 1.0 - smoothstep(0.0, size, dist);
+
+    float twinkle = 0.75 + 0.25 * sin(time * twinkle_speed + hash(cell + 71.0) * 6.2831);
+
+    color = vec4(star_color.rgb, star_color.a * glow * twinkle);
+}
+"#;
+
+const CLOUDS_FRAGMENT_SHADER: &str = r#"
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+uniform vec4 cloud_color;
+uniform float scale;
+uniform float coverage;
+uniform float speed;
+uniform float time;
+uniform vec2 screen_size;
+uniform bool flip_y;
+
+// NOISE
+
+void main() {
+    vec2 uv = v_tex_coords;
+    if (flip_y) uv.y = 1.0 - uv.y;
+
+    vec2 p = uv * vec2(screen_size.x / screen_size.y, 1.0) * scale;
+    p.x += time * speed;
+
+    float n = fbm(p);
+    float threshold = 1.0 - coverage;
+    float cloud = smoothstep(threshold - 0.1, threshold + 0.15, n);
+
+    color = vec4(cloud_color.rgb, cloud_color.a * cloud);
+}
+"#;
+
+const HILLS_FRAGMENT_SHADER: &str = r#"
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+uniform vec4 hill_color;
+uniform float height;
+uniform float amplitude;
+uniform float frequency;
+uniform float speed;
+uniform float time;
+uniform vec2 screen_size;
+uniform bool flip_y;
+
+// NOISE
+
+void main() {
+    vec2 uv = v_tex_coords;
+    if (flip_y) uv.y = 1.0 - uv.y;
+
+    float x = uv.x * (screen_size.x / screen_size.y) * frequency + time * speed;
+    float hill = height + amplitude * (fbm(vec2(x, 0.0)) - 0.5);
+
+    // a pixel of antialiasing on the edge
+    float edge = 1.0 / screen_size.y;
+    float inside = 1.0 - smoothstep(hill - edge, hill + edge, uv.y);
+
+    color = vec4(hill_color.rgb, hill_color.a * inside);
+}
+"#;
@@ -1 +1,185 @@
+//! GPU buffers that live for the whole program, so drawing doesn't have to make new ones every
+//! frame. Each draw's data is written after the last one's, and once a buffer is full it's
+//! orphaned (the driver hands back fresh memory while the GPU finishes with the old) and
+//! writing starts again from the front.
 
+use std::ops::Range;
+
+use glium::index::{IndexBufferSlice, PrimitiveType};
+use glium::vertex::VertexBufferSlice;
+use glium::{IndexBuffer, Vertex, VertexBuffer};
+
+use crate::draw_queue_2d::{CircleInstance, SdfVertex, SpriteVertex, Vertex2D, Vertex3D};
+use crate::error::OrReport;
+use crate::get_state;
+use crate::particles_3d::ParticleInstance3D;
+use crate::shapes_2d::{QUAD_INDICES, UNIT_QUAD};
+
+/// The smallest a stream buffer starts at, in elements.
+const MIN_CAPACITY: usize = 4096;
+
+/// Where the next write goes in a stream buffer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Placement {
+    /// Straight after the last write.
+    Append(Range<usize>),
+    /// Doesn't fit after the last write, so orphan the buffer and start from the front.
+    Orphan(Range<usize>),
+    /// Doesn't fit at all, so make a new buffer of `capacity`.
+    Grow {
+        capacity: usize,
+        range: Range<usize>,
+    },
+}
+
+impl Placement {
+    pub(crate) fn plan(capacity: usize, cursor: usize, len: usize) -> Self {
+        if len > capacity {
+            let capacity = len.max(capacity * 2).max(MIN_CAPACITY).next_power_of_two();
+            Self::Grow {
+                capacity,
+                range: 0..len,
+            }
+        } else if cursor + len > capacity {
+            Self::Orphan(0..len)
+        } else {
+            Self::Append(cursor..cursor + len)
+        }
+    }
+
+    fn range(&self) -> Range<usize> {
+        match self {
+            Self::Append(range) | Self::Orphan(range) | Self::Grow { range, .. } => range.clone(),
+        }
+    }
+}
+
+pub(crate) struct StreamVertexBuffer<T: Vertex> {
+    buffer: Option<VertexBuffer<T>>,
+    cursor: usize,
+}
+
+impl<T: Vertex> StreamVertexBuffer<T> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            buffer: None,
+            cursor: 0,
+        }
+    }
+
+    /// Writes `data` and returns the part of the buffer it went into. `None` if `data` is
+    /// empty, or if a bigger buffer was needed and couldn't be made, which is reported.
+    pub(crate) fn upload(&mut self, data: &[T]) -> Option<VertexBufferSlice<'_, T>> {
+        if data.is_empty() {
+            return None;
+        }
+        let capacity = self.buffer.as_ref().map_or(0, |buffer| buffer.len());
+        let placement = Placement::plan(capacity, self.cursor, data.len());
+
+        match &placement {
+            Placement::Append(_) => {}
+            Placement::Orphan(_) => self.buffer.as_ref()?.invalidate(),
+            Placement::Grow { capacity, .. } => {
+                self.buffer = Some(
+                    VertexBuffer::empty_dynamic(get_state().display(), *capacity).or_report()?,
+                );
+            }
+        }
+
+        let range = placement.range();
+        self.cursor = range.end;
+        let slice = self.buffer.as_ref()?.slice(range)?;
+        slice.write(data);
+        Some(slice)
+    }
+}
+
+pub(crate) struct StreamIndexBuffer {
+    buffer: Option<IndexBuffer<u32>>,
+    cursor: usize,
+}
+
+impl StreamIndexBuffer {
+    pub(crate) const fn new() -> Self {
+        Self {
+            buffer: None,
+            cursor: 0,
+        }
+    }
+
+    /// Writes `data` as a triangle list and returns the part of the buffer it went into. `None`
+    /// if `data` is empty, or if a bigger buffer was needed and couldn't be made, which is
+    /// reported.
+    pub(crate) fn upload(&mut self, data: &[u32]) -> Option<IndexBufferSlice<'_, u32>> {
+        if data.is_empty() {
+            return None;
+        }
+        let capacity = self.buffer.as_ref().map_or(0, |buffer| buffer.len());
+        let placement = Placement::plan(capacity, self.cursor, data.len());
+
+        match &placement {
+            Placement::Append(_) => {}
+            Placement::Orphan(_) => self.buffer.as_ref()?.invalidate(),
+            Placement::Grow { capacity, .. } => {
+                self.buffer = Some(
+                    IndexBuffer::empty_dynamic(
+                        get_state().display(),
+                        PrimitiveType::TrianglesList,
+                        *capacity,
+                    )
+                    .or_report()?,
+                );
+            }
+        }
+
+        let range = placement.range();
+        self.cursor = range.end;
+        let slice = self.buffer.as_ref()?.slice(range)?;
+        slice.write(data);
+        Some(slice)
+    }
+}
+
+/// [`UNIT_QUAD`] and [`QUAD_INDICES`], uploaded the first time they're needed.
+pub(crate) struct QuadBuffers(Option<(VertexBuffer<Vertex2D>, IndexBuffer<u32>)>);
+
+impl QuadBuffers {
+    /// `None` if they couldn't be uploaded, which is reported.
+    pub(crate) fn get(&mut self) -> Option<(&VertexBuffer<Vertex2D>, &IndexBuffer<u32>)> {
+        if self.0.is_none() {
+            let display = get_state().display();
+            let vertices = VertexBuffer::new(display, &UNIT_QUAD).or_report()?;
+            let indices = IndexBuffer::new(display, PrimitiveType::TrianglesList, &QUAD_INDICES)
+                .or_report()?;
+            self.0 = Some((vertices, indices));
+        }
+        self.0
+            .as_ref()
+            .map(|(vertices, indices)| (vertices, indices))
+    }
+}
+
+/// Every buffer the 2D renderer and 3D particles stream into, plus the quad they instance.
+pub(crate) struct DrawBuffers {
+    pub(crate) shapes: StreamVertexBuffer<Vertex3D>,
+    pub(crate) sprites: StreamVertexBuffer<SpriteVertex>,
+    pub(crate) glyphs: StreamVertexBuffer<SdfVertex>,
+    pub(crate) circles: StreamVertexBuffer<CircleInstance>,
+    pub(crate) particles_3d: StreamVertexBuffer<ParticleInstance3D>,
+    pub(crate) indices: StreamIndexBuffer,
+    pub(crate) quad: QuadBuffers,
+}
+
+impl DrawBuffers {
+    pub(crate) const fn new() -> Self {
+        Self {
+            shapes: StreamVertexBuffer::new(),
+            sprites: StreamVertexBuffer::new(),
+            glyphs: StreamVertexBuffer::new(),
+            circles: StreamVertexBuffer::new(),
+            particles_3d: StreamVertexBuffer::new(),
+            indices: StreamIndexBuffer::new(),
+            quad: QuadBuffers(None),
+        }
+    }
+}
@@ -18,6 +18,17 @@ impl PanningCameraController {
 
             camera2d_zoom_at(cursor_pos(), diff);
         }
+
+        let pan = touch_pan();
+        if pan != Vec2::ZERO {
+            mutate_camera_2d(|camera| {
+                camera.translation -= pan / camera.scale;
+            });
+        }
+
+        if let Some((center, scale)) = pinch_zoom() {
+            camera2d_zoom_at(center, scale);
+        }
     }
 
     pub fn new() -> Self {
@@ -1,10 +1,11 @@
 use bevy_math::{Mat3, Mat4, Vec2, Vec3, Vec4};
 use glium::winit::window::Window;
 
-use crate::EngineState;
+use crate::{EngineState, collisions::AABB2D, collisions3d::Frustum, shapes_3d::Ray3D};
 const BIG_NUMBER: f32 = 9999.9;
 
 pub mod controllers;
+pub mod shake;
 
 #[derive(Clone, Debug, Copy)]
 pub struct Camera2D {
@@ -18,6 +19,19 @@ pub struct Camera2D {
     inverse_view_matrix: Mat3,
     projection_matrix: Mat4,
     needs_update: bool,
+    /// See `culling_bounds`.
+    culling_cache: Option<CullingCache2D>,
+}
+
+/// The camera's placement when `bounds` were worked out. Its fields are public, so this is
+/// checked instead of relying on `mark_dirty`.
+#[derive(Clone, Debug, Copy)]
+struct CullingCache2D {
+    translation: Vec2,
+    scale: f32,
+    rotation: f32,
+    window_size: Vec2,
+    bounds: AABB2D,
 }
 
 #[derive(Clone, Debug, Copy)]
@@ -58,6 +72,7 @@ impl Camera2D {
             inverse_view_matrix: Mat3::IDENTITY,
             projection_matrix: Mat4::IDENTITY,
             needs_update: true,
+            culling_cache: None,
         };
         camera.update_matrices();
         camera
@@ -130,6 +145,36 @@ impl Camera2D {
         (min, max)
     }
 
+    /// What's visible, grown by a screen's worth of margin so thick outlines and such don't pop
+    /// in at the edges. Only worked out again when the camera moves, so it's cheap to call for
+    /// every shape.
+    pub fn culling_bounds(&mut self) -> AABB2D {
+        let cached = self.culling_cache.filter(|cache| {
+            cache.translation == self.translation
+                && cache.scale == self.scale
+                && cache.rotation == self.rotation
+                && cache.window_size == self.window_size
+        });
+        if let Some(cache) = cached {
+            return cache.bounds;
+        }
+
+        // the matrices might be stale if the fields were changed without `mark_dirty`
+        self.mark_dirty();
+        let (min, max) = self.visible_bounds();
+        let margin = self.window_size.max_element() / self.scale;
+        let bounds = AABB2D::new(min - Vec2::splat(margin), max + Vec2::splat(margin));
+
+        self.culling_cache = Some(CullingCache2D {
+            translation: self.translation,
+            scale: self.scale,
+            rotation: self.rotation,
+            window_size: self.window_size,
+            bounds,
+        });
+        bounds
+    }
+
     pub fn world_distance_to_screen(&self, world_distance: f32) -> f32 {
         world_distance * self.scale
     }
@@ -262,6 +307,38 @@ impl Camera3D {
         self.view_proj
     }
 
+    /// What the camera can see, for skipping things that are off screen.
+    pub fn frustum(&mut self) -> Frustum {
+        Frustum::from_view_proj(self.view_proj())
+    }
+
+    /// Ray going from the camera through the given pixel, for picking things in the world.
+    pub fn screen_to_ray(&mut self, screen_pos: Vec2) -> Ray3D {
+        self.update_matrices();
+
+        let ndc = Vec2::new(
+            screen_pos.x / self.window_size.x * 2.0 - 1.0,
+            1.0 - screen_pos.y / self.window_size.y * 2.0,
+        );
+        let inverse = self.view_proj.inverse();
+
+        let near = inverse.project_point3(ndc.extend(0.0));
+        let far = inverse.project_point3(ndc.extend(1.0));
+
+        Ray3D::from_points(near, far)
+    }
+
+    pub fn world_to_screen(&mut self, world_pos: Vec3) -> Vec2 {
+        self.update_matrices();
+
+        let ndc = self.view_proj.project_point3(world_pos);
+
+        Vec2::new(
+            (ndc.x + 1.0) * 0.5 * self.window_size.x,
+            (1.0 - ndc.y) * 0.5 * self.window_size.y,
+        )
+    }
+
     pub fn window_aspect_ratio(&self) -> f32 {
         self.window_size.x / self.window_size.y
     }
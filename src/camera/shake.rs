@@ -0,0 +1,76 @@
+use bevy_math::Vec2;
+
+use crate::get_state;
+
+/// Trauma based screen shake. Trauma goes from 0 to 1 and wears off over time, and the shake is
+/// trauma squared, so small hits barely move the screen and big ones throw it around.
+///
+/// Only moves the [`RenderPass`](crate::gfx::RenderPass)es that have
+/// [`camera_shake`](crate::gfx::PassSettings::camera_shake) on, which by default is just the
+/// world, so the HUD stays put.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraShake {
+    pub trauma: f32,
+    /// How far the screen moves at full trauma, in pixels.
+    pub max_offset: f32,
+    /// How much trauma wears off each second.
+    pub decay: f32,
+    /// How quickly it wobbles. Higher is more jittery.
+    pub frequency: f32,
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        Self {
+            trauma: 0.0,
+            max_offset: 16.0,
+            decay: 1.5,
+            frequency: 20.0,
+        }
+    }
+}
+
+impl CameraShake {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn update(&mut self, delta_time: f32) {
+        self.trauma = (self.trauma - self.decay * delta_time).max(0.0);
+    }
+
+    /// How far the screen is moved at `time`, in pixels.
+    pub fn offset(&self, time: f32) -> Vec2 {
+        if self.trauma <= 0.0 {
+            return Vec2::ZERO;
+        }
+
+        // a few sines at unrelated rates, which is smooth but doesn't look like it repeats
+        let t = time * self.frequency;
+        let wobble = |phase: f32| {
+            ((t + phase).sin()
+                + (t * 1.7 + phase * 2.3).sin() * 0.5
+                + (t * 3.1 + phase).sin() * 0.25)
+                / 1.75
+        };
+        Vec2::new(wobble(0.0), wobble(11.0)) * self.max_offset * self.trauma * self.trauma
+    }
+}
+
+/// Adds `trauma` to the [`CameraShake`], up to 1. `0.2` is a bump, `1.0` is an explosion.
+pub fn shake_camera(trauma: f32) {
+    get_state().camera_shake.add_trauma(trauma);
+}
+
+pub fn camera_shake() -> CameraShake {
+    get_state().camera_shake
+}
+
+/// For changing how strong and how long shakes are.
+pub fn set_camera_shake(shake: CameraShake) {
+    get_state().camera_shake = shake;
+}
+
+pub(crate) fn update_camera_shake(delta_time: f32) {
+    get_state().camera_shake.update(delta_time);
+}
@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+
+use bevy_math::Vec2;
+use rapier2d::{
+    control::{CharacterAutostep, CharacterLength, KinematicCharacterController},
+    prelude::{
+        Collider, ColliderHandle, EventHandler, PhysicsHooks, QueryFilter, RigidBodyBuilder,
+        RigidBodyHandle,
+    },
+};
+
+use crate::physics_world::World;
+
+/// The usual platformer character: a kinematic body that moves where it's told, sliding along
+/// walls and floors instead of stopping dead, climbing small steps and gentle slopes, and
+/// dropping through one-way platforms from below.
+///
+/// Call [`move_and_slide`](Self::move_and_slide) once per frame before stepping the world, then
+/// check [`is_grounded`](Self::is_grounded) and friends.
+#[derive(Clone)]
+pub struct CharacterController2D {
+    pub body: RigidBodyHandle,
+    pub collider: ColliderHandle,
+    /// Rapier's controller, for settings not covered here.
+    pub controller: KinematicCharacterController,
+    one_way_platforms: HashSet<ColliderHandle>,
+    grounded: bool,
+    on_wall: bool,
+    on_ceiling: bool,
+    velocity: Vec2,
+}
+
+impl CharacterController2D {
+    /// Adds a kinematic body at `position` with `collider` attached to the world.
+    pub fn new<H: PhysicsHooks, E: EventHandler>(
+        world: &mut World<H, E>,
+        position: Vec2,
+        collider: Collider,
+    ) -> Self {
+        let (body, collider) = world.insert_rigid_body_with_collider(
+            RigidBodyBuilder::kinematic_position_based()
+                .translation(position.into())
+                .build(),
+            collider,
+        );
+
+        Self {
+            body,
+            collider,
+            controller: KinematicCharacterController::default(),
+            one_way_platforms: HashSet::new(),
+            grounded: false,
+            on_wall: false,
+            on_ceiling: false,
+            velocity: Vec2::ZERO,
+        }
+    }
+
+    /// Steps up to `height` high are climbed without jumping.
+    pub fn with_step_height(mut self, height: f32) -> Self {
+        self.controller.autostep = Some(CharacterAutostep {
+            max_height: CharacterLength::Absolute(height),
+            min_width: CharacterLength::Relative(0.5),
+            include_dynamic_bodies: false,
+        });
+        self
+    }
+
+    /// Steepest slope, in radians, that can be walked up. Anything steeper is a wall.
+    pub fn with_max_slope(mut self, angle: f32) -> Self {
+        self.controller.max_slope_climb_angle = angle;
+        self.controller.min_slope_slide_angle = angle;
+        self
+    }
+
+    /// Keeps the character stuck to the ground when going down slopes or steps up to
+    /// `distance` high, instead of flying off them. `None` turns it off.
+    pub fn with_snap_to_ground(mut self, distance: Option<f32>) -> Self {
+        self.controller.snap_to_ground = distance.map(CharacterLength::Absolute);
+        self
+    }
+
+    /// Makes `platform` solid only from above: the character can jump up through it and land
+    /// on top.
+    pub fn add_one_way_platform(&mut self, platform: ColliderHandle) {
+        self.one_way_platforms.insert(platform);
+    }
+
+    pub fn remove_one_way_platform(&mut self, platform: ColliderHandle) {
+        self.one_way_platforms.remove(&platform);
+    }
+
+    /// Moves by `velocity * dt`, sliding along anything in the way. Returns the velocity it
+    /// actually moved at, which is what to keep using next frame (so running into a ceiling
+    /// stops a jump).
+    ///
+    /// The move happens on the next [`step`](World::step) of the world.
+    pub fn move_and_slide<H: PhysicsHooks, E: EventHandler>(
+        &mut self,
+        world: &mut World<H, E>,
+        velocity: Vec2,
+        dt: f32,
+    ) -> Vec2 {
+        let (Some(body), Some(collider)) = (
+            world.rigid_body_set.get(self.body),
+            world.collider_set.get(self.collider),
+        ) else {
+            return Vec2::ZERO;
+        };
+
+        // where the body will be, in case it's moved twice before a step
+        let position = *body.next_position();
+        let shape = collider.shared_shape().clone();
+        let feet = shape.compute_aabb(&position).mins.y;
+        let moving_up = velocity.y > 0.0;
+
+        let one_way_platforms = &self.one_way_platforms;
+        let predicate = |handle: ColliderHandle, platform: &Collider| {
+            if !one_way_platforms.contains(&handle) {
+                return true;
+            }
+            // solid only when coming down onto it from above
+            let top = platform.compute_aabb().maxs.y;
+            !moving_up && feet >= top - 0.01
+        };
+        let filter = QueryFilter::default()
+            .exclude_rigid_body(self.body)
+            .exclude_sensors()
+            .predicate(&predicate);
+
+        let mut hit_normals = Vec::new();
+        let movement = self.controller.move_shape(
+            dt,
+            &world.query_pipeline(filter),
+            shape.as_ref(),
+            &position,
+            (velocity * dt).into(),
+            |collision| hit_normals.push(collision.hit.normal1),
+        );
+
+        let max_slope = self.controller.max_slope_climb_angle;
+        self.grounded = movement.grounded;
+        self.on_wall = false;
+        self.on_ceiling = false;
+        for normal in hit_normals {
+            let angle_from_up = normal.y.clamp(-1.0, 1.0).acos();
+            if angle_from_up >= std::f32::consts::PI - max_slope {
+                self.on_ceiling = true;
+            } else if angle_from_up > max_slope {
+                self.on_wall = true;
+            }
+        }
+
+        let translation = Vec2::new(movement.translation.x, movement.translation.y);
+        let next = position.translation.vector + movement.translation;
+        if let Some(body) = world.rigid_body_set.get_mut(self.body) {
+            body.set_next_kinematic_translation(next);
+        }
+
+        self.velocity = if dt > 0.0 {
+            translation / dt
+        } else {
+            Vec2::ZERO
+        };
+        self.velocity
+    }
+
+    /// Standing on something, as of the last move.
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    /// Ran into something too steep to walk up, as of the last move.
+    pub fn is_on_wall(&self) -> bool {
+        self.on_wall
+    }
+
+    /// Hit something from below, as of the last move.
+    pub fn is_on_ceiling(&self) -> bool {
+        self.on_ceiling
+    }
+
+    /// How fast the last move actually went.
+    pub fn velocity(&self) -> Vec2 {
+        self.velocity
+    }
+
+    /// Where the character will be after the next step.
+    pub fn position<H, E>(&self, world: &World<H, E>) -> Vec2 {
+        world
+            .rigid_body_set
+            .get(self.body)
+            .map(|body| {
+                let translation = body.next_position().translation;
+                Vec2::new(translation.x, translation.y)
+            })
+            .unwrap_or_default()
+    }
+}
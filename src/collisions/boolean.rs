@@ -0,0 +1,139 @@
+use bevy_math::Vec2;
+use i_overlay::{
+    core::{fill_rule::FillRule, overlay_rule::OverlayRule},
+    float::single::SingleFloatOverlay,
+};
+
+use super::{AABB2D, HasBounds2D, IntersectsWith, Point, Polygon};
+
+/// A polygon with holes cut out of it. This is what boolean operations give back, since
+/// cutting a hole in the middle of something can't be described by a single outline.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PolygonWithHoles {
+    pub outline: Polygon,
+    pub holes: Vec<Polygon>,
+}
+
+impl PolygonWithHoles {
+    pub fn new(outline: Polygon, holes: Vec<Polygon>) -> Self {
+        Self { outline, holes }
+    }
+
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        self.outline.contains_point(point)
+            && !self.holes.iter().any(|hole| hole.contains_point(point))
+    }
+
+    /// Area of the outline minus the area of the holes.
+    pub fn area(&self) -> f32 {
+        polygon_area(&self.outline) - self.holes.iter().map(polygon_area).sum::<f32>()
+    }
+}
+
+impl From<Polygon> for PolygonWithHoles {
+    fn from(outline: Polygon) -> Self {
+        Self::new(outline, Vec::new())
+    }
+}
+
+impl HasBounds2D for PolygonWithHoles {
+    fn bounds(&self) -> AABB2D {
+        self.outline.bounds()
+    }
+}
+
+impl IntersectsWith<Point> for PolygonWithHoles {
+    fn intersects_with(&self, point: &Point) -> bool {
+        self.contains_point(point.position)
+    }
+}
+
+/// Union, intersection and difference of polygons. Works on single polygons, polygons with
+/// holes and lists of them (like the result of a previous operation), so shapes can be carved
+/// up again and again, e.g. for destructible terrain.
+///
+/// Inside is decided with the even-odd rule, so the polygons in a list shouldn't overlap each
+/// other. Results never do.
+pub trait BooleanOps {
+    /// Every outline and hole, as lists of points.
+    fn contours(&self) -> Vec<Vec<[f32; 2]>>;
+
+    fn union(&self, other: &impl BooleanOps) -> Vec<PolygonWithHoles> {
+        overlay(self, other, OverlayRule::Union)
+    }
+
+    fn intersection(&self, other: &impl BooleanOps) -> Vec<PolygonWithHoles> {
+        overlay(self, other, OverlayRule::Intersect)
+    }
+
+    /// `self` with `other` cut out of it.
+    fn difference(&self, other: &impl BooleanOps) -> Vec<PolygonWithHoles> {
+        overlay(self, other, OverlayRule::Difference)
+    }
+}
+
+impl BooleanOps for Polygon {
+    fn contours(&self) -> Vec<Vec<[f32; 2]>> {
+        vec![to_contour(self)]
+    }
+}
+
+impl BooleanOps for PolygonWithHoles {
+    fn contours(&self) -> Vec<Vec<[f32; 2]>> {
+        std::iter::once(&self.outline)
+            .chain(&self.holes)
+            .map(to_contour)
+            .collect()
+    }
+}
+
+impl<T: BooleanOps> BooleanOps for [T] {
+    fn contours(&self) -> Vec<Vec<[f32; 2]>> {
+        self.iter().flat_map(BooleanOps::contours).collect()
+    }
+}
+
+impl<T: BooleanOps> BooleanOps for Vec<T> {
+    fn contours(&self) -> Vec<Vec<[f32; 2]>> {
+        self.as_slice().contours()
+    }
+}
+
+fn overlay(
+    subject: &(impl BooleanOps + ?Sized),
+    clip: &(impl BooleanOps + ?Sized),
+    rule: OverlayRule,
+) -> Vec<PolygonWithHoles> {
+    let shapes = subject
+        .contours()
+        .overlay(&clip.contours(), rule, FillRule::EvenOdd);
+
+    shapes
+        .into_iter()
+        .filter_map(|contours| {
+            let mut contours = contours.into_iter().map(|contour| Polygon {
+                vertices: contour.into_iter().map(Vec2::from).collect(),
+            });
+            // the first contour of each shape is its outline, the rest are holes in it
+            let outline = contours.next()?;
+            Some(PolygonWithHoles::new(outline, contours.collect()))
+        })
+        .collect()
+}
+
+fn to_contour(polygon: &Polygon) -> Vec<[f32; 2]> {
+    polygon
+        .vertices
+        .iter()
+        .map(|vertex| vertex.to_array())
+        .collect()
+}
+
+fn polygon_area(polygon: &Polygon) -> f32 {
+    let n = polygon.vertices.len();
+    let twice_area: f32 = (0..n)
+        .map(|i| polygon.vertices[i].perp_dot(polygon.vertices[(i + 1) % n]))
+        .sum();
+    twice_area.abs() * 0.5
+}
@@ -0,0 +1,351 @@
+use bevy_math::Vec2;
+
+use super::{Circle, Point, Polygon, Square, closest_point_on_segment};
+
+/// How two overlapping shapes overlap. `normal` points from the first shape towards the second,
+/// so moving the second one by `normal * depth` (or the first by `-normal * depth`) separates
+/// them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Collision {
+    pub normal: Vec2,
+    pub depth: f32,
+    /// Where the shapes touch, in world space. One or two points.
+    pub contact_points: Vec<Vec2>,
+}
+
+impl Collision {
+    /// The same collision seen from the other shape.
+    pub fn flipped(self) -> Self {
+        Self {
+            normal: -self.normal,
+            ..self
+        }
+    }
+}
+
+/// Like [`IntersectsWith`](super::IntersectsWith), but also says how to push the shapes apart.
+/// Shapes that are only touching give a collision with a depth of zero.
+///
+/// Polygons are treated as convex when colliding with other polygons and squares, and can be
+/// any shape when colliding with circles and points.
+pub trait CollideWith<T> {
+    fn collide_with(&self, other: &T) -> Option<Collision>;
+}
+
+impl CollideWith<Circle> for Circle {
+    fn collide_with(&self, other: &Circle) -> Option<Collision> {
+        let offset = other.center - self.center;
+        let distance = offset.length();
+        let depth = self.radius + other.radius - distance;
+        if depth < 0.0 {
+            return None;
+        }
+
+        // circles right on top of each other can be pushed apart any way
+        let normal = offset.try_normalize().unwrap_or(Vec2::X);
+        Some(Collision {
+            normal,
+            depth,
+            contact_points: vec![self.center + normal * (self.radius - depth * 0.5)],
+        })
+    }
+}
+
+impl CollideWith<Polygon> for Circle {
+    fn collide_with(&self, polygon: &Polygon) -> Option<Collision> {
+        let n = polygon.vertices.len();
+        if n < 3 {
+            return None;
+        }
+
+        let closest = (0..n)
+            .map(|i| {
+                closest_point_on_segment(
+                    self.center,
+                    polygon.vertices[i],
+                    polygon.vertices[(i + 1) % n],
+                )
+            })
+            .min_by(|a, b| {
+                let a = a.distance_squared(self.center);
+                let b = b.distance_squared(self.center);
+                a.total_cmp(&b)
+            })?;
+
+        let offset = closest - self.center;
+        let distance = offset.length();
+
+        if polygon.contains_point(self.center) {
+            // the polygon has to go past the center too, so the normal is flipped
+            let normal = (-offset).try_normalize().unwrap_or(Vec2::X);
+            return Some(Collision {
+                normal,
+                depth: self.radius + distance,
+                contact_points: vec![closest],
+            });
+        }
+
+        if distance > self.radius {
+            return None;
+        }
+
+        Some(Collision {
+            normal: offset.try_normalize().unwrap_or(Vec2::X),
+            depth: self.radius - distance,
+            contact_points: vec![closest],
+        })
+    }
+}
+
+impl CollideWith<Square> for Circle {
+    fn collide_with(&self, square: &Square) -> Option<Collision> {
+        self.collide_with(&square_polygon(square))
+    }
+}
+
+impl CollideWith<Point> for Circle {
+    fn collide_with(&self, point: &Point) -> Option<Collision> {
+        self.collide_with(&point_circle(point))
+    }
+}
+
+impl CollideWith<Polygon> for Polygon {
+    fn collide_with(&self, other: &Polygon) -> Option<Collision> {
+        if self.vertices.len() < 3 || other.vertices.len() < 3 {
+            return None;
+        }
+
+        let (normal, depth) = separating_axis(&self.vertices, &other.vertices)?;
+        let contact_points = clip_contacts(&self.vertices, &other.vertices, normal);
+
+        Some(Collision {
+            normal,
+            depth,
+            contact_points,
+        })
+    }
+}
+
+impl CollideWith<Square> for Polygon {
+    fn collide_with(&self, square: &Square) -> Option<Collision> {
+        self.collide_with(&square_polygon(square))
+    }
+}
+
+impl CollideWith<Point> for Polygon {
+    fn collide_with(&self, point: &Point) -> Option<Collision> {
+        point_circle(point)
+            .collide_with(self)
+            .map(Collision::flipped)
+    }
+}
+
+impl CollideWith<Square> for Square {
+    fn collide_with(&self, other: &Square) -> Option<Collision> {
+        square_polygon(self).collide_with(&square_polygon(other))
+    }
+}
+
+impl CollideWith<Polygon> for Square {
+    fn collide_with(&self, polygon: &Polygon) -> Option<Collision> {
+        square_polygon(self).collide_with(polygon)
+    }
+}
+
+impl CollideWith<Point> for Square {
+    fn collide_with(&self, point: &Point) -> Option<Collision> {
+        square_polygon(self).collide_with(point)
+    }
+}
+
+impl CollideWith<Point> for Point {
+    fn collide_with(&self, other: &Point) -> Option<Collision> {
+        point_circle(self).collide_with(&point_circle(other))
+    }
+}
+
+macro_rules! flipped_collisions {
+    ($($a:ty => $b:ty),* $(,)?) => {
+        $(
+            impl CollideWith<$b> for $a {
+                fn collide_with(&self, other: &$b) -> Option<Collision> {
+                    other.collide_with(self).map(Collision::flipped)
+                }
+            }
+        )*
+    };
+}
+
+flipped_collisions!(
+    Square => Circle,
+    Polygon => Circle,
+    Point => Circle,
+    Point => Square,
+    Point => Polygon,
+);
+
+fn square_polygon(square: &Square) -> Polygon {
+    let h = square.half_size;
+    Polygon {
+        vertices: vec![
+            square.center + Vec2::new(-h, -h),
+            square.center + Vec2::new(h, -h),
+            square.center + Vec2::new(h, h),
+            square.center + Vec2::new(-h, h),
+        ],
+    }
+}
+
+fn point_circle(point: &Point) -> Circle {
+    Circle {
+        center: point.position,
+        radius: 0.0,
+    }
+}
+
+/// Separating axis test. Returns the axis with the least overlap, pointing from `a` to `b`, and
+/// the overlap along it, or nothing if there's a gap.
+fn separating_axis(a: &[Vec2], b: &[Vec2]) -> Option<(Vec2, f32)> {
+    let mut best: Option<(Vec2, f32)> = None;
+
+    for vertices in [a, b] {
+        for i in 0..vertices.len() {
+            let edge = vertices[(i + 1) % vertices.len()] - vertices[i];
+            let Some(axis) = edge.perp().try_normalize() else {
+                continue;
+            };
+
+            let (min_a, max_a) = project(a, axis);
+            let (min_b, max_b) = project(b, axis);
+
+            let forward = max_a - min_b;
+            let backward = max_b - min_a;
+            let overlap = forward.min(backward);
+            if overlap < 0.0 {
+                return None;
+            }
+
+            if best.is_none_or(|(_, depth)| overlap < depth) {
+                let axis = if forward <= backward { axis } else { -axis };
+                best = Some((axis, overlap));
+            }
+        }
+    }
+
+    best
+}
+
+fn project(vertices: &[Vec2], axis: Vec2) -> (f32, f32) {
+    vertices
+        .iter()
+        .map(|vertex| vertex.dot(axis))
+        .fold((f32::MAX, f32::MIN), |(min, max), d| {
+            (min.min(d), max.max(d))
+        })
+}
+
+struct Edge {
+    /// Vertex furthest along the normal the edge was picked for.
+    furthest: Vec2,
+    start: Vec2,
+    end: Vec2,
+}
+
+impl Edge {
+    fn direction(&self) -> Vec2 {
+        (self.end - self.start).normalize_or_zero()
+    }
+}
+
+/// The edge of a convex polygon that faces `normal` the most.
+fn facing_edge(vertices: &[Vec2], normal: Vec2) -> Edge {
+    let n = vertices.len();
+    let index = (0..n)
+        .max_by(|&a, &b| vertices[a].dot(normal).total_cmp(&vertices[b].dot(normal)))
+        .unwrap_or(0);
+
+    let furthest = vertices[index];
+    let next = vertices[(index + 1) % n];
+    let previous = vertices[(index + n - 1) % n];
+
+    // pick whichever neighbour makes the edge more perpendicular to the normal
+    let to_next = (furthest - next).normalize_or_zero();
+    let to_previous = (furthest - previous).normalize_or_zero();
+    if to_previous.dot(normal) <= to_next.dot(normal) {
+        Edge {
+            furthest,
+            start: previous,
+            end: furthest,
+        }
+    } else {
+        Edge {
+            furthest,
+            start: furthest,
+            end: next,
+        }
+    }
+}
+
+/// Finds the contact points of two overlapping convex polygons by clipping the edge of one
+/// against the side planes of the other's.
+fn clip_contacts(a: &[Vec2], b: &[Vec2], normal: Vec2) -> Vec<Vec2> {
+    let edge_a = facing_edge(a, normal);
+    let edge_b = facing_edge(b, -normal);
+
+    let (reference, incident, reference_normal) =
+        if edge_a.direction().dot(normal).abs() <= edge_b.direction().dot(normal).abs() {
+            (edge_a, edge_b, normal)
+        } else {
+            (edge_b, edge_a, -normal)
+        };
+
+    let direction = reference.direction();
+    let points = clip(
+        [incident.start, incident.end],
+        direction,
+        direction.dot(reference.start),
+    );
+    let points = match points.as_slice() {
+        [first, second] => clip([*first, *second], -direction, -direction.dot(reference.end)),
+        _ => points,
+    };
+
+    // the face normal rather than the collision normal, so points are measured against the edge
+    let mut face_normal = direction.perp();
+    if face_normal.dot(reference_normal) < 0.0 {
+        face_normal = -face_normal;
+    }
+    let face = face_normal.dot(reference.furthest);
+
+    let contacts: Vec<Vec2> = points
+        .into_iter()
+        .filter(|point| face_normal.dot(*point) <= face + f32::EPSILON * face.abs().max(1.0))
+        .collect();
+
+    if contacts.is_empty() {
+        vec![incident.furthest]
+    } else {
+        contacts
+    }
+}
+
+/// Keeps the parts of a segment where `normal.dot(point) >= offset`.
+fn clip(segment: [Vec2; 2], normal: Vec2, offset: f32) -> Vec<Vec2> {
+    let [start, end] = segment;
+    let d_start = normal.dot(start) - offset;
+    let d_end = normal.dot(end) - offset;
+
+    let mut points = Vec::with_capacity(2);
+    if d_start >= 0.0 {
+        points.push(start);
+    }
+    if d_end >= 0.0 {
+        points.push(end);
+    }
+    if d_start * d_end < 0.0 {
+        let t = d_start / (d_start - d_end);
+        points.push(start + (end - start) * t);
+    }
+
+    points
+}
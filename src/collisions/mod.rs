@@ -1,29 +1,46 @@
 use bevy_math::Vec2;
 
+pub mod boolean;
+pub mod contact;
 pub mod ray;
+pub mod spatial_grid;
+pub mod sweep;
 
 pub trait IntersectsWith<T> {
     fn intersects_with(&self, other: &T) -> bool;
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Circle {
     pub center: Vec2,
     pub radius: f32,
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Square {
     pub center: Vec2,
     pub half_size: f32,
 }
 
+/// A line segment grown by `radius`, so a rectangle with round ends. Good for characters.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Capsule {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub radius: f32,
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Polygon {
     pub vertices: Vec<Vec2>,
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
     pub position: Vec2,
 }
@@ -35,6 +52,7 @@ impl Point {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AABB2D {
     pub min: Vec2,
     pub max: Vec2,
@@ -68,17 +86,24 @@ impl AABB2D {
     }
 
     pub fn is_visible_in_world(&self) -> bool {
-        let camera = &mut get_state().camera_2d;
-        let (view_min, view_max) = camera.visible_bounds();
+        self.intersects(&world_view_bounds())
+    }
+}
 
-        let margin = window_height().max(window_width()) / camera.scale;
-        let view_bounds = AABB2D::new(
-            view_min - Vec2::splat(margin),
-            view_max + Vec2::splat(margin),
-        );
+/// The part of the world that things need to be in to get drawn, see
+/// [`Camera2D::culling_bounds`](crate::camera::Camera2D::culling_bounds).
+pub fn world_view_bounds() -> AABB2D {
+    get_state().camera_2d.culling_bounds()
+}
 
-        self.intersects(&view_bounds)
-    }
+/// Which of `bounds` are visible in the world. Same as `is_visible_in_world` on each of them, but
+/// only looks at the camera once.
+pub fn cull_aabbs(bounds: &[AABB2D]) -> Vec<bool> {
+    let view = world_view_bounds();
+    bounds
+        .iter()
+        .map(|bounds| bounds.intersects(&view))
+        .collect()
 }
 
 pub trait HasBounds2D {
@@ -115,6 +140,12 @@ impl HasBounds2D for Polygon {
     }
 }
 
+impl HasBounds2D for Capsule {
+    fn bounds(&self) -> AABB2D {
+        AABB2D::new(self.start.min(self.end), self.start.max(self.end)).expand(self.radius)
+    }
+}
+
 impl HasBounds2D for Point {
     fn bounds(&self) -> AABB2D {
         AABB2D::from_center_size(self.position, Vec2::ZERO)
@@ -395,7 +426,7 @@ impl Polygon {
     }
 }
 
-fn closest_point_on_segment(point: Vec2, v1: Vec2, v2: Vec2) -> Vec2 {
+pub(crate) fn closest_point_on_segment(point: Vec2, v1: Vec2, v2: Vec2) -> Vec2 {
     let segment = v2 - v1;
     let segment_length_squared = segment.length_squared();
 
@@ -441,10 +472,7 @@ fn on_segment(p: Vec2, q: Vec2, r: Vec2) -> bool {
     q.x <= p.x.max(r.x) && q.x >= p.x.min(r.x) && q.y <= p.y.max(r.y) && q.y >= p.y.min(r.y)
 }
 
-use crate::{
-    api::{window_height, window_width},
-    get_state, shapes_2d,
-};
+use crate::{get_state, shapes_2d};
 
 pub trait ToCollider<T> {
     fn to_collider(&self) -> T;
@@ -459,6 +487,16 @@ impl ToCollider<Circle> for shapes_2d::Circle {
     }
 }
 
+impl ToCollider<Capsule> for shapes_2d::Capsule2D {
+    fn to_collider(&self) -> Capsule {
+        Capsule {
+            start: self.start,
+            end: self.end,
+            radius: self.radius,
+        }
+    }
+}
+
 impl ToCollider<Polygon> for shapes_2d::Poly {
     fn to_collider(&self) -> Polygon {
         Polygon {
@@ -475,6 +513,23 @@ impl ToCollider<Polygon> for shapes_2d::CustomShape {
     }
 }
 
+impl ToCollider<boolean::PolygonWithHoles> for shapes_2d::ShapeWithHoles {
+    fn to_collider(&self) -> boolean::PolygonWithHoles {
+        boolean::PolygonWithHoles {
+            outline: Polygon {
+                vertices: self.outline.clone(),
+            },
+            holes: self
+                .holes
+                .iter()
+                .map(|hole| Polygon {
+                    vertices: hole.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
 pub fn circle(x: f32, y: f32, r: f32) -> Circle {
     Circle {
         center: Vec2::new(x, y),
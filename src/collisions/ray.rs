@@ -1,6 +1,7 @@
 use bevy_math::Vec2;
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ray {
     pub origin: Vec2,
     pub direction: Vec2,
@@ -0,0 +1,335 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use bevy_math::{IVec2, Vec2};
+
+use super::{AABB2D, ray::Ray};
+
+/// Broadphase for lots of colliders: a uniform grid where each thing is stored in every cell its
+/// bounds touch, so queries only look at things nearby instead of checking everything against
+/// everything. `K` is whatever you use to find your things again, like an index or an entity.
+///
+/// Works best when the cells are a bit bigger than a typical collider. Things that would cover
+/// more than [`MAX_CELLS`](Self::MAX_CELLS) cells, or have infinite bounds, are kept in a list
+/// that every query checks instead.
+///
+/// This is for the shapes in [`collisions`](super). Physics bodies in a
+/// [`PhysicsWorld`](crate::physics::PhysicsWorld) keep using rapier's broadphase, since rapier's
+/// pipeline only takes its own BVH.
+#[derive(Debug, Clone)]
+pub struct SpatialGrid<K = usize> {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<K>>,
+    bounds: HashMap<K, AABB2D>,
+    /// Things too big to put in cells.
+    oversized: Vec<K>,
+    /// Every cell that's ever been used is inside this. Stops rays early.
+    occupied: Option<AABB2D>,
+}
+
+impl<K: Copy + Eq + Hash> SpatialGrid<K> {
+    /// The most cells one thing is stored in.
+    pub const MAX_CELLS: i64 = 1024;
+
+    pub fn new(cell_size: f32) -> Self {
+        assert!(cell_size > 0.0, "SpatialGrid cells need a positive size");
+
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            bounds: HashMap::new(),
+            oversized: Vec::new(),
+            occupied: None,
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounds.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+        self.bounds.clear();
+        self.oversized.clear();
+        self.occupied = None;
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        self.bounds.contains_key(&key)
+    }
+
+    /// The bounds `key` was last inserted or updated with.
+    pub fn bounds(&self, key: K) -> Option<AABB2D> {
+        self.bounds.get(&key).copied()
+    }
+
+    /// Adds `key`, or moves it if it's already in the grid.
+    pub fn insert(&mut self, key: K, bounds: AABB2D) {
+        self.remove(key);
+        self.bounds.insert(key, bounds);
+
+        let Some((min, max)) = self.cell_range(&bounds) else {
+            self.oversized.push(key);
+            return;
+        };
+        for_each_cell(min, max, |cell| {
+            self.cells.entry(cell).or_default().push(key)
+        });
+        self.grow_occupied(bounds);
+    }
+
+    /// Moves `key` to new bounds. Cheap if it stays in the same cells.
+    pub fn update(&mut self, key: K, bounds: AABB2D) {
+        if let Some(old) = self.bounds.get(&key) {
+            let range = self.cell_range(&bounds);
+            if self.cell_range(old) == range {
+                self.bounds.insert(key, bounds);
+                if range.is_some() {
+                    self.grow_occupied(bounds);
+                }
+                return;
+            }
+        }
+
+        self.insert(key, bounds);
+    }
+
+    /// Returns whether `key` was in the grid.
+    pub fn remove(&mut self, key: K) -> bool {
+        let Some(bounds) = self.bounds.remove(&key) else {
+            return false;
+        };
+
+        let Some((min, max)) = self.cell_range(&bounds) else {
+            self.oversized.retain(|k| *k != key);
+            return true;
+        };
+        for_each_cell(min, max, |cell| {
+            if let Some(keys) = self.cells.get_mut(&cell) {
+                keys.retain(|k| *k != key);
+                if keys.is_empty() {
+                    self.cells.remove(&cell);
+                }
+            }
+        });
+
+        true
+    }
+
+    /// Everything whose bounds overlap `area`.
+    pub fn query_aabb(&self, area: &AABB2D) -> Vec<K> {
+        // an area this big would take longer to walk than checking everything
+        let Some((min, max)) = self.cell_range(area) else {
+            return self
+                .bounds
+                .iter()
+                .filter(|(_, bounds)| bounds.intersects(area))
+                .map(|(key, _)| *key)
+                .collect();
+        };
+
+        let mut seen = HashSet::new();
+        let mut found: Vec<K> = self
+            .oversized
+            .iter()
+            .copied()
+            .filter(|key| self.bounds[key].intersects(area))
+            .collect();
+
+        for_each_cell(min, max, |cell| {
+            for key in self.cells.get(&cell).into_iter().flatten() {
+                if seen.insert(*key) && self.bounds[key].intersects(area) {
+                    found.push(*key);
+                }
+            }
+        });
+
+        found
+    }
+
+    /// Everything that's visible in the world. Only looks at the cells on screen, so with lots of
+    /// things off screen it's much faster than culling each of them.
+    pub fn query_visible_in_world(&self) -> Vec<K> {
+        self.query_aabb(&super::world_view_bounds())
+    }
+
+    /// Everything whose bounds contain `point`.
+    pub fn query_point(&self, point: Vec2) -> Vec<K> {
+        self.query_aabb(&AABB2D::new(point, point))
+    }
+
+    /// Everything whose bounds the ray passes through within `max_distance`, closest first.
+    /// Only checks bounds, so follow up with a [`Raycast`](super::ray::Raycast) on the shapes
+    /// themselves for exact hits.
+    pub fn query_ray(&self, ray: &Ray, max_distance: f32) -> Vec<K> {
+        let mut hits: Vec<(K, f32)> = self
+            .oversized
+            .iter()
+            .filter_map(|key| Some((*key, ray_aabb(ray, &self.bounds[key])?)))
+            .filter(|(_, t)| *t <= max_distance)
+            .collect();
+
+        let Some(occupied) = self.occupied else {
+            return hits.into_iter().map(|(key, _)| key).collect();
+        };
+        let (enter, exit) = slabs(ray, &occupied);
+        let start = enter.max(0.0);
+        let max_distance = max_distance.min(exit);
+
+        let mut seen = HashSet::new();
+
+        // no need to walk through empty cells to get to the occupied part
+        let mut cell = self.cell_of(ray.point_at(start));
+        let step = IVec2::new(
+            ray.direction.x.signum() as i32,
+            ray.direction.y.signum() as i32,
+        );
+
+        // distance along the ray to the next cell edge on each axis, and between edges
+        let next_edge = |cell: i32, origin: f32, direction: f32| {
+            if direction == 0.0 {
+                return f32::INFINITY;
+            }
+            let edge = if direction > 0.0 { cell + 1 } else { cell } as f32 * self.cell_size;
+            (edge - origin) / direction
+        };
+        let mut t_max = Vec2::new(
+            next_edge(cell.x, ray.origin.x, ray.direction.x),
+            next_edge(cell.y, ray.origin.y, ray.direction.y),
+        );
+        let t_delta = Vec2::new(
+            (self.cell_size / ray.direction.x).abs(),
+            (self.cell_size / ray.direction.y).abs(),
+        );
+
+        let mut distance = start;
+        while distance <= max_distance && exit >= start {
+            for key in self.cells.get(&cell).into_iter().flatten() {
+                if seen.insert(*key)
+                    && let Some(t) = ray_aabb(ray, &self.bounds[key])
+                    && t <= max_distance
+                {
+                    hits.push((*key, t));
+                }
+            }
+
+            if t_max.x < t_max.y {
+                distance = t_max.x;
+                t_max.x += t_delta.x;
+                cell.x += step.x;
+            } else {
+                distance = t_max.y;
+                t_max.y += t_delta.y;
+                cell.y += step.y;
+            }
+
+            if !distance.is_finite() {
+                break;
+            }
+        }
+
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+        hits.into_iter().map(|(key, _)| key).collect()
+    }
+
+    /// Every pair of things whose bounds overlap, each pair once. Run the exact checks (like
+    /// [`IntersectsWith`](super::IntersectsWith)) on these instead of on every pair.
+    pub fn pairs(&self) -> Vec<(K, K)> {
+        let mut pairs = Vec::new();
+
+        for (cell, keys) in &self.cells {
+            for (i, a) in keys.iter().enumerate() {
+                for b in &keys[i + 1..] {
+                    let bounds_a = &self.bounds[a];
+                    let bounds_b = &self.bounds[b];
+                    if !bounds_a.intersects(bounds_b) {
+                        continue;
+                    }
+
+                    // things sharing several cells are only reported from the first of them
+                    let first_a = cell_range(self.cell_size, bounds_a).0;
+                    let first_b = cell_range(self.cell_size, bounds_b).0;
+                    if first_a.max(first_b) == *cell {
+                        pairs.push((*a, *b));
+                    }
+                }
+            }
+        }
+
+        for (i, a) in self.oversized.iter().enumerate() {
+            let bounds_a = &self.bounds[a];
+            for (b, bounds_b) in &self.bounds {
+                // pairs of oversized things are only reported from the first of them
+                if self.oversized[..=i].contains(b) {
+                    continue;
+                }
+                if bounds_a.intersects(bounds_b) {
+                    pairs.push((*a, *b));
+                }
+            }
+        }
+
+        pairs
+    }
+
+    fn grow_occupied(&mut self, bounds: AABB2D) {
+        self.occupied = Some(match self.occupied {
+            Some(occupied) => {
+                AABB2D::new(occupied.min.min(bounds.min), occupied.max.max(bounds.max))
+            }
+            None => bounds,
+        });
+    }
+
+    fn cell_of(&self, point: Vec2) -> IVec2 {
+        (point / self.cell_size).floor().as_ivec2()
+    }
+
+    /// `None` if `bounds` would cover too many cells to walk, or aren't finite.
+    fn cell_range(&self, bounds: &AABB2D) -> Option<(IVec2, IVec2)> {
+        if !bounds.min.is_finite() || !bounds.max.is_finite() {
+            return None;
+        }
+        let (min, max) = cell_range(self.cell_size, bounds);
+        let size = max.as_i64vec2() - min.as_i64vec2() + 1;
+        (size.x.saturating_mul(size.y) <= Self::MAX_CELLS).then_some((min, max))
+    }
+}
+
+fn cell_range(cell_size: f32, bounds: &AABB2D) -> (IVec2, IVec2) {
+    (
+        (bounds.min / cell_size).floor().as_ivec2(),
+        (bounds.max / cell_size).floor().as_ivec2(),
+    )
+}
+
+fn for_each_cell(min: IVec2, max: IVec2, mut f: impl FnMut(IVec2)) {
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            f(IVec2::new(x, y));
+        }
+    }
+}
+
+/// Distance along the ray to where it enters `bounds`, or 0 if it starts inside.
+fn ray_aabb(ray: &Ray, bounds: &AABB2D) -> Option<f32> {
+    let (t_enter, t_exit) = slabs(ray, bounds);
+    (t_exit >= t_enter.max(0.0)).then_some(t_enter.max(0.0))
+}
+
+/// Distances along the ray to where it enters and leaves `bounds`. It misses them if it leaves
+/// before it enters.
+fn slabs(ray: &Ray, bounds: &AABB2D) -> (f32, f32) {
+    let inverse = ray.direction.recip();
+    let t1 = (bounds.min - ray.origin) * inverse;
+    let t2 = (bounds.max - ray.origin) * inverse;
+    (t1.min(t2).max_element(), t1.max(t2).min_element())
+}
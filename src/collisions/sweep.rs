@@ -0,0 +1,330 @@
+use bevy_math::Vec2;
+
+use super::{
+    AABB2D, Capsule, Circle, Polygon, Square, closest_point_on_segment, line_segments_intersect,
+};
+
+/// When and where a moving shape first touches another one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeOfImpact {
+    /// Seconds into the move when they first touch, from 0 to `dt`. 0 if they already overlap
+    /// and are moving further in.
+    pub time: f32,
+    /// Where they touch, once the moving shape has moved for `time`.
+    pub point: Vec2,
+    /// Normal of the hit surface, pointing back towards the moving shape.
+    pub normal: Vec2,
+}
+
+/// Continuous collision detection: checks the whole path of a shape moving at `velocity` for
+/// `dt` seconds, instead of just where it ends up. Fast things like bullets can't skip through
+/// thin walls this way, no matter the frame rate.
+///
+/// `other` is treated as not moving. For two moving shapes, sweep one with the difference of
+/// their velocities.
+pub trait Sweep<T> {
+    fn sweep(&self, other: &T, velocity: Vec2, dt: f32) -> Option<TimeOfImpact>;
+}
+
+/// Every shape here is a point, segment or polygon grown by a radius.
+struct Rounded {
+    vertices: Vec<Vec2>,
+    radius: f32,
+}
+
+impl Rounded {
+    fn edges(&self) -> Vec<(Vec2, Vec2)> {
+        let n = self.vertices.len();
+        match n {
+            0 => vec![],
+            1 => vec![(self.vertices[0], self.vertices[0])],
+            2 => vec![(self.vertices[0], self.vertices[1])],
+            _ => (0..n)
+                .map(|i| (self.vertices[i], self.vertices[(i + 1) % n]))
+                .collect(),
+        }
+    }
+
+    fn contains_point(&self, point: Vec2) -> bool {
+        self.vertices.len() >= 3
+            && Polygon {
+                vertices: self.vertices.clone(),
+            }
+            .contains_point(point)
+    }
+}
+
+trait ToRounded {
+    fn to_rounded(&self) -> Rounded;
+}
+
+impl ToRounded for Circle {
+    fn to_rounded(&self) -> Rounded {
+        Rounded {
+            vertices: vec![self.center],
+            radius: self.radius,
+        }
+    }
+}
+
+impl ToRounded for Capsule {
+    fn to_rounded(&self) -> Rounded {
+        Rounded {
+            vertices: vec![self.start, self.end],
+            radius: self.radius,
+        }
+    }
+}
+
+impl ToRounded for AABB2D {
+    fn to_rounded(&self) -> Rounded {
+        Rounded {
+            vertices: vec![
+                self.min,
+                Vec2::new(self.max.x, self.min.y),
+                self.max,
+                Vec2::new(self.min.x, self.max.y),
+            ],
+            radius: 0.0,
+        }
+    }
+}
+
+impl ToRounded for Square {
+    fn to_rounded(&self) -> Rounded {
+        AABB2D::from_center_size(self.center, Vec2::splat(self.half_size * 2.0)).to_rounded()
+    }
+}
+
+impl ToRounded for Polygon {
+    fn to_rounded(&self) -> Rounded {
+        Rounded {
+            vertices: self.vertices.clone(),
+            radius: 0.0,
+        }
+    }
+}
+
+macro_rules! impl_sweep {
+    ($($moving:ty),*; $targets:tt) => {
+        $(impl_sweep!(@targets $moving, $targets);)*
+    };
+    (@targets $moving:ty, [$($target:ty),*]) => {
+        $(
+            impl Sweep<$target> for $moving {
+                fn sweep(&self, other: &$target, velocity: Vec2, dt: f32) -> Option<TimeOfImpact> {
+                    sweep_rounded(&self.to_rounded(), &other.to_rounded(), velocity, dt)
+                }
+            }
+        )*
+    };
+}
+
+impl_sweep!(Circle, AABB2D, Capsule; [Circle, Square, Polygon, AABB2D, Capsule]);
+
+fn sweep_rounded(
+    moving: &Rounded,
+    target: &Rounded,
+    velocity: Vec2,
+    dt: f32,
+) -> Option<TimeOfImpact> {
+    if moving.vertices.is_empty() || target.vertices.is_empty() {
+        return None;
+    }
+
+    let radius = moving.radius + target.radius;
+    let motion = velocity * dt;
+
+    if let Some((point, normal)) = overlap(moving, target, motion) {
+        return Some(TimeOfImpact {
+            time: 0.0,
+            point,
+            normal,
+        });
+    }
+
+    let moving_edges = moving.edges();
+    let target_edges = target.edges();
+    let mut best: Option<(f32, Vec2, Vec2)> = None;
+    let mut consider = |hit: Option<(f32, Vec2, Vec2)>| {
+        if let Some(hit) = hit
+            && best.is_none_or(|(t, _, _)| hit.0 < t)
+        {
+            best = Some(hit);
+        }
+    };
+
+    // the first touch is always a corner of one shape reaching a side of the other
+    for &vertex in &moving.vertices {
+        for &(start, end) in &target_edges {
+            consider(
+                ray_capsule(vertex, motion, start, end, radius).map(|(t, normal)| {
+                    let point = vertex + motion * t - normal * moving.radius;
+                    (t, point, normal)
+                }),
+            );
+        }
+    }
+
+    for &vertex in &target.vertices {
+        for &(start, end) in &moving_edges {
+            consider(
+                ray_capsule(vertex, -motion, start, end, radius).map(|(t, normal)| {
+                    let point = vertex - normal * target.radius;
+                    (t, point, -normal)
+                }),
+            );
+        }
+    }
+
+    best.map(|(t, point, normal)| TimeOfImpact {
+        time: t * dt,
+        point,
+        normal,
+    })
+}
+
+/// If the shapes already overlap and the move would take them further in, where and which way
+/// to push them apart.
+fn overlap(moving: &Rounded, target: &Rounded, motion: Vec2) -> Option<(Vec2, Vec2)> {
+    let fallback_normal = (-motion).normalize_or_zero();
+
+    let inside = moving
+        .vertices
+        .iter()
+        .copied()
+        .find(|vertex| target.contains_point(*vertex))
+        .or_else(|| {
+            target
+                .vertices
+                .iter()
+                .copied()
+                .find(|vertex| moving.contains_point(*vertex))
+        });
+    if let Some(point) = inside {
+        return Some((point, fallback_normal));
+    }
+
+    let radius = moving.radius + target.radius;
+    let mut closest: Option<(f32, Vec2, Vec2)> = None;
+    for (a1, a2) in moving.edges() {
+        for (b1, b2) in target.edges() {
+            let (distance, on_moving, on_target) = segment_distance(a1, a2, b1, b2);
+            if closest.is_none_or(|(d, _, _)| distance < d) {
+                closest = Some((distance, on_moving, on_target));
+            }
+        }
+    }
+
+    let (distance, on_moving, on_target) = closest?;
+    if distance >= radius {
+        return None;
+    }
+
+    let normal = (on_moving - on_target)
+        .try_normalize()
+        .unwrap_or(fallback_normal);
+    // let things that are stuck together move apart
+    if normal.dot(motion) > 0.0 {
+        return None;
+    }
+
+    Some((on_target + normal * target.radius, normal))
+}
+
+/// Distance between two segments, and the closest point on each.
+fn segment_distance(a1: Vec2, a2: Vec2, b1: Vec2, b2: Vec2) -> (f32, Vec2, Vec2) {
+    if a1 != a2 && b1 != b2 && line_segments_intersect(a1, a2, b1, b2) {
+        let point = closest_point_on_segment(a1, b1, b2);
+        return (0.0, point, point);
+    }
+
+    [
+        (a1, closest_point_on_segment(a1, b1, b2)),
+        (a2, closest_point_on_segment(a2, b1, b2)),
+    ]
+    .into_iter()
+    .chain([
+        (closest_point_on_segment(b1, a1, a2), b1),
+        (closest_point_on_segment(b2, a1, a2), b2),
+    ])
+    .map(|(on_a, on_b)| (on_a.distance(on_b), on_a, on_b))
+    .min_by(|a, b| a.0.total_cmp(&b.0))
+    .unwrap()
+}
+
+/// First time in 0..=1 that `origin + motion * t` gets within `radius` of the segment, and the
+/// surface normal there.
+fn ray_capsule(
+    origin: Vec2,
+    motion: Vec2,
+    start: Vec2,
+    end: Vec2,
+    radius: f32,
+) -> Option<(f32, Vec2)> {
+    let mut best: Option<(f32, Vec2)> = None;
+    let mut consider = |hit: Option<(f32, Vec2)>| {
+        if let Some(hit) = hit
+            && best.is_none_or(|(t, _)| hit.0 < t)
+        {
+            best = Some(hit);
+        }
+    };
+
+    if radius > 0.0 {
+        consider(ray_circle(origin, motion, start, radius));
+        consider(ray_circle(origin, motion, end, radius));
+    }
+
+    if let Some(normal) = (end - start).perp().try_normalize() {
+        for side in [normal, -normal] {
+            if motion.dot(side) < 0.0 {
+                let offset = side * radius;
+                consider(
+                    ray_segment(origin, motion, start + offset, end + offset).map(|t| (t, side)),
+                );
+            }
+        }
+    }
+
+    best
+}
+
+fn ray_circle(origin: Vec2, motion: Vec2, center: Vec2, radius: f32) -> Option<(f32, Vec2)> {
+    let to_origin = origin - center;
+    let a = motion.length_squared();
+    let b = to_origin.dot(motion);
+    let c = to_origin.length_squared() - radius * radius;
+
+    // moving away, or not moving
+    if a == 0.0 || b >= 0.0 {
+        return None;
+    }
+
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let t = (-b - discriminant.sqrt()) / a;
+    if !(0.0..=1.0).contains(&t) {
+        return None;
+    }
+
+    let normal = (origin + motion * t - center).normalize_or_zero();
+    Some((t, normal))
+}
+
+fn ray_segment(origin: Vec2, motion: Vec2, start: Vec2, end: Vec2) -> Option<f32> {
+    let segment = end - start;
+    let denominator = motion.perp_dot(segment);
+    if denominator.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let to_start = start - origin;
+    let t = to_start.perp_dot(segment) / denominator;
+    let u = to_start.perp_dot(motion) / denominator;
+
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
+}
@@ -0,0 +1,72 @@
+use bevy_math::{Mat4, Vec3, Vec4};
+
+use super::{AABB3D, IntersectsWith, OBB, Plane, Sphere};
+
+/// The space a camera can see, as six planes facing inwards. Anything outside it is off screen
+/// and doesn't need drawing.
+///
+/// Tests against it are conservative: something reported as intersecting might still be just
+/// outside a corner, but something reported as outside definitely is.
+#[derive(Debug, Clone, Copy)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near and far.
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// From a projection or view-projection matrix, with depth going from 0 to 1 like the ones
+    /// made by [`Camera3D`](crate::gfx::Camera3D).
+    pub fn from_view_proj(view_proj: Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_proj.row(i));
+        let plane = |row: Vec4| Plane::new(row.truncate(), -row.w);
+
+        Self {
+            planes: [
+                plane(w + x),
+                plane(w - x),
+                plane(w + y),
+                plane(w - y),
+                plane(z),
+                plane(w - z),
+            ],
+        }
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(point) >= 0.0)
+    }
+}
+
+impl IntersectsWith<Vec3> for Frustum {
+    fn intersects_with(&self, point: &Vec3) -> bool {
+        self.contains_point(*point)
+    }
+}
+
+impl IntersectsWith<Sphere> for Frustum {
+    fn intersects_with(&self, sphere: &Sphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(sphere.center) >= -sphere.radius)
+    }
+}
+
+impl IntersectsWith<AABB3D> for Frustum {
+    fn intersects_with(&self, aabb: &AABB3D) -> bool {
+        let center = aabb.center();
+        let half_extents = aabb.half_extents();
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(center) >= -half_extents.dot(plane.normal.abs()))
+    }
+}
+
+impl IntersectsWith<OBB> for Frustum {
+    fn intersects_with(&self, obb: &OBB) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(obb.center) >= -obb.projected_radius(plane.normal))
+    }
+}
@@ -0,0 +1,431 @@
+//! The 3D side of [`collisions`](crate::collisions): simple shapes that can be checked against
+//! each other, hit with rays, and culled against the camera's [`Frustum`].
+
+use bevy_math::{Quat, Vec3};
+
+pub use crate::collisions::IntersectsWith;
+pub use crate::shapes_3d::{AABB3D, HasBounds3D, Ray3D};
+
+pub mod frustum;
+pub mod ray;
+
+pub use frustum::Frustum;
+pub use ray::{Raycast3D, RaycastHit3D};
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl Sphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+}
+
+/// A box that can be rotated, unlike an [`AABB3D`].
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OBB {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    pub rotation: Quat,
+}
+
+impl OBB {
+    pub fn new(center: Vec3, half_extents: Vec3, rotation: Quat) -> Self {
+        Self {
+            center,
+            half_extents,
+            rotation,
+        }
+    }
+
+    /// The box's local X, Y and Z axes in world space.
+    pub fn axes(&self) -> [Vec3; 3] {
+        [
+            self.rotation * Vec3::X,
+            self.rotation * Vec3::Y,
+            self.rotation * Vec3::Z,
+        ]
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        let local = self.local_point(point);
+        local.abs().cmple(self.half_extents).all()
+    }
+
+    pub fn closest_point(&self, point: Vec3) -> Vec3 {
+        let local = self
+            .local_point(point)
+            .clamp(-self.half_extents, self.half_extents);
+        self.center + self.rotation * local
+    }
+
+    fn local_point(&self, point: Vec3) -> Vec3 {
+        self.rotation.inverse() * (point - self.center)
+    }
+
+    /// Half the length of the box's shadow on `axis`.
+    fn projected_radius(&self, axis: Vec3) -> f32 {
+        let [x, y, z] = self.axes();
+        self.half_extents.x * x.dot(axis).abs()
+            + self.half_extents.y * y.dot(axis).abs()
+            + self.half_extents.z * z.dot(axis).abs()
+    }
+}
+
+impl From<AABB3D> for OBB {
+    fn from(aabb: AABB3D) -> Self {
+        Self::new(
+            (aabb.min + aabb.max) * 0.5,
+            (aabb.max - aabb.min) * 0.5,
+            Quat::IDENTITY,
+        )
+    }
+}
+
+/// A line segment grown by `radius`. Good for characters and limbs.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Capsule3D {
+    pub start: Vec3,
+    pub end: Vec3,
+    pub radius: f32,
+}
+
+impl Capsule3D {
+    pub fn new(start: Vec3, end: Vec3, radius: f32) -> Self {
+        Self { start, end, radius }
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        closest_point_on_segment(point, self.start, self.end).distance_squared(point)
+            <= self.radius * self.radius
+    }
+}
+
+/// An infinite flat surface: every point where `normal.dot(point) == distance`. The side
+/// `normal` points towards is in front of it.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Plane {
+    pub normal: Vec3,
+    pub distance: f32,
+}
+
+impl Plane {
+    /// `normal` doesn't need to be normalized.
+    pub fn new(normal: Vec3, distance: f32) -> Self {
+        let length = normal.length();
+        Self {
+            normal: normal / length,
+            distance: distance / length,
+        }
+    }
+
+    pub fn from_point_normal(point: Vec3, normal: Vec3) -> Self {
+        let normal = normal.normalize();
+        Self {
+            normal,
+            distance: normal.dot(point),
+        }
+    }
+
+    /// How far in front of the plane `point` is. Negative when it's behind.
+    pub fn signed_distance(&self, point: Vec3) -> f32 {
+        self.normal.dot(point) - self.distance
+    }
+
+    pub fn closest_point(&self, point: Vec3) -> Vec3 {
+        point - self.normal * self.signed_distance(point)
+    }
+}
+
+impl AABB3D {
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn closest_point(&self, point: Vec3) -> Vec3 {
+        point.clamp(self.min, self.max)
+    }
+}
+
+impl Sphere {
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.center.distance_squared(point) <= self.radius * self.radius
+    }
+}
+
+impl HasBounds3D for Sphere {
+    fn bounds(&self) -> AABB3D {
+        AABB3D::from_center_size(self.center, Vec3::splat(self.radius * 2.0))
+    }
+}
+
+impl HasBounds3D for OBB {
+    fn bounds(&self) -> AABB3D {
+        let extents = Vec3::new(
+            self.projected_radius(Vec3::X),
+            self.projected_radius(Vec3::Y),
+            self.projected_radius(Vec3::Z),
+        );
+        AABB3D::new(self.center - extents, self.center + extents)
+    }
+}
+
+impl HasBounds3D for Capsule3D {
+    fn bounds(&self) -> AABB3D {
+        AABB3D::new(self.start.min(self.end), self.start.max(self.end)).expand(self.radius)
+    }
+}
+
+impl IntersectsWith<Sphere> for Sphere {
+    fn intersects_with(&self, other: &Sphere) -> bool {
+        let radius_sum = self.radius + other.radius;
+        self.center.distance_squared(other.center) <= radius_sum * radius_sum
+    }
+}
+
+impl IntersectsWith<AABB3D> for Sphere {
+    fn intersects_with(&self, aabb: &AABB3D) -> bool {
+        self.contains_point(aabb.closest_point(self.center))
+    }
+}
+
+impl IntersectsWith<OBB> for Sphere {
+    fn intersects_with(&self, obb: &OBB) -> bool {
+        self.contains_point(obb.closest_point(self.center))
+    }
+}
+
+impl IntersectsWith<Capsule3D> for Sphere {
+    fn intersects_with(&self, capsule: &Capsule3D) -> bool {
+        let closest = closest_point_on_segment(self.center, capsule.start, capsule.end);
+        let radius_sum = self.radius + capsule.radius;
+        self.center.distance_squared(closest) <= radius_sum * radius_sum
+    }
+}
+
+impl IntersectsWith<Plane> for Sphere {
+    fn intersects_with(&self, plane: &Plane) -> bool {
+        plane.signed_distance(self.center).abs() <= self.radius
+    }
+}
+
+impl IntersectsWith<Vec3> for Sphere {
+    fn intersects_with(&self, point: &Vec3) -> bool {
+        self.contains_point(*point)
+    }
+}
+
+impl IntersectsWith<AABB3D> for AABB3D {
+    fn intersects_with(&self, other: &AABB3D) -> bool {
+        self.intersects(other)
+    }
+}
+
+impl IntersectsWith<OBB> for AABB3D {
+    fn intersects_with(&self, obb: &OBB) -> bool {
+        OBB::from(*self).intersects_with(obb)
+    }
+}
+
+impl IntersectsWith<Capsule3D> for AABB3D {
+    fn intersects_with(&self, capsule: &Capsule3D) -> bool {
+        segment_distance_to(capsule.start, capsule.end, |point| {
+            self.closest_point(point)
+        }) <= capsule.radius
+    }
+}
+
+impl IntersectsWith<Plane> for AABB3D {
+    fn intersects_with(&self, plane: &Plane) -> bool {
+        let radius = self.half_extents().dot(plane.normal.abs());
+        plane.signed_distance(self.center()).abs() <= radius
+    }
+}
+
+impl IntersectsWith<Vec3> for AABB3D {
+    fn intersects_with(&self, point: &Vec3) -> bool {
+        self.contains_point(*point)
+    }
+}
+
+impl IntersectsWith<OBB> for OBB {
+    fn intersects_with(&self, other: &OBB) -> bool {
+        let a = self.axes();
+        let b = other.axes();
+        let offset = other.center - self.center;
+
+        // separating axis test: the box faces, plus every pair of edges
+        let edge_axes = a
+            .iter()
+            .flat_map(|a| b.iter().map(move |b| a.cross(*b)))
+            .filter_map(Vec3::try_normalize);
+
+        a.into_iter().chain(b).chain(edge_axes).all(|axis| {
+            offset.dot(axis).abs() <= self.projected_radius(axis) + other.projected_radius(axis)
+        })
+    }
+}
+
+impl IntersectsWith<Capsule3D> for OBB {
+    fn intersects_with(&self, capsule: &Capsule3D) -> bool {
+        segment_distance_to(capsule.start, capsule.end, |point| {
+            self.closest_point(point)
+        }) <= capsule.radius
+    }
+}
+
+impl IntersectsWith<Plane> for OBB {
+    fn intersects_with(&self, plane: &Plane) -> bool {
+        plane.signed_distance(self.center).abs() <= self.projected_radius(plane.normal)
+    }
+}
+
+impl IntersectsWith<Vec3> for OBB {
+    fn intersects_with(&self, point: &Vec3) -> bool {
+        self.contains_point(*point)
+    }
+}
+
+impl IntersectsWith<Capsule3D> for Capsule3D {
+    fn intersects_with(&self, other: &Capsule3D) -> bool {
+        let (a, b) = closest_points_on_segments(self.start, self.end, other.start, other.end);
+        let radius_sum = self.radius + other.radius;
+        a.distance_squared(b) <= radius_sum * radius_sum
+    }
+}
+
+impl IntersectsWith<Plane> for Capsule3D {
+    fn intersects_with(&self, plane: &Plane) -> bool {
+        let start = plane.signed_distance(self.start);
+        let end = plane.signed_distance(self.end);
+        start.min(end) <= self.radius && start.max(end) >= -self.radius
+    }
+}
+
+impl IntersectsWith<Vec3> for Capsule3D {
+    fn intersects_with(&self, point: &Vec3) -> bool {
+        self.contains_point(*point)
+    }
+}
+
+macro_rules! flipped_intersections {
+    ($($a:ty => $b:ty),* $(,)?) => {
+        $(
+            impl IntersectsWith<$b> for $a {
+                fn intersects_with(&self, other: &$b) -> bool {
+                    other.intersects_with(self)
+                }
+            }
+        )*
+    };
+}
+
+flipped_intersections!(
+    AABB3D => Sphere,
+    OBB => Sphere,
+    OBB => AABB3D,
+    Capsule3D => Sphere,
+    Capsule3D => AABB3D,
+    Capsule3D => OBB,
+    Plane => Sphere,
+    Plane => AABB3D,
+    Plane => OBB,
+    Plane => Capsule3D,
+    Vec3 => Sphere,
+    Vec3 => AABB3D,
+    Vec3 => OBB,
+    Vec3 => Capsule3D,
+);
+
+pub(crate) fn closest_point_on_segment(point: Vec3, start: Vec3, end: Vec3) -> Vec3 {
+    let segment = end - start;
+    let length_squared = segment.length_squared();
+    if length_squared == 0.0 {
+        return start;
+    }
+
+    let t = ((point - start).dot(segment) / length_squared).clamp(0.0, 1.0);
+    start + segment * t
+}
+
+/// The closest pair of points between two segments, one on each.
+fn closest_points_on_segments(a1: Vec3, a2: Vec3, b1: Vec3, b2: Vec3) -> (Vec3, Vec3) {
+    let d1 = a2 - a1;
+    let d2 = b2 - b1;
+    let r = a1 - b1;
+    let a = d1.length_squared();
+    let e = d2.length_squared();
+    let f = d2.dot(r);
+
+    if a <= f32::EPSILON && e <= f32::EPSILON {
+        return (a1, b1);
+    }
+    if a <= f32::EPSILON {
+        return (a1, closest_point_on_segment(a1, b1, b2));
+    }
+    if e <= f32::EPSILON {
+        return (closest_point_on_segment(b1, a1, a2), b1);
+    }
+
+    let c = d1.dot(r);
+    let b = d1.dot(d2);
+    let denominator = a * e - b * b;
+
+    // parallel segments have no single closest pair, so any point on the first will do
+    let mut s = if denominator > f32::EPSILON {
+        ((b * f - c * e) / denominator).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let mut t = (b * s + f) / e;
+
+    if t < 0.0 {
+        t = 0.0;
+        s = (-c / a).clamp(0.0, 1.0);
+    } else if t > 1.0 {
+        t = 1.0;
+        s = ((b - c) / a).clamp(0.0, 1.0);
+    }
+
+    (a1 + d1 * s, b1 + d2 * t)
+}
+
+/// Shortest distance from a segment to a convex shape, given the shape's closest point
+/// function. The distance is convex along the segment, so a golden section search finds it.
+fn segment_distance_to(start: Vec3, end: Vec3, closest_point: impl Fn(Vec3) -> Vec3) -> f32 {
+    let distance = |t: f32| {
+        let point = start.lerp(end, t);
+        point.distance(closest_point(point))
+    };
+
+    let ratio = (5.0_f32.sqrt() - 1.0) * 0.5;
+    let (mut low, mut high) = (0.0_f32, 1.0_f32);
+    for _ in 0..32 {
+        let a = high - (high - low) * ratio;
+        let b = low + (high - low) * ratio;
+        if distance(a) < distance(b) {
+            high = b;
+        } else {
+            low = a;
+        }
+    }
+
+    distance((low + high) * 0.5)
+        .min(distance(0.0))
+        .min(distance(1.0))
+}
@@ -0,0 +1,185 @@
+use bevy_math::Vec3;
+
+use super::{AABB3D, Capsule3D, OBB, Plane, Ray3D, Sphere, closest_point_on_segment};
+
+#[derive(Debug, Clone, Copy)]
+pub struct RaycastHit3D {
+    pub point: Vec3,
+    pub distance: f32,
+    pub normal: Vec3,
+}
+
+/// Rays that start inside a shape hit it straight away, at a distance of 0 with the normal
+/// pointing back along the ray.
+pub trait Raycast3D {
+    fn intersect_ray(&self, ray: &Ray3D) -> Option<RaycastHit3D>;
+
+    fn intersect_ray_max(&self, ray: &Ray3D, max_distance: f32) -> Option<RaycastHit3D> {
+        self.intersect_ray(ray)
+            .filter(|hit| hit.distance <= max_distance)
+    }
+}
+
+impl Raycast3D for Sphere {
+    fn intersect_ray(&self, ray: &Ray3D) -> Option<RaycastHit3D> {
+        if self.contains_point(ray.origin) {
+            return Some(inside(ray));
+        }
+
+        let t = ray_sphere(ray, self.center, self.radius)?;
+        let point = ray.point_at(t);
+        Some(RaycastHit3D {
+            point,
+            distance: t,
+            normal: (point - self.center).normalize_or_zero(),
+        })
+    }
+}
+
+impl Raycast3D for AABB3D {
+    fn intersect_ray(&self, ray: &Ray3D) -> Option<RaycastHit3D> {
+        if self.contains_point(ray.origin) {
+            return Some(inside(ray));
+        }
+
+        let t = self.raycast(ray)?;
+
+        // the axis the ray enters through is the one whose slab it enters last
+        let inv_dir = ray.direction.recip();
+        let t_enter = ((self.min - ray.origin) * inv_dir).min((self.max - ray.origin) * inv_dir);
+        let axis = if t_enter.x >= t_enter.y && t_enter.x >= t_enter.z {
+            Vec3::X
+        } else if t_enter.y >= t_enter.z {
+            Vec3::Y
+        } else {
+            Vec3::Z
+        };
+        let normal = -axis * ray.direction.dot(axis).signum();
+
+        Some(RaycastHit3D {
+            point: ray.point_at(t),
+            distance: t,
+            normal,
+        })
+    }
+}
+
+impl Raycast3D for OBB {
+    fn intersect_ray(&self, ray: &Ray3D) -> Option<RaycastHit3D> {
+        // in the box's own space it's just an AABB
+        let inverse = self.rotation.inverse();
+        let local_ray = Ray3D {
+            origin: inverse * (ray.origin - self.center),
+            direction: inverse * ray.direction,
+        };
+        let hit = AABB3D::new(-self.half_extents, self.half_extents).intersect_ray(&local_ray)?;
+
+        Some(RaycastHit3D {
+            point: ray.point_at(hit.distance),
+            distance: hit.distance,
+            normal: self.rotation * hit.normal,
+        })
+    }
+}
+
+impl Raycast3D for Capsule3D {
+    fn intersect_ray(&self, ray: &Ray3D) -> Option<RaycastHit3D> {
+        if self.contains_point(ray.origin) {
+            return Some(inside(ray));
+        }
+
+        let t = [
+            ray_sphere(ray, self.start, self.radius),
+            ray_sphere(ray, self.end, self.radius),
+            ray_cylinder(ray, self.start, self.end, self.radius),
+        ]
+        .into_iter()
+        .flatten()
+        .min_by(f32::total_cmp)?;
+
+        let point = ray.point_at(t);
+        let axis_point = closest_point_on_segment(point, self.start, self.end);
+        Some(RaycastHit3D {
+            point,
+            distance: t,
+            normal: (point - axis_point).normalize_or_zero(),
+        })
+    }
+}
+
+/// Hits the plane from either side. The normal faces the side the ray came from.
+impl Raycast3D for Plane {
+    fn intersect_ray(&self, ray: &Ray3D) -> Option<RaycastHit3D> {
+        let denominator = self.normal.dot(ray.direction);
+        if denominator.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let t = -self.signed_distance(ray.origin) / denominator;
+        if t < 0.0 {
+            return None;
+        }
+
+        Some(RaycastHit3D {
+            point: ray.point_at(t),
+            distance: t,
+            normal: if denominator < 0.0 {
+                self.normal
+            } else {
+                -self.normal
+            },
+        })
+    }
+}
+
+fn inside(ray: &Ray3D) -> RaycastHit3D {
+    RaycastHit3D {
+        point: ray.origin,
+        distance: 0.0,
+        normal: -ray.direction,
+    }
+}
+
+/// Distance to where the ray enters the sphere, if it starts outside it.
+fn ray_sphere(ray: &Ray3D, center: Vec3, radius: f32) -> Option<f32> {
+    let oc = ray.origin - center;
+    let b = oc.dot(ray.direction);
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let t = -b - discriminant.sqrt();
+    (t >= 0.0).then_some(t)
+}
+
+/// Distance to where the ray enters the sides of the cylinder between `start` and `end`, not
+/// counting its ends.
+fn ray_cylinder(ray: &Ray3D, start: Vec3, end: Vec3, radius: f32) -> Option<f32> {
+    let axis = (end - start).try_normalize()?;
+    let oc = ray.origin - start;
+
+    // only the parts of the ray and offset that go across the axis matter
+    let direction = ray.direction - axis * ray.direction.dot(axis);
+    let offset = oc - axis * oc.dot(axis);
+
+    let a = direction.length_squared();
+    if a < f32::EPSILON {
+        return None;
+    }
+    let b = offset.dot(direction);
+    let c = offset.length_squared() - radius * radius;
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let t = (-b - discriminant.sqrt()) / a;
+    if t < 0.0 {
+        return None;
+    }
+
+    let along = (ray.point_at(t) - start).dot(axis);
+    (0.0..=start.distance(end)).contains(&along).then_some(t)
+}
@@ -2,10 +2,12 @@ use bevy_math::Vec4;
 use palette::{Hsl, IntoColor, LinSrgb, Oklch, Srgb};
 
 pub mod schemes;
+pub mod theme;
 pub mod u8;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
     pub r: f32,
     pub g: f32,
@@ -0,0 +1,135 @@
+use egui_glium::egui_winit::egui::{self, Stroke, Visuals};
+
+use super::Color;
+use crate::get_state;
+
+/// Colors by what they're used for instead of what they look like, so a whole app can be
+/// re-skinned by swapping the theme.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub dark: bool,
+
+    pub background: Color,
+    pub surface: Color,
+    pub surface_alt: Color,
+    pub border: Color,
+
+    pub text: Color,
+    pub text_muted: Color,
+
+    pub primary: Color,
+    pub secondary: Color,
+    pub accent: Color,
+
+    pub success: Color,
+    pub warning: Color,
+    pub danger: Color,
+}
+
+impl Theme {
+    pub const DARK: Self = Self {
+        dark: true,
+
+        background: Color::NEUTRAL_950,
+        surface: Color::NEUTRAL_900,
+        surface_alt: Color::NEUTRAL_800,
+        border: Color::NEUTRAL_700,
+
+        text: Color::NEUTRAL_100,
+        text_muted: Color::NEUTRAL_400,
+
+        primary: Color::BLUE_500,
+        secondary: Color::SLATE_500,
+        accent: Color::AMBER_400,
+
+        success: Color::GREEN_500,
+        warning: Color::YELLOW_500,
+        danger: Color::RED_500,
+    };
+
+    pub const LIGHT: Self = Self {
+        dark: false,
+
+        background: Color::NEUTRAL_50,
+        surface: Color::WHITE,
+        surface_alt: Color::NEUTRAL_100,
+        border: Color::NEUTRAL_300,
+
+        text: Color::NEUTRAL_900,
+        text_muted: Color::NEUTRAL_500,
+
+        primary: Color::BLUE_600,
+        secondary: Color::SLATE_600,
+        accent: Color::AMBER_500,
+
+        success: Color::GREEN_600,
+        warning: Color::YELLOW_600,
+        danger: Color::RED_600,
+    };
+
+    pub fn egui_visuals(&self) -> Visuals {
+        let mut visuals = if self.dark {
+            Visuals::dark()
+        } else {
+            Visuals::light()
+        };
+        let stroke = |color: Color| Stroke::new(1.0_f32, color);
+
+        visuals.override_text_color = Some(self.text.into());
+        visuals.panel_fill = self.surface.into();
+        visuals.window_fill = self.surface.into();
+        visuals.window_stroke = stroke(self.border);
+        visuals.extreme_bg_color = self.background.into();
+        visuals.faint_bg_color = self.surface_alt.into();
+        visuals.code_bg_color = self.surface_alt.into();
+        visuals.hyperlink_color = self.primary.into();
+        visuals.warn_fg_color = self.warning.into();
+        visuals.error_fg_color = self.danger.into();
+        visuals.selection.bg_fill = self.primary.with_alpha(0.5).into();
+        visuals.selection.stroke = stroke(self.text);
+
+        let widgets = &mut visuals.widgets;
+        widgets.noninteractive.bg_fill = self.surface.into();
+        widgets.noninteractive.weak_bg_fill = self.surface.into();
+        widgets.noninteractive.bg_stroke = stroke(self.border);
+        widgets.noninteractive.fg_stroke = stroke(self.text_muted);
+
+        for (widget, fill) in [
+            (&mut widgets.inactive, self.surface_alt),
+            (&mut widgets.hovered, self.border),
+            (&mut widgets.active, self.primary),
+            (&mut widgets.open, self.surface_alt),
+        ] {
+            widget.bg_fill = fill.into();
+            widget.weak_bg_fill = fill.into();
+            widget.fg_stroke = stroke(self.text);
+        }
+        widgets.hovered.bg_stroke = stroke(self.primary);
+
+        visuals
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::DARK
+    }
+}
+
+impl From<Color> for egui::Color32 {
+    fn from(color: Color) -> Self {
+        egui::Rgba::from_rgba_unmultiplied(color.r, color.g, color.b, color.a).into()
+    }
+}
+
+/// Sets the theme used by the engine's UI and debug overlay. Takes effect on the next
+/// `run_ui` call.
+pub fn set_theme(theme: Theme) {
+    let state = get_state();
+    state.theme = theme;
+    state.theme_changed = true;
+}
+
+pub fn theme() -> &'static Theme {
+    &get_state().theme
+}
@@ -1,6 +1,7 @@
 use std::{fmt::Debug, hash::Hash};
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(C)]
 pub struct Rgba {
     pub r: u8,
@@ -422,6 +423,12 @@ impl Pixel {
     pub const ROSE_950: Pixel = Pixel::from_rgb(77, 2, 24);
 }
 
+impl From<super::Color> for Pixel {
+    fn from(color: super::Color) -> Self {
+        Self::from_rgba_f32(color.r, color.g, color.b, color.a)
+    }
+}
+
 impl From<image::Rgba<u8>> for Pixel {
     fn from(value: image::Rgba<u8>) -> Self {
         Self { raw: value.0 }
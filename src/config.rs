@@ -1,5 +1,33 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime},
+};
+
+use anyhow::{Context, bail};
+use bevy_math::UVec2;
 use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
 
+use glium::winit::dpi::PhysicalSize;
+
+use crate::{
+    color::Color,
+    events::{ConfigReloaded, emit},
+    get_state,
+    input_handling::Button,
+    post_processing::ColorFilter,
+    storage::SaveOptions,
+};
+
+mod file;
+
+use file::{
+    AccessibilitySection, AudioSection, ClearColor, ConfigFile, GraphicsSection, Named, OrNone,
+    WindowSection,
+};
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct EngineConfig {
     // applies when loading a texture, not drawing
     //
@@ -7,8 +35,42 @@ pub struct EngineConfig {
     //
     // setting this to false will sometimes make images look crisper
     pub use_mipmaps: bool,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impls::magnify_filter"))]
     pub default_magnify_filter: MagnifySamplerFilter,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impls::minify_filter"))]
     pub default_minify_filter: MinifySamplerFilter,
+    // waits for the monitor before showing each frame, which stops tearing and caps the frame
+    // rate at the refresh rate
+    //
+    // only read when the window is made, see `init_with_config`
+    pub vsync: bool,
+    // sleeps at the end of each frame to stay at or under this many frames per second, to save
+    // CPU/GPU when there's no need to go faster. `None` for no limit
+    pub target_fps: Option<f32>,
+    // where `storage::save` puts things, and how
+    pub saves: SaveOptions,
+    // in pixels. `None` lets the platform pick
+    pub window_size: Option<UVec2>,
+    // what the screen is cleared to each frame, unless `clear_screen` is called
+    pub clear_color: Color,
+    // the engine doesn't play anything by itself, so these are for the game to multiply its
+    // sounds by. music and effects are also multiplied by the master volume, see
+    // `music_volume()` and `effects_volume()`
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub effects_volume: f32,
+    // buttons by name, see `bind_named`
+    pub key_binds: BTreeMap<String, Button>,
+    // the file this was loaded from, and where `save_config` writes to
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub path: Option<PathBuf>,
+    // reloads the config whenever its file changes. on by default in debug builds
+    pub hot_reload: bool,
+    // multiplies the size of text and UI drawn with DPI scaling, and egui, for players who need
+    // it bigger. see `ui_scaling()`
+    pub ui_scale: f32,
+    // applied to everything drawn to the screen each frame, apart from gizmos and egui
+    pub color_filter: Option<ColorFilter>,
 }
 
 impl Default for EngineConfig {
@@ -17,6 +79,294 @@ impl Default for EngineConfig {
             use_mipmaps: true,
             default_magnify_filter: MagnifySamplerFilter::Nearest,
             default_minify_filter: MinifySamplerFilter::LinearMipmapLinear,
+            vsync: true,
+            target_fps: None,
+            saves: SaveOptions::default(),
+            window_size: None,
+            clear_color: Color::BLACK,
+            master_volume: 1.0,
+            music_volume: 1.0,
+            effects_volume: 1.0,
+            key_binds: BTreeMap::new(),
+            path: None,
+            hot_reload: cfg!(debug_assertions),
+            ui_scale: 1.0,
+            color_filter: None,
         }
     }
 }
+
+impl EngineConfig {
+    /// The default config with whatever's set in the file at `path` on top. The file looks like:
+    ///
+    /// ```toml
+    /// [window]
+    /// size = [1280, 720]
+    /// vsync = true
+    /// target_fps = 60
+    ///
+    /// [graphics]
+    /// clear_color = [0.1, 0.1, 0.1, 1] # or "#1a1a1a"
+    /// use_mipmaps = true
+    ///
+    /// [audio]
+    /// master_volume = 1
+    /// music_volume = 0.8
+    /// effects_volume = 1
+    ///
+    /// [accessibility]
+    /// ui_scale = 1.25
+    /// color_filter = "correct-deuteranopia" # or "simulate-...", or "none"
+    ///
+    /// [keys]
+    /// jump = "Space"
+    /// shoot = "MouseLeft"
+    /// ```
+    ///
+    /// Everything is optional.
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("couldn't read config file {}", path.display()))?;
+
+        let mut config = Self::default();
+        config
+            .read_toml(&text)
+            .with_context(|| format!("couldn't load config file {}", path.display()))?;
+        config.path = Some(path.to_path_buf());
+
+        Ok(config)
+    }
+
+    /// Like [`from_file`](Self::from_file), but gives the default config if the file doesn't
+    /// exist yet. It'll still be saved there by [`save`](Self::save).
+    pub fn from_file_or_default(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        if path.exists() {
+            Self::from_file(path)
+        } else {
+            Ok(Self {
+                path: Some(path.to_path_buf()),
+                ..Default::default()
+            })
+        }
+    }
+
+    /// Sets everything in `text` on this config, leaving anything it doesn't mention alone.
+    pub fn read_toml(&mut self, text: &str) -> anyhow::Result<()> {
+        let file: ConfigFile = toml::from_str(text)?;
+        for setting in file.unknown_settings() {
+            log::warn!("unknown config setting {setting}");
+        }
+
+        let ConfigFile {
+            window,
+            graphics,
+            audio,
+            accessibility,
+            keys,
+            ..
+        } = file;
+
+        if let Some(scale) = accessibility.ui_scale
+            && scale <= 0.0
+        {
+            bail!("[accessibility] ui_scale has to be more than 0");
+        }
+
+        if let Some([width, height]) = window.size {
+            self.window_size = Some(UVec2::new(width, height));
+        }
+        set(&mut self.vsync, window.vsync);
+        set(&mut self.target_fps, window.target_fps.map(|fps| fps.0));
+
+        set(&mut self.clear_color, graphics.clear_color.map(|c| c.0));
+        set(&mut self.use_mipmaps, graphics.use_mipmaps);
+
+        set(&mut self.master_volume, audio.master_volume);
+        set(&mut self.music_volume, audio.music_volume);
+        set(&mut self.effects_volume, audio.effects_volume);
+
+        set(&mut self.ui_scale, accessibility.ui_scale);
+        set(
+            &mut self.color_filter,
+            accessibility
+                .color_filter
+                .map(|filter| filter.0.map(|named| named.0)),
+        );
+
+        self.key_binds.extend(keys);
+        Ok(())
+    }
+
+    /// Everything that can go in a config file, in the same format
+    /// [`from_file`](Self::from_file) reads.
+    pub fn to_toml(&self) -> String {
+        let file = ConfigFile {
+            window: WindowSection {
+                size: self.window_size.map(|size| size.to_array()),
+                vsync: Some(self.vsync),
+                target_fps: Some(OrNone(self.target_fps)),
+                ..Default::default()
+            },
+            graphics: GraphicsSection {
+                clear_color: Some(ClearColor(self.clear_color)),
+                use_mipmaps: Some(self.use_mipmaps),
+                ..Default::default()
+            },
+            audio: AudioSection {
+                master_volume: Some(self.master_volume),
+                music_volume: Some(self.music_volume),
+                effects_volume: Some(self.effects_volume),
+                ..Default::default()
+            },
+            accessibility: AccessibilitySection {
+                ui_scale: Some(self.ui_scale),
+                color_filter: Some(OrNone(self.color_filter.map(Named))),
+                ..Default::default()
+            },
+            keys: self.key_binds.clone(),
+            ..Default::default()
+        };
+
+        // everything in the file is a plain value toml can write
+        toml::to_string(&file).unwrap_or_default()
+    }
+
+    /// Writes the config back to the file it was loaded from.
+    pub fn save(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.path else {
+            bail!("config wasn't loaded from a file, use `save_to` instead");
+        };
+        self.save_to(path)
+    }
+
+    pub fn save_to(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_toml())
+            .with_context(|| format!("couldn't write config file {}", path.display()))
+    }
+
+    pub fn music_volume(&self) -> f32 {
+        self.master_volume * self.music_volume
+    }
+
+    pub fn effects_volume(&self) -> f32 {
+        self.master_volume * self.effects_volume
+    }
+}
+
+/// Sets `setting` if the file had it.
+fn set<T>(setting: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *setting = value;
+    }
+}
+
+/// Notices when a config file has changed, checking a couple of times a second.
+pub(crate) struct ConfigWatcher {
+    path: PathBuf,
+    pub(crate) modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl ConfigWatcher {
+    const CHECK_INTERVAL: f32 = 0.5;
+
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            modified: modified_time(&path),
+            path,
+            last_check: Instant::now(),
+        }
+    }
+
+    pub(crate) fn changed(&mut self) -> bool {
+        if self.last_check.elapsed().as_secs_f32() < Self::CHECK_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        let modified = modified_time(&self.path);
+        if modified != self.modified {
+            self.modified = modified;
+            modified.is_some()
+        } else {
+            false
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+pub fn config() -> &'static EngineConfig {
+    &get_state().config
+}
+
+/// Changes made here that are only read at init, like [`EngineConfig::vsync`], won't do anything
+/// until the game is restarted, but will still be written by [`save_config`].
+pub fn config_mut() -> &'static mut EngineConfig {
+    &mut get_state().config
+}
+
+/// Writes the engine's config back to the file it was loaded from.
+pub fn save_config() -> anyhow::Result<()> {
+    let state = get_state();
+    state.config.save()?;
+    // our own write isn't a change to reload
+    if let Some(watcher) = &mut state.config_watcher {
+        watcher.modified = state.config.path.as_deref().and_then(modified_time);
+    }
+    Ok(())
+}
+
+/// Reads the config file again, and applies whatever can be changed while running. Keys binds
+/// made with [`bind_named`](crate::input::bind_named) follow it, and the window is resized if
+/// its size changed. Sends a [`ConfigReloaded`].
+pub fn reload_config() -> anyhow::Result<()> {
+    let state = get_state();
+    let Some(path) = state.config.path.clone() else {
+        bail!("config wasn't loaded from a file");
+    };
+
+    let old_size = state.config.window_size;
+    let old_vsync = state.config.vsync;
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("couldn't read config file {}", path.display()))?;
+    state
+        .config
+        .read_toml(&text)
+        .with_context(|| format!("couldn't load config file {}", path.display()))?;
+
+    if state.config.vsync != old_vsync {
+        log::warn!("vsync can't change while the game is running, restart to apply it");
+    }
+    if let Some(size) = state.config.window_size
+        && state.config.window_size != old_size
+        && let Some(context) = &state.window_context
+    {
+        let _ = context
+            .window
+            .request_inner_size(PhysicalSize::new(size.x, size.y));
+    }
+    state.input.apply_named_binds(&state.config.key_binds);
+
+    emit(ConfigReloaded);
+    Ok(())
+}
+
+/// Reloads the config if it's being watched and its file changed.
+pub(crate) fn update_hot_reload() {
+    let state = get_state();
+    let Some(watcher) = &mut state.config_watcher else {
+        return;
+    };
+
+    if watcher.changed()
+        && let Err(e) = reload_config()
+    {
+        log::error!("{e:#}");
+    }
+}
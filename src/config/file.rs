@@ -0,0 +1,165 @@
+//! What config files look like, read and written with `toml`. Every setting is optional, so a
+//! file only changes what it mentions.
+
+use std::collections::BTreeMap;
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{DeserializeOwned, Error},
+};
+
+use crate::{color::Color, input_handling::Button, post_processing::ColorFilter};
+
+/// Anything that isn't a setting, warned about rather than failing the whole file.
+pub(crate) type Unknown = BTreeMap<String, toml::Value>;
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct ConfigFile {
+    pub window: WindowSection,
+    pub graphics: GraphicsSection,
+    pub audio: AudioSection,
+    pub accessibility: AccessibilitySection,
+    /// By the name given to `bind_named`, which can be anything, spaces and all.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub keys: BTreeMap<String, Button>,
+    #[serde(flatten, skip_serializing)]
+    pub unknown: Unknown,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct WindowSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<[u32; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vsync: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_fps: Option<OrNone<f32>>,
+    #[serde(flatten, skip_serializing)]
+    pub unknown: Unknown,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct GraphicsSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clear_color: Option<ClearColor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub use_mipmaps: Option<bool>,
+    #[serde(flatten, skip_serializing)]
+    pub unknown: Unknown,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct AudioSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub master_volume: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub music_volume: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effects_volume: Option<f32>,
+    #[serde(flatten, skip_serializing)]
+    pub unknown: Unknown,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct AccessibilitySection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ui_scale: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color_filter: Option<OrNone<Named<ColorFilter>>>,
+    #[serde(flatten, skip_serializing)]
+    pub unknown: Unknown,
+}
+
+impl ConfigFile {
+    /// Everything in the file that isn't a setting, like `[audio] volume`.
+    pub fn unknown_settings(&self) -> Vec<String> {
+        let sections = [
+            ("window", &self.window.unknown),
+            ("graphics", &self.graphics.unknown),
+            ("audio", &self.audio.unknown),
+            ("accessibility", &self.accessibility.unknown),
+        ];
+
+        let mut settings: Vec<String> = self.unknown.keys().cloned().collect();
+        for (section, unknown) in sections {
+            settings.extend(unknown.keys().map(|key| format!("[{section}] {key}")));
+        }
+        settings
+    }
+}
+
+/// A setting that can be turned off with `"none"`.
+pub(crate) struct OrNone<T>(pub Option<T>);
+
+impl<T: Serialize> Serialize for OrNone<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.0 {
+            Some(value) => value.serialize(serializer),
+            None => serializer.serialize_str("none"),
+        }
+    }
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for OrNone<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = toml::Value::deserialize(deserializer)?;
+        if value.as_str() == Some("none") {
+            return Ok(Self(None));
+        }
+        T::deserialize(value)
+            .map(|value| Self(Some(value)))
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Written as its name, through `Display` and `FromStr`.
+pub(crate) struct Named<T>(pub T);
+
+impl<T: std::fmt::Display> Serialize for Named<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&self.0)
+    }
+}
+
+impl<'de, T: std::str::FromStr<Err: std::fmt::Display>> Deserialize<'de> for Named<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map(Self).map_err(D::Error::custom)
+    }
+}
+
+/// `[r, g, b, a]`, or read from a hex string like `"#1a1a1a"` or `"#1a1a1a80"`.
+pub(crate) struct ClearColor(pub Color);
+
+impl Serialize for ClearColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let Color { r, g, b, a } = self.0;
+        [r, g, b, a].serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ClearColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = toml::Value::deserialize(deserializer)?;
+        if let Some(hex) = value.as_str() {
+            let digits = hex.trim_start_matches('#');
+            let color = u32::from_str_radix(digits, 16)
+                .ok()
+                .and_then(|n| match digits.len() {
+                    6 => Some(Color::hex(n)),
+                    8 => Some(Color::hex_alpha(n)),
+                    _ => None,
+                })
+                .ok_or_else(|| D::Error::custom(format!("`{hex}` isn't a hex color")))?;
+            return Ok(Self(color));
+        }
+
+        let [r, g, b, a] = <[f32; 4]>::deserialize(value).map_err(D::Error::custom)?;
+        Ok(Self(Color::from_rgba(r, g, b, a)))
+    }
+}
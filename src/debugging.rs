@@ -1,9 +1,25 @@
-use egui_glium::egui_winit::egui::Window;
+use std::time::Instant;
+
+use egui_glium::egui_winit::egui::{ComboBox, Window};
 use egui_plot::{Line, Plot, PlotPoints};
 
 use crate::{Fps, get_state};
 
+pub mod console;
 pub mod grid;
+pub mod profiler;
+pub mod render_mode;
+
+pub use console::{
+    Console, ConsoleLine, ConsoleLineKind, close_console, console_log, console_mut,
+    is_console_open, open_console, register_command, run_command, set_console_key, toggle_console,
+    unregister_command,
+};
+pub use profiler::{
+    ProfileScope, ProfiledFrame, Profiler, ScopeTiming, export_chrome_trace, profiler,
+    set_profiling,
+};
+pub use render_mode::{DebugRenderMode, debug_render_mode, set_debug_render_mode};
 
 const FRAME_BACKLOG: usize = 240;
 
@@ -13,6 +29,8 @@ pub struct DebugInfo {
     pub frames: [FrameInfo; FRAME_BACKLOG],
     pub show_window: bool,
     pub max: FrameInfo,
+    pub profiler: Profiler,
+    pub(crate) render_modes: render_mode::DebugRenderModes,
 }
 
 #[derive(Clone, Copy)]
@@ -42,6 +60,8 @@ impl DebugInfo {
             frames: [FrameInfo::ZERO; FRAME_BACKLOG],
             max: FrameInfo::ZERO,
             show_window: false,
+            profiler: Profiler::new(),
+            render_modes: render_mode::DebugRenderModes::default(),
         }
     }
 
@@ -56,6 +76,7 @@ impl DebugInfo {
         self.frame_offset = (self.frame_offset + 1) % FRAME_BACKLOG;
         self.fps.tick();
         self.frames[self.frame_offset] = FrameInfo::ZERO;
+        self.profiler.next_frame(Instant::now());
     }
 
     pub fn current_frame(&self) -> &FrameInfo {
@@ -103,11 +124,12 @@ impl DebugInfo {
             })
             .collect();
 
-        let vertex_line = Line::new(vertex_points);
-        let index_line = Line::new(index_points);
-        let draw_call_line = Line::new(draw_call_points);
-        let drawn_object_line = Line::new(drawn_object_points);
-        let engine_time_line = Line::new(engine_time_points);
+        let line_color = state.theme.primary;
+        let vertex_line = Line::new(vertex_points).color(line_color);
+        let index_line = Line::new(index_points).color(line_color);
+        let draw_call_line = Line::new(draw_call_points).color(line_color);
+        let drawn_object_line = Line::new(drawn_object_points).color(line_color);
+        let engine_time_line = Line::new(engine_time_points).color(line_color);
 
         Window::new("Debug info").show(ui, |ui| {
             for (id, max, label, line) in [
@@ -167,10 +189,28 @@ impl DebugInfo {
                 "Engine time: {:.1}ms",
                 self.current_frame().engine_time
             ));
+
+            let render_mode = &mut self.render_modes.global;
+            ComboBox::from_label("3D render mode")
+                .selected_text(render_mode.name())
+                .show_ui(ui, |ui| {
+                    for mode in DebugRenderMode::ALL {
+                        ui.selectable_value(render_mode, mode, mode.name());
+                    }
+                });
+
+            ui.collapsing("Profiler", |ui| self.profiler.draw_flame_chart(ui));
         });
     }
 }
 
+impl DebugInfo {
+    /// Whether anything the engine draws with egui is showing.
+    pub(crate) fn wants_ui(&self) -> bool {
+        self.show_window || get_state().console.is_open()
+    }
+}
+
 impl Default for DebugInfo {
     fn default() -> Self {
         Self::new()
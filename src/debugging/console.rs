@@ -0,0 +1,499 @@
+use std::collections::BTreeMap;
+
+use egui_glium::egui_winit::egui::{
+    self, Color32, FontId, Key, RichText, ScrollArea, TextEdit, TopBottomPanel,
+};
+use glium::winit::keyboard::KeyCode;
+
+use crate::get_state;
+
+/// How many lines of output are kept before the oldest are dropped.
+const MAX_OUTPUT_LINES: usize = 500;
+
+type CommandCallback = Box<dyn FnMut(&[&str]) -> anyhow::Result<()>>;
+
+struct Command {
+    description: String,
+    callback: CommandCallback,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleLineKind {
+    /// Something typed in.
+    Input,
+    Output,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleLine {
+    pub kind: ConsoleLineKind,
+    pub text: String,
+}
+
+/// A drop-down console for running commands while the game is going, opened with the backtick
+/// key (see [`set_console_key`]). Games add their own commands with [`register_command`]:
+///
+/// ```ignore
+/// register_command("spawn", "spawn <count>: adds enemies", |args| {
+///     let count: usize = args.first().unwrap_or(&"1").parse()?;
+///     spawn_enemies(count);
+///     console_log(format!("spawned {count}"));
+///     Ok(())
+/// });
+/// ```
+///
+/// Arguments are split on whitespace. Up and down go through past commands, and tab completes
+/// command names. Errors returned by commands are shown in the console.
+pub struct Console {
+    open: bool,
+    toggle_key: KeyCode,
+    input: String,
+    output: Vec<ConsoleLine>,
+    commands: BTreeMap<String, Command>,
+    history: Vec<String>,
+    /// Where up/down has got to in `history`, if they've been used since the last command.
+    history_position: Option<usize>,
+    focus_input: bool,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Console {
+    /// A console with just `help` and `clear`.
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            toggle_key: KeyCode::Backquote,
+            input: String::new(),
+            output: Vec::new(),
+            commands: BTreeMap::new(),
+            history: Vec::new(),
+            history_position: None,
+            focus_input: false,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn set_open(&mut self, open: bool) {
+        self.open = open;
+        self.focus_input = open;
+    }
+
+    pub fn toggle(&mut self) {
+        self.set_open(!self.open);
+    }
+
+    pub fn toggle_key(&self) -> KeyCode {
+        self.toggle_key
+    }
+
+    pub fn set_toggle_key(&mut self, key: KeyCode) {
+        self.toggle_key = key;
+    }
+
+    /// Adds a command, replacing any with the same name. `description` is shown by `help`.
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        callback: impl FnMut(&[&str]) -> anyhow::Result<()> + 'static,
+    ) {
+        self.commands.insert(
+            name.into(),
+            Command {
+                description: description.into(),
+                callback: Box::new(callback),
+            },
+        );
+    }
+
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.commands.remove(name).is_some()
+    }
+
+    pub fn has_command(&self, name: &str) -> bool {
+        matches!(name, "help" | "clear") || self.commands.contains_key(name)
+    }
+
+    pub fn log(&mut self, text: impl Into<String>) {
+        self.push_line(ConsoleLineKind::Output, text.into());
+    }
+
+    pub fn log_error(&mut self, text: impl Into<String>) {
+        self.push_line(ConsoleLineKind::Error, text.into());
+    }
+
+    pub fn output(&self) -> &[ConsoleLine] {
+        &self.output
+    }
+
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+
+    /// Past commands, oldest first.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Runs a line as if it had been typed in.
+    pub fn execute(&mut self, line: &str) {
+        let line = line.trim();
+        if line.is_empty() {
+            return;
+        }
+
+        self.push_line(ConsoleLineKind::Input, line.to_string());
+        if self.history.last().map(String::as_str) != Some(line) {
+            self.history.push(line.to_string());
+        }
+        self.history_position = None;
+
+        let mut words = line.split_whitespace();
+        let name = words.next().unwrap();
+        let args: Vec<&str> = words.collect();
+
+        match name {
+            "help" => self.help(),
+            "clear" => self.clear(),
+            _ => {
+                // taken out while it runs, and only put back if it didn't replace itself
+                let Some(mut command) = self.commands.remove(name) else {
+                    self.log_error(format!("unknown command `{name}`, try `help`"));
+                    return;
+                };
+                if let Err(e) = (command.callback)(&args) {
+                    self.log_error(format!("{e:#}"));
+                }
+                self.commands.entry(name.to_string()).or_insert(command);
+            }
+        }
+    }
+
+    /// Completes the command name being typed as far as it can. If more than one command could
+    /// fit, lists them.
+    pub fn complete(&mut self) {
+        if self.input.contains(char::is_whitespace) {
+            return;
+        }
+
+        let candidates: Vec<&str> = ["clear", "help"]
+            .into_iter()
+            .chain(self.commands.keys().map(String::as_str))
+            .filter(|name| name.starts_with(self.input.as_str()))
+            .collect();
+
+        let Some(first) = candidates.first() else {
+            return;
+        };
+        let common = candidates.iter().fold(first.len(), |len, name| {
+            first
+                .chars()
+                .zip(name.chars())
+                .take(len)
+                .take_while(|(a, b)| a == b)
+                .count()
+        });
+
+        let mut completed: String = first.chars().take(common).collect();
+        if candidates.len() == 1 {
+            completed.push(' ');
+        } else if completed == self.input {
+            let list = candidates.join("  ");
+            self.log(list);
+        }
+        self.input = completed;
+    }
+
+    /// Moves back through the history into the input.
+    pub fn history_up(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        let position = match self.history_position {
+            Some(position) => position.saturating_sub(1),
+            None => self.history.len() - 1,
+        };
+        self.history_position = Some(position);
+        self.input = self.history[position].clone();
+    }
+
+    /// Moves forward through the history, ending on an empty input.
+    pub fn history_down(&mut self) {
+        let Some(position) = self.history_position else {
+            return;
+        };
+
+        if position + 1 < self.history.len() {
+            self.history_position = Some(position + 1);
+            self.input = self.history[position + 1].clone();
+        } else {
+            self.history_position = None;
+            self.input.clear();
+        }
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn set_input(&mut self, input: impl Into<String>) {
+        self.input = input.into();
+    }
+
+    /// Takes over commands and output added to `other`.
+    pub(crate) fn merge(&mut self, other: Console) {
+        self.commands.extend(other.commands);
+        for line in other.output {
+            self.push_line(line.kind, line.text);
+        }
+    }
+
+    fn help(&mut self) {
+        let mut lines = vec![
+            "clear: empties the console".to_string(),
+            "help: lists commands".to_string(),
+        ];
+        for (name, command) in &self.commands {
+            if command.description.is_empty() {
+                lines.push(name.clone());
+            } else {
+                lines.push(format!("{name}: {}", command.description));
+            }
+        }
+        lines.sort();
+
+        for line in lines {
+            self.log(line);
+        }
+    }
+
+    fn push_line(&mut self, kind: ConsoleLineKind, text: String) {
+        self.output.push(ConsoleLine { kind, text });
+        if self.output.len() > MAX_OUTPUT_LINES {
+            let extra = self.output.len() - MAX_OUTPUT_LINES;
+            self.output.drain(..extra);
+        }
+    }
+
+    /// Shows the console if it's open. Returns a line to run, if one was entered.
+    pub(crate) fn draw(&mut self, ctx: &egui::Context) -> Option<String> {
+        if !self.open {
+            return None;
+        }
+
+        let theme = &get_state().theme;
+        let font = FontId::monospace(14.0);
+        let mut submitted = None;
+
+        TopBottomPanel::top("engine_console")
+            .resizable(true)
+            .default_height(ctx.screen_rect().height() * 0.4)
+            .show(ctx, |ui| {
+                let input_height = ui.spacing().interact_size.y + ui.spacing().item_spacing.y;
+                ScrollArea::vertical()
+                    .auto_shrink(false)
+                    .stick_to_bottom(true)
+                    .max_height(ui.available_height() - input_height)
+                    .show(ui, |ui| {
+                        for line in &self.output {
+                            let color: Color32 = match line.kind {
+                                ConsoleLineKind::Input => theme.primary.into(),
+                                ConsoleLineKind::Output => theme.text.into(),
+                                ConsoleLineKind::Error => theme.danger.into(),
+                            };
+                            let text = match line.kind {
+                                ConsoleLineKind::Input => format!("> {}", line.text),
+                                _ => line.text.clone(),
+                            };
+                            ui.label(RichText::new(text).font(font.clone()).color(color));
+                        }
+                    });
+
+                let response = ui.add(
+                    TextEdit::singleline(&mut self.input)
+                        .font(font.clone())
+                        .desired_width(f32::INFINITY)
+                        .lock_focus(true),
+                );
+
+                if self.focus_input {
+                    response.request_focus();
+                    self.focus_input = false;
+                }
+
+                if response.has_focus() {
+                    let (up, down, tab) = ui.input_mut(|input| {
+                        (
+                            input.consume_key(egui::Modifiers::NONE, Key::ArrowUp),
+                            input.consume_key(egui::Modifiers::NONE, Key::ArrowDown),
+                            input.consume_key(egui::Modifiers::NONE, Key::Tab),
+                        )
+                    });
+                    if up {
+                        self.history_up();
+                    }
+                    if down {
+                        self.history_down();
+                    }
+                    if tab {
+                        self.complete();
+                    }
+                    if up || down || tab {
+                        move_cursor_to_end(ui.ctx(), response.id, &self.input);
+                    }
+                }
+
+                if response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter)) {
+                    submitted = Some(std::mem::take(&mut self.input));
+                    response.request_focus();
+                }
+            });
+
+        submitted
+    }
+}
+
+fn move_cursor_to_end(ctx: &egui::Context, id: egui::Id, text: &str) {
+    if let Some(mut state) = egui::TextEdit::load_state(ctx, id) {
+        let cursor = egui::text::CCursor::new(text.chars().count());
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::one(cursor)));
+        state.store(ctx, id);
+    }
+}
+
+/// The built-in commands the engine's console starts with, on top of `help` and `clear`.
+pub(crate) fn register_builtin_commands(console: &mut Console) {
+    console.register(
+        "debug_info",
+        "debug_info [on|off]: shows or hides the debug overlay",
+        |args| {
+            let debug = super::get_debug_info_mut();
+            debug.show_window = match args.first() {
+                None => !debug.show_window,
+                Some(arg) => parse_bool(arg)?,
+            };
+            Ok(())
+        },
+    );
+
+    console.register(
+        "gizmos",
+        "gizmos [on|off]: shows or hides everything drawn with debug_draw_*",
+        |args| {
+            match args.first() {
+                None => crate::gizmos::toggle_gizmos(),
+                Some(arg) => crate::gizmos::set_gizmos_enabled(parse_bool(arg)?),
+            }
+            Ok(())
+        },
+    );
+
+    console.register(
+        "set",
+        "set <setting> <value>: changes a setting, or lists them with no arguments",
+        |args| {
+            let state = get_state();
+            let [setting, value] = args else {
+                console_log(format!("time_scale {}", state.time_scale));
+                console_log(match state.config.target_fps {
+                    Some(fps) => format!("target_fps {fps}"),
+                    None => "target_fps none".to_string(),
+                });
+                console_log(format!("use_mipmaps {}", state.config.use_mipmaps));
+                console_log(format!("physics_paused {}", state.is_physics_time_paused));
+                return Ok(());
+            };
+
+            match *setting {
+                "time_scale" => crate::api::set_time_scale(value.parse()?),
+                "target_fps" => crate::api::set_target_fps(match *value {
+                    "none" | "off" => None,
+                    fps => Some(fps.parse()?),
+                }),
+                "use_mipmaps" => state.config.use_mipmaps = parse_bool(value)?,
+                "physics_paused" => state.is_physics_time_paused = parse_bool(value)?,
+                _ => anyhow::bail!("unknown setting `{setting}`, try `set`"),
+            }
+            Ok(())
+        },
+    );
+}
+
+fn parse_bool(text: &str) -> anyhow::Result<bool> {
+    match text {
+        "on" | "true" | "1" => Ok(true),
+        "off" | "false" | "0" => Ok(false),
+        _ => anyhow::bail!("expected on or off, got `{text}`"),
+    }
+}
+
+/// Runs a line in the engine's console, with the console swapped out while it runs so commands
+/// can use the free functions here.
+pub(crate) fn execute_in_console(line: &str) {
+    let state = get_state();
+    let mut running = std::mem::take(&mut state.console);
+    running.execute(line);
+    let added = std::mem::replace(&mut state.console, running);
+    state.console.merge(added);
+}
+
+/// Adds a command to the engine's console. See [`Console`].
+pub fn register_command(
+    name: impl Into<String>,
+    description: impl Into<String>,
+    callback: impl FnMut(&[&str]) -> anyhow::Result<()> + 'static,
+) {
+    get_state().console.register(name, description, callback);
+}
+
+pub fn unregister_command(name: &str) -> bool {
+    get_state().console.unregister(name)
+}
+
+/// Prints a line to the engine's console.
+pub fn console_log(text: impl Into<String>) {
+    get_state().console.log(text);
+}
+
+/// Runs a line in the engine's console as if it had been typed in.
+pub fn run_command(line: &str) {
+    execute_in_console(line);
+}
+
+pub fn open_console() {
+    get_state().console.set_open(true);
+}
+
+pub fn close_console() {
+    get_state().console.set_open(false);
+}
+
+pub fn toggle_console() {
+    get_state().console.toggle();
+}
+
+pub fn is_console_open() -> bool {
+    get_state().console.is_open()
+}
+
+/// Changes the key that opens and closes the console. Defaults to backtick.
+pub fn set_console_key(key: KeyCode) {
+    get_state().console.set_toggle_key(key);
+}
+
+/// The engine's console, for anything the free functions don't cover.
+pub fn console_mut() -> &'static mut Console {
+    &mut get_state().console
+}
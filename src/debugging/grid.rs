@@ -2,11 +2,11 @@ use crate::utils::EngineCreate;
 use crate::{
     Color,
     draw_queue_2d::MaterialVertex3D,
-    get_state,
-    prelude::{Material, Mesh, Object3D, Object3DRef, Transform3D, load_program},
+    error::EngineResult,
+    prelude::{BlendMode, Material, Mesh, Object3D, Object3DRef, Transform3D, load_program},
     programs::ProgramRef,
 };
-use glium::{IndexBuffer, VertexBuffer, implement_vertex};
+use glium::implement_vertex;
 
 implement_vertex!(GridVertex, position);
 #[derive(Copy, Clone, Debug)]
@@ -15,54 +15,38 @@ pub struct GridVertex {
 }
 
 pub fn create_infinite_grid() -> anyhow::Result<Object3DRef> {
-    let state = get_state();
-    let display = &state.display;
-
     let size = 1000.0;
     let vertices = vec![
         MaterialVertex3D {
             position: [-size, 0.0, -size],
             normal: [0.0, 1.0, 0.0],
             tex_coords: [0.0, 0.0],
+            tangent: [0.0; 4],
         },
         MaterialVertex3D {
             position: [size, 0.0, -size],
             normal: [0.0, 1.0, 0.0],
             tex_coords: [1.0, 0.0],
+            tangent: [0.0; 4],
         },
         MaterialVertex3D {
             position: [size, 0.0, size],
             normal: [0.0, 1.0, 0.0],
             tex_coords: [1.0, 1.0],
+            tangent: [0.0; 4],
         },
         MaterialVertex3D {
             position: [-size, 0.0, size],
             normal: [0.0, 1.0, 0.0],
             tex_coords: [0.0, 1.0],
+            tangent: [0.0; 4],
         },
     ];
 
     let indices = vec![0, 1, 2, 0, 2, 3];
 
-    let vertex_buffer = VertexBuffer::new(display, &vertices)?;
-    let index_buffer = IndexBuffer::new(
-        display,
-        glium::index::PrimitiveType::TrianglesList,
-        &indices,
-    )?;
-
     let program = load_grid_program()?;
 
-    let draw_params = glium::DrawParameters {
-        blend: glium::Blend::alpha_blending(),
-        depth: glium::Depth {
-            test: glium::DepthTest::IfLess,
-            write: false,
-            ..Default::default()
-        },
-        ..Default::default()
-    };
-
     let material = Material::new(program)
         .with_float("grid_scale", 1.0)
         .with_float("grid_size", 10.0)
@@ -71,15 +55,11 @@ pub fn create_infinite_grid() -> anyhow::Result<Object3DRef> {
         .with_color("x_axis_color", Color::RED_200)
         .with_color("z_axis_color", Color::BLUE_200)
         .with_float("axis_width", 0.02)
-        .with_draw_param_overrides(draw_params)
+        .with_blend_mode(BlendMode::Alpha)
         .create();
 
     let object = Object3D {
-        mesh: Mesh {
-            vertices: vertex_buffer,
-            indices: index_buffer,
-        }
-        .create(),
+        mesh: Mesh::new(&vertices, &indices)?.create(),
         material,
         transform: Transform3D::IDENTITY,
     };
@@ -87,7 +67,7 @@ pub fn create_infinite_grid() -> anyhow::Result<Object3DRef> {
     Ok(object.create())
 }
 
-fn load_grid_program() -> anyhow::Result<ProgramRef> {
+fn load_grid_program() -> EngineResult<ProgramRef> {
     let vertex_shader = include_str!("../../assets/shaders/grid/vertex.glsl");
     let fragment_shader = include_str!("../../assets/shaders/grid/fragment.glsl");
 
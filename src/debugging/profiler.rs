@@ -0,0 +1,305 @@
+use std::{
+    collections::VecDeque,
+    fmt::Write,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use egui_glium::egui_winit::egui::{self, Align2, FontId, Rect, Sense, Ui, pos2, vec2};
+
+use crate::{get_state, try_get_state};
+
+use super::FRAME_BACKLOG;
+
+/// Times the rest of the enclosing block, showing it in the profiler in the debug overlay:
+///
+/// ```ignore
+/// {
+///     profile_scope!("pathfinding");
+///     update_paths();
+/// }
+/// ```
+///
+/// Scopes can be nested, and show up under the scope they're in.
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::debugging::ProfileScope::new($name);
+    };
+}
+
+/// One finished [`profile_scope!`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScopeTiming {
+    pub name: &'static str,
+    /// From the start of the frame.
+    pub start: Duration,
+    pub duration: Duration,
+    /// How many scopes it was inside.
+    pub depth: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ProfiledFrame {
+    /// From when the profiler was made.
+    pub start: Duration,
+    pub duration: Duration,
+    pub scopes: Vec<ScopeTiming>,
+}
+
+/// Collects [`profile_scope!`] timings into frames. The engine keeps one in its
+/// [`DebugInfo`](super::DebugInfo), with the last few seconds of frames.
+pub struct Profiler {
+    enabled: bool,
+    epoch: Instant,
+    frame_start: Instant,
+    depth: u32,
+    current: Vec<ScopeTiming>,
+    frames: VecDeque<ProfiledFrame>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            enabled: true,
+            epoch: now,
+            frame_start: now,
+            depth: 0,
+            current: Vec::new(),
+            frames: VecDeque::with_capacity(FRAME_BACKLOG),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Starts a scope. Returns whether it's being recorded, and so whether to call
+    /// [`end_scope`](Self::end_scope).
+    pub fn begin_scope(&mut self) -> bool {
+        if self.enabled {
+            self.depth += 1;
+        }
+        self.enabled
+    }
+
+    pub fn end_scope(&mut self, name: &'static str, start: Instant) {
+        self.depth = self.depth.saturating_sub(1);
+        self.current.push(ScopeTiming {
+            name,
+            start: start.saturating_duration_since(self.frame_start),
+            duration: start.elapsed(),
+            depth: self.depth,
+        });
+    }
+
+    /// Files the scopes recorded so far under a frame ending `now`, and starts the next one.
+    pub fn next_frame(&mut self, now: Instant) {
+        let mut scopes = std::mem::take(&mut self.current);
+        scopes.sort_by_key(|scope| (scope.start, scope.depth));
+
+        if self.frames.len() == FRAME_BACKLOG {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(ProfiledFrame {
+            start: self.frame_start.saturating_duration_since(self.epoch),
+            duration: now.saturating_duration_since(self.frame_start),
+            scopes,
+        });
+
+        self.frame_start = now;
+    }
+
+    /// Finished frames, oldest first.
+    pub fn frames(&self) -> impl Iterator<Item = &ProfiledFrame> {
+        self.frames.iter()
+    }
+
+    pub fn last_frame(&self) -> Option<&ProfiledFrame> {
+        self.frames.back()
+    }
+
+    /// Total time in each scope name over the stored frames, longest first. Nested scopes are
+    /// counted in their parents too.
+    pub fn totals(&self) -> Vec<(&'static str, Duration)> {
+        let mut totals: Vec<(&'static str, Duration)> = Vec::new();
+        for scope in self.frames.iter().flat_map(|frame| &frame.scopes) {
+            match totals.iter_mut().find(|(name, _)| *name == scope.name) {
+                Some((_, total)) => *total += scope.duration,
+                None => totals.push((scope.name, scope.duration)),
+            }
+        }
+        totals.sort_by_key(|(_, total)| std::cmp::Reverse(*total));
+        totals
+    }
+
+    /// The stored frames in the Trace Event Format, for loading into `chrome://tracing` or
+    /// Perfetto.
+    pub fn chrome_trace_json(&self) -> String {
+        let mut json = String::from("{\"traceEvents\":[");
+        let mut first = true;
+        let mut event = |json: &mut String, name: &str, start: Duration, duration: Duration| {
+            if !first {
+                json.push(',');
+            }
+            first = false;
+            let _ = write!(
+                json,
+                "{{\"name\":\"{}\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":0,\"tid\":0}}",
+                escape_json(name),
+                start.as_micros(),
+                duration.as_micros(),
+            );
+        };
+
+        for frame in &self.frames {
+            event(&mut json, "frame", frame.start, frame.duration);
+            for scope in &frame.scopes {
+                event(
+                    &mut json,
+                    scope.name,
+                    frame.start + scope.start,
+                    scope.duration,
+                );
+            }
+        }
+
+        json.push_str("]}");
+        json
+    }
+
+    /// Draws the last frame's scopes as a flame chart, with the widest scopes on top.
+    pub(crate) fn draw_flame_chart(&self, ui: &mut Ui) {
+        let Some(frame) = self.last_frame() else {
+            ui.label("No frames yet");
+            return;
+        };
+
+        const ROW_HEIGHT: f32 = 18.0;
+        let rows = frame.scopes.iter().map(|scope| scope.depth + 1).max();
+        let rows = rows.unwrap_or(1) as f32;
+        let (response, painter) = ui.allocate_painter(
+            vec2(ui.available_width(), rows * ROW_HEIGHT),
+            Sense::hover(),
+        );
+        let area = response.rect;
+        let frame_time = frame.duration.as_secs_f32().max(f32::EPSILON);
+        let theme = &get_state().theme;
+        let colors = [theme.primary, theme.secondary, theme.accent, theme.success];
+
+        for scope in &frame.scopes {
+            let left = area.left() + scope.start.as_secs_f32() / frame_time * area.width();
+            let width = scope.duration.as_secs_f32() / frame_time * area.width();
+            let top = area.top() + scope.depth as f32 * ROW_HEIGHT;
+            let rect = Rect::from_min_size(pos2(left, top), vec2(width.max(1.0), ROW_HEIGHT - 2.0));
+
+            painter.rect_filled(rect, 2.0, colors[scope.depth as usize % colors.len()]);
+            let label = format!(
+                "{} {:.2}ms",
+                scope.name,
+                scope.duration.as_secs_f64() * 1000.0
+            );
+            if width > 40.0 {
+                painter.with_clip_rect(rect).text(
+                    rect.left_center() + vec2(3.0, 0.0),
+                    Align2::LEFT_CENTER,
+                    &label,
+                    FontId::proportional(12.0),
+                    theme.background.into(),
+                );
+            }
+            if response
+                .hover_pos()
+                .is_some_and(|position| rect.contains(position))
+            {
+                response.clone().on_hover_text(label);
+            }
+        }
+
+        ui.label(format!(
+            "Frame: {:.2}ms",
+            frame.duration.as_secs_f64() * 1000.0
+        ));
+        egui::Grid::new("profiler_totals").show(ui, |ui| {
+            let frame_count = self.frames.len().max(1) as f64;
+            for (name, total) in self.totals() {
+                ui.label(name);
+                ui.label(format!(
+                    "{:.2}ms avg",
+                    total.as_secs_f64() * 1000.0 / frame_count
+                ));
+                ui.end_row();
+            }
+        });
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Made by [`profile_scope!`]; records the scope when it's dropped.
+pub struct ProfileScope {
+    name: &'static str,
+    start: Instant,
+    recording: bool,
+}
+
+impl ProfileScope {
+    pub fn new(name: &'static str) -> Self {
+        let recording =
+            try_get_state().is_some_and(|state| state.debug_info.profiler.begin_scope());
+        Self {
+            name,
+            start: Instant::now(),
+            recording,
+        }
+    }
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        if self.recording
+            && let Some(state) = try_get_state()
+        {
+            state.debug_info.profiler.end_scope(self.name, self.start);
+        }
+    }
+}
+
+pub fn profiler() -> &'static mut Profiler {
+    &mut get_state().debug_info.profiler
+}
+
+pub fn set_profiling(enabled: bool) {
+    profiler().set_enabled(enabled);
+}
+
+/// Writes the last few seconds of [`profile_scope!`] timings to a file that can be opened in
+/// `chrome://tracing` or Perfetto.
+pub fn export_chrome_trace(path: impl AsRef<Path>) -> anyhow::Result<()> {
+    std::fs::write(path, profiler().chrome_trace_json())?;
+    Ok(())
+}
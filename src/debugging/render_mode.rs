@@ -0,0 +1,93 @@
+//! Ways of drawing 3D objects that show what's wrong with a mesh instead of what it looks
+//! like. Pick one for everything from the debug window, or set one per object.
+
+use std::collections::HashMap;
+
+use crate::{get_state, object_3d::Object3DRef};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum DebugRenderMode {
+    /// Drawn normally, with its material.
+    #[default]
+    Shaded,
+    /// Just the edges of the triangles.
+    Wireframe,
+    /// World space normals as colors: +X is red, +Y green, +Z blue.
+    Normals,
+    /// A checkerboard tinted by the UVs, to spot stretching, seams and flipped UVs.
+    UvChecker,
+    /// Every triangle adds a little light, ignoring depth, so places that get drawn over many
+    /// times glow.
+    Overdraw,
+}
+
+impl DebugRenderMode {
+    pub const ALL: [Self; 5] = [
+        Self::Shaded,
+        Self::Wireframe,
+        Self::Normals,
+        Self::UvChecker,
+        Self::Overdraw,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Shaded => "Shaded",
+            Self::Wireframe => "Wireframe",
+            Self::Normals => "Normals",
+            Self::UvChecker => "UV checker",
+            Self::Overdraw => "Overdraw",
+        }
+    }
+
+    /// The `debug_mode` uniform of the debug program.
+    pub(crate) fn shader_index(self) -> f32 {
+        self as u8 as f32
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct DebugRenderModes {
+    pub(crate) global: DebugRenderMode,
+    per_object: HashMap<Object3DRef, DebugRenderMode>,
+}
+
+impl DebugRenderModes {
+    /// The object's own mode if it has one, otherwise the global one.
+    pub(crate) fn mode_for(&self, object: Object3DRef) -> DebugRenderMode {
+        self.per_object.get(&object).copied().unwrap_or(self.global)
+    }
+
+    pub(crate) fn set(&mut self, object: Object3DRef, mode: Option<DebugRenderMode>) {
+        match mode {
+            Some(mode) => self.per_object.insert(object, mode),
+            None => self.per_object.remove(&object),
+        };
+    }
+}
+
+/// Draws every 3D object in `mode`, apart from ones with their own mode set.
+pub fn set_debug_render_mode(mode: DebugRenderMode) {
+    get_state().debug_info.render_modes.global = mode;
+}
+
+pub fn debug_render_mode() -> DebugRenderMode {
+    get_state().debug_info.render_modes.global
+}
+
+impl Object3DRef {
+    /// Draws just this object in `mode`, whatever the global mode is. `None` goes back to
+    /// following the global mode.
+    pub fn set_debug_render_mode(&self, mode: Option<DebugRenderMode>) {
+        get_state().debug_info.render_modes.set(*self, mode);
+    }
+
+    pub fn debug_render_mode(&self) -> Option<DebugRenderMode> {
+        get_state()
+            .debug_info
+            .render_modes
+            .per_object
+            .get(self)
+            .copied()
+    }
+}
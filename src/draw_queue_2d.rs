@@ -1,28 +1,255 @@
 use std::collections::HashMap;
-
-use crate::api::debugger_add_drawn_objects;
+use std::ops::Range;
+
+use crate::api::{
+    debugger_add_draw_calls, debugger_add_drawn_objects, debugger_add_indices,
+    debugger_add_vertices,
+};
+use crate::error::OrReport;
+use crate::materials::BlendMode;
+use crate::picking::{PickId, pick_color};
 use crate::prelude::Transform2D;
-use crate::programs::{CIRCLE_PROGRAM, FLAT_PROGRAM, TEXTURED_PROGRAM};
-use crate::shapes_2d::{QUAD_INDICES, Shape2D, UNIT_QUAD};
+use crate::programs::{CIRCLE_PROGRAM, FLAT_PROGRAM, PICK_PROGRAM, SDF_PROGRAM, TEXTURED_PROGRAM};
+use crate::shapes_2d::Shape2D;
 use crate::textures::TextureRef;
-use crate::{Color, get_state};
+use crate::{Color, get_state, with_frame_arena};
 use bevy_math::{Mat4, Rect, Vec2};
-use glium::{Blend, DrawParameters, IndexBuffer, Surface, VertexBuffer, uniform};
+use bumpalo::collections::Vec as BumpVec;
+use glium::draw_parameters::{Stencil, StencilOperation, StencilTest};
+use glium::{Blend, DrawParameters, Surface, uniform};
 use glium::{Depth, DepthTest, implement_vertex};
 
+#[derive(Clone)]
 pub struct DrawQueue2D {
+    /// One batch per blend mode, indexed by `batch_index`.
+    batches: [DrawBatch2D; BATCH_BLEND_MODES.len()],
+    blend_mode: BlendMode,
+    /// Given to everything added after it is set, see [`crate::prelude::set_pick_id`].
+    pick_id: Option<PickId>,
+    /// Given to everything added after it is set, see [`crate::prelude::set_outline`].
+    outline: Option<Color>,
+
+    /// Everything added so far, so it can be re-sorted by layer before drawing.
+    items: Vec<DrawItem2D>,
+    layer: f32,
+    uses_layers: bool,
+    layers_sorted: bool,
+
+    /// Draws that are masked or clipped, see `push_mask` and `push_clip_rect`.
+    sections: Vec<Section2D>,
+    /// Indices into `sections`, innermost last.
+    section_stack: Vec<usize>,
+
+    current_z: f32,
+    start_z: f32,
+    z_increment: f32,
+}
+
+#[derive(Clone, Default)]
+struct DrawBatch2D {
     shape_vertices: Vec<Vertex3D>,
     shape_indices: Vec<u32>,
     current_max_index: u32,
 
     circle_instances: Vec<CircleInstance>,
     sprite_draws: HashMap<TextureRef, SpriteDrawBatch>,
+    sdf_draws: HashMap<TextureRef, SdfDrawBatch>,
+}
 
-    current_z: f32,
-    start_z: f32,
-    z_increment: f32,
+impl DrawBatch2D {
+    fn is_empty(&self) -> bool {
+        self.shape_vertices.is_empty()
+            && self.circle_instances.is_empty()
+            && self
+                .sprite_draws
+                .values()
+                .all(|batch| batch.vertices.is_empty())
+            && self
+                .sdf_draws
+                .values()
+                .all(|batch| batch.vertices.is_empty())
+    }
+
+    fn clear(&mut self) {
+        self.shape_vertices.clear();
+        self.shape_indices.clear();
+        self.current_max_index = 0;
+        self.circle_instances.clear();
+        // textures that were drawn with keep their buffers for next frame, the rest are dropped
+        self.sprite_draws.retain(|_, batch| {
+            let used = !batch.vertices.is_empty();
+            batch.vertices.clear();
+            batch.indices.clear();
+            used
+        });
+        self.sdf_draws.retain(|_, batch| {
+            let used = !batch.vertices.is_empty();
+            batch.vertices.clear();
+            batch.indices.clear();
+            used
+        });
+    }
+
+    /// Moves one of the things in this batch to `z`.
+    fn set_z(&mut self, kind: &DrawItemKind, z: f32) {
+        match kind {
+            DrawItemKind::Shape(range) => {
+                for vertex in &mut self.shape_vertices[range.clone()] {
+                    vertex.position[2] = z;
+                }
+            }
+            DrawItemKind::Circle(index) => self.circle_instances[*index].center[2] = z,
+            DrawItemKind::Sprite(texture, range) => {
+                let sprites = self.sprite_draws.get_mut(texture).unwrap();
+                for vertex in &mut sprites.vertices[range.clone()] {
+                    vertex.position[2] = z;
+                }
+            }
+            DrawItemKind::Sdf(texture, range) => {
+                let glyphs = self.sdf_draws.get_mut(texture).unwrap();
+                for vertex in &mut glyphs.vertices[range.clone()] {
+                    vertex.position[2] = z;
+                }
+            }
+        }
+    }
+
+    /// Adds everything from `other` after what's already here, returning where it ended up.
+    fn append(&mut self, other: DrawBatch2D) -> BatchOffsets {
+        let mut offsets = BatchOffsets {
+            shapes: self.shape_vertices.len(),
+            circles: self.circle_instances.len(),
+            ..Default::default()
+        };
+
+        let base_index = self.current_max_index;
+        self.shape_indices
+            .extend(other.shape_indices.iter().map(|index| index + base_index));
+        self.shape_vertices.extend(other.shape_vertices);
+        self.current_max_index += other.current_max_index;
+        self.circle_instances.extend(other.circle_instances);
+
+        for (texture, sprites) in other.sprite_draws {
+            let batch = self
+                .sprite_draws
+                .entry(texture)
+                .or_insert_with(|| SpriteDrawBatch {
+                    vertices: Vec::new(),
+                    indices: Vec::new(),
+                });
+            let start = batch.vertices.len();
+            batch
+                .indices
+                .extend(sprites.indices.iter().map(|index| index + start as u32));
+            batch.vertices.extend(sprites.vertices);
+            offsets.sprites.insert(texture, start);
+        }
+
+        for (texture, glyphs) in other.sdf_draws {
+            let batch = self.sdf_draws.entry(texture).or_default();
+            let start = batch.vertices.len();
+            batch
+                .indices
+                .extend(glyphs.indices.iter().map(|index| index + start as u32));
+            batch.vertices.extend(glyphs.vertices);
+            offsets.sdfs.insert(texture, start);
+        }
+
+        offsets
+    }
+}
+
+/// How far everything from a batch moved when it was appended to another, see
+/// `DrawBatch2D::append`.
+#[derive(Default)]
+struct BatchOffsets {
+    shapes: usize,
+    circles: usize,
+    sprites: HashMap<TextureRef, usize>,
+    sdfs: HashMap<TextureRef, usize>,
+}
+
+impl BatchOffsets {
+    fn shift(&self, kind: DrawItemKind) -> DrawItemKind {
+        let offset = |range: Range<usize>, by: usize| range.start + by..range.end + by;
+        match kind {
+            DrawItemKind::Shape(range) => DrawItemKind::Shape(offset(range, self.shapes)),
+            DrawItemKind::Circle(index) => DrawItemKind::Circle(index + self.circles),
+            DrawItemKind::Sprite(texture, range) => {
+                DrawItemKind::Sprite(texture, offset(range, self.sprites[&texture]))
+            }
+            DrawItemKind::Sdf(texture, range) => {
+                DrawItemKind::Sdf(texture, offset(range, self.sdfs[&texture]))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Section2D {
+    /// Only the batches of this are used, and they're written to the stencil buffer instead of
+    /// the screen.
+    mask: Option<DrawQueue2D>,
+    /// In the same coordinates as everything else in the queue, and already intersected with
+    /// the parent's.
+    clip: Option<Rect>,
+    parent: Option<usize>,
+    batches: [DrawBatch2D; BATCH_BLEND_MODES.len()],
+}
+
+#[derive(Clone, Debug)]
+struct DrawItem2D {
+    layer: f32,
+    z: f32,
+    section: Option<usize>,
+    batch: usize,
+    kind: DrawItemKind,
+    pick: Option<PickId>,
+    outline: Option<Color>,
+}
+
+#[derive(Clone, Debug)]
+enum DrawItemKind {
+    Shape(Range<usize>),
+    Circle(usize),
+    Sprite(TextureRef, Range<usize>),
+    Sdf(TextureRef, Range<usize>),
 }
 
+/// One batch per blend mode, drawn in this order rather than the order things were added in.
+/// Opaque things go first and alpha blended things go over them, which the depth buffer sorts
+/// out either way. Additive and multiplied things come last so they blend over the rest, but
+/// anything opaque or alpha blended that was added after them still hides them, even where
+/// it's see-through.
+const BATCH_BLEND_MODES: [BlendMode; 4] = [
+    BlendMode::Opaque,
+    BlendMode::Alpha,
+    BlendMode::Additive,
+    BlendMode::Multiply,
+];
+
+/// What a draw does with the stencil buffer, which is only used for masks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum Stencil2D {
+    #[default]
+    Off,
+    /// Draws nothing visible, just increments the stencil wherever it's equal to the value.
+    /// Writing a chain of nested masks like this leaves their overlap at the chain's length.
+    WriteMask(u8),
+    /// Only draws where the stencil is equal to the value.
+    Inside(u8),
+}
+
+fn batch_index(blend_mode: BlendMode) -> usize {
+    match blend_mode {
+        BlendMode::Opaque => 0,
+        BlendMode::Alpha => 1,
+        BlendMode::Additive => 2,
+        BlendMode::Multiply => 3,
+    }
+}
+
+#[derive(Clone)]
 struct SpriteDrawBatch {
     vertices: Vec<SpriteVertex>,
     indices: Vec<u32>,
@@ -30,10 +257,53 @@ struct SpriteDrawBatch {
 
 implement_vertex!(SpriteVertex, position, tex_coords, color);
 #[derive(Copy, Clone, Debug)]
-struct SpriteVertex {
+pub(crate) struct SpriteVertex {
+    pub position: [f32; 3],
+    pub tex_coords: [f32; 2],
+    pub color: [f32; 4],
+}
+
+#[derive(Clone, Default)]
+struct SdfDrawBatch {
+    vertices: Vec<SdfVertex>,
+    indices: Vec<u32>,
+}
+
+implement_vertex!(
+    SdfVertex,
+    position,
+    tex_coords,
+    color,
+    outline_color,
+    shadow_color,
+    effects,
+    tex_bounds
+);
+/// A corner of a glyph from a signed distance field, see `add_sdf_glyph`.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct SdfVertex {
     pub position: [f32; 3],
     pub tex_coords: [f32; 2],
     pub color: [f32; 4],
+    pub outline_color: [f32; 4],
+    pub shadow_color: [f32; 4],
+    /// Outline width and shadow softness in distance field units, then the shadow's offset in
+    /// texture coordinates.
+    pub effects: [f32; 4],
+    /// The glyph's rect in texture coordinates, so shadows don't pick up its neighbours.
+    pub tex_bounds: [f32; 4],
+}
+
+/// How a distance field glyph is shaded. Everything but the colors is in the units of
+/// [`SdfVertex::effects`].
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct SdfLook {
+    pub color: Color,
+    pub outline_width: f32,
+    pub outline_color: Color,
+    pub shadow_offset: Vec2,
+    pub shadow_softness: f32,
+    pub shadow_color: Color,
 }
 
 implement_vertex!(
@@ -60,12 +330,15 @@ pub struct Vertex3D {
     pub color: [f32; 4],
 }
 
-implement_vertex!(MaterialVertex3D, position, normal, tex_coords);
-#[derive(Copy, Clone, Debug)]
+implement_vertex!(MaterialVertex3D, position, normal, tex_coords, tangent);
+#[derive(Copy, Clone, Debug, Default)]
 pub struct MaterialVertex3D {
     pub position: [f32; 3],
     pub normal: [f32; 3],
     pub tex_coords: [f32; 2],
+    /// Points along +U, with the handedness of the bitangent in `w`. Leave it zeroed and
+    /// [`Mesh::new`](crate::object_3d::Mesh::new) works it out.
+    pub tangent: [f32; 4],
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -124,11 +397,16 @@ impl CircleInstance {
 impl DrawQueue2D {
     pub fn empty() -> Self {
         Self {
-            shape_vertices: vec![],
-            shape_indices: vec![],
-            current_max_index: 0,
-            circle_instances: vec![],
-            sprite_draws: HashMap::new(),
+            batches: Default::default(),
+            blend_mode: BlendMode::Alpha,
+            pick_id: None,
+            outline: None,
+            items: Vec::new(),
+            layer: 0.0,
+            uses_layers: false,
+            layers_sorted: false,
+            sections: Vec::new(),
+            section_stack: Vec::new(),
             current_z: 0.0,
             start_z: 0.0,
             z_increment: 0.001,
@@ -137,17 +415,180 @@ impl DrawQueue2D {
 
     pub fn with_z_config(start_z: f32, z_increment: f32) -> Self {
         Self {
-            shape_vertices: vec![],
-            shape_indices: vec![],
-            current_max_index: 0,
-            circle_instances: vec![],
-            sprite_draws: HashMap::new(),
+            batches: Default::default(),
+            blend_mode: BlendMode::Alpha,
+            pick_id: None,
+            outline: None,
+            items: Vec::new(),
+            layer: 0.0,
+            uses_layers: false,
+            layers_sorted: false,
+            sections: Vec::new(),
+            section_stack: Vec::new(),
             current_z: 0.0,
             start_z,
             z_increment,
         }
     }
 
+    pub fn blend_mode(&self) -> BlendMode {
+        self.blend_mode
+    }
+
+    /// Blend mode for everything added after this.
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
+    pub fn layer(&self) -> f32 {
+        self.layer
+    }
+
+    pub fn pick_id(&self) -> Option<PickId> {
+        self.pick_id
+    }
+
+    /// Pick id for everything added after this. Only used when picking is turned on.
+    pub fn set_pick_id(&mut self, pick_id: Option<PickId>) {
+        self.pick_id = pick_id;
+    }
+
+    pub fn outline(&self) -> Option<Color> {
+        self.outline
+    }
+
+    /// Outline color for everything added after this.
+    pub fn set_outline(&mut self, outline: Option<Color>) {
+        self.outline = outline;
+    }
+
+    pub fn has_outlines(&self) -> bool {
+        self.items.iter().any(|item| item.outline.is_some())
+    }
+
+    /// Layer for everything added after this. Lower layers are drawn below higher ones, and
+    /// things on the same layer keep the order they were added in. Fractional layers are fine,
+    /// so sorting world objects by their Y position works too.
+    pub fn set_layer(&mut self, layer: f32) {
+        self.layer = layer;
+        self.uses_layers |= layer != 0.0;
+    }
+
+    /// Everything added after this only shows up inside of `shape`, until `pop_mask` is called.
+    /// Masks can be nested, in which case things have to be inside of all of them.
+    pub fn push_mask(&mut self, shape: &impl Shape2D) {
+        let mut queue = DrawQueue2D::empty();
+        shape.add_to_draw_queue(&mut queue);
+
+        self.push_section(Some(queue), self.clip_rect());
+    }
+
+    pub fn pop_mask(&mut self) {
+        self.section_stack.pop();
+    }
+
+    /// How many masks are currently applied.
+    pub fn mask_depth(&self) -> usize {
+        self.section_stack
+            .last()
+            .map_or(0, |&section| self.mask_chain(section).len())
+    }
+
+    /// Everything added after this gets cut off outside of `rect`, until `pop_clip_rect` is
+    /// called. Nested clip rects intersect. Cheaper than a mask, but only does rectangles.
+    pub fn push_clip_rect(&mut self, rect: Rect) {
+        let clip = match self.clip_rect() {
+            Some(outer) => outer.intersect(rect),
+            None => rect,
+        };
+        self.push_section(None, Some(clip));
+    }
+
+    pub fn pop_clip_rect(&mut self) {
+        self.section_stack.pop();
+    }
+
+    /// The area everything is currently clipped to, if anything.
+    pub fn clip_rect(&self) -> Option<Rect> {
+        self.section_stack
+            .last()
+            .and_then(|&section| self.sections[section].clip)
+    }
+
+    fn push_section(&mut self, mask: Option<DrawQueue2D>, clip: Option<Rect>) {
+        self.sections.push(Section2D {
+            mask,
+            clip,
+            parent: self.section_stack.last().copied(),
+            batches: Default::default(),
+        });
+        self.section_stack.push(self.sections.len() - 1);
+    }
+
+    fn batch(&mut self) -> &mut DrawBatch2D {
+        let index = batch_index(self.blend_mode);
+        match self.section_stack.last() {
+            Some(&section) => &mut self.sections[section].batches[index],
+            None => &mut self.batches[index],
+        }
+    }
+
+    /// Every section with a mask around this one (including itself), outermost first.
+    fn mask_chain(&self, section: usize) -> Vec<usize> {
+        let mut chain = Vec::new();
+        let mut current = Some(section);
+        while let Some(index) = current {
+            if self.sections[index].mask.is_some() {
+                chain.push(index);
+            }
+            current = self.sections[index].parent;
+        }
+        chain.reverse();
+        chain
+    }
+
+    fn push_item(&mut self, z: f32, kind: DrawItemKind) {
+        self.items.push(DrawItem2D {
+            layer: self.layer,
+            z,
+            section: self.section_stack.last().copied(),
+            batch: batch_index(self.blend_mode),
+            kind,
+            pick: self.pick_id,
+            outline: self.outline,
+        });
+        self.layers_sorted = false;
+    }
+
+    /// Rewrites the z of everything so that it's ordered by layer first, then by its original
+    /// z. Does nothing unless a layer other than 0 was used.
+    fn apply_layers(&mut self) {
+        if !self.uses_layers || self.layers_sorted {
+            return;
+        }
+
+        with_frame_arena(|arena| {
+            let mut order = BumpVec::from_iter_in(0..self.items.len(), arena);
+            order.sort_by(|&a, &b| {
+                let (a, b) = (&self.items[a], &self.items[b]);
+                a.layer.total_cmp(&b.layer).then(a.z.total_cmp(&b.z))
+            });
+
+            for (rank, index) in order.into_iter().enumerate() {
+                let z = self.start_z + rank as f32 * self.z_increment;
+                let item = &self.items[index];
+                let batch = match item.section {
+                    Some(section) => &mut self.sections[section].batches[item.batch],
+                    None => &mut self.batches[item.batch],
+                };
+                batch.set_z(&item.kind, z);
+            }
+        });
+
+        // no need to do it again if this gets drawn twice
+        self.layers_sorted = true;
+    }
+
     pub fn current_z(&self) -> f32 {
         self.current_z
     }
@@ -167,23 +608,22 @@ impl DrawQueue2D {
     }
 
     pub fn add_shape_at_z(&mut self, shape: &impl Shape2D, z: f32) {
-        #[cfg(feature = "debugging")]
-        {
-            use crate::debugging::get_debug_info_mut;
+        debugger_add_drawn_objects(1);
 
-            let debug = get_debug_info_mut();
-            let frame = debug.current_frame_mut();
-            frame.drawn_objects += 1;
-        }
+        let batch = self.batch();
+        let start = batch.shape_vertices.len();
+        with_frame_arena(|arena| {
+            let (indices, vertices) = shape.points_in(batch.current_max_index, arena);
+            for vertex in &vertices {
+                batch.shape_vertices.push(vertex.to_3d(z));
+            }
 
-        let (mut indices, vertices) = shape.points(self.current_max_index);
+            batch.current_max_index += vertices.len() as u32;
+            batch.shape_indices.extend_from_slice(&indices);
+        });
 
-        for vertex in &vertices {
-            self.shape_vertices.push(vertex.to_3d(z));
-        }
-
-        self.current_max_index += vertices.len() as u32;
-        self.shape_indices.append(&mut indices);
+        let end = batch.shape_vertices.len();
+        self.push_item(z, DrawItemKind::Shape(start..end));
     }
 
     pub fn add_circle(&mut self, center: Vec2, radius: Vec2, color: Color) {
@@ -211,17 +651,15 @@ impl DrawQueue2D {
     }
 
     pub fn add_circle_at_z(&mut self, center: Vec2, radius: Vec2, color: Color, z: f32) {
-        #[cfg(feature = "debugging")]
-        {
-            use crate::debugging::get_debug_info_mut;
-
-            let debug = get_debug_info_mut();
-            let frame = debug.current_frame_mut();
-            frame.drawn_objects += 1;
-        }
+        debugger_add_drawn_objects(1);
 
-        self.circle_instances
+        let batch = self.batch();
+        let index = batch.circle_instances.len();
+        batch
+            .circle_instances
             .push(CircleInstance::new(center, z, radius, color));
+
+        self.push_item(z, DrawItemKind::Circle(index));
     }
 
     pub fn add_circle_with_outline_at_z(
@@ -233,23 +671,22 @@ impl DrawQueue2D {
         outline_color: Color,
         z: f32,
     ) {
-        #[cfg(feature = "debugging")]
-        {
-            use crate::debugging::get_debug_info_mut;
-
-            let debug = get_debug_info_mut();
-            let frame = debug.current_frame_mut();
-            frame.drawn_objects += 1;
-        }
+        debugger_add_drawn_objects(1);
 
-        self.circle_instances.push(CircleInstance::new_with_outline(
-            center,
-            z,
-            radius,
-            fill_color,
-            outline_thickness,
-            outline_color,
-        ));
+        let batch = self.batch();
+        let index = batch.circle_instances.len();
+        batch
+            .circle_instances
+            .push(CircleInstance::new_with_outline(
+                center,
+                z,
+                radius,
+                fill_color,
+                outline_thickness,
+                outline_color,
+            ));
+
+        self.push_item(z, DrawItemKind::Circle(index));
     }
 
     pub fn add_sprite(
@@ -273,20 +710,12 @@ impl DrawQueue2D {
     ) {
         debugger_add_drawn_objects(1);
 
-        let batch = self
-            .sprite_draws
-            .entry(texture)
-            .or_insert_with(|| SpriteDrawBatch {
-                vertices: Vec::new(),
-                indices: Vec::new(),
-            });
-
-        let base_index = batch.vertices.len() as u32;
-
         let (tex_min_x, tex_min_y, tex_max_x, tex_max_y) = if let Some(region) = region {
-            let tex = texture.get();
-            let tex_width = tex.gl_texture.width() as f32;
-            let tex_height = tex.gl_texture.height() as f32;
+            let Some(tex) = texture.get_or_report() else {
+                return;
+            };
+            let tex_width = tex.dimensions.x as f32;
+            let tex_height = tex.dimensions.y as f32;
 
             (
                 region.min.x / tex_width,
@@ -298,6 +727,17 @@ impl DrawQueue2D {
             (0.0, 0.0, 1.0, 1.0)
         };
 
+        let batch = self
+            .batch()
+            .sprite_draws
+            .entry(texture)
+            .or_insert_with(|| SpriteDrawBatch {
+                vertices: Vec::new(),
+                indices: Vec::new(),
+            });
+
+        let base_index = batch.vertices.len() as u32;
+
         let color_gpu = color.for_gpu();
         let mat = transform.matrix();
 
@@ -332,171 +772,844 @@ impl DrawQueue2D {
             base_index + 2,
             base_index + 3,
         ]);
+
+        let range = base_index as usize..base_index as usize + 4;
+        self.push_item(z, DrawItemKind::Sprite(texture, range));
     }
 
-    pub fn draw<T: Surface>(&mut self, frame: &mut T, projection: &Mat4) {
-        let state = get_state();
-        let display = &state.display;
+    /// Adds arbitrary textured triangles. Each vertex is a position and a texture coordinate,
+    /// with (0, 0) at the top left of the texture and (1, 1) at the bottom right.
+    pub fn add_textured_mesh(
+        &mut self,
+        texture: TextureRef,
+        vertices: &[(Vec2, Vec2)],
+        indices: &[u32],
+        color: Color,
+    ) {
+        self.add_textured_mesh_at_z(texture, vertices, indices, color, self.current_z);
+        self.current_z += self.z_increment;
+    }
 
-        let params = DrawParameters {
-            blend: Blend {
-                color: glium::BlendingFunction::Addition {
-                    source: glium::LinearBlendingFactor::SourceAlpha,
-                    destination: glium::LinearBlendingFactor::OneMinusSourceAlpha,
-                },
-                alpha: glium::BlendingFunction::Addition {
-                    source: glium::LinearBlendingFactor::One,
-                    destination: glium::LinearBlendingFactor::OneMinusSourceAlpha,
-                },
-                constant_value: (1.0, 1.0, 1.0, 1.0),
-            },
-            depth: Depth {
-                test: DepthTest::IfLess,
-                write: true,
-                ..Default::default()
-            },
-            ..Default::default()
+    pub fn add_textured_mesh_at_z(
+        &mut self,
+        texture: TextureRef,
+        vertices: &[(Vec2, Vec2)],
+        indices: &[u32],
+        color: Color,
+        z: f32,
+    ) {
+        let color = color.for_gpu();
+        let vertices = vertices.iter().map(|(position, uv)| SpriteVertex {
+            position: [position.x, position.y, z],
+            tex_coords: (*uv).into(),
+            color,
+        });
+        self.push_textured_mesh(texture, vertices, indices, z);
+    }
+
+    /// Like [`add_textured_mesh`](Self::add_textured_mesh), with a color for each vertex that's
+    /// blended across the triangles.
+    pub fn add_colored_textured_mesh(
+        &mut self,
+        texture: TextureRef,
+        vertices: &[(Vec2, Vec2, Color)],
+        indices: &[u32],
+    ) {
+        let z = self.current_z;
+        let vertices = vertices.iter().map(|(position, uv, color)| SpriteVertex {
+            position: [position.x, position.y, z],
+            tex_coords: (*uv).into(),
+            color: color.for_gpu(),
+        });
+        self.push_textured_mesh(texture, vertices, indices, z);
+        self.current_z += self.z_increment;
+    }
+
+    fn push_textured_mesh(
+        &mut self,
+        texture: TextureRef,
+        vertices: impl Iterator<Item = SpriteVertex>,
+        indices: &[u32],
+        z: f32,
+    ) {
+        debugger_add_drawn_objects(1);
+
+        let batch = self
+            .batch()
+            .sprite_draws
+            .entry(texture)
+            .or_insert_with(|| SpriteDrawBatch {
+                vertices: Vec::new(),
+                indices: Vec::new(),
+            });
+
+        let start = batch.vertices.len();
+        batch.vertices.extend(vertices);
+        batch
+            .indices
+            .extend(indices.iter().map(|index| index + start as u32));
+
+        let end = batch.vertices.len();
+        self.push_item(z, DrawItemKind::Sprite(texture, start..end));
+    }
+
+    /// Adds a glyph whose texture holds a signed distance field, which stays sharp at any
+    /// size. `uv` is the glyph's rect in texture coordinates.
+    pub(crate) fn add_sdf_glyph(
+        &mut self,
+        texture: TextureRef,
+        top_left: Vec2,
+        size: Vec2,
+        uv: Rect,
+        look: SdfLook,
+    ) {
+        let z = self.current_z;
+        self.current_z += self.z_increment;
+        debugger_add_drawn_objects(1);
+
+        let Rect { min, max } = uv;
+        let effects = [
+            look.outline_width,
+            look.shadow_softness,
+            look.shadow_offset.x,
+            look.shadow_offset.y,
+        ];
+
+        let batch = self.batch().sdf_draws.entry(texture).or_default();
+        let base_index = batch.vertices.len() as u32;
+
+        let corners = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(0.0, 1.0),
+        ];
+        for corner in corners {
+            let position = top_left + size * corner;
+            batch.vertices.push(SdfVertex {
+                position: [position.x, position.y, z],
+                tex_coords: (min + (max - min) * corner).to_array(),
+                color: look.color.for_gpu(),
+                outline_color: look.outline_color.for_gpu(),
+                shadow_color: look.shadow_color.for_gpu(),
+                effects,
+                tex_bounds: [min.x, min.y, max.x, max.y],
+            });
+        }
+
+        batch.indices.extend_from_slice(&[
+            base_index,
+            base_index + 1,
+            base_index + 2,
+            base_index,
+            base_index + 2,
+            base_index + 3,
+        ]);
+
+        let range = base_index as usize..base_index as usize + 4;
+        self.push_item(z, DrawItemKind::Sdf(texture, range));
+    }
+
+    /// Adds everything from `other` after what's already in this queue, as if it had been drawn
+    /// into it directly. `other` goes inside of whatever mask or clip rect is active, and takes
+    /// the current layer, pick id and outline unless it set its own.
+    pub(crate) fn append(&mut self, mut other: DrawQueue2D) {
+        let parent = self.section_stack.last().copied();
+        let outer_clip = self.clip_rect();
+        let section_offset = self.sections.len();
+
+        // `other` counted its z from its own start, so it gets moved to where this one is at
+        let base_z = self.current_z;
+        let z_scale = self.z_increment / other.z_increment;
+        for item in &mut other.items {
+            item.z = base_z + (item.z - other.start_z) * z_scale;
+            let batch = match item.section {
+                Some(section) => &mut other.sections[section].batches[item.batch],
+                None => &mut other.batches[item.batch],
+            };
+            batch.set_z(&item.kind, item.z);
+        }
+        self.current_z += (other.current_z - other.start_z) * z_scale;
+
+        for section in other.sections {
+            let clip = match (outer_clip, section.clip) {
+                (Some(outer), Some(clip)) => Some(outer.intersect(clip)),
+                (outer, clip) => clip.or(outer),
+            };
+            self.sections.push(Section2D {
+                parent: section.parent.map(|p| p + section_offset).or(parent),
+                clip,
+                ..section
+            });
+        }
+
+        // only the unsectioned batches land somewhere that already has things in it
+        let targets = match parent {
+            Some(section) => &mut self.sections[section].batches,
+            None => &mut self.batches,
         };
+        let offsets: Vec<BatchOffsets> = targets
+            .iter_mut()
+            .zip(other.batches)
+            .map(|(target, batch)| target.append(batch))
+            .collect();
+
+        for item in other.items {
+            let (section, kind) = match item.section {
+                Some(section) => (Some(section + section_offset), item.kind),
+                None => (parent, offsets[item.batch].shift(item.kind)),
+            };
+            self.items.push(DrawItem2D {
+                layer: if other.uses_layers {
+                    item.layer
+                } else {
+                    self.layer
+                },
+                section,
+                kind,
+                pick: item.pick.or(self.pick_id),
+                outline: item.outline.or(self.outline),
+                ..item
+            });
+        }
 
-        if !self.shape_vertices.is_empty() {
-            let vertex_buffer = VertexBuffer::new(display, &self.shape_vertices).unwrap();
-            let index_buffer = IndexBuffer::new(
-                display,
-                glium::index::PrimitiveType::TrianglesList,
-                &self.shape_indices,
-            )
-            .unwrap();
+        self.uses_layers |= other.uses_layers;
+        self.layers_sorted = false;
+    }
+
+    /// A copy with everything colored by its pick id, for drawing into the picking buffer.
+    /// Things without one are still drawn, so they hide what's behind them.
+    pub(crate) fn for_picking(&self) -> DrawQueue2D {
+        self.recolored(|item| Some(pick_color(item.pick)))
+    }
+
+    /// A copy with only outlined things, each solid in its outline color, for drawing into the
+    /// outline mask. Everything else is left out, so outlines go around what's hidden too.
+    pub(crate) fn for_outlining(&self) -> DrawQueue2D {
+        self.recolored(|item| item.outline.map(|color| color.for_gpu()))
+    }
+
+    /// A copy with every item drawn in one flat color, or squashed down to nothing where
+    /// `color` gives `None`.
+    fn recolored(&self, color: impl Fn(&DrawItem2D) -> Option<[f32; 4]>) -> DrawQueue2D {
+        let mut queue = self.clone();
 
-            let uniforms = uniform! {
-                transform: projection.to_cols_array_2d(),
+        for item in &self.items {
+            let color = color(item);
+            let batch = match item.section {
+                Some(section) => &mut queue.sections[section].batches[item.batch],
+                None => &mut queue.batches[item.batch],
             };
 
-            #[cfg(feature = "debugging")]
-            {
-                use crate::debugging::get_debug_info_mut;
+            match &item.kind {
+                DrawItemKind::Shape(range) => {
+                    for vertex in &mut batch.shape_vertices[range.clone()] {
+                        match color {
+                            Some(color) => vertex.color = color,
+                            None => vertex.position = [0.0; 3],
+                        }
+                    }
+                }
+                DrawItemKind::Circle(index) => {
+                    let circle = &mut batch.circle_instances[*index];
+                    match color {
+                        Some(color) => {
+                            circle.fill_color = color;
+                            circle.outline_color = color;
+                        }
+                        None => circle.radius = [0.0; 2],
+                    }
+                }
+                DrawItemKind::Sprite(texture, range) => {
+                    let sprites = batch.sprite_draws.get_mut(texture).unwrap();
+                    for vertex in &mut sprites.vertices[range.clone()] {
+                        match color {
+                            Some(color) => vertex.color = color,
+                            None => vertex.position = [0.0; 3],
+                        }
+                    }
+                }
+                DrawItemKind::Sdf(texture, range) => {
+                    let glyphs = batch.sdf_draws.get_mut(texture).unwrap();
+                    for vertex in &mut glyphs.vertices[range.clone()] {
+                        match color {
+                            Some(color) => vertex.color = color,
+                            None => vertex.position = [0.0; 3],
+                        }
+                    }
+                }
+            }
+        }
+
+        queue
+    }
+
+    pub fn draw<T: Surface>(&mut self, frame: &mut T, projection: &Mat4) {
+        self.draw_to(&mut SurfaceDrawTarget::new(frame), projection);
+    }
+
+    pub(crate) fn draw_to(&mut self, target: &mut impl DrawTarget2D, projection: &Mat4) {
+        self.apply_layers();
+        draw_batches(&self.batches, target, projection);
+
+        if self.sections.is_empty() {
+            return;
+        }
 
-                let debug = get_debug_info_mut();
-                let frame = debug.current_frame_mut();
-                frame.draw_calls += 1;
-                frame.vertex_count += vertex_buffer.len();
-                frame.index_count += index_buffer.len();
+        for (index, section) in self.sections.iter().enumerate() {
+            if section.batches.iter().all(DrawBatch2D::is_empty) {
+                continue;
             }
 
-            frame
-                .draw(
-                    &vertex_buffer,
-                    &index_buffer,
-                    FLAT_PROGRAM.get(),
-                    &uniforms,
-                    &params,
-                )
-                .unwrap();
-        }
-
-        if !self.circle_instances.is_empty() {
-            let quad_buffer = VertexBuffer::new(display, &UNIT_QUAD).unwrap();
-            let instance_buffer = VertexBuffer::dynamic(display, &self.circle_instances).unwrap();
-            let index_buffer = IndexBuffer::new(
-                display,
-                glium::index::PrimitiveType::TrianglesList,
-                &QUAD_INDICES,
-            )
-            .unwrap();
+            // masks are drawn unclipped, then clipped along with everything inside them
+            target.set_clip(None);
 
-            let uniforms = uniform! {
-                transform: projection.to_cols_array_2d(),
-            };
+            let chain = self.mask_chain(index);
+            if chain.is_empty() {
+                target.set_stencil(Stencil2D::Off);
+            } else {
+                // each section starts from scratch, otherwise overlapping masks would mess
+                // each other up
+                target.clear_stencil();
 
-            #[cfg(feature = "debugging")]
-            {
-                use crate::debugging::get_debug_info_mut;
+                for (depth, &outer) in chain.iter().enumerate() {
+                    let mask = self.sections[outer].mask.as_ref().unwrap();
+                    target.set_stencil(Stencil2D::WriteMask(depth as u8));
+                    draw_batches(&mask.batches, target, projection);
+                }
 
-                let debug = get_debug_info_mut();
-                let frame = debug.current_frame_mut();
-                frame.draw_calls += 1;
-                frame.vertex_count += quad_buffer.len() * self.circle_instances.len();
-                frame.index_count += index_buffer.len() * self.circle_instances.len();
+                target.set_stencil(Stencil2D::Inside(chain.len() as u8));
             }
 
-            frame
-                .draw(
-                    (&quad_buffer, instance_buffer.per_instance().unwrap()),
-                    &index_buffer,
-                    CIRCLE_PROGRAM.get(),
-                    &uniforms,
-                    &params,
-                )
-                .unwrap();
+            target.set_clip(section.clip.map(|clip| clip_to_ndc(clip, projection)));
+            draw_batches(&section.batches, target, projection);
+        }
+
+        target.set_stencil(Stencil2D::Off);
+        target.set_clip(None);
+    }
+
+    pub fn clear(&mut self) {
+        for batch in &mut self.batches {
+            batch.clear();
+        }
+        self.items.clear();
+        self.sections.clear();
+        self.section_stack.clear();
+        self.uses_layers = self.layer != 0.0;
+        self.current_z = self.start_z;
+    }
+}
+
+/// 2D draws recorded somewhere other than the main thread, to be drawn later with
+/// [`submit_draw_commands`](crate::prelude::submit_draw_commands). Tessellating lots of
+/// polygons is slow, and this lets it happen on as many threads as there are, see
+/// [`draw_parallel`](crate::prelude::draw_parallel).
+///
+/// Nothing here touches the engine, so it can't look up textures or cameras. Culling has to be
+/// done by whoever's recording.
+#[derive(Clone)]
+pub struct DrawCommands2D {
+    queue: DrawQueue2D,
+}
+
+impl Default for DrawCommands2D {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DrawCommands2D {
+    pub fn new() -> Self {
+        Self {
+            queue: DrawQueue2D::empty(),
         }
+    }
+
+    /// Calls `f` for every item, split over a thread per core, each recording into its own
+    /// commands. They come back in the same order as `items`.
+    pub fn record_parallel<T: Sync>(
+        items: &[T],
+        f: impl Fn(&T, &mut DrawCommands2D) + Sync,
+    ) -> Vec<DrawCommands2D> {
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let chunk_size = items.len().div_ceil(threads).max(1);
+        let f = &f;
+
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = items
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut commands = DrawCommands2D::new();
+                        for item in chunk {
+                            f(item, &mut commands);
+                        }
+                        commands
+                    })
+                })
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.items.is_empty()
+    }
 
-        for (texture_ref, batch) in &self.sprite_draws {
-            if !batch.vertices.is_empty() {
-                self.draw_sprite_batch(frame, projection, *texture_ref, batch);
+    pub fn draw(&mut self, shape: &impl Shape2D) {
+        shape.add_to_draw_queue(&mut self.queue);
+    }
+
+    /// Draws the whole texture on a 1x1 square moved by `transform`.
+    pub fn draw_texture(&mut self, texture: TextureRef, transform: Transform2D, color: Color) {
+        self.queue.add_sprite(texture, transform, color, None);
+    }
+
+    /// Arbitrary textured triangles. Each vertex is a position and a texture coordinate, with
+    /// (0, 0) at the top left of the texture and (1, 1) at the bottom right.
+    pub fn draw_textured_mesh(
+        &mut self,
+        texture: TextureRef,
+        vertices: &[(Vec2, Vec2)],
+        indices: &[u32],
+        color: Color,
+    ) {
+        self.queue
+            .add_textured_mesh(texture, vertices, indices, color);
+    }
+
+    /// Layer for everything recorded after this. If it's never set, everything goes on the layer
+    /// that's current when the commands are submitted.
+    pub fn set_layer(&mut self, layer: f32) {
+        self.queue.set_layer(layer);
+    }
+
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.queue.set_blend_mode(blend_mode);
+    }
+
+    /// Pick id for everything recorded after this. Things without one get the pick id that's
+    /// current when the commands are submitted.
+    pub fn set_pick_id(&mut self, pick_id: Option<PickId>) {
+        self.queue.set_pick_id(pick_id);
+    }
+
+    /// Outline color for everything recorded after this. Things without one get the outline
+    /// that's current when the commands are submitted.
+    pub fn set_outline(&mut self, outline: Option<Color>) {
+        self.queue.set_outline(outline);
+    }
+
+    pub(crate) fn into_queue(self) -> DrawQueue2D {
+        self.queue
+    }
+}
+
+/// Where a clip rect ends up on screen, from -1 to 1 on both axes.
+pub(crate) fn clip_to_ndc(clip: Rect, projection: &Mat4) -> Rect {
+    let a = projection.project_point3(clip.min.extend(0.0));
+    let b = projection.project_point3(clip.max.extend(0.0));
+    Rect::from_corners(a.truncate(), b.truncate())
+}
+
+/// Turns a clip rect from `clip_to_ndc` into pixels, counting from the bottom left like
+/// OpenGL does.
+pub(crate) fn ndc_to_scissor(ndc: Rect, dimensions: (u32, u32)) -> glium::Rect {
+    let size = Vec2::new(dimensions.0 as f32, dimensions.1 as f32);
+    let min = ((ndc.min * 0.5 + 0.5).clamp(Vec2::ZERO, Vec2::ONE) * size).round();
+    let max = ((ndc.max * 0.5 + 0.5).clamp(Vec2::ZERO, Vec2::ONE) * size).round();
+
+    glium::Rect {
+        left: min.x as u32,
+        bottom: min.y as u32,
+        width: (max.x - min.x) as u32,
+        height: (max.y - min.y) as u32,
+    }
+}
+
+fn draw_batches(
+    batches: &[DrawBatch2D; BATCH_BLEND_MODES.len()],
+    target: &mut impl DrawTarget2D,
+    projection: &Mat4,
+) {
+    for (batch, blend_mode) in batches.iter().zip(BATCH_BLEND_MODES) {
+        if !batch.shape_vertices.is_empty() {
+            target.draw_shapes(
+                &batch.shape_vertices,
+                &batch.shape_indices,
+                projection,
+                blend_mode,
+            );
+        }
+
+        if !batch.circle_instances.is_empty() {
+            target.draw_circles(&batch.circle_instances, projection, blend_mode);
+        }
+
+        for (texture, sprites) in &batch.sprite_draws {
+            if !sprites.vertices.is_empty() {
+                target.draw_sprites(
+                    *texture,
+                    &sprites.vertices,
+                    &sprites.indices,
+                    projection,
+                    blend_mode,
+                );
+            }
+        }
+
+        for (texture, glyphs) in &batch.sdf_draws {
+            if !glyphs.vertices.is_empty() {
+                target.draw_sdf_glyphs(
+                    *texture,
+                    &glyphs.vertices,
+                    &glyphs.indices,
+                    projection,
+                    blend_mode,
+                );
             }
         }
     }
+}
 
-    fn draw_sprite_batch<T: Surface>(
-        &self,
-        frame: &mut T,
+/// Where a `DrawQueue2D` sends its batches. Normally a glium surface, but tests swap in a mock
+/// so they can run without a GPU.
+pub(crate) trait DrawTarget2D {
+    fn draw_shapes(
+        &mut self,
+        vertices: &[Vertex3D],
+        indices: &[u32],
+        projection: &Mat4,
+        blend_mode: BlendMode,
+    );
+    fn draw_circles(
+        &mut self,
+        instances: &[CircleInstance],
         projection: &Mat4,
+        blend_mode: BlendMode,
+    );
+    fn draw_sprites(
+        &mut self,
         texture: TextureRef,
-        batch: &SpriteDrawBatch,
+        vertices: &[SpriteVertex],
+        indices: &[u32],
+        projection: &Mat4,
+        blend_mode: BlendMode,
+    );
+    fn draw_sdf_glyphs(
+        &mut self,
+        texture: TextureRef,
+        vertices: &[SdfVertex],
+        indices: &[u32],
+        projection: &Mat4,
+        blend_mode: BlendMode,
+    );
+    /// Applies to every draw after this.
+    fn set_stencil(&mut self, stencil: Stencil2D);
+    fn clear_stencil(&mut self);
+    /// Also applies to every draw after this. The rect goes from -1 to 1, see `clip_to_ndc`.
+    fn set_clip(&mut self, clip: Option<Rect>);
+}
+
+pub(crate) struct SurfaceDrawTarget<'a, T: Surface> {
+    surface: &'a mut T,
+    stencil: Stencil2D,
+    scissor: Option<glium::Rect>,
+    /// Draws pick ids instead of colors, see `DrawQueue2D::for_picking`.
+    picking: bool,
+}
+
+impl<'a, T: Surface> SurfaceDrawTarget<'a, T> {
+    pub fn new(surface: &'a mut T) -> Self {
+        Self {
+            surface,
+            stencil: Stencil2D::Off,
+            scissor: None,
+            picking: false,
+        }
+    }
+
+    /// Textures are only used to cut out their transparent parts, and nothing is blended, so
+    /// the ids come out exactly.
+    pub fn for_picking(surface: &'a mut T) -> Self {
+        Self {
+            picking: true,
+            ..Self::new(surface)
+        }
+    }
+
+    fn draw_parameters(&self, blend_mode: BlendMode) -> DrawParameters<'static> {
+        let params = DrawParameters {
+            scissor: self.scissor,
+            ..draw_parameters_2d(blend_mode, self.stencil)
+        };
+
+        if self.picking {
+            DrawParameters {
+                blend: Blend::default(),
+                ..params
+            }
+        } else {
+            params
+        }
+    }
+}
+
+pub(crate) fn draw_parameters_2d(
+    blend_mode: BlendMode,
+    stencil: Stencil2D,
+) -> DrawParameters<'static> {
+    let normal = matches!(blend_mode, BlendMode::Opaque | BlendMode::Alpha);
+
+    let params = DrawParameters {
+        blend: if blend_mode == BlendMode::Alpha {
+            NORMAL_BLEND_2D
+        } else {
+            blend_mode.to_glium()
+        },
+        depth: Depth {
+            test: DepthTest::IfLess,
+            // additive and multiplied things shouldn't hide what's drawn after them
+            write: normal,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    match stencil {
+        Stencil2D::Off => params,
+        Stencil2D::WriteMask(value) => DrawParameters {
+            color_mask: (false, false, false, false),
+            depth: Depth::default(),
+            stencil: stencil_2d(value, StencilOperation::Increment),
+            ..params
+        },
+        Stencil2D::Inside(value) => DrawParameters {
+            stencil: stencil_2d(value, StencilOperation::Keep),
+            ..params
+        },
+    }
+}
+
+/// Passes where the stencil equals `value`, for both windings since 2D shapes can have either.
+fn stencil_2d(value: u8, on_pass: StencilOperation) -> Stencil {
+    let test = StencilTest::IfEqual { mask: 0xff };
+
+    Stencil {
+        test_clockwise: test,
+        reference_value_clockwise: value as i32,
+        depth_pass_operation_clockwise: on_pass,
+        test_counter_clockwise: test,
+        reference_value_counter_clockwise: value as i32,
+        depth_pass_operation_counter_clockwise: on_pass,
+        ..Default::default()
+    }
+}
+
+const NORMAL_BLEND_2D: Blend = Blend {
+    color: glium::BlendingFunction::Addition {
+        source: glium::LinearBlendingFactor::SourceAlpha,
+        destination: glium::LinearBlendingFactor::OneMinusSourceAlpha,
+    },
+    alpha: glium::BlendingFunction::Addition {
+        source: glium::LinearBlendingFactor::One,
+        destination: glium::LinearBlendingFactor::OneMinusSourceAlpha,
+    },
+    constant_value: (1.0, 1.0, 1.0, 1.0),
+};
+
+impl<T: Surface> DrawTarget2D for SurfaceDrawTarget<'_, T> {
+    fn draw_shapes(
+        &mut self,
+        vertices: &[Vertex3D],
+        indices: &[u32],
+        projection: &Mat4,
+        blend_mode: BlendMode,
+    ) {
+        let buffers = &mut get_state().draw_buffers;
+        let (Some(vertex_buffer), Some(index_buffer)) = (
+            buffers.shapes.upload(vertices),
+            buffers.indices.upload(indices),
+        ) else {
+            return;
+        };
+
+        let uniforms = uniform! {
+            transform: projection.to_cols_array_2d(),
+        };
+
+        debugger_add_draw_calls(1);
+        debugger_add_vertices(vertex_buffer.len());
+        debugger_add_indices(index_buffer.len());
+
+        self.surface
+            .draw(
+                vertex_buffer,
+                &index_buffer,
+                FLAT_PROGRAM.get(),
+                &uniforms,
+                &self.draw_parameters(blend_mode),
+            )
+            .or_report();
+    }
+
+    fn draw_circles(
+        &mut self,
+        instances: &[CircleInstance],
+        projection: &Mat4,
+        blend_mode: BlendMode,
     ) {
-        let state = get_state();
-        let display = &state.display;
-
-        let texture = texture.get();
-        let vertex_buffer = VertexBuffer::new(display, &batch.vertices).unwrap();
-        let index_buffer = IndexBuffer::new(
-            display,
-            glium::index::PrimitiveType::TrianglesList,
-            &batch.indices,
-        )
-        .unwrap();
+        let buffers = &mut get_state().draw_buffers;
+        let (Some(instance_buffer), Some((quad_buffer, index_buffer))) =
+            (buffers.circles.upload(instances), buffers.quad.get())
+        else {
+            return;
+        };
 
         let uniforms = uniform! {
-            tex: texture.gl_texture.sampled().minify_filter(texture.minify_filter).magnify_filter(texture.magnify_filter),
+            transform: projection.to_cols_array_2d(),
+        };
+
+        let Some(per_instance) = instance_buffer
+            .per_instance()
+            .map_err(|_| anyhow::anyhow!("instancing isn't supported by this GPU"))
+            .or_report()
+        else {
+            return;
+        };
+
+        debugger_add_draw_calls(1);
+        debugger_add_vertices(quad_buffer.len() * instances.len());
+        debugger_add_indices(index_buffer.len() * instances.len());
+
+        self.surface
+            .draw(
+                (quad_buffer, per_instance),
+                index_buffer,
+                CIRCLE_PROGRAM.get(),
+                &uniforms,
+                &self.draw_parameters(blend_mode),
+            )
+            .or_report();
+    }
+
+    fn draw_sprites(
+        &mut self,
+        texture: TextureRef,
+        vertices: &[SpriteVertex],
+        indices: &[u32],
+        projection: &Mat4,
+        blend_mode: BlendMode,
+    ) {
+        let Some(texture) = texture.get_or_report() else {
+            return;
+        };
+        let buffers = &mut get_state().draw_buffers;
+        let (Some(vertex_buffer), Some(index_buffer)) = (
+            buffers.sprites.upload(vertices),
+            buffers.indices.upload(indices),
+        ) else {
+            return;
+        };
+
+        let uniforms = uniform! {
+            tex: texture.sampled(),
             projection: projection.to_cols_array_2d()
         };
 
-        let params = DrawParameters {
-            blend: Blend::alpha_blending(),
-            depth: Depth {
-                test: DepthTest::IfLess,
-                write: true,
-                ..Default::default()
-            },
-            ..Default::default()
+        let mut params = self.draw_parameters(blend_mode);
+        if blend_mode == BlendMode::Alpha && !self.picking {
+            params.blend = Blend::alpha_blending();
+        }
+        let program = if self.picking {
+            PICK_PROGRAM
+        } else {
+            TEXTURED_PROGRAM
+        };
+
+        debugger_add_draw_calls(1);
+        debugger_add_vertices(vertex_buffer.len());
+        debugger_add_indices(index_buffer.len());
+
+        self.surface
+            .draw(
+                vertex_buffer,
+                &index_buffer,
+                program.get(),
+                &uniforms,
+                &params,
+            )
+            .or_report();
+    }
+
+    fn draw_sdf_glyphs(
+        &mut self,
+        texture: TextureRef,
+        vertices: &[SdfVertex],
+        indices: &[u32],
+        projection: &Mat4,
+        blend_mode: BlendMode,
+    ) {
+        let Some(texture) = texture.get_or_report() else {
+            return;
+        };
+        let buffers = &mut get_state().draw_buffers;
+        let (Some(vertex_buffer), Some(index_buffer)) = (
+            buffers.glyphs.upload(vertices),
+            buffers.indices.upload(indices),
+        ) else {
+            return;
+        };
+
+        // distance fields only work with linear filtering
+        let uniforms = uniform! {
+            tex: texture.gl_texture.sampled()
+                .minify_filter(glium::uniforms::MinifySamplerFilter::Linear)
+                .magnify_filter(glium::uniforms::MagnifySamplerFilter::Linear),
+            projection: projection.to_cols_array_2d()
         };
 
-        #[cfg(feature = "debugging")]
-        {
-            use crate::debugging::get_debug_info_mut;
-            let debug = get_debug_info_mut();
-            let frame_info = debug.current_frame_mut();
-            frame_info.draw_calls += 1;
-            frame_info.vertex_count += vertex_buffer.len();
-            frame_info.index_count += index_buffer.len();
+        let mut params = self.draw_parameters(blend_mode);
+        if blend_mode == BlendMode::Alpha && !self.picking {
+            params.blend = Blend::alpha_blending();
         }
+        // inside of glyphs is above 0.5, so the pick program's cutoff works for them too
+        let program = if self.picking {
+            PICK_PROGRAM
+        } else {
+            SDF_PROGRAM
+        };
 
-        frame
+        debugger_add_draw_calls(1);
+        debugger_add_vertices(vertex_buffer.len());
+        debugger_add_indices(index_buffer.len());
+
+        self.surface
             .draw(
-                &vertex_buffer,
+                vertex_buffer,
                 &index_buffer,
-                TEXTURED_PROGRAM.get(),
+                program.get(),
                 &uniforms,
                 &params,
             )
-            .unwrap();
+            .or_report();
     }
 
-    pub fn clear(&mut self) {
-        self.shape_vertices.clear();
-        self.shape_indices.clear();
-        self.current_max_index = 0;
-        self.circle_instances.clear();
-        self.sprite_draws.clear();
-        self.current_z = self.start_z;
+    fn set_stencil(&mut self, stencil: Stencil2D) {
+        self.stencil = stencil;
+    }
+
+    fn clear_stencil(&mut self) {
+        self.surface.clear_stencil(0);
+    }
+
+    fn set_clip(&mut self, clip: Option<Rect>) {
+        let dimensions = self.surface.get_dimensions();
+        self.scissor = clip.map(|clip| ndc_to_scissor(clip, dimensions));
     }
 }
@@ -1,19 +1,35 @@
-use bevy_math::Mat4;
-use glium::{DrawParameters, Surface};
+use bevy_math::{Mat4, Vec3};
+use glium::{DrawParameters, Surface, uniform};
 use rand::Rng;
 
 use crate::api::{
     debugger_add_draw_calls, debugger_add_drawn_objects, debugger_add_indices,
     debugger_add_vertices,
 };
+use crate::atmosphere::Fog;
+use crate::color::Color;
+#[cfg(feature = "debugging")]
+use crate::debugging::DebugRenderMode;
+use crate::error::OrReport;
 use crate::get_state;
-use crate::materials::Material;
+use crate::materials::{BlendMode, Material};
 use crate::object_3d::Object3D;
 use crate::object_3d::Object3DRef;
+use crate::particles_3d::ParticleBatch3D;
+use crate::picking::{PickId, pick_color};
 use crate::prelude::Transform3D;
+#[cfg(feature = "debugging")]
+use crate::programs::DEBUG_3D_PROGRAM;
+use crate::programs::{PARTICLE_3D_PROGRAM, PICK_3D_PROGRAM};
 
 pub struct DrawQueue3D {
     pub(crate) objects: Vec<ObjectToDraw>,
+    /// The pick id of each of `objects`.
+    pub(crate) pick_ids: Vec<Option<PickId>>,
+    pick_id: Option<PickId>,
+    /// The outline color of each of `objects`.
+    pub(crate) outlines: Vec<Option<Color>>,
+    outline: Option<Color>,
 }
 
 pub enum ObjectToDraw {
@@ -23,14 +39,42 @@ pub enum ObjectToDraw {
         transforms: Vec<Transform3D>,
     },
     WithTransform(Object3DRef, Transform3D),
+    Particles(ParticleBatch3D),
 }
 
 impl DrawQueue3D {
     pub fn empty() -> Self {
-        Self { objects: vec![] }
+        Self {
+            objects: vec![],
+            pick_ids: vec![],
+            pick_id: None,
+            outlines: vec![],
+            outline: None,
+        }
+    }
+
+    pub fn push(&mut self, object: ObjectToDraw) {
+        self.objects.push(object);
+        self.pick_ids.push(self.pick_id);
+        self.outlines.push(self.outline);
+    }
+
+    /// Pick id for everything added after this. Only used when picking is turned on.
+    pub fn set_pick_id(&mut self, pick_id: Option<PickId>) {
+        self.pick_id = pick_id;
     }
 
-    pub fn draw<T: Surface>(&mut self, frame: &mut T, view_proj: &Mat4) {
+    /// Outline color for everything added after this.
+    pub fn set_outline(&mut self, outline: Option<Color>) {
+        self.outline = outline;
+    }
+
+    pub fn has_outlines(&self) -> bool {
+        self.outlines.iter().any(Option::is_some)
+    }
+
+    /// With `hdr`, lit materials leave tonemapping to the end of the frame.
+    pub fn draw<T: Surface>(&mut self, frame: &mut T, view_proj: &Mat4, hdr: bool) {
         let state = get_state();
 
         let params = DrawParameters {
@@ -47,6 +91,8 @@ impl DrawQueue3D {
         let time = state.time;
         let random_number: f32 = state.rng.random();
         let screen_size = state.window_size();
+        let skybox = state.skybox;
+        let fog = state.fog;
 
         let set_common_uniforms = |material: &mut Material, mut transform: Transform3D| {
             material.set_mat4("view_proj_matrix", *view_proj);
@@ -57,15 +103,47 @@ impl DrawQueue3D {
             material.set_float("random", random_number);
             material.set_vec2("screen_size", screen_size);
             material.set_vec3("camera_pos", state.camera_3d.eye);
+            material.set_float("hdr", if hdr { 1.0 } else { 0.0 });
+
+            match skybox {
+                Some(skybox) => {
+                    material.set_texture("environment_map", skybox.texture);
+                    material.set_float("environment_strength", skybox.lighting_strength);
+                }
+                None => material.set_float("environment_strength", 0.0),
+            }
+            Fog::set_uniforms(fog, material);
         };
 
-        let draw_object = |frame: &mut T, object: &mut Object3D, transform: Transform3D| {
-            let material = object.material.get_mut();
+        let draw_object = |frame: &mut T, object: Object3DRef, transform: Transform3D| {
+            #[cfg(feature = "debugging")]
+            {
+                let mode = state.debug_info.render_modes.mode_for(object);
+                if mode != DebugRenderMode::Shaded {
+                    draw_debug_object(frame, &object, transform, mode, view_proj);
+                    return;
+                }
+            }
+
+            let Some(object) = object.get_mut_or_report() else {
+                return;
+            };
+            let Some(material) = object.material.get_mut_or_report() else {
+                return;
+            };
             set_common_uniforms(material, transform);
-            let program = material.program.get();
+            let Some(program) = material.program.get_or_report() else {
+                return;
+            };
 
+            let blend_mode = material.blend_mode;
             let default_params = DrawParameters {
-                backface_culling: object.transform.desired_culling_mode(),
+                backface_culling: transform.desired_culling_mode(),
+                blend: blend_mode.to_glium(),
+                depth: glium::Depth {
+                    write: !blend_mode.is_transparent(),
+                    ..params.depth
+                },
                 ..params.clone()
             };
             let params = object
@@ -87,27 +165,274 @@ impl DrawQueue3D {
                     material,
                     params,
                 )
-                .unwrap();
+                .or_report();
         };
 
-        for object in std::mem::take(&mut self.objects).iter_mut() {
-            match object {
-                ObjectToDraw::Many { object, transforms } => {
-                    let object = object.get_mut();
-                    for transform in transforms.iter_mut() {
-                        draw_object(frame, object, *transform);
-                    }
-                }
-                ObjectToDraw::Single(object) => {
-                    let object = object.get_mut();
-                    let transform = object.transform;
-                    draw_object(frame, object, transform);
-                }
-                ObjectToDraw::WithTransform(object, transform) => {
-                    let object = object.get_mut();
-                    draw_object(frame, object, *transform);
+        let mut opaque = Vec::new();
+        let mut transparent = Vec::new();
+        let mut particles = Vec::new();
+
+        self.pick_ids.clear();
+        self.outlines.clear();
+        for object in std::mem::take(&mut self.objects) {
+            let instances: Vec<_> = match object {
+                ObjectToDraw::Many { object, transforms } => transforms
+                    .into_iter()
+                    .map(|transform| (object, transform))
+                    .collect(),
+                ObjectToDraw::Single(object) => vec![(object, object.transform)],
+                ObjectToDraw::WithTransform(object, transform) => vec![(object, transform)],
+                ObjectToDraw::Particles(batch) => {
+                    particles.push(batch);
+                    continue;
                 }
+            };
+
+            if object_is_transparent(instances.first()) {
+                transparent.extend(instances);
+            } else {
+                opaque.extend(instances);
+            }
+        }
+
+        let eye = state.camera_3d.eye;
+        let mut transparent: Vec<_> = transparent
+            .into_iter()
+            .map(|(object, mut transform)| {
+                let bounds = object.mesh.bounds;
+                let center = transform.transformed_point((bounds.min + bounds.max) * 0.5);
+                (center.distance_squared(eye), object, transform)
+            })
+            .collect();
+        transparent.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        for (object, transform) in opaque {
+            draw_object(frame, object, transform);
+        }
+
+        for (_, object, transform) in transparent {
+            draw_object(frame, object, transform);
+        }
+
+        for batch in particles {
+            draw_particles(frame, batch, view_proj);
+        }
+    }
+}
+
+/// Draws an object with the debug program instead of its material.
+#[cfg(feature = "debugging")]
+fn draw_debug_object<T: Surface>(
+    frame: &mut T,
+    object: &Object3D,
+    mut transform: Transform3D,
+    mode: DebugRenderMode,
+    view_proj: &Mat4,
+) {
+    let uniforms = uniform! {
+        view_proj_matrix: view_proj.to_cols_array_2d(),
+        model_matrix: transform.matrix().to_cols_array_2d(),
+        normal_matrix: transform.into_normal_matrix().to_cols_array_2d(),
+        debug_mode: mode.shader_index(),
+    };
+
+    let mut params = DrawParameters {
+        backface_culling: transform.desired_culling_mode(),
+        depth: glium::Depth {
+            test: glium::DepthTest::IfLess,
+            write: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    match mode {
+        DebugRenderMode::Wireframe => params.polygon_mode = glium::PolygonMode::Line,
+        DebugRenderMode::Overdraw => {
+            params.depth = glium::Depth::default();
+            params.blend = BlendMode::Additive.to_glium();
+        }
+        _ => {}
+    }
+
+    debugger_add_vertices(object.mesh.vertices.len());
+    debugger_add_indices(object.mesh.indices.len());
+    debugger_add_drawn_objects(1);
+    debugger_add_draw_calls(1);
+
+    frame
+        .draw(
+            &object.mesh.vertices,
+            &object.mesh.indices,
+            DEBUG_3D_PROGRAM.get(),
+            &uniforms,
+            &params,
+        )
+        .or_report();
+}
+
+/// Draws one emitter's particles as quads facing the camera. They're tested against the depth
+/// buffer, but don't write to it, so they don't cut holes in each other.
+fn draw_particles<T: Surface>(frame: &mut T, mut batch: ParticleBatch3D, view_proj: &Mat4) {
+    let state = get_state();
+    let camera = &state.camera_3d;
+
+    let forward = (camera.target - camera.eye).normalize_or(Vec3::NEG_Z);
+    let right = forward.cross(camera.up).normalize_or(Vec3::X);
+    let up = right.cross(forward);
+
+    if batch.blend_mode != BlendMode::Additive {
+        let eye = camera.eye;
+        batch.instances.sort_by(|a, b| {
+            let a = Vec3::from(a.center).distance_squared(eye);
+            let b = Vec3::from(b.center).distance_squared(eye);
+            b.total_cmp(&a)
+        });
+    }
+
+    let draw_buffers = &mut get_state().draw_buffers;
+    let (Some(instance_buffer), Some((quad_buffer, index_buffer))) = (
+        draw_buffers.particles_3d.upload(&batch.instances),
+        draw_buffers.quad.get(),
+    ) else {
+        return;
+    };
+
+    let params = DrawParameters {
+        blend: batch.blend_mode.to_glium(),
+        depth: glium::Depth {
+            test: glium::DepthTest::IfLess,
+            write: false,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    debugger_add_draw_calls(1);
+    debugger_add_drawn_objects(batch.instances.len());
+    debugger_add_vertices(quad_buffer.len() * batch.instances.len());
+    debugger_add_indices(index_buffer.len() * batch.instances.len());
+
+    let Some(per_instance) = instance_buffer
+        .per_instance()
+        .map_err(|_| anyhow::anyhow!("instancing isn't supported by this GPU"))
+        .or_report()
+    else {
+        return;
+    };
+    let buffers = (quad_buffer, per_instance);
+    let program = PARTICLE_3D_PROGRAM.get();
+    let view_proj_matrix = view_proj.to_cols_array_2d();
+    let (camera_right, camera_up) = (right.to_array(), up.to_array());
+    let camera_pos = camera.eye.to_array();
+    let (fog_mode, fog_color, fog_start, fog_end, fog_density) = Fog::uniform_values(state.fog);
+    match batch.texture {
+        Some(texture) => {
+            let uniforms = uniform! {
+                view_proj_matrix: view_proj_matrix,
+                camera_right: camera_right,
+                camera_up: camera_up,
+                tex: texture.sampled(),
+                use_texture: 1.0f32,
+                camera_pos: camera_pos,
+                fog_mode: fog_mode,
+                fog_color: fog_color,
+                fog_start: fog_start,
+                fog_end: fog_end,
+                fog_density: fog_density,
+            };
+            frame
+                .draw(buffers, index_buffer, program, &uniforms, &params)
+                .or_report();
+        }
+        None => {
+            let uniforms = uniform! {
+                view_proj_matrix: view_proj_matrix,
+                camera_right: camera_right,
+                camera_up: camera_up,
+                use_texture: 0.0f32,
+                camera_pos: camera_pos,
+                fog_mode: fog_mode,
+                fog_color: fog_color,
+                fog_start: fog_start,
+                fog_end: fog_end,
+                fog_density: fog_density,
+            };
+            frame
+                .draw(buffers, index_buffer, program, &uniforms, &params)
+                .or_report();
+        }
+    }
+}
+
+impl DrawQueue3D {
+    /// Draws every object in the color of its pick id, without its material. Leaves the queue
+    /// as it is, so it can still be drawn normally after.
+    pub(crate) fn draw_for_picking<T: Surface>(&self, frame: &mut T, view_proj: &Mat4) {
+        let colors = self.pick_ids.iter().map(|pick| Some(pick_color(*pick)));
+        self.draw_flat(frame, view_proj, colors);
+    }
+
+    /// Draws only the outlined objects, each solid in its outline color, for the outline mask.
+    pub(crate) fn draw_for_outlining<T: Surface>(&self, frame: &mut T, view_proj: &Mat4) {
+        let colors = self
+            .outlines
+            .iter()
+            .map(|outline| outline.map(|color| color.for_gpu()));
+        self.draw_flat(frame, view_proj, colors);
+    }
+
+    /// Draws each object in one color, skipping the ones where it's `None`.
+    fn draw_flat<T: Surface>(
+        &self,
+        frame: &mut T,
+        view_proj: &Mat4,
+        colors: impl Iterator<Item = Option<[f32; 4]>>,
+    ) {
+        let params = DrawParameters {
+            depth: glium::Depth {
+                test: glium::DepthTest::IfLess,
+                write: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        for (object, color) in self.objects.iter().zip(colors) {
+            let Some(color) = color else {
+                continue;
+            };
+            let (object, transforms) = match object {
+                ObjectToDraw::Single(object) => (*object, vec![object.transform]),
+                ObjectToDraw::Many { object, transforms } => (*object, transforms.clone()),
+                ObjectToDraw::WithTransform(object, transform) => (*object, vec![*transform]),
+                // see through, and too small to aim at
+                ObjectToDraw::Particles(_) => continue,
+            };
+
+            for mut transform in transforms {
+                let uniforms = uniform! {
+                    view_proj_matrix: view_proj.to_cols_array_2d(),
+                    model_matrix: transform.matrix().to_cols_array_2d(),
+                    pick_color: color,
+                };
+
+                frame
+                    .draw(
+                        &object.mesh.vertices,
+                        &object.mesh.indices,
+                        PICK_3D_PROGRAM.get(),
+                        &uniforms,
+                        &DrawParameters {
+                            backface_culling: transform.desired_culling_mode(),
+                            ..params.clone()
+                        },
+                    )
+                    .or_report();
             }
         }
     }
 }
+
+fn object_is_transparent(instance: Option<&(Object3DRef, Transform3D)>) -> bool {
+    instance.is_some_and(|(object, _)| object.material.blend_mode.is_transparent())
+}
@@ -0,0 +1,410 @@
+use std::{
+    any::{Any, TypeId},
+    cell::UnsafeCell,
+    collections::HashMap,
+    marker::PhantomData,
+};
+
+use crate::get_state;
+
+/// Something in the game. It's only an id: everything about it is in its components.
+///
+/// Ids of despawned entities get reused, but with a new generation, so an old `Entity` never
+/// refers to whatever took its place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    pub fn index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+trait ComponentStorage: Any {
+    fn remove(&mut self, index: usize);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// One component type for every entity, indexed by entity index. The `UnsafeCell` lets a query
+/// hand out `&mut` to several storages at once; [`Entities::query`] makes sure those never
+/// alias.
+struct Storage<T>(UnsafeCell<Vec<Option<T>>>);
+
+impl<T> Storage<T> {
+    /// Points at one entity's slot without making a reference to the whole `Vec`, which would
+    /// alias the items a query already handed out for other entities.
+    fn slot(&self, index: usize) -> Option<*mut Option<T>> {
+        let data = self.0.get();
+        // SAFETY: the `Vec` itself is only touched here and by `&mut Entities` methods, so these
+        // short-lived borrows of it never overlap another one
+        unsafe { (index < (*data).len()).then(|| (*data).as_mut_ptr().add(index)) }
+    }
+}
+
+impl<T: 'static> ComponentStorage for Storage<T> {
+    fn remove(&mut self, index: usize) {
+        if let Some(slot) = self.0.get_mut().get_mut(index) {
+            *slot = None;
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A lightweight entity-component store: entities are ids, and each can have at most one
+/// component of each type. Saves keeping a handful of parallel `Vec`s in sync by hand.
+///
+/// The engine keeps one of these, used by the free functions like [`spawn`] and [`query`], but
+/// they work fine on their own too.
+#[derive(Default)]
+pub struct Entities {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    storages: HashMap<TypeId, Box<dyn ComponentStorage>>,
+    pending_despawns: Vec<Entity>,
+}
+
+impl Entities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        if let Some(index) = self.free.pop() {
+            self.alive[index as usize] = true;
+            return Entity {
+                index,
+                generation: self.generations[index as usize],
+            };
+        }
+
+        self.generations.push(0);
+        self.alive.push(true);
+        Entity {
+            index: self.generations.len() as u32 - 1,
+            generation: 0,
+        }
+    }
+
+    /// Removes the entity and all its components. Returns whether it was alive.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        let index = entity.index();
+        for storage in self.storages.values_mut() {
+            storage.remove(index);
+        }
+        self.alive[index] = false;
+        self.generations[index] += 1;
+        self.free.push(entity.index);
+        true
+    }
+
+    /// Despawns the entity on the next [`flush`](Self::flush), so it's safe to call while
+    /// looping over a query.
+    pub fn despawn_later(&mut self, entity: Entity) {
+        self.pending_despawns.push(entity);
+    }
+
+    /// Applies everything that was put off until later. The engine does this for its own
+    /// entities at the end of every frame.
+    pub fn flush(&mut self) {
+        for entity in std::mem::take(&mut self.pending_despawns) {
+            self.despawn(entity);
+        }
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.index();
+        self.alive.get(index).copied().unwrap_or(false)
+            && self.generations[index] == entity.generation
+    }
+
+    /// How many entities are alive.
+    pub fn len(&self) -> usize {
+        self.generations.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Despawns everything.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Adds a component, replacing any the entity already has of the same type. Does nothing if
+    /// the entity was despawned.
+    pub fn insert<T: 'static>(&mut self, entity: Entity, component: T) {
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        let len = self.generations.len();
+        let data = self.storage_mut::<T>().get_mut();
+        if data.len() < len {
+            data.resize_with(len, || None);
+        }
+        data[entity.index()] = Some(component);
+    }
+
+    pub fn remove<T: 'static>(&mut self, entity: Entity) -> Option<T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        self.storages
+            .get_mut(&TypeId::of::<T>())?
+            .as_any_mut()
+            .downcast_mut::<Storage<T>>()?
+            .0
+            .get_mut()
+            .get_mut(entity.index())?
+            .take()
+    }
+
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        // SAFETY: shared access to self, so nothing can be mutating the storage
+        let data = unsafe { &*self.storage::<T>()?.0.get() };
+        data.get(entity.index())?.as_ref()
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        self.storages
+            .get_mut(&TypeId::of::<T>())?
+            .as_any_mut()
+            .downcast_mut::<Storage<T>>()?
+            .0
+            .get_mut()
+            .get_mut(entity.index())?
+            .as_mut()
+    }
+
+    pub fn has<T: 'static>(&self, entity: Entity) -> bool {
+        self.get::<T>(entity).is_some()
+    }
+
+    /// Every entity that has all the components in `Q`, along with them. `Q` is a reference or
+    /// tuple of references, mutable or not, and can include [`Entity`] to get the id too:
+    ///
+    /// ```ignore
+    /// for (position, velocity) in entities.query::<(&mut Position, &Velocity)>() {
+    ///     position.0 += velocity.0 * delta_time();
+    /// }
+    /// ```
+    ///
+    /// Panics if a component type is asked for mutably more than once, or mutably and not.
+    pub fn query<Q: Query>(&mut self) -> QueryIter<'_, Q> {
+        let mut accesses = Vec::new();
+        Q::accesses(&mut accesses);
+        for (i, (type_id, mutable)) in accesses.iter().enumerate() {
+            let clashes = accesses[i + 1..]
+                .iter()
+                .any(|(other, other_mutable)| other == type_id && (*mutable || *other_mutable));
+            assert!(
+                !clashes,
+                "a query can't borrow the same component mutably more than once"
+            );
+        }
+
+        QueryIter {
+            entities: self,
+            index: 0,
+            marker: PhantomData,
+        }
+    }
+
+    fn storage<T: 'static>(&self) -> Option<&Storage<T>> {
+        self.storages
+            .get(&TypeId::of::<T>())?
+            .as_any()
+            .downcast_ref::<Storage<T>>()
+    }
+
+    fn storage_mut<T: 'static>(&mut self) -> &mut UnsafeCell<Vec<Option<T>>> {
+        let storage = self
+            .storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(Storage::<T>(UnsafeCell::new(Vec::new()))));
+        &mut storage.as_any_mut().downcast_mut::<Storage<T>>().unwrap().0
+    }
+}
+
+/// What an [`Entities::query`] can ask for. Implemented for `&T`, `&mut T`, [`Entity`] and
+/// tuples of them.
+pub trait Query {
+    type Item<'a>;
+
+    #[doc(hidden)]
+    fn accesses(accesses: &mut Vec<(TypeId, bool)>);
+
+    /// # Safety
+    /// Must only be called once per entity index while the items are alive, with no other
+    /// access to the storages `accesses` lists as mutable.
+    #[doc(hidden)]
+    unsafe fn fetch<'a>(entities: &'a Entities, entity: Entity) -> Option<Self::Item<'a>>;
+}
+
+impl Query for Entity {
+    type Item<'a> = Entity;
+
+    fn accesses(_: &mut Vec<(TypeId, bool)>) {}
+
+    unsafe fn fetch(_: &Entities, entity: Entity) -> Option<Entity> {
+        Some(entity)
+    }
+}
+
+impl<T: 'static> Query for &T {
+    type Item<'a> = &'a T;
+
+    fn accesses(accesses: &mut Vec<(TypeId, bool)>) {
+        accesses.push((TypeId::of::<T>(), false));
+    }
+
+    unsafe fn fetch(entities: &Entities, entity: Entity) -> Option<&T> {
+        let slot = entities.storage::<T>()?.slot(entity.index())?;
+        unsafe { (*slot).as_ref() }
+    }
+}
+
+impl<T: 'static> Query for &mut T {
+    type Item<'a> = &'a mut T;
+
+    fn accesses(accesses: &mut Vec<(TypeId, bool)>) {
+        accesses.push((TypeId::of::<T>(), true));
+    }
+
+    unsafe fn fetch(entities: &Entities, entity: Entity) -> Option<&mut T> {
+        let slot = entities.storage::<T>()?.slot(entity.index())?;
+        unsafe { (*slot).as_mut() }
+    }
+}
+
+macro_rules! impl_query_for_tuples {
+    ($($name:ident),*) => {
+        impl<$($name: Query),*> Query for ($($name,)*) {
+            type Item<'a> = ($($name::Item<'a>,)*);
+
+            fn accesses(accesses: &mut Vec<(TypeId, bool)>) {
+                $($name::accesses(accesses);)*
+            }
+
+            unsafe fn fetch<'a>(entities: &'a Entities, entity: Entity) -> Option<Self::Item<'a>> {
+                Some(($(unsafe { $name::fetch(entities, entity) }?,)*))
+            }
+        }
+    };
+}
+
+impl_query_for_tuples!(A);
+impl_query_for_tuples!(A, B);
+impl_query_for_tuples!(A, B, C);
+impl_query_for_tuples!(A, B, C, D);
+impl_query_for_tuples!(A, B, C, D, E);
+impl_query_for_tuples!(A, B, C, D, E, F);
+impl_query_for_tuples!(A, B, C, D, E, F, G);
+impl_query_for_tuples!(A, B, C, D, E, F, G, H);
+
+pub struct QueryIter<'a, Q: Query> {
+    entities: &'a Entities,
+    index: usize,
+    marker: PhantomData<(&'a mut Entities, Q)>,
+}
+
+impl<'a, Q: Query> Iterator for QueryIter<'a, Q> {
+    type Item = Q::Item<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.entities.generations.len() {
+            let index = self.index;
+            self.index += 1;
+
+            if !self.entities.alive[index] {
+                continue;
+            }
+            let entity = Entity {
+                index: index as u32,
+                generation: self.entities.generations[index],
+            };
+
+            // SAFETY: the query holds the only borrow of the entities, checked its accesses
+            // don't alias when it was made, and visits each index once
+            if let Some(item) = unsafe { Q::fetch(self.entities, entity) } {
+                return Some(item);
+            }
+        }
+
+        None
+    }
+}
+
+fn entities() -> &'static mut Entities {
+    &mut get_state().storage.entities
+}
+
+/// Makes a new entity in the engine's [`Entities`]. Give it components with
+/// [`insert_component`].
+pub fn spawn() -> Entity {
+    entities().spawn()
+}
+
+/// Despawns the entity at the end of the frame, so it's safe to call while looping over a
+/// [`query`].
+pub fn despawn(entity: Entity) {
+    entities().despawn_later(entity);
+}
+
+pub fn is_alive(entity: Entity) -> bool {
+    entities().is_alive(entity)
+}
+
+/// Don't call this while looping over a [`query`] for the same component type, as adding a
+/// component can move the others.
+pub fn insert_component<T: 'static>(entity: Entity, component: T) {
+    entities().insert(entity, component);
+}
+
+pub fn remove_component<T: 'static>(entity: Entity) -> Option<T> {
+    entities().remove(entity)
+}
+
+pub fn get_component<T: 'static>(entity: Entity) -> Option<&'static T> {
+    entities().get(entity)
+}
+
+pub fn get_component_mut<T: 'static>(entity: Entity) -> Option<&'static mut T> {
+    entities().get_mut(entity)
+}
+
+/// See [`Entities::query`].
+pub fn query<Q: Query>() -> QueryIter<'static, Q> {
+    entities().query()
+}
+
+/// The engine's own [`Entities`], for anything the free functions don't cover.
+pub fn entities_mut() -> &'static mut Entities {
+    entities()
+}
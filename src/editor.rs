@@ -0,0 +1,433 @@
+//! A level editor that runs inside the game, so levels can be laid out and tweaked while
+//! playing them. Needs the `editor` feature.
+//!
+//! ```ignore
+//! let mut scene = Scene::load("assets/level.scene").unwrap_or_default();
+//! let mut editor = Editor::new("assets/level.scene");
+//!
+//! loop {
+//!     editor.update(&mut scene);
+//!     scene.draw();
+//!     run_ui(|ctx| editor.ui(ctx, &mut scene));
+//!     next_frame();
+//! }
+//! ```
+//!
+//! Press the toggle key (F2 by default) to open it. Click shapes or markers to select them,
+//! drag to move them, and edit everything else in the properties window.
+
+use bevy_math::Vec2;
+use egui_glium::egui_winit::egui::{self, DragValue, ScrollArea};
+use glium::winit::{event::MouseButton, keyboard::KeyCode};
+
+use crate::{
+    api::{cursor_pos, screen_to_world, window_size, world_to_screen},
+    collisions::world_view_bounds,
+    color::Color,
+    gizmos::{debug_draw_circle, debug_draw_line, debug_draw_line_world},
+    input_handling::{key_pressed, mouse_held, mouse_pressed, mouse_released},
+};
+
+mod scene;
+
+pub use scene::{Scene, SceneObject, SceneShape};
+
+/// How close to a marker, in pixels, counts as clicking it.
+const MARKER_PICK_RADIUS: f32 = 8.0;
+/// The snapping grid isn't shown when it would take more lines than this.
+const MAX_GRID_LINES: usize = 200;
+
+pub struct Editor {
+    pub open: bool,
+    pub toggle_key: KeyCode,
+    /// Size of the grid things snap to when they're moved, or `None` to move freely.
+    pub snap: Option<f32>,
+    /// Where Save and Load go.
+    pub path: String,
+    selected: Option<usize>,
+    /// From the cursor to the position of what's being dragged.
+    drag_offset: Option<Vec2>,
+    pointer_over_ui: bool,
+    typing: bool,
+    new_property: String,
+    status: Option<String>,
+}
+
+impl Editor {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            open: false,
+            toggle_key: KeyCode::F2,
+            snap: Some(1.0),
+            path: path.into(),
+            selected: None,
+            drag_offset: None,
+            pointer_over_ui: false,
+            typing: false,
+            new_property: String::new(),
+            status: None,
+        }
+    }
+
+    /// The index of the selected object in the scene.
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn select(&mut self, index: Option<usize>) {
+        self.selected = index;
+        self.drag_offset = None;
+    }
+
+    /// Opens and closes the editor, and handles selecting and dragging. Call once a frame,
+    /// before [`ui`](Self::ui).
+    pub fn update(&mut self, scene: &mut Scene) {
+        if key_pressed(self.toggle_key) && !self.typing {
+            self.open = !self.open;
+            self.drag_offset = None;
+        }
+        if !self.open {
+            return;
+        }
+        if self
+            .selected
+            .is_some_and(|index| index >= scene.objects.len())
+        {
+            self.select(None);
+        }
+
+        let cursor = screen_to_world(cursor_pos());
+        if mouse_pressed(MouseButton::Left) && !self.pointer_over_ui {
+            self.select(self.pick(scene));
+            self.drag_offset = self
+                .selected
+                .map(|index| scene.objects[index].position - cursor);
+        }
+        if mouse_released(MouseButton::Left) {
+            self.drag_offset = None;
+        }
+        if let (Some(index), Some(offset)) = (self.selected, self.drag_offset)
+            && mouse_held(MouseButton::Left)
+        {
+            scene.objects[index].position = self.snapped(cursor + offset);
+        }
+
+        if key_pressed(KeyCode::Delete)
+            && !self.typing
+            && let Some(index) = self.selected
+        {
+            scene.objects.remove(index);
+            self.select(None);
+        }
+
+        self.draw_gizmos(scene);
+    }
+
+    /// The editor's windows. Call inside [`run_ui`](crate::prelude::run_ui).
+    pub fn ui(&mut self, ctx: &egui::Context, scene: &mut Scene) {
+        if !self.open {
+            self.pointer_over_ui = false;
+            self.typing = false;
+            return;
+        }
+
+        egui::Window::new("Scene")
+            .default_pos([10.0, 10.0])
+            .show(ctx, |ui| self.scene_ui(ui, scene));
+
+        if let Some(index) = self.selected.filter(|index| *index < scene.objects.len()) {
+            let mut action = None;
+            egui::Window::new("Properties")
+                .default_pos([10.0, 360.0])
+                .show(ctx, |ui| {
+                    self.properties_ui(ui, &mut scene.objects[index]);
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        if ui.button("Duplicate").clicked() {
+                            action = Some(true);
+                        }
+                        if ui.button("Delete").clicked() {
+                            action = Some(false);
+                        }
+                    });
+                });
+
+            match action {
+                Some(true) => {
+                    let mut copy = scene.objects[index].clone();
+                    copy.name = format!("{} copy", copy.name);
+                    copy.position =
+                        self.snapped(copy.position + Vec2::splat(self.snap.unwrap_or(1.0)));
+                    self.select(Some(scene.add(copy)));
+                }
+                Some(false) => {
+                    scene.objects.remove(index);
+                    self.select(None);
+                }
+                None => {}
+            }
+        }
+
+        // egui only knows this once it's laid everything out, so `update` uses last frame's
+        self.pointer_over_ui = ctx.is_pointer_over_area() || ctx.wants_pointer_input();
+        self.typing = ctx.wants_keyboard_input();
+    }
+
+    fn scene_ui(&mut self, ui: &mut egui::Ui, scene: &mut Scene) {
+        ui.horizontal(|ui| {
+            ui.label("Add");
+            let shapes = [
+                ("Rect", SceneShape::Rect { size: Vec2::ONE }),
+                ("Circle", SceneShape::Circle { radius: 0.5 }),
+                (
+                    "Triangle",
+                    SceneShape::Polygon {
+                        points: vec![
+                            Vec2::new(-0.5, -0.5),
+                            Vec2::new(0.5, -0.5),
+                            Vec2::new(0.0, 0.5),
+                        ],
+                    },
+                ),
+                ("Marker", SceneShape::Marker),
+            ];
+            for (name, shape) in shapes {
+                if ui.button(name).clicked() {
+                    let center = self.snapped(screen_to_world(window_size() / 2.0));
+                    let name = format!("{name} {}", scene.objects.len() + 1);
+                    let object = SceneObject::new(name, shape).with_position(center);
+                    self.select(Some(scene.add(object)));
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let mut snapping = self.snap.is_some();
+            ui.checkbox(&mut snapping, "Snap to grid");
+            let mut size = self.snap.unwrap_or(1.0);
+            ui.add_enabled(
+                snapping,
+                DragValue::new(&mut size).speed(0.05).range(0.01..=f32::MAX),
+            );
+            self.snap = snapping.then_some(size);
+        });
+
+        ui.separator();
+        ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+            for (index, object) in scene.objects.iter().enumerate() {
+                if ui
+                    .selectable_label(self.selected == Some(index), &object.name)
+                    .clicked()
+                {
+                    self.select(Some(index));
+                }
+            }
+        });
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("File");
+            ui.text_edit_singleline(&mut self.path);
+        });
+        ui.horizontal(|ui| {
+            if ui.button("Save").clicked() {
+                self.status = Some(match scene.save(&self.path) {
+                    Ok(()) => format!("Saved {} objects", scene.objects.len()),
+                    Err(e) => format!("{e:#}"),
+                });
+            }
+            if ui.button("Load").clicked() {
+                self.status = Some(match Scene::load(&self.path) {
+                    Ok(loaded) => {
+                        *scene = loaded;
+                        self.select(None);
+                        format!("Loaded {} objects", scene.objects.len())
+                    }
+                    Err(e) => format!("{e:#}"),
+                });
+            }
+        });
+        if let Some(status) = &self.status {
+            ui.label(status);
+        }
+    }
+
+    fn properties_ui(&mut self, ui: &mut egui::Ui, object: &mut SceneObject) {
+        ui.text_edit_singleline(&mut object.name);
+
+        egui::Grid::new("editor_transform").show(ui, |ui| {
+            ui.label("Position");
+            ui.add(DragValue::new(&mut object.position.x).speed(0.1));
+            ui.add(DragValue::new(&mut object.position.y).speed(0.1));
+            ui.end_row();
+
+            ui.label("Rotation");
+            ui.drag_angle(&mut object.rotation);
+            ui.end_row();
+
+            ui.label("Scale");
+            ui.add(DragValue::new(&mut object.scale.x).speed(0.05));
+            ui.add(DragValue::new(&mut object.scale.y).speed(0.05));
+            ui.end_row();
+
+            match &mut object.shape {
+                SceneShape::Rect { size } => {
+                    ui.label("Size");
+                    ui.add(
+                        DragValue::new(&mut size.x)
+                            .speed(0.05)
+                            .range(0.0..=f32::MAX),
+                    );
+                    ui.add(
+                        DragValue::new(&mut size.y)
+                            .speed(0.05)
+                            .range(0.0..=f32::MAX),
+                    );
+                    ui.end_row();
+                }
+                SceneShape::Circle { radius } => {
+                    ui.label("Radius");
+                    ui.add(DragValue::new(radius).speed(0.05).range(0.0..=f32::MAX));
+                    ui.end_row();
+                }
+                SceneShape::Polygon { .. } | SceneShape::Marker => {}
+            }
+
+            ui.label("Color");
+            let mut rgba = object.color.for_gpu();
+            if ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed() {
+                object.color = Color::from_rgba(rgba[0], rgba[1], rgba[2], rgba[3]);
+            }
+            ui.end_row();
+        });
+
+        if let SceneShape::Polygon { points } = &mut object.shape {
+            ui.separator();
+            ui.label("Points");
+            let mut removed = None;
+            for (i, point) in points.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add(DragValue::new(&mut point.x).speed(0.05));
+                    ui.add(DragValue::new(&mut point.y).speed(0.05));
+                    if ui.small_button("x").clicked() {
+                        removed = Some(i);
+                    }
+                });
+            }
+            if let Some(i) = removed
+                && points.len() > 3
+            {
+                points.remove(i);
+            }
+            if ui.button("Add point").clicked() {
+                // halfway along the closing edge, so the shape doesn't change until it's moved
+                let midpoint = (points[0] + points[points.len() - 1]) / 2.0;
+                points.push(midpoint);
+            }
+        }
+
+        ui.separator();
+        ui.label("Properties");
+        let mut removed = None;
+        egui::Grid::new("editor_properties").show(ui, |ui| {
+            for (key, value) in &mut object.properties {
+                ui.label(key);
+                ui.text_edit_singleline(value);
+                if ui.small_button("x").clicked() {
+                    removed = Some(key.clone());
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(key) = removed {
+            object.properties.remove(&key);
+        }
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_property);
+            if ui.button("Add").clicked() && !self.new_property.is_empty() {
+                object
+                    .properties
+                    .entry(std::mem::take(&mut self.new_property))
+                    .or_default();
+            }
+        });
+    }
+
+    /// The topmost object under the cursor, markers included.
+    fn pick(&self, scene: &Scene) -> Option<usize> {
+        let cursor = cursor_pos();
+        let world_cursor = screen_to_world(cursor);
+        scene.objects.iter().rposition(|object| match object.shape {
+            SceneShape::Marker => {
+                world_to_screen(object.position).distance(cursor) <= MARKER_PICK_RADIUS
+            }
+            _ => object.contains_point(world_cursor),
+        })
+    }
+
+    fn snapped(&self, position: Vec2) -> Vec2 {
+        match self.snap {
+            Some(size) if size > 0.0 => (position / size).round() * size,
+            _ => position,
+        }
+    }
+
+    fn draw_gizmos(&self, scene: &Scene) {
+        if let Some(size) = self.snap.filter(|size| *size > 0.0) {
+            let view = world_view_bounds();
+            let first = (view.min / size).floor();
+            let last = (view.max / size).ceil();
+            let lines = (last - first).element_sum();
+            if lines.is_finite() && lines as usize <= MAX_GRID_LINES {
+                let color = Color::from_rgba(1.0, 1.0, 1.0, 0.1);
+                for x in first.x as i32..=last.x as i32 {
+                    let x = x as f32 * size;
+                    debug_draw_line_world(
+                        Vec2::new(x, view.min.y),
+                        Vec2::new(x, view.max.y),
+                        color,
+                    );
+                }
+                for y in first.y as i32..=last.y as i32 {
+                    let y = y as f32 * size;
+                    debug_draw_line_world(
+                        Vec2::new(view.min.x, y),
+                        Vec2::new(view.max.x, y),
+                        color,
+                    );
+                }
+            }
+        }
+
+        for (index, object) in scene.objects.iter().enumerate() {
+            let color = if self.selected == Some(index) {
+                Color::YELLOW_400
+            } else {
+                Color::from_rgba(1.0, 1.0, 1.0, 0.3)
+            };
+
+            match object.shape {
+                // markers are drawn in pixels, so they're the same size however far out it's
+                // zoomed
+                SceneShape::Marker => {
+                    let center = world_to_screen(object.position);
+                    let arm = Vec2::splat(MARKER_PICK_RADIUS * 0.7);
+                    debug_draw_line(center - arm, center + arm, color);
+                    debug_draw_line(
+                        center + arm * Vec2::new(1.0, -1.0),
+                        center - arm * Vec2::new(1.0, -1.0),
+                        color,
+                    );
+                    debug_draw_circle(center, MARKER_PICK_RADIUS, color);
+                }
+                _ if self.selected == Some(index) => {
+                    let outline = object.outline();
+                    for (i, point) in outline.iter().enumerate() {
+                        debug_draw_line_world(*point, outline[(i + 1) % outline.len()], color);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
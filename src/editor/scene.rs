@@ -0,0 +1,225 @@
+use std::{collections::BTreeMap, f32::consts::TAU, path::Path};
+
+use anyhow::{Context, ensure};
+use bevy_math::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    collisions::Polygon, color::Color, shapes_2d::CustomShape, shapes_2d::draw_shape_world,
+    storage::format,
+};
+
+const MAGIC: &[u8; 4] = b"E4SC";
+/// Bumped when the layout of [`Scene`] changes.
+const VERSION: u32 = 1;
+/// How many sides circles get when drawn and outlined.
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// A level: shapes to draw and collide with, and markers for where things go, each with a name
+/// and whatever properties the game wants to read back. Made with the
+/// [`Editor`](super::Editor), and loaded by the game with [`Scene::load`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    /// Drawn in order, so later ones are on top.
+    pub objects: Vec<SceneObject>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes =
+            std::fs::read(path).with_context(|| format!("couldn't read {}", path.display()))?;
+        Self::from_bytes(&bytes).with_context(|| format!("couldn't load {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_bytes()?)
+            .with_context(|| format!("couldn't write {}", path.display()))
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend(format::to_bytes(self)?);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let bytes = bytes.strip_prefix(MAGIC).context("not a scene file")?;
+        let (version, bytes) = bytes
+            .split_first_chunk::<4>()
+            .context("scene file cut short")?;
+        let version = u32::from_le_bytes(*version);
+        ensure!(
+            version == VERSION,
+            "scene file is version {version}, but only version {VERSION} can be read"
+        );
+        Ok(format::from_bytes(bytes)?)
+    }
+
+    /// Returns its index.
+    pub fn add(&mut self, object: SceneObject) -> usize {
+        self.objects.push(object);
+        self.objects.len() - 1
+    }
+
+    pub fn find(&self, name: &str) -> Option<&SceneObject> {
+        self.objects.iter().find(|object| object.name == name)
+    }
+
+    /// Everything with `key` set, like every `"spawn"`.
+    pub fn with_property<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a SceneObject> {
+        self.objects
+            .iter()
+            .filter(move |object| object.properties.contains_key(key))
+    }
+
+    /// The index of the topmost shape under `point`. Markers have no area, so are never found.
+    pub fn object_at(&self, point: Vec2) -> Option<usize> {
+        self.objects
+            .iter()
+            .rposition(|object| object.contains_point(point))
+    }
+
+    /// Draws every shape in world space. Markers aren't drawn.
+    pub fn draw(&self) {
+        for object in &self.objects {
+            let points = object.outline();
+            if points.len() >= 3 {
+                draw_shape_world(&CustomShape {
+                    points,
+                    color: object.color,
+                });
+            }
+        }
+    }
+}
+
+/// What a [`SceneObject`] looks like, before it's moved, rotated and scaled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SceneShape {
+    /// Centered on the object's position.
+    Rect {
+        size: Vec2,
+    },
+    Circle {
+        radius: f32,
+    },
+    Polygon {
+        points: Vec<Vec2>,
+    },
+    /// Just a position, for spawn points, waypoints and the like.
+    Marker,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneObject {
+    pub name: String,
+    pub position: Vec2,
+    /// In radians.
+    pub rotation: f32,
+    pub scale: Vec2,
+    pub shape: SceneShape,
+    pub color: Color,
+    /// Whatever the game wants to know about it, like `"spawn" = "player"`.
+    pub properties: BTreeMap<String, String>,
+}
+
+impl SceneObject {
+    pub fn new(name: impl Into<String>, shape: SceneShape) -> Self {
+        Self {
+            name: name.into(),
+            position: Vec2::ZERO,
+            rotation: 0.0,
+            scale: Vec2::ONE,
+            shape,
+            color: Color::WHITE,
+            properties: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_position(mut self, position: Vec2) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: Vec2) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_property(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.properties.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn property(&self, key: &str) -> Option<&str> {
+        self.properties.get(key).map(String::as_str)
+    }
+
+    /// From the shape's own space into the world.
+    pub fn to_world(&self, local: Vec2) -> Vec2 {
+        self.position + Vec2::from_angle(self.rotation).rotate(local * self.scale)
+    }
+
+    /// From the world into the shape's own space.
+    pub fn to_local(&self, world: Vec2) -> Vec2 {
+        Vec2::from_angle(-self.rotation).rotate(world - self.position) / self.scale
+    }
+
+    /// The outline in world space. Empty for markers.
+    pub fn outline(&self) -> Vec<Vec2> {
+        let local = match &self.shape {
+            SceneShape::Rect { size } => {
+                let half = *size * 0.5;
+                vec![
+                    Vec2::new(-half.x, -half.y),
+                    Vec2::new(half.x, -half.y),
+                    Vec2::new(half.x, half.y),
+                    Vec2::new(-half.x, half.y),
+                ]
+            }
+            SceneShape::Circle { radius } => (0..CIRCLE_SEGMENTS)
+                .map(|i| Vec2::from_angle(i as f32 / CIRCLE_SEGMENTS as f32 * TAU) * *radius)
+                .collect(),
+            SceneShape::Polygon { points } => points.clone(),
+            SceneShape::Marker => vec![],
+        };
+        local
+            .into_iter()
+            .map(|point| self.to_world(point))
+            .collect()
+    }
+
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        if self.scale.x == 0.0 || self.scale.y == 0.0 {
+            return false;
+        }
+
+        let local = self.to_local(point);
+        match &self.shape {
+            SceneShape::Rect { size } => local.abs().cmple(*size * 0.5).all(),
+            SceneShape::Circle { radius } => local.length() <= *radius,
+            SceneShape::Polygon { points } => Polygon {
+                vertices: points.clone(),
+            }
+            .contains_point(local),
+            SceneShape::Marker => false,
+        }
+    }
+}
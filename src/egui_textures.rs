@@ -0,0 +1,237 @@
+//! Showing engine textures in egui, and drawing egui onto engine textures.
+//!
+//! egui can only sample sRGB textures, so each texture shown in egui gets an sRGB copy that's
+//! refreshed every frame it's used, right before egui is painted. That way render textures
+//! show what was drawn to them this frame.
+
+use std::{collections::HashMap, rc::Rc};
+
+use bevy_math::UVec2;
+use egui_glium::{
+    Painter,
+    egui_winit::egui::{self, Context, TextureId, TextureOptions},
+};
+use glium::{
+    BlitTarget, Surface,
+    framebuffer::SimpleFrameBuffer,
+    texture::{MipmapsOption, SrgbFormat, SrgbTexture2d},
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter},
+};
+
+use crate::{
+    api::{frame_count, time},
+    error::{EngineResult, OrReport, report_error},
+    get_state,
+    render_pipeline::RenderTextureRef,
+    textures::TextureRef,
+};
+
+#[derive(Default)]
+pub(crate) struct EguiTextures {
+    links: HashMap<TextureRef, EguiTextureLink>,
+    /// For [`run_ui_on_texture`], made the first time it's used.
+    offscreen: Option<OffscreenUi>,
+}
+
+struct EguiTextureLink {
+    id: TextureId,
+    copy: Rc<SrgbTexture2d>,
+    /// Only textures asked for this frame get copied.
+    last_used: usize,
+}
+
+struct OffscreenUi {
+    context: Context,
+    painter: Painter,
+}
+
+impl TextureRef {
+    /// An id for showing this texture in [`run_ui`](crate::prelude::run_ui), with
+    /// `egui::Image` or `ui.image`. Call it every frame the texture is shown, so egui's copy
+    /// stays up to date.
+    ///
+    /// Returns egui's default texture in headless mode, and for textures that are still
+    /// compressed on the GPU, which can't be copied.
+    pub fn to_egui_texture_id(&self) -> TextureId {
+        let state = get_state();
+        let Some(context) = &mut state.window_context else {
+            return TextureId::default();
+        };
+        if self.get().compressed.is_some() {
+            report_error(anyhow::anyhow!(
+                "compressed textures can't be shown in egui"
+            ));
+            return TextureId::default();
+        }
+
+        let dimensions = self.dimensions();
+        let frame = frame_count();
+
+        if let Some(link) = state.egui_textures.links.get_mut(self) {
+            link.last_used = frame;
+            if copy_dimensions(&link.copy) == dimensions {
+                return link.id;
+            }
+
+            // the texture was replaced with a different size, like a resized render texture
+            let Some(copy) = srgb_copy(dimensions).or_report() else {
+                return TextureId::default();
+            };
+            link.copy = Rc::new(copy);
+            context.gui.painter.replace_native_texture(
+                link.id,
+                link.copy.clone(),
+                egui_options(self),
+            );
+            return link.id;
+        }
+
+        let Some(copy) = srgb_copy(dimensions).or_report() else {
+            return TextureId::default();
+        };
+        let copy = Rc::new(copy);
+        let id = context
+            .gui
+            .painter
+            .register_native_texture(copy.clone(), egui_options(self));
+        state.egui_textures.links.insert(
+            *self,
+            EguiTextureLink {
+                id,
+                copy,
+                last_used: frame,
+            },
+        );
+        id
+    }
+
+    /// Lets go of egui's copy of the texture. It's made again if
+    /// [`to_egui_texture_id`](Self::to_egui_texture_id) is called after.
+    pub fn free_egui_texture(&self) {
+        let state = get_state();
+        if let Some(link) = state.egui_textures.links.remove(self)
+            && let Some(context) = &mut state.window_context
+        {
+            context.gui.painter.free_texture(link.id);
+        }
+    }
+}
+
+impl RenderTextureRef {
+    /// What was drawn to the render texture this frame, for showing in egui. See
+    /// [`TextureRef::to_egui_texture_id`].
+    pub fn to_egui_texture_id(&self) -> TextureId {
+        self.get().color_texture.to_egui_texture_id()
+    }
+}
+
+fn srgb_copy(dimensions: UVec2) -> EngineResult<SrgbTexture2d> {
+    Ok(SrgbTexture2d::empty_with_format(
+        get_state().display(),
+        SrgbFormat::U8U8U8U8,
+        MipmapsOption::NoMipmap,
+        dimensions.x,
+        dimensions.y,
+    )?)
+}
+
+fn copy_dimensions(texture: &SrgbTexture2d) -> UVec2 {
+    UVec2::new(texture.width(), texture.height())
+}
+
+fn egui_options(texture: &TextureRef) -> TextureOptions {
+    filter_options(texture.magnify_filter, texture.minify_filter)
+}
+
+/// egui's copy is only ever one mip level, so it's nearest if both filters are, and linear
+/// otherwise.
+pub(crate) fn filter_options(
+    magnify_filter: MagnifySamplerFilter,
+    minify_filter: MinifySamplerFilter,
+) -> TextureOptions {
+    let nearest = matches!(magnify_filter, MagnifySamplerFilter::Nearest)
+        && matches!(
+            minify_filter,
+            MinifySamplerFilter::Nearest | MinifySamplerFilter::NearestMipmapNearest
+        );
+    if nearest {
+        TextureOptions::NEAREST
+    } else {
+        TextureOptions::LINEAR
+    }
+}
+
+/// Copies every texture used in egui this frame into its sRGB copy. Called just before egui
+/// is painted, after everything else has been drawn.
+pub(crate) fn sync_egui_textures() {
+    let state = get_state();
+    let frame = frame_count();
+    let display = state.display();
+
+    for (texture, link) in &state.egui_textures.links {
+        if link.last_used != frame {
+            continue;
+        }
+
+        let dimensions = copy_dimensions(&link.copy);
+        let source = texture.gl_texture.as_surface();
+        // egui shows last frame's copy, or nothing, rather than taking the app down
+        let Some(target) = SimpleFrameBuffer::new(display, &*link.copy).or_report() else {
+            continue;
+        };
+        source.blit_whole_color_to(
+            &target,
+            &BlitTarget {
+                left: 0,
+                bottom: 0,
+                width: dimensions.x as i32,
+                height: dimensions.y as i32,
+            },
+            MagnifySamplerFilter::Nearest,
+        );
+    }
+}
+
+/// Draws egui onto `texture` instead of the screen, for UI on in-game screens or anything else
+/// that's part of the world. It's drawn straight away and replaces what was on the texture.
+///
+/// The UI doesn't get any input, so it's for showing things rather than for buttons.
+pub fn run_ui_on_texture(texture: RenderTextureRef, f: impl FnMut(&Context)) {
+    let state = get_state();
+    if state.window_context.is_none() {
+        return;
+    }
+
+    let offscreen = state
+        .egui_textures
+        .offscreen
+        .get_or_insert_with(|| OffscreenUi {
+            context: Context::default(),
+            painter: Painter::new(get_state().display()),
+        });
+    offscreen.context.set_visuals(state.theme.egui_visuals());
+
+    let size = texture.dimensions().as_vec2();
+    let input = egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::vec2(size.x, size.y),
+        )),
+        time: Some(time() as f64),
+        ..Default::default()
+    };
+    let output = offscreen.context.run(input, f);
+    let primitives = offscreen
+        .context
+        .tessellate(output.shapes, output.pixels_per_point);
+
+    let mut framebuffer = texture.framebuffer();
+    framebuffer.clear_color(0.0, 0.0, 0.0, 0.0);
+    offscreen.painter.paint_and_update_textures(
+        get_state().display(),
+        &mut framebuffer,
+        output.pixels_per_point,
+        &primitives,
+        &output.textures_delta,
+    );
+}
@@ -0,0 +1,200 @@
+//! What the engine returns when something goes wrong, and what it does with errors it can't
+//! return, like a draw call failing halfway through a frame.
+
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Everything that can go wrong inside the engine. Converts into `anyhow::Error`, so `?` works
+/// in a `main` that returns `anyhow::Result`.
+#[derive(Debug)]
+pub enum EngineError {
+    /// The engine was used before `init` was called.
+    NotInitialized,
+    /// The engine was used from a thread other than the one `init` was called on.
+    WrongThread,
+    /// A handle, like a `TextureRef`, to something that's been removed.
+    StaleRef(&'static str),
+    /// Something the engine made itself, like the default font, that something tried to remove.
+    RemovedBuiltin(&'static str),
+    /// Bytes that couldn't be decoded as an image.
+    Image(::image::ImageError),
+    /// Pixel data whose length doesn't match the size it's meant to be.
+    ImageSize {
+        expected: usize,
+        actual: usize,
+    },
+    /// The GPU refused to make a texture, usually because it's too big.
+    Texture(glium::texture::TextureCreationError),
+    /// A shader that didn't compile or link. The message has the driver's log in it.
+    Shader(glium::ProgramCreationError),
+    /// Bytes that couldn't be read as a font.
+    Font(String),
+    /// A KTX2 file that's broken, or in a format the engine can't load.
+    Ktx2(String),
+    /// A draw call that failed, usually because of a uniform that doesn't match the shader.
+    Draw(glium::DrawError),
+    /// A shape that couldn't be tessellated. It's drawn as nothing instead.
+    Tessellation(lyon::tessellation::TessellationError),
+    /// A datagram that was ignored, because it was too big to be one of ours, or broke the
+    /// protocol in a way that could only be a bug or an attack.
+    BadDatagram {
+        from: std::net::SocketAddr,
+        reason: &'static str,
+    },
+    Io(std::io::Error),
+    Other(anyhow::Error),
+}
+
+pub type EngineResult<T> = Result<T, EngineError>;
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotInitialized => {
+                write!(f, "the engine hasn't been initialized, call `init` first")
+            }
+            Self::WrongThread => write!(
+                f,
+                "the engine can only be used from the thread that called `init`"
+            ),
+            Self::StaleRef(name) => write!(f, "{name} points to something that's been removed"),
+            Self::RemovedBuiltin(name) => {
+                write!(f, "{name} is one of the engine's own, and can't be removed")
+            }
+            Self::Image(error) => write!(f, "couldn't decode image: {error}"),
+            Self::ImageSize { expected, actual } => {
+                write!(f, "image should be {expected} bytes, but it's {actual}")
+            }
+            Self::Texture(error) => write!(f, "couldn't create texture: {error}"),
+            Self::Shader(error) => write!(f, "couldn't compile shader: {error}"),
+            Self::Font(error) => write!(f, "couldn't load font: {error}"),
+            Self::Ktx2(error) => write!(f, "couldn't load KTX2 texture: {error}"),
+            Self::Draw(error) => write!(f, "draw call failed: {error}"),
+            Self::Tessellation(error) => write!(f, "couldn't tessellate shape: {error:?}"),
+            Self::BadDatagram { from, reason } => {
+                write!(f, "ignored datagram from {from}: {reason}")
+            }
+            Self::Io(error) => write!(f, "{error}"),
+            Self::Other(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Image(error) => Some(error),
+            Self::Texture(error) => Some(error),
+            Self::Shader(error) => Some(error),
+            Self::Draw(error) => Some(error),
+            Self::Io(error) => Some(error),
+            Self::Other(error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<::image::ImageError> for EngineError {
+    fn from(error: ::image::ImageError) -> Self {
+        Self::Image(error)
+    }
+}
+
+impl From<glium::texture::TextureCreationError> for EngineError {
+    fn from(error: glium::texture::TextureCreationError) -> Self {
+        Self::Texture(error)
+    }
+}
+
+impl From<glium::ProgramCreationError> for EngineError {
+    fn from(error: glium::ProgramCreationError) -> Self {
+        Self::Shader(error)
+    }
+}
+
+impl From<glium::DrawError> for EngineError {
+    fn from(error: glium::DrawError) -> Self {
+        Self::Draw(error)
+    }
+}
+
+impl From<glium::framebuffer::ValidationError> for EngineError {
+    fn from(error: glium::framebuffer::ValidationError) -> Self {
+        Self::Other(error.into())
+    }
+}
+
+impl From<glium::vertex::BufferCreationError> for EngineError {
+    fn from(error: glium::vertex::BufferCreationError) -> Self {
+        Self::Other(anyhow::Error::new(error).context("couldn't create vertex buffer"))
+    }
+}
+
+impl From<glium::index::BufferCreationError> for EngineError {
+    fn from(error: glium::index::BufferCreationError) -> Self {
+        Self::Other(anyhow::Error::new(error).context("couldn't create index buffer"))
+    }
+}
+
+impl From<lyon::tessellation::TessellationError> for EngineError {
+    fn from(error: lyon::tessellation::TessellationError) -> Self {
+        Self::Tessellation(error)
+    }
+}
+
+impl From<std::io::Error> for EngineError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl From<anyhow::Error> for EngineError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::Other(error)
+    }
+}
+
+type ErrorHandler = Arc<dyn Fn(&EngineError) + Send + Sync>;
+
+/// Shared between threads, since shapes can be tessellated off the main thread.
+static ERROR_HANDLER: Mutex<Option<ErrorHandler>> = Mutex::new(None);
+
+/// A handler that panicked doesn't stop the next one from being set.
+fn error_handler() -> MutexGuard<'static, Option<ErrorHandler>> {
+    ERROR_HANDLER.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Called with errors that happen where they can't be returned, like in the middle of drawing a
+/// frame. Without one of these they're logged and the game carries on, with a missing sprite or
+/// the like. Panicking in here is a good way to catch them while working on a game.
+pub fn set_error_handler(handler: impl Fn(&EngineError) + Send + Sync + 'static) {
+    *error_handler() = Some(Arc::new(handler));
+}
+
+/// Goes back to logging errors, see [`set_error_handler`].
+pub fn reset_error_handler() {
+    *error_handler() = None;
+}
+
+/// Hands `error` to the error handler, or logs it if there isn't one. The handler is called
+/// without holding the lock, so it can report errors itself or set a new handler.
+pub(crate) fn report_error(error: impl Into<EngineError>) {
+    let error = error.into();
+    let handler = error_handler().clone();
+    match handler {
+        Some(handler) => handler(&error),
+        None => log::error!("{error}"),
+    }
+}
+
+/// For errors that can't be returned, see [`report_error`].
+pub(crate) trait OrReport<T> {
+    /// `None` if it failed, after the error has been reported.
+    fn or_report(self) -> Option<T>;
+}
+
+impl<T, E: Into<EngineError>> OrReport<T> for Result<T, E> {
+    fn or_report(self) -> Option<T> {
+        self.map_err(report_error).ok()
+    }
+}
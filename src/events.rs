@@ -0,0 +1,157 @@
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    path::PathBuf,
+};
+
+use bevy_math::UVec2;
+
+use crate::{audio::SoundHandle, get_state};
+
+/// Sent when the window changes size, with the new size in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowResized {
+    pub size: UVec2,
+}
+
+/// Sent when the window gains or loses focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowFocused {
+    pub focused: bool,
+}
+
+/// Sent for each file drag-and-dropped onto the window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileDropped {
+    pub path: PathBuf,
+}
+
+/// Sent whenever what an IME is composing changes, with the same text as
+/// [`ime_preedit`](crate::input::ime_preedit). Empty once it's committed or cancelled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImeComposition {
+    pub text: String,
+    pub cursor: Option<(usize, usize)>,
+}
+
+/// Sent when the config file changes and has been reloaded, if
+/// [`EngineConfig::hot_reload`](crate::config::EngineConfig::hot_reload) is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigReloaded;
+
+/// Sent when a sound passed to [`notify_when_finished`] plays to the end. Seeking it doesn't
+/// count, and neither does [`stop`](SoundHandle::stop)ping it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoundFinished {
+    pub handle: SoundHandle,
+}
+
+trait AnyQueue: Any {
+    fn swap(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+struct Queue<T> {
+    /// Sent last frame, and readable now.
+    readable: Vec<T>,
+    /// Sent this frame, readable next frame.
+    sent: Vec<T>,
+}
+
+impl<T: 'static> AnyQueue for Queue<T> {
+    fn swap(&mut self) {
+        self.readable.clear();
+        std::mem::swap(&mut self.readable, &mut self.sent);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Typed publish/subscribe, so one part of a game can announce something happened without
+/// knowing who cares. Any `'static` type can be an event.
+///
+/// Events sent during a frame can be read during the whole of the next one, by as many readers
+/// as want them, and then they're gone. Waiting a frame means it doesn't matter whether the
+/// code sending or the code reading runs first.
+///
+/// The engine has one of these, used by [`emit`] and [`events`], that it also sends its own
+/// events on: [`WindowResized`], [`WindowFocused`], [`SoundFinished`],
+/// [`AnimationEvent`](crate::prelude::AnimationEvent), and rapier's `CollisionEvent` from every
+/// [`PhysicsWorld`](crate::physics::PhysicsWorld) step.
+#[derive(Default)]
+pub struct EventBus {
+    queues: HashMap<TypeId, Box<dyn AnyQueue>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn emit<T: 'static>(&mut self, event: T) {
+        self.queue_mut::<T>().sent.push(event);
+    }
+
+    /// Everything of type `T` sent before the last [`update`](Self::update).
+    pub fn read<T: 'static>(&self) -> std::slice::Iter<'_, T> {
+        self.queues
+            .get(&TypeId::of::<T>())
+            .and_then(|queue| queue.as_any().downcast_ref::<Queue<T>>())
+            .map(|queue| queue.readable.iter())
+            .unwrap_or_default()
+    }
+
+    /// Makes everything sent since the last update readable, and drops what was readable
+    /// before. The engine does this for its own bus at the end of every frame.
+    pub fn update(&mut self) {
+        for queue in self.queues.values_mut() {
+            queue.swap();
+        }
+    }
+
+    /// Drops every event, sent or readable.
+    pub fn clear(&mut self) {
+        self.queues.clear();
+    }
+
+    fn queue_mut<T: 'static>(&mut self) -> &mut Queue<T> {
+        self.queues
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| {
+                Box::new(Queue::<T> {
+                    readable: Vec::new(),
+                    sent: Vec::new(),
+                })
+            })
+            .as_any_mut()
+            .downcast_mut::<Queue<T>>()
+            .unwrap()
+    }
+}
+
+/// Sends an event, readable with [`events`] during the next frame.
+pub fn emit<T: 'static>(event: T) {
+    get_state().events.emit(event);
+}
+
+/// Every `T` sent last frame, by the game or the engine.
+pub fn events<T: 'static>() -> std::slice::Iter<'static, T> {
+    get_state().events.read()
+}
+
+/// Sends a [`SoundFinished`] event once the sound plays to the end. Same as
+/// [`SoundHandle::notify_when_finished`].
+pub fn notify_when_finished(sound: SoundHandle) {
+    sound.notify_when_finished();
+}
+
+pub(crate) fn update_events() {
+    get_state().events.update();
+}
@@ -0,0 +1,6 @@
+//! Internals that are reachable for people who really need them, but can change in any release
+//! without warning. Only available with the `experimental` feature.
+
+pub use crate::camera::{projection, projection_from_window};
+pub use crate::draw_queue_2d::{Vertex2D, Vertex3D};
+pub use crate::textures::TexturedVertex2D;
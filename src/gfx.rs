@@ -0,0 +1,82 @@
+//! Everything that ends up on screen: 2D shapes, text, textures, 3D objects, materials, cameras,
+//! backgrounds, post processing and debug gizmos.
+
+pub use crate::api::{
+    add_background_layer, add_post_processing_effect, blend_mode, bloom_screen, blur_screen,
+    brighten_screen, camera2d_zoom_at, chromatic_abberation_screen, clear_screen,
+    color_filter_screen, contrast_screen, create_empty_render_texture, crt_screen, default_font,
+    draw_background_clouds, draw_background_hills, draw_background_stars, draw_fps,
+    draw_fullscreen_texture, draw_parallel, draw_parallel_world, draw_poly_outline,
+    draw_poly_outline_world, draw_rect_outline, draw_rect_outline_world, draw_sky_gradient,
+    draw_square_outline, draw_square_outline_world, draw_texture, draw_texture_ex,
+    draw_texture_scaled, draw_texture_scaled_world, draw_texture_world, draw_texture_world_ex,
+    draw_tri_outline, draw_tri_outline_world, end_rendering_to_texture, get_camera2d, get_camera3d,
+    greyscale_screen, hdr, hue_rotate_screen, invert_screen, layer, mutate_camera_2d,
+    mutate_camera_3d, pass_settings, pixelate_screen, pop_clip_rect, push_clip_rect, run_ui,
+    saturate_screen, screen_to_world, set_blend_mode, set_hdr, set_layer, set_magnify_filter,
+    set_minify_filter, set_pass_settings, start_rendering_to_texture, submit_draw_commands,
+    submit_draw_commands_world, use_default_filtering, use_linear_filtering, use_mipmaps,
+    use_nearest_filtering, vignette_screen, with_blend_mode, with_clip_rect, with_layer, with_mask,
+    with_mask_world, with_overlay, world_to_screen,
+};
+pub use crate::atmosphere::*;
+pub use crate::background::BackgroundLayer;
+pub use crate::camera::controllers::orbit::OrbitCameraController;
+pub use crate::camera::controllers::pan::PanningCameraController;
+pub use crate::camera::shake::{CameraShake, camera_shake, set_camera_shake, shake_camera};
+pub use crate::camera::{Camera2D, Camera3D};
+pub use crate::color::Color;
+pub use crate::color::theme::*;
+pub use crate::color::u8::Pixel;
+pub use crate::draw_queue_2d::{DrawCommands2D, MaterialVertex3D};
+pub use crate::egui_textures::run_ui_on_texture;
+pub use crate::gizmos::{
+    debug_draw_aabb, debug_draw_aabb_3d, debug_draw_aabb_world, debug_draw_circle,
+    debug_draw_circle_world, debug_draw_line, debug_draw_line_3d, debug_draw_line_world,
+    debug_draw_ray, debug_draw_ray_world, debug_draw_text, debug_draw_text_world, gizmos_enabled,
+    set_gizmos_enabled, toggle_gizmos,
+};
+pub use crate::guides::*;
+pub use crate::image::Image;
+pub use crate::include_program;
+pub use crate::materials::*;
+pub use crate::nine_slice::*;
+pub use crate::object_3d::*;
+pub use crate::outlines::{
+    outline, outline_thickness, set_outline, set_outline_thickness, with_outline,
+};
+pub use crate::palette::{MAX_PALETTE_COLORS, Palette, PaletteSwap, palette_screen};
+pub use crate::particles::{Curve, ParticleConfig};
+pub use crate::particles_3d::{EmissionShape3D, ParticleEmitter3D};
+pub use crate::picking::{
+    PickId, hovered_pick, pick_at, pick_id, set_pick_id, use_picking, with_pick_id,
+};
+pub use crate::post_processing::{
+    ColorBlindness, ColorFilter, PostProcessingEffect, TonemapOperator, Tonemapping, crt_distort,
+};
+pub use crate::programs::{ProgramRef, load_program};
+pub use crate::render_pipeline::{PassSettings, RenderPass, RenderTextureRef};
+pub use crate::rich_text::*;
+pub use crate::scene_graph::{NodeId, SceneGraph, SceneTransform, scene_2d, scene_3d};
+pub use crate::shapes_2d::*;
+pub use crate::shapes_3d::*;
+pub use crate::skybox::*;
+#[cfg(feature = "svg")]
+pub use crate::svg::Svg;
+pub use crate::terrain::*;
+pub use crate::text_animation::*;
+pub use crate::text_rendering::*;
+pub use crate::textures::async_loading::{
+    TextureHandle, load_texture_async, load_texture_bytes_async, placeholder_texture,
+    textures_loading,
+};
+pub use crate::textures::atlas::*;
+pub use crate::textures::atlas_builder::*;
+pub use crate::textures::compressed::{BlockFormat, CompressedImage, load_compressed_texture};
+pub use crate::textures::{TextureRef, load_texture};
+pub use crate::trail::Trail;
+pub use crate::transform::*;
+pub use crate::ui::*;
+pub use crate::ui_layout::*;
+pub use egui_glium::egui_winit::egui;
+pub use engine_4_macros::Uniforms;
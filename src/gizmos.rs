@@ -0,0 +1,382 @@
+use bevy_math::{Mat4, Vec2, Vec3};
+use glium::Surface;
+
+use crate::{
+    collisions::{AABB2D, ray::Ray},
+    color::Color,
+    draw_queue_2d::DrawQueue2D,
+    get_state,
+    shapes_2d::{CircleOutline, Line, Shape2D},
+    shapes_3d::AABB3D,
+    text_rendering::{TextDrawParams, draw_text_to},
+};
+
+/// Thickness of gizmo lines, in pixels whatever the camera zoom.
+const THICKNESS: f32 = 2.0;
+const TEXT_SIZE: usize = 14;
+/// Size of the arrowhead on rays, in pixels.
+const ARROW_SIZE: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GizmoSpace {
+    /// Pixels, from the top left of the window.
+    Screen,
+    /// Through the 2D camera.
+    World,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Gizmo {
+    Line {
+        start: Vec2,
+        end: Vec2,
+        color: Color,
+        space: GizmoSpace,
+    },
+    Circle {
+        center: Vec2,
+        radius: f32,
+        color: Color,
+        space: GizmoSpace,
+    },
+    Text {
+        text: String,
+        position: Vec2,
+        color: Color,
+        space: GizmoSpace,
+    },
+    /// Through the 3D camera.
+    Line3D {
+        start: Vec3,
+        end: Vec3,
+        color: Color,
+    },
+}
+
+/// Debug shapes that are drawn on top of everything, including post-processing, for one frame.
+/// For showing physics shapes, paths and AI state without mixing it into the game's drawing.
+///
+/// Everything is outlines with a fixed pixel thickness, so it stays readable however far the
+/// camera is zoomed.
+pub(crate) struct Gizmos {
+    enabled: bool,
+    queued: Vec<Gizmo>,
+}
+
+impl Gizmos {
+    pub(crate) fn new() -> Self {
+        Self {
+            enabled: true,
+            queued: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, gizmo: Gizmo) {
+        if self.enabled {
+            self.queued.push(gizmo);
+        }
+    }
+
+    pub(crate) fn line(&mut self, start: Vec2, end: Vec2, color: Color, space: GizmoSpace) {
+        self.push(Gizmo::Line {
+            start,
+            end,
+            color,
+            space,
+        });
+    }
+
+    /// Lines from `points[0]` to `points[1]` and so on, and back to the first.
+    fn closed_loop(&mut self, points: &[Vec2], color: Color, space: GizmoSpace) {
+        for (i, start) in points.iter().enumerate() {
+            let end = points[(i + 1) % points.len()];
+            self.line(*start, end, color, space);
+        }
+    }
+
+    pub(crate) fn aabb(&mut self, aabb: AABB2D, color: Color, space: GizmoSpace) {
+        let AABB2D { min, max } = aabb;
+        let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+        self.closed_loop(&corners, color, space);
+    }
+
+    pub(crate) fn ray(
+        &mut self,
+        ray: Ray,
+        length: f32,
+        arrow_size: f32,
+        color: Color,
+        space: GizmoSpace,
+    ) {
+        let direction = ray.direction.normalize_or_zero();
+        let end = ray.origin + direction * length;
+        self.line(ray.origin, end, color, space);
+
+        let back = -direction * arrow_size;
+        self.line(end, end + back.rotate(Vec2::from_angle(0.5)), color, space);
+        self.line(end, end + back.rotate(Vec2::from_angle(-0.5)), color, space);
+    }
+
+    pub(crate) fn aabb_3d(&mut self, aabb: AABB3D, color: Color) {
+        let AABB3D { min, max } = aabb;
+        let corner = |i: usize| {
+            Vec3::new(
+                if i & 1 == 0 { min.x } else { max.x },
+                if i & 2 == 0 { min.y } else { max.y },
+                if i & 4 == 0 { min.z } else { max.z },
+            )
+        };
+
+        // each corner joins up with the ones that differ by one axis
+        for i in 0..8 {
+            for axis in [1, 2, 4] {
+                if i & axis == 0 {
+                    self.push(Gizmo::Line3D {
+                        start: corner(i),
+                        end: corner(i | axis),
+                        color,
+                    });
+                }
+            }
+        }
+    }
+
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turning them off also forgets anything already queued this frame.
+    pub(crate) fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.queued.clear();
+    }
+
+    #[cfg(test)]
+    pub(crate) fn queued(&self) -> &[Gizmo] {
+        &self.queued
+    }
+
+    /// Draws and forgets everything queued.
+    pub(crate) fn draw_on<T: Surface>(&mut self, target: &mut T) {
+        let gizmos = std::mem::take(&mut self.queued);
+        if gizmos.is_empty() {
+            return;
+        }
+
+        let mut cameras = get_state().cameras();
+        let view_proj = cameras.d3.view_proj();
+        let window_size = cameras.d2.window_size();
+        // keeps world lines the same thickness on screen at any zoom
+        let world_thickness = cameras.d2.screen_distance_to_world(THICKNESS);
+
+        let mut screen = DrawQueue2D::empty();
+        let mut world = DrawQueue2D::empty();
+
+        for gizmo in gizmos {
+            match gizmo {
+                Gizmo::Line {
+                    start,
+                    end,
+                    color,
+                    space,
+                } => {
+                    let (queue, thickness) = match space {
+                        GizmoSpace::Screen => (&mut screen, THICKNESS),
+                        GizmoSpace::World => (&mut world, world_thickness),
+                    };
+                    Line {
+                        start,
+                        end,
+                        thickness,
+                        color,
+                    }
+                    .add_to_draw_queue(queue);
+                }
+                Gizmo::Circle {
+                    center,
+                    radius,
+                    color,
+                    space,
+                } => {
+                    let (queue, thickness) = match space {
+                        GizmoSpace::Screen => (&mut screen, THICKNESS),
+                        GizmoSpace::World => (&mut world, world_thickness),
+                    };
+                    CircleOutline {
+                        center,
+                        radius: Vec2::splat(radius),
+                        color,
+                        thickness,
+                    }
+                    .add_to_draw_queue(queue);
+                }
+                Gizmo::Text {
+                    text,
+                    position,
+                    color,
+                    space,
+                } => {
+                    // text is always drawn at screen size, so world text is moved to where
+                    // the camera puts it instead of being drawn through the camera
+                    let position = match space {
+                        GizmoSpace::Screen => position,
+                        GizmoSpace::World => cameras.d2.world_to_screen(position),
+                    };
+                    let params = TextDrawParams {
+                        font_size: TEXT_SIZE,
+                        color,
+                        position,
+                        ..Default::default()
+                    };
+                    draw_text_to(text, params, &mut screen);
+                }
+                Gizmo::Line3D { start, end, color } => {
+                    if let Some((start, end)) = project_line(start, end, &view_proj, window_size) {
+                        Line {
+                            start,
+                            end,
+                            thickness: THICKNESS,
+                            color,
+                        }
+                        .add_to_draw_queue(&mut screen);
+                    }
+                }
+            }
+        }
+
+        target.clear_depth(1.0);
+        world.draw(target, &cameras.d2.projection_matrix());
+        target.clear_depth(1.0);
+        screen.draw(target, &cameras.flat);
+    }
+}
+
+/// Where a 3D line ends up on the screen, clipped to the part in front of the camera.
+pub(crate) fn project_line(
+    start: Vec3,
+    end: Vec3,
+    view_proj: &Mat4,
+    window_size: Vec2,
+) -> Option<(Vec2, Vec2)> {
+    // just in front of the camera, so nothing divides by zero
+    const NEAR_W: f32 = 1e-4;
+
+    let mut a = *view_proj * start.extend(1.0);
+    let mut b = *view_proj * end.extend(1.0);
+    if a.w < NEAR_W && b.w < NEAR_W {
+        return None;
+    }
+    if a.w < NEAR_W {
+        a = a.lerp(b, (NEAR_W - a.w) / (b.w - a.w));
+    } else if b.w < NEAR_W {
+        b = b.lerp(a, (NEAR_W - b.w) / (a.w - b.w));
+    }
+
+    let to_screen = |clip: bevy_math::Vec4| {
+        let ndc = clip.truncate().truncate() / clip.w;
+        Vec2::new(
+            (ndc.x + 1.0) / 2.0 * window_size.x,
+            (1.0 - ndc.y) / 2.0 * window_size.y,
+        )
+    };
+    Some((to_screen(a), to_screen(b)))
+}
+
+fn gizmos() -> &'static mut Gizmos {
+    &mut get_state().gizmos
+}
+
+pub fn debug_draw_line(start: Vec2, end: Vec2, color: Color) {
+    gizmos().line(start, end, color, GizmoSpace::Screen);
+}
+
+pub fn debug_draw_line_world(start: Vec2, end: Vec2, color: Color) {
+    gizmos().line(start, end, color, GizmoSpace::World);
+}
+
+pub fn debug_draw_circle(center: Vec2, radius: f32, color: Color) {
+    gizmos().push(Gizmo::Circle {
+        center,
+        radius,
+        color,
+        space: GizmoSpace::Screen,
+    });
+}
+
+pub fn debug_draw_circle_world(center: Vec2, radius: f32, color: Color) {
+    gizmos().push(Gizmo::Circle {
+        center,
+        radius,
+        color,
+        space: GizmoSpace::World,
+    });
+}
+
+pub fn debug_draw_aabb(aabb: AABB2D, color: Color) {
+    gizmos().aabb(aabb, color, GizmoSpace::Screen);
+}
+
+pub fn debug_draw_aabb_world(aabb: AABB2D, color: Color) {
+    gizmos().aabb(aabb, color, GizmoSpace::World);
+}
+
+/// An arrow `length` long from the ray's origin.
+pub fn debug_draw_ray(ray: Ray, length: f32, color: Color) {
+    gizmos().ray(ray, length, ARROW_SIZE, color, GizmoSpace::Screen);
+}
+
+/// An arrow `length` long from the ray's origin.
+pub fn debug_draw_ray_world(ray: Ray, length: f32, color: Color) {
+    let arrow_size = get_state()
+        .camera_2d
+        .screen_distance_to_world(ARROW_SIZE)
+        .min(length / 2.0);
+    gizmos().ray(ray, length, arrow_size, color, GizmoSpace::World);
+}
+
+pub fn debug_draw_text(text: impl Into<String>, position: Vec2, color: Color) {
+    gizmos().push(Gizmo::Text {
+        text: text.into(),
+        position,
+        color,
+        space: GizmoSpace::Screen,
+    });
+}
+
+/// Text at a point in the world, drawn the same size however the camera is zoomed.
+pub fn debug_draw_text_world(text: impl Into<String>, position: Vec2, color: Color) {
+    gizmos().push(Gizmo::Text {
+        text: text.into(),
+        position,
+        color,
+        space: GizmoSpace::World,
+    });
+}
+
+pub fn debug_draw_line_3d(start: Vec3, end: Vec3, color: Color) {
+    gizmos().push(Gizmo::Line3D { start, end, color });
+}
+
+pub fn debug_draw_aabb_3d(aabb: AABB3D, color: Color) {
+    gizmos().aabb_3d(aabb, color);
+}
+
+/// Turns every `debug_draw_*` function on or off at once. They're on to start with.
+pub fn set_gizmos_enabled(enabled: bool) {
+    gizmos().set_enabled(enabled);
+}
+
+pub fn gizmos_enabled() -> bool {
+    gizmos().is_enabled()
+}
+
+pub fn toggle_gizmos() {
+    let gizmos = gizmos();
+    gizmos.set_enabled(!gizmos.is_enabled());
+}
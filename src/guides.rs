@@ -0,0 +1,186 @@
+use bevy_math::Vec2;
+
+use crate::{
+    color::Color,
+    get_state,
+    shapes_2d::{draw_line, draw_line_world, draw_rect},
+    text_rendering::{TextDrawParams, draw_text_ex},
+};
+
+/// Grid lines closer together than this (in pixels) get merged into the next level up.
+const MIN_GRID_SPACING_PX: f32 = 8.0;
+/// Labeled ruler ticks are at least this far apart (in pixels).
+const MIN_RULER_SPACING_PX: f32 = 80.0;
+const RULER_SIZE: f32 = 20.0;
+const RULER_FONT_SIZE: usize = 11;
+
+/// Grid spacing to actually draw at the given zoom. `spacing` gets doubled when zooming out and
+/// halved when zooming in, so lines stay roughly as far apart on screen as they are at a scale
+/// of 1. The second value is how faded in every other line should be, from 0 to 1, so
+/// zooming doesn't pop.
+pub fn adaptive_grid_spacing(spacing: f32, scale: f32) -> (f32, f32) {
+    let base = spacing.max(MIN_GRID_SPACING_PX * 2.0);
+    let level = (base / (spacing * scale)).log2().floor();
+    let spacing = spacing * 2f32.powf(level);
+
+    let half = base * 0.5;
+    let fade = ((spacing * scale - half) / half).clamp(0.0, 1.0);
+
+    (spacing, fade)
+}
+
+/// Smallest 1, 2 or 5 times a power of ten that is at least `min`.
+pub fn nice_step(min: f32) -> f32 {
+    let magnitude = 10f32.powf(min.log10().floor());
+
+    for multiple in [1.0, 2.0, 5.0, 10.0] {
+        let step = magnitude * multiple;
+        if step >= min {
+            return step;
+        }
+    }
+
+    magnitude * 10.0
+}
+
+/// Draws an infinite grid in world space. Only the lines that are actually on screen are
+/// emitted, and the spacing adapts to the camera zoom. `thickness` is in pixels.
+pub fn draw_grid_world(spacing: f32, color: Color, thickness: f32) {
+    let camera = &mut get_state().camera_2d;
+    let (min, max) = camera.visible_bounds();
+    let (spacing, fade) = adaptive_grid_spacing(spacing, camera.scale);
+    let thickness = camera.screen_distance_to_world(thickness);
+
+    let faded = color.with_alpha(color.a * fade);
+    let is_major = |i: i64| i.rem_euclid(2) == 0;
+
+    for i in (min.x / spacing).floor() as i64..=(max.x / spacing).ceil() as i64 {
+        let x = i as f32 * spacing;
+        let color = if is_major(i) { color } else { faded };
+        draw_line_world(Vec2::new(x, min.y), Vec2::new(x, max.y), thickness, color);
+    }
+
+    for i in (min.y / spacing).floor() as i64..=(max.y / spacing).ceil() as i64 {
+        let y = i as f32 * spacing;
+        let color = if is_major(i) { color } else { faded };
+        draw_line_world(Vec2::new(min.x, y), Vec2::new(max.x, y), thickness, color);
+    }
+}
+
+/// Draws the world x and y axes across the whole screen. `thickness` is in pixels.
+pub fn draw_axes_world(thickness: f32) {
+    let camera = &mut get_state().camera_2d;
+    let (min, max) = camera.visible_bounds();
+    let thickness = camera.screen_distance_to_world(thickness);
+
+    draw_line_world(
+        Vec2::new(min.x, 0.0),
+        Vec2::new(max.x, 0.0),
+        thickness,
+        Color::RED_500,
+    );
+    draw_line_world(
+        Vec2::new(0.0, min.y),
+        Vec2::new(0.0, max.y),
+        thickness,
+        Color::GREEN_500,
+    );
+}
+
+/// Draws rulers along the top and left edges of the screen, labeled in world units. Assumes
+/// the 2D camera isn't rotated.
+pub fn draw_rulers() {
+    let state = get_state();
+    let theme = state.theme;
+    let camera = &mut state.camera_2d;
+    let window_size = camera.window_size();
+    let (min, max) = camera.visible_bounds();
+
+    let step = nice_step(camera.screen_distance_to_world(MIN_RULER_SPACING_PX));
+    let minor_step = step / 5.0;
+
+    let top_ticks: Vec<_> = ticks(min.x, max.x, minor_step)
+        .map(|(i, x)| (i, x, camera.world_to_screen(Vec2::new(x, 0.0)).x))
+        .collect();
+    let left_ticks: Vec<_> = ticks(min.y, max.y, minor_step)
+        .map(|(i, y)| (i, y, camera.world_to_screen(Vec2::new(0.0, y)).y))
+        .collect();
+
+    draw_rect(
+        Vec2::ZERO,
+        Vec2::new(window_size.x, RULER_SIZE),
+        theme.surface,
+    );
+    draw_rect(
+        Vec2::ZERO,
+        Vec2::new(RULER_SIZE, window_size.y),
+        theme.surface,
+    );
+    draw_line(
+        Vec2::new(0.0, RULER_SIZE),
+        Vec2::new(window_size.x, RULER_SIZE),
+        1.0,
+        theme.border,
+    );
+    draw_line(
+        Vec2::new(RULER_SIZE, 0.0),
+        Vec2::new(RULER_SIZE, window_size.y),
+        1.0,
+        theme.border,
+    );
+
+    let decimals = (-step.log10().floor()).max(0.0) as usize;
+    let label = |value: f32, position: Vec2| {
+        draw_text_ex(
+            format!("{value:.decimals$}"),
+            TextDrawParams {
+                font_size: RULER_FONT_SIZE,
+                color: theme.text_muted,
+                position,
+                ..Default::default()
+            },
+        );
+    };
+
+    for (i, x, screen_x) in top_ticks {
+        if screen_x < RULER_SIZE {
+            continue;
+        }
+
+        let major = i % 5 == 0;
+        let length = if major { RULER_SIZE } else { RULER_SIZE * 0.3 };
+        draw_line(
+            Vec2::new(screen_x, RULER_SIZE - length),
+            Vec2::new(screen_x, RULER_SIZE),
+            1.0,
+            theme.text_muted,
+        );
+
+        if major {
+            label(x, Vec2::new(screen_x + 3.0, 2.0));
+        }
+    }
+
+    for (i, y, screen_y) in left_ticks {
+        if screen_y < RULER_SIZE {
+            continue;
+        }
+
+        let major = i % 5 == 0;
+        let length = if major { RULER_SIZE } else { RULER_SIZE * 0.3 };
+        draw_line(
+            Vec2::new(RULER_SIZE - length, screen_y),
+            Vec2::new(RULER_SIZE, screen_y),
+            1.0,
+            theme.text_muted,
+        );
+
+        if major {
+            label(y, Vec2::new(2.0, screen_y + 2.0));
+        }
+    }
+}
+
+fn ticks(min: f32, max: f32, step: f32) -> impl Iterator<Item = (i64, f32)> {
+    ((min / step).floor() as i64..=(max / step).ceil() as i64).map(move |i| (i, i as f32 * step))
+}
@@ -1,12 +1,20 @@
-use std::marker::PhantomData;
+use std::{io::Cursor, marker::PhantomData, path::Path};
 
-use crate::get_state;
+use crate::error::{EngineError, EngineResult};
+use crate::{checked_state, get_state};
 use bevy_math::{USizeVec2, UVec2};
 use engine_4_macros::gen_ref_type;
+use image::{DynamicImage, ImageFormat, RgbaImage};
 
 use crate::color::u8::Pixel;
+use crate::textures::{EngineTexture, TextureRef};
+use crate::utils::EngineCreate;
 use crate::utils::usize_rect::USizeRect;
 
+mod edit;
+
+pub use edit::ResizeFilter;
+
 #[derive(Clone)]
 pub struct Image {
     width: usize,
@@ -30,12 +38,14 @@ impl Image {
         Self { width, height, buf }
     }
 
-    pub fn from_bytes(width: usize, height: usize, buf: Vec<u8>) -> anyhow::Result<Self> {
+    pub fn from_bytes(width: usize, height: usize, buf: Vec<u8>) -> EngineResult<Self> {
         let len = buf.len();
 
         if width * height * 4 != len {
-            dbg!(width, height, len);
-            Err(anyhow::anyhow!("Image size mismatch"))
+            Err(EngineError::ImageSize {
+                expected: width * height * 4,
+                actual: len,
+            })
         } else {
             let size = width * height;
             let ptr = buf.as_ptr() as *const Pixel;
@@ -45,6 +55,41 @@ impl Image {
         }
     }
 
+    /// Decodes a PNG, JPEG or anything else the `image` crate can read.
+    pub fn load(bytes: &[u8], format: ImageFormat) -> EngineResult<Self> {
+        let image = image::load(Cursor::new(bytes), format)?.to_rgba8();
+        let (width, height) = image.dimensions();
+        Self::from_bytes(width as usize, height as usize, image.into_raw())
+    }
+
+    /// Writes the image to `path`. Formats without transparency, like JPEG, drop the alpha.
+    pub fn save(&self, path: impl AsRef<Path>, format: ImageFormat) -> EngineResult<()> {
+        let buffer = RgbaImage::from_raw(
+            self.width as u32,
+            self.height as u32,
+            self.clone().into_bytes(),
+        )
+        .ok_or(EngineError::ImageSize {
+            expected: self.size() * 4,
+            actual: self.buf.len() * 4,
+        })?;
+
+        if format == ImageFormat::Jpeg {
+            DynamicImage::ImageRgba8(buffer)
+                .to_rgb8()
+                .save_with_format(path, format)?;
+        } else {
+            buffer.save_with_format(path, format)?;
+        }
+        Ok(())
+    }
+
+    /// Uploads the image to a new texture, for drawing it.
+    pub fn to_texture(&self) -> EngineResult<TextureRef> {
+        checked_state()?;
+        Ok(EngineTexture::from_engine_image(self.clone())?.create())
+    }
+
     pub fn into_bytes(self) -> Vec<u8> {
         let ptr = self.buf.as_ptr() as *const u8;
         let len = self.size() * 4;
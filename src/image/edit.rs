@@ -0,0 +1,341 @@
+//! Editing images on the CPU before they're uploaded: resizing, cropping, rotating, blitting
+//! and drawing simple shapes and text.
+
+use bevy_math::IVec2;
+
+use super::Image;
+use crate::{
+    color::u8::Pixel,
+    text_rendering::{FontRef, chain_fonts, layout_text_with_fallbacks},
+    utils::usize_rect::USizeRect,
+};
+
+/// How [`Image::resize`] picks colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResizeFilter {
+    /// The closest pixel, for pixel art.
+    #[default]
+    Nearest,
+    /// Blends the four closest pixels, for smooth images.
+    Bilinear,
+}
+
+impl Image {
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.buf
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Pixel] {
+        &mut self.buf
+    }
+
+    /// Replaces every pixel with what `f` returns for it and its position.
+    pub fn map_pixels(&mut self, mut f: impl FnMut(usize, usize, Pixel) -> Pixel) {
+        let width = self.width;
+        for (i, pixel) in self.buf.iter_mut().enumerate() {
+            *pixel = f(i % width, i / width, *pixel);
+        }
+    }
+
+    /// A copy stretched or squashed to `width` by `height`.
+    pub fn resize(&self, width: usize, height: usize, filter: ResizeFilter) -> Image {
+        if self.width == 0 || self.height == 0 {
+            return Image::empty(width, height);
+        }
+
+        let scale_x = self.width as f32 / width as f32;
+        let scale_y = self.height as f32 / height as f32;
+        let mut buf = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            for x in 0..width {
+                // sample from the middle of each pixel, so images don't shift when scaled
+                let source_x = (x as f32 + 0.5) * scale_x;
+                let source_y = (y as f32 + 0.5) * scale_y;
+
+                buf.push(match filter {
+                    ResizeFilter::Nearest => self.clamped(source_x as i32, source_y as i32),
+                    ResizeFilter::Bilinear => self.sample_bilinear(source_x - 0.5, source_y - 0.5),
+                });
+            }
+        }
+
+        Image::new(width, height, buf)
+    }
+
+    fn clamped(&self, x: i32, y: i32) -> Pixel {
+        let x = x.clamp(0, self.width as i32 - 1) as usize;
+        let y = y.clamp(0, self.height as i32 - 1) as usize;
+        self.buf[y * self.width + x]
+    }
+
+    fn sample_bilinear(&self, x: f32, y: f32) -> Pixel {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let corners = [
+            (self.clamped(x0, y0), (1.0 - fx) * (1.0 - fy)),
+            (self.clamped(x0 + 1, y0), fx * (1.0 - fy)),
+            (self.clamped(x0, y0 + 1), (1.0 - fx) * fy),
+            (self.clamped(x0 + 1, y0 + 1), fx * fy),
+        ];
+
+        let mut channels = [0.0f32; 4];
+        for (pixel, weight) in corners {
+            for (channel, value) in channels.iter_mut().zip(pixel.raw()) {
+                *channel += value as f32 * weight;
+            }
+        }
+
+        let [r, g, b, a] = channels.map(|channel| channel.round().clamp(0.0, 255.0) as u8);
+        Pixel::from_rgba(r, g, b, a)
+    }
+
+    /// The part of the image inside of `rect`. Unlike [`sub_image`](Self::sub_image), parts of
+    /// `rect` outside of the image are cut off instead of panicking.
+    pub fn crop(&self, rect: USizeRect) -> Image {
+        let max_x = rect.max.x.min(self.width);
+        let max_y = rect.max.y.min(self.height);
+        let min_x = rect.min.x.min(max_x);
+        let min_y = rect.min.y.min(max_y);
+        self.sub_image(USizeRect::new(min_x, min_y, max_x, max_y))
+    }
+
+    /// Rotated a quarter turn clockwise.
+    pub fn rotate_90(&self) -> Image {
+        let mut rotated = Image::empty(self.height, self.width);
+        for (x, y, pixel) in self.iter() {
+            rotated.set(self.height - 1 - y, x, pixel);
+        }
+        rotated
+    }
+
+    pub fn rotate_180(&self) -> Image {
+        let mut rotated = self.clone();
+        rotated.buf.reverse();
+        rotated
+    }
+
+    /// Rotated a quarter turn anticlockwise.
+    pub fn rotate_270(&self) -> Image {
+        let mut rotated = Image::empty(self.height, self.width);
+        for (x, y, pixel) in self.iter() {
+            rotated.set(y, self.width - 1 - x, pixel);
+        }
+        rotated
+    }
+
+    /// Mirrors the image left to right.
+    pub fn flip_horizontal(&mut self) {
+        for row in self.rows_mut() {
+            row.reverse();
+        }
+    }
+
+    /// Mirrors the image top to bottom.
+    pub fn flip_vertical(&mut self) {
+        let (width, height) = (self.width, self.height);
+        for y in 0..height / 2 {
+            let (top, bottom) = self.buf.split_at_mut((height - 1 - y) * width);
+            top[y * width..(y + 1) * width].swap_with_slice(&mut bottom[..width]);
+        }
+    }
+
+    /// Draws `source` with its top left at `position`, blending it over what's already there.
+    /// Parts that end up outside of the image are left out.
+    pub fn blit(&mut self, source: &Image, position: IVec2) {
+        self.blit_with(source, position, |source, destination| {
+            source.blend_over(destination)
+        });
+    }
+
+    /// Like [`blit`](Self::blit), but replaces pixels instead of blending, alpha and all.
+    pub fn copy_from(&mut self, source: &Image, position: IVec2) {
+        self.blit_with(source, position, |source, _| source);
+    }
+
+    fn blit_with(
+        &mut self,
+        source: &Image,
+        position: IVec2,
+        combine: impl Fn(Pixel, Pixel) -> Pixel,
+    ) {
+        for (x, y, pixel) in source.iter() {
+            let target = position + IVec2::new(x as i32, y as i32);
+            if target.x < 0 || target.y < 0 {
+                continue;
+            }
+            if let Some(destination) = self.get_pixel_mut(target.x as usize, target.y as usize) {
+                *destination = combine(pixel, *destination);
+            }
+        }
+    }
+
+    pub fn fill_rect(&mut self, top_left: IVec2, size: IVec2, color: Pixel) {
+        let min = top_left.max(IVec2::ZERO);
+        let max = (top_left + size).min(IVec2::new(self.width as i32, self.height as i32));
+        for y in min.y..max.y {
+            for x in min.x..max.x {
+                self.set_blend(x as usize, y as usize, color);
+            }
+        }
+    }
+
+    /// A rect outline `thickness` pixels wide, inside of the rect.
+    pub fn draw_rect_outline(
+        &mut self,
+        top_left: IVec2,
+        size: IVec2,
+        thickness: i32,
+        color: Pixel,
+    ) {
+        let thickness = thickness.min(size.x / 2).min(size.y / 2).max(1);
+        let bottom = top_left.y + size.y - thickness;
+        let right = top_left.x + size.x - thickness;
+        let side_height = size.y - thickness * 2;
+
+        self.fill_rect(top_left, IVec2::new(size.x, thickness), color);
+        self.fill_rect(
+            IVec2::new(top_left.x, bottom),
+            IVec2::new(size.x, thickness),
+            color,
+        );
+        self.fill_rect(
+            IVec2::new(top_left.x, top_left.y + thickness),
+            IVec2::new(thickness, side_height),
+            color,
+        );
+        self.fill_rect(
+            IVec2::new(right, top_left.y + thickness),
+            IVec2::new(thickness, side_height),
+            color,
+        );
+    }
+
+    /// A one pixel wide line, including both ends.
+    pub fn draw_line(&mut self, start: IVec2, end: IVec2, color: Pixel) {
+        // bresenham's
+        let delta = (end - start).abs();
+        let step = (end - start).signum();
+        let mut error = delta.x - delta.y;
+        let mut point = start;
+
+        loop {
+            self.seti_blend(point.x, point.y, color);
+            if point == end {
+                break;
+            }
+
+            let doubled = error * 2;
+            if doubled > -delta.y {
+                error -= delta.y;
+                point.x += step.x;
+            }
+            if doubled < delta.x {
+                error += delta.x;
+                point.y += step.y;
+            }
+        }
+    }
+
+    pub fn fill_circle(&mut self, center: IVec2, radius: i32, color: Pixel) {
+        self.circle_spans(center, radius, |image, y, x_range| {
+            for x in x_range {
+                image.seti_blend(x, y, color);
+            }
+        });
+    }
+
+    /// A one pixel wide circle outline.
+    pub fn draw_circle_outline(&mut self, center: IVec2, radius: i32, color: Pixel) {
+        // midpoint circle, one octant mirrored eight ways
+        let mut x = radius;
+        let mut y = 0;
+        let mut error = 1 - radius;
+
+        while x >= y {
+            let mut points = vec![
+                IVec2::new(x, y),
+                IVec2::new(y, x),
+                IVec2::new(-y, x),
+                IVec2::new(-x, y),
+                IVec2::new(-x, -y),
+                IVec2::new(-y, -x),
+                IVec2::new(y, -x),
+                IVec2::new(x, -y),
+            ];
+            // the octants meet on the diagonals and axes, so don't blend those twice
+            points.sort_by_key(|point| (point.x, point.y));
+            points.dedup();
+            for point in points {
+                let point = center + point;
+                self.seti_blend(point.x, point.y, color);
+            }
+
+            y += 1;
+            if error < 0 {
+                error += 2 * y + 1;
+            } else {
+                x -= 1;
+                error += 2 * (y - x) + 1;
+            }
+        }
+    }
+
+    /// Calls `f` with each row of a filled circle and the x positions it covers.
+    fn circle_spans(
+        &mut self,
+        center: IVec2,
+        radius: i32,
+        mut f: impl FnMut(&mut Self, i32, std::ops::Range<i32>),
+    ) {
+        if radius < 0 {
+            return;
+        }
+        let radius_squared = radius * radius + radius;
+        for dy in -radius..=radius {
+            let half_width = ((radius_squared - dy * dy) as f32).sqrt() as i32;
+            f(
+                self,
+                center.y + dy,
+                center.x - half_width..center.x + half_width + 1,
+            );
+        }
+    }
+
+    /// Draws `text` with its top left at `position`, using `font` and its fallbacks. Returns
+    /// how big the text is.
+    pub fn draw_text(
+        &mut self,
+        text: &str,
+        position: IVec2,
+        font_size: f32,
+        color: Pixel,
+        font: FontRef,
+    ) -> IVec2 {
+        let chain = font.chain();
+        let fonts = chain_fonts(&chain);
+        let layout = layout_text_with_fallbacks(&fonts, text, font_size);
+
+        for glyph in &layout.glyphs {
+            if glyph.size.x <= 0.0 || glyph.size.y <= 0.0 {
+                continue;
+            }
+
+            let (metrics, coverage) = fonts[glyph.font].rasterize(glyph.character, font_size);
+            let top_left = position + glyph.position.round().as_ivec2();
+            for (i, alpha) in coverage.into_iter().enumerate() {
+                if alpha == 0 {
+                    continue;
+                }
+                let x = top_left.x + (i % metrics.width) as i32;
+                let y = top_left.y + (i / metrics.width) as i32;
+                let alpha = (color.a() as u32 * alpha as u32 / 255) as u8;
+                self.seti_blend(x, y, color.with_alpha(alpha));
+            }
+        }
+
+        layout.size.ceil().as_ivec2()
+    }
+}
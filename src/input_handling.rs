@@ -0,0 +1,1100 @@
+use anyhow::Context;
+use bevy_math::{UVec2, Vec2};
+use glium::winit;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::{Deref, DerefMut},
+    time::Instant,
+};
+use winit::event::{ElementState, Ime, MouseButton, WindowEvent};
+use winit::keyboard::{Key, KeyCode, PhysicalKey};
+use winit_input_helper::WinitInputHelper;
+
+use crate::get_state;
+
+pub(crate) mod actions;
+mod gamepad;
+pub(crate) mod key_names;
+mod recording;
+mod text_field;
+pub(crate) mod touch;
+
+pub use actions::{Axis, AxisBinding, AxisSource, GamepadAxis, InputContext};
+pub use key_names::{key_name, parse_key};
+pub use recording::InputRecording;
+pub use text_field::TextField;
+pub use touch::{Gesture, Touch};
+
+pub(crate) struct Input {
+    helper: WinitInputHelper,
+    action_map: HashMap<Action, Button>,
+    /// Actions that need several buttons held together.
+    chords: HashMap<Action, Vec<Button>>,
+    axes: HashMap<Axis, AxisBinding>,
+    /// Bottom to top.
+    contexts: Vec<(String, InputContext)>,
+    /// Waiting for a button to bind to this, see [`start_rebind`].
+    rebinding: Option<Action>,
+    /// Rebound during the last step.
+    rebound: Option<(Action, Button)>,
+    /// Being recorded to, see [`start_recording`].
+    recording: Option<InputRecording>,
+    /// Buttons that went down or up since the last recorded frame.
+    changes: Vec<(u16, recording::ButtonChange)>,
+    /// Being played back instead of the real input, see [`start_playback`].
+    playback: Option<recording::Playback>,
+    /// `None` if gamepads aren't supported here.
+    gamepads: Option<gamepad::GamepadInputState>,
+    /// Actions bound with [`bind_named`], which follow the config's key binds.
+    named_actions: HashMap<String, Action>,
+    /// Dropped on the window during the last step.
+    dropped_files: Vec<PathBuf>,
+    /// Being dragged over the window right now.
+    hovered_files: Vec<PathBuf>,
+    /// Typed or committed by the IME during the last step.
+    text: String,
+    ime_preedit: Option<ImePreedit>,
+    touch: touch::TouchState,
+    /// What touch times are measured from.
+    created: Instant,
+}
+
+/// Text an IME is in the middle of composing, which hasn't been typed yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ImePreedit {
+    pub text: String,
+    /// Byte range in `text` of the IME's cursor or selection. `None` hides the cursor.
+    pub cursor: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Button {
+    Mouse(MouseButton),
+    Keyboard(KeyCode),
+}
+
+impl Button {
+    /// Returns `true` if the button is [`Mouse`].
+    ///
+    /// [`Mouse`]: Button::Mouse
+    #[must_use]
+    pub fn is_mouse(&self) -> bool {
+        matches!(self, Self::Mouse(..))
+    }
+
+    /// Returns `true` if the button is [`Keyboard`].
+    ///
+    /// [`Keyboard`]: Button::Keyboard
+    #[must_use]
+    pub fn is_keyboard(&self) -> bool {
+        matches!(self, Self::Keyboard(..))
+    }
+
+    pub fn as_mouse(&self) -> Option<&MouseButton> {
+        if let Self::Mouse(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_keyboard(&self) -> Option<&KeyCode> {
+        if let Self::Keyboard(v) = self {
+            Some(v)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<MouseButton> for Button {
+    fn from(value: MouseButton) -> Self {
+        Self::Mouse(value)
+    }
+}
+
+impl From<KeyCode> for Button {
+    fn from(value: KeyCode) -> Self {
+        Self::Keyboard(value)
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, Debug)]
+pub struct Action(u32);
+
+impl Action {
+    pub const fn new(n: u32) -> Self {
+        Self(n)
+    }
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self {
+            helper: WinitInputHelper::new(),
+            action_map: HashMap::new(),
+            chords: HashMap::new(),
+            axes: HashMap::new(),
+            contexts: Vec::new(),
+            rebinding: None,
+            rebound: None,
+            recording: None,
+            changes: Vec::new(),
+            playback: None,
+            gamepads: gamepad::GamepadInputState::new().ok(),
+            named_actions: HashMap::new(),
+            dropped_files: Vec::new(),
+            hovered_files: Vec::new(),
+            text: String::new(),
+            ime_preedit: None,
+            touch: touch::TouchState::default(),
+            created: Instant::now(),
+        }
+    }
+
+    pub fn step(&mut self) {
+        self.dropped_files.clear();
+        self.text.clear();
+        self.rebound = None;
+        self.touch.step(self.created.elapsed().as_secs_f32());
+        if let Some(gamepads) = &mut self.gamepads {
+            gamepads.update();
+        }
+        self.helper.step();
+    }
+
+    /// Returns `true` if the window was asked to close, like [`WinitInputHelper`]'s.
+    pub fn process_window_event(&mut self, event: &WindowEvent) -> bool {
+        // the button that's rebound doesn't count as being pressed, so it doesn't also trigger
+        // whatever it's now bound to
+        if let Some(action) = self.rebinding
+            && let Some(button) = pressed_button(event)
+        {
+            self.rebinding = None;
+            if button != Button::Keyboard(KeyCode::Escape) {
+                self.bind(action, button);
+                self.rebound = Some((action, button));
+            }
+            return false;
+        }
+
+        match event {
+            WindowEvent::HoveredFile(path) => self.hovered_files.push(path.clone()),
+            WindowEvent::HoveredFileCancelled => self.hovered_files.clear(),
+            WindowEvent::DroppedFile(path) => {
+                self.dropped_files.push(path.clone());
+                self.hovered_files.retain(|hovered| hovered != path);
+            }
+            WindowEvent::KeyboardInput { event, .. } => {
+                if event.state.is_pressed()
+                    && let Some(text) = &event.text
+                {
+                    self.text.extend(text.chars().filter(|c| !c.is_control()));
+                }
+                if let PhysicalKey::Code(key) = event.physical_key {
+                    let change = match (event.state, event.repeat) {
+                        (ElementState::Released, _) => recording::ButtonChange::Released,
+                        (ElementState::Pressed, false) => recording::ButtonChange::Pressed,
+                        (ElementState::Pressed, true) => recording::ButtonChange::Repeated,
+                    };
+                    self.record_change(key.into(), change);
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let change = match state {
+                    ElementState::Pressed => recording::ButtonChange::Pressed,
+                    ElementState::Released => recording::ButtonChange::Released,
+                };
+                self.record_change((*button).into(), change);
+            }
+            WindowEvent::Ime(Ime::Preedit(text, cursor)) => {
+                self.ime_preedit = (!text.is_empty()).then(|| ImePreedit {
+                    text: text.clone(),
+                    cursor: *cursor,
+                });
+            }
+            WindowEvent::Ime(Ime::Commit(text)) => {
+                self.text.push_str(text);
+                self.ime_preedit = None;
+            }
+            WindowEvent::Ime(Ime::Disabled) => self.ime_preedit = None,
+            WindowEvent::Touch(touch) => self.touch.process(
+                touch.id,
+                touch.phase,
+                Vec2::new(touch.location.x as f32, touch.location.y as f32),
+                touch.force.map(|force| force.normalized() as f32),
+                self.created.elapsed().as_secs_f32(),
+            ),
+            _ => (),
+        }
+        self.helper.process_window_event(event)
+    }
+
+    pub fn dropped_files(&self) -> &[PathBuf] {
+        &self.dropped_files
+    }
+
+    pub fn hovered_files(&self) -> &[PathBuf] {
+        &self.hovered_files
+    }
+
+    pub fn ime_preedit(&self) -> Option<&ImePreedit> {
+        self.ime_preedit.as_ref()
+    }
+
+    pub fn touches(&self) -> &[Touch] {
+        self.touch.touches()
+    }
+
+    pub fn gestures(&self) -> Vec<Gesture> {
+        self.touch.gestures()
+    }
+
+    pub fn bind_key(&mut self, action: Action, key: KeyCode) {
+        self.action_map.insert(action, key.into());
+    }
+
+    pub fn bind_mouse(&mut self, action: Action, mouse_button: MouseButton) {
+        self.action_map.insert(action, mouse_button.into());
+    }
+
+    pub fn bind_button(&mut self, action: Action, button: Button) {
+        self.action_map.insert(action, button);
+    }
+
+    pub fn bind(&mut self, action: Action, button: impl Into<Button>) {
+        self.action_map.insert(action, button.into());
+    }
+
+    pub fn get_key(&self, action: Action) -> Option<&KeyCode> {
+        self.action_map.get(&action).and_then(|n| n.as_keyboard())
+    }
+
+    pub fn get_mouse(&self, action: Action) -> Option<&MouseButton> {
+        self.action_map.get(&action).and_then(|n| n.as_mouse())
+    }
+
+    pub fn get_button(&self, action: Action) -> Option<&Button> {
+        self.action_map.get(&action)
+    }
+
+    /// Binds `action` to several buttons that have to be held together, like control + S. While
+    /// they are, actions bound to just one of them don't fire.
+    pub fn bind_chord(&mut self, action: Action, buttons: Vec<Button>) {
+        self.chords.insert(action, buttons);
+    }
+
+    pub fn get_chord(&self, action: Action) -> Option<&[Button]> {
+        self.chords.get(&action).map(Vec::as_slice)
+    }
+
+    pub fn bind_axis(&mut self, axis: Axis, binding: AxisBinding) {
+        self.axes.insert(axis, binding);
+    }
+
+    pub fn get_axis_binding(&self, axis: Axis) -> Option<&AxisBinding> {
+        self.axes.get(&axis)
+    }
+
+    pub fn action_pressed(&self, action: Action) -> bool {
+        self.check_action(action, |button| match button {
+            Button::Keyboard(key) => self.key_pressed(key),
+            Button::Mouse(button) => self.mouse_pressed(button),
+        })
+    }
+
+    pub fn action_pressed_os(&self, action: Action) -> bool {
+        self.check_action(action, |button| match button {
+            Button::Keyboard(key) => self.key_pressed_os(key),
+            Button::Mouse(button) => self.mouse_pressed(button),
+        })
+    }
+
+    pub fn action_released(&self, action: Action) -> bool {
+        if !self.action_allowed(action) {
+            return false;
+        }
+
+        let bind_released = self
+            .get_button(action)
+            .is_some_and(|&button| self.button_released(button));
+        // the chord was held until one of its buttons let go
+        let chord_released = self.chords.get(&action).is_some_and(|chord| {
+            chord
+                .iter()
+                .all(|&button| self.button_held(button) || self.button_released(button))
+                && chord.iter().any(|&button| self.button_released(button))
+        });
+
+        bind_released || chord_released
+    }
+
+    pub fn action_held(&self, action: Action) -> bool {
+        self.check_action(action, |button| self.button_held(button))
+    }
+
+    /// Whether `check` is true for the action's button, or for one of its chord's buttons while
+    /// the rest are held.
+    fn check_action(&self, action: Action, check: impl Fn(Button) -> bool) -> bool {
+        if !self.action_allowed(action) {
+            return false;
+        }
+
+        let bind = self
+            .get_button(action)
+            .is_some_and(|&button| !self.in_held_chord(button) && check(button));
+        let chord = self.chords.get(&action).is_some_and(|chord| {
+            chord.iter().all(|&button| self.button_held(button))
+                && chord.iter().any(|&button| check(button))
+        });
+
+        bind || chord
+    }
+
+    fn button_held(&self, button: Button) -> bool {
+        match button {
+            Button::Keyboard(key) => self.key_held(key),
+            Button::Mouse(button) => self.mouse_held(button),
+        }
+    }
+
+    fn button_released(&self, button: Button) -> bool {
+        match button {
+            Button::Keyboard(key) => self.key_released(key),
+            Button::Mouse(button) => self.mouse_released(button),
+        }
+    }
+
+    /// Whether `button` is part of a chord that's being held, which takes it over.
+    fn in_held_chord(&self, button: Button) -> bool {
+        self.chords.values().any(|chord| {
+            chord.len() > 1
+                && chord.contains(&button)
+                && chord.iter().all(|&button| self.button_held(button))
+        })
+    }
+
+    /// Everything `action` is bound to.
+    fn buttons_for(&self, action: Action) -> Vec<Button> {
+        self.action_map
+            .get(&action)
+            .into_iter()
+            .chain(self.chords.get(&action).into_iter().flatten())
+            .copied()
+            .collect()
+    }
+
+    /// Whether the input contexts let `action` fire.
+    pub fn action_allowed(&self, action: Action) -> bool {
+        actions::context_allows_action(&self.contexts, action, |action| self.buttons_for(action))
+    }
+
+    /// Returns 0 if the axis isn't bound, or an input context is blocking it.
+    pub fn axis_value(&self, axis: Axis) -> f32 {
+        let Some(binding) = self.axes.get(&axis) else {
+            return 0.0;
+        };
+        if !actions::context_allows_axis(&self.contexts, axis) {
+            return 0.0;
+        }
+
+        binding.combine(binding.sources.iter().map(|source| {
+            match *source {
+                AxisSource::Buttons { negative, positive } => {
+                    self.button_held(positive) as u8 as f32
+                        - self.button_held(negative) as u8 as f32
+                }
+                AxisSource::Gamepad(axis) => self
+                    .gamepads
+                    .as_ref()
+                    .map_or(0.0, |gamepads| gamepads.axis(axis)),
+                AxisSource::MouseX => self.mouse_diff().0,
+                AxisSource::MouseY => self.mouse_diff().1,
+                AxisSource::ScrollX => self.scroll_diff().0,
+                AxisSource::ScrollY => self.scroll_diff().1,
+            }
+        }))
+    }
+
+    /// Replaces any context with the same name, putting it on top.
+    pub fn push_context(&mut self, name: &str, context: InputContext) {
+        self.remove_context(name);
+        self.contexts.push((name.to_string(), context));
+    }
+
+    pub fn pop_context(&mut self) -> Option<(String, InputContext)> {
+        self.contexts.pop()
+    }
+
+    pub fn remove_context(&mut self, name: &str) -> Option<InputContext> {
+        let index = self.contexts.iter().position(|(n, _)| n == name)?;
+        Some(self.contexts.remove(index).1)
+    }
+
+    pub fn get_all_binds(&self) -> &HashMap<Action, Button> {
+        &self.action_map
+    }
+
+    pub fn start_rebind(&mut self, action: Action) {
+        self.rebinding = Some(action);
+    }
+
+    pub fn cancel_rebind(&mut self) {
+        self.rebinding = None;
+    }
+
+    pub fn rebinding(&self) -> Option<Action> {
+        self.rebinding
+    }
+
+    pub fn rebound(&self) -> Option<(Action, Button)> {
+        self.rebound
+    }
+
+    /// The button and chord binds, in the same format as config files.
+    pub fn serialize_binds(&self) -> String {
+        let file = BindsFile {
+            binds: self
+                .action_map
+                .iter()
+                .map(|(action, button)| (action.0.to_string(), *button))
+                .collect(),
+            chords: self
+                .chords
+                .iter()
+                .map(|(action, chord)| (action.0.to_string(), chord.clone()))
+                .collect(),
+        };
+        // buttons are written by name, which toml can always write
+        toml::to_string(&file).unwrap_or_default()
+    }
+
+    /// Binds everything in `text`, which comes from [`serialize_binds`](Self::serialize_binds).
+    /// Nothing is bound if any of it is wrong.
+    pub fn load_binds(&mut self, text: &str) -> anyhow::Result<()> {
+        let file: BindsFile = toml::from_str(text)?;
+        let action = |key: &str| -> anyhow::Result<Action> {
+            let number = key
+                .parse()
+                .with_context(|| format!("`{key}` isn't an action number"))?;
+            Ok(Action(number))
+        };
+
+        let binds = file
+            .binds
+            .into_iter()
+            .map(|(key, button)| Ok((action(&key)?, button)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let chords = file
+            .chords
+            .into_iter()
+            .map(|(key, chord)| Ok((action(&key)?, chord)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.action_map.extend(binds);
+        self.chords.extend(chords);
+        Ok(())
+    }
+
+    /// The name the action was bound with in [`bind_named`], if it was.
+    pub(crate) fn action_name(&self, action: Action) -> Option<&str> {
+        self.named_actions
+            .iter()
+            .find(|(_, named)| **named == action)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Rebinds every action bound with [`bind_named`] to its button in `key_binds`.
+    pub(crate) fn apply_named_binds(&mut self, key_binds: &BTreeMap<String, Button>) {
+        for (name, action) in &self.named_actions {
+            if let Some(button) = key_binds.get(name) {
+                self.action_map.insert(*action, *button);
+            }
+        }
+    }
+}
+
+/// What [`Input::serialize_binds`] writes, by action number.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct BindsFile {
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    binds: BTreeMap<String, Button>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    chords: BTreeMap<String, Vec<Button>>,
+}
+
+/// The button `event` pressed, ignoring key repeats.
+fn pressed_button(event: &WindowEvent) -> Option<Button> {
+    match event {
+        WindowEvent::KeyboardInput { event, .. } if event.state.is_pressed() && !event.repeat => {
+            match event.physical_key {
+                PhysicalKey::Code(key) => Some(key.into()),
+                PhysicalKey::Unidentified(_) => None,
+            }
+        }
+        WindowEvent::MouseInput {
+            state: ElementState::Pressed,
+            button,
+            ..
+        } => Some((*button).into()),
+        _ => None,
+    }
+}
+
+impl Deref for Input {
+    type Target = WinitInputHelper;
+
+    fn deref(&self) -> &Self::Target {
+        &self.helper
+    }
+}
+
+impl DerefMut for Input {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.helper
+    }
+}
+
+/// Returns true when the key with the specified keycode goes from "not pressed" to "pressed".
+/// Otherwise returns false.
+///
+/// Uses physical keys in the US layout, so for example the `W` key will be in the same physical key on both US and french keyboards.
+///
+/// This is suitable for game controls.
+pub fn key_pressed(keycode: KeyCode) -> bool {
+    get_state().input.key_pressed(keycode)
+}
+
+/// Returns true when the key with the specified keycode goes from "not pressed" to "pressed".
+/// Otherwise returns false.
+///
+/// Uses physical keys in the US layout, so for example the `W` key will be in the same physical key on both US and french keyboards.
+///
+/// Will repeat key presses while held down according to the OS's key repeat configuration
+/// This is suitable for UI.
+pub fn key_pressed_os(keycode: KeyCode) -> bool {
+    get_state().input.key_pressed_os(keycode)
+}
+
+/// Returns true when the key with the specified KeyCode goes from "pressed" to "not pressed".
+/// Otherwise returns false.
+///
+/// Uses physical keys in the US layout.
+pub fn key_released(keycode: KeyCode) -> bool {
+    get_state().input.key_released(keycode)
+}
+
+/// Returns true when the key with the specified keycode remains "pressed".
+/// Otherwise returns false.
+///
+/// Uses physical keys in the US layout.
+pub fn key_held(keycode: KeyCode) -> bool {
+    get_state().input.key_held(keycode)
+}
+
+/// Returns true while any shift key is held on the keyboard.
+/// Otherwise returns false.
+pub fn held_shift() -> bool {
+    get_state().input.held_shift()
+}
+
+/// Returns true while any control key is held on the keyboard.
+/// Otherwise returns false.
+pub fn held_control() -> bool {
+    get_state().input.held_control()
+}
+
+/// Returns true while any alt key is held on the keyboard.
+/// Otherwise returns false.
+pub fn held_alt() -> bool {
+    get_state().input.held_alt()
+}
+
+/// Returns true when the specified keyboard key goes from "not pressed" to "pressed".
+/// Otherwise returns false.
+///
+/// Uses logical keypresses, so for example W is changed between a US and french keyboard.
+/// Will never repeat keypresses while held.
+pub fn key_pressed_logical(check_key: Key<&str>) -> bool {
+    get_state().input.key_pressed_logical(check_key)
+}
+
+/// Returns true when the specified keyboard key goes from "not pressed" to "pressed".
+/// Otherwise returns false.
+///
+/// Uses logical keypresses, so for example W is changed between a US and french keyboard.
+/// Will repeat key presses while held down according to the OS's key repeat configuration.
+/// This is suitable for UI.
+pub fn key_pressed_os_logical(check_key: Key<&str>) -> bool {
+    get_state().input.key_pressed_os_logical(check_key)
+}
+
+/// Returns true when the specified keyboard key goes from "pressed" to "not pressed".
+/// Otherwise returns false.
+///
+/// Uses logical keypresses, so for example W is changed between a US and french keyboard.
+pub fn key_released_logical(check_key: Key<&str>) -> bool {
+    get_state().input.key_released_logical(check_key)
+}
+
+/// Returns true while the specified keyboard key remains "pressed".
+/// Otherwise returns false.
+///
+/// Uses logical keypresses, so for example W is changed between a US and french keyboard.
+pub fn key_held_logical(check_key: Key<&str>) -> bool {
+    get_state().input.key_held_logical(check_key)
+}
+
+/// Returns true when the specified mouse button goes from "not pressed" to "pressed".
+/// Otherwise returns false.
+pub fn mouse_pressed(mouse_button: MouseButton) -> bool {
+    get_state().input.mouse_pressed(mouse_button)
+}
+
+/// Returns true when the specified mouse button goes from "pressed" to "not pressed".
+/// Otherwise returns false.
+pub fn mouse_released(mouse_button: MouseButton) -> bool {
+    get_state().input.mouse_released(mouse_button)
+}
+
+/// Returns true while the specified mouse button remains "pressed".
+/// Otherwise returns false.
+pub fn mouse_held(mouse_button: MouseButton) -> bool {
+    get_state().input.mouse_held(mouse_button)
+}
+
+/// Returns the amount scrolled by the mouse during the last step.
+/// Returns (horizontally, vertically).
+///
+/// Returns (0.0, 0.0) when the window is not focused.
+pub fn scroll_diff() -> (f32, f32) {
+    get_state().input.scroll_diff()
+}
+
+/// Returns the cursor coordinates in pixels, when window is focused AND
+/// (cursor is on window OR any mouse button remains held while cursor moved off window).
+/// Otherwise returns None.
+pub fn cursor() -> Option<(f32, f32)> {
+    get_state().input.cursor()
+}
+
+/// Returns the change in cursor coordinates that occurred during the last step,
+/// when window is focused AND (cursor is on window OR any mouse button remains held
+/// while cursor moved off window). Otherwise returns (0.0, 0.0).
+pub fn cursor_diff() -> (f32, f32) {
+    get_state().input.cursor_diff()
+}
+
+/// Returns the change in mouse coordinates that occurred during the last step.
+///
+/// This is useful when implementing first person controls with a captured mouse.
+pub fn mouse_diff() -> (f32, f32) {
+    get_state().input.mouse_diff()
+}
+
+/// Returns the characters pressed during the last step.
+/// The characters are in the order they were pressed.
+///
+/// These are keys, so they include things like backspace and the arrows. For typing use
+/// [`typed_text`] instead, which also has whatever an IME committed.
+pub fn input_text() -> &'static [Key] {
+    get_state().input.helper.text()
+}
+
+/// The text typed during the last step, after the keyboard layout and any IME, without control
+/// characters. [`TextField`] handles this and the editing keys for you.
+pub fn typed_text() -> &'static str {
+    get_state().input.text()
+}
+
+/// What an IME is composing, to be drawn at the text cursor until it's committed to
+/// [`typed_text`]. IMEs are only used after [`set_ime_allowed`](crate::window::set_ime_allowed).
+pub fn ime_preedit() -> Option<&'static ImePreedit> {
+    get_state().input.ime_preedit()
+}
+
+/// Returns the path to a file that has been drag-and-dropped onto the window. If several were
+/// dropped at once this is only the last one, see [`dropped_files`].
+pub fn dropped_file() -> Option<PathBuf> {
+    get_state().input.dropped_file()
+}
+
+/// Every file drag-and-dropped onto the window during the last step, in the order they were
+/// dropped. Also sent as [`FileDropped`](crate::events::FileDropped) events.
+pub fn dropped_files() -> Vec<PathBuf> {
+    get_state().input.dropped_files().to_vec()
+}
+
+/// Returns true while files are being dragged over the window, before they're dropped.
+pub fn is_file_hovering() -> bool {
+    !get_state().input.hovered_files().is_empty()
+}
+
+/// The files being dragged over the window right now, so a drop target can be highlighted or
+/// check what's coming before it lands.
+pub fn hovered_files() -> &'static [PathBuf] {
+    get_state().input.hovered_files()
+}
+
+/// Every finger on the screen, in the order they were put down.
+pub fn touches() -> &'static [Touch] {
+    get_state().input.touches()
+}
+
+/// Fingers lifted off the screen during the last step, or cancelled by the OS.
+pub fn ended_touches() -> &'static [Touch] {
+    get_state().input.touch.ended()
+}
+
+/// The taps, long presses, pinches and pans made during the last step.
+pub fn gestures() -> Vec<Gesture> {
+    get_state().input.gestures()
+}
+
+/// Where the screen was tapped during the last step, if it was.
+pub fn tapped() -> Option<Vec2> {
+    get_state().input.touch.taps().last().copied()
+}
+
+/// Where a finger has just been held down long enough to be a long press, if one has.
+pub fn long_pressed() -> Option<Vec2> {
+    get_state().input.touch.long_presses().last().copied()
+}
+
+/// The center of a pinch and how much it zoomed during the last step, ready for
+/// [`camera2d_zoom_at`](crate::gfx::camera2d_zoom_at).
+pub fn pinch_zoom() -> Option<(Vec2, f32)> {
+    get_state().input.touch.pinch()
+}
+
+/// How far one finger was dragged, or two moved together, during the last step.
+pub fn touch_pan() -> Vec2 {
+    get_state().input.touch.pan()
+}
+
+/// Returns the current window size if it was resized during the last step.
+/// Otherwise returns None.
+pub fn window_resized() -> Option<UVec2> {
+    get_state()
+        .input
+        .window_resized()
+        .map(|size| UVec2::new(size.width, size.height))
+}
+
+/// Returns the current resolution of the window.
+///
+/// Returns None when no WindowEvent::Resized have been received yet.
+pub fn resolution() -> Option<(u32, u32)> {
+    get_state().input.resolution()
+}
+
+/// Returns the current scale factor if it was changed during the last step.
+/// Otherwise returns None.
+pub fn scale_factor_changed() -> Option<f64> {
+    get_state().input.scale_factor_changed()
+}
+
+/// Returns the current scale_factor of the window.
+///
+/// Returns None when no WindowEvent::ScaleFactorChanged have been received yet.
+pub fn scale_factor() -> Option<f64> {
+    get_state().input.scale_factor()
+}
+
+/// Returns true if the window has been destroyed. Otherwise returns false.
+///
+/// Once this method has returned true once, all following calls to this method will also return true.
+pub fn destroyed() -> bool {
+    get_state().input.destroyed()
+}
+
+/// Returns true if the OS has requested the application to close during this step.
+/// Otherwise returns false.
+pub fn close_requested() -> bool {
+    get_state().input.close_requested()
+}
+
+/// Returns true when the action's bound button goes from "not pressed" to "pressed".
+/// Otherwise returns false.
+///
+/// Returns false if the action is not bound to any button.
+pub fn action_pressed(action: Action) -> bool {
+    get_state().input.action_pressed(action)
+}
+
+/// Returns true when the action's bound button goes from "not pressed" to "pressed".
+/// Otherwise returns false.
+///
+/// For keyboard keys, will repeat key presses while held down according to the OS's key repeat configuration.
+/// This is suitable for UI.
+///
+/// Returns false if the action is not bound to any button.
+pub fn action_pressed_os(action: Action) -> bool {
+    get_state().input.action_pressed_os(action)
+}
+
+/// Returns true when the action's bound button goes from "pressed" to "not pressed".
+/// Otherwise returns false.
+///
+/// Returns false if the action is not bound to any button.
+pub fn action_released(action: Action) -> bool {
+    get_state().input.action_released(action)
+}
+
+/// Returns true while the action's bound button remains "pressed".
+/// Otherwise returns false.
+///
+/// Returns false if the action is not bound to any button.
+pub fn action_held(action: Action) -> bool {
+    get_state().input.action_held(action)
+}
+
+/// Binds a keyboard key to an action.
+///
+/// When the key is pressed, `action_pressed()` and related functions will return true for this action.
+pub fn bind_key(action: Action, key: KeyCode) {
+    get_state().input.bind_key(action, key)
+}
+
+/// Binds a mouse button to an action.
+///
+/// When the mouse button is pressed, `action_pressed()` and related functions will return true for this action.
+pub fn bind_mouse(action: Action, mouse_button: MouseButton) {
+    get_state().input.bind_mouse(action, mouse_button)
+}
+
+/// Binds a button (either keyboard or mouse) to an action.
+///
+/// When the button is pressed, `action_pressed()` and related functions will return true for this action.
+pub fn bind_button(action: Action, button: Button) {
+    get_state().input.bind_button(action, button)
+}
+
+/// Binds a button (either keyboard or mouse) to an action.
+///
+/// When the button is pressed, `action_pressed()` and related functions will return true for this action.
+pub fn bind(action: Action, button: impl Into<Button>) {
+    get_state().input.bind(action, button)
+}
+
+/// Binds an action to the button called `name` in the config's
+/// [`key_binds`](crate::config::EngineConfig::key_binds), or to `default` if the config doesn't
+/// have one, which is then added to the config so it gets written out by `save_config`.
+///
+/// The action follows the config from then on, including when it's hot reloaded.
+pub fn bind_named(action: Action, name: &str, default: impl Into<Button>) {
+    let state = get_state();
+    let button = *state
+        .config
+        .key_binds
+        .entry(name.to_string())
+        .or_insert(default.into());
+    state.input.named_actions.insert(name.to_string(), action);
+    state.input.bind(action, button);
+}
+
+/// Binds the action to the next key or mouse button pressed, for controls menus. Pressing
+/// escape cancels it. The press doesn't count as the action being pressed.
+///
+/// Actions bound with [`bind_named`] also have their button changed in the config, so
+/// `save_config` keeps it.
+pub fn start_rebind(action: Action) {
+    get_state().input.start_rebind(action)
+}
+
+pub fn cancel_rebind() {
+    get_state().input.cancel_rebind()
+}
+
+/// The action waiting for a button, if [`start_rebind`] was called and nothing's been pressed
+/// yet.
+pub fn rebinding() -> Option<Action> {
+    get_state().input.rebinding()
+}
+
+/// The action rebound during the last step and its new button, if one was.
+pub fn rebound() -> Option<(Action, Button)> {
+    get_state().input.rebound()
+}
+
+/// Every button and chord bind as text, to be saved and loaded again with [`load_binds`].
+pub fn serialize_binds() -> String {
+    get_state().input.serialize_binds()
+}
+
+/// Loads binds saved with [`serialize_binds`], on top of those already bound.
+pub fn load_binds(text: &str) -> anyhow::Result<()> {
+    let state = get_state();
+    state.input.load_binds(text)?;
+    sync_named_binds();
+    Ok(())
+}
+
+/// Copies the buttons of actions bound with [`bind_named`] into the config's key binds.
+fn sync_named_binds() {
+    let state = get_state();
+    for (name, action) in &state.input.named_actions {
+        if let Some(button) = state.input.action_map.get(action) {
+            state.config.key_binds.insert(name.clone(), *button);
+        }
+    }
+}
+
+pub(crate) fn update_rebinding() {
+    let state = get_state();
+    if let Some((action, button)) = state.input.rebound()
+        && let Some(name) = state.input.action_name(action)
+    {
+        state.config.key_binds.insert(name.to_string(), button);
+    }
+}
+
+/// Starts recording the input each frame, for replays or tests. The random numbers are
+/// reseeded, and the seed kept in the recording, so that playing it back gives the same game.
+pub fn start_recording() {
+    let state = get_state();
+    let seed = state.rng.random();
+    crate::api::seed_rng(seed);
+    state.input.start_recording(seed);
+}
+
+/// Stops recording, giving back everything recorded since [`start_recording`].
+pub fn stop_recording() -> Option<InputRecording> {
+    get_state().input.stop_recording()
+}
+
+pub fn is_recording() -> bool {
+    get_state().input.is_recording()
+}
+
+/// Plays `recording` back from the next frame on, instead of the real input, until it runs out
+/// or [`stop_playback`] is called. The random numbers are seeded like they were when it was
+/// recorded, and each frame's delta time is the recorded one, so the game plays out the same
+/// way as long as it only depends on those.
+///
+/// This works in headless mode too, which makes it handy for testing gameplay.
+pub fn start_playback(recording: InputRecording) {
+    crate::api::seed_rng(recording.seed);
+    get_state().input.start_playback(recording);
+}
+
+pub fn stop_playback() {
+    get_state().input.stop_playback();
+}
+
+/// Returns true until the recording being played back runs out.
+pub fn is_playing_back() -> bool {
+    get_state().input.is_playing_back()
+}
+
+/// Records or plays back the frame the game's about to see, returning the delta time to use.
+pub(crate) fn update_recording(delta_time: f32) -> f32 {
+    get_state().input.end_frame(delta_time)
+}
+
+/// Returns the keyboard key bound to the specified action, if any.
+///
+/// Returns None if the action is not bound or is bound to a mouse button instead.
+pub fn get_key_binding(action: Action) -> Option<&'static KeyCode> {
+    get_state().input.get_key(action)
+}
+
+/// Returns the mouse button bound to the specified action, if any.
+///
+/// Returns None if the action is not bound or is bound to a keyboard key instead.
+pub fn get_mouse_binding(action: Action) -> Option<&'static MouseButton> {
+    get_state().input.get_mouse(action)
+}
+
+/// Returns the button (keyboard or mouse) bound to the specified action, if any.
+///
+/// Returns None if the action is not bound to any button.
+pub fn get_binding(action: Action) -> Option<&'static Button> {
+    get_state().input.get_button(action)
+}
+
+/// Binds several buttons that have to be held together to an action, like control + S.
+///
+/// The action is pressed when the last of them goes down. While they're all held, actions
+/// bound to only one of the buttons don't fire, so a save shortcut doesn't also move the player.
+pub fn bind_chord<B: Into<Button>>(action: Action, buttons: impl IntoIterator<Item = B>) {
+    get_state()
+        .input
+        .bind_chord(action, buttons.into_iter().map(Into::into).collect())
+}
+
+/// Returns the buttons of the chord bound to the action, if it has one.
+pub fn get_chord_binding(action: Action) -> Option<&'static [Button]> {
+    get_state().input.get_chord(action)
+}
+
+/// Binds an analog axis, made of buttons, gamepad sticks, the mouse or the scroll wheel.
+///
+/// ```ignore
+/// const MOVE_X: Axis = Axis::new(0);
+///
+/// bind_axis(
+///     MOVE_X,
+///     AxisBinding::buttons(KeyCode::KeyA, KeyCode::KeyD)
+///         .with_source(AxisSource::Gamepad(GamepadAxis::LeftStickX)),
+/// );
+/// ```
+pub fn bind_axis(axis: Axis, binding: AxisBinding) {
+    get_state().input.bind_axis(axis, binding)
+}
+
+pub fn get_axis_binding(axis: Axis) -> Option<&'static AxisBinding> {
+    get_state().input.get_axis_binding(axis)
+}
+
+/// The axis' current value. Buttons and sticks go from -1 to 1 before the sensitivity is
+/// applied, and the mouse and scroll wheel add how far they moved during the last step.
+///
+/// Returns 0 if the axis isn't bound, or is blocked by an input context.
+pub fn axis_value(axis: Axis) -> f32 {
+    get_state().input.axis_value(axis)
+}
+
+/// Two axes as a vector no longer than 1, for movement that isn't faster diagonally.
+pub fn axis_vec2(x: Axis, y: Axis) -> Vec2 {
+    Vec2::new(axis_value(x), axis_value(y)).clamp_length_max(1.0)
+}
+
+/// Puts a context on top of the stack, replacing any other called `name`. See
+/// [`InputContext`] for how they decide which actions fire.
+pub fn push_input_context(name: &str, context: InputContext) {
+    get_state().input.push_context(name, context)
+}
+
+/// Takes the top context off the stack.
+pub fn pop_input_context() -> Option<(String, InputContext)> {
+    get_state().input.pop_context()
+}
+
+pub fn remove_input_context(name: &str) -> Option<InputContext> {
+    get_state().input.remove_context(name)
+}
+
+/// The names of the contexts on the stack, from the bottom to the top.
+pub fn input_contexts() -> Vec<&'static str> {
+    get_state()
+        .input
+        .contexts
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect()
+}
+
+/// Returns false if the input contexts are stopping the action from firing.
+pub fn is_action_allowed(action: Action) -> bool {
+    get_state().input.action_allowed(action)
+}
+
+/// Get a map of all the bindings that have been registered with the engine.
+pub fn get_all_binds() -> &'static HashMap<Action, Button> {
+    get_state().input.get_all_binds()
+}
@@ -0,0 +1,188 @@
+//! Analog axes and input contexts, which sit on top of the plain button binds.
+
+use std::collections::HashSet;
+
+pub use gilrs::Axis as GamepadAxis;
+
+use super::{Action, Button};
+
+/// Like an [`Action`], but for something with a value instead of being on or off, such as
+/// moving left and right.
+#[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Copy, Debug)]
+pub struct Axis(u32);
+
+impl Axis {
+    pub const fn new(n: u32) -> Self {
+        Self(n)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AxisSource {
+    /// -1 while `negative` is held and 1 while `positive` is.
+    Buttons {
+        negative: Button,
+        positive: Button,
+    },
+    /// A stick or trigger on whichever gamepad is pushing it furthest, from -1 to 1. Up is
+    /// positive.
+    Gamepad(GamepadAxis),
+    /// How far the mouse moved during the last step, in pixels.
+    MouseX,
+    MouseY,
+    /// How far the scroll wheel moved during the last step.
+    ScrollX,
+    ScrollY,
+}
+
+impl AxisSource {
+    /// Buttons and sticks go from -1 to 1, and the mouse and scroll wheel can go as far as
+    /// they like.
+    fn is_bounded(&self) -> bool {
+        matches!(self, Self::Buttons { .. } | Self::Gamepad(_))
+    }
+}
+
+/// What an [`Axis`] is made of. Every source is added together, with the buttons and sticks
+/// kept between -1 and 1 so that holding a key while pushing the stick isn't twice as fast.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AxisBinding {
+    pub sources: Vec<AxisSource>,
+    /// How far, from 0 to 1, the buttons and sticks have to be pushed before the axis moves, so
+    /// a stick that doesn't quite center doesn't drift. The rest of the range is stretched to
+    /// still reach 1.
+    pub deadzone: f32,
+    /// Multiplies the value.
+    pub sensitivity: f32,
+}
+
+impl AxisBinding {
+    pub fn new(sources: impl IntoIterator<Item = AxisSource>) -> Self {
+        Self {
+            sources: sources.into_iter().collect(),
+            deadzone: 0.15,
+            sensitivity: 1.0,
+        }
+    }
+
+    /// An axis that's -1 while `negative` is held and 1 while `positive` is.
+    pub fn buttons(negative: impl Into<Button>, positive: impl Into<Button>) -> Self {
+        Self::new([AxisSource::Buttons {
+            negative: negative.into(),
+            positive: positive.into(),
+        }])
+    }
+
+    pub fn with_source(mut self, source: AxisSource) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    pub fn with_deadzone(mut self, deadzone: f32) -> Self {
+        self.deadzone = deadzone;
+        self
+    }
+
+    pub fn with_sensitivity(mut self, sensitivity: f32) -> Self {
+        self.sensitivity = sensitivity;
+        self
+    }
+
+    /// Combines the value of each source, in the same order as `sources`.
+    pub(crate) fn combine(&self, values: impl IntoIterator<Item = f32>) -> f32 {
+        let mut bounded = 0.0;
+        let mut unbounded = 0.0;
+        for (source, value) in self.sources.iter().zip(values) {
+            if source.is_bounded() {
+                bounded += value;
+            } else {
+                unbounded += value;
+            }
+        }
+
+        let bounded: f32 = bounded.clamp(-1.0, 1.0);
+        let deadzone = self.deadzone.clamp(0.0, 0.999);
+        let bounded = if bounded.abs() <= deadzone {
+            0.0
+        } else {
+            bounded.signum() * (bounded.abs() - deadzone) / (1.0 - deadzone)
+        };
+
+        (bounded + unbounded) * self.sensitivity
+    }
+}
+
+/// A set of actions and axes that belong together, like those for gameplay or for a menu. They
+/// go on a stack with [`push_input_context`](crate::input::push_input_context), and the ones
+/// on top get first pick of the buttons.
+///
+/// An action that's in a context only fires if no context above it is [`blocking`], and no
+/// context above it has an action using one of the same buttons. Actions and axes that aren't
+/// in any context always work.
+///
+/// [`blocking`]: InputContext::blocking
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InputContext {
+    pub actions: HashSet<Action>,
+    pub axes: HashSet<Axis>,
+    /// Stops everything in the contexts below this one, like a pause menu over gameplay.
+    pub blocking: bool,
+}
+
+impl InputContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_actions(mut self, actions: impl IntoIterator<Item = Action>) -> Self {
+        self.actions.extend(actions);
+        self
+    }
+
+    pub fn with_axes(mut self, axes: impl IntoIterator<Item = Axis>) -> Self {
+        self.axes.extend(axes);
+        self
+    }
+
+    pub fn blocking(mut self) -> Self {
+        self.blocking = true;
+        self
+    }
+}
+
+/// Whether the contexts let `action` through, with `stack` from bottom to top and `buttons`
+/// giving what each action is bound to.
+pub(crate) fn context_allows_action(
+    stack: &[(String, InputContext)],
+    action: Action,
+    buttons: impl Fn(Action) -> Vec<Button>,
+) -> bool {
+    let Some(owner) = stack
+        .iter()
+        .rposition(|(_, context)| context.actions.contains(&action))
+    else {
+        return true;
+    };
+
+    let own = buttons(action);
+    !stack[owner + 1..].iter().any(|(_, context)| {
+        context.blocking
+            || context
+                .actions
+                .iter()
+                .any(|&other| buttons(other).iter().any(|button| own.contains(button)))
+    })
+}
+
+pub(crate) fn context_allows_axis(stack: &[(String, InputContext)], axis: Axis) -> bool {
+    let Some(owner) = stack
+        .iter()
+        .rposition(|(_, context)| context.axes.contains(&axis))
+    else {
+        return true;
+    };
+
+    !stack[owner + 1..]
+        .iter()
+        .any(|(_, context)| context.blocking)
+}
@@ -0,0 +1,28 @@
+use anyhow::anyhow;
+use gilrs::{Axis, Gilrs};
+
+pub struct GamepadInputState {
+    gilrs: Gilrs,
+}
+
+impl GamepadInputState {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            gilrs: Gilrs::new().map_err(|_| anyhow!("Could not initialize gamepad state."))?,
+        })
+    }
+
+    /// Takes gilrs' events, which is what keeps its gamepad state up to date.
+    pub(crate) fn update(&mut self) {
+        while self.gilrs.next_event().is_some() {}
+    }
+
+    /// The value of `axis` on whichever connected gamepad has it pushed furthest.
+    pub(crate) fn axis(&self, axis: Axis) -> f32 {
+        self.gilrs
+            .gamepads()
+            .map(|(_, gamepad)| gamepad.value(axis))
+            .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .unwrap_or(0.0)
+    }
+}
@@ -0,0 +1,151 @@
+//! Names for keys and mouse buttons, for config files and rebinding menus. Keys use winit's
+//! names (`KeyA`, `Digit1`, `ArrowUp`, `Space`), and mouse buttons are `MouseLeft`,
+//! `MouseRight`, `MouseMiddle`, `MouseBack`, `MouseForward` or `Mouse<n>`.
+//!
+//! Parsing ignores case and lets letters and digits leave off `Key` and `Digit`, so `a` and `1`
+//! work too.
+
+use std::{fmt::Display, str::FromStr};
+
+use glium::winit::{event::MouseButton, keyboard::KeyCode};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+use super::Button;
+
+macro_rules! key_names {
+    ($($key:ident)*) => {
+        const KEYS: &[(KeyCode, &str)] = &[$((KeyCode::$key, stringify!($key)),)*];
+    };
+}
+
+key_names! {
+    Backquote Backslash BracketLeft BracketRight Comma Digit0 Digit1 Digit2 Digit3 Digit4
+    Digit5 Digit6 Digit7 Digit8 Digit9 Equal IntlBackslash IntlRo IntlYen KeyA KeyB KeyC
+    KeyD KeyE KeyF KeyG KeyH KeyI KeyJ KeyK KeyL KeyM KeyN KeyO KeyP KeyQ KeyR KeyS KeyT
+    KeyU KeyV KeyW KeyX KeyY KeyZ Minus Period Quote Semicolon Slash AltLeft AltRight
+    Backspace CapsLock ContextMenu ControlLeft ControlRight Enter SuperLeft SuperRight
+    ShiftLeft ShiftRight Space Tab Convert KanaMode Lang1 Lang2 Lang3 Lang4 Lang5 NonConvert
+    Delete End Help Home Insert PageDown PageUp ArrowDown ArrowLeft ArrowRight ArrowUp
+    NumLock Numpad0 Numpad1 Numpad2 Numpad3 Numpad4 Numpad5 Numpad6 Numpad7 Numpad8 Numpad9
+    NumpadAdd NumpadBackspace NumpadClear NumpadClearEntry NumpadComma NumpadDecimal
+    NumpadDivide NumpadEnter NumpadEqual NumpadHash NumpadMemoryAdd NumpadMemoryClear
+    NumpadMemoryRecall NumpadMemoryStore NumpadMemorySubtract NumpadMultiply NumpadParenLeft
+    NumpadParenRight NumpadStar NumpadSubtract Escape Fn FnLock PrintScreen ScrollLock Pause
+    BrowserBack BrowserFavorites BrowserForward BrowserHome BrowserRefresh BrowserSearch
+    BrowserStop Eject LaunchApp1 LaunchApp2 LaunchMail MediaPlayPause MediaSelect MediaStop
+    MediaTrackNext MediaTrackPrevious Power Sleep AudioVolumeDown AudioVolumeMute
+    AudioVolumeUp WakeUp Meta Hyper Turbo Abort Resume Suspend Again Copy Cut Find Open
+    Paste Props Select Undo Hiragana Katakana F1 F2 F3 F4 F5 F6 F7 F8 F9 F10 F11 F12 F13 F14
+    F15 F16 F17 F18 F19 F20 F21 F22 F23 F24 F25 F26 F27 F28 F29 F30 F31 F32 F33 F34 F35
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    KEYS.iter()
+        .find(|(k, _)| *k == key)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| format!("{key:?}"))
+}
+
+pub fn parse_key(name: &str) -> Option<KeyCode> {
+    let find = |name: &str| {
+        KEYS.iter()
+            .find(|(_, n)| n.eq_ignore_ascii_case(name))
+            .map(|(key, _)| *key)
+    };
+
+    find(name).or_else(|| {
+        let mut chars = name.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_alphabetic() => find(&format!("Key{c}")),
+            (Some(c), None) if c.is_ascii_digit() => find(&format!("Digit{c}")),
+            _ => None,
+        }
+    })
+}
+
+/// Where mouse buttons start in [`button_code`], after every key.
+const MOUSE_CODES: u16 = 1024;
+
+/// A small number standing for `button`, for packing into input recordings. `None` for keys
+/// that aren't in the table.
+pub(crate) fn button_code(button: Button) -> Option<u16> {
+    match button {
+        Button::Keyboard(key) => KEYS.iter().position(|(k, _)| *k == key).map(|i| i as u16),
+        Button::Mouse(MouseButton::Left) => Some(MOUSE_CODES),
+        Button::Mouse(MouseButton::Right) => Some(MOUSE_CODES + 1),
+        Button::Mouse(MouseButton::Middle) => Some(MOUSE_CODES + 2),
+        Button::Mouse(MouseButton::Back) => Some(MOUSE_CODES + 3),
+        Button::Mouse(MouseButton::Forward) => Some(MOUSE_CODES + 4),
+        Button::Mouse(MouseButton::Other(n)) => n.checked_add(MOUSE_CODES + 5),
+    }
+}
+
+pub(crate) fn button_from_code(code: u16) -> Option<Button> {
+    let mouse = match code.checked_sub(MOUSE_CODES) {
+        None => {
+            return KEYS
+                .get(code as usize)
+                .map(|(key, _)| Button::Keyboard(*key));
+        }
+        Some(0) => MouseButton::Left,
+        Some(1) => MouseButton::Right,
+        Some(2) => MouseButton::Middle,
+        Some(3) => MouseButton::Back,
+        Some(4) => MouseButton::Forward,
+        Some(n) => MouseButton::Other(n - 5),
+    };
+    Some(Button::Mouse(mouse))
+}
+
+impl Display for Button {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Button::Keyboard(key) => f.write_str(&key_name(*key)),
+            Button::Mouse(MouseButton::Left) => f.write_str("MouseLeft"),
+            Button::Mouse(MouseButton::Right) => f.write_str("MouseRight"),
+            Button::Mouse(MouseButton::Middle) => f.write_str("MouseMiddle"),
+            Button::Mouse(MouseButton::Back) => f.write_str("MouseBack"),
+            Button::Mouse(MouseButton::Forward) => f.write_str("MouseForward"),
+            Button::Mouse(MouseButton::Other(n)) => write!(f, "Mouse{n}"),
+        }
+    }
+}
+
+/// By name, like in config files, so `"Space"` or `"MouseLeft"`.
+impl Serialize for Button {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Button {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(D::Error::custom)
+    }
+}
+
+impl FromStr for Button {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mouse = match s.to_ascii_lowercase().as_str() {
+            "mouseleft" => Some(MouseButton::Left),
+            "mouseright" => Some(MouseButton::Right),
+            "mousemiddle" => Some(MouseButton::Middle),
+            "mouseback" => Some(MouseButton::Back),
+            "mouseforward" => Some(MouseButton::Forward),
+            lower => lower
+                .strip_prefix("mouse")
+                .and_then(|n| n.parse().ok())
+                .map(MouseButton::Other),
+        };
+
+        match mouse {
+            Some(button) => Ok(Button::Mouse(button)),
+            None => parse_key(s)
+                .map(Button::Keyboard)
+                .ok_or_else(|| anyhow::anyhow!("`{s}` isn't a key or mouse button")),
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! Recording what the player does each frame, and feeding it back in for replays and automated
+//! tests.
+//!
+//! While a recording plays, the button, cursor, mouse, scroll and text queries on [`Input`]
+//! answer from it instead of from the window. Logical keys, touches and the IME aren't
+//! recorded.
+
+use std::{
+    collections::HashSet,
+    io::{Read, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
+use glium::winit::{event::MouseButton, keyboard::KeyCode};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    Button, Input,
+    key_names::{button_code, button_from_code},
+};
+use crate::storage::format;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ButtonChange {
+    Pressed,
+    /// Held long enough for the OS to repeat it.
+    Repeated,
+    Released,
+}
+
+/// Everything the game could see of the input during one frame.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct InputFrame {
+    /// Buttons by their [`button_code`].
+    changes: Vec<(u16, ButtonChange)>,
+    cursor: Option<(f32, f32)>,
+    mouse_diff: (f32, f32),
+    scroll_diff: (f32, f32),
+    text: String,
+    delta_time: f32,
+}
+
+/// The input for a run of frames, from [`start_recording`](crate::input::start_recording).
+///
+/// It can be kept with [`storage::save`](crate::storage::save) like anything else, or turned
+/// into a compact buffer with [`to_bytes`](Self::to_bytes).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputRecording {
+    /// What the random numbers were seeded with when recording started, so they come out the
+    /// same when it's played back.
+    pub seed: u64,
+    frames: Vec<InputFrame>,
+}
+
+impl InputRecording {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// In seconds, unscaled.
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.delta_time).sum()
+    }
+
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&format::to_bytes(self)?)?;
+        Ok(encoder.finish()?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut decompressed = Vec::new();
+        DeflateDecoder::new(bytes)
+            .read_to_end(&mut decompressed)
+            .context("input recording is corrupted")?;
+        Ok(format::from_bytes(&decompressed)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_bytes()?)
+            .with_context(|| format!("couldn't write input recording {}", path.display()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("couldn't read input recording {}", path.display()))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+pub(super) struct Playback {
+    recording: InputRecording,
+    next: usize,
+    frame: InputFrame,
+    previous_cursor: Option<(f32, f32)>,
+    held: HashSet<Button>,
+}
+
+impl Playback {
+    fn new(recording: InputRecording) -> Self {
+        Self {
+            recording,
+            next: 0,
+            frame: InputFrame::default(),
+            previous_cursor: None,
+            held: HashSet::new(),
+        }
+    }
+
+    /// Moves on to the next frame, returning its delta time, or `None` once they've all played.
+    fn advance(&mut self) -> Option<f32> {
+        let frame = self.recording.frames.get(self.next)?.clone();
+        self.next += 1;
+
+        for (button, change) in self.frame_changes(&frame) {
+            match change {
+                ButtonChange::Pressed | ButtonChange::Repeated => self.held.insert(button),
+                ButtonChange::Released => self.held.remove(&button),
+            };
+        }
+        self.previous_cursor = self.frame.cursor;
+        self.frame = frame;
+
+        Some(self.frame.delta_time)
+    }
+
+    fn frame_changes(&self, frame: &InputFrame) -> Vec<(Button, ButtonChange)> {
+        frame
+            .changes
+            .iter()
+            .filter_map(|&(code, change)| Some((button_from_code(code)?, change)))
+            .collect()
+    }
+
+    fn changed(&self, button: Button, wanted: &[ButtonChange]) -> bool {
+        let Some(code) = button_code(button) else {
+            return false;
+        };
+        self.frame
+            .changes
+            .iter()
+            .any(|&(c, change)| c == code && wanted.contains(&change))
+    }
+}
+
+impl Input {
+    pub fn start_recording(&mut self, seed: u64) {
+        self.changes.clear();
+        self.recording = Some(InputRecording::new(seed));
+    }
+
+    pub fn stop_recording(&mut self) -> Option<InputRecording> {
+        self.recording.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn start_playback(&mut self, recording: InputRecording) {
+        self.playback = Some(Playback::new(recording));
+    }
+
+    pub fn stop_playback(&mut self) {
+        self.playback = None;
+    }
+
+    pub fn is_playing_back(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    pub(crate) fn record_change(&mut self, button: Button, change: ButtonChange) {
+        if self.recording.is_some()
+            && let Some(code) = button_code(button)
+        {
+            self.changes.push((code, change));
+        }
+    }
+
+    /// Records the frame the game's about to see, or swaps in the next recorded one while
+    /// playing back. Returns the delta time to use, which is the recorded one while playing.
+    pub(crate) fn end_frame(&mut self, delta_time: f32) -> f32 {
+        if let Some(recording) = &mut self.recording {
+            recording.frames.push(InputFrame {
+                changes: std::mem::take(&mut self.changes),
+                cursor: self.helper.cursor(),
+                mouse_diff: self.helper.mouse_diff(),
+                scroll_diff: self.helper.scroll_diff(),
+                text: self.text.clone(),
+                delta_time,
+            });
+        }
+
+        if let Some(playback) = &mut self.playback {
+            match playback.advance() {
+                Some(recorded) => return recorded,
+                None => self.playback = None,
+            }
+        }
+
+        delta_time
+    }
+
+    // these hide `WinitInputHelper`'s versions, so that everything asking `Input` gets the
+    // recorded input while it's playing
+
+    pub fn key_pressed(&self, key: KeyCode) -> bool {
+        match &self.playback {
+            Some(playback) => playback.changed(key.into(), &[ButtonChange::Pressed]),
+            None => self.helper.key_pressed(key),
+        }
+    }
+
+    pub fn key_pressed_os(&self, key: KeyCode) -> bool {
+        match &self.playback {
+            Some(playback) => {
+                playback.changed(key.into(), &[ButtonChange::Pressed, ButtonChange::Repeated])
+            }
+            None => self.helper.key_pressed_os(key),
+        }
+    }
+
+    pub fn key_released(&self, key: KeyCode) -> bool {
+        match &self.playback {
+            Some(playback) => playback.changed(key.into(), &[ButtonChange::Released]),
+            None => self.helper.key_released(key),
+        }
+    }
+
+    pub fn key_held(&self, key: KeyCode) -> bool {
+        match &self.playback {
+            Some(playback) => playback.held.contains(&key.into()),
+            None => self.helper.key_held(key),
+        }
+    }
+
+    pub fn held_shift(&self) -> bool {
+        self.key_held(KeyCode::ShiftLeft) || self.key_held(KeyCode::ShiftRight)
+    }
+
+    pub fn held_control(&self) -> bool {
+        self.key_held(KeyCode::ControlLeft) || self.key_held(KeyCode::ControlRight)
+    }
+
+    pub fn held_alt(&self) -> bool {
+        self.key_held(KeyCode::AltLeft) || self.key_held(KeyCode::AltRight)
+    }
+
+    pub fn mouse_pressed(&self, button: MouseButton) -> bool {
+        match &self.playback {
+            Some(playback) => playback.changed(button.into(), &[ButtonChange::Pressed]),
+            None => self.helper.mouse_pressed(button),
+        }
+    }
+
+    pub fn mouse_released(&self, button: MouseButton) -> bool {
+        match &self.playback {
+            Some(playback) => playback.changed(button.into(), &[ButtonChange::Released]),
+            None => self.helper.mouse_released(button),
+        }
+    }
+
+    pub fn mouse_held(&self, button: MouseButton) -> bool {
+        match &self.playback {
+            Some(playback) => playback.held.contains(&button.into()),
+            None => self.helper.mouse_held(button),
+        }
+    }
+
+    pub fn cursor(&self) -> Option<(f32, f32)> {
+        match &self.playback {
+            Some(playback) => playback.frame.cursor,
+            None => self.helper.cursor(),
+        }
+    }
+
+    pub fn cursor_diff(&self) -> (f32, f32) {
+        match &self.playback {
+            Some(playback) => match (playback.frame.cursor, playback.previous_cursor) {
+                (Some(current), Some(previous)) => (current.0 - previous.0, current.1 - previous.1),
+                _ => (0.0, 0.0),
+            },
+            None => self.helper.cursor_diff(),
+        }
+    }
+
+    pub fn mouse_diff(&self) -> (f32, f32) {
+        match &self.playback {
+            Some(playback) => playback.frame.mouse_diff,
+            None => self.helper.mouse_diff(),
+        }
+    }
+
+    pub fn scroll_diff(&self) -> (f32, f32) {
+        match &self.playback {
+            Some(playback) => playback.frame.scroll_diff,
+            None => self.helper.scroll_diff(),
+        }
+    }
+
+    /// Characters typed during the last step, with control characters like backspace left out.
+    pub fn text(&self) -> &str {
+        match &self.playback {
+            Some(playback) => &playback.frame.text,
+            None => &self.text,
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! A single line of editable text, for name entry, chat boxes and the like.
+
+use std::ops::Range;
+
+use bevy_math::Vec2;
+use glium::winit::keyboard::KeyCode;
+
+use crate::{
+    shapes_2d::{draw_line, draw_rect},
+    text_rendering::{TextDrawParams, draw_text_ex, measure_text_ex},
+    window::set_ime_cursor_area,
+};
+
+use super::{held_alt, held_control, held_shift, ime_preedit, key_pressed_os, typed_text};
+
+/// Text with a cursor and selection, edited by [`update`](Self::update) each frame.
+///
+/// Positions are byte indices into [`text`](Self::text), always on a char boundary.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TextField {
+    text: String,
+    cursor: usize,
+    /// The other end of the selection, if anything is selected.
+    anchor: Option<usize>,
+    /// In chars.
+    max_len: Option<usize>,
+}
+
+impl TextField {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts with `text`, with the cursor at the end.
+    pub fn with_text(text: impl Into<String>) -> Self {
+        let mut field = Self::new();
+        field.set_text(text);
+        field
+    }
+
+    /// Stops anything being typed past `max_len` chars.
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = Some(max_len);
+        self.truncate();
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Replaces the text, and puts the cursor at the end.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.truncate();
+        self.cursor = self.text.len();
+        self.anchor = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.set_text(String::new());
+    }
+
+    /// Returns the text and clears the field, like after sending a chat message.
+    pub fn take(&mut self) -> String {
+        let text = std::mem::take(&mut self.text);
+        self.clear();
+        text
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor?;
+        Some(anchor.min(self.cursor)..anchor.max(self.cursor))
+    }
+
+    pub fn selected_text(&self) -> &str {
+        self.selection().map_or("", |range| &self.text[range])
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.text.len();
+        self.collapse_empty_selection();
+    }
+
+    /// Types `text` at the cursor, replacing the selection. Control characters are left out, and
+    /// it's cut short if it would go past the max length.
+    pub fn insert(&mut self, text: &str) {
+        self.delete_selection();
+
+        let room = match self.max_len {
+            Some(max_len) => max_len.saturating_sub(self.text.chars().count()),
+            None => usize::MAX,
+        };
+        let text: String = text
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(room)
+            .collect();
+
+        self.text.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+    }
+
+    /// Deletes the selection, or the char before the cursor.
+    pub fn backspace(&mut self) {
+        if !self.delete_selection() {
+            let start = self.prev_boundary(self.cursor);
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+    }
+
+    /// Deletes the selection, or the char after the cursor.
+    pub fn delete(&mut self) {
+        if !self.delete_selection() {
+            let end = self.next_boundary(self.cursor);
+            self.text.replace_range(self.cursor..end, "");
+        }
+    }
+
+    /// Deletes the selection, or back to the start of the word before the cursor.
+    pub fn backspace_word(&mut self) {
+        if !self.delete_selection() {
+            let start = self.prev_word(self.cursor);
+            self.text.replace_range(start..self.cursor, "");
+            self.cursor = start;
+        }
+    }
+
+    /// Moves one char left, or to the start of the selection. Holding `select` extends the
+    /// selection instead.
+    pub fn move_left(&mut self, select: bool) {
+        match self.selection() {
+            Some(range) if !select => self.move_to(range.start, false),
+            _ => self.move_to(self.prev_boundary(self.cursor), select),
+        }
+    }
+
+    pub fn move_right(&mut self, select: bool) {
+        match self.selection() {
+            Some(range) if !select => self.move_to(range.end, false),
+            _ => self.move_to(self.next_boundary(self.cursor), select),
+        }
+    }
+
+    /// Moves to the start of the word before the cursor.
+    pub fn move_word_left(&mut self, select: bool) {
+        self.move_to(self.prev_word(self.cursor), select);
+    }
+
+    /// Moves to the end of the word after the cursor.
+    pub fn move_word_right(&mut self, select: bool) {
+        self.move_to(self.next_word(self.cursor), select);
+    }
+
+    pub fn move_home(&mut self, select: bool) {
+        self.move_to(0, select);
+    }
+
+    pub fn move_end(&mut self, select: bool) {
+        self.move_to(self.text.len(), select);
+    }
+
+    /// Takes this frame's typing and editing keys. Returns true if enter was pressed.
+    ///
+    /// Handles backspace, delete, the arrows, home and end, with shift to select and control to
+    /// go by words, and control + A to select everything.
+    pub fn update(&mut self) -> bool {
+        let select = held_shift();
+        let words = held_control();
+
+        if words && key_pressed_os(KeyCode::KeyA) {
+            self.select_all();
+        } else {
+            let text = typed_text();
+            // AltGr shows up as control and alt held together on some platforms
+            if !text.is_empty() && (!words || held_alt()) {
+                self.insert(text);
+            }
+        }
+
+        if key_pressed_os(KeyCode::Backspace) {
+            if words {
+                self.backspace_word();
+            } else {
+                self.backspace();
+            }
+        }
+        if key_pressed_os(KeyCode::Delete) {
+            self.delete();
+        }
+        if key_pressed_os(KeyCode::ArrowLeft) {
+            if words {
+                self.move_word_left(select);
+            } else {
+                self.move_left(select);
+            }
+        }
+        if key_pressed_os(KeyCode::ArrowRight) {
+            if words {
+                self.move_word_right(select);
+            } else {
+                self.move_right(select);
+            }
+        }
+        if key_pressed_os(KeyCode::Home) {
+            self.move_home(select);
+        }
+        if key_pressed_os(KeyCode::End) {
+            self.move_end(select);
+        }
+
+        key_pressed_os(KeyCode::Enter) || key_pressed_os(KeyCode::NumpadEnter)
+    }
+
+    /// Draws the text at `params.position` in screen space, with the selection, the cursor, and
+    /// anything an IME is composing underlined at the cursor. Also tells the IME where the
+    /// cursor is.
+    pub fn draw(&self, params: TextDrawParams) {
+        let width = |text: &str| measure_text_ex(text, params).size.x;
+        let height = params.font_size as f32;
+        let top_left = params.position;
+
+        if let Some(range) = self.selection() {
+            let start = width(&self.text[..range.start]);
+            let end = width(&self.text[..range.end]);
+            draw_rect(
+                top_left + Vec2::new(start, 0.0),
+                Vec2::new(end - start, height),
+                params.color.with_alpha(0.3),
+            );
+        }
+
+        let (before, after) = self.text.split_at(self.cursor);
+        let preedit = ime_preedit().map_or("", |preedit| preedit.text.as_str());
+        draw_text_ex(format!("{before}{preedit}{after}"), params);
+
+        let cursor_x = width(before);
+        if !preedit.is_empty() {
+            let y = top_left.y + height;
+            let end = cursor_x + width(preedit);
+            draw_line(
+                Vec2::new(top_left.x + cursor_x, y),
+                Vec2::new(top_left.x + end, y),
+                1.0,
+                params.color,
+            );
+        }
+
+        let cursor_top = top_left + Vec2::new(cursor_x, 0.0);
+        draw_rect(cursor_top, Vec2::new(1.0, height), params.color);
+        set_ime_cursor_area(cursor_top, Vec2::new(1.0, height));
+    }
+
+    fn move_to(&mut self, position: usize, select: bool) {
+        if select {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = position;
+        self.collapse_empty_selection();
+    }
+
+    fn collapse_empty_selection(&mut self) {
+        if self.anchor == Some(self.cursor) {
+            self.anchor = None;
+        }
+    }
+
+    /// Returns whether there was a selection to delete.
+    fn delete_selection(&mut self) -> bool {
+        let Some(range) = self.selection() else {
+            return false;
+        };
+        self.text.replace_range(range.clone(), "");
+        self.cursor = range.start;
+        self.anchor = None;
+        true
+    }
+
+    fn truncate(&mut self) {
+        if let Some(max_len) = self.max_len
+            && let Some((end, _)) = self.text.char_indices().nth(max_len)
+        {
+            self.text.truncate(end);
+            self.cursor = self.cursor.min(end);
+            self.anchor = self.anchor.map(|anchor| anchor.min(end));
+        }
+    }
+
+    fn prev_boundary(&self, position: usize) -> usize {
+        self.text[..position]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self, position: usize) -> usize {
+        self.text[position..]
+            .chars()
+            .next()
+            .map_or(position, |c| position + c.len_utf8())
+    }
+
+    fn prev_word(&self, position: usize) -> usize {
+        let before = self.text[..position].trim_end();
+        before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8())
+    }
+
+    fn next_word(&self, position: usize) -> usize {
+        let after = &self.text[position..];
+        let word_start = after.len() - after.trim_start().len();
+        after[word_start..]
+            .find(char::is_whitespace)
+            .map_or(self.text.len(), |i| position + word_start + i)
+    }
+}
@@ -0,0 +1,205 @@
+//! Fingers on a touch screen, and the gestures they make.
+
+use bevy_math::Vec2;
+use glium::winit::event::TouchPhase;
+
+/// How far, in pixels, a finger can wander and still count as a tap or long press.
+const TAP_SLOP: f32 = 10.0;
+/// How long, in seconds, a finger has to stay down for a long press. Anything shorter is a tap.
+const LONG_PRESS_TIME: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Touch {
+    /// Stays the same for as long as the finger is down.
+    pub id: u64,
+    /// In screen space, like the cursor.
+    pub position: Vec2,
+    pub start_position: Vec2,
+    /// How many seconds the finger has been down.
+    pub duration: f32,
+    /// From 0 to 1, on devices that can tell how hard the screen is being pressed.
+    pub force: Option<f32>,
+    start_time: f32,
+    /// Moved too far to be a tap or long press.
+    moved: bool,
+    /// Was down at the same time as another finger, so it's part of a pinch.
+    multi: bool,
+    long_pressed: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    /// A single finger put down and lifted again quickly, without moving.
+    Tap { position: Vec2 },
+    /// A single finger held down without moving. Sent once, while it's still down.
+    LongPress { position: Vec2 },
+    /// Two fingers moving apart or together. `scale` is how much further apart they are than
+    /// last step, so it can be passed straight to
+    /// [`camera2d_zoom_at`](crate::gfx::camera2d_zoom_at) along with `center`.
+    Pinch { center: Vec2, scale: f32 },
+    /// One finger dragged, or two moved together, by `delta` pixels since last step.
+    Pan { delta: Vec2 },
+}
+
+#[derive(Default)]
+pub(crate) struct TouchState {
+    touches: Vec<Touch>,
+    /// Lifted or cancelled during the last step.
+    ended: Vec<Touch>,
+    taps: Vec<Vec2>,
+    long_presses: Vec<Vec2>,
+    pan: Vec2,
+    pinch: Option<(Vec2, f32)>,
+}
+
+impl TouchState {
+    pub(crate) fn step(&mut self, now: f32) {
+        self.ended.clear();
+        self.taps.clear();
+        self.long_presses.clear();
+        self.pan = Vec2::ZERO;
+        self.pinch = None;
+
+        for touch in &mut self.touches {
+            touch.duration = now - touch.start_time;
+            if !touch.moved
+                && !touch.multi
+                && !touch.long_pressed
+                && touch.duration >= LONG_PRESS_TIME
+            {
+                touch.long_pressed = true;
+                self.long_presses.push(touch.position);
+            }
+        }
+    }
+
+    pub(crate) fn process(
+        &mut self,
+        id: u64,
+        phase: TouchPhase,
+        position: Vec2,
+        force: Option<f32>,
+        now: f32,
+    ) {
+        match phase {
+            TouchPhase::Started => {
+                let multi = !self.touches.is_empty();
+                for touch in &mut self.touches {
+                    touch.multi = true;
+                }
+                self.touches.push(Touch {
+                    id,
+                    position,
+                    start_position: position,
+                    duration: 0.0,
+                    force,
+                    start_time: now,
+                    moved: false,
+                    multi,
+                    long_pressed: false,
+                });
+            }
+            TouchPhase::Moved => {
+                let Some(index) = self.touches.iter().position(|touch| touch.id == id) else {
+                    return;
+                };
+                let old_pair = self.pinch_pair();
+
+                let touch = &mut self.touches[index];
+                let delta = position - touch.position;
+                touch.position = position;
+                touch.force = force;
+                touch.duration = now - touch.start_time;
+                if position.distance(touch.start_position) > TAP_SLOP {
+                    touch.moved = true;
+                }
+
+                match (old_pair, self.pinch_pair()) {
+                    (Some((a, b)), Some((new_a, new_b))) if index < 2 => {
+                        let old_distance = a.distance(b);
+                        let new_distance = new_a.distance(new_b);
+                        let center = (new_a + new_b) / 2.0;
+                        if old_distance > 0.0 {
+                            let scale = self.pinch.map_or(1.0, |(_, scale)| scale);
+                            self.pinch = Some((center, scale * new_distance / old_distance));
+                        }
+                        self.pan += center - (a + b) / 2.0;
+                    }
+                    _ if self.touches.len() == 1 && self.touches[0].moved => self.pan += delta,
+                    _ => (),
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                let Some(index) = self.touches.iter().position(|touch| touch.id == id) else {
+                    return;
+                };
+                let mut touch = self.touches.remove(index);
+                touch.position = position;
+                touch.duration = now - touch.start_time;
+
+                if phase == TouchPhase::Ended
+                    && !touch.moved
+                    && !touch.multi
+                    && !touch.long_pressed
+                    && touch.duration < LONG_PRESS_TIME
+                {
+                    self.taps.push(touch.position);
+                }
+                self.ended.push(touch);
+            }
+        }
+    }
+
+    /// The first two fingers down, which are the ones pinching.
+    fn pinch_pair(&self) -> Option<(Vec2, Vec2)> {
+        match self.touches.as_slice() {
+            [a, b, ..] => Some((a.position, b.position)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn touches(&self) -> &[Touch] {
+        &self.touches
+    }
+
+    pub(crate) fn ended(&self) -> &[Touch] {
+        &self.ended
+    }
+
+    /// Everything recognised during the last step, taps and long presses first.
+    pub(crate) fn gestures(&self) -> Vec<Gesture> {
+        let mut gestures: Vec<Gesture> = self
+            .taps
+            .iter()
+            .map(|&position| Gesture::Tap { position })
+            .chain(
+                self.long_presses
+                    .iter()
+                    .map(|&position| Gesture::LongPress { position }),
+            )
+            .collect();
+        if let Some((center, scale)) = self.pinch {
+            gestures.push(Gesture::Pinch { center, scale });
+        }
+        if self.pan != Vec2::ZERO {
+            gestures.push(Gesture::Pan { delta: self.pan });
+        }
+        gestures
+    }
+
+    pub(crate) fn pinch(&self) -> Option<(Vec2, f32)> {
+        self.pinch
+    }
+
+    pub(crate) fn pan(&self) -> Vec2 {
+        self.pan
+    }
+
+    pub(crate) fn taps(&self) -> &[Vec2] {
+        &self.taps
+    }
+
+    pub(crate) fn long_presses(&self) -> &[Vec2] {
+        &self.long_presses
+    }
+}
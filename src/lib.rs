@@ -1,219 +1,440 @@
+//! Games usually just `use engine_4::prelude::*`. The same API is also split into the
+//! [`gfx`], [`input`], [`audio`] and [`physics`] namespaces for code that prefers qualified
+//! paths. Those, the prelude and [`collisions`] are the stable surface; everything else is an
+//! implementation detail, and internals that are exposed anyway live in `experimental`.
+
 #![allow(static_mut_refs)]
 #![feature(duration_millis_float)]
-use std::time::Instant;
+
+// lets the derive macros refer to `::engine_4` from inside this crate too
+extern crate self as engine_4;
+
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::time::{Duration, Instant};
 
 use bevy_math::Mat4;
+use bevy_math::UVec2;
 use bevy_math::Vec2;
+use bumpalo::Bump;
 use camera::Camera2D;
 use camera::Camera3D;
-use camera::projection_from_window;
+use camera::{projection, projection_from_window};
 use color::Color;
-use config::EngineConfig;
+use color::theme::Theme;
+use config::{ConfigWatcher, EngineConfig};
 #[cfg(feature = "debugging")]
 use debugging::DebugInfo;
-pub use draw_queue_2d::Vertex3D;
+use ecs::Entities;
 use egui_glium::{EguiGlium, egui_winit::egui::ViewportId};
+use error::{EngineError, EngineResult};
+use events::EventBus;
 use fps_ticker::Fps;
 use glium::Program;
 use glium::{
     Frame,
-    backend::glutin::{Display, SimpleWindowBuilder},
-    glutin::surface::WindowSurface,
+    backend::glutin::Display,
+    glutin::{
+        config::ConfigTemplateBuilder,
+        context::ContextAttributesBuilder,
+        display::GetGlDisplay,
+        prelude::*,
+        surface::{SurfaceAttributesBuilder, SwapInterval, WindowSurface},
+    },
     winit::{
-        event::Event, event_loop::EventLoop, platform::pump_events::EventLoopExtPumpEvents,
+        dpi::PhysicalSize,
+        event::{Event, Ime, WindowEvent},
+        event_loop::EventLoop,
+        platform::pump_events::EventLoopExtPumpEvents,
+        raw_window_handle::HasWindowHandle,
         window::Window,
     },
 };
+use glutin_winit::DisplayBuilder;
 use image::Image;
-use input::Input;
+use input_handling::Input;
 use materials::Material;
 use object_3d::Mesh;
 use object_3d::Object3D;
+use post_processing::PostProcessingEffect;
 use prelude::TextureAtlas;
 use prelude::init_fonts;
 use prelude::init_materials;
 use programs::init_programs;
-use rand::rngs::ThreadRng;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
 use render_pipeline::RenderPipeline;
 use render_pipeline::RenderTexture;
+use scene_graph::SceneGraph;
+use scheduler::Scheduler;
+use skybox::Skybox;
+use terrain::Terrain;
 use text_rendering::EngineFont;
 use textures::EngineTexture;
+use textures::TextureRef;
 use textures::init_textures;
+use transform::{Transform2D, Transform3D};
 use tunes::engine::AudioEngine;
+use tween::Tweens;
 use user_storage::UserStorage;
+use utils::slots::Slots;
 
 mod animation;
 mod api;
+mod atmosphere;
+pub mod audio;
+mod background;
+mod buffers;
 mod camera;
+mod character_controller;
 pub mod collisions;
+pub mod collisions3d;
 mod color;
 mod config;
 #[cfg(feature = "debugging")]
 mod debugging;
 mod draw_queue_2d;
 mod draw_queue_3d;
+mod ecs;
+#[cfg(feature = "editor")]
+pub mod editor;
+mod egui_textures;
+mod error;
+mod events;
+#[cfg(feature = "experimental")]
+pub mod experimental;
+pub mod gfx;
+mod gizmos;
+mod guides;
 mod image;
-mod input;
+pub mod input;
+mod input_handling;
 mod materials;
+pub mod net;
+mod nine_slice;
 mod object_3d;
-mod physics;
+mod outlines;
+mod palette;
+mod particles;
+mod particles_3d;
+pub mod pathfinding;
+pub mod physics;
+mod physics_world;
+mod picking;
 mod post_processing;
 pub mod prelude;
 mod programs;
 mod render_pipeline;
+mod rich_text;
+mod scene_graph;
+mod scheduler;
+#[cfg(feature = "serde")]
+mod serde_impls;
 mod shapes_2d;
 mod shapes_3d;
+mod skybox;
 mod slop;
+pub mod steering;
+pub mod storage;
+#[cfg(feature = "svg")]
+mod svg;
+mod terrain;
+#[cfg(test)]
+mod testing;
+mod text_animation;
 mod text_rendering;
 mod textures;
+mod trail;
 mod transform;
+mod tween;
+mod ui;
+mod ui_layout;
 mod user_storage;
 mod utils;
+mod verlet;
+pub mod window;
 
 pub(crate) static mut ENGINE_STATE: Option<EngineState> = None;
 
 fn get_state() -> &'static mut EngineState {
-    thread_assert::same_thread();
+    checked_state().unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Like `get_state`, but returns [`EngineError::NotInitialized`] or
+/// [`EngineError::WrongThread`] instead of panicking, for public functions that can fail anyway.
+fn checked_state() -> EngineResult<&'static mut EngineState> {
+    thread_assert::same_thread()?;
+    unsafe { ENGINE_STATE.as_mut().ok_or(EngineError::NotInitialized) }
+}
+
+/// Runs `f` with the frame arena, memory that's all freed at once at the end of the frame.
+/// Much cheaper than the heap for lots of small short lived allocations, but nothing allocated
+/// in it can outlive `f`, so it's for scratch space while drawing, not for anything kept in a
+/// draw queue. Uses a throwaway arena when there's no engine on this thread, like in tests or
+/// while building [`DrawCommands2D`](crate::prelude::DrawCommands2D) on other threads.
+fn with_frame_arena<T>(f: impl FnOnce(&Bump) -> T) -> T {
+    match try_get_state() {
+        Some(state) => f(&state.frame_arena),
+        None => f(&Bump::new()),
+    }
+}
 
-    unsafe { ENGINE_STATE.as_mut().unwrap_or_else(|| panic!()) }
+/// Like `get_state`, but returns None instead of panicking if `init` hasn't been called, or
+/// when called from another thread. For the few things that should keep working without a
+/// window, like tests, or off the main thread, like building draw commands.
+fn try_get_state() -> Option<&'static mut EngineState> {
+    if !thread_assert::is_same_thread() {
+        return None;
+    }
+    unsafe { ENGINE_STATE.as_mut() }
 }
 
 type EngineDisplay = Display<WindowSurface>;
 
-struct EngineState {
+/// Everything that needs a real window and GL context.
+struct WindowContext {
     window: Window,
     display: EngineDisplay,
     event_loop: EventLoop<()>,
-    // bump_allocator: Bump,
-    input: Input,
+    gui: EguiGlium,
     frame: Option<Frame>,
+}
+
+/// The size `window_size` pretends the window is in headless mode, so layout code still works.
+const HEADLESS_WINDOW_SIZE: UVec2 = UVec2::new(1280, 720);
+
+struct EngineState {
+    /// `None` in headless mode.
+    window_context: Option<WindowContext>,
+    /// Scratch memory for drawing, see [`with_frame_arena`].
+    frame_arena: Bump,
+    input: Input,
     /// used for screen-space rendering
     flat_projection: Mat4,
     camera_2d: Camera2D,
     camera_3d: Camera3D,
-    gui: EguiGlium,
-    audio_engine: AudioEngine,
+    /// `None` in headless mode.
+    audio_engine: Option<AudioEngine>,
     gui_initialized: bool,
     render_pipeline: RenderPipeline,
     texture_pipeline: Option<RenderPipeline>,
     #[cfg(feature = "debugging")]
     debug_info: debugging::DebugInfo,
+    #[cfg(feature = "debugging")]
+    console: debugging::Console,
+    /// Whether `run_ui` was called since the last frame was shown.
+    ui_ran_this_frame: bool,
     storage: EngineStorage,
-    rng: ThreadRng,
+    rng: ChaCha8Rng,
     config: EngineConfig,
+    /// Watches the config's file if it has one and hot reloading is on.
+    config_watcher: Option<ConfigWatcher>,
     time: f32,
     physics_time: f32,
     is_physics_time_paused: bool,
     frame_count: usize,
     delta_time: f32,
+    /// Multiplies how fast time passes for the game. Doesn't affect unscaled times.
+    time_scale: f32,
+    unscaled_time: f32,
+    unscaled_delta_time: f32,
     last_frame_end_time: Instant,
     cursor_position: Vec2,
     user_storage: UserStorage,
+    scheduler: Scheduler,
+    tweens: Tweens,
+    events: EventBus,
+    music: audio::MusicState,
+    sounds: audio::SoundState,
+    audio_buses: audio::BusState,
+    microphone: audio::MicrophoneState,
+    gizmos: gizmos::Gizmos,
+    cursor_grab: window::CursorGrab,
+    theme: Theme,
+    theme_changed: bool,
+    egui_textures: egui_textures::EguiTextures,
+    /// Icons for rich text, by name.
+    text_icons: HashMap<String, TextureRef>,
+    async_textures: textures::async_loading::AsyncTextures,
+    picking: picking::Picking,
+    safe_area: ui_layout::SafeArea,
+    skybox: Option<Skybox>,
+    sky_gradient: Option<atmosphere::SkyGradient>,
+    fog: Option<atmosphere::Fog>,
+    terrain: Option<Terrain>,
+    draw_buffers: buffers::DrawBuffers,
+    camera_shake: camera::shake::CameraShake,
+    /// Indexed by [`render_pipeline::RenderPass`].
+    pass_settings: [render_pipeline::PassSettings; 3],
+    /// Whether screen space draws go to the overlay, inside of `with_overlay`.
+    drawing_overlay: bool,
+    hdr: Option<post_processing::Tonemapping>,
+    outline_thickness: f32,
 }
 
 unsafe impl Sync for EngineState {}
 unsafe impl Send for EngineState {}
 
 pub(crate) struct EngineStorage {
-    textures: Vec<EngineTexture>,
-    render_textures: Vec<RenderTexture>,
-    programs: Vec<Program>,
-    materials: Vec<Material>,
-    objects: Vec<Object3D>,
-    fonts: Vec<EngineFont>,
-    meshes: Vec<Mesh>,
-    texture_atlasses: Vec<TextureAtlas>,
-    images: Vec<Image>,
+    textures: Slots<EngineTexture>,
+    render_textures: Slots<RenderTexture>,
+    programs: Slots<Program>,
+    materials: Slots<Material>,
+    objects: Slots<Object3D>,
+    fonts: Slots<EngineFont>,
+    meshes: Slots<Mesh>,
+    texture_atlasses: Slots<TextureAtlas>,
+    images: Slots<Image>,
+    entities: Entities,
+    scene_2d: SceneGraph<Transform2D>,
+    scene_3d: SceneGraph<Transform3D>,
 }
 
 impl EngineStorage {
     pub fn new() -> Self {
         Self {
-            textures: vec![],
-            programs: vec![],
-            materials: vec![],
-            objects: vec![],
-            render_textures: vec![],
-            fonts: vec![],
-            meshes: vec![],
-            texture_atlasses: vec![],
-            images: vec![],
+            textures: Slots::new(),
+            programs: Slots::new(),
+            materials: Slots::new(),
+            objects: Slots::new(),
+            render_textures: Slots::new(),
+            fonts: Slots::new(),
+            meshes: Slots::new(),
+            texture_atlasses: Slots::new(),
+            images: Slots::new(),
+            entities: Entities::new(),
+            scene_2d: SceneGraph::new(),
+            scene_3d: SceneGraph::new(),
         }
     }
 }
 
 pub fn init(title: &str) -> anyhow::Result<()> {
+    init_with_config(title, EngineConfig::default())
+}
+
+/// Like [`init`], with settings that have to be known before the window is made, like
+/// [`vsync`](EngineConfig::vsync).
+pub fn init_with_config(title: &str, config: EngineConfig) -> anyhow::Result<()> {
     env_logger::init();
     color_eyre::install().expect("could not install color_eyre");
 
     let event_loop = EventLoop::builder().build()?;
-    let window_params = Window::default_attributes().with_transparent(false);
-    let (window, display) = SimpleWindowBuilder::new()
-        .set_window_builder(window_params)
-        .with_title(title)
-        .build(&event_loop);
-    let input = Input::new();
+    let (window, display) = create_window(&event_loop, title, &config)?;
     window.request_redraw();
 
-    let frame = None;
-
-    let flat_projection = projection_from_window(&window);
-    let camera_2d = Camera2D::from_window(&window);
-    let camera_3d = Camera3D::from_window(&window);
+    let size = window.inner_size();
     let gui = EguiGlium::new(ViewportId::ROOT, &display, &window, &event_loop);
-    #[cfg(feature = "debugging")]
-    let debug_info = DebugInfo::new();
     let mut storage = EngineStorage::new();
     init_programs(&display, &mut storage)?;
     init_textures(&mut storage, &display);
-    init_materials(&mut storage);
-    let rng = rand::rng();
-    let gui_initialized = false;
-    let config = EngineConfig::default();
-    let time = 0.0;
-    let delta_time = 0.0;
-    let last_frame_end_time = Instant::now();
-    let render_pipeline = RenderPipeline::screen();
     let audio_engine = AudioEngine::new()?;
-    let user_storage = UserStorage::new();
-    // let bump_allocator = Bump::new();
+
+    let window_context = WindowContext {
+        window,
+        display,
+        event_loop,
+        gui,
+        frame: None,
+    };
+    let state = EngineState::new(
+        Some(window_context),
+        Some(audio_engine),
+        storage,
+        config,
+        UVec2::new(size.width, size.height),
+    );
+    start(state);
+
+    Ok(())
+}
+
+/// Starts the engine without a window, GL context or audio device, for running game logic in
+/// tests and on dedicated servers. Time, input (which stays empty), physics, entities, the
+/// scheduler, tweens and events all work as normal.
+///
+/// Draw calls, text included, and [`run_ui`](crate::prelude::run_ui) do nothing, and
+/// [`window_size`](crate::prelude::window_size) is fixed at
+/// [`EngineConfig::window_size`], or 1280x720. Fonts still load and text can still be
+/// measured, so layout code works. Anything that needs the GPU, like loading textures or
+/// meshes, panics, as does [`audio`](crate::prelude::audio).
+pub fn init_headless() -> anyhow::Result<()> {
+    init_headless_with_config(EngineConfig::default())
+}
+
+/// Like [`init_headless`], with settings. Set [`target_fps`](EngineConfig::target_fps) so a
+/// server's loop doesn't spin as fast as it can.
+pub fn init_headless_with_config(config: EngineConfig) -> anyhow::Result<()> {
+    // tests can start the engine more than once
+    let _ = env_logger::try_init();
+    let _ = color_eyre::install();
+
+    let window_size = config.window_size.unwrap_or(HEADLESS_WINDOW_SIZE);
+    let state = EngineState::new(None, None, EngineStorage::new(), config, window_size);
+    start(state);
+
+    Ok(())
+}
+
+fn start(mut state: EngineState) {
+    init_materials(&mut state.storage);
 
     unsafe {
-        ENGINE_STATE = Some(EngineState {
-            window,
-            display,
-            texture_pipeline: None,
-            event_loop,
-            input,
-            // bump_allocator,
-            frame,
-            flat_projection,
-            camera_2d,
-            camera_3d,
-            audio_engine,
-            gui,
-            gui_initialized,
-            debug_info,
-            storage,
-            rng,
-            render_pipeline,
-            config,
-            time,
-            delta_time,
-            last_frame_end_time,
-            is_physics_time_paused: false,
-            cursor_position: Vec2::ZERO,
-            frame_count: 0,
-            physics_time: 0.0,
-            user_storage,
-        });
+        ENGINE_STATE = Some(state);
     }
 
     thread_assert::set_thread_id();
 
     init_fonts();
+}
 
-    Ok(())
+/// What [`SimpleWindowBuilder`](glium::backend::glutin::SimpleWindowBuilder) does, but with
+/// control over the swap interval, which glium has no way to change once the display exists.
+fn create_window(
+    event_loop: &EventLoop<()>,
+    title: &str,
+    config: &EngineConfig,
+) -> anyhow::Result<(Window, Display<WindowSurface>)> {
+    let mut window_attributes = Window::default_attributes()
+        .with_transparent(false)
+        .with_title(title);
+    if let Some(size) = config.window_size {
+        window_attributes = window_attributes.with_inner_size(PhysicalSize::new(size.x, size.y));
+    }
+    // the stencil buffer is used for 2D masks
+    let config_template = ConfigTemplateBuilder::new().with_stencil_size(8);
+    let (window, gl_config) = DisplayBuilder::new()
+        .with_window_attributes(Some(window_attributes))
+        .build(event_loop, config_template, |mut configs| {
+            configs.next().unwrap()
+        })
+        .map_err(|e| anyhow::anyhow!("could not create window: {e}"))?;
+    let window = window.ok_or_else(|| anyhow::anyhow!("could not create window"))?;
+
+    let (width, height): (u32, u32) = window.inner_size().into();
+    let window_handle = window.window_handle()?.as_raw();
+    let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::new().build(
+        window_handle,
+        NonZeroU32::new(width).unwrap_or(NonZeroU32::MIN),
+        NonZeroU32::new(height).unwrap_or(NonZeroU32::MIN),
+    );
+    let gl_display = gl_config.display();
+    let surface = unsafe { gl_display.create_window_surface(&gl_config, &surface_attributes)? };
+    let context_attributes = ContextAttributesBuilder::new().build(Some(window_handle));
+    let context = unsafe { gl_display.create_context(&gl_config, &context_attributes)? }
+        .make_current(&surface)?;
+
+    let interval = if config.vsync {
+        SwapInterval::Wait(NonZeroU32::MIN)
+    } else {
+        SwapInterval::DontWait
+    };
+    if let Err(e) = surface.set_swap_interval(&context, interval) {
+        log::warn!("could not set vsync: {e}");
+    }
+
+    let display = Display::from_context_surface(context, surface)?;
+    Ok((window, display))
 }
 
 pub fn next_frame() {
@@ -223,28 +444,137 @@ pub fn next_frame() {
 
     state.debug_info.next_frame();
 
+    // the debug overlays are drawn by `run_ui`, which games that don't use egui never call
+    #[cfg(feature = "debugging")]
+    if !state.ui_ran_this_frame && state.debug_info.wants_ui() {
+        api::run_ui(|_| {});
+    }
+    state.ui_ran_this_frame = false;
+
+    match &mut state.window_context {
+        Some(context) => {
+            profile_scope!("present");
+            present_frame(context);
+        }
+        // nothing to draw on, so just forget what was drawn
+        None => {
+            state.render_pipeline = RenderPipeline::screen();
+            state.gizmos.clear();
+        }
+    }
+    // only ever borrowed inside `with_frame_arena`, so nothing in it is still around
+    state.frame_arena.reset();
+
+    let limiter_sleep = limit_frame_rate();
+
+    let unscaled_delta_time =
+        input_handling::update_recording(state.last_frame_end_time.elapsed().as_secs_f32());
+    state.unscaled_delta_time = unscaled_delta_time;
+    state.unscaled_time += unscaled_delta_time;
+
+    let delta_time = unscaled_delta_time * state.time_scale;
+    state.delta_time = delta_time;
+    state.time += delta_time;
+    state.last_frame_end_time = Instant::now();
+
+    if !state.is_physics_time_paused {
+        state.physics_time += delta_time;
+    }
+
+    state.frame_count += 1;
+    {
+        profile_scope!("scheduler");
+        scheduler::update_scheduler(delta_time);
+    }
+    {
+        profile_scope!("tweens");
+        tween::update_tweens(delta_time);
+    }
+    state.storage.entities.flush();
+    textures::async_loading::update_async_textures();
+    state.storage.scene_2d.update();
+    state.storage.scene_3d.update();
+    config::update_hot_reload();
+    input_handling::update_rebinding();
+    // keeps shaking through hit-stop
+    camera::shake::update_camera_shake(unscaled_delta_time);
+    audio::update_music(unscaled_delta_time);
+    audio::update_sounds(unscaled_delta_time);
+    audio::update_microphone();
+    events::update_events();
+
+    if let Some(c) = state.input.cursor() {
+        state.cursor_position = c.into();
+    }
+    window::update_cursor_grab();
+
+    #[cfg(feature = "debugging")]
+    {
+        let engine_time = engine_start_time.elapsed() - limiter_sleep;
+        state.debug_info.current_frame_mut().engine_time = engine_time.as_millis_f64();
+    }
+}
+
+/// Handles window events, then draws everything queued up this frame and shows it.
+fn present_frame(context: &mut WindowContext) {
+    let state = get_state();
+
     #[allow(deprecated)]
-    state
+    context
         .event_loop
         .pump_events(None, |event, event_loop_window_target| match event {
             Event::WindowEvent { event, .. } => {
-                let gui_response = state.gui.on_event(&state.window, &event);
+                if let WindowEvent::Focused(focused) = event {
+                    state.events.emit(events::WindowFocused { focused });
+                    // some platforms let go of the cursor when the window loses focus
+                    if focused {
+                        window::apply_cursor_grab(&context.window);
+                    }
+                }
+
+                // handled before egui sees it, so the key doesn't get typed into the console
+                #[cfg(feature = "debugging")]
+                if let WindowEvent::KeyboardInput { event, .. } = &event
+                    && event.state.is_pressed()
+                    && !event.repeat
+                    && event.physical_key == state.console.toggle_key()
+                {
+                    state.console.toggle();
+                    return;
+                }
+
+                let gui_response = context.gui.on_event(&context.window, &event);
                 if gui_response.consumed {
                     return;
                 }
 
                 state.input.process_window_event(&event);
+                if let WindowEvent::DroppedFile(path) = &event {
+                    state
+                        .events
+                        .emit(events::FileDropped { path: path.clone() });
+                }
+                if let WindowEvent::Ime(Ime::Preedit(text, cursor)) = &event {
+                    state.events.emit(events::ImeComposition {
+                        text: text.clone(),
+                        cursor: *cursor,
+                    });
+                }
 
                 if state.input.close_requested() {
                     event_loop_window_target.exit();
+                    audio::remove_loop_files();
                 }
 
                 if let Some(size) = state.input.window_resized() {
-                    state.display.resize(size.into());
-                    state.flat_projection = projection_from_window(&state.window);
-                    let size = state.window.inner_size();
+                    context.display.resize(size.into());
+                    state.flat_projection = projection_from_window(&context.window);
+                    let size = context.window.inner_size();
                     state.camera_2d.update_sizes(size.width, size.height);
                     state.camera_3d.update_sizes(size.width, size.height);
+                    state.events.emit(events::WindowResized {
+                        size: UVec2::new(size.width, size.height),
+                    });
                 }
             }
             Event::DeviceEvent { event, .. } => {
@@ -256,43 +586,57 @@ pub fn next_frame() {
             _ => (),
         });
 
-    let mut frame = state.frame.take().unwrap_or_else(|| state.display.draw());
+    let mut frame = context
+        .frame
+        .take()
+        .unwrap_or_else(|| context.display.draw());
 
+    if let Some(filter) = state.config.color_filter {
+        state
+            .render_pipeline
+            .final_effects
+            .push(PostProcessingEffect::ColorFilter(filter));
+    }
     state.render_pipeline.draw_on(&mut frame);
     state.render_pipeline = RenderPipeline::screen();
+    state.gizmos.draw_on(&mut frame);
 
     if state.gui_initialized {
-        state.gui.paint(&state.display, &mut frame);
+        egui_textures::sync_egui_textures();
+        context.gui.paint(&context.display, &mut frame);
     }
 
     frame.finish().unwrap();
-    state.window.request_redraw();
-
-    state.frame = Some(state.display.draw());
-
-    let delta_time = state.last_frame_end_time.elapsed().as_secs_f32();
-    state.delta_time = delta_time;
-    state.time += delta_time;
-    state.last_frame_end_time = Instant::now();
-
-    if !state.is_physics_time_paused {
-        state.physics_time += delta_time;
-    }
+    context.window.request_redraw();
 
-    state.frame_count += 1;
+    context.frame = Some(context.display.draw());
+}
 
-    if let Some(c) = state.input.cursor() {
-        state.cursor_position = c.into();
+/// Sleeps until the frame has taken as long as the target fps wants, if there is one. Returns
+/// how long it slept.
+fn limit_frame_rate() -> Duration {
+    let state = get_state();
+    let remaining = frame_rate_wait(state.config.target_fps, state.last_frame_end_time.elapsed());
+    if !remaining.is_zero() {
+        std::thread::sleep(remaining);
     }
+    remaining
+}
 
-    #[cfg(feature = "debugging")]
-    {
-        let engine_time = engine_start_time.elapsed();
-        state.debug_info.current_frame_mut().engine_time = engine_time.as_millis_f64();
+/// How much longer a frame that's taken `elapsed` so far has to wait to stay at or under
+/// `target_fps`. A target that isn't more than 0 is no limit.
+pub(crate) fn frame_rate_wait(target_fps: Option<f32>, elapsed: Duration) -> Duration {
+    match target_fps {
+        Some(fps) if fps > 0.0 && fps.is_finite() => {
+            Duration::from_secs_f32(1.0 / fps).saturating_sub(elapsed)
+        }
+        _ => Duration::ZERO,
     }
 }
 
 pub(crate) mod thread_assert {
+    use crate::error::{EngineError, EngineResult};
+
     static mut THREAD_ID: Option<std::thread::ThreadId> = None;
 
     pub fn set_thread_id() {
@@ -301,32 +645,143 @@ pub(crate) mod thread_assert {
         }
     }
 
-    pub fn same_thread() {
-        unsafe {
-            thread_local! {
-                static CURRENT_THREAD_ID: std::thread::ThreadId = std::thread::current().id();
-            }
-            assert!(THREAD_ID.is_some());
-            assert!(THREAD_ID.unwrap() == CURRENT_THREAD_ID.with(|id| *id));
+    pub fn is_same_thread() -> bool {
+        thread_local! {
+            static CURRENT_THREAD_ID: std::thread::ThreadId = std::thread::current().id();
+        }
+        unsafe { THREAD_ID == Some(CURRENT_THREAD_ID.with(|id| *id)) }
+    }
+
+    pub fn same_thread() -> EngineResult<()> {
+        thread_local! {
+            static CURRENT_THREAD_ID: std::thread::ThreadId = std::thread::current().id();
+        }
+        let Some(thread_id) = (unsafe { THREAD_ID }) else {
+            return Err(EngineError::NotInitialized);
+        };
+        if thread_id != CURRENT_THREAD_ID.with(|id| *id) {
+            return Err(EngineError::WrongThread);
         }
+        Ok(())
     }
 }
 
 impl Drop for EngineState {
     fn drop(&mut self) {
-        if let Some(frame) = self.frame.take() {
+        if let Some(frame) = self
+            .window_context
+            .as_mut()
+            .and_then(|context| context.frame.take())
+        {
             let _ = frame.finish();
         }
     }
 }
 
 impl EngineState {
+    fn new(
+        window_context: Option<WindowContext>,
+        audio_engine: Option<AudioEngine>,
+        storage: EngineStorage,
+        config: EngineConfig,
+        window_size: UVec2,
+    ) -> Self {
+        let music = audio::MusicState::new(audio_engine.as_ref());
+        Self {
+            window_context,
+            texture_pipeline: None,
+            input: Input::new(),
+            flat_projection: projection(window_size.x, window_size.y),
+            camera_2d: Camera2D::new(window_size.x, window_size.y),
+            camera_3d: Camera3D::new(window_size.x, window_size.y),
+            audio_engine,
+            gui_initialized: false,
+            #[cfg(feature = "debugging")]
+            debug_info: DebugInfo::new(),
+            #[cfg(feature = "debugging")]
+            console: {
+                let mut console = debugging::Console::new();
+                debugging::console::register_builtin_commands(&mut console);
+                console
+            },
+            ui_ran_this_frame: false,
+            storage,
+            rng: ChaCha8Rng::from_rng(&mut rand::rng()),
+            render_pipeline: RenderPipeline::screen(),
+            config_watcher: config
+                .path
+                .clone()
+                .filter(|_| config.hot_reload)
+                .map(ConfigWatcher::new),
+            config,
+            time: 0.0,
+            delta_time: 0.0,
+            time_scale: 1.0,
+            unscaled_time: 0.0,
+            unscaled_delta_time: 0.0,
+            last_frame_end_time: Instant::now(),
+            is_physics_time_paused: false,
+            cursor_position: Vec2::ZERO,
+            frame_count: 0,
+            physics_time: 0.0,
+            user_storage: UserStorage::new(),
+            scheduler: Scheduler::new(),
+            tweens: Tweens::new(),
+            events: EventBus::new(),
+            music,
+            sounds: audio::SoundState::default(),
+            audio_buses: audio::BusState::default(),
+            microphone: audio::MicrophoneState::default(),
+            gizmos: gizmos::Gizmos::new(),
+            cursor_grab: window::CursorGrab::default(),
+            theme: Theme::default(),
+            theme_changed: true,
+            egui_textures: egui_textures::EguiTextures::default(),
+            text_icons: HashMap::new(),
+            async_textures: textures::async_loading::AsyncTextures::default(),
+            picking: picking::Picking::default(),
+            safe_area: ui_layout::SafeArea::default(),
+            skybox: None,
+            sky_gradient: None,
+            fog: None,
+            terrain: None,
+            frame_arena: Bump::new(),
+            draw_buffers: buffers::DrawBuffers::new(),
+            camera_shake: camera::shake::CameraShake::default(),
+            pass_settings: render_pipeline::RenderPass::ALL.map(|pass| pass.default_settings()),
+            drawing_overlay: false,
+            hdr: None,
+            outline_thickness: 3.0,
+        }
+    }
+
+    /// Panics in headless mode.
+    pub(crate) fn display(&self) -> &EngineDisplay {
+        &self
+            .window_context
+            .as_ref()
+            .expect("there's no window or GL context in headless mode")
+            .display
+    }
+
     pub(crate) fn window_size(&self) -> Vec2 {
-        let size = self.window.inner_size();
-        Vec2::new(size.width as f32, size.height as f32)
+        match &self.window_context {
+            Some(context) => {
+                let size = context.window.inner_size();
+                Vec2::new(size.width as f32, size.height as f32)
+            }
+            None => self
+                .config
+                .window_size
+                .unwrap_or(HEADLESS_WINDOW_SIZE)
+                .as_vec2(),
+        }
     }
 
     pub(crate) fn dpi_scaling(&self) -> f32 {
-        self.window.scale_factor() as f32
+        match &self.window_context {
+            Some(context) => context.window.scale_factor() as f32,
+            None => 1.0,
+        }
     }
 }
@@ -6,23 +6,72 @@ use crate::{
     color::Color,
     get_state,
     programs::{
-        BLINN_PHONG_3D_PROGRAM, FLAT_3D_PROGRAM, GOURAUD_3D_PROGRAM, ProgramRef,
+        BLINN_PHONG_3D_PROGRAM, FLAT_3D_PROGRAM, GOURAUD_3D_PROGRAM, PBR_3D_PROGRAM, ProgramRef,
         TEXTURED_3D_PROGRAM,
     },
     textures::TextureRef,
 };
 use bevy_math::{Mat3, Mat4, Vec2, Vec3, Vec4};
 use engine_4_macros::gen_ref_type;
-use glium::uniforms::{SamplerBehavior, UniformValue};
+use glium::{Blend, BlendingFunction, LinearBlendingFactor, uniforms::UniformValue};
 
-pub const DEFAULT_MATERIAL: MaterialRef = MaterialRef(0);
+pub const DEFAULT_MATERIAL: MaterialRef = MaterialRef::from_raw(0, 0);
 
 pub struct Material {
     pub(crate) program: ProgramRef,
     pub(crate) uniforms: BTreeMap<String, UniformData>,
+    pub blend_mode: BlendMode,
     pub draw_param_overrides: Option<glium::DrawParameters<'static>>,
 }
 
+/// How a material's output gets combined with what's already on screen. `Opaque` replaces it,
+/// alpha and all. Anything else is drawn after all the opaque objects, sorted back to front,
+/// without writing depth.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    #[default]
+    Opaque,
+    Alpha,
+    Additive,
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn is_transparent(self) -> bool {
+        self != Self::Opaque
+    }
+
+    pub fn to_glium(self) -> Blend {
+        match self {
+            Self::Opaque => Blend::default(),
+            Self::Alpha => Blend::alpha_blending(),
+            Self::Additive => Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::SourceAlpha,
+                    destination: LinearBlendingFactor::One,
+                },
+                alpha: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::Zero,
+                    destination: LinearBlendingFactor::One,
+                },
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+            Self::Multiply => Blend {
+                color: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::DestinationColor,
+                    // leaves the background alone where the source is transparent
+                    destination: LinearBlendingFactor::OneMinusSourceAlpha,
+                },
+                alpha: BlendingFunction::Addition {
+                    source: LinearBlendingFactor::Zero,
+                    destination: LinearBlendingFactor::One,
+                },
+                constant_value: (0.0, 0.0, 0.0, 0.0),
+            },
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum UniformData {
     Float(f32),
@@ -40,6 +89,7 @@ impl Material {
         Self {
             program,
             uniforms: BTreeMap::new(),
+            blend_mode: BlendMode::Opaque,
             draw_param_overrides: None,
         }
     }
@@ -49,6 +99,11 @@ impl Material {
         self
     }
 
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
     pub fn with_float(mut self, name: impl Into<String>, value: f32) -> Self {
         self.uniforms.insert(name.into(), UniformData::Float(value));
         self
@@ -96,6 +151,10 @@ impl Material {
         self.draw_param_overrides = Some(params);
     }
 
+    pub fn set_blend_mode(&mut self, blend_mode: BlendMode) {
+        self.blend_mode = blend_mode;
+    }
+
     pub fn set_float(&mut self, name: impl Into<String>, value: f32) {
         self.uniforms.insert(name.into(), UniformData::Float(value));
     }
@@ -134,22 +193,150 @@ impl Material {
     }
 }
 
+/// Normal mapping, for the built in lit programs (the default gouraud one, and PBR). Tangents
+/// come from the mesh, see [`generate_tangents`](crate::object_3d::generate_tangents).
+impl Material {
+    /// A tangent space normal map, with +Y up (the OpenGL and glTF convention). Turns normal
+    /// mapping on.
+    pub fn with_normal_map(mut self, texture: TextureRef) -> Self {
+        self.set_normal_map(texture);
+        self
+    }
+
+    /// Turns normal mapping on or off, keeping the normal map.
+    pub fn with_normal_mapping(mut self, enabled: bool) -> Self {
+        self.set_normal_mapping(enabled);
+        self
+    }
+
+    /// How strong the bumps are. 1 by default, 0 is flat.
+    pub fn with_normal_scale(mut self, scale: f32) -> Self {
+        self.set_normal_scale(scale);
+        self
+    }
+
+    pub fn set_normal_map(&mut self, texture: TextureRef) {
+        self.set_texture("normal_map", texture);
+        self.set_normal_mapping(true);
+    }
+
+    pub fn set_normal_mapping(&mut self, enabled: bool) {
+        self.set_float("use_normal_map", if enabled { 1.0 } else { 0.0 });
+        self.uniforms
+            .entry("normal_scale".into())
+            .or_insert(UniformData::Float(1.0));
+    }
+
+    pub fn set_normal_scale(&mut self, scale: f32) {
+        self.set_float("normal_scale", scale);
+    }
+
+    pub fn normal_mapping(&self) -> bool {
+        self.get_uniform("use_normal_map") == Some(UniformData::Float(1.0))
+    }
+}
+
+/// Metallic-roughness PBR, the same model glTF uses. Each texture slot is multiplied by its
+/// factor, so a material with no textures is just its factors, and one with textures set can
+/// still be tinted.
+impl Material {
+    /// A white, fully rough dielectric, lit by a white light above and in front of the origin.
+    pub fn pbr() -> Self {
+        Self::new(PBR_3D_PROGRAM)
+            .with_color("albedo", Color::WHITE)
+            .with_float("metallic", 0.0)
+            .with_float("roughness", 1.0)
+            .with_color("emissive", Color::BLACK)
+            .with_float("use_albedo_map", 0.0)
+            .with_float("use_metallic_roughness_map", 0.0)
+            .with_normal_mapping(false)
+            .with_float("use_emissive_map", 0.0)
+            .with_vec3("light_pos", Vec3::new(2.0, 4.0, 3.0))
+            .with_color("light_color", Color::WHITE)
+            .with_float("light_intensity", 3.0)
+            .with_color("ambient_color", Color::hex(0x333333))
+    }
+
+    /// Base color, in sRGB. Alpha only matters with a transparent [`BlendMode`].
+    pub fn with_albedo(mut self, color: Color) -> Self {
+        self.set_albedo(color);
+        self
+    }
+
+    pub fn with_metallic(mut self, metallic: f32) -> Self {
+        self.set_metallic(metallic);
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: f32) -> Self {
+        self.set_roughness(roughness);
+        self
+    }
+
+    /// Light given off regardless of lighting. The alpha is used as a strength.
+    pub fn with_emissive(mut self, color: Color) -> Self {
+        self.set_emissive(color);
+        self
+    }
+
+    pub fn with_albedo_map(mut self, texture: TextureRef) -> Self {
+        self.set_albedo_map(texture);
+        self
+    }
+
+    /// Packed like glTF: roughness in the green channel, metallic in blue. The map is
+    /// multiplied by the metallic and roughness factors, so set metallic to 1 to use it as is.
+    pub fn with_metallic_roughness_map(mut self, texture: TextureRef) -> Self {
+        self.set_metallic_roughness_map(texture);
+        self
+    }
+
+    pub fn with_emissive_map(mut self, texture: TextureRef) -> Self {
+        self.set_emissive_map(texture);
+        self
+    }
+
+    // ----------------------------------------------------------------------------------
+
+    pub fn set_albedo(&mut self, color: Color) {
+        self.set_color("albedo", color);
+    }
+
+    pub fn set_metallic(&mut self, metallic: f32) {
+        self.set_float("metallic", metallic);
+    }
+
+    pub fn set_roughness(&mut self, roughness: f32) {
+        self.set_float("roughness", roughness);
+    }
+
+    pub fn set_emissive(&mut self, color: Color) {
+        self.set_color("emissive", color);
+    }
+
+    pub fn set_albedo_map(&mut self, texture: TextureRef) {
+        self.set_texture("albedo_map", texture);
+        self.set_float("use_albedo_map", 1.0);
+    }
+
+    pub fn set_metallic_roughness_map(&mut self, texture: TextureRef) {
+        self.set_texture("metallic_roughness_map", texture);
+        self.set_float("use_metallic_roughness_map", 1.0);
+    }
+
+    pub fn set_emissive_map(&mut self, texture: TextureRef) {
+        self.set_texture("emissive_map", texture);
+        self.set_float("use_emissive_map", 1.0);
+    }
+}
+
 impl UniformData {
     fn to_gpu<'a>(self) -> UniformValue<'a> {
         match self {
             Self::Float(f) => UniformValue::Float(f),
             Self::Mat3(m) => UniformValue::Mat3(m.to_cols_array_2d()),
             Self::Mat4(m) => UniformValue::Mat4(m.to_cols_array_2d()),
-            Self::Texture(texture) => {
-                let texture = texture.get();
-                let behaviour = SamplerBehavior {
-                    magnify_filter: texture.magnify_filter,
-                    minify_filter: texture.minify_filter,
-                    ..Default::default()
-                };
-
-                UniformValue::Texture2d(&texture.gl_texture, Some(behaviour))
-            }
+            Self::Texture(texture) => texture.get().sampled().0,
             Self::Vec2(v) => UniformValue::Vec2(v.into()),
             Self::Vec3(v) => UniformValue::Vec3(v.into()),
             Self::Vec4(v) => UniformValue::Vec4(v.into()),
@@ -158,6 +345,66 @@ impl UniformData {
     }
 }
 
+/// Converts a value into something glium can upload as a uniform. Used by
+/// `#[derive(Uniforms)]`, so any field type in a derived struct needs to implement this.
+pub trait AsUniformValue {
+    fn as_uniform_value(&self) -> UniformValue<'_>;
+}
+
+impl AsUniformValue for UniformData {
+    fn as_uniform_value(&self) -> UniformValue<'_> {
+        self.to_gpu()
+    }
+}
+
+macro_rules! impl_as_uniform_value {
+    ($($ty:ty => $variant:ident),*,) => {
+        $(
+            impl AsUniformValue for $ty {
+                fn as_uniform_value(&self) -> UniformValue<'_> {
+                    UniformData::$variant(*self).to_gpu()
+                }
+            }
+        )*
+    };
+}
+
+impl_as_uniform_value!(
+    f32 => Float,
+    Vec2 => Vec2,
+    Vec3 => Vec3,
+    Vec4 => Vec4,
+    Mat3 => Mat3,
+    Mat4 => Mat4,
+    Color => Color,
+    TextureRef => Texture,
+);
+
+impl AsUniformValue for i32 {
+    fn as_uniform_value(&self) -> UniformValue<'_> {
+        UniformValue::SignedInt(*self)
+    }
+}
+
+impl AsUniformValue for u32 {
+    fn as_uniform_value(&self) -> UniformValue<'_> {
+        UniformValue::UnsignedInt(*self)
+    }
+}
+
+impl AsUniformValue for bool {
+    fn as_uniform_value(&self) -> UniformValue<'_> {
+        UniformValue::Bool(*self)
+    }
+}
+
+/// For textures sampled some other way than a [`TextureRef`]'s defaults.
+impl AsUniformValue for glium::uniforms::Sampler<'_, glium::Texture2d> {
+    fn as_uniform_value(&self) -> UniformValue<'_> {
+        glium::uniforms::AsUniformValue::as_uniform_value(self)
+    }
+}
+
 impl glium::uniforms::Uniforms for Material {
     fn visit_values<'a, F: FnMut(&str, UniformValue<'a>)>(&'a self, mut add: F) {
         for key in self.uniforms.keys() {
@@ -208,6 +455,14 @@ pub fn create_blinn_phong_material(
     material.create()
 }
 
+pub fn create_pbr_material(albedo: Color, metallic: f32, roughness: f32) -> MaterialRef {
+    let material = Material::pbr()
+        .with_albedo(albedo)
+        .with_metallic(metallic)
+        .with_roughness(roughness);
+    material.create()
+}
+
 pub(crate) fn init_materials(storage: &mut EngineStorage) {
     let regular_color = Color::hex(0xBBBDBD);
     let dark_color = Color::hex(0x333333);
@@ -217,5 +472,6 @@ pub(crate) fn init_materials(storage: &mut EngineStorage) {
         .with_color("dark_color", dark_color)
         .with_vec3("light_pos", light_pos);
 
-    storage.materials.push(material);
+    storage.materials.insert(material);
+    storage.materials.mark_builtin();
 }
@@ -0,0 +1,525 @@
+//! Networking over UDP, for multiplayer games.
+//!
+//! ```ignore
+//! // on the host
+//! let mut socket = NetSocket::host("0.0.0.0:7777")?;
+//!
+//! // on each player's machine
+//! let mut socket = NetSocket::connect("192.168.1.20:7777")?;
+//!
+//! // every frame, on both
+//! for event in socket.poll_messages() {
+//!     match event {
+//!         NetEvent::Connected(peer) => println!("{peer:?} joined"),
+//!         NetEvent::Disconnected(peer) => println!("{peer:?} left"),
+//!         NetEvent::Message { peer, bytes, .. } => { /* ... */ }
+//!     }
+//! }
+//! socket.send_value(peer, Channel::Reliable, &PlayerMoved { x, y })?;
+//! ```
+//!
+//! Messages go on a [`Channel`], which decides whether they're resent when lost and whether
+//! they arrive in order. Nothing blocks: everything that's arrived is picked up by
+//! [`poll_messages`](NetSocket::poll_messages), which also resends, keeps connections alive and
+//! notices peers that have gone quiet.
+
+use std::{
+    collections::HashMap,
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, bail, ensure};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use crate::{
+    error::{EngineError, report_error},
+    storage::format,
+};
+
+pub(crate) mod channel;
+mod lockstep;
+pub(crate) mod packet;
+mod snapshot;
+
+use channel::ChannelState;
+use packet::{MESSAGE_HEADER_SIZE, Packet};
+
+pub use lockstep::{ClockSync, Lockstep, TickInputs};
+pub use snapshot::{Snapshot, SnapshotDelta, SnapshotReceiver, SnapshotSender};
+
+/// The most that can go in one message. Bigger datagrams get split up by the network, and if
+/// any piece is lost so is the whole thing.
+pub const MAX_MESSAGE_SIZE: usize = 1200;
+/// The biggest datagram we send. Anything bigger that arrives isn't one of ours.
+const MAX_PACKET_SIZE: usize = MAX_MESSAGE_SIZE + MESSAGE_HEADER_SIZE;
+
+/// How often a connecting socket asks again.
+const CONNECT_RETRY: Duration = Duration::from_millis(250);
+/// How long a reliable message waits for an ack before it's sent again.
+const RESEND_AFTER: Duration = Duration::from_millis(200);
+/// How long a connection can go without sending anything before it sends a heartbeat.
+const HEARTBEAT_AFTER: Duration = Duration::from_millis(500);
+
+/// How a message is delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Channel {
+    /// Might not arrive, might arrive more than once, might arrive out of order. Cheapest.
+    Unreliable,
+    /// Might not arrive, but never arrives after a newer one on the same channel. Good for
+    /// positions and other state that's sent every frame, where only the latest matters.
+    Sequenced,
+    /// Always arrives, exactly once, but maybe out of order.
+    Reliable,
+    /// Always arrives, exactly once, in the order it was sent. A lost message holds up the
+    /// ones behind it until it's been resent.
+    ReliableOrdered,
+}
+
+impl Channel {
+    const ALL: [Channel; 4] = [
+        Channel::Unreliable,
+        Channel::Sequenced,
+        Channel::Reliable,
+        Channel::ReliableOrdered,
+    ];
+
+    pub fn is_reliable(self) -> bool {
+        matches!(self, Channel::Reliable | Channel::ReliableOrdered)
+    }
+
+    fn from_u8(n: u8) -> Option<Self> {
+        Self::ALL.get(n as usize).copied()
+    }
+}
+
+/// Messages the snapshot and lockstep helpers send each other. They start with [`SYNC_MAGIC`]
+/// so they can be told apart from the game's own messages.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum SyncMessage {
+    Snapshot(SnapshotDelta),
+    SnapshotAck(u32),
+    Inputs {
+        player: u8,
+        tick: u32,
+        input: Vec<u8>,
+    },
+    Ping {
+        sent: f64,
+    },
+    Pong {
+        sent: f64,
+        host_time: f64,
+    },
+}
+
+const SYNC_MAGIC: &[u8; 4] = b"E4SY";
+
+impl SyncMessage {
+    fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = SYNC_MAGIC.to_vec();
+        bytes.extend(format::to_bytes(self)?);
+        Ok(bytes)
+    }
+
+    /// `None` for messages that aren't one of these.
+    fn decode(event: &NetEvent) -> Option<(PeerId, Self)> {
+        let NetEvent::Message { peer, bytes, .. } = event else {
+            return None;
+        };
+        let message = format::from_bytes(bytes.strip_prefix(SYNC_MAGIC)?).ok()?;
+        Some((*peer, message))
+    }
+}
+
+/// Someone on the other end of a [`NetSocket`]. Ids aren't reused, so one that reconnects
+/// gets a new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PeerId(u32);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetEvent {
+    /// The handshake finished, so messages can be sent to them.
+    Connected(PeerId),
+    /// They disconnected, timed out, or, for a socket made with [`NetSocket::connect`], never
+    /// answered.
+    Disconnected(PeerId),
+    Message {
+        peer: PeerId,
+        channel: Channel,
+        bytes: Vec<u8>,
+    },
+}
+
+impl NetEvent {
+    /// Reads a message sent with [`NetSocket::send_value`]. `None` if this isn't a message.
+    pub fn read<T: DeserializeOwned>(&self) -> Option<anyhow::Result<T>> {
+        match self {
+            NetEvent::Message { bytes, .. } => {
+                Some(format::from_bytes(bytes).context("couldn't read network message"))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PeerState {
+    Connecting { last_attempt: Instant },
+    Connected,
+}
+
+#[derive(Debug)]
+struct Peer {
+    id: PeerId,
+    state: PeerState,
+    /// When connecting started, or when anything last arrived once connected.
+    last_received: Instant,
+    last_sent: Instant,
+    channels: [ChannelState; 4],
+}
+
+impl Peer {
+    fn new(id: PeerId, state: PeerState, now: Instant) -> Self {
+        Self {
+            id,
+            state,
+            last_received: now,
+            last_sent: now,
+            channels: Channel::ALL.map(ChannelState::new),
+        }
+    }
+}
+
+/// A UDP socket that keeps track of who it's connected to. See the [module docs](self).
+pub struct NetSocket {
+    socket: UdpSocket,
+    peers: HashMap<SocketAddr, Peer>,
+    next_id: u32,
+    /// Whether new peers can connect, which is only the case for hosts.
+    accepting: bool,
+    /// How long a peer can go without sending anything before it's dropped.
+    pub timeout: Duration,
+}
+
+impl NetSocket {
+    fn new(socket: UdpSocket, accepting: bool) -> anyhow::Result<Self> {
+        socket
+            .set_nonblocking(true)
+            .context("couldn't make socket non-blocking")?;
+
+        Ok(Self {
+            socket,
+            peers: HashMap::new(),
+            next_id: 0,
+            accepting,
+            timeout: Duration::from_secs(5),
+        })
+    }
+
+    /// Listens on `addr` for others to [`connect`](Self::connect) to.
+    pub fn host(addr: impl ToSocketAddrs) -> anyhow::Result<Self> {
+        let socket = UdpSocket::bind(addr).context("couldn't bind socket")?;
+        Self::new(socket, true)
+    }
+
+    /// Starts connecting to a host. [`NetEvent::Connected`] comes out of
+    /// [`poll_messages`](Self::poll_messages) once it's answered, with the id to send to.
+    pub fn connect(addr: impl ToSocketAddrs) -> anyhow::Result<Self> {
+        let addr = addr
+            .to_socket_addrs()
+            .context("couldn't resolve address")?
+            .next()
+            .context("address didn't resolve to anything")?;
+        let bind: SocketAddr = match addr {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+
+        let socket = UdpSocket::bind(bind).context("couldn't bind socket")?;
+        let mut net = Self::new(socket, false)?;
+
+        let now = Instant::now();
+        let id = net.next_peer_id();
+        net.peers.insert(
+            addr,
+            Peer::new(id, PeerState::Connecting { last_attempt: now }, now),
+        );
+        net.send_packet(addr, &Packet::Connect);
+
+        Ok(net)
+    }
+
+    /// Whether this was made with [`host`](Self::host), rather than [`connect`](Self::connect).
+    pub fn is_host(&self) -> bool {
+        self.accepting
+    }
+
+    pub fn local_addr(&self) -> anyhow::Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Everyone it's finished connecting to.
+    pub fn peers(&self) -> impl Iterator<Item = PeerId> + '_ {
+        self.peers
+            .values()
+            .filter(|peer| peer.state == PeerState::Connected)
+            .map(|peer| peer.id)
+    }
+
+    pub fn is_connected(&self, peer: PeerId) -> bool {
+        self.peers().any(|id| id == peer)
+    }
+
+    pub fn peer_addr(&self, peer: PeerId) -> Option<SocketAddr> {
+        self.peers
+            .iter()
+            .find(|(_, p)| p.id == peer)
+            .map(|(addr, _)| *addr)
+    }
+
+    pub fn send(&mut self, peer: PeerId, channel: Channel, bytes: &[u8]) -> anyhow::Result<()> {
+        ensure!(
+            bytes.len() <= MAX_MESSAGE_SIZE,
+            "message is {} bytes, but can't be more than {MAX_MESSAGE_SIZE}",
+            bytes.len()
+        );
+        let Some((&addr, peer_data)) = self
+            .peers
+            .iter_mut()
+            .find(|(_, p)| p.id == peer && p.state == PeerState::Connected)
+        else {
+            bail!("not connected to {peer:?}");
+        };
+
+        let now = Instant::now();
+        let sequence = peer_data.channels[channel as usize].send(bytes, now);
+        self.send_packet(
+            addr,
+            &Packet::Message {
+                channel,
+                sequence,
+                payload: bytes.to_vec(),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Sends anything that can be saved with [`storage::save`](crate::storage::save). Read it
+    /// back with [`NetEvent::read`].
+    pub fn send_value<T: Serialize + ?Sized>(
+        &mut self,
+        peer: PeerId,
+        channel: Channel,
+        value: &T,
+    ) -> anyhow::Result<()> {
+        let bytes = format::to_bytes(value)?;
+        self.send(peer, channel, &bytes)
+    }
+
+    /// Sends to everyone it's connected to.
+    pub fn broadcast(&mut self, channel: Channel, bytes: &[u8]) -> anyhow::Result<()> {
+        for peer in self.peers().collect::<Vec<_>>() {
+            self.send(peer, channel, bytes)?;
+        }
+        Ok(())
+    }
+
+    pub fn broadcast_value<T: Serialize + ?Sized>(
+        &mut self,
+        channel: Channel,
+        value: &T,
+    ) -> anyhow::Result<()> {
+        let bytes = format::to_bytes(value)?;
+        self.broadcast(channel, &bytes)
+    }
+
+    fn send_sync(
+        &mut self,
+        peer: PeerId,
+        channel: Channel,
+        message: &SyncMessage,
+    ) -> anyhow::Result<()> {
+        self.send(peer, channel, &message.encode()?)
+    }
+
+    /// Tells them it's over. Anything reliable still waiting for an ack is dropped.
+    pub fn disconnect(&mut self, peer: PeerId) {
+        if let Some(addr) = self.peer_addr(peer) {
+            self.send_packet(addr, &Packet::Disconnect);
+            self.peers.remove(&addr);
+        }
+    }
+
+    /// Everything that's happened since the last call. Call it once a frame.
+    ///
+    /// Datagrams that are too big to be ours, and peers that send messages far past what's
+    /// expected, are ignored and reported as [`EngineError::BadDatagram`].
+    pub fn poll_messages(&mut self) -> Vec<NetEvent> {
+        let mut events = vec![];
+        // one byte spare, so a datagram that's too big fills it instead of being cut down to
+        // a size that looks fine
+        let mut buffer = [0; MAX_PACKET_SIZE + 1];
+
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, addr)) => {
+                    if len > MAX_PACKET_SIZE {
+                        report_error(EngineError::BadDatagram {
+                            from: addr,
+                            reason: "bigger than the biggest packet",
+                        });
+                        continue;
+                    }
+                    if let Some(packet) = Packet::decode(&buffer[..len]) {
+                        self.handle_packet(addr, packet, &mut events);
+                    }
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                // on some platforms an ICMP "port unreachable" from an earlier send shows up
+                // here, which is the timeout's problem, not ours
+                Err(error) if error.kind() == ErrorKind::ConnectionReset => continue,
+                Err(error) => {
+                    log::warn!("couldn't receive from socket: {error}");
+                    break;
+                }
+            }
+        }
+
+        self.update_peers(&mut events);
+        events
+    }
+
+    fn handle_packet(&mut self, addr: SocketAddr, packet: Packet, events: &mut Vec<NetEvent>) {
+        let now = Instant::now();
+
+        if !self.peers.contains_key(&addr) {
+            if packet == Packet::Connect && self.accepting {
+                let id = self.next_peer_id();
+                self.peers
+                    .insert(addr, Peer::new(id, PeerState::Connected, now));
+                events.push(NetEvent::Connected(id));
+                self.send_packet(addr, &Packet::Accept);
+            }
+            return;
+        }
+
+        let peer = self.peers.get_mut(&addr).unwrap();
+        peer.last_received = now;
+        let id = peer.id;
+
+        match packet {
+            // the accept got lost, so they're asking again
+            Packet::Connect => self.send_packet(addr, &Packet::Accept),
+            Packet::Accept => {
+                if let PeerState::Connecting { .. } = peer.state {
+                    peer.state = PeerState::Connected;
+                    events.push(NetEvent::Connected(id));
+                }
+            }
+            Packet::Disconnect => {
+                self.peers.remove(&addr);
+                events.push(NetEvent::Disconnected(id));
+            }
+            Packet::Heartbeat => {}
+            Packet::Message {
+                channel,
+                sequence,
+                payload,
+            } => {
+                if peer.state != PeerState::Connected {
+                    return;
+                }
+                let Some(delivered) = peer.channels[channel as usize].receive(sequence, payload)
+                else {
+                    // not acked, so a reliable message is sent again once the ones before it
+                    // have caught up. sequenced messages aren't, and skipping this far ahead
+                    // means they're broken or lying, so they're dropped
+                    if !channel.is_reliable() {
+                        report_error(EngineError::BadDatagram {
+                            from: addr,
+                            reason: "sequence number too far ahead",
+                        });
+                        self.disconnect(id);
+                        events.push(NetEvent::Disconnected(id));
+                    }
+                    return;
+                };
+                events.extend(delivered.into_iter().map(|bytes| NetEvent::Message {
+                    peer: id,
+                    channel,
+                    bytes,
+                }));
+
+                if channel.is_reliable() {
+                    self.send_packet(addr, &Packet::Ack { channel, sequence });
+                }
+            }
+            Packet::Ack { channel, sequence } => peer.channels[channel as usize].ack(sequence),
+        }
+    }
+
+    fn update_peers(&mut self, events: &mut Vec<NetEvent>) {
+        let now = Instant::now();
+        let mut to_send = vec![];
+
+        self.peers.retain(|&addr, peer| {
+            if now - peer.last_received > self.timeout {
+                events.push(NetEvent::Disconnected(peer.id));
+                return false;
+            }
+
+            match &mut peer.state {
+                PeerState::Connecting { last_attempt } => {
+                    if now - *last_attempt >= CONNECT_RETRY {
+                        *last_attempt = now;
+                        to_send.push((addr, Packet::Connect));
+                    }
+                }
+                PeerState::Connected => {
+                    for (channel, state) in Channel::ALL.into_iter().zip(&mut peer.channels) {
+                        for (sequence, payload) in state.due_for_resend(now, RESEND_AFTER) {
+                            to_send.push((
+                                addr,
+                                Packet::Message {
+                                    channel,
+                                    sequence,
+                                    payload,
+                                },
+                            ));
+                        }
+                    }
+                    if now - peer.last_sent >= HEARTBEAT_AFTER {
+                        to_send.push((addr, Packet::Heartbeat));
+                    }
+                }
+            }
+
+            true
+        });
+
+        for (addr, packet) in to_send {
+            self.send_packet(addr, &packet);
+        }
+    }
+
+    fn send_packet(&mut self, addr: SocketAddr, packet: &Packet) {
+        if let Some(peer) = self.peers.get_mut(&addr) {
+            peer.last_sent = Instant::now();
+        }
+
+        // UDP doesn't promise anything arrives anyway, so a failed send is treated like a lost
+        // packet, and either resent or noticed by the timeout
+        if let Err(error) = self.socket.send_to(&packet.encode(), addr) {
+            log::debug!("couldn't send to {addr}: {error}");
+        }
+    }
+
+    fn next_peer_id(&mut self) -> PeerId {
+        let id = PeerId(self.next_id);
+        self.next_id += 1;
+        id
+    }
+}
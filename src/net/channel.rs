@@ -0,0 +1,133 @@
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+
+use super::Channel;
+
+/// How far past the next expected message one can be and still be accepted. Also caps how many
+/// messages an ordered channel holds on to while it waits for a gap to be filled.
+pub(crate) const RECEIVE_WINDOW: u32 = 1024;
+
+/// A reliable message that hasn't been acked yet.
+#[derive(Debug, Clone)]
+struct Unacked {
+    payload: Vec<u8>,
+    last_sent: Instant,
+}
+
+/// One direction of one channel to one peer: numbering what's sent, and deciding what to do
+/// with what arrives.
+#[derive(Debug, Clone)]
+pub(crate) struct ChannelState {
+    channel: Channel,
+    next_send: u32,
+    unacked: BTreeMap<u32, Unacked>,
+    /// Everything before this has been delivered, or skipped over for sequenced channels.
+    next_expected: u32,
+    /// Arrived ahead of `next_expected`, within [`RECEIVE_WINDOW`] of it. Ordered channels
+    /// hold on to the payload until the gap is filled, unordered ones only need to know it's
+    /// been seen.
+    ahead: BTreeMap<u32, Vec<u8>>,
+}
+
+impl ChannelState {
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            channel,
+            next_send: 0,
+            unacked: BTreeMap::new(),
+            next_expected: 0,
+            ahead: BTreeMap::new(),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn starting_at(channel: Channel, sequence: u32) -> Self {
+        Self {
+            next_send: sequence,
+            next_expected: sequence,
+            ..Self::new(channel)
+        }
+    }
+
+    /// Numbers a message to send, and keeps hold of it to resend if it needs acking.
+    pub fn send(&mut self, payload: &[u8], now: Instant) -> u32 {
+        let sequence = self.next_send;
+        self.next_send = self.next_send.wrapping_add(1);
+
+        if self.channel.is_reliable() {
+            self.unacked.insert(
+                sequence,
+                Unacked {
+                    payload: payload.to_vec(),
+                    last_sent: now,
+                },
+            );
+        }
+
+        sequence
+    }
+
+    pub fn ack(&mut self, sequence: u32) {
+        self.unacked.remove(&sequence);
+    }
+
+    /// Reliable messages that have gone `resend_after` without an ack.
+    pub fn due_for_resend(&mut self, now: Instant, resend_after: Duration) -> Vec<(u32, Vec<u8>)> {
+        self.unacked
+            .iter_mut()
+            .filter(|(_, unacked)| now - unacked.last_sent >= resend_after)
+            .map(|(sequence, unacked)| {
+                unacked.last_sent = now;
+                (*sequence, unacked.payload.clone())
+            })
+            .collect()
+    }
+
+    /// What should be handed to the game now that `sequence` has arrived. Can be nothing, for
+    /// duplicates, stale sequenced messages, or ordered ones waiting on an earlier message,
+    /// or several, when an ordered message fills a gap.
+    ///
+    /// `None` if it's more than [`RECEIVE_WINDOW`] ahead, which it's ignored for. It shouldn't
+    /// be acked, so reliable messages are sent again once the others have caught up.
+    pub fn receive(&mut self, sequence: u32, payload: Vec<u8>) -> Option<Vec<Vec<u8>>> {
+        // sequence numbers wrap around, so anything up to half the range back is behind
+        let distance = sequence.wrapping_sub(self.next_expected);
+        let behind = distance > u32::MAX / 2;
+
+        Some(match self.channel {
+            Channel::Unreliable => vec![payload],
+            // already delivered or skipped over
+            _ if behind => vec![],
+            _ if distance >= RECEIVE_WINDOW => return None,
+            Channel::Sequenced => {
+                self.next_expected = sequence.wrapping_add(1);
+                vec![payload]
+            }
+            Channel::Reliable => {
+                if self.ahead.contains_key(&sequence) {
+                    return Some(vec![]);
+                }
+                self.ahead.insert(sequence, Vec::new());
+                while self.ahead.remove(&self.next_expected).is_some() {
+                    self.next_expected = self.next_expected.wrapping_add(1);
+                }
+                vec![payload]
+            }
+            Channel::ReliableOrdered => {
+                if self.ahead.contains_key(&sequence) {
+                    return Some(vec![]);
+                }
+                self.ahead.insert(sequence, payload);
+
+                let mut delivered = vec![];
+                while let Some(payload) = self.ahead.remove(&self.next_expected) {
+                    delivered.push(payload);
+                    self.next_expected = self.next_expected.wrapping_add(1);
+                }
+                delivered
+            }
+        })
+    }
+}
@@ -0,0 +1,364 @@
+//! Lockstep: everyone runs the same simulation with the same inputs, so only the inputs need
+//! sending. Each tick only runs once everyone's input for it has arrived.
+
+use std::{
+    collections::{BTreeMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::{Channel, NetEvent, NetSocket, SyncMessage};
+use crate::{api::unscaled_delta_time, storage::format};
+
+/// How often clients ask the host the time.
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+/// How many round trips are kept. The quickest is trusted most, since it spent the least time
+/// sat in queues.
+const PING_SAMPLES: usize = 8;
+/// The most ticks one update can run, so a long stall doesn't turn into a burst.
+const MAX_CATCH_UP: u32 = 8;
+
+/// Works out the host's clock from the client's, by pinging it.
+#[derive(Debug)]
+pub struct ClockSync {
+    start: Instant,
+    /// Added to this clock to get the host's, in seconds.
+    offset: f64,
+    round_trip: Option<f64>,
+    /// Round trips and the offsets worked out from them.
+    samples: VecDeque<(f64, f64)>,
+    last_ping: Option<Instant>,
+}
+
+impl Default for ClockSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockSync {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            offset: 0.0,
+            round_trip: None,
+            samples: VecDeque::new(),
+            last_ping: None,
+        }
+    }
+
+    fn local_time(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    /// Seconds since the host's clock started, as best as can be told. On the host, just its
+    /// own clock.
+    pub fn host_time(&self) -> f64 {
+        self.local_time() + self.offset
+    }
+
+    /// How long a message takes to get to the host and back, in seconds. `None` until the host
+    /// has answered.
+    pub fn round_trip(&self) -> Option<f64> {
+        self.round_trip
+    }
+
+    /// Pings the host every so often. Does nothing on the host.
+    pub fn update(&mut self, socket: &mut NetSocket) {
+        if socket.is_host()
+            || self
+                .last_ping
+                .is_some_and(|last| last.elapsed() < PING_INTERVAL)
+        {
+            return;
+        }
+        self.last_ping = Some(Instant::now());
+
+        let ping = SyncMessage::Ping {
+            sent: self.local_time(),
+        };
+        for peer in socket.peers().collect::<Vec<_>>() {
+            let _ = socket.send_sync(peer, Channel::Unreliable, &ping);
+        }
+    }
+
+    /// Answers pings on the host, and learns from the answers on clients. Returns `true` if
+    /// `event` was one of them.
+    pub fn receive(&mut self, socket: &mut NetSocket, event: &NetEvent) -> bool {
+        match SyncMessage::decode(event) {
+            Some((peer, SyncMessage::Ping { sent })) => {
+                let pong = SyncMessage::Pong {
+                    sent,
+                    host_time: self.host_time(),
+                };
+                let _ = socket.send_sync(peer, Channel::Unreliable, &pong);
+                true
+            }
+            Some((_, SyncMessage::Pong { sent, host_time })) => {
+                let now = self.local_time();
+                let round_trip = now - sent;
+                // the host answered about halfway through the round trip
+                let offset = host_time + round_trip / 2.0 - now;
+
+                self.samples.push_back((round_trip, offset));
+                if self.samples.len() > PING_SAMPLES {
+                    self.samples.pop_front();
+                }
+                let (round_trip, offset) = self
+                    .samples
+                    .iter()
+                    .copied()
+                    .min_by(|a, b| a.0.total_cmp(&b.0))
+                    .unwrap();
+                self.round_trip = Some(round_trip);
+                self.offset = offset;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Everyone's input for one tick, to step the simulation with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TickInputs<I> {
+    pub tick: u32,
+    /// By player number.
+    pub inputs: Vec<I>,
+}
+
+/// Runs a fixed tick simulation in lockstep with everyone else.
+///
+/// ```ignore
+/// let mut lockstep = Lockstep::<PlayerInput>::new(my_player, 2);
+///
+/// // every frame
+/// for event in socket.poll_messages() {
+///     lockstep.receive(&mut socket, &event);
+/// }
+/// lockstep.set_input(read_my_input());
+/// for tick in lockstep.update(&mut socket)? {
+///     game.step(&tick.inputs);
+/// }
+/// ```
+///
+/// Player numbers are up to the game, as long as everyone agrees on them. Clients only need
+/// to be connected to the host, who passes their inputs on. If a player leaves, everyone waits
+/// for their input forever, so the game should start again without them.
+#[derive(Debug)]
+pub struct Lockstep<I> {
+    local_player: u8,
+    player_count: u8,
+    tick_length: f32,
+    input_delay: u32,
+    /// The next tick to run.
+    tick: u32,
+    accumulator: f32,
+    /// By tick then player, including ones that have arrived early.
+    inputs: BTreeMap<u32, Vec<Option<I>>>,
+    /// The local input, sent for each tick until it's changed.
+    local_input: I,
+    /// The tick the next local input is sent for.
+    next_input_tick: u32,
+    pub clock: ClockSync,
+}
+
+impl<I: Serialize + DeserializeOwned + Clone + Default> Lockstep<I> {
+    pub fn new(local_player: u8, player_count: u8) -> Self {
+        let input_delay = 3;
+        Self {
+            local_player,
+            player_count,
+            tick_length: 1.0 / 60.0,
+            input_delay,
+            tick: 0,
+            accumulator: 0.0,
+            inputs: BTreeMap::new(),
+            local_input: I::default(),
+            next_input_tick: input_delay,
+            clock: ClockSync::new(),
+        }
+    }
+
+    /// Seconds per tick. Defaults to 1/60th.
+    pub fn with_tick_length(mut self, tick_length: f32) -> Self {
+        self.tick_length = tick_length;
+        self
+    }
+
+    /// How many ticks later local input is used, to give it time to reach everyone before they
+    /// need it. Higher hides more lag, but makes the controls feel slower. Defaults to 3, and
+    /// everyone needs the same one.
+    pub fn with_input_delay(mut self, ticks: u32) -> Self {
+        self.input_delay = ticks;
+        self.next_input_tick = ticks;
+        self
+    }
+
+    /// The next tick to run.
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    pub fn tick_length(&self) -> f32 {
+        self.tick_length
+    }
+
+    /// Whether the next tick is waiting on someone's input.
+    pub fn is_waiting(&self) -> bool {
+        self.tick >= self.input_delay
+            && !self
+                .inputs
+                .get(&self.tick)
+                .is_some_and(|inputs| inputs.iter().all(Option::is_some))
+    }
+
+    /// The input to send for the coming ticks.
+    pub fn set_input(&mut self, input: I) {
+        self.local_input = input;
+    }
+
+    /// Picks inputs and clock sync messages out of what
+    /// [`poll_messages`](NetSocket::poll_messages) gave back. Returns `true` if `event` was one.
+    pub fn receive(&mut self, socket: &mut NetSocket, event: &NetEvent) -> bool {
+        if self.clock.receive(socket, event) {
+            return true;
+        }
+        let Some((
+            from,
+            SyncMessage::Inputs {
+                player,
+                tick,
+                input,
+            },
+        )) = SyncMessage::decode(event)
+        else {
+            return false;
+        };
+
+        if player >= self.player_count || player == self.local_player || tick < self.tick {
+            return true;
+        }
+        if !self.accepts_tick(tick) {
+            log::warn!("ignoring input from player {player} for tick {tick}, too far ahead");
+            return true;
+        }
+        let Ok(decoded) = format::from_bytes(&input) else {
+            log::warn!("couldn't read input from player {player}");
+            return true;
+        };
+        self.inputs_for(tick)[player as usize] = Some(decoded);
+
+        // clients are only connected to the host, so it passes everyone's input on
+        if socket.is_host() {
+            let message = SyncMessage::Inputs {
+                player,
+                tick,
+                input,
+            };
+            for peer in socket
+                .peers()
+                .filter(|peer| *peer != from)
+                .collect::<Vec<_>>()
+            {
+                let _ = socket.send_sync(peer, Channel::Reliable, &message);
+            }
+        }
+
+        true
+    }
+
+    /// Runs as many ticks as this frame's [`unscaled_delta_time`] covers, and everyone's
+    /// input has arrived for.
+    pub fn update(&mut self, socket: &mut NetSocket) -> anyhow::Result<Vec<TickInputs<I>>> {
+        self.advance(socket, unscaled_delta_time())
+    }
+
+    /// Like [`update`](Self::update), for `delta_time` seconds.
+    pub fn advance(
+        &mut self,
+        socket: &mut NetSocket,
+        delta_time: f32,
+    ) -> anyhow::Result<Vec<TickInputs<I>>> {
+        self.clock.update(socket);
+
+        // run a little faster when behind the host's clock, and slower when ahead, so no-one
+        // gets far enough ahead to spend their time waiting on everyone else
+        let host_tick = self.clock.host_time() / self.tick_length as f64;
+        let behind = (host_tick - self.tick as f64) as f32;
+        self.accumulator += delta_time * (1.0 + behind * 0.1).clamp(0.5, 1.5);
+        self.accumulator = self.accumulator.min(self.tick_length * MAX_CATCH_UP as f32);
+
+        let mut ticks = vec![];
+        while self.accumulator >= self.tick_length {
+            self.send_input(socket)?;
+
+            let Some(inputs) = self.take_inputs() else {
+                break;
+            };
+            ticks.push(TickInputs {
+                tick: self.tick,
+                inputs,
+            });
+            self.tick += 1;
+            self.accumulator -= self.tick_length;
+        }
+
+        Ok(ticks)
+    }
+
+    /// Whether input for `tick` could have been sent by someone playing fairly. Nobody can run
+    /// a tick before they have our input for it, which we've sent up to `input_delay` ahead,
+    /// and then they send theirs up to `input_delay` past the tick after that. Anything
+    /// further is a bug or a lie, and would otherwise be held on to forever.
+    pub(crate) fn accepts_tick(&self, tick: u32) -> bool {
+        let furthest = self
+            .tick
+            .saturating_add(self.input_delay.saturating_mul(2).saturating_add(1));
+        (self.tick..=furthest).contains(&tick)
+    }
+
+    fn inputs_for(&mut self, tick: u32) -> &mut Vec<Option<I>> {
+        let player_count = self.player_count as usize;
+        self.inputs
+            .entry(tick)
+            .or_insert_with(|| vec![None; player_count])
+    }
+
+    /// Sends the local input for every tick up to `input_delay` ahead.
+    fn send_input(&mut self, socket: &mut NetSocket) -> anyhow::Result<()> {
+        while self.next_input_tick <= self.tick + self.input_delay {
+            let tick = self.next_input_tick;
+            let input = self.local_input.clone();
+            let message = SyncMessage::Inputs {
+                player: self.local_player,
+                tick,
+                input: format::to_bytes(&input)?,
+            };
+
+            let local_player = self.local_player as usize;
+            self.inputs_for(tick)[local_player] = Some(input);
+            for peer in socket.peers().collect::<Vec<_>>() {
+                socket.send_sync(peer, Channel::Reliable, &message)?;
+            }
+            self.next_input_tick += 1;
+        }
+        Ok(())
+    }
+
+    /// Everyone's input for the next tick, if it's all arrived. Nobody has input for the ticks
+    /// before the delay, so those run with the default.
+    fn take_inputs(&mut self) -> Option<Vec<I>> {
+        if self.tick < self.input_delay {
+            return Some(vec![I::default(); self.player_count as usize]);
+        }
+
+        let inputs = self.inputs.get(&self.tick)?;
+        if !inputs.iter().all(Option::is_some) {
+            return None;
+        }
+        let inputs = self.inputs.remove(&self.tick)?;
+        Some(inputs.into_iter().flatten().collect())
+    }
+}
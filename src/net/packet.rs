@@ -0,0 +1,91 @@
+//! What goes in each datagram. Every packet starts with [`PROTOCOL`], so stray traffic on the
+//! same port is ignored, then a byte saying what kind of packet it is.
+
+use super::Channel;
+
+const PROTOCOL: [u8; 4] = *b"E4NT";
+
+/// The protocol, kind, channel and sequence number in front of a message's payload.
+pub(crate) const MESSAGE_HEADER_SIZE: usize = PROTOCOL.len() + 1 + 1 + 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Packet {
+    Connect,
+    Accept,
+    Disconnect,
+    /// Sent when nothing else has been for a while, so the other side doesn't time out.
+    Heartbeat,
+    Message {
+        channel: Channel,
+        sequence: u32,
+        payload: Vec<u8>,
+    },
+    Ack {
+        channel: Channel,
+        sequence: u32,
+    },
+}
+
+impl Packet {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = PROTOCOL.to_vec();
+
+        match self {
+            Packet::Connect => bytes.push(0),
+            Packet::Accept => bytes.push(1),
+            Packet::Disconnect => bytes.push(2),
+            Packet::Heartbeat => bytes.push(3),
+            Packet::Message {
+                channel,
+                sequence,
+                payload,
+            } => {
+                bytes.push(4);
+                bytes.push(*channel as u8);
+                bytes.extend_from_slice(&sequence.to_le_bytes());
+                bytes.extend_from_slice(payload);
+            }
+            Packet::Ack { channel, sequence } => {
+                bytes.push(5);
+                bytes.push(*channel as u8);
+                bytes.extend_from_slice(&sequence.to_le_bytes());
+            }
+        }
+
+        bytes
+    }
+
+    /// `None` for anything that isn't one of ours, or is cut short.
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.strip_prefix(&PROTOCOL)?;
+        let (&kind, rest) = bytes.split_first()?;
+
+        Some(match kind {
+            0 => Packet::Connect,
+            1 => Packet::Accept,
+            2 => Packet::Disconnect,
+            3 => Packet::Heartbeat,
+            4 => {
+                let (channel, sequence, payload) = channel_and_sequence(rest)?;
+                Packet::Message {
+                    channel,
+                    sequence,
+                    payload: payload.to_vec(),
+                }
+            }
+            5 => {
+                let (channel, sequence, _) = channel_and_sequence(rest)?;
+                Packet::Ack { channel, sequence }
+            }
+            _ => return None,
+        })
+    }
+}
+
+/// The channel and sequence number at the start of `bytes`, and whatever comes after them.
+fn channel_and_sequence(bytes: &[u8]) -> Option<(Channel, u32, &[u8])> {
+    let (&channel, rest) = bytes.split_first()?;
+    let channel = Channel::from_u8(channel)?;
+    let (sequence, rest) = rest.split_first_chunk::<4>()?;
+    Some((channel, u32::from_le_bytes(*sequence), rest))
+}
@@ -0,0 +1,302 @@
+//! The host sending the state of the game to everyone else. Each snapshot only carries what
+//! changed since the last one that peer acked, and values that only changed a little only
+//! carry the bytes that did.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use super::{Channel, NetEvent, NetSocket, PeerId, SyncMessage};
+use crate::storage::format;
+
+/// How many snapshots are kept to diff against. A peer that hasn't acked any of them gets the
+/// whole thing.
+const HISTORY: usize = 64;
+
+/// The state of the game at one tick, as values stored under names. Values are kept serialized,
+/// so finding what changed between two snapshots is cheap.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    pub tick: u32,
+    values: BTreeMap<String, Vec<u8>>,
+}
+
+impl Snapshot {
+    pub fn new(tick: u32) -> Self {
+        Self {
+            tick,
+            values: BTreeMap::new(),
+        }
+    }
+
+    pub fn set<T: Serialize + ?Sized>(
+        &mut self,
+        key: impl Into<String>,
+        value: &T,
+    ) -> anyhow::Result<()> {
+        self.values.insert(key.into(), format::to_bytes(value)?);
+        Ok(())
+    }
+
+    /// `None` if there's nothing under `key`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<anyhow::Result<T>> {
+        let bytes = self.values.get(key)?;
+        Some(format::from_bytes(bytes).with_context(|| format!("couldn't read `{key}`")))
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.values.remove(key);
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    /// What has to be sent for someone with `base` to end up with this. With no base,
+    /// everything is sent.
+    pub fn delta_from(&self, base: Option<&Snapshot>) -> SnapshotDelta {
+        let empty = BTreeMap::new();
+        let base_values = base.map_or(&empty, |base| &base.values);
+
+        let changed = self
+            .values
+            .iter()
+            .filter_map(|(key, value)| {
+                let change = match base_values.get(key) {
+                    Some(old) if old == value => return None,
+                    Some(old) if old.len() == value.len() => {
+                        let patch = patch(old, value);
+                        if patch.len() < value.len() {
+                            ValueChange::Patch(patch)
+                        } else {
+                            ValueChange::Whole(value.clone())
+                        }
+                    }
+                    _ => ValueChange::Whole(value.clone()),
+                };
+                Some((key.clone(), change))
+            })
+            .collect();
+
+        let removed = base_values
+            .keys()
+            .filter(|key| !self.values.contains_key(*key))
+            .cloned()
+            .collect();
+
+        SnapshotDelta {
+            base: base.map(|base| base.tick),
+            tick: self.tick,
+            changed,
+            removed,
+        }
+    }
+}
+
+/// What changed between two [`Snapshot`]s.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotDelta {
+    base: Option<u32>,
+    tick: u32,
+    changed: Vec<(String, ValueChange)>,
+    removed: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum ValueChange {
+    Whole(Vec<u8>),
+    /// Made by [`patch`], for values the same length as before.
+    Patch(Vec<u8>),
+}
+
+impl SnapshotDelta {
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// The tick of the snapshot this has to be applied to, if any.
+    pub fn base(&self) -> Option<u32> {
+        self.base
+    }
+
+    /// `None` if `base` isn't the snapshot this was made from.
+    pub fn apply(&self, base: Option<&Snapshot>) -> Option<Snapshot> {
+        if self.base != base.map(|base| base.tick) {
+            return None;
+        }
+
+        let mut snapshot = base.cloned().unwrap_or_default();
+        snapshot.tick = self.tick;
+
+        for key in &self.removed {
+            snapshot.values.remove(key);
+        }
+        for (key, change) in &self.changed {
+            let value = match change {
+                ValueChange::Whole(value) => value.clone(),
+                ValueChange::Patch(patch) => apply_patch(snapshot.values.get(key)?, patch)?,
+            };
+            snapshot.values.insert(key.clone(), value);
+        }
+
+        Some(snapshot)
+    }
+}
+
+/// The bytes of `new` that differ from `old`, as runs of: how many bytes are the same, how many
+/// differ, then the bytes that differ.
+fn patch(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut patch = vec![];
+    let mut i = 0;
+
+    while i < new.len() {
+        let same_start = i;
+        while i < new.len() && old[i] == new[i] {
+            i += 1;
+        }
+        let changed_start = i;
+        while i < new.len() && old[i] != new[i] {
+            i += 1;
+        }
+
+        write_varint(&mut patch, changed_start - same_start);
+        write_varint(&mut patch, i - changed_start);
+        patch.extend_from_slice(&new[changed_start..i]);
+    }
+
+    patch
+}
+
+fn apply_patch(old: &[u8], mut patch: &[u8]) -> Option<Vec<u8>> {
+    let mut new = old.to_vec();
+    let mut i = 0;
+
+    while !patch.is_empty() {
+        i += read_varint(&mut patch)?;
+        let changed = read_varint(&mut patch)?;
+        let (bytes, rest) = patch.split_at_checked(changed)?;
+        new.get_mut(i..i + changed)?.copy_from_slice(bytes);
+        i += changed;
+        patch = rest;
+    }
+
+    Some(new)
+}
+
+/// Seven bits at a time, so small numbers take one byte.
+fn write_varint(bytes: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        bytes.push((n as u8 & 0x7f) | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<usize> {
+    let mut n = 0;
+    for shift in (0..usize::BITS).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        n |= ((byte & 0x7f) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+/// Sends snapshots from the host. Each peer gets a delta from the newest snapshot they've acked.
+#[derive(Debug, Default)]
+pub struct SnapshotSender {
+    history: VecDeque<Snapshot>,
+    acked: HashMap<PeerId, u32>,
+}
+
+impl SnapshotSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends `snapshot` to everyone `socket` is connected to. Snapshots go on
+    /// [`Channel::Sequenced`], so one that's lost is just replaced by the next, and one that's
+    /// late is never applied over a newer one.
+    pub fn send(&mut self, socket: &mut NetSocket, snapshot: Snapshot) -> anyhow::Result<()> {
+        self.acked.retain(|peer, _| socket.is_connected(*peer));
+
+        for peer in socket.peers().collect::<Vec<_>>() {
+            let base = self
+                .acked
+                .get(&peer)
+                .and_then(|tick| self.history.iter().find(|old| old.tick == *tick));
+            let delta = snapshot.delta_from(base);
+            socket.send_sync(peer, Channel::Sequenced, &SyncMessage::Snapshot(delta))?;
+        }
+
+        self.history.push_back(snapshot);
+        if self.history.len() > HISTORY {
+            self.history.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Picks acks out of what [`poll_messages`](NetSocket::poll_messages) gave back. Returns
+    /// `true` if `event` was one.
+    pub fn receive(&mut self, event: &NetEvent) -> bool {
+        let Some((peer, SyncMessage::SnapshotAck(tick))) = SyncMessage::decode(event) else {
+            return false;
+        };
+
+        let acked = self.acked.entry(peer).or_insert(tick);
+        *acked = (*acked).max(tick);
+        true
+    }
+}
+
+/// Puts snapshots back together on the other end of a [`SnapshotSender`].
+#[derive(Debug, Default)]
+pub struct SnapshotReceiver {
+    history: VecDeque<Snapshot>,
+}
+
+impl SnapshotReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The new snapshot, if `event` was one that could be applied. Acks it, so the next one
+    /// can be sent as a delta from it.
+    pub fn receive(&mut self, socket: &mut NetSocket, event: &NetEvent) -> Option<&Snapshot> {
+        let Some((peer, SyncMessage::Snapshot(delta))) = SyncMessage::decode(event) else {
+            return None;
+        };
+        if let Some(latest) = self.history.back()
+            && delta.tick <= latest.tick
+        {
+            return None;
+        }
+
+        let base = match delta.base {
+            Some(tick) => Some(self.history.iter().find(|old| old.tick == tick)?),
+            None => None,
+        };
+        let snapshot = delta.apply(base)?;
+
+        // this only fails if the host has gone, which the game hears about anyway
+        let _ = socket.send_sync(
+            peer,
+            Channel::Unreliable,
+            &SyncMessage::SnapshotAck(snapshot.tick),
+        );
+
+        self.history.push_back(snapshot);
+        if self.history.len() > HISTORY {
+            self.history.pop_front();
+        }
+        self.history.back()
+    }
+
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.history.back()
+    }
+}
@@ -0,0 +1,166 @@
+use bevy_math::{Rect, Vec2};
+
+use crate::{
+    collisions::AABB2D, color::Color, draw_queue_2d::DrawQueue2D, get_state, prelude::Transform2D,
+    textures::TextureRef,
+};
+
+/// How far in from each edge of a texture its corners go, in pixels of the texture. Corners
+/// are drawn at this size no matter how big the rect is, edges only stretch along their length,
+/// and the center stretches both ways.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NineSliceMargins {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl NineSliceMargins {
+    pub fn new(left: f32, right: f32, top: f32, bottom: f32) -> Self {
+        Self {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+
+    pub fn uniform(margin: f32) -> Self {
+        Self::new(margin, margin, margin, margin)
+    }
+}
+
+/// Splits `rect` into up to nine pieces, each paired with the part of the texture it shows
+/// (in pixels, from the top left). Pieces with no area are left out. If the rect is too small
+/// for the corners, they shrink to fit.
+pub fn nine_slice_patches(
+    rect: Rect,
+    texture_size: Vec2,
+    margins: NineSliceMargins,
+) -> Vec<(Rect, Rect)> {
+    let shrink = |start: f32, end: f32, available: f32| {
+        let total = start + end;
+        if total > available && total > 0.0 {
+            let factor = available / total;
+            (start * factor, end * factor)
+        } else {
+            (start, end)
+        }
+    };
+
+    let (left, right) = shrink(margins.left, margins.right, rect.width());
+    let (top, bottom) = shrink(margins.top, margins.bottom, rect.height());
+
+    let destination_x = [
+        rect.min.x,
+        rect.min.x + left,
+        rect.max.x - right,
+        rect.max.x,
+    ];
+    let destination_y = [
+        rect.min.y,
+        rect.min.y + top,
+        rect.max.y - bottom,
+        rect.max.y,
+    ];
+    let source_x = [
+        0.0,
+        margins.left,
+        texture_size.x - margins.right,
+        texture_size.x,
+    ];
+    let source_y = [
+        0.0,
+        margins.top,
+        texture_size.y - margins.bottom,
+        texture_size.y,
+    ];
+
+    let mut patches = Vec::with_capacity(9);
+    for row in 0..3 {
+        for column in 0..3 {
+            let destination = Rect::new(
+                destination_x[column],
+                destination_y[row],
+                destination_x[column + 1],
+                destination_y[row + 1],
+            );
+
+            if destination.is_empty() {
+                continue;
+            }
+
+            let source = Rect::new(
+                source_x[column],
+                source_y[row],
+                source_x[column + 1],
+                source_y[row + 1],
+            );
+            patches.push((destination, source));
+        }
+    }
+
+    patches
+}
+
+impl DrawQueue2D {
+    pub fn add_nine_slice(
+        &mut self,
+        texture: TextureRef,
+        rect: Rect,
+        margins: NineSliceMargins,
+        color: Color,
+    ) {
+        let texture_size = texture.dimensions().as_vec2();
+
+        // all the pieces share a z so they count as one thing for draw order
+        let z = self.current_z();
+        for (destination, source) in nine_slice_patches(rect, texture_size, margins) {
+            self.add_sprite_at_z(
+                texture,
+                Transform2D::from_scale_translation(destination.size(), destination.min),
+                color,
+                Some(source),
+                z,
+            );
+        }
+        self.next_z();
+    }
+}
+
+/// Draws `texture` stretched over `rect` without stretching its corners, for UI panels and
+/// buttons.
+pub fn draw_nine_slice(texture: TextureRef, rect: Rect, margins: NineSliceMargins) {
+    draw_nine_slice_ex(texture, rect, margins, Color::WHITE);
+}
+
+pub fn draw_nine_slice_ex(
+    texture: TextureRef,
+    rect: Rect,
+    margins: NineSliceMargins,
+    color: Color,
+) {
+    get_state()
+        .draw_queue_2d()
+        .add_nine_slice(texture, rect, margins, color);
+}
+
+pub fn draw_nine_slice_world(texture: TextureRef, rect: Rect, margins: NineSliceMargins) {
+    draw_nine_slice_world_ex(texture, rect, margins, Color::WHITE);
+}
+
+pub fn draw_nine_slice_world_ex(
+    texture: TextureRef,
+    rect: Rect,
+    margins: NineSliceMargins,
+    color: Color,
+) {
+    if !AABB2D::new(rect.min, rect.max).is_visible_in_world() {
+        return;
+    }
+
+    get_state()
+        .world_draw_queue_2d()
+        .add_nine_slice(texture, rect, margins, color);
+}
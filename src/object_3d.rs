@@ -1,6 +1,7 @@
 use std::io::Cursor;
 
 use crate::utils::EngineCreate;
+use bevy_math::{Vec2, Vec3};
 use engine_4_macros::gen_ref_type;
 use glium::{IndexBuffer, VertexBuffer};
 use obj::{FromRawVertex, load_obj, raw::object::Polygon};
@@ -10,8 +11,10 @@ use crate::{
     draw_queue_2d::MaterialVertex3D,
     draw_queue_3d::ObjectToDraw,
     get_state,
+    input_handling::cursor,
     materials::{DEFAULT_MATERIAL, MaterialRef},
     prelude::{Material, Transform3D, create_flat_3d_material},
+    shapes_3d::{AABB3D, Ray3D},
 };
 
 pub struct Object3D {
@@ -37,7 +40,6 @@ impl Object3D {
         data: &[u8],
         material: MaterialRef,
     ) -> anyhow::Result<Object3DRef> {
-        let state = get_state();
         let buf = Cursor::new(data);
         let obj = load_obj::<MaterialVertex3D, _, _>(buf)?;
 
@@ -47,15 +49,8 @@ impl Object3D {
         // dbg!(&vertices);
         // dbg!(&indices);
 
-        let vertices = VertexBuffer::new(&state.display, &vertices)?;
-        let indices = IndexBuffer::new(
-            &state.display,
-            glium::index::PrimitiveType::TrianglesList,
-            &indices,
-        )?;
-
         let object = Self {
-            mesh: Mesh { vertices, indices }.create(),
+            mesh: Mesh::new(&vertices, &indices)?.create(),
             material,
             transform: Transform3D::IDENTITY,
         };
@@ -64,7 +59,6 @@ impl Object3D {
     }
 
     pub fn compute_smooth_normals(&mut self) {
-        use bevy_math::Vec3;
         use std::collections::HashMap;
 
         let vertices: Vec<MaterialVertex3D> = self.mesh.vertices.read().unwrap();
@@ -95,18 +89,21 @@ impl Object3D {
             }
         }
 
-        let new_vertices: Vec<MaterialVertex3D> = vertices
+        let mut new_vertices: Vec<MaterialVertex3D> = vertices
             .iter()
             .enumerate()
             .map(|(i, v)| MaterialVertex3D {
                 position: v.position,
                 normal: new_normals[i],
                 tex_coords: v.tex_coords,
+                tangent: [0.0; 4],
             })
             .collect();
+        let indices: Vec<u32> = self.mesh.indices.read().unwrap();
+        generate_tangents(&mut new_vertices, &indices);
 
         let state = get_state();
-        self.mesh.vertices = VertexBuffer::new(&state.display, &new_vertices).unwrap();
+        self.mesh.vertices = VertexBuffer::new(state.display(), &new_vertices).unwrap();
     }
 
     pub fn from_mesh_and_material(mesh: MeshRef, material: MaterialRef) -> Object3DRef {
@@ -154,6 +151,7 @@ impl FromRawVertex<u32> for MaterialVertex3D {
                                     position: [pos.0, pos.1, pos.2],
                                     normal: [0.0, 0.0, 0.0],
                                     tex_coords: [0.0, 0.0],
+                                    tangent: [0.0; 4],
                                 });
                                 idx
                             });
@@ -177,6 +175,7 @@ impl FromRawVertex<u32> for MaterialVertex3D {
                                     position: [pos.0, pos.1, pos.2],
                                     normal: [0.0, 0.0, 0.0],
                                     tex_coords: [tex.0, tex.1],
+                                    tangent: [0.0; 4],
                                 });
                                 idx
                             });
@@ -201,6 +200,7 @@ impl FromRawVertex<u32> for MaterialVertex3D {
                                     position: [pos.0, pos.1, pos.2],
                                     normal: [normal.0, normal.1, normal.2],
                                     tex_coords: [tex.0, tex.1],
+                                    tangent: [0.0; 4],
                                 });
                                 idx
                             });
@@ -224,6 +224,7 @@ impl FromRawVertex<u32> for MaterialVertex3D {
                                     position: [pos.0, pos.1, pos.2],
                                     normal: [normal.0, normal.1, normal.2],
                                     tex_coords: [0.0, 0.0],
+                                    tangent: [0.0; 4],
                                 });
                                 idx
                             });
@@ -244,24 +245,19 @@ impl Object3DRef {
     pub fn draw(&self) {
         get_state()
             .draw_queue_3d()
-            .objects
             .push(ObjectToDraw::Single(*self));
     }
 
     pub fn draw_many(&self, transforms: Vec<Transform3D>) {
-        get_state()
-            .draw_queue_3d()
-            .objects
-            .push(ObjectToDraw::Many {
-                object: *self,
-                transforms,
-            });
+        get_state().draw_queue_3d().push(ObjectToDraw::Many {
+            object: *self,
+            transforms,
+        });
     }
 
     pub fn draw_with_transform(&self, transform: Transform3D) {
         get_state()
             .draw_queue_3d()
-            .objects
             .push(ObjectToDraw::WithTransform(*self, transform));
     }
 
@@ -294,31 +290,26 @@ pub fn test_triangle() -> anyhow::Result<Object3DRef> {
             position: [0.0, 1.0, 0.0],
             normal: [0.0, 0.0, 1.0],
             tex_coords: [0.5, 0.0],
+            tangent: [0.0; 4],
         },
         MaterialVertex3D {
             position: [-1.0, -1.0, 0.0],
             normal: [0.0, 0.0, 1.0],
             tex_coords: [0.0, 1.0],
+            tangent: [0.0; 4],
         },
         MaterialVertex3D {
             position: [1.0, -1.0, 0.0],
             normal: [0.0, 0.0, 1.0],
             tex_coords: [1.0, 1.0],
+            tangent: [0.0; 4],
         },
     ];
 
     let indices = vec![0, 1, 2];
 
-    let state = get_state();
-    let vertices = VertexBuffer::new(&state.display, &vertices)?;
-    let indices = IndexBuffer::new(
-        &state.display,
-        glium::index::PrimitiveType::TrianglesList,
-        &indices,
-    )?;
-
     let triangle = Object3D {
-        mesh: Mesh { vertices, indices }.create(),
+        mesh: Mesh::new(&vertices, &indices)?.create(),
         material: create_flat_3d_material(Color::RED_500),
         transform: Transform3D::IDENTITY,
     };
@@ -329,6 +320,150 @@ pub fn test_triangle() -> anyhow::Result<Object3DRef> {
 pub struct Mesh {
     pub vertices: VertexBuffer<MaterialVertex3D>,
     pub indices: IndexBuffer<u32>,
+    /// Bounds in model space, used for picking.
+    pub bounds: AABB3D,
+    /// Copies of the vertex positions and indices, so picking doesn't have to read the buffers
+    /// back from the GPU.
+    pub(crate) positions: Vec<Vec3>,
+    pub(crate) triangles: Vec<u32>,
+}
+
+impl Mesh {
+    /// Tangents are generated from the normals and UVs, unless every vertex already has one.
+    pub fn new(vertices: &[MaterialVertex3D], indices: &[u32]) -> anyhow::Result<Self> {
+        let state = get_state();
+
+        let mut with_tangents;
+        let vertices = if vertices.iter().any(|v| v.tangent == [0.0; 4]) {
+            with_tangents = vertices.to_vec();
+            generate_tangents(&mut with_tangents, indices);
+            &with_tangents
+        } else {
+            vertices
+        };
+
+        Ok(Self {
+            vertices: VertexBuffer::new(state.display(), vertices)?,
+            indices: IndexBuffer::new(
+                state.display(),
+                glium::index::PrimitiveType::TrianglesList,
+                indices,
+            )?,
+            bounds: AABB3D::from_points(vertices.iter().map(|v| Vec3::from(v.position))),
+            positions: vertices.iter().map(|v| Vec3::from(v.position)).collect(),
+            triangles: indices.to_vec(),
+        })
+    }
+
+    /// Closest triangle hit in model space. Goes through every triangle, so only call this
+    /// after a cheaper bounds check.
+    pub fn raycast(&self, ray: &Ray3D) -> Option<f32> {
+        raycast_triangles(&self.positions, &self.triangles, ray)
+    }
+}
+
+/// Closest of the triangles `indices` makes out of `positions` that `ray` hits. Indices past
+/// the end of `positions` are skipped rather than panicking.
+pub(crate) fn raycast_triangles(positions: &[Vec3], indices: &[u32], ray: &Ray3D) -> Option<f32> {
+    indices
+        .chunks_exact(3)
+        .filter_map(|tri| {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(|i| positions.get(i as usize).copied());
+            ray.intersect_triangle(a?, b?, c?)
+        })
+        .min_by(|a, b| a.total_cmp(b))
 }
 
 gen_ref_type!(Mesh, MeshRef, meshes);
+
+/// Fills in the tangent of every vertex from the triangles around it, for normal mapping.
+/// Vertices without usable UVs get an arbitrary tangent perpendicular to their normal.
+pub fn generate_tangents(vertices: &mut [MaterialVertex3D], indices: &[u32]) {
+    let mut tangents = vec![Vec3::ZERO; vertices.len()];
+    let mut bitangents = vec![Vec3::ZERO; vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| i as usize);
+        let [pa, pb, pc] = [a, b, c].map(|i| Vec3::from(vertices[i].position));
+        let [ua, ub, uc] = [a, b, c].map(|i| Vec2::from(vertices[i].tex_coords));
+
+        let (edge_1, edge_2) = (pb - pa, pc - pa);
+        let (delta_1, delta_2) = (ub - ua, uc - ua);
+        let determinant = delta_1.x * delta_2.y - delta_2.x * delta_1.y;
+        if determinant.abs() < f32::EPSILON {
+            continue;
+        }
+
+        let tangent = (edge_1 * delta_2.y - edge_2 * delta_1.y) / determinant;
+        let bitangent = (edge_2 * delta_1.x - edge_1 * delta_2.x) / determinant;
+        for i in [a, b, c] {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        }
+    }
+
+    for (i, vertex) in vertices.iter_mut().enumerate() {
+        let normal = Vec3::from(vertex.normal).normalize_or(Vec3::Y);
+        let tangent = (tangents[i] - normal * normal.dot(tangents[i]))
+            .try_normalize()
+            .unwrap_or_else(|| normal.any_orthonormal_vector());
+        // V goes down the image, and normal maps have +Y pointing up it
+        let handedness = if normal.cross(tangent).dot(bitangents[i]) > 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        vertex.tangent = [tangent.x, tangent.y, tangent.z, handedness];
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ObjectHit {
+    pub object: Object3DRef,
+    pub point: Vec3,
+    pub distance: f32,
+}
+
+/// Casts a ray against every object in storage, using their current transforms. Objects are
+/// first checked against their bounding boxes and then against the actual triangles.
+/// Hits are sorted closest first.
+pub fn raycast_objects(ray: &Ray3D) -> Vec<ObjectHit> {
+    let state = get_state();
+    let mut hits = Vec::new();
+
+    for (index, generation, object) in state.storage.objects.iter_mut() {
+        // its mesh may have been removed since
+        let Some(mesh) = object.mesh.try_get() else {
+            continue;
+        };
+        let matrix = object.transform.matrix();
+
+        if mesh.bounds.transformed(matrix).raycast(ray).is_none() {
+            continue;
+        }
+
+        let local_ray = ray.transformed(matrix.inverse());
+        let Some(local_distance) = mesh.raycast(&local_ray) else {
+            continue;
+        };
+
+        let point = matrix.transform_point3(local_ray.point_at(local_distance));
+
+        hits.push(ObjectHit {
+            object: Object3DRef::from_raw(index, generation),
+            point,
+            distance: ray.origin.distance(point),
+        });
+    }
+
+    hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+    hits
+}
+
+/// Closest object under the cursor, if there is one.
+pub fn object_under_cursor() -> Option<ObjectHit> {
+    let (x, y) = cursor()?;
+    let ray = get_state().camera_3d.screen_to_ray(Vec2::new(x, y));
+
+    raycast_objects(&ray).into_iter().next()
+}
@@ -0,0 +1,118 @@
+//! Colored outlines around selected things, for selection feedback in strategy and puzzle
+//! games.
+//!
+//! Give draws an outline with [`set_outline`] or [`with_outline`], the same way as pick ids.
+//! At the end of the frame everything outlined is drawn again into a mask, in its outline
+//! color, and a ring of that color is drawn around the edges of the mask. Works for shapes,
+//! sprites (around their solid parts), text and 3D objects, in the world and in the UI.
+//!
+//! Outlines go around the whole silhouette, even the parts hidden behind other things, and are
+//! drawn on top of the world but under the UI and overlay. Only things drawn to the screen get
+//! outlines, not things drawn to render textures.
+
+use bevy_math::Vec2;
+use glium::{Surface, Texture2d, uniform};
+
+use crate::{
+    color::Color,
+    error::OrReport,
+    get_state,
+    post_processing::{POSTPROCESS_VERTEX_SHADER, render_fullscreen_quad},
+    programs::{ProgramRef, cached_program, load_program},
+};
+
+/// Outline color for everything drawn after this, in 2D and 3D, until the end of the frame or
+/// until it's changed again. `None` stops outlining.
+pub fn set_outline(outline: Option<Color>) {
+    let state = get_state();
+    state.draw_queue_2d().set_outline(outline);
+    state.world_draw_queue_2d().set_outline(outline);
+    state.draw_queue_3d().set_outline(outline);
+}
+
+pub fn outline() -> Option<Color> {
+    get_state().draw_queue_2d().outline()
+}
+
+/// Outlines everything drawn in `f`, then goes back to the previous outline.
+pub fn with_outline<T>(color: Color, f: impl FnOnce() -> T) -> T {
+    let previous = self::outline();
+    set_outline(Some(color));
+    let result = f();
+    set_outline(previous);
+    result
+}
+
+/// How wide outlines are, in pixels. Defaults to 3, and goes up to 16.
+pub fn set_outline_thickness(thickness: f32) {
+    get_state().outline_thickness = thickness.clamp(0.0, MAX_THICKNESS);
+}
+
+pub fn outline_thickness() -> f32 {
+    get_state().outline_thickness
+}
+
+/// The shader searches every pixel this far around, so it's kept small.
+const MAX_THICKNESS: f32 = 16.0;
+
+/// Draws the ring around everything in `mask` onto `target`.
+pub(crate) fn draw_outlines<T: Surface>(target: &mut T, mask: &Texture2d, screen_size: Vec2) {
+    static OUTLINE_PROGRAM: std::sync::OnceLock<ProgramRef> = std::sync::OnceLock::new();
+    let Some(program) = cached_program(&OUTLINE_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, OUTLINE_FRAGMENT_SHADER)
+    })
+    .or_report() else {
+        return;
+    };
+
+    let uniforms = uniform! {
+        mask: mask.sampled()
+            .minify_filter(glium::uniforms::MinifySamplerFilter::Nearest)
+            .magnify_filter(glium::uniforms::MagnifySamplerFilter::Nearest),
+        thickness: outline_thickness(),
+        screen_size: [screen_size.x, screen_size.y],
+    };
+    render_fullscreen_quad(target, program.get(), &uniforms).or_report();
+}
+
+const OUTLINE_FRAGMENT_SHADER: &str = r#"
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+uniform sampler2D mask;
+uniform float thickness;
+uniform vec2 screen_size;
+
+void main() {
+    // only outside of the shapes
+    if (texture(mask, v_tex_coords).a > 0.0) {
+        discard;
+    }
+
+    int radius = int(ceil(thickness));
+    vec2 pixel = 1.0 / screen_size;
+    float closest = thickness * thickness + 1.0;
+    vec4 found = vec4(0.0);
+    for (int y = -16; y <= 16; y++) {
+        for (int x = -16; x <= 16; x++) {
+            if (abs(x) > radius || abs(y) > radius) {
+                continue;
+            }
+            float distance = float(x * x + y * y);
+            if (distance > thickness * thickness || distance >= closest) {
+                continue;
+            }
+            vec4 neighbour = texture(mask, v_tex_coords + vec2(x, y) * pixel);
+            if (neighbour.a > 0.0) {
+                closest = distance;
+                found = neighbour;
+            }
+        }
+    }
+
+    if (found.a <= 0.0) {
+        discard;
+    }
+    color = found;
+}
+"#;
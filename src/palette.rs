@@ -0,0 +1,197 @@
+//! Indexed color, for pixel art with a fixed set of colors.
+//!
+//! A [`Palette`] is a list of colors, kept on the GPU as a texture. Keep the whole screen to
+//! it with [`palette_screen`] or [`PostProcessingEffect::Palette`], or give sprites
+//! different colors with a [`PaletteSwap`], like alternate outfits and enemy variants.
+
+use anyhow::ensure;
+use bevy_math::{UVec2, Vec3};
+use glium::{Surface, framebuffer::SimpleFrameBuffer, uniform};
+
+use crate::{
+    api::add_post_processing_effect,
+    color::{Color, u8::Pixel},
+    get_state,
+    image::Image,
+    post_processing::{
+        POSTPROCESS_VERTEX_SHADER, PostProcessingEffect, render_fullscreen_quad_with_blend,
+    },
+    programs::{ProgramRef, cached_program, load_program},
+    textures::{EngineTexture, TextureRef},
+    utils::EngineCreate,
+};
+
+/// The most colors a palette can have, so indices fit in one channel of a texture.
+pub const MAX_PALETTE_COLORS: usize = 256;
+
+/// A 4x4 ordered dithering pattern, each from 0 to 15.
+const BAYER_4X4: [f32; 16] = [
+    0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0,
+];
+
+pub struct Palette {
+    colors: Vec<Color>,
+    /// One pixel per color, in a single row.
+    texture: TextureRef,
+}
+
+impl Palette {
+    /// Up to [`MAX_PALETTE_COLORS`] colors. Their alpha is ignored.
+    pub fn new(colors: Vec<Color>) -> anyhow::Result<Self> {
+        ensure!(!colors.is_empty(), "a palette needs at least one color");
+        ensure!(
+            colors.len() <= MAX_PALETTE_COLORS,
+            "a palette can have up to {MAX_PALETTE_COLORS} colors, not {}",
+            colors.len()
+        );
+
+        let pixels = colors
+            .iter()
+            .map(|color| Pixel::from(color.with_alpha(1.0)))
+            .collect();
+        let texture = Image::new(colors.len(), 1, pixels).to_texture()?;
+        Ok(Self { colors, texture })
+    }
+
+    /// Every pixel of `image`, left to right then top to bottom, like a palette file from an
+    /// art program.
+    pub fn from_image(image: &Image) -> anyhow::Result<Self> {
+        let colors = image
+            .iter()
+            .map(|(_, _, pixel)| {
+                let [r, g, b, _] = pixel.raw();
+                Color::from_rgba_u8(r, g, b, 255)
+            })
+            .collect();
+        Self::new(colors)
+    }
+
+    pub fn colors(&self) -> &[Color] {
+        &self.colors
+    }
+
+    /// For a [`PostProcessingEffect::Palette`].
+    pub fn texture(&self) -> TextureRef {
+        self.texture
+    }
+
+    /// The index of the closest color to `color`, the same as the shader.
+    pub fn nearest_index(&self, color: Color) -> usize {
+        nearest_index(&self.colors, color)
+    }
+
+    /// The closest color to `color`, keeping its alpha.
+    pub fn nearest(&self, color: Color) -> Color {
+        self.colors[self.nearest_index(color)].with_alpha(color.a)
+    }
+
+    /// Like [`nearest`](Self::nearest), but nudged by the dithering pattern at `pixel` first,
+    /// the same as the shader.
+    pub fn nearest_dithered(&self, color: Color, pixel: UVec2, dither: f32) -> Color {
+        self.nearest(dithered(color, pixel, dither, self.colors.len()))
+    }
+}
+
+pub(crate) fn nearest_index(colors: &[Color], color: Color) -> usize {
+    let target = Vec3::new(color.r, color.g, color.b);
+    colors
+        .iter()
+        .map(|entry| Vec3::new(entry.r, entry.g, entry.b).distance_squared(target))
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map_or(0, |(index, _)| index)
+}
+
+/// `color` nudged lighter or darker by the dithering pattern at `pixel`, by up to about half the
+/// distance between colors of a palette with `count` of them spread evenly.
+pub(crate) fn dithered(color: Color, pixel: UVec2, dither: f32, count: usize) -> Color {
+    let spacing = 1.0 / (count as f32).cbrt();
+    let nudge = dither_offset(pixel) * dither * spacing;
+    Color::from_rgba(color.r + nudge, color.g + nudge, color.b + nudge, color.a)
+}
+
+/// From -0.5 to 0.5, averaging to 0 over each 4x4 block of pixels.
+pub(crate) fn dither_offset(pixel: UVec2) -> f32 {
+    let index = (pixel.y % 4) * 4 + pixel.x % 4;
+    (BAYER_4X4[index as usize] + 0.5) / 16.0 - 0.5
+}
+
+/// A sprite drawn in indexed color. The red channel of each pixel of the index texture picks a
+/// color from the palette, with 0 for the first, 1 for the second and so on, out of 255. Its
+/// alpha is kept, so transparent parts stay transparent.
+///
+/// The colored sprite is drawn once on the GPU, when it's made and when the palette changes,
+/// and [`texture`](Self::texture) is drawn like any other texture.
+pub struct PaletteSwap {
+    indices: TextureRef,
+    texture: TextureRef,
+}
+
+impl PaletteSwap {
+    pub fn new(indices: TextureRef, palette: &Palette) -> anyhow::Result<Self> {
+        let size = indices.dimensions();
+        let texture = EngineTexture::empty(size.x, size.y)?.create();
+        let mut swap = Self { indices, texture };
+        swap.set_palette(palette)?;
+        Ok(swap)
+    }
+
+    /// Colors the sprite again with a different palette. Indices past the end of it use its
+    /// last color.
+    pub fn set_palette(&mut self, palette: &Palette) -> anyhow::Result<()> {
+        static PALETTE_SWAP_PROGRAM: std::sync::OnceLock<ProgramRef> = std::sync::OnceLock::new();
+        let program = cached_program(&PALETTE_SWAP_PROGRAM, || {
+            load_program(POSTPROCESS_VERTEX_SHADER, PALETTE_SWAP_FRAGMENT_SHADER)
+        })?;
+
+        let display = get_state().display();
+        let texture = self.texture.get();
+        let mut target = SimpleFrameBuffer::new(display, &texture.gl_texture)?;
+        target.clear_color(0.0, 0.0, 0.0, 0.0);
+
+        let uniforms = uniform! {
+            indices: self.indices.get().gl_texture.sampled(),
+            palette: palette.texture.get().gl_texture.sampled(),
+            palette_size: palette.colors.len() as i32,
+        };
+        render_fullscreen_quad_with_blend(
+            &mut target,
+            program.get(),
+            &uniforms,
+            glium::Blend::default(),
+        )
+    }
+
+    /// The colored sprite.
+    pub fn texture(&self) -> TextureRef {
+        self.texture
+    }
+
+    pub fn indices(&self) -> TextureRef {
+        self.indices
+    }
+}
+
+/// Keeps every color on screen to `palette` for this frame. `dither` blends between
+/// neighbouring colors with a fine checker pattern instead of flat bands, from 0 for none to 1.
+pub fn palette_screen(palette: &Palette, dither: f32) {
+    add_post_processing_effect(PostProcessingEffect::Palette {
+        palette: palette.texture,
+        dither,
+    });
+}
+
+const PALETTE_SWAP_FRAGMENT_SHADER: &str = r#"
+#version 140
+out vec4 color;
+uniform sampler2D indices;
+uniform sampler2D palette;
+uniform int palette_size;
+
+void main() {
+    // the same size as the index texture, so each pixel lines up with one of its texels
+    vec4 index_color = texelFetch(indices, ivec2(gl_FragCoord.xy), 0);
+    int index = min(int(index_color.r * 255.0 + 0.5), palette_size - 1);
+    color = vec4(texelFetch(palette, ivec2(index, 0), 0).rgb, index_color.a);
+}
+"#;
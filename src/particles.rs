@@ -0,0 +1,124 @@
+//! Settings shared by every particle system: how long particles live, how fast they start,
+//! and how their size and color change over their life.
+
+use rand::Rng;
+
+use crate::{
+    animation::{Animatable, Ease, EasingFunction},
+    color::Color,
+};
+
+/// A value that changes over a particle's life, from 0 (just spawned) to 1 (about to die).
+#[derive(Clone, Debug)]
+pub struct Curve<T> {
+    /// Sorted by time. Each key's ease is used on the way into it from the one before.
+    keys: Vec<(f32, T, Ease)>,
+}
+
+impl<T: Animatable + Copy> Curve<T> {
+    pub fn constant(value: T) -> Self {
+        Self {
+            keys: vec![(0.0, value, Ease::Linear)],
+        }
+    }
+
+    pub fn linear(start: T, end: T) -> Self {
+        Self::eased(start, end, Ease::Linear)
+    }
+
+    pub fn eased(start: T, end: T, ease: Ease) -> Self {
+        Self {
+            keys: vec![(0.0, start, Ease::Linear), (1.0, end, ease)],
+        }
+    }
+
+    /// Adds a point the curve passes through at `time`, eased into from the point before it.
+    pub fn with_key(mut self, time: f32, value: T, ease: Ease) -> Self {
+        let time = time.clamp(0.0, 1.0);
+        let index = self.keys.partition_point(|(t, ..)| *t <= time);
+        self.keys.insert(index, (time, value, ease));
+        self
+    }
+
+    pub fn sample(&self, t: f32) -> T {
+        let index = self.keys.partition_point(|(time, ..)| *time < t);
+        if index == 0 {
+            return self.keys[0].1;
+        }
+        let Some(&(end_time, end, ease)) = self.keys.get(index) else {
+            return self.keys[self.keys.len() - 1].1;
+        };
+
+        let (start_time, start, _) = self.keys[index - 1];
+        let progress = (t - start_time) / (end_time - start_time);
+        T::lerp(start, end, ease.progress(progress))
+    }
+}
+
+/// How particles behave once they've been spawned.
+#[derive(Clone, Debug)]
+pub struct ParticleConfig {
+    /// Seconds each particle lives for, picked between the two.
+    pub lifetime: (f32, f32),
+    /// Starting speed, picked between the two.
+    pub speed: (f32, f32),
+    /// Spin in radians per second, picked between the two.
+    pub spin: (f32, f32),
+    /// How much of its velocity a particle loses each second, from 0 to 1.
+    pub drag: f32,
+    pub size: Curve<f32>,
+    pub color: Curve<Color>,
+}
+
+impl Default for ParticleConfig {
+    fn default() -> Self {
+        Self {
+            lifetime: (1.0, 2.0),
+            speed: (1.0, 2.0),
+            spin: (0.0, 0.0),
+            drag: 0.0,
+            size: Curve::linear(0.2, 0.0),
+            color: Curve::linear(Color::WHITE, Color::WHITE.with_alpha(0.0)),
+        }
+    }
+}
+
+impl ParticleConfig {
+    pub fn with_lifetime(mut self, min: f32, max: f32) -> Self {
+        self.lifetime = (min, max);
+        self
+    }
+
+    pub fn with_speed(mut self, min: f32, max: f32) -> Self {
+        self.speed = (min, max);
+        self
+    }
+
+    pub fn with_spin(mut self, min: f32, max: f32) -> Self {
+        self.spin = (min, max);
+        self
+    }
+
+    pub fn with_drag(mut self, drag: f32) -> Self {
+        self.drag = drag;
+        self
+    }
+
+    pub fn with_size(mut self, size: Curve<f32>) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_color(mut self, color: Curve<Color>) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+pub(crate) fn random_between(rng: &mut impl Rng, (min, max): (f32, f32)) -> f32 {
+    if max > min {
+        rng.random_range(min..=max)
+    } else {
+        min
+    }
+}
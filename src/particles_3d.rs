@@ -0,0 +1,263 @@
+//! Particles in 3D. They're simulated on the CPU, and drawn as camera facing quads in one
+//! instanced draw call per emitter, after the rest of the 3D scene.
+
+use std::f32::consts::TAU;
+
+use bevy_math::{Quat, Vec3};
+use glium::implement_vertex;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{
+    draw_queue_3d::ObjectToDraw,
+    get_state,
+    materials::BlendMode,
+    particles::{ParticleConfig, random_between},
+    textures::TextureRef,
+};
+
+/// Where new particles start, and which way they go.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmissionShape3D {
+    /// From the emitter's position, in every direction.
+    Point,
+    /// From anywhere inside the sphere, moving away from its center.
+    Sphere { radius: f32 },
+    /// From a disc of `radius` facing `direction`, moving within `angle` radians of it.
+    Cone {
+        direction: Vec3,
+        angle: f32,
+        radius: f32,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Particle3D {
+    position: Vec3,
+    velocity: Vec3,
+    rotation: f32,
+    spin: f32,
+    age: f32,
+    lifetime: f32,
+}
+
+implement_vertex!(ParticleInstance3D, center, size, rotation, color);
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct ParticleInstance3D {
+    pub center: [f32; 3],
+    pub size: f32,
+    pub rotation: f32,
+    pub color: [f32; 4],
+}
+
+/// Everything one emitter draws in a frame.
+pub struct ParticleBatch3D {
+    pub(crate) instances: Vec<ParticleInstance3D>,
+    pub(crate) texture: Option<TextureRef>,
+    pub(crate) blend_mode: BlendMode,
+}
+
+pub struct ParticleEmitter3D {
+    pub position: Vec3,
+    pub shape: EmissionShape3D,
+    /// New particles per second while [`emitting`](Self::emitting).
+    pub rate: f32,
+    pub config: ParticleConfig,
+    /// Added to every particle's velocity each second.
+    pub gravity: Vec3,
+    /// `Alpha` particles are sorted back to front, `Additive` ones don't need to be.
+    pub blend_mode: BlendMode,
+    /// Drawn tinted by the color curve. Without one, particles are soft round blobs.
+    pub texture: Option<TextureRef>,
+    pub emitting: bool,
+    /// Particles past this many aren't spawned.
+    pub max_particles: usize,
+    particles: Vec<Particle3D>,
+    spawn_timer: f32,
+    rng: ChaCha8Rng,
+}
+
+impl ParticleEmitter3D {
+    pub fn new(shape: EmissionShape3D) -> Self {
+        Self {
+            position: Vec3::ZERO,
+            shape,
+            rate: 20.0,
+            config: ParticleConfig::default(),
+            gravity: Vec3::ZERO,
+            blend_mode: BlendMode::Alpha,
+            texture: None,
+            emitting: true,
+            max_particles: 10_000,
+            particles: Vec::new(),
+            spawn_timer: 0.0,
+            rng: ChaCha8Rng::from_rng(&mut rand::rng()),
+        }
+    }
+
+    pub fn with_position(mut self, position: Vec3) -> Self {
+        self.position = position;
+        self
+    }
+
+    pub fn with_rate(mut self, rate: f32) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    pub fn with_config(mut self, config: ParticleConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn with_gravity(mut self, gravity: Vec3) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn with_texture(mut self, texture: TextureRef) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    pub fn with_max_particles(mut self, max_particles: usize) -> Self {
+        self.max_particles = max_particles;
+        self
+    }
+
+    /// For the same particles every run.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+        self
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// Whether it's stopped emitting and every particle has died.
+    pub fn is_finished(&self) -> bool {
+        !self.emitting && self.particles.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    /// Spawns `count` particles at once, whether or not it's emitting.
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            self.spawn();
+        }
+    }
+
+    /// Moves the particles along, removes dead ones and spawns new ones. Usually called with
+    /// [`delta_time`](crate::prelude::delta_time).
+    pub fn update(&mut self, delta_time: f32) {
+        let drag = (1.0 - self.config.drag.clamp(0.0, 1.0)).powf(delta_time);
+        for particle in &mut self.particles {
+            particle.age += delta_time;
+            particle.velocity += self.gravity * delta_time;
+            particle.velocity *= drag;
+            particle.position += particle.velocity * delta_time;
+            particle.rotation += particle.spin * delta_time;
+        }
+        self.particles
+            .retain(|particle| particle.age < particle.lifetime);
+
+        if self.emitting && self.rate > 0.0 {
+            self.spawn_timer += delta_time * self.rate;
+            while self.spawn_timer >= 1.0 {
+                self.spawn_timer -= 1.0;
+                self.spawn();
+            }
+        } else {
+            self.spawn_timer = 0.0;
+        }
+    }
+
+    fn spawn(&mut self) {
+        if self.particles.len() >= self.max_particles {
+            return;
+        }
+
+        let rng = &mut self.rng;
+        let (offset, direction) = match self.shape {
+            EmissionShape3D::Point => (Vec3::ZERO, random_direction(rng)),
+            EmissionShape3D::Sphere { radius } => {
+                let direction = random_direction(rng);
+                // cube root, so they're spread evenly through the volume
+                let distance = radius * rng.random::<f32>().cbrt();
+                (direction * distance, direction)
+            }
+            EmissionShape3D::Cone {
+                direction,
+                angle,
+                radius,
+            } => {
+                let rotation = Quat::from_rotation_arc(Vec3::Z, direction.normalize_or(Vec3::Y));
+                let phi = rng.random::<f32>() * TAU;
+                let distance = radius * rng.random::<f32>().sqrt();
+                let offset = Vec3::new(phi.cos(), phi.sin(), 0.0) * distance;
+
+                let cos_theta = rng.random_range(angle.clamp(0.0, TAU / 2.0).cos()..=1.0);
+                let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                let phi = rng.random::<f32>() * TAU;
+                let local = Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+                (rotation * offset, rotation * local)
+            }
+        };
+
+        let config = &self.config;
+        let speed = random_between(rng, config.speed);
+        self.particles.push(Particle3D {
+            position: self.position + offset,
+            velocity: direction * speed,
+            rotation: rng.random::<f32>() * TAU,
+            spin: random_between(rng, config.spin),
+            age: 0.0,
+            lifetime: random_between(rng, config.lifetime).max(f32::EPSILON),
+        });
+    }
+
+    pub(crate) fn instances(&self) -> Vec<ParticleInstance3D> {
+        self.particles
+            .iter()
+            .map(|particle| {
+                let t = particle.age / particle.lifetime;
+                ParticleInstance3D {
+                    center: particle.position.into(),
+                    size: self.config.size.sample(t),
+                    rotation: particle.rotation,
+                    color: self.config.color.sample(t).for_gpu(),
+                }
+            })
+            .collect()
+    }
+
+    pub fn draw(&self) {
+        if self.particles.is_empty() {
+            return;
+        }
+
+        get_state()
+            .draw_queue_3d()
+            .push(ObjectToDraw::Particles(ParticleBatch3D {
+                instances: self.instances(),
+                texture: self.texture,
+                blend_mode: self.blend_mode,
+            }));
+    }
+}
+
+fn random_direction(rng: &mut impl Rng) -> Vec3 {
+    let z = rng.random_range(-1.0..=1.0f32);
+    let phi = rng.random::<f32>() * TAU;
+    let r = (1.0 - z * z).sqrt();
+    Vec3::new(r * phi.cos(), r * phi.sin(), z)
+}
@@ -0,0 +1,136 @@
+//! A* pathfinding, over grids of tiles ([`NavGrid`]) or over the open space around colliders
+//! ([`NavMesh`]). Both give back paths with the corners cut wherever there's room, so they can
+//! be followed in straight lines.
+
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use bevy_math::Vec2;
+
+use crate::{
+    color::Color,
+    gizmos::{debug_draw_circle_world, debug_draw_line_world},
+};
+
+mod grid;
+mod navmesh;
+
+pub use grid::{NavGrid, Neighborhood};
+pub use navmesh::NavMesh;
+
+/// What a search found, and how hard it had to look.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathSearch {
+    /// From the start to the goal, smoothed. `None` if there's no way there.
+    pub path: Option<Vec<Vec2>>,
+    /// The middle of every cell or polygon that was explored, in the order they were.
+    pub explored: Vec<Vec2>,
+}
+
+impl PathSearch {
+    /// Shows what was explored as dots `size` across, and the path on top. For working out why
+    /// a search is slow or goes the wrong way.
+    pub fn debug_draw(&self, size: f32) {
+        for (i, point) in self.explored.iter().enumerate() {
+            // later ones are warmer, to show which way the search spread
+            let t = i as f32 / self.explored.len().max(1) as f32;
+            let color =
+                Color::from_vec4(Color::SKY_400.to_vec4().lerp(Color::ROSE_400.to_vec4(), t));
+            debug_draw_circle_world(*point, size * 0.5, color);
+        }
+
+        if let Some(path) = &self.path {
+            for pair in path.windows(2) {
+                debug_draw_line_world(pair[0], pair[1], Color::YELLOW_400);
+            }
+        }
+    }
+}
+
+/// Somewhere waiting to be explored, and the best guess at the cost of a path through it.
+#[derive(Debug, Clone, Copy)]
+struct Open {
+    estimate: f32,
+    node: usize,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    // backwards, so the heap gives back the cheapest first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+/// A* over nodes numbered `0..node_count`. `neighbours` fills in where a node leads and what
+/// it costs to get there, and `heuristic` must never guess higher than the real cost to the
+/// goal, or the path might not be the shortest.
+///
+/// Returns the nodes on the path, start and goal included, and every node explored.
+fn astar(
+    node_count: usize,
+    start: usize,
+    goal: usize,
+    mut neighbours: impl FnMut(usize, &mut Vec<(usize, f32)>),
+    heuristic: impl Fn(usize) -> f32,
+) -> (Option<Vec<usize>>, Vec<usize>) {
+    let mut cost = vec![f32::INFINITY; node_count];
+    let mut came_from = vec![usize::MAX; node_count];
+    let mut closed = vec![false; node_count];
+    let mut open = BinaryHeap::new();
+    let mut explored = vec![];
+    let mut next = vec![];
+
+    cost[start] = 0.0;
+    open.push(Open {
+        estimate: heuristic(start),
+        node: start,
+    });
+
+    while let Some(Open { node, .. }) = open.pop() {
+        if closed[node] {
+            continue;
+        }
+        closed[node] = true;
+        explored.push(node);
+
+        if node == goal {
+            let mut path = vec![goal];
+            while let Some(&previous) = path.last().map(|node| &came_from[*node])
+                && previous != usize::MAX
+            {
+                path.push(previous);
+            }
+            path.reverse();
+            return (Some(path), explored);
+        }
+
+        next.clear();
+        neighbours(node, &mut next);
+        for &(neighbour, step) in &next {
+            let new_cost = cost[node] + step;
+            if new_cost < cost[neighbour] {
+                cost[neighbour] = new_cost;
+                came_from[neighbour] = node;
+                open.push(Open {
+                    estimate: new_cost + heuristic(neighbour),
+                    node: neighbour,
+                });
+            }
+        }
+    }
+
+    (None, explored)
+}
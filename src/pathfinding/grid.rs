@@ -0,0 +1,318 @@
+use std::f32::consts::SQRT_2;
+
+use bevy_math::{IVec2, UVec2, Vec2};
+
+use super::{PathSearch, astar};
+
+/// Which tiles count as next to each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Neighborhood {
+    /// Up, down, left and right.
+    Four,
+    /// Diagonals too, but never cutting the corner of a blocked tile.
+    #[default]
+    Eight,
+}
+
+/// A grid of tiles to find paths across, like a tilemap's. Each tile costs something to walk
+/// through, or is blocked. Tile (0, 0) starts at `origin`, and the rest go along +x and +y.
+#[derive(Debug, Clone)]
+pub struct NavGrid {
+    size: UVec2,
+    cell_size: f32,
+    origin: Vec2,
+    /// Row by row. `None` is blocked.
+    costs: Vec<Option<f32>>,
+    neighborhood: Neighborhood,
+}
+
+impl NavGrid {
+    /// Every tile walkable, costing 1.
+    pub fn new(size: UVec2, cell_size: f32) -> Self {
+        Self::from_fn(size, cell_size, |_| Some(1.0))
+    }
+
+    /// Asks `cost` about every tile, see [`set_cost`](Self::set_cost).
+    pub fn from_fn(
+        size: UVec2,
+        cell_size: f32,
+        mut cost: impl FnMut(UVec2) -> Option<f32>,
+    ) -> Self {
+        assert!(cell_size > 0.0, "NavGrid cells need a positive size");
+
+        let costs = (0..size.y)
+            .flat_map(|y| (0..size.x).map(move |x| UVec2::new(x, y)))
+            .map(|cell| cost(cell).inspect(|cost| check_cost(*cost)))
+            .collect();
+
+        Self {
+            size,
+            cell_size,
+            origin: Vec2::ZERO,
+            costs,
+            neighborhood: Neighborhood::default(),
+        }
+    }
+
+    pub fn with_origin(mut self, origin: Vec2) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn with_neighborhood(mut self, neighborhood: Neighborhood) -> Self {
+        self.neighborhood = neighborhood;
+        self
+    }
+
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    /// `None` if it's blocked or off the grid.
+    pub fn cost(&self, cell: UVec2) -> Option<f32> {
+        self.cost_at(cell.as_ivec2())
+    }
+
+    /// How much walking through `cell` costs compared to other tiles, so 2 is avoided unless
+    /// going around is more than twice as far. `None` blocks it. Costs have to be positive.
+    pub fn set_cost(&mut self, cell: UVec2, cost: Option<f32>) {
+        if let Some(cost) = cost {
+            check_cost(cost);
+        }
+        if let Some(index) = self.index(cell.as_ivec2()) {
+            self.costs[index] = cost;
+        }
+    }
+
+    pub fn set_blocked(&mut self, cell: UVec2) {
+        self.set_cost(cell, None);
+    }
+
+    pub fn is_walkable(&self, cell: UVec2) -> bool {
+        self.cost(cell).is_some()
+    }
+
+    /// The tile `position` is in, if it's on the grid.
+    pub fn cell_at(&self, position: Vec2) -> Option<UVec2> {
+        let cell = self.to_grid(position).floor().as_ivec2();
+        self.index(cell)?;
+        Some(cell.as_uvec2())
+    }
+
+    pub fn cell_center(&self, cell: UVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + 0.5) * self.cell_size
+    }
+
+    /// The cheapest way from `start` to `goal`, going straight wherever there's a clear line,
+    /// or `None` if either is blocked or off the grid, or there's no way through.
+    pub fn find_path(&self, start: Vec2, goal: Vec2) -> Option<Vec<Vec2>> {
+        self.search(start, goal).path
+    }
+
+    /// Like [`find_path`](Self::find_path), but also says which tiles were explored.
+    pub fn search(&self, start: Vec2, goal: Vec2) -> PathSearch {
+        let (Some(start_cell), Some(goal_cell)) = (self.cell_at(start), self.cell_at(goal)) else {
+            return PathSearch::default();
+        };
+        if !self.is_walkable(start_cell) || !self.is_walkable(goal_cell) {
+            return PathSearch::default();
+        }
+
+        let width = self.size.x as i32;
+        let to_node = |cell: IVec2| (cell.y * width + cell.x) as usize;
+        let to_cell = |node: usize| IVec2::new(node as i32 % width, node as i32 / width);
+
+        // the heuristic has to assume the rest of the way is as cheap as it could be
+        let cheapest = self
+            .costs
+            .iter()
+            .flatten()
+            .copied()
+            .fold(f32::INFINITY, f32::min);
+        let goal_cell = goal_cell.as_ivec2();
+        let heuristic = |node| {
+            let distance = (to_cell(node) - goal_cell).abs().as_vec2();
+            let steps = match self.neighborhood {
+                Neighborhood::Four => distance.x + distance.y,
+                Neighborhood::Eight => {
+                    distance.max_element() + (SQRT_2 - 1.0) * distance.min_element()
+                }
+            };
+            steps * cheapest
+        };
+
+        let neighbours = |node, next: &mut Vec<(usize, f32)>| {
+            let cell = to_cell(node);
+            for offset in NEIGHBOURS {
+                let diagonal = offset.x != 0 && offset.y != 0;
+                if diagonal
+                    && (self.neighborhood == Neighborhood::Four
+                        || self.cost_at(cell + IVec2::new(offset.x, 0)).is_none()
+                        || self.cost_at(cell + IVec2::new(0, offset.y)).is_none())
+                {
+                    continue;
+                }
+
+                if let Some(cost) = self.cost_at(cell + offset) {
+                    let distance = if diagonal { SQRT_2 } else { 1.0 };
+                    next.push((to_node(cell + offset), distance * cost));
+                }
+            }
+        };
+
+        let (path, explored) = astar(
+            self.costs.len(),
+            to_node(start_cell.as_ivec2()),
+            to_node(goal_cell),
+            neighbours,
+            heuristic,
+        );
+        let center = |node| self.cell_center(to_cell(node).as_uvec2());
+
+        PathSearch {
+            path: path.map(|nodes| {
+                let mut points: Vec<Vec2> = nodes.into_iter().map(center).collect();
+                points[0] = start;
+                *points.last_mut().unwrap() = goal;
+                self.smooth(&points)
+            }),
+            explored: explored.into_iter().map(center).collect(),
+        }
+    }
+
+    /// Skips every point that can be walked past in a straight line, without crossing blocked
+    /// tiles or ones dearer than the ones on the way round.
+    fn smooth(&self, points: &[Vec2]) -> Vec<Vec2> {
+        let mut smoothed = vec![points[0]];
+        let mut anchor = 0;
+
+        while anchor < points.len() - 1 {
+            let mut next = anchor + 1;
+            for candidate in anchor + 2..points.len() {
+                if !self.can_shortcut(&points[anchor..=candidate]) {
+                    break;
+                }
+                next = candidate;
+            }
+            smoothed.push(points[next]);
+            anchor = next;
+        }
+
+        smoothed
+    }
+
+    fn can_shortcut(&self, points: &[Vec2]) -> bool {
+        let most_expensive = points
+            .iter()
+            .filter_map(|point| self.cost_at(self.to_grid(*point).floor().as_ivec2()))
+            .fold(0.0, f32::max);
+
+        let start = self.to_grid(points[0]);
+        let end = self.to_grid(*points.last().unwrap());
+        cells_along(start, end, |cell| {
+            self.cost_at(cell)
+                .is_some_and(|cost| cost <= most_expensive)
+        })
+    }
+
+    fn to_grid(&self, position: Vec2) -> Vec2 {
+        (position - self.origin) / self.cell_size
+    }
+
+    fn index(&self, cell: IVec2) -> Option<usize> {
+        let inside = cell.x >= 0
+            && cell.y >= 0
+            && (cell.x as u32) < self.size.x
+            && (cell.y as u32) < self.size.y;
+        inside.then(|| cell.y as usize * self.size.x as usize + cell.x as usize)
+    }
+
+    fn cost_at(&self, cell: IVec2) -> Option<f32> {
+        self.costs[self.index(cell)?]
+    }
+}
+
+const NEIGHBOURS: [IVec2; 8] = [
+    IVec2::new(1, 0),
+    IVec2::new(-1, 0),
+    IVec2::new(0, 1),
+    IVec2::new(0, -1),
+    IVec2::new(1, 1),
+    IVec2::new(1, -1),
+    IVec2::new(-1, 1),
+    IVec2::new(-1, -1),
+];
+
+fn check_cost(cost: f32) {
+    assert!(cost > 0.0, "NavGrid costs have to be positive, got {cost}");
+}
+
+/// Walks every cell the line from `start` to `end` passes through, in grid units, until
+/// `visit` returns `false`. Returns whether it got to the end.
+fn cells_along(start: Vec2, end: Vec2, mut visit: impl FnMut(IVec2) -> bool) -> bool {
+    let mut cell = start.floor().as_ivec2();
+    let end_cell = end.floor().as_ivec2();
+    let delta = end - start;
+
+    let step = IVec2::new(sign(delta.x), sign(delta.y));
+    let t_delta = Vec2::new(1.0 / delta.x.abs(), 1.0 / delta.y.abs());
+    let boundary = |cell: i32, step: i32| if step > 0 { cell + 1 } else { cell } as f32;
+    let mut t_max = Vec2::new(
+        if step.x != 0 {
+            (boundary(cell.x, step.x) - start.x) / delta.x
+        } else {
+            f32::INFINITY
+        },
+        if step.y != 0 {
+            (boundary(cell.y, step.y) - start.y) / delta.y
+        } else {
+            f32::INFINITY
+        },
+    );
+
+    if !visit(cell) {
+        return false;
+    }
+    // rounding can make the walk miss the end cell by a hair, so it can't go on forever
+    let max_steps = (end_cell - cell).abs().element_sum() + 1;
+    for _ in 0..max_steps {
+        if cell == end_cell {
+            break;
+        }
+
+        if t_max.x < t_max.y {
+            cell.x += step.x;
+            t_max.x += t_delta.x;
+        } else if t_max.y < t_max.x {
+            cell.y += step.y;
+            t_max.y += t_delta.y;
+        } else {
+            // exactly through a corner, so both tiles beside it have to be clear too
+            if !visit(cell + IVec2::new(step.x, 0)) || !visit(cell + IVec2::new(0, step.y)) {
+                return false;
+            }
+            cell += step;
+            t_max += t_delta;
+        }
+
+        if !visit(cell) {
+            return false;
+        }
+    }
+
+    true
+}
+
+fn sign(n: f32) -> i32 {
+    if n > 0.0 {
+        1
+    } else if n < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
@@ -0,0 +1,350 @@
+use std::{collections::HashMap, f32::consts::PI};
+
+use bevy_math::Vec2;
+use lyon::tessellation::{
+    BuffersBuilder, FillOptions, FillTessellator, FillVertex, VertexBuffers, math::point,
+    path::Path,
+};
+use rapier2d::prelude::{Collider, EventHandler, PhysicsHooks};
+
+use super::{PathSearch, astar};
+use crate::{
+    collisions::{
+        AABB2D, Polygon, boolean::BooleanOps, boolean::PolygonWithHoles, closest_point_on_segment,
+    },
+    color::Color,
+    error::OrReport,
+    gizmos::debug_draw_line_world,
+    physics_world::World,
+};
+
+/// How many sides round colliders get when they're cut out of the mesh.
+const CIRCLE_SEGMENTS: usize = 16;
+
+/// The open space agents can walk around in, split into triangles. Paths go from triangle to
+/// triangle, then get pulled tight around the corners.
+#[derive(Debug, Clone, Default)]
+pub struct NavMesh {
+    vertices: Vec<Vec2>,
+    /// Corners go counter-clockwise.
+    triangles: Vec<[u32; 3]>,
+    /// The triangle on the other side of each edge, where edge `i` goes from corner `i` to
+    /// corner `i + 1`.
+    neighbours: Vec<[Option<u32>; 3]>,
+}
+
+impl NavMesh {
+    /// A mesh covering `areas`, like what's left after cutting obstacles out of the level with
+    /// [`BooleanOps`].
+    pub fn from_walkable(areas: &[PolygonWithHoles]) -> Self {
+        let mut builder = Path::builder();
+        for contour in areas.iter().flat_map(|area| area.contours()) {
+            let Some((first, rest)) = contour.split_first() else {
+                continue;
+            };
+            builder.begin(point(first[0], first[1]));
+            for vertex in rest {
+                builder.line_to(point(vertex[0], vertex[1]));
+            }
+            builder.end(true);
+        }
+        let path = builder.build();
+
+        let mut buffers = VertexBuffers::<Vec2, u32>::new();
+        let result = FillTessellator::new().tessellate_path(
+            &path,
+            &FillOptions::even_odd(),
+            &mut BuffersBuilder::new(&mut buffers, |vertex: FillVertex| {
+                Vec2::new(vertex.position().x, vertex.position().y)
+            }),
+        );
+        if result.or_report().is_none() {
+            return Self::default();
+        }
+
+        Self::from_triangles(&buffers.vertices, &buffers.indices)
+    }
+
+    /// The open space in `bounds` around the colliders in `world` that never move. Sensors and
+    /// colliders on dynamic or kinematic bodies are left out, since they'd be somewhere else by
+    /// the time anyone got there.
+    ///
+    /// Obstacles are grown by `agent_radius`, so an agent's center can go anywhere on the mesh
+    /// without clipping them. Balls and cuboids keep their shape, anything else is blocked off
+    /// by its bounding box.
+    pub fn from_physics_world<H: PhysicsHooks, E: EventHandler>(
+        world: &World<H, E>,
+        bounds: AABB2D,
+        agent_radius: f32,
+    ) -> Self {
+        let mut walkable = vec![PolygonWithHoles::from(rectangle(bounds))];
+
+        for (_, collider) in world.collider_set.iter() {
+            let moves = collider
+                .parent()
+                .and_then(|parent| world.rigid_body_set.get(parent))
+                .is_some_and(|body| !body.is_fixed());
+            if collider.is_sensor() || moves {
+                continue;
+            }
+
+            // one at a time, since overlapping obstacles would cancel out in a single cut
+            walkable = walkable.difference(&obstacle(collider, agent_radius));
+        }
+
+        Self::from_walkable(&walkable)
+    }
+
+    fn from_triangles(vertices: &[Vec2], indices: &[u32]) -> Self {
+        let mut mesh = Self::default();
+
+        // the tessellator can give the same point more than once, which would hide which
+        // triangles are next to each other
+        let mut merged = HashMap::new();
+        let mut merge = |vertex: Vec2, vertices: &mut Vec<Vec2>| {
+            *merged
+                .entry((vertex.x.to_bits(), vertex.y.to_bits()))
+                .or_insert_with(|| {
+                    vertices.push(vertex);
+                    vertices.len() as u32 - 1
+                })
+        };
+
+        for triangle in indices.chunks_exact(3) {
+            let mut corners =
+                [0, 1, 2].map(|i| merge(vertices[triangle[i] as usize], &mut mesh.vertices));
+            let [a, b, c] = corners.map(|i| mesh.vertices[i as usize]);
+            let twice_area = (b - a).perp_dot(c - a);
+            if twice_area.abs() <= f32::EPSILON {
+                continue;
+            }
+            if twice_area < 0.0 {
+                corners.swap(1, 2);
+            }
+            mesh.triangles.push(corners);
+        }
+
+        let mut edges: HashMap<(u32, u32), (usize, usize)> = HashMap::new();
+        mesh.neighbours = vec![[None; 3]; mesh.triangles.len()];
+        for (triangle, corners) in mesh.triangles.iter().enumerate() {
+            for edge in 0..3 {
+                let (a, b) = (corners[edge], corners[(edge + 1) % 3]);
+                match edges.remove(&(a.min(b), a.max(b))) {
+                    Some((other, other_edge)) => {
+                        mesh.neighbours[triangle][edge] = Some(other as u32);
+                        mesh.neighbours[other][other_edge] = Some(triangle as u32);
+                    }
+                    None => {
+                        edges.insert((a.min(b), a.max(b)), (triangle, edge));
+                    }
+                }
+            }
+        }
+
+        mesh
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.triangles.is_empty()
+    }
+
+    pub fn triangles(&self) -> impl Iterator<Item = [Vec2; 3]> + '_ {
+        (0..self.triangles.len()).map(|triangle| self.corners(triangle))
+    }
+
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        (0..self.triangles.len()).any(|triangle| self.triangle_contains(triangle, point))
+    }
+
+    /// The shortest way from `start` to `goal`, or `None` if they're not connected. Points off
+    /// the mesh are moved to the closest point on it first.
+    pub fn find_path(&self, start: Vec2, goal: Vec2) -> Option<Vec<Vec2>> {
+        self.search(start, goal).path
+    }
+
+    /// Like [`find_path`](Self::find_path), but also says which triangles were explored.
+    pub fn search(&self, start: Vec2, goal: Vec2) -> PathSearch {
+        let (Some((start_triangle, start)), Some((goal_triangle, goal))) =
+            (self.closest_triangle(start), self.closest_triangle(goal))
+        else {
+            return PathSearch::default();
+        };
+
+        // centroids are only a rough stand in for where the path really goes, which the funnel
+        // works out after
+        let neighbours = |triangle: usize, next: &mut Vec<(usize, f32)>| {
+            for neighbour in self.neighbours[triangle].into_iter().flatten() {
+                let neighbour = neighbour as usize;
+                let distance = self.centroid(triangle).distance(self.centroid(neighbour));
+                next.push((neighbour, distance));
+            }
+        };
+        let heuristic = |triangle| self.centroid(triangle).distance(goal);
+
+        let (path, explored) = astar(
+            self.triangles.len(),
+            start_triangle,
+            goal_triangle,
+            neighbours,
+            heuristic,
+        );
+
+        PathSearch {
+            path: path.map(|triangles| self.funnel(&triangles, start, goal)),
+            explored: explored
+                .into_iter()
+                .map(|triangle| self.centroid(triangle))
+                .collect(),
+        }
+    }
+
+    /// Outlines every triangle.
+    pub fn debug_draw(&self, color: Color) {
+        for [a, b, c] in self.triangles() {
+            debug_draw_line_world(a, b, color);
+            debug_draw_line_world(b, c, color);
+            debug_draw_line_world(c, a, color);
+        }
+    }
+
+    fn corners(&self, triangle: usize) -> [Vec2; 3] {
+        self.triangles[triangle].map(|i| self.vertices[i as usize])
+    }
+
+    fn centroid(&self, triangle: usize) -> Vec2 {
+        let [a, b, c] = self.corners(triangle);
+        (a + b + c) / 3.0
+    }
+
+    fn triangle_contains(&self, triangle: usize, point: Vec2) -> bool {
+        let [a, b, c] = self.corners(triangle);
+        [(a, b), (b, c), (c, a)]
+            .into_iter()
+            .all(|(from, to)| (to - from).perp_dot(point - from) >= -1e-4)
+    }
+
+    /// The triangle `point` is in, or the one closest to it and the closest point on it.
+    fn closest_triangle(&self, point: Vec2) -> Option<(usize, Vec2)> {
+        if let Some(triangle) =
+            (0..self.triangles.len()).find(|triangle| self.triangle_contains(*triangle, point))
+        {
+            return Some((triangle, point));
+        }
+
+        (0..self.triangles.len())
+            .flat_map(|triangle| {
+                let [a, b, c] = self.corners(triangle);
+                [(a, b), (b, c), (c, a)]
+                    .map(|(from, to)| (triangle, closest_point_on_segment(point, from, to)))
+            })
+            .min_by(|a, b| {
+                a.1.distance_squared(point)
+                    .total_cmp(&b.1.distance_squared(point))
+            })
+    }
+
+    /// Pulls the path through `triangles` tight, so it only turns at corners it has to go
+    /// around. This is the "simple stupid funnel": a wedge from the last corner is narrowed
+    /// edge by edge, and when one side would cross the other, that side's corner is a turn.
+    fn funnel(&self, triangles: &[usize], start: Vec2, goal: Vec2) -> Vec<Vec2> {
+        let mut portals = vec![(start, start)];
+        for pair in triangles.windows(2) {
+            let edge = self.neighbours[pair[0]]
+                .iter()
+                .position(|neighbour| *neighbour == Some(pair[1] as u32))
+                .unwrap();
+            let corners = self.corners(pair[0]);
+            // going out of a counter-clockwise triangle, an edge's start is on the right
+            portals.push((corners[(edge + 1) % 3], corners[edge]));
+        }
+        portals.push((goal, goal));
+
+        let mut path = vec![start];
+        let (mut apex, mut left, mut right) = (start, start, start);
+        let (mut left_index, mut right_index) = (0, 0);
+
+        let mut i = 1;
+        while i < portals.len() {
+            let (new_left, new_right) = portals[i];
+
+            if (right - apex).perp_dot(new_right - apex) >= 0.0 {
+                if apex == right || (left - apex).perp_dot(new_right - apex) < 0.0 {
+                    right = new_right;
+                    right_index = i;
+                } else {
+                    path.push(left);
+                    apex = left;
+                    (right, right_index) = (left, left_index);
+                    i = left_index + 1;
+                    continue;
+                }
+            }
+
+            if (left - apex).perp_dot(new_left - apex) <= 0.0 {
+                if apex == left || (right - apex).perp_dot(new_left - apex) > 0.0 {
+                    left = new_left;
+                    left_index = i;
+                } else {
+                    path.push(right);
+                    apex = right;
+                    (left, left_index) = (right, right_index);
+                    i = right_index + 1;
+                    continue;
+                }
+            }
+
+            i += 1;
+        }
+
+        if path.last() != Some(&goal) {
+            path.push(goal);
+        }
+        path
+    }
+}
+
+fn rectangle(bounds: AABB2D) -> Polygon {
+    Polygon {
+        vertices: vec![
+            bounds.min,
+            Vec2::new(bounds.max.x, bounds.min.y),
+            bounds.max,
+            Vec2::new(bounds.min.x, bounds.max.y),
+        ],
+    }
+}
+
+/// The space `collider` takes up, grown by `agent_radius`.
+fn obstacle(collider: &Collider, agent_radius: f32) -> Polygon {
+    let position = collider.position();
+    let center = Vec2::new(position.translation.x, position.translation.y);
+    let rotation = Vec2::from_angle(position.rotation.angle());
+    let shape = collider.shape();
+
+    if let Some(ball) = shape.as_ball() {
+        // far enough out that the flat sides still cover the circle
+        let radius = (ball.radius + agent_radius) / (PI / CIRCLE_SEGMENTS as f32).cos();
+        let vertices = (0..CIRCLE_SEGMENTS)
+            .map(|i| {
+                center + Vec2::from_angle(i as f32 / CIRCLE_SEGMENTS as f32 * PI * 2.0) * radius
+            })
+            .collect();
+        Polygon { vertices }
+    } else if let Some(cuboid) = shape.as_cuboid() {
+        let half_size =
+            Vec2::new(cuboid.half_extents.x, cuboid.half_extents.y) + Vec2::splat(agent_radius);
+        let vertices = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .map(|(x, y)| center + rotation.rotate(half_size * Vec2::new(x, y)))
+            .to_vec();
+        Polygon { vertices }
+    } else {
+        let aabb = collider.compute_aabb();
+        rectangle(
+            AABB2D::new(
+                Vec2::new(aabb.mins.x, aabb.mins.y),
+                Vec2::new(aabb.maxs.x, aabb.maxs.y),
+            )
+            .expand(agent_radius),
+        )
+    }
+}
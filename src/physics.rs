@@ -1,133 +1,13 @@
-pub use World as PhysicsWorld;
-use bevy_math::Vec2;
-use rapier2d::{
-    na::{Vector2, vector},
-    prelude::{
-        CCDSolver, Collider, ColliderHandle, ColliderSet, DefaultBroadPhase, EventHandler,
-        ImpulseJointSet, IntegrationParameters, IslandManager, MultibodyJointSet, NarrowPhase,
-        PhysicsHooks, PhysicsPipeline, RigidBody, RigidBodyHandle, RigidBodySet,
-    },
-};
-
-pub struct World<H = (), E = ()> {
-    pub gravity: Vector2<f32>,
-    pub integration_parameters: IntegrationParameters,
-    pub physics_pipeline: PhysicsPipeline,
-    pub island_manager: IslandManager,
-    pub broad_phase: DefaultBroadPhase,
-    pub narrow_phase: NarrowPhase,
-    pub impulse_joint_set: ImpulseJointSet,
-    pub multibody_joint_set: MultibodyJointSet,
-    pub ccd_solver: CCDSolver,
-    pub rigid_body_set: RigidBodySet,
-    pub collider_set: ColliderSet,
-    pub physics_hooks: H,
-    pub event_handler: E,
-}
-
-impl World<(), ()> {
-    pub fn new() -> Self {
-        Self::with_hooks_and_event_handler((), ())
-    }
-}
-
-impl Default for World<(), ()> {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-impl<H: PhysicsHooks, E: EventHandler> World<H, E> {
-    pub fn with_hooks_and_event_handler(hooks: H, event_handler: E) -> Self {
-        let rigid_body_set = RigidBodySet::new();
-        let collider_set = ColliderSet::new();
-        let gravity = vector![0.0, -9.81];
-        let integration_parameters = IntegrationParameters::default();
-        let physics_pipeline = PhysicsPipeline::new();
-        let island_manager = IslandManager::new();
-        let broad_phase = DefaultBroadPhase::new();
-        let narrow_phase = NarrowPhase::new();
-        let impulse_joint_set = ImpulseJointSet::new();
-        let multibody_joint_set = MultibodyJointSet::new();
-        let ccd_solver = CCDSolver::new();
-
-        Self {
-            gravity,
-            integration_parameters,
-            physics_pipeline,
-            island_manager,
-            broad_phase,
-            narrow_phase,
-            impulse_joint_set,
-            multibody_joint_set,
-            ccd_solver,
-            rigid_body_set,
-            collider_set,
-            physics_hooks: hooks,
-            event_handler,
-        }
-    }
-
-    pub fn step(&mut self) {
-        self.physics_pipeline.step(
-            &self.gravity,
-            &self.integration_parameters,
-            &mut self.island_manager,
-            &mut self.broad_phase,
-            &mut self.narrow_phase,
-            &mut self.rigid_body_set,
-            &mut self.collider_set,
-            &mut self.impulse_joint_set,
-            &mut self.multibody_joint_set,
-            &mut self.ccd_solver,
-            &self.physics_hooks,
-            &self.event_handler,
-        );
-    }
-
-    pub fn with_custom_gravity(mut self, gravity: Vec2) -> Self {
-        self.gravity = gravity.into();
-        self
-    }
+//! 2D physics. [`PhysicsWorld`] wraps a rapier world, and everything from rapier's prelude is
+//! re-exported here so games don't need to depend on rapier directly.
 
-    pub fn with_no_gravity(mut self) -> Self {
-        self.gravity = vector![0.0, 0.0];
-        self
-    }
-
-    pub fn insert_rigid_body(&mut self, rigid_body: RigidBody) -> RigidBodyHandle {
-        self.rigid_body_set.insert(rigid_body)
-    }
-
-    pub fn insert_collider(&mut self, collider: Collider) -> ColliderHandle {
-        self.collider_set.insert(collider)
-    }
-
-    pub fn insert_rigid_body_with_collider(
-        &mut self,
-        rigid_body: RigidBody,
-        collider: Collider,
-    ) -> (RigidBodyHandle, ColliderHandle) {
-        let body_handle = self.rigid_body_set.insert(rigid_body);
-        let collider_handle =
-            self.collider_set
-                .insert_with_parent(collider, body_handle, &mut self.rigid_body_set);
-        (body_handle, collider_handle)
-    }
-
-    pub fn get_rigid_body(&self, handle: RigidBodyHandle) -> Option<&RigidBody> {
-        self.rigid_body_set.get(handle)
-    }
-
-    pub fn get_rigid_body_mut(&mut self, handle: RigidBodyHandle) -> Option<&mut RigidBody> {
-        self.rigid_body_set.get_mut(handle)
-    }
-
-    pub fn get_collider(&self, handle: ColliderHandle) -> Option<&Collider> {
-        self.collider_set.get(handle)
-    }
-
-    pub fn get_collider_mut(&mut self, handle: ColliderHandle) -> Option<&mut Collider> {
-        self.collider_set.get_mut(handle)
-    }
-}
+pub use crate::api::{
+    is_physics_time_paused, is_physics_time_paused_mut, pause_physics_timer, physics_time,
+    play_physics_timer, toggle_physics_timer,
+};
+pub use crate::character_controller::CharacterController2D;
+pub use crate::collisions::{self, IntersectsWith};
+pub use crate::physics_world::{Hit, PhysicsWorld};
+pub use crate::verlet::{DistanceConstraint, Rope, SoftBody2D, VerletPoint};
+pub use nalgebra::vector;
+pub use rapier2d::prelude::*;
@@ -0,0 +1,490 @@
+pub use World as PhysicsWorld;
+use bevy_math::Vec2;
+use rapier2d::{
+    na::{Vector2, vector},
+    parry::query::ShapeCastOptions,
+    prelude::{
+        ActiveCollisionTypes, ActiveEvents, CCDSolver, Collider, ColliderHandle, ColliderSet,
+        CollisionEvent, ContactPair, DefaultBroadPhase, EventHandler, GenericJoint, Group,
+        ImpulseJoint, ImpulseJointHandle, ImpulseJointSet, IntegrationParameters,
+        InteractionGroups, IslandManager, JointAxis, MultibodyJointSet, NarrowPhase, PhysicsHooks,
+        PhysicsPipeline, PrismaticJointBuilder, QueryFilter, QueryPipeline, RevoluteJointBuilder,
+        RigidBody, RigidBodyHandle, RigidBodySet, RopeJointBuilder, Shape, SpringJointBuilder,
+    },
+};
+use std::sync::Mutex;
+
+use crate::collisions::ray::Ray;
+use crate::try_get_state;
+
+/// A collider found by a query on a [`PhysicsWorld`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    pub collider: ColliderHandle,
+    /// Where it was hit. For shapecasts, where the shape first touches it.
+    pub point: Vec2,
+    /// Surface normal of the collider at `point`.
+    pub normal: Vec2,
+    /// How far along the ray (or how far the shape moved) before the hit.
+    pub distance: f32,
+}
+
+pub struct World<H = (), E = ()> {
+    pub gravity: Vector2<f32>,
+    pub integration_parameters: IntegrationParameters,
+    pub physics_pipeline: PhysicsPipeline,
+    pub island_manager: IslandManager,
+    pub broad_phase: DefaultBroadPhase,
+    pub narrow_phase: NarrowPhase,
+    pub impulse_joint_set: ImpulseJointSet,
+    pub multibody_joint_set: MultibodyJointSet,
+    pub ccd_solver: CCDSolver,
+    pub rigid_body_set: RigidBodySet,
+    pub collider_set: ColliderSet,
+    pub physics_hooks: H,
+    pub event_handler: E,
+    collision_events: Vec<CollisionEvent>,
+}
+
+impl World<(), ()> {
+    pub fn new() -> Self {
+        Self::with_hooks_and_event_handler((), ())
+    }
+}
+
+impl Default for World<(), ()> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<H: PhysicsHooks, E: EventHandler> World<H, E> {
+    pub fn with_hooks_and_event_handler(hooks: H, event_handler: E) -> Self {
+        let rigid_body_set = RigidBodySet::new();
+        let collider_set = ColliderSet::new();
+        let gravity = vector![0.0, -9.81];
+        let integration_parameters = IntegrationParameters::default();
+        let physics_pipeline = PhysicsPipeline::new();
+        let island_manager = IslandManager::new();
+        let broad_phase = DefaultBroadPhase::new();
+        let narrow_phase = NarrowPhase::new();
+        let impulse_joint_set = ImpulseJointSet::new();
+        let multibody_joint_set = MultibodyJointSet::new();
+        let ccd_solver = CCDSolver::new();
+
+        Self {
+            gravity,
+            integration_parameters,
+            physics_pipeline,
+            island_manager,
+            broad_phase,
+            narrow_phase,
+            impulse_joint_set,
+            multibody_joint_set,
+            ccd_solver,
+            rigid_body_set,
+            collider_set,
+            physics_hooks: hooks,
+            event_handler,
+            collision_events: Vec::new(),
+        }
+    }
+
+    /// Steps the simulation by `integration_parameters.dt`, scaled by the engine's
+    /// [`time_scale`](crate::prelude::time_scale). Does nothing while time is frozen.
+    pub fn step(&mut self) {
+        self.step_scaled(try_get_state().map_or(1.0, |state| state.time_scale));
+    }
+
+    /// Steps by `integration_parameters.dt` times `time_scale`.
+    pub(crate) fn step_scaled(&mut self, time_scale: f32) {
+        crate::profile_scope!("physics");
+
+        let mut integration_parameters = self.integration_parameters;
+        integration_parameters.dt *= time_scale;
+        if integration_parameters.dt <= 0.0 {
+            self.collision_events.clear();
+            return;
+        }
+
+        let event_handler = CollectingEventHandler {
+            inner: &self.event_handler,
+            collision_events: Mutex::new(Vec::new()),
+        };
+
+        self.physics_pipeline.step(
+            &self.gravity,
+            &integration_parameters,
+            &mut self.island_manager,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.rigid_body_set,
+            &mut self.collider_set,
+            &mut self.impulse_joint_set,
+            &mut self.multibody_joint_set,
+            &mut self.ccd_solver,
+            &self.physics_hooks,
+            &event_handler,
+        );
+
+        self.collision_events = event_handler
+            .collision_events
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(state) = try_get_state() {
+            for event in &self.collision_events {
+                state.events.emit(*event);
+            }
+        }
+    }
+
+    pub fn with_custom_gravity(mut self, gravity: Vec2) -> Self {
+        self.gravity = gravity.into();
+        self
+    }
+
+    pub fn with_no_gravity(mut self) -> Self {
+        self.gravity = vector![0.0, 0.0];
+        self
+    }
+
+    pub fn insert_rigid_body(&mut self, rigid_body: RigidBody) -> RigidBodyHandle {
+        self.rigid_body_set.insert(rigid_body)
+    }
+
+    pub fn insert_collider(&mut self, collider: Collider) -> ColliderHandle {
+        self.collider_set.insert(collider)
+    }
+
+    pub fn insert_rigid_body_with_collider(
+        &mut self,
+        rigid_body: RigidBody,
+        collider: Collider,
+    ) -> (RigidBodyHandle, ColliderHandle) {
+        let body_handle = self.rigid_body_set.insert(rigid_body);
+        let collider_handle =
+            self.collider_set
+                .insert_with_parent(collider, body_handle, &mut self.rigid_body_set);
+        (body_handle, collider_handle)
+    }
+
+    /// Inserts a trigger: a collider that things pass through, but that still reports when they
+    /// start and stop touching it, for pickups, damage zones and checkpoints. See
+    /// [`on_enter`](Self::on_enter) and [`on_exit`](Self::on_exit).
+    pub fn insert_sensor(&mut self, collider: Collider) -> ColliderHandle {
+        self.collider_set.insert(into_sensor(collider))
+    }
+
+    /// Like [`insert_sensor`](Self::insert_sensor), but attached to a body so it moves with it.
+    pub fn insert_sensor_with_parent(
+        &mut self,
+        collider: Collider,
+        parent: RigidBodyHandle,
+    ) -> ColliderHandle {
+        self.collider_set.insert_with_parent(
+            into_sensor(collider),
+            parent,
+            &mut self.rigid_body_set,
+        )
+    }
+
+    /// Everything that started or stopped touching during the last [`step`](Self::step). Only
+    /// colliders with `ActiveEvents::COLLISION_EVENTS` report these, which sensors inserted with
+    /// [`insert_sensor`](Self::insert_sensor) have.
+    pub fn collision_events(&self) -> &[CollisionEvent] {
+        &self.collision_events
+    }
+
+    /// Colliders that started touching `collider` during the last step.
+    pub fn on_enter(&self, collider: ColliderHandle) -> impl Iterator<Item = ColliderHandle> + '_ {
+        self.touching_events(collider, true)
+    }
+
+    /// Colliders that stopped touching `collider` during the last step.
+    pub fn on_exit(&self, collider: ColliderHandle) -> impl Iterator<Item = ColliderHandle> + '_ {
+        self.touching_events(collider, false)
+    }
+
+    fn touching_events(
+        &self,
+        collider: ColliderHandle,
+        started: bool,
+    ) -> impl Iterator<Item = ColliderHandle> + '_ {
+        self.collision_events
+            .iter()
+            .filter(move |event| event.started() == started)
+            .filter_map(move |event| {
+                if event.collider1() == collider {
+                    Some(event.collider2())
+                } else if event.collider2() == collider {
+                    Some(event.collider1())
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// Connects two bodies with any rapier joint. The helpers below cover the usual ones.
+    pub fn insert_joint(
+        &mut self,
+        body_1: RigidBodyHandle,
+        body_2: RigidBodyHandle,
+        joint: impl Into<GenericJoint>,
+    ) -> ImpulseJointHandle {
+        self.impulse_joint_set.insert(body_1, body_2, joint, true)
+    }
+
+    pub fn remove_joint(&mut self, handle: ImpulseJointHandle) -> Option<ImpulseJoint> {
+        self.impulse_joint_set.remove(handle, true)
+    }
+
+    /// Keeps `anchor_1` and `anchor_2` exactly `length` apart, like a rigid rod. Anchors are
+    /// relative to each body's center.
+    pub fn add_distance_joint(
+        &mut self,
+        body_1: RigidBodyHandle,
+        body_2: RigidBodyHandle,
+        anchor_1: Vec2,
+        anchor_2: Vec2,
+        length: f32,
+    ) -> ImpulseJointHandle {
+        let mut joint: GenericJoint = RopeJointBuilder::new(length)
+            .local_anchor1(anchor_1.into())
+            .local_anchor2(anchor_2.into())
+            .into();
+        joint.set_limits(JointAxis::LinX, [length, length]);
+        self.insert_joint(body_1, body_2, joint)
+    }
+
+    /// Keeps `anchor_1` and `anchor_2` at most `max_length` apart, but lets them get closer.
+    pub fn add_rope_joint(
+        &mut self,
+        body_1: RigidBodyHandle,
+        body_2: RigidBodyHandle,
+        anchor_1: Vec2,
+        anchor_2: Vec2,
+        max_length: f32,
+    ) -> ImpulseJointHandle {
+        let joint = RopeJointBuilder::new(max_length)
+            .local_anchor1(anchor_1.into())
+            .local_anchor2(anchor_2.into());
+        self.insert_joint(body_1, body_2, joint)
+    }
+
+    /// Pins the bodies together at the anchors, leaving them free to rotate around the pin,
+    /// like a door hinge or a wheel on an axle.
+    pub fn add_revolute_joint(
+        &mut self,
+        body_1: RigidBodyHandle,
+        body_2: RigidBodyHandle,
+        anchor_1: Vec2,
+        anchor_2: Vec2,
+    ) -> ImpulseJointHandle {
+        let joint = RevoluteJointBuilder::new()
+            .local_anchor1(anchor_1.into())
+            .local_anchor2(anchor_2.into());
+        self.insert_joint(body_1, body_2, joint)
+    }
+
+    /// Lets the second body only slide along `axis` (in the first body's space), like a piston
+    /// or an elevator. `limits` is how far it can slide each way from the anchor.
+    pub fn add_prismatic_joint(
+        &mut self,
+        body_1: RigidBodyHandle,
+        body_2: RigidBodyHandle,
+        anchor_1: Vec2,
+        anchor_2: Vec2,
+        axis: Vec2,
+        limits: Option<[f32; 2]>,
+    ) -> ImpulseJointHandle {
+        let axis = rapier2d::na::Unit::new_normalize(axis.into());
+        let mut joint = PrismaticJointBuilder::new(axis)
+            .local_anchor1(anchor_1.into())
+            .local_anchor2(anchor_2.into());
+        if let Some(limits) = limits {
+            joint = joint.limits(limits);
+        }
+        self.insert_joint(body_1, body_2, joint)
+    }
+
+    /// Pulls the anchors towards being `rest_length` apart, for suspension and bouncy things.
+    /// Higher `stiffness` pulls harder and `damping` stops it wobbling forever.
+    #[allow(clippy::too_many_arguments)]
+    pub fn add_spring_joint(
+        &mut self,
+        body_1: RigidBodyHandle,
+        body_2: RigidBodyHandle,
+        anchor_1: Vec2,
+        anchor_2: Vec2,
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    ) -> ImpulseJointHandle {
+        let joint = SpringJointBuilder::new(rest_length, stiffness, damping)
+            .local_anchor1(anchor_1.into())
+            .local_anchor2(anchor_2.into());
+        self.insert_joint(body_1, body_2, joint)
+    }
+
+    pub fn get_rigid_body(&self, handle: RigidBodyHandle) -> Option<&RigidBody> {
+        self.rigid_body_set.get(handle)
+    }
+
+    pub fn get_rigid_body_mut(&mut self, handle: RigidBodyHandle) -> Option<&mut RigidBody> {
+        self.rigid_body_set.get_mut(handle)
+    }
+
+    pub fn get_collider(&self, handle: ColliderHandle) -> Option<&Collider> {
+        self.collider_set.get(handle)
+    }
+
+    pub fn get_collider_mut(&mut self, handle: ColliderHandle) -> Option<&mut Collider> {
+        self.collider_set.get_mut(handle)
+    }
+
+    /// Rapier's query pipeline, for queries not covered here. Like all queries, it sees the
+    /// world as it was at the last [`step`](Self::step).
+    pub fn query_pipeline<'a>(&'a self, filter: QueryFilter<'a>) -> QueryPipeline<'a> {
+        self.broad_phase.as_query_pipeline(
+            self.narrow_phase.query_dispatcher(),
+            &self.rigid_body_set,
+            &self.collider_set,
+            filter,
+        )
+    }
+
+    /// The first collider the ray hits within `max_distance`. Rays starting inside a collider
+    /// hit it straight away.
+    pub fn raycast(&self, ray: &Ray, max_distance: f32) -> Option<Hit> {
+        let rapier_ray = to_rapier_ray(ray);
+        self.query_pipeline(QueryFilter::default())
+            .cast_ray_and_get_normal(&rapier_ray, max_distance, true)
+            .map(|(collider, hit)| Hit {
+                collider,
+                point: ray.point_at(hit.time_of_impact),
+                normal: Vec2::new(hit.normal.x, hit.normal.y),
+                distance: hit.time_of_impact,
+            })
+    }
+
+    /// Every collider the ray hits within `max_distance`, closest first. For bullets that go
+    /// through things, or checking everything along a line.
+    pub fn raycast_all(&self, ray: &Ray, max_distance: f32) -> Vec<Hit> {
+        self.raycast_with_filter(ray, max_distance, QueryFilter::default())
+    }
+
+    /// Like [`raycast_all`](Self::raycast_all), but only hits colliders that are in at least one
+    /// of the groups in `mask` (see [`ColliderBuilder::collision_groups`](rapier2d::prelude::ColliderBuilder::collision_groups)).
+    pub fn raycast_filtered(&self, ray: &Ray, max_distance: f32, mask: Group) -> Vec<Hit> {
+        let filter = QueryFilter::default().groups(InteractionGroups::new(Group::ALL, mask));
+        self.raycast_with_filter(ray, max_distance, filter)
+    }
+
+    fn raycast_with_filter(&self, ray: &Ray, max_distance: f32, filter: QueryFilter) -> Vec<Hit> {
+        let rapier_ray = to_rapier_ray(ray);
+        let mut hits: Vec<Hit> = self
+            .query_pipeline(filter)
+            .intersect_ray(rapier_ray, max_distance, true)
+            .map(|(collider, _, hit)| Hit {
+                collider,
+                point: ray.point_at(hit.time_of_impact),
+                normal: Vec2::new(hit.normal.x, hit.normal.y),
+                distance: hit.time_of_impact,
+            })
+            .collect();
+
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+
+    /// Moves `shape` from `position` in `direction` and returns the first collider it would
+    /// touch within `max_distance`. Good for checking if a character fits somewhere before
+    /// moving it there. Any shape from rapier works, like `Ball::new(0.5)` or
+    /// `Cuboid::new(vector![1.0, 2.0])`.
+    pub fn shapecast(
+        &self,
+        shape: &dyn Shape,
+        position: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+    ) -> Option<Hit> {
+        let direction = direction.normalize_or_zero();
+        if direction == Vec2::ZERO {
+            return None;
+        }
+
+        let position = rapier2d::prelude::Isometry::translation(position.x, position.y);
+        let velocity: Vector2<f32> = direction.into();
+        let options = ShapeCastOptions {
+            max_time_of_impact: max_distance,
+            compute_impact_geometry_on_penetration: true,
+            ..Default::default()
+        };
+
+        self.query_pipeline(QueryFilter::default())
+            .cast_shape(&position, &velocity, shape, options)
+            .map(|(collider, hit)| Hit {
+                collider,
+                point: Vec2::new(hit.witness1.x, hit.witness1.y),
+                normal: Vec2::new(hit.normal1.x, hit.normal1.y),
+                distance: hit.time_of_impact,
+            })
+    }
+}
+
+fn into_sensor(mut collider: Collider) -> Collider {
+    collider.set_sensor(true);
+    collider.set_active_events(collider.active_events() | ActiveEvents::COLLISION_EVENTS);
+    // sensors sitting still should still notice kinematic bodies like characters
+    collider.set_active_collision_types(ActiveCollisionTypes::all());
+    collider
+}
+
+/// Passes events on to the world's own handler, and keeps the collision events for
+/// [`World::collision_events`].
+struct CollectingEventHandler<'a, E> {
+    inner: &'a E,
+    collision_events: Mutex<Vec<CollisionEvent>>,
+}
+
+impl<E: EventHandler> EventHandler for CollectingEventHandler<'_, E> {
+    fn handle_collision_event(
+        &self,
+        bodies: &RigidBodySet,
+        colliders: &ColliderSet,
+        event: CollisionEvent,
+        contact_pair: Option<&ContactPair>,
+    ) {
+        self.inner
+            .handle_collision_event(bodies, colliders, event, contact_pair);
+        self.collision_events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(event);
+    }
+
+    fn handle_contact_force_event(
+        &self,
+        dt: f32,
+        bodies: &RigidBodySet,
+        colliders: &ColliderSet,
+        contact_pair: &ContactPair,
+        total_force_magnitude: f32,
+    ) {
+        self.inner.handle_contact_force_event(
+            dt,
+            bodies,
+            colliders,
+            contact_pair,
+            total_force_magnitude,
+        );
+    }
+}
+
+fn to_rapier_ray(ray: &Ray) -> rapier2d::prelude::Ray {
+    rapier2d::prelude::Ray::new(
+        rapier2d::prelude::point![ray.origin.x, ray.origin.y],
+        ray.direction.into(),
+    )
+}
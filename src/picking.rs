@@ -0,0 +1,204 @@
+//! Finding out what's under the cursor by drawing everything again into an offscreen buffer,
+//! in colors that encode pick ids instead of their real colors. It's exact for sprites with
+//! transparent parts and meshes of any shape, without any hit testing on the CPU.
+//!
+//! Turn it on with [`use_picking`], give draws ids with [`set_pick_id`] or [`with_pick_id`],
+//! then ask what's at a position with [`pick_at`]. Only things drawn to the screen can be
+//! picked, not things drawn to render textures.
+
+use bevy_math::{UVec2, Vec2};
+use glium::{
+    Surface, Texture2d,
+    framebuffer::SimpleFrameBuffer,
+    texture::{DepthStencilTexture2d, MipmapsOption, UncompressedFloatFormat},
+};
+
+use crate::{
+    draw_queue_2d::SurfaceDrawTarget,
+    error::{EngineResult, OrReport},
+    get_state,
+    input_handling::cursor,
+    render_pipeline::{RenderPipeline, RenderStep},
+};
+
+/// Identifies something that was drawn. Up to `2^24 - 2`, since ids are stored as colors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PickId(pub u32);
+
+#[derive(Default)]
+pub(crate) struct Picking {
+    enabled: bool,
+    buffer: Option<PickBuffer>,
+}
+
+struct PickBuffer {
+    color: Texture2d,
+    depth: DepthStencilTexture2d,
+}
+
+impl PickBuffer {
+    fn new(size: UVec2) -> EngineResult<Self> {
+        let display = get_state().display();
+        Ok(Self {
+            color: Texture2d::empty_with_format(
+                display,
+                UncompressedFloatFormat::U8U8U8U8,
+                MipmapsOption::NoMipmap,
+                size.x,
+                size.y,
+            )?,
+            depth: DepthStencilTexture2d::empty(display, size.x, size.y)?,
+        })
+    }
+
+    fn size(&self) -> UVec2 {
+        UVec2::new(self.color.width(), self.color.height())
+    }
+}
+
+/// The color something with `pick` is drawn in. 0 is for things without an id.
+pub(crate) fn pick_color(pick: Option<PickId>) -> [f32; 4] {
+    let value = pick.map_or(0, |pick| pick.0 + 1);
+    let [r, g, b, _] = value.to_le_bytes();
+    [r, g, b, 255].map(|channel| channel as f32 / 255.0)
+}
+
+/// Turns a color read back from the picking buffer into the id it was drawn with.
+pub(crate) fn pick_from_color([r, g, b, _]: [u8; 4]) -> Option<PickId> {
+    let value = u32::from_le_bytes([r, g, b, 0]);
+    value.checked_sub(1).map(PickId)
+}
+
+/// Turns the picking buffer on or off. It costs drawing everything twice, so it's off by
+/// default.
+pub fn use_picking(enabled: bool) {
+    let picking = &mut get_state().picking;
+    picking.enabled = enabled;
+    if !enabled {
+        picking.buffer = None;
+    }
+}
+
+/// Pick id for everything drawn after this, in 2D and 3D, until the end of the frame or until
+/// it's changed again. `None` draws things that can't be picked, but still hide what's behind.
+pub fn set_pick_id(pick_id: Option<PickId>) {
+    let state = get_state();
+    state.draw_queue_2d().set_pick_id(pick_id);
+    state.world_draw_queue_2d().set_pick_id(pick_id);
+    state.draw_queue_3d().set_pick_id(pick_id);
+}
+
+pub fn pick_id() -> Option<PickId> {
+    get_state().draw_queue_2d().pick_id()
+}
+
+/// Draws everything in `f` with the given pick id, then goes back to the previous one.
+pub fn with_pick_id<T>(pick_id: PickId, f: impl FnOnce() -> T) -> T {
+    let previous = self::pick_id();
+    set_pick_id(Some(pick_id));
+    let result = f();
+    set_pick_id(previous);
+    result
+}
+
+/// The pick id of whatever's on top at `position`, in pixels from the top left of the window.
+/// Drawing happens at the end of the frame, so this is what was on screen last frame, which is
+/// what the player is looking at.
+///
+/// Reading from the GPU waits for it to finish drawing, so it's best to only do this once or
+/// twice a frame.
+pub fn pick_at(position: Vec2) -> Option<PickId> {
+    let buffer = get_state().picking.buffer.as_ref()?;
+    let size = buffer.size();
+    if position.x < 0.0 || position.y < 0.0 {
+        return None;
+    }
+    let (x, y) = (position.x as u32, position.y as u32);
+    if x >= size.x || y >= size.y {
+        return None;
+    }
+
+    let pixel: Vec<Vec<(u8, u8, u8, u8)>> = buffer
+        .color
+        .main_level()
+        .first_layer()
+        .into_image(None)?
+        .raw_read(&glium::Rect {
+            left: x,
+            // textures count up from the bottom
+            bottom: size.y - 1 - y,
+            width: 1,
+            height: 1,
+        });
+    let (r, g, b, a) = pixel[0][0];
+    pick_from_color([r, g, b, a])
+}
+
+/// What the cursor is over, see [`pick_at`].
+pub fn hovered_pick() -> Option<PickId> {
+    let (x, y) = cursor()?;
+    pick_at(Vec2::new(x, y))
+}
+
+/// Draws everything queued in `pipeline` into the picking buffer, before it's drawn for real.
+pub(crate) fn draw_picking_buffer(pipeline: &RenderPipeline) {
+    let state = get_state();
+    if !state.picking.enabled {
+        return;
+    }
+
+    let (width, height) = state.display().get_framebuffer_dimensions();
+    let size = UVec2::new(width, height);
+    if size.x == 0 || size.y == 0 {
+        return;
+    }
+    if state
+        .picking
+        .buffer
+        .as_ref()
+        .is_none_or(|buffer| buffer.size() != size)
+    {
+        state.picking.buffer = PickBuffer::new(size).or_report();
+    }
+    let Some(buffer) = state.picking.buffer.as_ref() else {
+        return;
+    };
+
+    let Some(mut target) =
+        SimpleFrameBuffer::with_depth_stencil_buffer(state.display(), &buffer.color, &buffer.depth)
+            .or_report()
+    else {
+        return;
+    };
+    target.clear_all((0.0, 0.0, 0.0, 0.0), 1.0, 0);
+
+    let mut cameras = pipeline.cameras();
+    for step in &pipeline.steps {
+        let RenderStep::Drawing(queues) = step else {
+            continue;
+        };
+
+        queues
+            .draw_queue_3d
+            .draw_for_picking(&mut target, &cameras.d3.view_proj());
+        target.clear_depth(1.0);
+
+        queues.world_draw_queue_2d.for_picking().draw_to(
+            &mut SurfaceDrawTarget::for_picking(&mut target),
+            &cameras.d2.projection_matrix(),
+        );
+        target.clear_depth(1.0);
+
+        queues.draw_queue_2d.for_picking().draw_to(
+            &mut SurfaceDrawTarget::for_picking(&mut target),
+            &cameras.flat,
+        );
+        target.clear_depth(1.0);
+
+        queues.overlay_draw_queue_2d.for_picking().draw_to(
+            &mut SurfaceDrawTarget::for_picking(&mut target),
+            &cameras.flat,
+        );
+        target.clear_depth(1.0);
+    }
+}
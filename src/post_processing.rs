@@ -1,7 +1,23 @@
-use bevy_math::Vec2;
-use glium::{Program, Surface, framebuffer::SimpleFrameBuffer, texture::Texture2d, uniform};
-
-use crate::{EngineDisplay, color::Color, get_state, programs::ProgramRef, textures::TextureRef};
+use anyhow::Context;
+use bevy_math::{Mat3, Vec2, Vec3};
+use engine_4_macros::Uniforms;
+use glium::{
+    Program, Surface,
+    framebuffer::SimpleFrameBuffer,
+    texture::{MipmapsOption, Texture2d, UncompressedFloatFormat},
+    uniform,
+    uniforms::Sampler,
+};
+
+use crate::{
+    EngineDisplay,
+    color::Color,
+    error::EngineResult,
+    get_state,
+    palette::MAX_PALETTE_COLORS,
+    programs::{ProgramRef, cached_program, load_program},
+    textures::TextureRef,
+};
 
 #[derive(Clone, Debug)]
 pub enum PostProcessingEffect {
@@ -29,9 +45,259 @@ pub enum PostProcessingEffect {
     ChromaticAberration {
         strength: f32,
     },
+    ColorFilter(ColorFilter),
+    /// An old CRT screen, for pixel art. Start from one of the presets, like
+    /// [`PostProcessingEffect::crt_arcade`].
+    Crt {
+        /// How much the screen bulges out, like a curved tube. 0 is flat, 0.1 to 0.3 looks right.
+        curvature: f32,
+        /// How dark the gaps between scanlines are, from 0 to 1.
+        scanlines: f32,
+        /// How many scanlines go down the screen. Matching the game's resolution looks best.
+        scanline_count: f32,
+        /// How strong the red, green and blue phosphor stripes are, from 0 to 1.
+        aperture_grille: f32,
+        /// How much bright parts bleed light into their surroundings.
+        glow: f32,
+        /// How much flickering static there is, from 0 to 1.
+        noise: f32,
+    },
+    /// Keeps every color to the nearest one in `palette`, see [`Palette`](crate::gfx::Palette).
+    /// `dither` blends between neighbouring colors with a fine checker pattern instead of flat
+    /// bands, from 0 for none to 1.
+    Palette {
+        palette: TextureRef,
+        dither: f32,
+    },
+    /// Brings HDR colors down into what the screen can show. Done for you at the end of the
+    /// frame with [`set_hdr`](crate::gfx::set_hdr).
+    Tonemap(Tonemapping),
+}
+
+/// The common kinds of color blindness, each missing one of the three types of cone.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorBlindness {
+    /// No red cones, so reds look dark and get confused with greens.
+    Protanopia,
+    /// No green cones, the most common. Reds and greens get confused.
+    Deuteranopia,
+    /// No blue cones. Blues get confused with greens, and yellows with pinks.
+    Tritanopia,
+}
+
+impl ColorBlindness {
+    pub const ALL: [Self; 3] = [Self::Protanopia, Self::Deuteranopia, Self::Tritanopia];
+
+    /// How linear RGB looks with it, by row. From Machado, Oliveira and Fernandes (2009).
+    fn matrix(self) -> [[f32; 3]; 3] {
+        match self {
+            Self::Protanopia => [
+                [0.152286, 1.052583, -0.204868],
+                [0.114503, 0.786281, 0.099216],
+                [-0.003882, -0.048116, 1.051998],
+            ],
+            Self::Deuteranopia => [
+                [0.367322, 0.860646, -0.227968],
+                [0.280085, 0.672501, 0.047413],
+                [-0.011820, 0.042940, 0.968881],
+            ],
+            Self::Tritanopia => [
+                [1.255528, -0.076749, -0.178779],
+                [-0.078411, 0.930809, 0.147602],
+                [0.004733, 0.691367, 0.303900],
+            ],
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Protanopia => "protanopia",
+            Self::Deuteranopia => "deuteranopia",
+            Self::Tritanopia => "tritanopia",
+        }
+    }
+}
+
+/// A filter for color blindness, as a [`PostProcessingEffect`] or in
+/// [`EngineConfig::color_filter`](crate::config::EngineConfig::color_filter).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorFilter {
+    /// Shows the game how someone with it would see it, for checking colors are still told
+    /// apart.
+    Simulate(ColorBlindness),
+    /// Moves the colors that get lost into ones that can still be seen, so things that only
+    /// differ in those colors can be told apart.
+    Correct(ColorBlindness),
+}
+
+impl ColorFilter {
+    /// What the filter does to `color`, the same as the shader.
+    pub fn apply(self, color: Color) -> Color {
+        let (blindness, correct) = match self {
+            Self::Simulate(blindness) => (blindness, false),
+            Self::Correct(blindness) => (blindness, true),
+        };
+
+        let linear = Vec3::new(color.r, color.g, color.b)
+            .max(Vec3::ZERO)
+            .powf(2.2);
+        let simulated = Mat3::from_cols_array_2d(&blindness.matrix()).transpose() * linear;
+        let filtered = if correct {
+            // the difference is what can't be seen, so it's shifted into green and blue
+            let lost = linear - simulated;
+            linear + Vec3::new(0.0, lost.x * 0.7 + lost.y, lost.x * 0.7 + lost.z)
+        } else {
+            simulated
+        };
+
+        let rgb = filtered.clamp(Vec3::ZERO, Vec3::ONE).powf(1.0 / 2.2);
+        Color::from_rgba(rgb.x, rgb.y, rgb.z, color.a)
+    }
+}
+
+impl std::fmt::Display for ColorFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Simulate(blindness) => write!(f, "simulate-{}", blindness.name()),
+            Self::Correct(blindness) => write!(f, "correct-{}", blindness.name()),
+        }
+    }
+}
+
+impl std::str::FromStr for ColorFilter {
+    type Err = anyhow::Error;
+
+    /// Like `"correct-deuteranopia"`, the same as it's displayed.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        let (filter, name): (fn(ColorBlindness) -> Self, _) =
+            if let Some(name) = lower.strip_prefix("simulate-") {
+                (Self::Simulate, name)
+            } else if let Some(name) = lower.strip_prefix("correct-") {
+                (Self::Correct, name)
+            } else {
+                anyhow::bail!("`{s}` isn't a color filter, like `correct-deuteranopia`");
+            };
+
+        ColorBlindness::ALL
+            .into_iter()
+            .find(|blindness| blindness.name() == name)
+            .map(filter)
+            .with_context(|| format!("`{name}` isn't a kind of color blindness"))
+    }
+}
+
+/// How HDR colors over 1.0 get squeezed down into what the screen can show.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TonemapOperator {
+    /// Simple and soft, but washes out bright colors.
+    Reinhard,
+    /// The filmic curve most games use, with more contrast and saturation.
+    Aces,
+}
+
+impl TonemapOperator {
+    /// Maps one linear channel into 0 to 1, the same as the shader.
+    pub fn map(self, x: f32) -> f32 {
+        let x = x.max(0.0);
+        match self {
+            Self::Reinhard => x / (1.0 + x),
+            // Krzysztof Narkowicz's fit of the ACES curve
+            Self::Aces => {
+                ((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/// See [`set_hdr`](crate::gfx::set_hdr).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tonemapping {
+    pub operator: TonemapOperator,
+    /// Multiplies the light before it's tonemapped, like a camera's exposure. Higher is brighter.
+    pub exposure: f32,
+}
+
+impl Tonemapping {
+    pub fn reinhard(exposure: f32) -> Self {
+        Self {
+            operator: TonemapOperator::Reinhard,
+            exposure,
+        }
+    }
+
+    pub fn aces(exposure: f32) -> Self {
+        Self {
+            operator: TonemapOperator::Aces,
+            exposure,
+        }
+    }
+
+    /// What tonemapping does to `color`, the same as the shader.
+    pub fn apply(self, color: Color) -> Color {
+        let map = |c: f32| {
+            let linear = c.max(0.0).powf(2.2) * self.exposure;
+            self.operator.map(linear).powf(1.0 / 2.2)
+        };
+        Color::from_rgba(map(color.r), map(color.g), map(color.b), color.a)
+    }
+}
+
+impl Default for Tonemapping {
+    fn default() -> Self {
+        Self::aces(1.0)
+    }
+}
+
+/// Where a point on the curved screen of a [`PostProcessingEffect::Crt`] shows, on the flat
+/// screen underneath, both from 0 to 1, the same as the shader. `None` is off the edge of the
+/// tube. For clicking on things through the curve.
+pub fn crt_distort(uv: Vec2, curvature: f32) -> Option<Vec2> {
+    let centered = uv * 2.0 - 1.0;
+    let curved = centered * (1.0 + curvature * Vec2::new(centered.y, centered.x).powf(2.0));
+    let uv = curved * 0.5 + 0.5;
+    (uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::ONE).all()).then_some(uv)
 }
 
 impl PostProcessingEffect {
+    /// Barely there, for keeping pixel art crisp.
+    pub fn crt_subtle() -> Self {
+        Self::Crt {
+            curvature: 0.05,
+            scanlines: 0.25,
+            scanline_count: 240.0,
+            aperture_grille: 0.1,
+            glow: 0.1,
+            noise: 0.0,
+        }
+    }
+
+    /// A well kept arcade cabinet.
+    pub fn crt_arcade() -> Self {
+        Self::Crt {
+            curvature: 0.15,
+            scanlines: 0.5,
+            scanline_count: 240.0,
+            aperture_grille: 0.3,
+            glow: 0.35,
+            noise: 0.03,
+        }
+    }
+
+    /// A cheap TV that's seen better days.
+    pub fn crt_old_tv() -> Self {
+        Self::Crt {
+            curvature: 0.3,
+            scanlines: 0.65,
+            scanline_count: 200.0,
+            aperture_grille: 0.45,
+            glow: 0.5,
+            noise: 0.15,
+        }
+    }
+
     pub fn apply<T: Surface>(
         &self,
         source: TextureRef,
@@ -39,7 +305,7 @@ impl PostProcessingEffect {
         screen_size: Vec2,
     ) -> anyhow::Result<()> {
         let state = get_state();
-        let display = &state.display;
+        let display = state.display();
 
         match self {
             Self::GaussianBlur { sigma } => {
@@ -48,7 +314,7 @@ impl PostProcessingEffect {
                 let mut temp_fb = SimpleFrameBuffer::new(display, &temp_texture)?;
 
                 // Horizontal pass
-                let program = get_or_create_gaussian_blur_program();
+                let program = get_or_create_gaussian_blur_program()?;
                 let uniforms = uniform! {
                     tex: source.get().gl_texture.sampled(),
                     sigma: *sigma,
@@ -67,7 +333,7 @@ impl PostProcessingEffect {
                 render_fullscreen_quad(target, program.get(), &uniforms)?;
             }
             Self::Pixelate { pixel_size } => {
-                let program = get_or_create_pixelate_program();
+                let program = get_or_create_pixelate_program()?;
                 let uniforms = uniform! {
                     tex: source.get().gl_texture.sampled(),
                     pixel_size: *pixel_size,
@@ -76,7 +342,7 @@ impl PostProcessingEffect {
                 render_fullscreen_quad(target, program.get(), &uniforms)?;
             }
             Self::Saturate(amount) => {
-                let program = get_or_create_saturate_program();
+                let program = get_or_create_saturate_program()?;
                 let uniforms = uniform! {
                     tex: source.get().gl_texture.sampled(),
                     saturation: *amount,
@@ -84,7 +350,7 @@ impl PostProcessingEffect {
                 render_fullscreen_quad(target, program.get(), &uniforms)?;
             }
             Self::HueRotate(degrees) => {
-                let program = get_or_create_hue_rotate_program();
+                let program = get_or_create_hue_rotate_program()?;
                 let uniforms = uniform! {
                     tex: source.get().gl_texture.sampled(),
                     hue_shift: degrees.to_radians(),
@@ -92,7 +358,7 @@ impl PostProcessingEffect {
                 render_fullscreen_quad(target, program.get(), &uniforms)?;
             }
             Self::Brighten(amount) => {
-                let program = get_or_create_brighten_program();
+                let program = get_or_create_brighten_program()?;
                 let uniforms = uniform! {
                     tex: source.get().gl_texture.sampled(),
                     brightness: *amount,
@@ -100,12 +366,12 @@ impl PostProcessingEffect {
                 render_fullscreen_quad(target, program.get(), &uniforms)?;
             }
             Self::Vignette { color, intensity } => {
-                let program = get_or_create_vignette_program();
-                let uniforms = uniform! {
+                let program = get_or_create_vignette_program()?;
+                let uniforms = VignetteUniforms {
                     tex: source.get().gl_texture.sampled(),
-                    vignette_color: color.for_gpu(),
-                    vignette_intensity: *intensity,
-                    screen_size: [screen_size.x, screen_size.y],
+                    color: *color,
+                    intensity: *intensity,
+                    screen_size,
                 };
                 render_fullscreen_quad(target, program.get(), &uniforms)?;
             }
@@ -118,7 +384,7 @@ impl PostProcessingEffect {
                 let bright_texture = create_temp_texture(display, screen_size)?;
                 let mut bright_fb = SimpleFrameBuffer::new(display, &bright_texture)?;
 
-                let bright_program = get_or_create_bright_pass_program();
+                let bright_program = get_or_create_bright_pass_program()?;
                 let uniforms = uniform! {
                     tex: source.get().gl_texture.sampled(),
                     threshold: *threshold,
@@ -129,7 +395,7 @@ impl PostProcessingEffect {
                 let temp_texture = create_temp_texture(display, screen_size)?;
                 let mut temp_fb = SimpleFrameBuffer::new(display, &temp_texture)?;
 
-                let blur_program = get_or_create_gaussian_blur_program();
+                let blur_program = get_or_create_gaussian_blur_program()?;
 
                 // Horizontal blur pass
                 let uniforms = uniform! {
@@ -151,7 +417,7 @@ impl PostProcessingEffect {
                 render_fullscreen_quad(&mut bright_fb, blur_program.get(), &uniforms)?;
 
                 // Step 3: Combine original + blurred bright areas
-                let combine_program = get_or_create_bloom_combine_program();
+                let combine_program = get_or_create_bloom_combine_program()?;
                 let uniforms = uniform! {
                     tex: source.get().gl_texture.sampled(),
                     bloom_tex: bright_texture.sampled(),
@@ -160,7 +426,7 @@ impl PostProcessingEffect {
                 render_fullscreen_quad(target, combine_program.get(), &uniforms)?;
             }
             Self::Contrast(amount) => {
-                let program = get_or_create_contrast_program();
+                let program = get_or_create_contrast_program()?;
                 let uniforms = uniform! {
                     tex: source.get().gl_texture.sampled(),
                     contrast: *amount,
@@ -168,21 +434,21 @@ impl PostProcessingEffect {
                 render_fullscreen_quad(target, program.get(), &uniforms)?;
             }
             Self::Grayscale => {
-                let program = get_or_create_grayscale_program();
+                let program = get_or_create_grayscale_program()?;
                 let uniforms = uniform! {
                     tex: source.get().gl_texture.sampled(),
                 };
                 render_fullscreen_quad(target, program.get(), &uniforms)?;
             }
             Self::Invert => {
-                let program = get_or_create_invert_program();
+                let program = get_or_create_invert_program()?;
                 let uniforms = uniform! {
                     tex: source.get().gl_texture.sampled(),
                 };
                 render_fullscreen_quad(target, program.get(), &uniforms)?;
             }
             Self::ChromaticAberration { strength } => {
-                let program = get_or_create_chromatic_aberration_program();
+                let program = get_or_create_chromatic_aberration_program()?;
                 let uniforms = uniform! {
                     tex: source.get().gl_texture.sampled(),
                     strength: *strength,
@@ -190,28 +456,111 @@ impl PostProcessingEffect {
                 };
                 render_fullscreen_quad(target, program.get(), &uniforms)?;
             }
+            Self::Crt {
+                curvature,
+                scanlines,
+                scanline_count,
+                aperture_grille,
+                glow,
+                noise,
+            } => {
+                let program = get_or_create_crt_program()?;
+                let uniforms = uniform! {
+                    tex: source.get().gl_texture.sampled(),
+                    curvature: *curvature,
+                    scanlines: *scanlines,
+                    scanline_count: *scanline_count,
+                    aperture_grille: *aperture_grille,
+                    glow: *glow,
+                    noise: *noise,
+                    time: state.unscaled_time,
+                    screen_size: [screen_size.x, screen_size.y],
+                };
+                render_fullscreen_quad(target, program.get(), &uniforms)?;
+            }
+            Self::ColorFilter(filter) => {
+                let (blindness, correct) = match filter {
+                    ColorFilter::Simulate(blindness) => (blindness, false),
+                    ColorFilter::Correct(blindness) => (blindness, true),
+                };
+                let program = get_or_create_color_filter_program()?;
+                let uniforms = uniform! {
+                    tex: source.get().gl_texture.sampled(),
+                    // rows go in as columns, which the shader undoes by multiplying on the left
+                    simulation: blindness.matrix(),
+                    correct: correct,
+                };
+                render_fullscreen_quad(target, program.get(), &uniforms)?;
+            }
+            Self::Palette { palette, dither } => {
+                let program = get_or_create_palette_program()?;
+                let dimensions = palette.dimensions();
+                let uniforms = uniform! {
+                    tex: source.get().gl_texture.sampled(),
+                    palette: palette.get().gl_texture.sampled(),
+                    palette_size: (dimensions.x * dimensions.y).min(MAX_PALETTE_COLORS as u32) as i32,
+                    dither: *dither,
+                };
+                render_fullscreen_quad(target, program.get(), &uniforms)?;
+            }
+            Self::Tonemap(tonemapping) => {
+                let program = get_or_create_tonemap_program()?;
+                let uniforms = uniform! {
+                    tex: source.get().gl_texture.sampled(),
+                    operator: tonemapping.operator as i32,
+                    exposure: tonemapping.exposure,
+                };
+                render_fullscreen_quad(target, program.get(), &uniforms)?;
+            }
         }
 
         Ok(())
     }
 }
 
+/// Half float, so bright HDR colors aren't clipped between steps.
 fn create_temp_texture(display: &EngineDisplay, size: Vec2) -> anyhow::Result<Texture2d> {
-    let texture = Texture2d::empty(display, size.x as u32, size.y as u32)?;
+    let texture = Texture2d::empty_with_format(
+        display,
+        UncompressedFloatFormat::F16F16F16F16,
+        MipmapsOption::NoMipmap,
+        size.x as u32,
+        size.y as u32,
+    )?;
     Ok(texture)
 }
 
+#[derive(Uniforms)]
+struct VignetteUniforms<'a> {
+    tex: Sampler<'a, Texture2d>,
+    #[uniform(name = "vignette_color")]
+    color: Color,
+    #[uniform(name = "vignette_intensity")]
+    intensity: f32,
+    screen_size: Vec2,
+}
+
 pub(crate) fn render_fullscreen_quad<T: Surface, U: glium::uniforms::Uniforms>(
     target: &mut T,
     program: &Program,
     uniforms: &U,
+) -> anyhow::Result<()> {
+    render_fullscreen_quad_with_blend(target, program, uniforms, glium::Blend::alpha_blending())
+}
+
+/// Like [`render_fullscreen_quad`], with `Blend::default()` to replace what's there.
+pub(crate) fn render_fullscreen_quad_with_blend<T: Surface, U: glium::uniforms::Uniforms>(
+    target: &mut T,
+    program: &Program,
+    uniforms: &U,
+    blend: glium::Blend,
 ) -> anyhow::Result<()> {
     use crate::shapes_2d::QUAD_INDICES;
     use crate::textures::TexturedVertex2D;
     use glium::{IndexBuffer, VertexBuffer};
 
     let state = get_state();
-    let display = &state.display;
+    let display = state.display();
 
     let vertices = [
         TexturedVertex2D {
@@ -240,7 +589,7 @@ pub(crate) fn render_fullscreen_quad<T: Surface, U: glium::uniforms::Uniforms>(
     )?;
 
     let params = glium::DrawParameters {
-        blend: glium::Blend::alpha_blending(),
+        blend,
         ..Default::default()
     };
 
@@ -263,88 +612,111 @@ static CONTRAST_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
 static GRAYSCALE_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
 static INVERT_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
 static CHROMATIC_ABERRATION_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
-
-fn get_or_create_gaussian_blur_program() -> &'static ProgramRef {
-    GAUSSIAN_BLUR_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(POSTPROCESS_VERTEX_SHADER, GAUSSIAN_BLUR_FRAGMENT_SHADER)
-            .unwrap()
+static COLOR_FILTER_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
+static TONEMAP_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
+static CRT_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
+static PALETTE_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
+
+fn get_or_create_gaussian_blur_program() -> EngineResult<ProgramRef> {
+    cached_program(&GAUSSIAN_BLUR_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, GAUSSIAN_BLUR_FRAGMENT_SHADER)
     })
 }
 
-fn get_or_create_pixelate_program() -> &'static ProgramRef {
-    PIXELATE_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(POSTPROCESS_VERTEX_SHADER, PIXELATE_FRAGMENT_SHADER).unwrap()
+fn get_or_create_pixelate_program() -> EngineResult<ProgramRef> {
+    cached_program(&PIXELATE_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, PIXELATE_FRAGMENT_SHADER)
     })
 }
 
-fn get_or_create_saturate_program() -> &'static ProgramRef {
-    SATURATE_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(POSTPROCESS_VERTEX_SHADER, SATURATE_FRAGMENT_SHADER).unwrap()
+fn get_or_create_saturate_program() -> EngineResult<ProgramRef> {
+    cached_program(&SATURATE_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, SATURATE_FRAGMENT_SHADER)
     })
 }
 
-fn get_or_create_hue_rotate_program() -> &'static ProgramRef {
-    HUE_ROTATE_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(POSTPROCESS_VERTEX_SHADER, HUE_ROTATE_FRAGMENT_SHADER)
-            .unwrap()
+fn get_or_create_hue_rotate_program() -> EngineResult<ProgramRef> {
+    cached_program(&HUE_ROTATE_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, HUE_ROTATE_FRAGMENT_SHADER)
     })
 }
 
-fn get_or_create_brighten_program() -> &'static ProgramRef {
-    BRIGHTEN_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(POSTPROCESS_VERTEX_SHADER, BRIGHTEN_FRAGMENT_SHADER).unwrap()
+fn get_or_create_brighten_program() -> EngineResult<ProgramRef> {
+    cached_program(&BRIGHTEN_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, BRIGHTEN_FRAGMENT_SHADER)
     })
 }
 
-fn get_or_create_vignette_program() -> &'static ProgramRef {
-    VIGNETTE_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(POSTPROCESS_VERTEX_SHADER, VIGNETTE_FRAGMENT_SHADER).unwrap()
+fn get_or_create_vignette_program() -> EngineResult<ProgramRef> {
+    cached_program(&VIGNETTE_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, VIGNETTE_FRAGMENT_SHADER)
     })
 }
 
-fn get_or_create_bright_pass_program() -> &'static ProgramRef {
-    BRIGHT_PASS_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(POSTPROCESS_VERTEX_SHADER, BRIGHT_PASS_FRAGMENT_SHADER)
-            .unwrap()
+fn get_or_create_bright_pass_program() -> EngineResult<ProgramRef> {
+    cached_program(&BRIGHT_PASS_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, BRIGHT_PASS_FRAGMENT_SHADER)
     })
 }
 
-fn get_or_create_bloom_combine_program() -> &'static ProgramRef {
-    BLOOM_COMBINE_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(POSTPROCESS_VERTEX_SHADER, BLOOM_COMBINE_FRAGMENT_SHADER)
-            .unwrap()
+fn get_or_create_bloom_combine_program() -> EngineResult<ProgramRef> {
+    cached_program(&BLOOM_COMBINE_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, BLOOM_COMBINE_FRAGMENT_SHADER)
     })
 }
 
-fn get_or_create_contrast_program() -> &'static ProgramRef {
-    CONTRAST_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(POSTPROCESS_VERTEX_SHADER, CONTRAST_FRAGMENT_SHADER).unwrap()
+fn get_or_create_contrast_program() -> EngineResult<ProgramRef> {
+    cached_program(&CONTRAST_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, CONTRAST_FRAGMENT_SHADER)
     })
 }
 
-fn get_or_create_grayscale_program() -> &'static ProgramRef {
-    GRAYSCALE_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(POSTPROCESS_VERTEX_SHADER, GRAYSCALE_FRAGMENT_SHADER).unwrap()
+fn get_or_create_grayscale_program() -> EngineResult<ProgramRef> {
+    cached_program(&GRAYSCALE_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, GRAYSCALE_FRAGMENT_SHADER)
     })
 }
 
-fn get_or_create_invert_program() -> &'static ProgramRef {
-    INVERT_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(POSTPROCESS_VERTEX_SHADER, INVERT_FRAGMENT_SHADER).unwrap()
+fn get_or_create_invert_program() -> EngineResult<ProgramRef> {
+    cached_program(&INVERT_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, INVERT_FRAGMENT_SHADER)
     })
 }
 
-fn get_or_create_chromatic_aberration_program() -> &'static ProgramRef {
-    CHROMATIC_ABERRATION_PROGRAM.get_or_init(|| {
-        crate::programs::load_program(
+fn get_or_create_chromatic_aberration_program() -> EngineResult<ProgramRef> {
+    cached_program(&CHROMATIC_ABERRATION_PROGRAM, || {
+        load_program(
             POSTPROCESS_VERTEX_SHADER,
             CHROMATIC_ABERRATION_FRAGMENT_SHADER,
         )
-        .unwrap()
     })
 }
 
-const POSTPROCESS_VERTEX_SHADER: &str = r#"
+fn get_or_create_color_filter_program() -> EngineResult<ProgramRef> {
+    cached_program(&COLOR_FILTER_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, COLOR_FILTER_FRAGMENT_SHADER)
+    })
+}
+
+fn get_or_create_crt_program() -> EngineResult<ProgramRef> {
+    cached_program(&CRT_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, CRT_FRAGMENT_SHADER)
+    })
+}
+
+fn get_or_create_palette_program() -> EngineResult<ProgramRef> {
+    cached_program(&PALETTE_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, PALETTE_FRAGMENT_SHADER)
+    })
+}
+
+fn get_or_create_tonemap_program() -> EngineResult<ProgramRef> {
+    cached_program(&TONEMAP_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, TONEMAP_FRAGMENT_SHADER)
+    })
+}
+
+pub(crate) const POSTPROCESS_VERTEX_SHADER: &str = r#"
 #version 140
 in vec2 position;
 in vec2 tex_coords;
@@ -495,8 +867,9 @@ void main() {
     float brightness = dot(tex_color.rgb, vec3(0.2126, 0.7152, 0.0722));
 
     if (brightness > threshold) {
-        // Soft threshold
-        float soft = (brightness - threshold) / (1.0 - threshold);
+        // Soft threshold. HDR thresholds can be over 1, so they ramp up over their own size
+        float range = threshold < 1.0 ? 1.0 - threshold : threshold;
+        float soft = (brightness - threshold) / range;
         color = tex_color * soft;
     } else {
         color = vec4(0.0);
@@ -578,3 +951,151 @@ void main() {
     color = vec4(r, g, b, a);
 }
 "#;
+
+const COLOR_FILTER_FRAGMENT_SHADER: &str = r#"
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+uniform sampler2D tex;
+uniform mat3 simulation;
+uniform bool correct;
+
+void main() {
+    vec4 tex_color = texture(tex, v_tex_coords);
+    vec3 linear = pow(max(tex_color.rgb, 0.0), vec3(2.2));
+    vec3 simulated = linear * simulation;
+
+    vec3 filtered = simulated;
+    if (correct) {
+        vec3 lost = linear - simulated;
+        filtered = linear + vec3(0.0, lost.r * 0.7 + lost.g, lost.r * 0.7 + lost.b);
+    }
+
+    color = vec4(pow(clamp(filtered, 0.0, 1.0), vec3(1.0 / 2.2)), tex_color.a);
+}
+"#;
+
+const TONEMAP_FRAGMENT_SHADER: &str = r#"
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+uniform sampler2D tex;
+// 0 is Reinhard, 1 is ACES
+uniform int operator;
+uniform float exposure;
+
+vec3 aces(vec3 x) {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+void main() {
+    vec4 tex_color = texture(tex, v_tex_coords);
+    vec3 linear = pow(max(tex_color.rgb, 0.0), vec3(2.2)) * exposure;
+
+    vec3 mapped;
+    if (operator == 0) {
+        mapped = linear / (1.0 + linear);
+    } else {
+        mapped = aces(linear);
+    }
+
+    color = vec4(pow(mapped, vec3(1.0 / 2.2)), tex_color.a);
+}
+"#;
+
+const CRT_FRAGMENT_SHADER: &str = r#"
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+uniform sampler2D tex;
+uniform float curvature;
+uniform float scanlines;
+uniform float scanline_count;
+uniform float aperture_grille;
+uniform float glow;
+uniform float noise;
+uniform float time;
+uniform vec2 screen_size;
+
+const float PI = 3.14159265;
+
+float hash(vec2 p) {
+    return fract(sin(dot(p, vec2(12.9898, 78.233))) * 43758.5453);
+}
+
+void main() {
+    // barrel distortion, the same as `crt_distort`
+    vec2 centered = v_tex_coords * 2.0 - 1.0;
+    centered *= 1.0 + curvature * centered.yx * centered.yx;
+    vec2 uv = centered * 0.5 + 0.5;
+    if (uv.x < 0.0 || uv.x > 1.0 || uv.y < 0.0 || uv.y > 1.0) {
+        color = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+
+    vec4 tex_color = texture(tex, uv);
+    vec3 rgb = tex_color.rgb;
+
+    // light bleeding from a ring of nearby pixels, more from brighter ones
+    vec2 pixel = 2.0 / screen_size;
+    vec3 around = vec3(0.0);
+    for (int i = 0; i < 8; i++) {
+        float angle = float(i) * PI / 4.0;
+        around += texture(tex, uv + vec2(cos(angle), sin(angle)) * pixel).rgb;
+    }
+    around /= 8.0;
+    rgb += around * around * glow;
+
+    float line = 0.5 + 0.5 * cos(uv.y * scanline_count * 2.0 * PI);
+    rgb *= 1.0 - scanlines * line;
+
+    // a red, green and blue stripe every three pixels, brightened to make up for the darkening
+    int column = int(mod(gl_FragCoord.x, 3.0));
+    vec3 grille = vec3(1.0 - aperture_grille);
+    grille[column] = 1.0;
+    rgb *= grille * (1.0 + aperture_grille * 0.5);
+
+    rgb += (hash(gl_FragCoord.xy + fract(time) * 100.0) - 0.5) * noise;
+
+    color = vec4(rgb, tex_color.a);
+}
+"#;
+
+const PALETTE_FRAGMENT_SHADER: &str = r#"
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+uniform sampler2D tex;
+uniform sampler2D palette;
+uniform int palette_size;
+uniform float dither;
+
+// a 4x4 ordered dithering pattern, the same as `BAYER_4X4` in palette.rs
+const float BAYER[16] = float[16](
+    0.0, 8.0, 2.0, 10.0, 12.0, 4.0, 14.0, 6.0, 3.0, 11.0, 1.0, 9.0, 15.0, 7.0, 13.0, 5.0
+);
+
+void main() {
+    vec4 tex_color = texture(tex, v_tex_coords);
+    int width = textureSize(palette, 0).x;
+
+    ivec2 pixel = ivec2(mod(gl_FragCoord.xy, 4.0));
+    float offset = (BAYER[pixel.y * 4 + pixel.x] + 0.5) / 16.0 - 0.5;
+    float spacing = 1.0 / pow(float(palette_size), 1.0 / 3.0);
+    vec3 target = tex_color.rgb + offset * dither * spacing;
+
+    vec3 nearest = vec3(0.0);
+    float closest = 1e9;
+    for (int i = 0; i < palette_size; i++) {
+        vec3 entry = texelFetch(palette, ivec2(i % width, i / width), 0).rgb;
+        vec3 difference = entry - target;
+        float distance = dot(difference, difference);
+        if (distance < closest) {
+            closest = distance;
+            nearest = entry;
+        }
+    }
+
+    color = vec4(nearest, tex_color.a);
+}
+"#;
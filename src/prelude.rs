@@ -1,55 +1,73 @@
+//! One glob import for games: everything from [`gfx`](crate::gfx), [`input`](crate::input),
+//! [`window`](crate::window) and [`audio`](crate::audio), the physics namespace itself, and the math types.
+
 pub use crate::api::*;
-pub use crate::camera::controllers::orbit::OrbitCameraController;
-pub use crate::camera::controllers::pan::PanningCameraController;
+pub use crate::audio::*;
 pub use crate::collisions;
 pub use crate::collisions::IntersectsWith;
-pub use crate::color::Color;
+pub use crate::collisions::boolean::BooleanOps;
+pub use crate::collisions::contact::CollideWith;
+pub use crate::collisions::sweep::Sweep;
+pub use crate::collisions3d;
+pub use crate::ecs::{
+    Entities, Entity, Query, despawn, entities_mut, get_component, get_component_mut,
+    insert_component, is_alive, query, remove_component, spawn,
+};
+#[cfg(feature = "editor")]
+pub use crate::editor;
+pub use crate::error::{EngineError, EngineResult, reset_error_handler, set_error_handler};
+pub use crate::events::{
+    ConfigReloaded, EventBus, FileDropped, ImeComposition, SoundFinished, WindowFocused,
+    WindowResized, emit, events, notify_when_finished,
+};
+pub use crate::gfx::*;
+pub use crate::input::*;
+// used to be `rapier2d::prelude`, which `physics` still re-exports all of
+pub use crate::physics;
 // pub use crate::color::schemes::ColorScheme;
 pub use crate::animation::*;
+pub use crate::config::{EngineConfig, config, config_mut, reload_config, save_config};
 #[cfg(feature = "debugging")]
 pub use crate::debugging::grid::create_infinite_grid;
 #[cfg(feature = "debugging")]
 pub use crate::debugging::*;
-pub use crate::draw_queue_2d::MaterialVertex3D;
-pub use crate::image::Image;
 pub use crate::image::*;
-pub use crate::include_program;
 pub use crate::init;
-pub use crate::input::*;
-pub use crate::materials::*;
+pub use crate::init_headless;
+pub use crate::init_headless_with_config;
+pub use crate::init_with_config;
+pub use crate::net;
 pub use crate::next_frame;
-pub use crate::object_3d::*;
+pub use crate::pathfinding;
 pub use crate::physics::PhysicsWorld;
-pub use crate::post_processing::*;
-pub use crate::programs::load_program;
-pub use crate::shapes_2d::*;
-pub use crate::shapes_3d::*;
-pub use crate::text_rendering::*;
-pub use crate::textures::atlas::*;
-pub use crate::textures::load_texture;
-pub use crate::transform::*;
+#[cfg(feature = "debugging")]
+pub use crate::profile_scope;
+pub use crate::scheduler::{
+    Scheduler, TaskHandle, after, cancel, every, is_scheduled, next_update, start_routine, wait,
+    wait_until,
+};
+pub use crate::steering;
+pub use crate::storage;
+pub use crate::tween::{Tween, Tweens, tween};
 pub use crate::utils::EngineCreate;
 pub use crate::utils::usize_rect::USizeRect;
 pub use crate::utils::*;
+pub use crate::window::*;
 pub use anyhow;
 pub use bevy_math;
 pub use bevy_math::Quat;
 pub use bevy_math::ops::*;
-pub use bevy_math::prelude::{Mat2, Mat3, Mat4, Vec2, Vec3, Vec4};
+pub use bevy_math::prelude::{IVec2, Mat2, Mat3, Mat4, UVec2, Vec2, Vec3, Vec4};
 pub use bevy_math::prelude::{mat2, mat3, mat4, vec2, vec3, vec4};
-pub use egui_glium::egui_winit::egui;
 #[cfg(feature = "debugging")]
 pub use egui_plot;
-pub use engine_4_macros::{actions, bind};
 pub use glium;
 pub use glium::Texture2d;
-pub use glium::winit::event::MouseButton;
-pub use glium::winit::keyboard::{Key, KeyCode, NamedKey};
 pub use image;
 pub use image::ImageFormat;
 pub use include_folder::include_folder;
 pub use log;
 pub use nalgebra::vector;
-pub use rapier2d::prelude as physics;
 pub use rapier2d::prelude::{Collider, ColliderBuilder, RigidBody, RigidBodyBuilder};
-pub use tunes;
+pub use serde;
+pub use serde::{Deserialize, Serialize};
@@ -1,7 +1,9 @@
+use std::sync::OnceLock;
+
 use engine_4_macros::gen_ref_type;
 use glium::Program;
 
-use crate::{EngineDisplay, EngineStorage, get_state};
+use crate::{EngineDisplay, EngineStorage, checked_state, error::EngineResult, get_state};
 
 macro_rules! include_program_internal {
     ($display: tt, $vertex: literal, $fragment: literal) => {{
@@ -20,13 +22,20 @@ macro_rules! include_program {
     }};
 }
 
-pub const FLAT_PROGRAM: ProgramRef = ProgramRef(0);
-pub const CIRCLE_PROGRAM: ProgramRef = ProgramRef(1);
-pub const TEXTURED_PROGRAM: ProgramRef = ProgramRef(2);
-pub const FLAT_3D_PROGRAM: ProgramRef = ProgramRef(3);
-pub const GOURAUD_3D_PROGRAM: ProgramRef = ProgramRef(4);
-pub const TEXTURED_3D_PROGRAM: ProgramRef = ProgramRef(5);
-pub const BLINN_PHONG_3D_PROGRAM: ProgramRef = ProgramRef(6);
+pub const FLAT_PROGRAM: ProgramRef = ProgramRef::from_raw(0, 0);
+pub const CIRCLE_PROGRAM: ProgramRef = ProgramRef::from_raw(1, 0);
+pub const TEXTURED_PROGRAM: ProgramRef = ProgramRef::from_raw(2, 0);
+pub const FLAT_3D_PROGRAM: ProgramRef = ProgramRef::from_raw(3, 0);
+pub const GOURAUD_3D_PROGRAM: ProgramRef = ProgramRef::from_raw(4, 0);
+pub const TEXTURED_3D_PROGRAM: ProgramRef = ProgramRef::from_raw(5, 0);
+pub const BLINN_PHONG_3D_PROGRAM: ProgramRef = ProgramRef::from_raw(6, 0);
+pub const TERRAIN_3D_PROGRAM: ProgramRef = ProgramRef::from_raw(7, 0);
+pub const SDF_PROGRAM: ProgramRef = ProgramRef::from_raw(8, 0);
+pub const PICK_PROGRAM: ProgramRef = ProgramRef::from_raw(9, 0);
+pub const PICK_3D_PROGRAM: ProgramRef = ProgramRef::from_raw(10, 0);
+pub const PBR_3D_PROGRAM: ProgramRef = ProgramRef::from_raw(11, 0);
+pub const PARTICLE_3D_PROGRAM: ProgramRef = ProgramRef::from_raw(12, 0);
+pub const DEBUG_3D_PROGRAM: ProgramRef = ProgramRef::from_raw(13, 0);
 
 gen_ref_type!(Program, ProgramRef, programs);
 
@@ -39,57 +48,119 @@ pub(crate) fn init_programs(
         "../assets/shaders/flat/vertex.glsl",
         "../assets/shaders/flat/fragment.glsl"
     )?;
-    storage.programs.push(program);
+    storage.programs.insert(program);
 
     let program = include_program_internal!(
         display,
         "../assets/shaders/circle/vertex.glsl",
         "../assets/shaders/circle/fragment.glsl"
     )?;
-    storage.programs.push(program);
+    storage.programs.insert(program);
 
     let program = include_program_internal!(
         display,
         "../assets/shaders/sprite/vertex.glsl",
         "../assets/shaders/sprite/fragment.glsl"
     )?;
-    storage.programs.push(program);
+    storage.programs.insert(program);
 
     let program = include_program_internal!(
         display,
         "../assets/shaders/flat_3d/vertex.glsl",
         "../assets/shaders/flat_3d/fragment.glsl"
     )?;
-    storage.programs.push(program);
+    storage.programs.insert(program);
 
     let program = include_program_internal!(
         display,
         "../assets/shaders/gourad/vertex.glsl",
         "../assets/shaders/gourad/fragment.glsl"
     )?;
-    storage.programs.push(program);
+    storage.programs.insert(program);
 
     let program = include_program_internal!(
         display,
         "../assets/shaders/textured/vertex.glsl",
         "../assets/shaders/textured/fragment.glsl"
     )?;
-    storage.programs.push(program);
+    storage.programs.insert(program);
 
     let program = include_program_internal!(
         display,
         "../assets/shaders/blinn_phong/vertex.glsl",
         "../assets/shaders/blinn_phong/fragment.glsl"
     )?;
-    storage.programs.push(program);
+    storage.programs.insert(program);
+
+    let program = include_program_internal!(
+        display,
+        "../assets/shaders/terrain/vertex.glsl",
+        "../assets/shaders/terrain/fragment.glsl"
+    )?;
+    storage.programs.insert(program);
+
+    let program = include_program_internal!(
+        display,
+        "../assets/shaders/sdf_text/vertex.glsl",
+        "../assets/shaders/sdf_text/fragment.glsl"
+    )?;
+    storage.programs.insert(program);
+
+    let program = include_program_internal!(
+        display,
+        "../assets/shaders/pick/vertex.glsl",
+        "../assets/shaders/pick/fragment.glsl"
+    )?;
+    storage.programs.insert(program);
+
+    let program = include_program_internal!(
+        display,
+        "../assets/shaders/pick_3d/vertex.glsl",
+        "../assets/shaders/pick_3d/fragment.glsl"
+    )?;
+    storage.programs.insert(program);
+
+    let program = include_program_internal!(
+        display,
+        "../assets/shaders/pbr/vertex.glsl",
+        "../assets/shaders/pbr/fragment.glsl"
+    )?;
+    storage.programs.insert(program);
+
+    let program = include_program_internal!(
+        display,
+        "../assets/shaders/particle_3d/vertex.glsl",
+        "../assets/shaders/particle_3d/fragment.glsl"
+    )?;
+    storage.programs.insert(program);
+
+    let program = include_program_internal!(
+        display,
+        "../assets/shaders/debug_3d/vertex.glsl",
+        "../assets/shaders/debug_3d/fragment.glsl"
+    )?;
+    storage.programs.insert(program);
+    storage.programs.mark_builtin();
 
     Ok(())
 }
 
-pub fn load_program(vertex: &str, fragment: &str) -> anyhow::Result<ProgramRef> {
-    let state = get_state();
-    let program = Program::from_source(&state.display, vertex, fragment, None)?;
-    let id = state.storage.programs.len();
-    state.storage.programs.push(program);
-    Ok(ProgramRef(id))
+/// One of the engine's own shaders, compiled the first time it's needed. If it doesn't compile
+/// the error is returned, and it's tried again next time.
+pub(crate) fn cached_program(
+    cache: &OnceLock<ProgramRef>,
+    load: impl FnOnce() -> EngineResult<ProgramRef>,
+) -> EngineResult<ProgramRef> {
+    if let Some(program) = cache.get() {
+        return Ok(*program);
+    }
+    let program = load()?;
+    Ok(*cache.get_or_init(|| program))
+}
+
+pub fn load_program(vertex: &str, fragment: &str) -> EngineResult<ProgramRef> {
+    let state = checked_state()?;
+    let program = Program::from_source(state.display(), vertex, fragment, None)?;
+    let (index, generation) = state.storage.programs.insert(program);
+    Ok(ProgramRef::from_raw(index, generation))
 }
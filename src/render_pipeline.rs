@@ -1,26 +1,42 @@
 use bevy_math::{Mat4, UVec2, Vec2, Vec3};
 use engine_4_macros::gen_ref_type;
-use glium::{Surface, framebuffer::SimpleFrameBuffer, texture::DepthTexture2d, uniform};
+use glium::{
+    Surface, Texture2d,
+    framebuffer::SimpleFrameBuffer,
+    texture::{DepthStencilTexture2d, MipmapsOption, UncompressedFloatFormat},
+    uniform,
+};
 use log::warn;
 
 use crate::{
-    EngineState, api::empty_render_texture, camera::Cameras, color::Color,
-    draw_queue_2d::DrawQueue2D, draw_queue_3d::DrawQueue3D, get_state,
-    post_processing::PostProcessingEffect, programs::ProgramRef, textures::TextureRef,
+    EngineState,
+    api::{empty_hdr_render_texture, empty_render_texture},
+    background::BackgroundLayer,
+    camera::Cameras,
+    color::Color,
+    draw_queue_2d::{DrawQueue2D, SurfaceDrawTarget},
+    draw_queue_3d::DrawQueue3D,
+    error::OrReport,
+    get_state,
+    image::Image,
+    outlines::draw_outlines,
+    post_processing::PostProcessingEffect,
+    programs::ProgramRef,
+    textures::TextureRef,
 };
 
 pub struct RenderTexture {
     pub dimensions: UVec2,
     pub color_texture: TextureRef,
-    pub depth_texture: DepthTexture2d,
+    pub depth_texture: DepthStencilTexture2d,
 }
 
 impl RenderTexture {
     pub fn framebuffer(&mut self) -> SimpleFrameBuffer<'_> {
         let state = get_state();
         let texture = self.color_texture.get();
-        SimpleFrameBuffer::with_depth_buffer(
-            &state.display,
+        SimpleFrameBuffer::with_depth_stencil_buffer(
+            state.display(),
             &texture.gl_texture,
             &self.depth_texture,
         )
@@ -38,6 +54,12 @@ impl RenderTextureRef {
     pub fn framebuffer(&self) -> SimpleFrameBuffer<'_> {
         self.get_mut().framebuffer()
     }
+
+    /// What's on the render texture. Drawing happens at the end of the frame, so this is what
+    /// was drawn to it last frame.
+    pub fn download(&self) -> Image {
+        self.get().color_texture.download()
+    }
 }
 
 pub enum RenderTarget {
@@ -49,7 +71,11 @@ pub struct RenderPipeline {
     pub steps: Vec<RenderStep>,
     pub output: RenderTarget,
     pub clear_color: Option<Color>,
+    /// drawn right after clearing, before any of the draw queues
+    pub background: Vec<BackgroundLayer>,
     pub camera_override: Option<Cameras>,
+    /// Applied once everything is drawn, to every pass, like accessibility filters.
+    pub final_effects: Vec<PostProcessingEffect>,
 }
 
 // i dont care
@@ -63,6 +89,8 @@ pub struct DrawQueues {
     pub draw_queue_2d: DrawQueue2D,
     pub world_draw_queue_2d: DrawQueue2D,
     pub draw_queue_3d: DrawQueue3D,
+    /// Screen space, drawn after everything else.
+    pub overlay_draw_queue_2d: DrawQueue2D,
 }
 
 impl DrawQueues {
@@ -70,11 +98,231 @@ impl DrawQueues {
         let draw_queue_2d = DrawQueue2D::empty();
         let world_draw_queue_2d = DrawQueue2D::empty();
         let draw_queue_3d = DrawQueue3D::empty();
+        let overlay_draw_queue_2d = DrawQueue2D::empty();
 
         Self {
             draw_queue_2d,
             draw_queue_3d,
             world_draw_queue_2d,
+            overlay_draw_queue_2d,
+        }
+    }
+}
+
+/// The parts of a frame, each drawn with its own camera. Within a [`RenderPipeline`] they're
+/// drawn in this order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RenderPass {
+    /// 3D, then 2D drawn in world space.
+    World,
+    /// 2D drawn in screen space, like HUDs and menus.
+    Ui,
+    /// 2D drawn in screen space inside of [`with_overlay`](crate::gfx::with_overlay), on top
+    /// of everything else, like fades, cursors and debug text.
+    Overlay,
+}
+
+impl RenderPass {
+    pub const ALL: [RenderPass; 3] = [RenderPass::World, RenderPass::Ui, RenderPass::Overlay];
+
+    /// The world is post processed and shakes, and the UI and overlay do neither.
+    pub fn default_settings(self) -> PassSettings {
+        match self {
+            RenderPass::World => PassSettings {
+                post_processing: true,
+                camera_shake: true,
+            },
+            RenderPass::Ui | RenderPass::Overlay => PassSettings {
+                post_processing: false,
+                camera_shake: false,
+            },
+        }
+    }
+}
+
+/// What a [`RenderPass`] takes part in. Set with
+/// [`set_pass_settings`](crate::gfx::set_pass_settings).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PassSettings {
+    /// Whether post processing effects like bloom apply to it. Passes that aren't post
+    /// processed are drawn on top of the ones that are.
+    pub post_processing: bool,
+    /// Whether [`shake_camera`](crate::gfx::shake_camera) moves it.
+    pub camera_shake: bool,
+}
+
+/// What's needed to draw each pass, worked out once per [`RenderPipeline::draw_on`].
+struct PassState {
+    settings: [PassSettings; 3],
+    /// In pixels.
+    shake: Vec2,
+    screen_size: Vec2,
+    is_texture_target: bool,
+    /// Whether post processed passes are drawn in HDR, to be tonemapped after.
+    hdr: bool,
+}
+
+impl PassState {
+    /// Moves a projection by the shake, if the pass shakes.
+    fn shaken(&self, pass: RenderPass, projection: Mat4) -> Mat4 {
+        if !self.settings[pass as usize].camera_shake || self.shake == Vec2::ZERO {
+            return projection;
+        }
+        let offset = self.shake / self.screen_size * 2.0;
+        Mat4::from_translation(Vec3::new(offset.x, -offset.y, 0.0)) * projection
+    }
+
+    /// Shaken, and flipped for texture targets.
+    fn projection_2d(&self, pass: RenderPass, projection: Mat4) -> Mat4 {
+        let projection = self.shaken(pass, projection);
+        if self.is_texture_target {
+            return Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0)) * projection;
+        }
+        projection
+    }
+
+    fn draw<T: Surface>(
+        &self,
+        pass: RenderPass,
+        target: &mut T,
+        draw_queues: &mut DrawQueues,
+        cameras: &mut Cameras,
+    ) {
+        match pass {
+            RenderPass::World => {
+                let view_proj = self.shaken(pass, cameras.d3.view_proj());
+                let hdr = self.hdr && self.settings[pass as usize].post_processing;
+                draw_queues.draw_queue_3d.draw(target, &view_proj, hdr);
+                target.clear_depth(1.0);
+
+                let projection = self.projection_2d(pass, cameras.d2.projection_matrix());
+                draw_queues.world_draw_queue_2d.draw(target, &projection);
+            }
+            RenderPass::Ui => {
+                let projection = self.projection_2d(pass, cameras.flat);
+                draw_queues.draw_queue_2d.draw(target, &projection);
+            }
+            RenderPass::Overlay => {
+                let projection = self.projection_2d(pass, cameras.flat);
+                draw_queues.overlay_draw_queue_2d.draw(target, &projection);
+            }
+        }
+        target.clear_depth(1.0);
+    }
+
+    /// Draws everything outlined in `steps` into a new mask, or `None` if nothing is.
+    fn draw_outline_mask(&self, steps: &[RenderStep], cameras: &mut Cameras) -> Option<Texture2d> {
+        let outlined = steps.iter().any(|step| match step {
+            RenderStep::Drawing(queues) => {
+                queues.draw_queue_3d.has_outlines()
+                    || queues.world_draw_queue_2d.has_outlines()
+                    || queues.draw_queue_2d.has_outlines()
+                    || queues.overlay_draw_queue_2d.has_outlines()
+            }
+            RenderStep::PostProcessing(_) => false,
+        });
+        if !outlined {
+            return None;
+        }
+
+        let display = get_state().display();
+        let (width, height) = (self.screen_size.x as u32, self.screen_size.y as u32);
+        let mask = Texture2d::empty_with_format(
+            display,
+            UncompressedFloatFormat::U8U8U8U8,
+            MipmapsOption::NoMipmap,
+            width,
+            height,
+        )
+        .ok()?;
+        let depth = DepthStencilTexture2d::empty(display, width, height).ok()?;
+        let mut target =
+            SimpleFrameBuffer::with_depth_stencil_buffer(display, &mask, &depth).ok()?;
+        target.clear_all((0.0, 0.0, 0.0, 0.0), 1.0, 0);
+
+        for step in steps {
+            let RenderStep::Drawing(queues) = step else {
+                continue;
+            };
+
+            let view_proj = self.shaken(RenderPass::World, cameras.d3.view_proj());
+            queues
+                .draw_queue_3d
+                .draw_for_outlining(&mut target, &view_proj);
+            target.clear_depth(1.0);
+
+            let queues_2d = [
+                (
+                    RenderPass::World,
+                    &queues.world_draw_queue_2d,
+                    cameras.d2.projection_matrix(),
+                ),
+                (RenderPass::Ui, &queues.draw_queue_2d, cameras.flat),
+                (
+                    RenderPass::Overlay,
+                    &queues.overlay_draw_queue_2d,
+                    cameras.flat,
+                ),
+            ];
+            for (pass, queue, projection) in queues_2d {
+                queue.for_outlining().draw_to(
+                    &mut SurfaceDrawTarget::for_picking(&mut target),
+                    &self.projection_2d(pass, projection),
+                );
+                target.clear_depth(1.0);
+            }
+        }
+
+        Some(mask)
+    }
+
+    /// Draws the passes that were held back, pass by pass, each in the order it was drawn in.
+    fn draw_deferred<T: Surface>(
+        &self,
+        target: &mut T,
+        mut deferred: Vec<DrawQueues>,
+        cameras: &mut Cameras,
+        is_deferred: impl Fn(RenderPass) -> bool,
+    ) {
+        for pass in RenderPass::ALL {
+            if is_deferred(pass) {
+                for draw_queues in &mut deferred {
+                    self.draw(pass, target, draw_queues, cameras);
+                }
+            }
+        }
+    }
+}
+
+/// Two textures to draw into and post process back and forth between, or `None` if they
+/// couldn't be made, after reporting why.
+fn render_texture_pair(
+    (width, height): (u32, u32),
+    empty: fn(u32, u32) -> anyhow::Result<RenderTexture>,
+) -> Option<(RenderTexture, RenderTexture)> {
+    let a = empty(width, height).or_report()?;
+    let Some(b) = empty(width, height).or_report() else {
+        a.color_texture.remove();
+        return None;
+    };
+    Some((a, b))
+}
+
+/// Applies each effect from `a` into `b`, then swaps them, so the result ends up in `a`.
+fn apply_effects(
+    effects: Vec<PostProcessingEffect>,
+    a: &mut RenderTexture,
+    b: &mut RenderTexture,
+    screen_size: Vec2,
+) {
+    for effect in effects {
+        // an effect that fails is skipped
+        let applied = effect
+            .apply(a.color_texture, &mut b.framebuffer(), screen_size)
+            .or_report();
+
+        if applied.is_some() {
+            std::mem::swap(a, b);
         }
     }
 }
@@ -115,6 +363,10 @@ impl RenderPipeline {
         &mut self.draw_queues().world_draw_queue_2d
     }
 
+    pub fn overlay_draw_queue_2d(&mut self) -> &mut DrawQueue2D {
+        &mut self.draw_queues().overlay_draw_queue_2d
+    }
+
     pub fn draw_queue_3d(&mut self) -> &mut DrawQueue3D {
         &mut self.draw_queues().draw_queue_3d
     }
@@ -140,26 +392,40 @@ impl RenderPipeline {
             steps: vec![RenderStep::Drawing(DrawQueues::empty())],
             output,
             clear_color: None,
+            background: Vec::new(),
             camera_override,
+            final_effects: Vec::new(),
         }
     }
 
     pub fn draw(&mut self) {
         let state = get_state();
+        let Some(context) = &mut state.window_context else {
+            // headless, so there's nothing to draw on
+            return;
+        };
 
         match self.output {
             RenderTarget::Screen => {
-                self.draw_on(&mut state.frame.take().unwrap_or_else(|| state.display.draw()));
+                crate::picking::draw_picking_buffer(self);
+                self.draw_on(
+                    &mut context
+                        .frame
+                        .take()
+                        .unwrap_or_else(|| context.display.draw()),
+                );
             }
             RenderTarget::Texture(rt) => {
                 let rt_mut = rt.get_mut();
                 let texture = rt_mut.color_texture.get();
-                let mut framebuffer = SimpleFrameBuffer::with_depth_buffer(
-                    &state.display,
+                let Some(mut framebuffer) = SimpleFrameBuffer::with_depth_stencil_buffer(
+                    &context.display,
                     &texture.gl_texture,
                     &rt_mut.depth_texture,
                 )
-                .unwrap();
+                .or_report() else {
+                    return;
+                };
                 self.draw_on(&mut framebuffer);
             }
         }
@@ -169,121 +435,189 @@ impl RenderPipeline {
         let state = get_state();
         let mut cameras = self.cameras();
         let is_texture_target = matches!(self.output, RenderTarget::Texture(_));
+        let dimensions = frame.get_dimensions();
+        let screen_size = Vec2::new(dimensions.0 as f32, dimensions.1 as f32);
+
+        // render textures have their own cameras, so they don't shake along with the screen
+        let shake = match self.output {
+            RenderTarget::Screen => state.camera_shake.offset(state.unscaled_time),
+            RenderTarget::Texture(_) => Vec2::ZERO,
+        };
+        let mut hdr = match self.output {
+            RenderTarget::Screen => state.hdr,
+            RenderTarget::Texture(_) => None,
+        };
 
-        let has_post_processing = self
+        let mut targets = None;
+        if hdr.is_some() {
+            targets = render_texture_pair(dimensions, empty_hdr_render_texture);
+            // without somewhere to keep colors over 1 there's nothing to tonemap, so it's drawn
+            // like HDR is off
+            if targets.is_none() {
+                hdr = None;
+            }
+        }
+        let has_effect_steps = self
             .steps
             .iter()
             .any(|step| matches!(step, RenderStep::PostProcessing(_)));
+        if targets.is_none() && (has_effect_steps || !self.final_effects.is_empty()) {
+            targets = render_texture_pair(dimensions, empty_render_texture);
+        }
 
-        if has_post_processing {
-            let dimensions = frame.get_dimensions();
-
-            let mut a = empty_render_texture(dimensions.0, dimensions.1).unwrap();
-            let mut b = empty_render_texture(dimensions.0, dimensions.1).unwrap();
+        let passes = PassState {
+            settings: state.pass_settings,
+            shake,
+            screen_size,
+            is_texture_target,
+            hdr: hdr.is_some(),
+        };
 
-            if let Some(c) = self.clear_color {
-                a.framebuffer().clear_color(c.r, c.g, c.b, c.a);
-                b.framebuffer().clear_color(c.r, c.g, c.b, c.a);
+        // tonemapping is post processing too, so the passes that skip it stay out of HDR
+        let has_post_processing_steps = targets.is_some() && (hdr.is_some() || has_effect_steps);
+        // passes that aren't post processed are drawn after the ones that are, on top of them,
+        // and the overlay is always drawn last
+        let is_deferred = |pass: RenderPass| {
+            if has_post_processing_steps {
+                !passes.settings[pass as usize].post_processing
             } else {
-                a.framebuffer().clear_color(0.0, 0.0, 0.0, 1.0);
-                b.framebuffer().clear_color(0.0, 0.0, 0.0, 1.0);
+                pass == RenderPass::Overlay
             }
+        };
+
+        let c = self.clear_color.unwrap_or(state.config.clear_color);
+        let mut deferred = Vec::new();
+        let outline_mask = match self.output {
+            RenderTarget::Screen => passes.draw_outline_mask(&self.steps, &mut cameras),
+            RenderTarget::Texture(_) => None,
+        };
+
+        if let Some((mut a, mut b)) = targets {
+            a.framebuffer().clear_color(c.r, c.g, c.b, c.a);
+            b.framebuffer().clear_color(c.r, c.g, c.b, c.a);
             a.framebuffer().clear_depth(1.0);
             b.framebuffer().clear_depth(1.0);
 
+            self.draw_background_to(&mut a.framebuffer(), is_texture_target);
+            self.draw_skybox_to(&mut a.framebuffer(), &mut cameras);
+
             for step in std::mem::take(&mut self.steps) {
                 match step {
-                    RenderStep::Drawing(draw_queues) => {
-                        self.draw_queues_to(
-                            &mut a.framebuffer(),
-                            draw_queues,
-                            &mut cameras,
-                            is_texture_target,
-                        );
+                    RenderStep::Drawing(mut draw_queues) => {
+                        for pass in RenderPass::ALL {
+                            if !is_deferred(pass) {
+                                passes.draw(
+                                    pass,
+                                    &mut a.framebuffer(),
+                                    &mut draw_queues,
+                                    &mut cameras,
+                                );
+                            }
+                        }
+                        deferred.push(draw_queues);
                     }
                     RenderStep::PostProcessing(effects) => {
-                        for effect in effects.0 {
-                            effect
-                                .apply(
-                                    a.color_texture,
-                                    &mut b.framebuffer(),
-                                    Vec2::new(dimensions.0 as f32, dimensions.1 as f32),
-                                )
-                                .unwrap();
-
-                            std::mem::swap(&mut a, &mut b);
-                        }
+                        apply_effects(effects.0, &mut a, &mut b, screen_size);
                     }
                 }
             }
 
+            if let Some(tonemapping) = hdr {
+                apply_effects(
+                    vec![PostProcessingEffect::Tonemap(tonemapping)],
+                    &mut a,
+                    &mut b,
+                    screen_size,
+                );
+            }
+
+            if let Some(mask) = &outline_mask {
+                draw_outlines(&mut a.framebuffer(), mask, screen_size);
+            }
+
+            a.framebuffer().clear_depth(1.0);
+            passes.draw_deferred(&mut a.framebuffer(), deferred, &mut cameras, is_deferred);
+            apply_effects(
+                std::mem::take(&mut self.final_effects),
+                &mut a,
+                &mut b,
+                screen_size,
+            );
+
             self.draw_texture_to_target(frame, a.color_texture);
 
-            state.storage.textures.pop();
-            state.storage.textures.pop();
+            a.color_texture.remove();
+            b.color_texture.remove();
         } else {
-            if let Some(c) = self.clear_color {
-                frame.clear_color(c.r, c.g, c.b, c.a);
-            } else {
-                frame.clear_color(0.0, 0.0, 0.0, 1.0);
-            }
+            frame.clear_color(c.r, c.g, c.b, c.a);
             frame.clear_depth(1.0);
 
+            self.draw_background_to(frame, is_texture_target);
+            self.draw_skybox_to(frame, &mut cameras);
+
             for step in std::mem::take(&mut self.steps) {
                 match step {
-                    RenderStep::Drawing(draw_queues) => {
-                        self.draw_queues_to(frame, draw_queues, &mut cameras, is_texture_target);
-                    }
-                    RenderStep::PostProcessing(_) => {
-                        unreachable!();
+                    RenderStep::Drawing(mut draw_queues) => {
+                        for pass in RenderPass::ALL {
+                            if !is_deferred(pass) {
+                                passes.draw(pass, frame, &mut draw_queues, &mut cameras);
+                            }
+                        }
+                        deferred.push(draw_queues);
                     }
+                    // only when the textures for it couldn't be made, which has been reported
+                    RenderStep::PostProcessing(_) => {}
                 }
             }
+
+            if let Some(mask) = &outline_mask {
+                draw_outlines(frame, mask, screen_size);
+            }
+
+            frame.clear_depth(1.0);
+            passes.draw_deferred(frame, deferred, &mut cameras, is_deferred);
         }
     }
 
-    fn draw_queues_to<T: Surface>(
-        &self,
-        target: &mut T,
-        mut draw_queues: DrawQueues,
-        cameras: &mut Cameras,
-        is_texture_target: bool,
-    ) {
-        let view_proj = cameras.d3.view_proj();
-        draw_queues.draw_queue_3d.draw(target, &view_proj);
-        target.clear_depth(1.0);
+    fn draw_background_to<T: Surface>(&mut self, target: &mut T, is_texture_target: bool) {
+        let dimensions = target.get_dimensions();
+        let screen_size = Vec2::new(dimensions.0 as f32, dimensions.1 as f32);
 
-        let mut projection = cameras.d2.projection_matrix();
-        if is_texture_target {
-            projection = Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0)) * projection;
+        for layer in std::mem::take(&mut self.background) {
+            layer
+                .draw(target, screen_size, is_texture_target)
+                .or_report();
         }
-        draw_queues.world_draw_queue_2d.draw(target, &projection);
-        target.clear_depth(1.0);
+    }
 
-        let mut flat_projection = cameras.flat;
-        if is_texture_target {
-            flat_projection = Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0)) * flat_projection;
+    fn draw_skybox_to<T: Surface>(&self, target: &mut T, cameras: &mut Cameras) {
+        let state = get_state();
+        if let Some(skybox) = state.skybox {
+            skybox.draw(target, cameras.d3.view_proj()).or_report();
+        } else if let Some(sky) = state.sky_gradient {
+            sky.draw(target, cameras.d3.view_proj()).or_report();
         }
-        draw_queues.draw_queue_2d.draw(target, &flat_projection);
-        target.clear_depth(1.0);
     }
 
     fn draw_texture_to_target<T: Surface>(&self, target: &mut T, texture: TextureRef) {
         use crate::post_processing::render_fullscreen_quad;
-        use crate::programs::load_program;
+        use crate::programs::{cached_program, load_program};
 
         static COPY_PROGRAM: std::sync::OnceLock<ProgramRef> = std::sync::OnceLock::new();
-        let program = COPY_PROGRAM.get_or_init(|| {
+        let Some(program) = cached_program(&COPY_PROGRAM, || {
             let vertex_shader = include_str!("../assets/shaders/copy/vertex.glsl");
             let fragment_shader = include_str!("../assets/shaders/copy/fragment.glsl");
-            load_program(vertex_shader, fragment_shader).unwrap()
-        });
+            load_program(vertex_shader, fragment_shader)
+        })
+        .or_report() else {
+            return;
+        };
 
         let uniforms = uniform! {
-            tex: texture.get().gl_texture.sampled()
+            tex: texture.get().uniform(Default::default())
         };
 
-        render_fullscreen_quad(target, program.get(), &uniforms).unwrap();
+        render_fullscreen_quad(target, program.get(), &uniforms).or_report();
     }
 
     pub fn screen() -> Self {
@@ -292,7 +626,11 @@ impl RenderPipeline {
 }
 
 impl EngineState {
+    /// The overlay's queue inside of `with_overlay`.
     pub fn draw_queue_2d(&mut self) -> &mut DrawQueue2D {
+        if self.drawing_overlay {
+            return self.current_render_pipeline().overlay_draw_queue_2d();
+        }
         self.current_render_pipeline().draw_queue_2d()
     }
 
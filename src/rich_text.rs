@@ -0,0 +1,413 @@
+//! Text with mixed colors, faces, sizes and inline icons, drawn in one call. Good for
+//! dialogue that highlights keywords or shows button prompts.
+
+use bevy_math::{Vec2, vec2};
+
+use crate::{
+    api::{default_font, ui_scaling},
+    color::{Color, theme::theme},
+    draw_queue_2d::DrawQueue2D,
+    get_state,
+    prelude::Transform2D,
+    text_rendering::{
+        FontRef, TextDimensions, TextDrawParams, TextLayout, chain_fonts, draw_layout,
+        layout_text_with_fallbacks,
+    },
+    textures::TextureRef,
+};
+
+/// A piece of rich text. Anything left as `None` comes from the [`TextDrawParams`] it's drawn
+/// with.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub color: Option<Color>,
+    pub font_size: Option<usize>,
+    pub font: Option<FontRef>,
+    /// Uses the font's bold face, see [`FontRef::set_bold`].
+    pub bold: bool,
+    pub italic: bool,
+    /// The name of an icon from [`register_text_icon`], drawn instead of the text.
+    pub icon: Option<String>,
+}
+
+impl TextSpan {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            ..Default::default()
+        }
+    }
+
+    /// An icon from [`register_text_icon`], as tall as the text around it.
+    pub fn icon(name: impl Into<String>) -> Self {
+        Self {
+            icon: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_size(mut self, font_size: usize) -> Self {
+        self.font_size = Some(font_size);
+        self
+    }
+
+    pub fn with_font(mut self, font: FontRef) -> Self {
+        self.font = Some(font);
+        self
+    }
+
+    pub fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub fn italic(mut self) -> Self {
+        self.italic = true;
+        self
+    }
+}
+
+/// Lets `[icon=name]` and [`TextSpan::icon`] draw `texture` inline with text.
+pub fn register_text_icon(name: impl Into<String>, texture: TextureRef) {
+    get_state().text_icons.insert(name.into(), texture);
+}
+
+/// Turns markup into spans. Supported tags are:
+///
+/// - `[b]bold[/b]` and `[i]italic[/i]`
+/// - `[color=#ff8800]`, `[color=#ff880080]` or a theme color like `[color=danger]`, ended
+///   with `[/color]`
+/// - `[size=24]`, ended with `[/size]`
+/// - `[icon=coin]` for an icon from [`register_text_icon`]
+///
+/// Tags can be nested. `[[` is a literal `[`, and anything in brackets that isn't a tag is
+/// kept as text.
+pub fn parse_rich_text(markup: &str) -> Vec<TextSpan> {
+    let mut parser = MarkupParser::default();
+    let mut rest = markup;
+
+    while let Some(open) = rest.find('[') {
+        parser.text.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        if let Some(after) = rest.strip_prefix("[[") {
+            parser.text.push('[');
+            rest = after;
+            continue;
+        }
+
+        let Some(close) = rest.find(']') else {
+            break;
+        };
+        let tag = &rest[1..close];
+        if !parser.apply_tag(tag) {
+            parser.text.push_str(&rest[..=close]);
+        }
+        rest = &rest[close + 1..];
+    }
+    parser.text.push_str(rest);
+    parser.flush();
+
+    parser.spans
+}
+
+#[derive(Default)]
+struct MarkupParser {
+    spans: Vec<TextSpan>,
+    /// Text waiting to be put in a span with the current style.
+    text: String,
+    colors: Vec<Color>,
+    sizes: Vec<usize>,
+    bold: usize,
+    italic: usize,
+}
+
+impl MarkupParser {
+    /// Returns whether `tag` was a tag.
+    fn apply_tag(&mut self, tag: &str) -> bool {
+        let (name, value) = match tag.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (tag.trim(), None),
+        };
+
+        match (name, value) {
+            ("b", None) => {
+                self.flush();
+                self.bold += 1;
+            }
+            ("/b", None) => {
+                self.flush();
+                self.bold = self.bold.saturating_sub(1);
+            }
+            ("i", None) => {
+                self.flush();
+                self.italic += 1;
+            }
+            ("/i", None) => {
+                self.flush();
+                self.italic = self.italic.saturating_sub(1);
+            }
+            ("color", Some(value)) => {
+                let Some(color) = parse_color(value) else {
+                    return false;
+                };
+                self.flush();
+                self.colors.push(color);
+            }
+            ("/color", None) => {
+                self.flush();
+                self.colors.pop();
+            }
+            ("size", Some(value)) => {
+                let Ok(size) = value.parse() else {
+                    return false;
+                };
+                self.flush();
+                self.sizes.push(size);
+            }
+            ("/size", None) => {
+                self.flush();
+                self.sizes.pop();
+            }
+            ("icon", Some(value)) => {
+                self.flush();
+                let mut span = self.span(String::new());
+                span.icon = Some(value.to_string());
+                self.spans.push(span);
+            }
+            _ => return false,
+        }
+
+        true
+    }
+
+    fn span(&self, text: String) -> TextSpan {
+        TextSpan {
+            text,
+            color: self.colors.last().copied(),
+            font_size: self.sizes.last().copied(),
+            font: None,
+            bold: self.bold > 0,
+            italic: self.italic > 0,
+            icon: None,
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.text.is_empty() {
+            let text = std::mem::take(&mut self.text);
+            self.spans.push(self.span(text));
+        }
+    }
+}
+
+/// `#rrggbb`, `#rrggbbaa`, or the name of one of the [`theme`]'s colors.
+fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        let number = u32::from_str_radix(hex, 16).ok()?;
+        return match hex.len() {
+            6 => Some(Color::hex(number)),
+            8 => Some(Color::hex_alpha(number)),
+            _ => None,
+        };
+    }
+
+    let theme = theme();
+    Some(match value {
+        "text" => theme.text,
+        "text_muted" => theme.text_muted,
+        "primary" => theme.primary,
+        "secondary" => theme.secondary,
+        "accent" => theme.accent,
+        "success" => theme.success,
+        "warning" => theme.warning,
+        "danger" => theme.danger,
+        _ => return None,
+    })
+}
+
+enum Piece {
+    Text {
+        chain: Vec<FontRef>,
+        layout: TextLayout,
+        font_size: f32,
+        color: Color,
+    },
+    Icon {
+        texture: TextureRef,
+        top_left: Vec2,
+        size: Vec2,
+    },
+}
+
+/// Lays out `spans` in lines, with each line's pieces sharing a baseline. Positions are
+/// relative to the top left.
+fn layout_spans(spans: &[TextSpan], params: &TextDrawParams) -> (Vec<Piece>, Vec2) {
+    let dpi_scaling = if params.do_dpi_scaling {
+        ui_scaling()
+    } else {
+        1.0
+    };
+
+    // each piece with how far its top is above the baseline, and how tall its line should be
+    let mut lines: Vec<Vec<(Piece, f32, f32)>> = vec![Vec::new()];
+    for span in spans {
+        let font_size = (span.font_size.unwrap_or(params.font_size) as f32 * dpi_scaling).ceil();
+
+        if let Some(name) = &span.icon {
+            let Some(&texture) = get_state().text_icons.get(name) else {
+                log::warn!("there's no text icon called {name:?}");
+                continue;
+            };
+            let aspect = texture.normalized_dimensions();
+            let size = vec2(font_size * aspect.x / aspect.y, font_size);
+            let icon = Piece::Icon {
+                texture,
+                top_left: Vec2::ZERO,
+                size,
+            };
+            // sit a little below the baseline, like a capital letter with a descender
+            lines.last_mut().unwrap().push((icon, size.y * 0.8, size.y));
+            continue;
+        }
+
+        let chain = span
+            .font
+            .or(params.font)
+            .unwrap_or(default_font())
+            .face(span.bold, span.italic)
+            .chain();
+        let fonts = chain_fonts(&chain);
+        let (ascent, line_height) = fonts[0]
+            .horizontal_line_metrics(font_size)
+            .map_or((font_size, font_size), |metrics| {
+                (metrics.ascent, metrics.new_line_size)
+            });
+        let color = span.color.unwrap_or(params.color);
+
+        for (i, text) in span.text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(Vec::new());
+            }
+            let text = Piece::Text {
+                layout: layout_text_with_fallbacks(&fonts, text, font_size),
+                chain: chain.clone(),
+                font_size,
+                color,
+            };
+            lines.last_mut().unwrap().push((text, ascent, line_height));
+        }
+    }
+
+    let mut pieces = Vec::new();
+    let mut size = Vec2::ZERO;
+    for line in lines {
+        let baseline = line
+            .iter()
+            .map(|(_, ascent, _)| *ascent)
+            .fold(0.0, f32::max);
+        let line_height = line
+            .iter()
+            .map(|(_, _, height)| *height)
+            .fold(0.0, f32::max);
+
+        let mut x = 0.0;
+        for (mut piece, ascent, _) in line {
+            let offset = vec2(x, size.y + baseline - ascent);
+            match &mut piece {
+                Piece::Text { layout, .. } => {
+                    for glyph in &mut layout.glyphs {
+                        glyph.position += offset;
+                    }
+                    x += layout.size.x;
+                }
+                Piece::Icon { top_left, size, .. } => {
+                    *top_left = offset;
+                    x += size.x;
+                }
+            }
+            pieces.push(piece);
+        }
+
+        size.x = size.x.max(x);
+        size.y += line_height;
+    }
+
+    (pieces, size)
+}
+
+pub(crate) fn draw_text_spans_to(
+    spans: &[TextSpan],
+    params: TextDrawParams,
+    draw_queue: &mut DrawQueue2D,
+) -> TextDimensions {
+    if get_state().window_context.is_none() {
+        return TextDimensions::default();
+    }
+
+    let (pieces, size) = layout_spans(spans, &params);
+    for piece in pieces {
+        match piece {
+            Piece::Text {
+                chain,
+                layout,
+                font_size,
+                color,
+            } => draw_layout(
+                &chain,
+                &layout.glyphs,
+                font_size,
+                params.position,
+                color,
+                draw_queue,
+            ),
+            Piece::Icon {
+                texture,
+                top_left,
+                size,
+            } => draw_queue.add_sprite(
+                texture,
+                Transform2D::from_scale_translation(size, params.position + top_left),
+                Color::WHITE,
+                None,
+            ),
+        }
+    }
+
+    TextDimensions { size }
+}
+
+/// Draws markup like `"Find the [color=accent][b]old key[/b][/color] [icon=key]"` on screen.
+/// See [`parse_rich_text`] for the tags. `params` sets everything the markup doesn't.
+pub fn draw_rich_text(markup: &str, params: TextDrawParams) -> TextDimensions {
+    draw_text_spans(&parse_rich_text(markup), params)
+}
+
+pub fn draw_rich_text_world(markup: &str, params: TextDrawParams) -> TextDimensions {
+    draw_text_spans_world(&parse_rich_text(markup), params)
+}
+
+/// Draws `spans` one after another on screen, breaking lines at `\n`.
+pub fn draw_text_spans(spans: &[TextSpan], params: TextDrawParams) -> TextDimensions {
+    draw_text_spans_to(spans, params, get_state().draw_queue_2d())
+}
+
+pub fn draw_text_spans_world(spans: &[TextSpan], params: TextDrawParams) -> TextDimensions {
+    draw_text_spans_to(spans, params, get_state().world_draw_queue_2d())
+}
+
+pub fn measure_rich_text(markup: &str, params: TextDrawParams) -> TextDimensions {
+    measure_text_spans(&parse_rich_text(markup), params)
+}
+
+pub fn measure_text_spans(spans: &[TextSpan], params: TextDrawParams) -> TextDimensions {
+    TextDimensions {
+        size: layout_spans(spans, &params).1,
+    }
+}
@@ -0,0 +1,334 @@
+use bevy_math::{EulerRot, Mat4};
+
+use crate::{
+    get_state,
+    transform::{Transform2D, Transform3D},
+};
+
+/// A node in a [`SceneGraph`]. Old ids of removed nodes never point at whatever replaced them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+/// The transforms a [`SceneGraph`] can hold.
+pub trait SceneTransform: Copy {
+    fn local_matrix(&mut self) -> Mat4;
+    /// Back from a matrix. Shears from non-uniform scale on a rotated parent are lost.
+    fn from_matrix(matrix: Mat4) -> Self;
+}
+
+impl SceneTransform for Transform2D {
+    fn local_matrix(&mut self) -> Mat4 {
+        self.matrix()
+    }
+
+    fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        let mut transform = Transform2D::IDENTITY;
+        transform.set_scale(scale.truncate());
+        transform.set_rotation(rotation.to_euler(EulerRot::ZYX).0);
+        transform.set_translation(translation.truncate());
+        transform
+    }
+}
+
+impl SceneTransform for Transform3D {
+    fn local_matrix(&mut self) -> Mat4 {
+        self.matrix()
+    }
+
+    fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        let mut transform = Transform3D::IDENTITY;
+        transform.set_scale(scale);
+        transform.set_rotation(rotation);
+        transform.set_translation(translation);
+        transform
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    generation: u32,
+    local: T,
+    world: Mat4,
+    /// The world matrix needs working out again.
+    dirty: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+/// Transforms that can be parented to each other, so moving a tank moves the turret on it, and
+/// a held sword follows the hand. Each node has a local transform relative to its parent, and
+/// the world transform is worked out from those.
+///
+/// World transforms are worked out lazily when asked for, and all at once by
+/// [`update`](Self::update). The engine keeps one 2D and one 3D graph, available with
+/// [`scene_2d`] and [`scene_3d`], and updates them at the end of every frame.
+#[derive(Debug, Clone)]
+pub struct SceneGraph<T> {
+    nodes: Vec<Option<Node<T>>>,
+    generations: Vec<u32>,
+    free: Vec<u32>,
+}
+
+impl<T: SceneTransform> Default for SceneGraph<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: SceneTransform> SceneGraph<T> {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            generations: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Adds a node with no parent.
+    pub fn add(&mut self, mut local: T) -> NodeId {
+        let world = local.local_matrix();
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.nodes.push(None);
+                self.generations.push(0);
+                self.nodes.len() as u32 - 1
+            }
+        };
+        let generation = self.generations[index as usize];
+
+        self.nodes[index as usize] = Some(Node {
+            generation,
+            local,
+            world,
+            dirty: false,
+            parent: None,
+            children: Vec::new(),
+        });
+
+        NodeId { index, generation }
+    }
+
+    /// Adds a node already attached to `parent`, with `local` relative to it.
+    pub fn add_child(&mut self, parent: NodeId, local: T) -> NodeId {
+        let child = self.add(local);
+        self.attach(child, parent);
+        child
+    }
+
+    /// Removes the node and everything attached to it.
+    pub fn remove(&mut self, node: NodeId) -> bool {
+        if !self.contains(node) {
+            return false;
+        }
+
+        self.unlink(node);
+        let mut stack = vec![node];
+        while let Some(id) = stack.pop() {
+            if let Some(removed) = self.nodes[id.index as usize].take() {
+                stack.extend(removed.children);
+                self.generations[id.index as usize] += 1;
+                self.free.push(id.index);
+            }
+        }
+
+        true
+    }
+
+    pub fn contains(&self, node: NodeId) -> bool {
+        self.node(node).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Parents `child` to `parent`, keeping its local transform, so it jumps to the same spot
+    /// relative to its new parent. Use [`attach_in_place`](Self::attach_in_place) to keep it
+    /// where it is in the world instead.
+    ///
+    /// Panics if `parent` is `child` or attached to it, since that would make a loop.
+    pub fn attach(&mut self, child: NodeId, parent: NodeId) {
+        if !self.contains(child) || !self.contains(parent) {
+            return;
+        }
+        assert!(
+            !self.is_ancestor_or_self(child, parent),
+            "can't attach a node to itself or one of its children"
+        );
+
+        self.unlink(child);
+        self.node_mut(child).unwrap().parent = Some(parent);
+        self.node_mut(parent).unwrap().children.push(child);
+        self.mark_dirty(child);
+    }
+
+    /// Parents `child` to `parent` without moving it in the world, like picking something up.
+    pub fn attach_in_place(&mut self, child: NodeId, parent: NodeId) {
+        let (Some(world), Some(parent_world)) =
+            (self.world_matrix(child), self.world_matrix(parent))
+        else {
+            return;
+        };
+
+        self.attach(child, parent);
+        self.node_mut(child).unwrap().local = T::from_matrix(parent_world.inverse() * world);
+    }
+
+    /// Unparents the node, leaving it where it is in the world, like dropping something.
+    pub fn detach(&mut self, node: NodeId) {
+        let Some(world) = self.world_matrix(node) else {
+            return;
+        };
+
+        self.unlink(node);
+        let node_data = self.node_mut(node).unwrap();
+        node_data.local = T::from_matrix(world);
+        node_data.dirty = false;
+        node_data.world = world;
+    }
+
+    pub fn parent(&self, node: NodeId) -> Option<NodeId> {
+        self.node(node)?.parent
+    }
+
+    pub fn children(&self, node: NodeId) -> &[NodeId] {
+        self.node(node)
+            .map(|node| node.children.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Relative to the parent.
+    pub fn local(&self, node: NodeId) -> Option<&T> {
+        self.node(node).map(|node| &node.local)
+    }
+
+    /// Relative to the parent. Moves everything attached to the node too.
+    pub fn local_mut(&mut self, node: NodeId) -> Option<&mut T> {
+        if !self.contains(node) {
+            return None;
+        }
+
+        self.mark_dirty(node);
+        self.node_mut(node).map(|node| &mut node.local)
+    }
+
+    pub fn set_local(&mut self, node: NodeId, local: T) {
+        if let Some(current) = self.local_mut(node) {
+            *current = local;
+        }
+    }
+
+    /// Where the node ends up after all its parents' transforms.
+    pub fn world_matrix(&mut self, node: NodeId) -> Option<Mat4> {
+        let data = self.node(node)?;
+        if !data.dirty {
+            return Some(data.world);
+        }
+
+        let parent_world = match data.parent {
+            Some(parent) => self.world_matrix(parent)?,
+            None => Mat4::IDENTITY,
+        };
+        let data = self.node_mut(node)?;
+        data.world = parent_world * data.local.local_matrix();
+        data.dirty = false;
+        Some(data.world)
+    }
+
+    pub fn world(&mut self, node: NodeId) -> Option<T> {
+        self.world_matrix(node).map(T::from_matrix)
+    }
+
+    /// Works out every world transform that's out of date.
+    pub fn update(&mut self) {
+        for index in 0..self.nodes.len() {
+            if let Some(node) = &self.nodes[index]
+                && node.dirty
+            {
+                let id = NodeId {
+                    index: index as u32,
+                    generation: node.generation,
+                };
+                self.world_matrix(id);
+            }
+        }
+    }
+
+    /// Every node with no parent.
+    pub fn roots(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes.iter().enumerate().filter_map(|(index, node)| {
+            let node = node.as_ref()?;
+            node.parent.is_none().then_some(NodeId {
+                index: index as u32,
+                generation: node.generation,
+            })
+        })
+    }
+
+    fn node(&self, id: NodeId) -> Option<&Node<T>> {
+        self.nodes
+            .get(id.index as usize)?
+            .as_ref()
+            .filter(|node| node.generation == id.generation)
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> Option<&mut Node<T>> {
+        self.nodes
+            .get_mut(id.index as usize)?
+            .as_mut()
+            .filter(|node| node.generation == id.generation)
+    }
+
+    fn is_ancestor_or_self(&self, ancestor: NodeId, mut node: NodeId) -> bool {
+        loop {
+            if node == ancestor {
+                return true;
+            }
+            match self.parent(node) {
+                Some(parent) => node = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// Takes the node out of its parent's children.
+    fn unlink(&mut self, node: NodeId) {
+        let Some(parent) = self.node_mut(node).and_then(|node| node.parent.take()) else {
+            return;
+        };
+        if let Some(parent) = self.node_mut(parent) {
+            parent.children.retain(|child| *child != node);
+        }
+        self.mark_dirty(node);
+    }
+
+    fn mark_dirty(&mut self, node: NodeId) {
+        let mut stack = vec![node];
+        while let Some(id) = stack.pop() {
+            if let Some(node) = self.node_mut(id) {
+                node.dirty = true;
+                stack.extend_from_slice(&node.children);
+            }
+        }
+    }
+}
+
+/// The engine's 2D scene graph, updated at the end of every frame.
+pub fn scene_2d() -> &'static mut SceneGraph<Transform2D> {
+    &mut get_state().storage.scene_2d
+}
+
+/// The engine's 3D scene graph, updated at the end of every frame.
+pub fn scene_3d() -> &'static mut SceneGraph<Transform3D> {
+    &mut get_state().storage.scene_3d
+}
@@ -0,0 +1,263 @@
+use std::{
+    cell::Cell,
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll, Waker},
+};
+
+use crate::get_state;
+
+/// Ids are shared between schedulers, so a handle can't cancel the wrong thing even when
+/// the engine swaps its scheduler out while running it.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// Time of the scheduler currently running routines, for [`wait`].
+    static ROUTINE_TIME: Cell<f32> = const { Cell::new(0.0) };
+}
+
+/// Something scheduled with [`after`], [`every`] or [`start_routine`], for cancelling it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TaskHandle(u64);
+
+enum TaskKind {
+    After(Box<dyn FnOnce()>),
+    Every {
+        interval: f32,
+        callback: Box<dyn FnMut()>,
+    },
+    Routine(Pin<Box<dyn Future<Output = ()>>>),
+}
+
+struct Task {
+    handle: TaskHandle,
+    /// When it next runs. Routines run every update.
+    due: f32,
+    kind: TaskKind,
+}
+
+/// Runs closures later, on repeat, or step by step over several frames, so delayed effects
+/// don't each need their own timer field.
+///
+/// Routines are `async` blocks that can [`wait`] for time to pass, resuming where they left
+/// off:
+///
+/// ```ignore
+/// start_routine(async {
+///     flash_screen();
+///     wait(0.5).await;
+///     spawn_boss();
+/// });
+/// ```
+///
+/// The engine keeps one of these going on engine time, used by the free functions, but they
+/// can also be driven by hand with [`update`](Self::update).
+#[derive(Default)]
+pub struct Scheduler {
+    time: f32,
+    tasks: Vec<Task>,
+    /// Cancellations of things that weren't here, which might be in the scheduler this one
+    /// gets merged into.
+    missed_cancels: Vec<TaskHandle>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How much time this scheduler has been updated by in total.
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    /// Runs `callback` once, `seconds` from now.
+    pub fn after(&mut self, seconds: f32, callback: impl FnOnce() + 'static) -> TaskHandle {
+        self.push(seconds, TaskKind::After(Box::new(callback)))
+    }
+
+    /// Runs `callback` every `seconds`, starting `seconds` from now.
+    pub fn every(&mut self, seconds: f32, callback: impl FnMut() + 'static) -> TaskHandle {
+        assert!(seconds > 0.0, "can't run something every {seconds} seconds");
+
+        self.push(
+            seconds,
+            TaskKind::Every {
+                interval: seconds,
+                callback: Box::new(callback),
+            },
+        )
+    }
+
+    /// Starts running `routine` on the next update, and keeps running it every update until it
+    /// finishes.
+    pub fn start_routine(&mut self, routine: impl Future<Output = ()> + 'static) -> TaskHandle {
+        self.push(0.0, TaskKind::Routine(Box::pin(routine)))
+    }
+
+    /// Stops something from running again. Returns whether it was still scheduled.
+    pub fn cancel(&mut self, handle: TaskHandle) -> bool {
+        let count = self.tasks.len();
+        self.tasks.retain(|task| task.handle != handle);
+        let found = self.tasks.len() != count;
+        if !found {
+            self.missed_cancels.push(handle);
+        }
+        found
+    }
+
+    pub fn is_scheduled(&self, handle: TaskHandle) -> bool {
+        self.tasks.iter().any(|task| task.handle == handle)
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.tasks.clear();
+    }
+
+    /// Moves time on by `delta_time` and runs everything that's due, in the order they're
+    /// due. Things repeating more often than that run several times to catch up.
+    pub fn update(&mut self, delta_time: f32) {
+        self.missed_cancels.clear();
+        self.time += delta_time;
+
+        let mut tasks = std::mem::take(&mut self.tasks);
+        tasks.sort_by(|a, b| a.due.total_cmp(&b.due));
+
+        ROUTINE_TIME.set(self.time);
+        let mut context = Context::from_waker(Waker::noop());
+
+        for mut task in tasks {
+            if task.due > self.time {
+                self.tasks.push(task);
+                continue;
+            }
+
+            match task.kind {
+                TaskKind::After(callback) => callback(),
+                TaskKind::Every {
+                    interval,
+                    ref mut callback,
+                } => {
+                    while task.due <= self.time {
+                        callback();
+                        task.due += interval;
+                    }
+                    self.tasks.push(task);
+                }
+                TaskKind::Routine(ref mut routine) => {
+                    if routine.as_mut().poll(&mut context).is_pending() {
+                        self.tasks.push(task);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Takes over everything scheduled on `other`, and applies any cancellations it couldn't.
+    pub fn merge(&mut self, mut other: Scheduler) {
+        for handle in other.missed_cancels.drain(..) {
+            self.cancel(handle);
+        }
+        for mut task in other.tasks {
+            // times were relative to the other scheduler's clock
+            task.due += self.time - other.time;
+            self.tasks.push(task);
+        }
+    }
+
+    fn push(&mut self, delay: f32, kind: TaskKind) -> TaskHandle {
+        let handle = TaskHandle(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+        self.tasks.push(Task {
+            handle,
+            due: self.time + delay,
+            kind,
+        });
+        handle
+    }
+}
+
+/// Finishes once `seconds` have passed on the scheduler running the routine. Only works inside
+/// routines.
+pub fn wait(seconds: f32) -> impl Future<Output = ()> {
+    let mut deadline = None;
+    std::future::poll_fn(move |_| {
+        let now = ROUTINE_TIME.get();
+        let deadline = *deadline.get_or_insert(now + seconds);
+        if now >= deadline {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+}
+
+/// Finishes on the next update after `condition` returns true. Only works inside routines.
+pub fn wait_until(mut condition: impl FnMut() -> bool) -> impl Future<Output = ()> {
+    std::future::poll_fn(move |_| {
+        if condition() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+}
+
+/// Waits until the next update. Only works inside routines.
+pub fn next_update() -> impl Future<Output = ()> {
+    let mut polled = false;
+    std::future::poll_fn(move |_| {
+        if polled {
+            Poll::Ready(())
+        } else {
+            polled = true;
+            Poll::Pending
+        }
+    })
+}
+
+fn scheduler() -> &'static mut Scheduler {
+    &mut get_state().scheduler
+}
+
+/// Runs `callback` once, `seconds` of engine time from now.
+pub fn after(seconds: f32, callback: impl FnOnce() + 'static) -> TaskHandle {
+    scheduler().after(seconds, callback)
+}
+
+/// Runs `callback` every `seconds` of engine time.
+pub fn every(seconds: f32, callback: impl FnMut() + 'static) -> TaskHandle {
+    scheduler().every(seconds, callback)
+}
+
+/// Runs an `async` block over as many frames as it needs. See [`Scheduler`].
+pub fn start_routine(routine: impl Future<Output = ()> + 'static) -> TaskHandle {
+    scheduler().start_routine(routine)
+}
+
+pub fn cancel(handle: TaskHandle) -> bool {
+    scheduler().cancel(handle)
+}
+
+pub fn is_scheduled(handle: TaskHandle) -> bool {
+    scheduler().is_scheduled(handle)
+}
+
+pub(crate) fn update_scheduler(delta_time: f32) {
+    let state = get_state();
+
+    // callbacks can schedule and cancel things, which goes to a fresh scheduler in the
+    // meantime instead of the one that's running
+    let mut running = std::mem::take(&mut state.scheduler);
+    running.update(delta_time);
+    let added = std::mem::replace(&mut state.scheduler, running);
+    state.scheduler.merge(added);
+}
@@ -0,0 +1,241 @@
+//! `Serialize` and `Deserialize` for engine types that can't just derive them. Transforms and
+//! cameras keep matrices that are worked out again after loading, so only their placement is
+//! saved. Everything simpler derives them where it's defined.
+
+use bevy_math::{BVec2, BVec3, Quat, Vec2, Vec3};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    camera::{Camera2D, Camera3D},
+    color::u8::{Pixel, Rgba},
+    transform::{Transform2D, Transform3D},
+};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Transform2D")]
+struct Transform2DData {
+    translation: Vec2,
+    rotation: f32,
+    scale: Vec2,
+    mirror: BVec2,
+}
+
+impl Serialize for Transform2D {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Transform2DData {
+            translation: self.translation(),
+            rotation: self.rotation(),
+            scale: self.scale(),
+            mirror: self.mirror(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Transform2D {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = Transform2DData::deserialize(deserializer)?;
+        Ok(Transform2D::from_translation(data.translation)
+            .with_rotation(data.rotation)
+            .with_scale(data.scale)
+            .with_mirror(data.mirror))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Transform3D")]
+struct Transform3DData {
+    translation: Vec3,
+    rotation: Quat,
+    scale: Vec3,
+    mirror: BVec3,
+}
+
+impl Serialize for Transform3D {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Transform3DData {
+            translation: self.translation(),
+            rotation: self.rotation(),
+            scale: self.scale(),
+            mirror: self.mirror(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Transform3D {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = Transform3DData::deserialize(deserializer)?;
+        Ok(Transform3D::from_translation(data.translation)
+            .with_rotation(data.rotation)
+            .with_scale(data.scale)
+            .with_mirror(data.mirror))
+    }
+}
+
+/// Cameras are loaded at the size of the window, since the size they were saved at might not
+/// be the size it is now.
+fn window_size() -> (u32, u32) {
+    crate::try_get_state().map_or((1, 1), |state| {
+        let size = state.window_size();
+        (size.x as u32, size.y as u32)
+    })
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Camera2D")]
+struct Camera2DData {
+    translation: Vec2,
+    scale: f32,
+    rotation: f32,
+}
+
+impl Serialize for Camera2D {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Camera2DData {
+            translation: self.translation,
+            scale: self.scale,
+            rotation: self.rotation,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Camera2D {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = Camera2DData::deserialize(deserializer)?;
+        let (width, height) = window_size();
+
+        let mut camera = Camera2D::new(width, height);
+        camera.translation = data.translation;
+        camera.scale = data.scale;
+        camera.rotation = data.rotation;
+        camera.mark_dirty();
+        Ok(camera)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "Camera3D")]
+struct Camera3DData {
+    eye: Vec3,
+    target: Vec3,
+    up: Vec3,
+    fovy: f32,
+    znear: f32,
+    zfar: f32,
+    isometric: bool,
+}
+
+impl Serialize for Camera3D {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Camera3DData {
+            eye: self.eye,
+            target: self.target,
+            up: self.up,
+            fovy: self.fovy,
+            znear: self.znear,
+            zfar: self.zfar,
+            isometric: self.isometric,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Camera3D {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = Camera3DData::deserialize(deserializer)?;
+        let (width, height) = window_size();
+
+        let mut camera = Camera3D::new(width, height);
+        camera.eye = data.eye;
+        camera.target = data.target;
+        camera.up = data.up;
+        camera.fovy = data.fovy;
+        camera.znear = data.znear;
+        camera.zfar = data.zfar;
+        camera.isometric = data.isometric;
+        camera.mark_dirty();
+        Ok(camera)
+    }
+}
+
+impl Serialize for Pixel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.rgba().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pixel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Rgba { r, g, b, a } = Rgba::deserialize(deserializer)?;
+        Ok(Pixel::from_rgba(r, g, b, a))
+    }
+}
+
+pub(crate) mod magnify_filter {
+    use glium::uniforms::MagnifySamplerFilter;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    pub fn serialize<S: Serializer>(
+        filter: &MagnifySamplerFilter,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match filter {
+            MagnifySamplerFilter::Nearest => "nearest",
+            MagnifySamplerFilter::Linear => "linear",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<MagnifySamplerFilter, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "nearest" => Ok(MagnifySamplerFilter::Nearest),
+            "linear" => Ok(MagnifySamplerFilter::Linear),
+            other => Err(D::Error::unknown_variant(other, &["nearest", "linear"])),
+        }
+    }
+}
+
+pub(crate) mod minify_filter {
+    use glium::uniforms::MinifySamplerFilter;
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+
+    const NAMES: &[&str] = &[
+        "nearest",
+        "linear",
+        "nearest_mipmap_nearest",
+        "linear_mipmap_nearest",
+        "nearest_mipmap_linear",
+        "linear_mipmap_linear",
+    ];
+
+    pub fn serialize<S: Serializer>(
+        filter: &MinifySamplerFilter,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(match filter {
+            MinifySamplerFilter::Nearest => "nearest",
+            MinifySamplerFilter::Linear => "linear",
+            MinifySamplerFilter::NearestMipmapNearest => "nearest_mipmap_nearest",
+            MinifySamplerFilter::LinearMipmapNearest => "linear_mipmap_nearest",
+            MinifySamplerFilter::NearestMipmapLinear => "nearest_mipmap_linear",
+            MinifySamplerFilter::LinearMipmapLinear => "linear_mipmap_linear",
+        })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<MinifySamplerFilter, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "nearest" => Ok(MinifySamplerFilter::Nearest),
+            "linear" => Ok(MinifySamplerFilter::Linear),
+            "nearest_mipmap_nearest" => Ok(MinifySamplerFilter::NearestMipmapNearest),
+            "linear_mipmap_nearest" => Ok(MinifySamplerFilter::LinearMipmapNearest),
+            "nearest_mipmap_linear" => Ok(MinifySamplerFilter::NearestMipmapLinear),
+            "linear_mipmap_linear" => Ok(MinifySamplerFilter::LinearMipmapLinear),
+            other => Err(D::Error::unknown_variant(other, NAMES)),
+        }
+    }
+}
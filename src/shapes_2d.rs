@@ -1,14 +1,31 @@
 use crate::{
-    collisions::{AABB2D, HasBounds2D},
+    collisions::{AABB2D, HasBounds2D, boolean::PolygonWithHoles},
     color::Color,
     draw_queue_2d::{DrawQueue2D, Vertex2D},
+    error::OrReport,
     get_state,
+    textures::TextureRef,
 };
 use bevy_math::Vec2;
-use std::f32::consts::TAU;
+use bumpalo::Bump;
+use bumpalo::collections::Vec as BumpVec;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
 
 pub trait Shape2D: HasBounds2D {
     fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>);
+    /// Same as `points`, but allocated in `arena`. The draw queue uses this with memory that's
+    /// freed at the end of the frame, so simple shapes can skip the heap entirely.
+    fn points_in<'a>(
+        &self,
+        starting_index: u32,
+        arena: &'a Bump,
+    ) -> (BumpVec<'a, u32>, BumpVec<'a, Vertex2D>) {
+        let (indices, vertices) = self.points(starting_index);
+        (
+            BumpVec::from_iter_in(indices, arena),
+            BumpVec::from_iter_in(vertices, arena),
+        )
+    }
     fn is_visible_in_world(&self) -> bool {
         self.bounds().is_visible_in_world()
     }
@@ -24,6 +41,7 @@ pub trait Shape2D: HasBounds2D {
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Circle {
     pub center: Vec2,
     pub radius: Vec2,
@@ -53,6 +71,7 @@ impl Circle {
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CircleOutline {
     pub center: Vec2,
     pub radius: Vec2,
@@ -84,6 +103,7 @@ impl Shape2D for CircleOutline {
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect {
     pub top_left: Vec2,
     pub size: Vec2,
@@ -97,11 +117,11 @@ impl HasBounds2D for Rect {
 }
 
 impl Rect {
-    fn gen_quad(&self) -> Vec<Vertex2D> {
+    fn gen_quad(&self) -> [Vertex2D; 4] {
         let tl = self.top_left;
         let br = self.top_left + self.size;
 
-        vec![
+        [
             Vertex2D::new(tl.x, tl.y, self.color),
             Vertex2D::new(br.x, tl.y, self.color),
             Vertex2D::new(tl.x, br.y, self.color),
@@ -114,7 +134,15 @@ impl Shape2D for Rect {
     fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
         let quad = self.gen_quad();
         let indices = QUAD_INDICES.map(|n| n + starting_index).to_vec();
-        (indices, quad)
+        (indices, quad.to_vec())
+    }
+
+    fn points_in<'a>(
+        &self,
+        starting_index: u32,
+        arena: &'a Bump,
+    ) -> (BumpVec<'a, u32>, BumpVec<'a, Vertex2D>) {
+        quad_in(self.gen_quad(), starting_index, arena)
     }
 
     fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
@@ -123,6 +151,7 @@ impl Shape2D for Rect {
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Triangle {
     pub points: [Vec2; 3],
     pub color: Color,
@@ -143,12 +172,26 @@ impl Shape2D for Triangle {
         (indices.collect(), tri.to_vec())
     }
 
+    fn points_in<'a>(
+        &self,
+        starting_index: u32,
+        arena: &'a Bump,
+    ) -> (BumpVec<'a, u32>, BumpVec<'a, Vertex2D>) {
+        let tri = self.points.map(|p| Vertex2D::new(p.x, p.y, self.color));
+        let indices = starting_index..starting_index + 3;
+        (
+            BumpVec::from_iter_in(indices, arena),
+            BumpVec::from_iter_in(tri, arena),
+        )
+    }
+
     fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
         draw_queue.add_shape(self);
     }
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Line {
     pub start: Vec2,
     pub end: Vec2,
@@ -167,7 +210,7 @@ impl HasBounds2D for Line {
 }
 
 impl Line {
-    fn gen_mesh(&self) -> Option<Vec<Vertex2D>> {
+    fn gen_mesh(&self) -> Option<[Vertex2D; 4]> {
         let direction = self.end - self.start;
         let length = direction.length();
 
@@ -178,7 +221,7 @@ impl Line {
         let normalized = direction / length;
         let perpendicular = Vec2::new(-normalized.y, normalized.x) * self.thickness / 2.0;
 
-        Some(vec![
+        Some([
             Vertex2D::new(
                 self.start.x - perpendicular.x,
                 self.start.y - perpendicular.y,
@@ -206,18 +249,33 @@ impl Line {
 impl Shape2D for Line {
     fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
         if let Some(mesh) = self.gen_mesh() {
-            (QUAD_INDICES.map(|n| n + starting_index).to_vec(), mesh)
+            (
+                QUAD_INDICES.map(|n| n + starting_index).to_vec(),
+                mesh.to_vec(),
+            )
         } else {
             (vec![], vec![])
         }
     }
 
+    fn points_in<'a>(
+        &self,
+        starting_index: u32,
+        arena: &'a Bump,
+    ) -> (BumpVec<'a, u32>, BumpVec<'a, Vertex2D>) {
+        match self.gen_mesh() {
+            Some(mesh) => quad_in(mesh, starting_index, arena),
+            None => (BumpVec::new_in(arena), BumpVec::new_in(arena)),
+        }
+    }
+
     fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
         draw_queue.add_shape(self);
     }
 }
 
 #[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Poly {
     pub sides: usize,
     pub radius: f32,
@@ -266,6 +324,7 @@ impl Shape2D for Poly {
 }
 
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CustomShape {
     pub points: Vec<Vec2>,
     pub color: Color,
@@ -301,6 +360,844 @@ impl Shape2D for CustomShape {
     }
 }
 
+/// A `CustomShape` with holes cut out of it. A point is filled if it's inside the outline and
+/// not inside any hole.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ShapeWithHoles {
+    pub outline: Vec<Vec2>,
+    pub holes: Vec<Vec<Vec2>>,
+    pub color: Color,
+}
+
+impl ShapeWithHoles {
+    /// For drawing the result of boolean operations on polygons.
+    pub fn from_polygon(polygon: &PolygonWithHoles, color: Color) -> Self {
+        Self {
+            outline: polygon.outline.vertices.clone(),
+            holes: polygon
+                .holes
+                .iter()
+                .map(|hole| hole.vertices.clone())
+                .collect(),
+            color,
+        }
+    }
+}
+
+impl HasBounds2D for ShapeWithHoles {
+    fn bounds(&self) -> AABB2D {
+        points_bounds(&self.outline)
+    }
+}
+
+impl Shape2D for ShapeWithHoles {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let (vertices, indices) = gen_mesh_with_holes(&self.outline, &self.holes, self.color);
+        let indices = indices.iter().map(|n| n + starting_index).collect();
+        (indices, vertices)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        draw_queue.add_shape(self);
+    }
+}
+
+/// A `CustomShape` with a texture on it. Each point has its own texture coordinate, from (0, 0)
+/// at the top left of the texture to (1, 1) at the bottom right. Used as a mask, only its shape
+/// matters.
+#[derive(Clone, Debug)]
+pub struct TexturedShape {
+    pub texture: TextureRef,
+    pub points: Vec<Vec2>,
+    pub uvs: Vec<Vec2>,
+    /// Multiplied with the texture.
+    pub color: Color,
+}
+
+impl TexturedShape {
+    pub fn new(texture: TextureRef, points: Vec<Vec2>, uvs: Vec<Vec2>) -> Self {
+        assert_eq!(
+            points.len(),
+            uvs.len(),
+            "TexturedShape needs exactly one uv per point"
+        );
+
+        Self {
+            texture,
+            points,
+            uvs,
+            color: Color::WHITE,
+        }
+    }
+
+    /// Stretches the whole texture over the shape's bounding box, like a stencil cut out of it.
+    pub fn projected(texture: TextureRef, points: Vec<Vec2>) -> Self {
+        let uvs = bounding_box_uvs(&points);
+        Self::new(texture, points, uvs)
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Triangulates the shape. Returns each vertex as a position and uv, and the indices.
+    pub fn gen_mesh(&self) -> (Vec<(Vec2, Vec2)>, Vec<u32>) {
+        if self.points.len() < 3 {
+            return (vec![], vec![]);
+        }
+
+        let mut builder = lyon::tessellation::path::Path::builder_with_attributes(2);
+        builder.begin(lyon_point(self.points[0]), &self.uvs[0].to_array());
+        for (point, uv) in self.points[1..].iter().zip(&self.uvs[1..]) {
+            builder.line_to(lyon_point(*point), &uv.to_array());
+        }
+        builder.end(true);
+        let path = builder.build();
+
+        struct VertexConstructor;
+
+        impl lyon::tessellation::FillVertexConstructor<(Vec2, Vec2)> for VertexConstructor {
+            fn new_vertex(&mut self, mut vertex: lyon::tessellation::FillVertex) -> (Vec2, Vec2) {
+                let position = vertex.position();
+                // only different from the points' uvs where edges cross each other
+                let uv = vertex.interpolated_attributes();
+                (Vec2::new(position.x, position.y), Vec2::new(uv[0], uv[1]))
+            }
+        }
+
+        let mut tessellator = lyon::tessellation::FillTessellator::new();
+        let mut buffers = lyon::tessellation::VertexBuffers::<(Vec2, Vec2), u32>::new();
+
+        if tessellator
+            .tessellate_path(
+                &path,
+                &lyon::tessellation::FillOptions::non_zero(),
+                &mut lyon::tessellation::BuffersBuilder::new(&mut buffers, VertexConstructor),
+            )
+            .or_report()
+            .is_none()
+        {
+            return (vec![], vec![]);
+        }
+
+        (buffers.vertices, buffers.indices)
+    }
+}
+
+impl HasBounds2D for TexturedShape {
+    fn bounds(&self) -> AABB2D {
+        points_bounds(&self.points)
+    }
+}
+
+impl Shape2D for TexturedShape {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let (vertices, indices) = gen_mesh_from_points(&self.points, self.color);
+        let indices = indices.iter().map(|n| n + starting_index).collect();
+        (indices, vertices)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        let (vertices, indices) = self.gen_mesh();
+        if !indices.is_empty() {
+            draw_queue.add_textured_mesh(self.texture, &vertices, &indices, self.color);
+        }
+    }
+}
+
+/// Texture coordinates that stretch a texture over the bounding box of `points`.
+pub fn bounding_box_uvs(points: &[Vec2]) -> Vec<Vec2> {
+    let bounds = points_bounds(points);
+    let size = (bounds.max - bounds.min).max(Vec2::splat(f32::EPSILON));
+
+    points
+        .iter()
+        .map(|point| (*point - bounds.min) / size)
+        .collect()
+}
+
+fn points_bounds(points: &[Vec2]) -> AABB2D {
+    let Some(&first) = points.first() else {
+        return AABB2D::new(Vec2::ZERO, Vec2::ZERO);
+    };
+
+    let (min, max) = points[1..]
+        .iter()
+        .fold((first, first), |(min, max), point| {
+            (min.min(*point), max.max(*point))
+        });
+    AABB2D::new(min, max)
+}
+
+/// A rectangle with rounded corners. The radius is clamped to half of the shorter side, so a
+/// huge radius gives a pill shape.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoundedRect {
+    pub top_left: Vec2,
+    pub size: Vec2,
+    pub radius: f32,
+    pub color: Color,
+}
+
+impl HasBounds2D for RoundedRect {
+    fn bounds(&self) -> AABB2D {
+        AABB2D::new(self.top_left, self.top_left + self.size)
+    }
+}
+
+impl RoundedRect {
+    pub fn gen_points(&self) -> Vec<Vec2> {
+        let radius = self.radius.clamp(0.0, self.size.min_element() * 0.5);
+        rounded_rect_points(self.top_left, self.size, radius, arc_segments(radius))
+    }
+}
+
+impl Shape2D for RoundedRect {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let (vertices, indices) = gen_mesh_from_points(&self.gen_points(), self.color);
+        let indices = indices.iter().map(|n| n + starting_index).collect();
+        (indices, vertices)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        draw_queue.add_shape(self);
+    }
+}
+
+/// The outline of a `RoundedRect`, centered on its edge like `draw_rect_outline`.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RoundedRectOutline {
+    pub top_left: Vec2,
+    pub size: Vec2,
+    pub radius: f32,
+    pub thickness: f32,
+    pub color: Color,
+}
+
+impl HasBounds2D for RoundedRectOutline {
+    fn bounds(&self) -> AABB2D {
+        let half_thick = Vec2::splat(self.thickness * 0.5);
+        AABB2D::new(
+            self.top_left - half_thick,
+            self.top_left + self.size + half_thick,
+        )
+    }
+}
+
+impl Shape2D for RoundedRectOutline {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let radius = self.radius.clamp(0.0, self.size.min_element() * 0.5);
+        let half_thick = self.thickness * 0.5;
+        // both edges need the same number of points to be stitched together
+        let segments = arc_segments(radius + half_thick);
+
+        let outer = rounded_rect_points(
+            self.top_left - Vec2::splat(half_thick),
+            self.size + Vec2::splat(self.thickness),
+            radius + half_thick,
+            segments,
+        );
+        let inner_size = (self.size - Vec2::splat(self.thickness)).max(Vec2::ZERO);
+        let inner = rounded_rect_points(
+            self.top_left + Vec2::splat(half_thick),
+            inner_size,
+            (radius - half_thick).clamp(0.0, inner_size.min_element() * 0.5),
+            segments,
+        );
+
+        gen_ring_mesh(&outer, &inner, true, self.color, starting_index)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        draw_queue.add_shape(self);
+    }
+}
+
+/// A line with round ends, or a circle stretched between two points.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Capsule2D {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub radius: f32,
+    pub color: Color,
+}
+
+impl HasBounds2D for Capsule2D {
+    fn bounds(&self) -> AABB2D {
+        AABB2D::new(
+            self.start.min(self.end) - Vec2::splat(self.radius),
+            self.start.max(self.end) + Vec2::splat(self.radius),
+        )
+    }
+}
+
+impl Capsule2D {
+    pub fn gen_points(&self) -> Vec<Vec2> {
+        capsule_points(self.start, self.end, self.radius, arc_segments(self.radius))
+    }
+}
+
+impl Shape2D for Capsule2D {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let (vertices, indices) = gen_mesh_from_points(&self.gen_points(), self.color);
+        let indices = indices.iter().map(|n| n + starting_index).collect();
+        (indices, vertices)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        draw_queue.add_shape(self);
+    }
+}
+
+/// The outline of a `Capsule2D`, centered on its edge.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Capsule2DOutline {
+    pub start: Vec2,
+    pub end: Vec2,
+    pub radius: f32,
+    pub thickness: f32,
+    pub color: Color,
+}
+
+impl HasBounds2D for Capsule2DOutline {
+    fn bounds(&self) -> AABB2D {
+        let extent = Vec2::splat(self.radius + self.thickness * 0.5);
+        AABB2D::new(
+            self.start.min(self.end) - extent,
+            self.start.max(self.end) + extent,
+        )
+    }
+}
+
+impl Shape2D for Capsule2DOutline {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let half_thick = self.thickness * 0.5;
+        let segments = arc_segments(self.radius + half_thick);
+
+        let outer = capsule_points(self.start, self.end, self.radius + half_thick, segments);
+        let inner = capsule_points(
+            self.start,
+            self.end,
+            (self.radius - half_thick).max(0.0),
+            segments,
+        );
+
+        gen_ring_mesh(&outer, &inner, true, self.color, starting_index)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        draw_queue.add_shape(self);
+    }
+}
+
+/// Part of a circle's outline, from `start_angle` to `end_angle` in radians. The line is
+/// centered on `radius`, same as the other outlines.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Arc {
+    pub center: Vec2,
+    pub radius: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+    pub thickness: f32,
+    pub color: Color,
+}
+
+impl HasBounds2D for Arc {
+    fn bounds(&self) -> AABB2D {
+        let half_thick = self.thickness * 0.5;
+        let outer = arc_bounds(
+            self.center,
+            self.radius + half_thick,
+            self.start_angle,
+            self.end_angle,
+        );
+        let inner = arc_bounds(
+            self.center,
+            (self.radius - half_thick).max(0.0),
+            self.start_angle,
+            self.end_angle,
+        );
+        AABB2D::new(outer.min.min(inner.min), outer.max.max(inner.max))
+    }
+}
+
+impl Shape2D for Arc {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let half_thick = self.thickness * 0.5;
+        let sweep = clamp_sweep(self.end_angle - self.start_angle);
+        let segments = sweep_segments(self.radius + half_thick, sweep);
+
+        let outer = arc_points(
+            self.center,
+            self.radius + half_thick,
+            self.start_angle,
+            sweep,
+            segments,
+        );
+        let inner = arc_points(
+            self.center,
+            (self.radius - half_thick).max(0.0),
+            self.start_angle,
+            sweep,
+            segments,
+        );
+
+        gen_ring_mesh(&outer, &inner, false, self.color, starting_index)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        draw_queue.add_shape(self);
+    }
+}
+
+/// A full circle outline, but made of triangles like the other shapes instead of drawn by the
+/// circle shader, so it can be used as a mask or batched with them.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ring {
+    pub center: Vec2,
+    pub radius: f32,
+    pub thickness: f32,
+    pub color: Color,
+}
+
+impl HasBounds2D for Ring {
+    fn bounds(&self) -> AABB2D {
+        AABB2D::from_center_size(
+            self.center,
+            Vec2::splat((self.radius + self.thickness * 0.5) * 2.0),
+        )
+    }
+}
+
+impl Shape2D for Ring {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let half_thick = self.thickness * 0.5;
+        let segments = sweep_segments(self.radius + half_thick, TAU);
+
+        // the last point would be the same as the first one, the ring closes by itself
+        let mut outer = arc_points(self.center, self.radius + half_thick, 0.0, TAU, segments);
+        let mut inner = arc_points(
+            self.center,
+            (self.radius - half_thick).max(0.0),
+            0.0,
+            TAU,
+            segments,
+        );
+        outer.pop();
+        inner.pop();
+
+        gen_ring_mesh(&outer, &inner, true, self.color, starting_index)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        draw_queue.add_shape(self);
+    }
+}
+
+/// A filled pie slice from `start_angle` to `end_angle` in radians. Handy for cooldowns and
+/// radial progress bars.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sector {
+    pub center: Vec2,
+    pub radius: f32,
+    pub start_angle: f32,
+    pub end_angle: f32,
+    pub color: Color,
+}
+
+impl HasBounds2D for Sector {
+    fn bounds(&self) -> AABB2D {
+        let arc = arc_bounds(self.center, self.radius, self.start_angle, self.end_angle);
+        AABB2D::new(arc.min.min(self.center), arc.max.max(self.center))
+    }
+}
+
+impl Sector {
+    pub fn gen_points(&self) -> Vec<Vec2> {
+        let sweep = clamp_sweep(self.end_angle - self.start_angle);
+        let segments = sweep_segments(self.radius, sweep);
+
+        let mut points = vec![self.center];
+        points.extend(arc_points(
+            self.center,
+            self.radius,
+            self.start_angle,
+            sweep,
+            segments,
+        ));
+        points
+    }
+}
+
+impl Shape2D for Sector {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let (vertices, indices) = gen_mesh_from_points(&self.gen_points(), self.color);
+        let indices = indices.iter().map(|n| n + starting_index).collect();
+        (indices, vertices)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        draw_queue.add_shape(self);
+    }
+}
+
+/// How the corners of a `Polyline` are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineJoin {
+    /// Sharp corners. Very sharp ones get cut off so they don't go on forever.
+    #[default]
+    Miter,
+    Round,
+    Bevel,
+}
+
+/// How the ends of a `Polyline` or `Bezier` are drawn.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineCap {
+    /// Stops right at the end point.
+    #[default]
+    Butt,
+    /// Goes half the thickness past the end point.
+    Square,
+    Round,
+}
+
+impl LineJoin {
+    fn to_lyon(self) -> lyon::tessellation::LineJoin {
+        match self {
+            Self::Miter => lyon::tessellation::LineJoin::MiterClip,
+            Self::Round => lyon::tessellation::LineJoin::Round,
+            Self::Bevel => lyon::tessellation::LineJoin::Bevel,
+        }
+    }
+}
+
+impl LineCap {
+    fn to_lyon(self) -> lyon::tessellation::LineCap {
+        match self {
+            Self::Butt => lyon::tessellation::LineCap::Butt,
+            Self::Square => lyon::tessellation::LineCap::Square,
+            Self::Round => lyon::tessellation::LineCap::Round,
+        }
+    }
+}
+
+/// Several connected lines, with proper corners where they meet instead of the overlapping
+/// ends you'd get from drawing each `Line` separately.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Polyline {
+    pub points: Vec<Vec2>,
+    pub thickness: f32,
+    pub color: Color,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    /// Joins the last point back up to the first.
+    pub closed: bool,
+}
+
+impl Polyline {
+    pub fn new(points: Vec<Vec2>, thickness: f32, color: Color) -> Self {
+        Self {
+            points,
+            thickness,
+            color,
+            join: LineJoin::default(),
+            cap: LineCap::default(),
+            closed: false,
+        }
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+
+    pub fn closed(mut self) -> Self {
+        self.closed = true;
+        self
+    }
+}
+
+impl HasBounds2D for Polyline {
+    fn bounds(&self) -> AABB2D {
+        let AABB2D { min, max } = points_bounds(&self.points);
+
+        // miters can poke out past the points, up to the miter limit
+        let extent = match self.join {
+            LineJoin::Miter => self.thickness * 0.5 * MITER_LIMIT,
+            LineJoin::Round | LineJoin::Bevel => self.thickness * 0.5,
+        };
+        AABB2D::new(min - Vec2::splat(extent), max + Vec2::splat(extent))
+    }
+}
+
+impl Shape2D for Polyline {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        if self.points.len() < 2 {
+            return (vec![], vec![]);
+        }
+
+        let mut builder = lyon::tessellation::path::Path::builder();
+        builder.begin(lyon_point(self.points[0]));
+        for point in &self.points[1..] {
+            builder.line_to(lyon_point(*point));
+        }
+        builder.end(self.closed);
+
+        let options = stroke_options(self.thickness, self.cap).with_line_join(self.join.to_lyon());
+        let (vertices, indices) = gen_stroke_mesh(&builder.build(), &options, self.color);
+        let indices = indices.iter().map(|n| n + starting_index).collect();
+        (indices, vertices)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        draw_queue.add_shape(self);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CubicBezier {
+    pub start: Vec2,
+    pub control_1: Vec2,
+    pub control_2: Vec2,
+    pub end: Vec2,
+}
+
+impl CubicBezier {
+    pub fn new(start: Vec2, control_1: Vec2, control_2: Vec2, end: Vec2) -> Self {
+        Self {
+            start,
+            control_1,
+            control_2,
+            end,
+        }
+    }
+
+    /// Point on the curve, `t` going from 0 at the start to 1 at the end.
+    pub fn point_at(&self, t: f32) -> Vec2 {
+        let u = 1.0 - t;
+        self.start * (u * u * u)
+            + self.control_1 * (3.0 * u * u * t)
+            + self.control_2 * (3.0 * u * t * t)
+            + self.end * (t * t * t)
+    }
+}
+
+/// A cubic bezier curve drawn as a line. It's split into more pieces where it bends more, so
+/// gentle curves stay cheap.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bezier {
+    pub curve: CubicBezier,
+    pub thickness: f32,
+    pub color: Color,
+    pub cap: LineCap,
+}
+
+impl HasBounds2D for Bezier {
+    fn bounds(&self) -> AABB2D {
+        // a bezier never leaves the box around its control points
+        let CubicBezier {
+            start,
+            control_1,
+            control_2,
+            end,
+        } = self.curve;
+        let extent = Vec2::splat(self.thickness * 0.5);
+
+        AABB2D::new(
+            start.min(control_1).min(control_2).min(end) - extent,
+            start.max(control_1).max(control_2).max(end) + extent,
+        )
+    }
+}
+
+impl Shape2D for Bezier {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let mut builder = lyon::tessellation::path::Path::builder();
+        builder.begin(lyon_point(self.curve.start));
+        builder.cubic_bezier_to(
+            lyon_point(self.curve.control_1),
+            lyon_point(self.curve.control_2),
+            lyon_point(self.curve.end),
+        );
+        builder.end(false);
+
+        let options =
+            stroke_options(self.thickness, self.cap).with_line_join(LineJoin::Round.to_lyon());
+        let (vertices, indices) = gen_stroke_mesh(&builder.build(), &options, self.color);
+        let indices = indices.iter().map(|n| n + starting_index).collect();
+        (indices, vertices)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        draw_queue.add_shape(self);
+    }
+}
+
+/// How far a miter can stick out, in multiples of half the thickness. Same as lyon's default.
+const MITER_LIMIT: f32 = 4.0;
+
+fn lyon_point(point: Vec2) -> lyon::math::Point {
+    lyon::math::point(point.x, point.y)
+}
+
+fn stroke_options(thickness: f32, cap: LineCap) -> lyon::tessellation::StrokeOptions {
+    lyon::tessellation::StrokeOptions::default()
+        .with_line_width(thickness)
+        .with_line_cap(cap.to_lyon())
+        .with_miter_limit(MITER_LIMIT)
+        // thin lines are usually in world space where units are big, so they need finer curves
+        .with_tolerance((thickness * 0.05).clamp(0.001, 0.25))
+}
+
+/// Anything past a full turn would just draw over itself.
+fn clamp_sweep(sweep: f32) -> f32 {
+    sweep.clamp(-TAU, TAU)
+}
+
+fn sweep_segments(radius: f32, sweep: f32) -> usize {
+    let quarters = sweep.abs() / FRAC_PI_2;
+    ((arc_segments(radius) as f32 * quarters).ceil() as usize).max(1)
+}
+
+/// Tight bounds of an arc: its ends, plus wherever it crosses the left, right, top or bottom
+/// of the circle.
+fn arc_bounds(center: Vec2, radius: f32, start_angle: f32, end_angle: f32) -> AABB2D {
+    let sweep = clamp_sweep(end_angle - start_angle);
+    let (from, to) = if sweep >= 0.0 {
+        (start_angle, start_angle + sweep)
+    } else {
+        (start_angle + sweep, start_angle)
+    };
+
+    let start = center + Vec2::from_angle(from) * radius;
+    let end = center + Vec2::from_angle(to) * radius;
+    let mut min = start.min(end);
+    let mut max = start.max(end);
+
+    let mut quarter = (from / FRAC_PI_2).ceil();
+    while quarter * FRAC_PI_2 <= to {
+        let point = center + Vec2::from_angle(quarter * FRAC_PI_2) * radius;
+        min = min.min(point);
+        max = max.max(point);
+        quarter += 1.0;
+    }
+
+    AABB2D::new(min, max)
+}
+
+/// Points per quarter circle, enough that corners look smooth without wasting vertices on
+/// tiny ones.
+fn arc_segments(radius: f32) -> usize {
+    (radius.max(0.0).sqrt() * 2.0).ceil().clamp(4.0, 32.0) as usize
+}
+
+/// Points of `segments` steps along an arc, including both ends.
+fn arc_points(
+    center: Vec2,
+    radius: f32,
+    start_angle: f32,
+    sweep: f32,
+    segments: usize,
+) -> Vec<Vec2> {
+    (0..=segments)
+        .map(|i| {
+            let angle = start_angle + sweep * i as f32 / segments as f32;
+            center + Vec2::from_angle(angle) * radius
+        })
+        .collect()
+}
+
+fn rounded_rect_points(top_left: Vec2, size: Vec2, radius: f32, segments: usize) -> Vec<Vec2> {
+    let min = top_left + Vec2::splat(radius);
+    let max = top_left + size - Vec2::splat(radius);
+    let corners = [
+        (Vec2::new(max.x, max.y), 0.0),
+        (Vec2::new(min.x, max.y), FRAC_PI_2),
+        (Vec2::new(min.x, min.y), PI),
+        (Vec2::new(max.x, min.y), PI + FRAC_PI_2),
+    ];
+
+    corners
+        .into_iter()
+        .flat_map(|(center, start)| arc_points(center, radius, start, FRAC_PI_2, segments))
+        .collect()
+}
+
+fn capsule_points(start: Vec2, end: Vec2, radius: f32, segments: usize) -> Vec<Vec2> {
+    let direction = end - start;
+    let angle = if direction.length_squared() > 0.0 {
+        direction.to_angle()
+    } else {
+        0.0
+    };
+
+    let mut points = arc_points(end, radius, angle - FRAC_PI_2, PI, segments * 2);
+    points.extend(arc_points(
+        start,
+        radius,
+        angle + FRAC_PI_2,
+        PI,
+        segments * 2,
+    ));
+    points
+}
+
+/// Fills the space between two lines with the same number of points. If `closed`, the last
+/// points are joined back up with the first ones.
+fn gen_ring_mesh(
+    outer: &[Vec2],
+    inner: &[Vec2],
+    closed: bool,
+    color: Color,
+    starting_index: u32,
+) -> (Vec<u32>, Vec<Vertex2D>) {
+    debug_assert_eq!(outer.len(), inner.len());
+
+    let count = outer.len() as u32;
+    let mut vertices = Vec::with_capacity(outer.len() * 2);
+    for (outer, inner) in outer.iter().zip(inner) {
+        vertices.push(Vertex2D::new(outer.x, outer.y, color));
+        vertices.push(Vertex2D::new(inner.x, inner.y, color));
+    }
+
+    let segments = if closed {
+        count
+    } else {
+        count.saturating_sub(1)
+    };
+
+    let mut indices = Vec::with_capacity(segments as usize * 6);
+    for i in 0..segments {
+        let next = (i + 1) % count;
+        let (outer_a, inner_a) = (i * 2, i * 2 + 1);
+        let (outer_b, inner_b) = (next * 2, next * 2 + 1);
+        indices.extend_from_slice(&[outer_a, outer_b, inner_a, inner_a, outer_b, inner_b]);
+    }
+
+    let indices = indices.iter().map(|n| n + starting_index).collect();
+    (indices, vertices)
+}
+
 pub(crate) const QUAD_INDICES: [u32; 6] = [0, 1, 2, 1, 2, 3];
 
 pub(crate) const UNIT_QUAD: [Vertex2D; 4] = [
@@ -348,8 +1245,21 @@ define_draw_functions!(
     draw_line: start: Vec2, end: Vec2, thickness: f32, color: Color => Line { start, end, thickness, color },
     draw_poly: center: Vec2, sides: usize, radius: f32, rotation: f32, color: Color => Poly { center, sides, radius, rotation, color },
     draw_custom_shape: points: Vec<Vec2>, color: Color => CustomShape { points, color },
+    draw_shape_with_holes: outline: Vec<Vec2>, holes: Vec<Vec<Vec2>>, color: Color => ShapeWithHoles { outline, holes, color },
     draw_hexagon: center: Vec2, radius: f32, color: Color => Poly { center, sides: 6, radius, rotation: 0.0, color },
     draw_hexagon_pointy: center: Vec2, radius: f32, color: Color => Poly { center, sides: 6, radius, rotation: std::f32::consts::FRAC_PI_6, color },
+    draw_rounded_rect: top_left: Vec2, size: Vec2, radius: f32, color: Color => RoundedRect { top_left, size, radius, color },
+    draw_rounded_rect_outline: top_left: Vec2, size: Vec2, radius: f32, thickness: f32, color: Color => RoundedRectOutline { top_left, size, radius, thickness, color },
+    draw_capsule: start: Vec2, end: Vec2, radius: f32, color: Color => Capsule2D { start, end, radius, color },
+    draw_capsule_outline: start: Vec2, end: Vec2, radius: f32, thickness: f32, color: Color => Capsule2DOutline { start, end, radius, thickness, color },
+    draw_arc: center: Vec2, radius: f32, start_angle: f32, end_angle: f32, thickness: f32, color: Color => Arc { center, radius, start_angle, end_angle, thickness, color },
+    draw_ring: center: Vec2, radius: f32, thickness: f32, color: Color => Ring { center, radius, thickness, color },
+    draw_sector: center: Vec2, radius: f32, start_angle: f32, end_angle: f32, color: Color => Sector { center, radius, start_angle, end_angle, color },
+    draw_polyline: points: Vec<Vec2>, thickness: f32, color: Color => Polyline::new(points, thickness, color),
+    draw_polyline_ex: points: Vec<Vec2>, thickness: f32, color: Color, join: LineJoin, cap: LineCap => Polyline::new(points, thickness, color).with_join(join).with_cap(cap),
+    draw_bezier: curve: CubicBezier, thickness: f32, color: Color => Bezier { curve, thickness, color, cap: LineCap::Butt },
+    draw_textured_shape: texture: TextureRef, points: Vec<Vec2>, uvs: Vec<Vec2> => TexturedShape::new(texture, points, uvs),
+    draw_textured_custom_shape: texture: TextureRef, points: Vec<Vec2> => TexturedShape::projected(texture, points),
 );
 
 pub fn draw_circle(center: Vec2, radius: f32, color: Color) {
@@ -527,6 +1437,17 @@ pub fn draw_shape_world(shape: &impl Shape2D) {
     shape.draw_world();
 }
 
+fn quad_in(
+    quad: [Vertex2D; 4],
+    starting_index: u32,
+    arena: &Bump,
+) -> (BumpVec<'_, u32>, BumpVec<'_, Vertex2D>) {
+    (
+        BumpVec::from_iter_in(QUAD_INDICES.map(|n| n + starting_index), arena),
+        BumpVec::from_iter_in(quad, arena),
+    )
+}
+
 fn gen_mesh_from_points(points: &[Vec2], color: Color) -> (Vec<Vertex2D>, Vec<u32>) {
     if points.len() < 3 {
         return (vec![], vec![]);
@@ -554,16 +1475,108 @@ fn gen_mesh_from_points(points: &[Vec2], color: Color) -> (Vec<Vertex2D>, Vec<u3
     let mut tessellator = lyon::tessellation::FillTessellator::new();
     let mut buffers = lyon::tessellation::VertexBuffers::<Vertex2D, u32>::new();
 
-    tessellator
+    if tessellator
         .tessellate_path(
             &polygon,
             &lyon::tessellation::FillOptions::non_zero(),
             &mut lyon::tessellation::BuffersBuilder::new(&mut buffers, VertexConstructor { color }),
         )
-        .unwrap();
+        .or_report()
+        .is_none()
+    {
+        return (vec![], vec![]);
+    }
 
     let vertices = buffers.vertices;
     let indices = buffers.indices;
 
     (vertices, indices)
 }
+
+/// Like `gen_mesh_from_points`, but with holes. Uses the even-odd rule, so it doesn't matter
+/// which way round the holes go.
+fn gen_mesh_with_holes(
+    outline: &[Vec2],
+    holes: &[Vec<Vec2>],
+    color: Color,
+) -> (Vec<Vertex2D>, Vec<u32>) {
+    if outline.len() < 3 {
+        return (vec![], vec![]);
+    }
+
+    let mut builder = lyon::tessellation::path::Path::builder();
+    for contour in std::iter::once(outline).chain(holes.iter().map(Vec::as_slice)) {
+        if contour.len() < 3 {
+            continue;
+        }
+
+        builder.begin(lyon_point(contour[0]));
+        for point in &contour[1..] {
+            builder.line_to(lyon_point(*point));
+        }
+        builder.end(true);
+    }
+    let path = builder.build();
+
+    struct VertexConstructor {
+        color: Color,
+    }
+
+    impl lyon::tessellation::FillVertexConstructor<Vertex2D> for VertexConstructor {
+        fn new_vertex(&mut self, vertex: lyon::tessellation::FillVertex) -> Vertex2D {
+            let pos = vertex.position();
+            Vertex2D::new(pos.x, pos.y, self.color)
+        }
+    }
+
+    let mut tessellator = lyon::tessellation::FillTessellator::new();
+    let mut buffers = lyon::tessellation::VertexBuffers::<Vertex2D, u32>::new();
+
+    if tessellator
+        .tessellate_path(
+            &path,
+            &lyon::tessellation::FillOptions::even_odd(),
+            &mut lyon::tessellation::BuffersBuilder::new(&mut buffers, VertexConstructor { color }),
+        )
+        .or_report()
+        .is_none()
+    {
+        return (vec![], vec![]);
+    }
+
+    (buffers.vertices, buffers.indices)
+}
+
+fn gen_stroke_mesh(
+    path: &lyon::tessellation::path::Path,
+    options: &lyon::tessellation::StrokeOptions,
+    color: Color,
+) -> (Vec<Vertex2D>, Vec<u32>) {
+    struct VertexConstructor {
+        color: Color,
+    }
+
+    impl lyon::tessellation::StrokeVertexConstructor<Vertex2D> for VertexConstructor {
+        fn new_vertex(&mut self, vertex: lyon::tessellation::StrokeVertex) -> Vertex2D {
+            let pos = vertex.position();
+            Vertex2D::new(pos.x, pos.y, self.color)
+        }
+    }
+
+    let mut tessellator = lyon::tessellation::StrokeTessellator::new();
+    let mut buffers = lyon::tessellation::VertexBuffers::<Vertex2D, u32>::new();
+
+    if tessellator
+        .tessellate_path(
+            path,
+            options,
+            &mut lyon::tessellation::BuffersBuilder::new(&mut buffers, VertexConstructor { color }),
+        )
+        .or_report()
+        .is_none()
+    {
+        return (vec![], vec![]);
+    }
+
+    (buffers.vertices, buffers.indices)
+}
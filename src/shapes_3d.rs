@@ -1,7 +1,9 @@
+use bevy_math::{Mat4, Vec3};
 
-use bevy_math::Vec3;
+use crate::draw_queue_2d::Vertex3D;
 
-use crate::Vertex3D;
+mod primitives;
+pub use primitives::*;
 
 // has a bounding box. not exact. not to be used for proper collisions, just culling
 pub trait HasBounds3D {
@@ -13,6 +15,7 @@ pub trait Shape3D: HasBounds3D {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AABB3D {
     pub min: Vec3,
     pub max: Vec3,
@@ -46,4 +49,117 @@ impl AABB3D {
             max: self.max + Vec3::splat(amount),
         }
     }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut min = Vec3::splat(f32::INFINITY);
+        let mut max = Vec3::splat(f32::NEG_INFINITY);
+
+        for point in points {
+            min = min.min(point);
+            max = max.max(point);
+        }
+
+        Self { min, max }
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// Box that contains this one after being transformed by `matrix`. Will be bigger than
+    /// needed when rotated.
+    pub fn transformed(&self, matrix: Mat4) -> Self {
+        Self::from_points(
+            self.corners()
+                .into_iter()
+                .map(|corner| matrix.transform_point3(corner)),
+        )
+    }
+
+    /// Distance along the ray to where it enters the box, or 0 if the ray starts inside.
+    pub fn raycast(&self, ray: &Ray3D) -> Option<f32> {
+        let inv_dir = ray.direction.recip();
+        let t1 = (self.min - ray.origin) * inv_dir;
+        let t2 = (self.max - ray.origin) * inv_dir;
+
+        let t_min = t1.min(t2).max_element();
+        let t_max = t1.max(t2).min_element();
+
+        if t_max < 0.0 || t_min > t_max {
+            return None;
+        }
+
+        Some(t_min.max(0.0))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ray3D {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray3D {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    pub fn from_points(from: Vec3, to: Vec3) -> Self {
+        Self::new(from, to - from)
+    }
+
+    pub fn point_at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    pub fn transformed(&self, matrix: Mat4) -> Self {
+        Self::from_points(
+            matrix.transform_point3(self.origin),
+            matrix.transform_point3(self.origin + self.direction),
+        )
+    }
+
+    /// Möller–Trumbore. Returns the distance along the ray, hits both sides of the triangle.
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+        const EPSILON: f32 = 1e-7;
+
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let h = self.direction.cross(edge2);
+        let det = edge1.dot(h);
+
+        if det.abs() < EPSILON {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let s = self.origin - a;
+        let u = inv_det * s.dot(h);
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(edge1);
+        let v = inv_det * self.direction.dot(q);
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = inv_det * edge2.dot(q);
+        (t > EPSILON).then_some(t)
+    }
 }
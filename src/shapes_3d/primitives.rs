@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+use bevy_math::{Vec2, Vec3};
+
+use crate::{draw_queue_2d::MaterialVertex3D, object_3d::Mesh};
+
+/// Vertices and indices for a mesh, before they get uploaded to the GPU. All the generators
+/// are centered on the origin, with Y up, and wind their triangles counter-clockwise when seen
+/// from the outside.
+#[derive(Clone, Debug, Default)]
+pub struct MeshData {
+    pub vertices: Vec<MaterialVertex3D>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn build(&self) -> anyhow::Result<Mesh> {
+        Mesh::new(&self.vertices, &self.indices)
+    }
+
+    fn push_vertex(&mut self, position: Vec3, normal: Vec3, tex_coords: Vec2) -> u32 {
+        self.vertices.push(MaterialVertex3D {
+            position: position.into(),
+            normal: normal.into(),
+            tex_coords: tex_coords.into(),
+            tangent: [0.0; 4],
+        });
+        self.vertices.len() as u32 - 1
+    }
+
+    /// Indices for a `(rows + 1) * (columns + 1)` grid of vertices starting at `first`, laid out
+    /// row by row.
+    fn push_grid_indices(&mut self, first: u32, rows: u32, columns: u32) {
+        let stride = columns + 1;
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = first + row * stride + column;
+                let b = a + stride;
+
+                self.indices
+                    .extend_from_slice(&[a, a + 1, b, a + 1, b + 1, b]);
+            }
+        }
+    }
+
+    /// A flat disc facing `normal` (which should be +Y or -Y), made of a triangle fan.
+    fn push_disc(&mut self, center: Vec3, radius: f32, sectors: u32, normal: Vec3) {
+        let first = self.push_vertex(center, normal, Vec2::splat(0.5));
+
+        for i in 0..=sectors {
+            let theta = i as f32 / sectors as f32 * TAU;
+            let (sin, cos) = theta.sin_cos();
+            self.push_vertex(
+                center + Vec3::new(cos, 0.0, sin) * radius,
+                normal,
+                Vec2::new(cos, sin) * 0.5 + 0.5,
+            );
+        }
+
+        for i in 0..sectors {
+            let a = first + 1 + i;
+            if normal.y > 0.0 {
+                self.indices.extend_from_slice(&[first, a + 1, a]);
+            } else {
+                self.indices.extend_from_slice(&[first, a, a + 1]);
+            }
+        }
+    }
+
+    pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> Self {
+        let sectors = sectors.max(3);
+        let stacks = stacks.max(2);
+        let mut data = Self::default();
+
+        for stack in 0..=stacks {
+            let v = stack as f32 / stacks as f32;
+            let phi = v * PI;
+
+            for sector in 0..=sectors {
+                let u = sector as f32 / sectors as f32;
+                let normal = spherical(u * TAU, phi);
+                data.push_vertex(normal * radius, normal, Vec2::new(u, v));
+            }
+        }
+
+        data.push_grid_indices(0, stacks, sectors);
+        data
+    }
+
+    /// Sphere made by subdividing an icosahedron, so the triangles are all about the same size.
+    /// UVs use the same spherical mapping as `uv_sphere`, and will be a bit off along the seam.
+    pub fn icosphere(radius: f32, subdivisions: u32) -> Self {
+        let t = (1.0 + 5f32.sqrt()) / 2.0;
+
+        let mut positions: Vec<Vec3> = [
+            [-1.0, t, 0.0],
+            [1.0, t, 0.0],
+            [-1.0, -t, 0.0],
+            [1.0, -t, 0.0],
+            [0.0, -1.0, t],
+            [0.0, 1.0, t],
+            [0.0, -1.0, -t],
+            [0.0, 1.0, -t],
+            [t, 0.0, -1.0],
+            [t, 0.0, 1.0],
+            [-t, 0.0, -1.0],
+            [-t, 0.0, 1.0],
+        ]
+        .into_iter()
+        .map(|p| Vec3::from(p).normalize())
+        .collect();
+
+        #[rustfmt::skip]
+        let mut faces: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    let middle = (positions[a as usize] + positions[b as usize]).normalize();
+                    positions.push(middle);
+                    positions.len() as u32 - 1
+                })
+            };
+
+            faces = faces
+                .into_iter()
+                .flat_map(|[a, b, c]| {
+                    let ab = midpoint(a, b);
+                    let bc = midpoint(b, c);
+                    let ca = midpoint(c, a);
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let mut data = Self::default();
+        for normal in positions {
+            let u = 0.5 + normal.z.atan2(normal.x) / TAU;
+            let v = normal.y.clamp(-1.0, 1.0).acos() / PI;
+            data.push_vertex(normal * radius, normal, Vec2::new(u, v));
+        }
+        data.indices = faces.into_iter().flatten().collect();
+
+        data
+    }
+
+    /// `height` is the length of the straight middle part, so the whole capsule is
+    /// `height + radius * 2` tall.
+    pub fn capsule(radius: f32, height: f32, sectors: u32, rings: u32) -> Self {
+        let sectors = sectors.max(3);
+        let rings = rings.max(1);
+        let half_height = height * 0.5;
+        let total_height = height + radius * 2.0;
+        let mut data = Self::default();
+
+        // each hemisphere gets `rings + 1` rings, the two at the equator make up the cylinder
+        for ring in 0..=rings * 2 + 1 {
+            let (phi, offset) = if ring <= rings {
+                (ring as f32 / rings as f32 * PI * 0.5, half_height)
+            } else {
+                ((ring - 1) as f32 / rings as f32 * PI * 0.5, -half_height)
+            };
+
+            for sector in 0..=sectors {
+                let u = sector as f32 / sectors as f32;
+                let normal = spherical(u * TAU, phi);
+                let position = normal * radius + Vec3::Y * offset;
+                let v = 0.5 - position.y / total_height;
+                data.push_vertex(position, normal, Vec2::new(u, v));
+            }
+        }
+
+        data.push_grid_indices(0, rings * 2 + 1, sectors);
+        data
+    }
+
+    pub fn cylinder(radius: f32, height: f32, sectors: u32) -> Self {
+        let sectors = sectors.max(3);
+        let half_height = height * 0.5;
+        let mut data = Self::default();
+
+        for (y, v) in [(half_height, 0.0), (-half_height, 1.0)] {
+            for sector in 0..=sectors {
+                let u = sector as f32 / sectors as f32;
+                let (sin, cos) = (u * TAU).sin_cos();
+                let normal = Vec3::new(cos, 0.0, sin);
+                data.push_vertex(normal * radius + Vec3::Y * y, normal, Vec2::new(u, v));
+            }
+        }
+        data.push_grid_indices(0, 1, sectors);
+
+        data.push_disc(Vec3::Y * half_height, radius, sectors, Vec3::Y);
+        data.push_disc(Vec3::NEG_Y * half_height, radius, sectors, Vec3::NEG_Y);
+
+        data
+    }
+
+    /// Point at the top, flat base at the bottom.
+    pub fn cone(radius: f32, height: f32, sectors: u32) -> Self {
+        let sectors = sectors.max(3);
+        let half_height = height * 0.5;
+        let mut data = Self::default();
+
+        let side_normal = |theta: f32| {
+            let (sin, cos) = theta.sin_cos();
+            Vec3::new(cos * height, radius, sin * height).normalize()
+        };
+
+        // the tip is split per sector so each face gets its own normal
+        for sector in 0..=sectors {
+            let u = sector as f32 / sectors as f32;
+            let theta = u * TAU;
+            let (sin, cos) = theta.sin_cos();
+
+            let tip_u = (sector as f32 + 0.5) / sectors as f32;
+            data.push_vertex(
+                Vec3::Y * half_height,
+                side_normal(tip_u * TAU),
+                Vec2::new(tip_u, 0.0),
+            );
+            data.push_vertex(
+                Vec3::new(cos * radius, -half_height, sin * radius),
+                side_normal(theta),
+                Vec2::new(u, 1.0),
+            );
+        }
+
+        for sector in 0..sectors {
+            let tip = sector * 2;
+            let base = tip + 1;
+            data.indices.extend_from_slice(&[tip, base + 2, base]);
+        }
+
+        data.push_disc(Vec3::NEG_Y * half_height, radius, sectors, Vec3::NEG_Y);
+
+        data
+    }
+
+    /// Lies flat in the XZ plane. `major_radius` is from the center to the middle of the tube,
+    /// `minor_radius` is the radius of the tube itself.
+    pub fn torus(
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> Self {
+        let major_segments = major_segments.max(3);
+        let minor_segments = minor_segments.max(3);
+        let mut data = Self::default();
+
+        for minor in 0..=minor_segments {
+            let v = minor as f32 / minor_segments as f32;
+            let (phi_sin, phi_cos) = (v * TAU).sin_cos();
+
+            for major in 0..=major_segments {
+                let u = major as f32 / major_segments as f32;
+                let (theta_sin, theta_cos) = (u * TAU).sin_cos();
+
+                let normal = Vec3::new(phi_cos * theta_cos, phi_sin, phi_cos * theta_sin);
+                let center = Vec3::new(theta_cos, 0.0, theta_sin) * major_radius;
+                data.push_vertex(center + normal * minor_radius, normal, Vec2::new(u, v));
+            }
+        }
+
+        // rings go bottom to top here instead of top to bottom, so flip the winding
+        let first = data.indices.len();
+        data.push_grid_indices(0, minor_segments, major_segments);
+        for triangle in data.indices[first..].chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+
+        data
+    }
+
+    /// Flat in the XZ plane, facing up. Split into `subdivisions * subdivisions` quads.
+    pub fn plane(size: Vec2, subdivisions: u32) -> Self {
+        let subdivisions = subdivisions.max(1);
+        let mut data = Self::default();
+
+        for row in 0..=subdivisions {
+            let v = row as f32 / subdivisions as f32;
+
+            for column in 0..=subdivisions {
+                let u = column as f32 / subdivisions as f32;
+                let position = Vec3::new((u - 0.5) * size.x, 0.0, (v - 0.5) * size.y);
+                data.push_vertex(position, Vec3::Y, Vec2::new(u, v));
+            }
+        }
+
+        let first = data.indices.len();
+        data.push_grid_indices(0, subdivisions, subdivisions);
+        for triangle in data.indices[first..].chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+
+        data
+    }
+}
+
+/// Point on the unit sphere. `phi` is 0 at the top and PI at the bottom.
+fn spherical(theta: f32, phi: f32) -> Vec3 {
+    let (theta_sin, theta_cos) = theta.sin_cos();
+    let (phi_sin, phi_cos) = phi.sin_cos();
+    Vec3::new(phi_sin * theta_cos, phi_cos, phi_sin * theta_sin)
+}
+
+pub fn uv_sphere_mesh(radius: f32, sectors: u32, stacks: u32) -> anyhow::Result<Mesh> {
+    MeshData::uv_sphere(radius, sectors, stacks).build()
+}
+
+pub fn icosphere_mesh(radius: f32, subdivisions: u32) -> anyhow::Result<Mesh> {
+    MeshData::icosphere(radius, subdivisions).build()
+}
+
+pub fn capsule_mesh(radius: f32, height: f32, sectors: u32, rings: u32) -> anyhow::Result<Mesh> {
+    MeshData::capsule(radius, height, sectors, rings).build()
+}
+
+pub fn cylinder_mesh(radius: f32, height: f32, sectors: u32) -> anyhow::Result<Mesh> {
+    MeshData::cylinder(radius, height, sectors).build()
+}
+
+pub fn cone_mesh(radius: f32, height: f32, sectors: u32) -> anyhow::Result<Mesh> {
+    MeshData::cone(radius, height, sectors).build()
+}
+
+pub fn torus_mesh(
+    major_radius: f32,
+    minor_radius: f32,
+    major_segments: u32,
+    minor_segments: u32,
+) -> anyhow::Result<Mesh> {
+    MeshData::torus(major_radius, minor_radius, major_segments, minor_segments).build()
+}
+
+pub fn plane_mesh(size: Vec2, subdivisions: u32) -> anyhow::Result<Mesh> {
+    MeshData::plane(size, subdivisions).build()
+}
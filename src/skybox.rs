@@ -0,0 +1,240 @@
+use std::f32::consts::{PI, TAU};
+use std::sync::OnceLock;
+
+use bevy_math::{Mat4, Vec3};
+use glium::{
+    Surface, Texture2d,
+    texture::{MipmapsOption, RawImage2d, UncompressedFloatFormat},
+    uniform,
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior, SamplerWrapFunction},
+};
+use image::Rgba32FImage;
+
+use crate::{
+    error::EngineResult,
+    get_state,
+    post_processing::{POSTPROCESS_VERTEX_SHADER, render_fullscreen_quad},
+    programs::{ProgramRef, cached_program, load_program},
+    textures::{EngineTexture, TextureRef},
+    utils::EngineCreate,
+};
+
+/// Equirectangular maps bigger than this get scaled down when built from cube faces.
+const MAX_EQUIRECTANGULAR_HEIGHT: u32 = 2048;
+
+/// An environment drawn behind all 3D geometry. Internally it's always stored as a single
+/// equirectangular texture, whether it was made from six cubemap faces or from an HDR panorama.
+///
+/// With `lighting_strength` above 0, materials also get the environment as `environment_map`
+/// (and the strength as `environment_strength`), which the built in Blinn-Phong material uses
+/// for ambient light. Set a `reflectivity` float on the material to also get reflections.
+#[derive(Clone, Copy, Debug)]
+pub struct Skybox {
+    pub texture: TextureRef,
+    pub lighting_strength: f32,
+}
+
+/// Order that cubemap faces are passed in, following the OpenGL convention.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl Skybox {
+    /// Builds a skybox from six images in the order +X, -X, +Y, -Y, +Z, -Z. Faces must all be
+    /// the same size and square.
+    pub fn from_faces(faces: [&[u8]; 6]) -> anyhow::Result<Self> {
+        let faces = faces
+            .into_iter()
+            .map(|bytes| Ok(image::load_from_memory(bytes)?.to_rgba32f()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let equirectangular = faces_to_equirectangular(&faces.try_into().unwrap())?;
+        Self::from_image(equirectangular)
+    }
+
+    /// Builds a skybox from an equirectangular panorama, usually an `.hdr` file, but anything
+    /// the `image` crate can load works.
+    pub fn from_equirectangular(bytes: &[u8]) -> anyhow::Result<Self> {
+        Self::from_image(image::load_from_memory(bytes)?.to_rgba32f())
+    }
+
+    pub fn from_image(image: Rgba32FImage) -> anyhow::Result<Self> {
+        let state = get_state();
+        let dimensions = image.dimensions();
+
+        // images start at the top, textures start at the bottom
+        let mut image = image;
+        image::imageops::flip_vertical_in_place(&mut image);
+
+        let raw = RawImage2d::from_raw_rgba(image.into_raw(), dimensions);
+        let texture = Texture2d::with_format(
+            state.display(),
+            raw,
+            UncompressedFloatFormat::F16F16F16F16,
+            MipmapsOption::AutoGeneratedMipmaps,
+        )?;
+
+        let mut texture = EngineTexture::new(texture);
+        texture.magnify_filter = MagnifySamplerFilter::Linear;
+        texture.minify_filter = MinifySamplerFilter::LinearMipmapLinear;
+
+        Ok(Self {
+            texture: texture.create(),
+            lighting_strength: 0.0,
+        })
+    }
+
+    pub fn with_lighting(mut self, strength: f32) -> Self {
+        self.lighting_strength = strength;
+        self
+    }
+
+    pub(crate) fn draw<T: Surface>(&self, target: &mut T, view_proj: Mat4) -> anyhow::Result<()> {
+        let program = get_or_create_skybox_program()?;
+        let behaviour = SamplerBehavior {
+            wrap_function: (
+                SamplerWrapFunction::Repeat,
+                SamplerWrapFunction::Clamp,
+                SamplerWrapFunction::Clamp,
+            ),
+            magnify_filter: MagnifySamplerFilter::Linear,
+            minify_filter: MinifySamplerFilter::Linear,
+            ..Default::default()
+        };
+
+        let uniforms = uniform! {
+            environment_map: glium::uniforms::Sampler(&self.texture.get().gl_texture, behaviour),
+            inverse_view_proj: view_proj.inverse().to_cols_array_2d(),
+        };
+
+        render_fullscreen_quad(target, program.get(), &uniforms)
+    }
+}
+
+/// Which face a direction points at, and where on that face it lands, from (0, 0) at the top
+/// left to (1, 1) at the bottom right.
+pub fn cube_face_uv(direction: Vec3) -> (CubeFace, f32, f32) {
+    let abs = direction.abs();
+
+    let (face, sc, tc, ma) = if abs.x >= abs.y && abs.x >= abs.z {
+        if direction.x > 0.0 {
+            (CubeFace::PositiveX, -direction.z, -direction.y, abs.x)
+        } else {
+            (CubeFace::NegativeX, direction.z, -direction.y, abs.x)
+        }
+    } else if abs.y >= abs.z {
+        if direction.y > 0.0 {
+            (CubeFace::PositiveY, direction.x, direction.z, abs.y)
+        } else {
+            (CubeFace::NegativeY, direction.x, -direction.z, abs.y)
+        }
+    } else if direction.z > 0.0 {
+        (CubeFace::PositiveZ, direction.x, -direction.y, abs.z)
+    } else {
+        (CubeFace::NegativeZ, -direction.x, -direction.y, abs.z)
+    };
+
+    (face, (sc / ma + 1.0) * 0.5, (tc / ma + 1.0) * 0.5)
+}
+
+/// Direction that a pixel of an equirectangular map looks towards. `u` goes around the horizon
+/// starting behind the camera (-Z is in the middle), `v` goes from the top (0) to the bottom
+/// (1), same as the rows of an image.
+pub fn equirectangular_direction(u: f32, v: f32) -> Vec3 {
+    let longitude = (u - 0.5) * TAU;
+    let latitude = (0.5 - v) * PI;
+
+    Vec3::new(
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        -latitude.cos() * longitude.cos(),
+    )
+}
+
+/// Resamples six cube faces (+X, -X, +Y, -Y, +Z, -Z) into one equirectangular image, twice as
+/// wide as it is tall.
+pub fn faces_to_equirectangular(faces: &[Rgba32FImage; 6]) -> anyhow::Result<Rgba32FImage> {
+    let size = faces[0].width();
+
+    for face in faces {
+        anyhow::ensure!(
+            face.width() == face.height(),
+            "Skybox faces must be square, got {}x{}",
+            face.width(),
+            face.height()
+        );
+        anyhow::ensure!(
+            face.width() == size,
+            "Skybox faces must all be the same size, got {} and {}",
+            size,
+            face.width()
+        );
+    }
+
+    let height = (size * 2).min(MAX_EQUIRECTANGULAR_HEIGHT);
+    let width = height * 2;
+
+    Ok(Rgba32FImage::from_fn(width, height, |x, y| {
+        let u = (x as f32 + 0.5) / width as f32;
+        let v = (y as f32 + 0.5) / height as f32;
+
+        let (face, s, t) = cube_face_uv(equirectangular_direction(u, v));
+        let face = &faces[face as usize];
+
+        let px = ((s * size as f32) as u32).min(size - 1);
+        let py = ((t * size as f32) as u32).min(size - 1);
+        *face.get_pixel(px, py)
+    }))
+}
+
+/// Sets the skybox drawn behind all 3D geometry. Stays until replaced or cleared.
+pub fn set_skybox(skybox: Skybox) {
+    get_state().skybox = Some(skybox);
+}
+
+pub fn clear_skybox() {
+    get_state().skybox = None;
+}
+
+pub fn skybox() -> Option<Skybox> {
+    get_state().skybox
+}
+
+static SKYBOX_PROGRAM: OnceLock<ProgramRef> = OnceLock::new();
+
+fn get_or_create_skybox_program() -> EngineResult<ProgramRef> {
+    cached_program(&SKYBOX_PROGRAM, || {
+        load_program(POSTPROCESS_VERTEX_SHADER, SKYBOX_FRAGMENT_SHADER)
+    })
+}
+
+const SKYBOX_FRAGMENT_SHADER: &str = r#"
+#version 140
+in vec2 v_tex_coords;
+out vec4 color;
+uniform sampler2D environment_map;
+uniform mat4 inverse_view_proj;
+
+const float PI = 3.14159265359;
+
+void main() {
+    vec2 ndc = v_tex_coords * 2.0 - 1.0;
+    vec4 near = inverse_view_proj * vec4(ndc, 0.0, 1.0);
+    vec4 far = inverse_view_proj * vec4(ndc, 1.0, 1.0);
+    vec3 direction = normalize(far.xyz / far.w - near.xyz / near.w);
+
+    vec2 uv = vec2(
+        atan(direction.x, -direction.z) / (2.0 * PI) + 0.5,
+        asin(clamp(direction.y, -1.0, 1.0)) / PI + 0.5
+    );
+
+    // explicit lod, otherwise the wrap around at the back shows up as a seam
+    color = vec4(textureLod(environment_map, uv, 0.0).rgb, 1.0);
+}
+"#;
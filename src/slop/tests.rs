@@ -857,6 +857,20 @@ mod camera_tests {
         assert!((screen_pos.x - back.x).abs() < 0.001);
         assert!((screen_pos.y - back.y).abs() < 0.001);
     }
+
+    #[test]
+    fn test_culling_bounds_follow_the_camera() {
+        let mut camera = create_test_camera();
+        let bounds = camera.culling_bounds();
+        assert!(bounds.min.abs_diff_eq(Vec2::new(-1200.0, -1100.0), 0.01));
+        assert!(bounds.max.abs_diff_eq(Vec2::new(1200.0, 1100.0), 0.01));
+
+        // moved without `mark_dirty`, which the cache shouldn't need
+        camera.translation = Vec2::new(1000.0, 0.0);
+        let moved = camera.culling_bounds();
+        assert!(moved.min.abs_diff_eq(Vec2::new(-200.0, -1100.0), 0.01));
+        assert!(moved.max.abs_diff_eq(Vec2::new(2200.0, 1100.0), 0.01));
+    }
 }
 
 #[cfg(test)]
@@ -1341,3 +1355,6939 @@ mod raycast_tests {
         assert!((distance_from_center - circle.radius).abs() < 0.001);
     }
 }
+
+#[cfg(test)]
+mod raycast_3d_tests {
+    use crate::camera::Camera3D;
+    use crate::shapes_3d::*;
+    use bevy_math::{Mat4, Vec2, Vec3};
+
+    #[test]
+    fn test_ray_hits_aabb() {
+        let aabb = AABB3D::from_center_size(Vec3::new(0.0, 0.0, -10.0), Vec3::splat(2.0));
+        let ray = Ray3D::new(Vec3::ZERO, Vec3::NEG_Z);
+
+        let distance = aabb.raycast(&ray).unwrap();
+        assert!((distance - 9.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_ray_misses_aabb_behind() {
+        let aabb = AABB3D::from_center_size(Vec3::new(0.0, 0.0, 10.0), Vec3::splat(2.0));
+        let ray = Ray3D::new(Vec3::ZERO, Vec3::NEG_Z);
+
+        assert!(aabb.raycast(&ray).is_none());
+    }
+
+    #[test]
+    fn test_ray_inside_aabb() {
+        let aabb = AABB3D::from_center_size(Vec3::ZERO, Vec3::splat(2.0));
+        let ray = Ray3D::new(Vec3::ZERO, Vec3::X);
+
+        assert_eq!(aabb.raycast(&ray), Some(0.0));
+    }
+
+    #[test]
+    fn test_aabb_transformed() {
+        let aabb = AABB3D::from_center_size(Vec3::ZERO, Vec3::splat(2.0));
+        let moved = aabb.transformed(Mat4::from_translation(Vec3::new(5.0, 0.0, 0.0)));
+
+        assert_eq!(moved.min, Vec3::new(4.0, -1.0, -1.0));
+        assert_eq!(moved.max, Vec3::new(6.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_ray_hits_triangle() {
+        let ray = Ray3D::new(Vec3::new(0.0, 0.0, 5.0), Vec3::NEG_Z);
+        let distance = ray.intersect_triangle(
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+        );
+
+        assert!((distance.unwrap() - 5.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_mesh_raycast_finds_the_closest_triangle() {
+        use crate::object_3d::raycast_triangles;
+
+        // two triangles facing the ray, one behind the other
+        let positions = [
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 2.0),
+            Vec3::new(-1.0, -1.0, 2.0),
+            Vec3::new(1.0, -1.0, 2.0),
+        ];
+        let ray = Ray3D::new(Vec3::new(0.0, 0.0, 5.0), Vec3::NEG_Z);
+
+        let distance = raycast_triangles(&positions, &[0, 1, 2, 3, 4, 5], &ray);
+        assert!((distance.unwrap() - 3.0).abs() < 0.001);
+
+        let away = Ray3D::new(Vec3::new(0.0, 0.0, 5.0), Vec3::Z);
+        assert!(raycast_triangles(&positions, &[0, 1, 2, 3, 4, 5], &away).is_none());
+        assert!(raycast_triangles(&positions, &[0, 1, 9], &ray).is_none());
+    }
+
+    #[test]
+    fn test_ray_misses_triangle() {
+        let ray = Ray3D::new(Vec3::new(3.0, 0.0, 5.0), Vec3::NEG_Z);
+        let distance = ray.intersect_triangle(
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+        );
+
+        assert!(distance.is_none());
+    }
+
+    #[test]
+    fn test_screen_center_ray_points_at_target() {
+        let mut camera = Camera3D::new(800, 600);
+        camera.eye = Vec3::new(0.0, 0.0, 10.0);
+        camera.target = Vec3::ZERO;
+        camera.mark_dirty();
+
+        let ray = camera.screen_to_ray(Vec2::new(400.0, 300.0));
+        assert!((ray.direction - Vec3::NEG_Z).length() < 0.001);
+    }
+}
+
+#[cfg(test)]
+mod guides_tests {
+    use crate::guides::*;
+
+    #[test]
+    fn test_nice_step() {
+        assert_eq!(nice_step(1.0), 1.0);
+        assert_eq!(nice_step(1.5), 2.0);
+        assert_eq!(nice_step(3.0), 5.0);
+        assert_eq!(nice_step(7.0), 10.0);
+        assert!((nice_step(0.03) - 0.05).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_adaptive_grid_spacing_unchanged_when_far_apart() {
+        let (spacing, fade) = adaptive_grid_spacing(32.0, 1.0);
+        assert_eq!(spacing, 32.0);
+        assert_eq!(fade, 1.0);
+    }
+
+    #[test]
+    fn test_adaptive_grid_spacing_doubles_when_zoomed_out() {
+        let (spacing, _) = adaptive_grid_spacing(32.0, 0.5);
+        assert_eq!(spacing, 64.0);
+
+        let (spacing, _) = adaptive_grid_spacing(1.0, 1.0);
+        assert_eq!(spacing, 16.0);
+    }
+
+    #[test]
+    fn test_adaptive_grid_spacing_halves_when_zoomed_in() {
+        let (spacing, _) = adaptive_grid_spacing(32.0, 16.0);
+        assert_eq!(spacing, 2.0);
+    }
+}
+
+#[cfg(test)]
+mod primitive_mesh_tests {
+    use crate::shapes_3d::MeshData;
+    use bevy_math::{Vec2, Vec3};
+
+    fn all_primitives() -> Vec<(&'static str, MeshData)> {
+        vec![
+            ("uv_sphere", MeshData::uv_sphere(1.0, 16, 8)),
+            ("icosphere", MeshData::icosphere(1.0, 2)),
+            ("capsule", MeshData::capsule(0.5, 1.0, 16, 4)),
+            ("cylinder", MeshData::cylinder(1.0, 2.0, 16)),
+            ("cone", MeshData::cone(1.0, 2.0, 16)),
+            ("torus", MeshData::torus(1.0, 0.25, 16, 8)),
+            ("plane", MeshData::plane(Vec2::splat(2.0), 4)),
+        ]
+    }
+
+    #[test]
+    fn test_indices_in_range() {
+        for (name, data) in all_primitives() {
+            assert_eq!(data.indices.len() % 3, 0, "{name}");
+            assert!(
+                data.indices
+                    .iter()
+                    .all(|&i| (i as usize) < data.vertices.len()),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_normals_are_unit_length() {
+        for (name, data) in all_primitives() {
+            for vertex in &data.vertices {
+                let length = Vec3::from(vertex.normal).length();
+                assert!((length - 1.0).abs() < 0.001, "{name}: {length}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_triangles_face_outwards() {
+        for (name, data) in all_primitives() {
+            for triangle in data.indices.chunks_exact(3) {
+                let [a, b, c] =
+                    [triangle[0], triangle[1], triangle[2]].map(|i| data.vertices[i as usize]);
+                let face_normal = (Vec3::from(b.position) - Vec3::from(a.position))
+                    .cross(Vec3::from(c.position) - Vec3::from(a.position));
+
+                if face_normal.length() < 1e-6 {
+                    continue;
+                }
+
+                let vertex_normal =
+                    Vec3::from(a.normal) + Vec3::from(b.normal) + Vec3::from(c.normal);
+                assert!(face_normal.dot(vertex_normal) > 0.0, "{name}: {triangle:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_sphere_vertices_on_surface() {
+        for data in [MeshData::uv_sphere(2.0, 12, 6), MeshData::icosphere(2.0, 1)] {
+            for vertex in &data.vertices {
+                let distance = Vec3::from(vertex.position).length();
+                assert!((distance - 2.0).abs() < 0.001);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod headless_draw_tests {
+    use crate::draw_queue_2d::DrawQueue2D;
+    use crate::shapes_2d::*;
+    use crate::testing::{DrawCall2D, MockDrawTarget2D};
+    use crate::text_rendering::layout_text;
+    use crate::textures::TextureRef;
+    use crate::{color::Color, prelude::Transform2D};
+    use bevy_math::{Mat4, Vec2};
+
+    fn draw(queue: &mut DrawQueue2D) -> MockDrawTarget2D {
+        let mut target = MockDrawTarget2D::default();
+        queue.draw_to(&mut target, &Mat4::IDENTITY);
+        target
+    }
+
+    #[test]
+    fn test_empty_queue_draws_nothing() {
+        let mut queue = DrawQueue2D::empty();
+        assert!(draw(&mut queue).calls.is_empty());
+    }
+
+    #[test]
+    fn test_shapes_are_batched() {
+        let mut queue = DrawQueue2D::empty();
+        queue.add_shape(&Rect {
+            top_left: Vec2::ZERO,
+            size: Vec2::ONE,
+            color: Color::WHITE,
+        });
+        queue.add_shape(&Triangle {
+            points: [Vec2::ZERO, Vec2::X, Vec2::Y],
+            color: Color::WHITE,
+        });
+
+        let target = draw(&mut queue);
+        assert_eq!(target.calls.len(), 1);
+
+        let DrawCall2D::Shapes { vertices, indices } = &target.calls[0] else {
+            panic!("expected a shape batch");
+        };
+        assert_eq!(vertices.len(), 7);
+        assert_eq!(indices.len(), 9);
+        assert_eq!(&indices[6..], &[4, 5, 6]);
+        assert!(vertices[4].position[2] > vertices[0].position[2]);
+    }
+
+    #[test]
+    fn test_circles_are_instanced() {
+        let mut queue = DrawQueue2D::empty();
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        queue.add_circle(Vec2::ONE, Vec2::ONE, Color::WHITE);
+
+        let target = draw(&mut queue);
+        assert_eq!(target.calls.len(), 1);
+        assert!(
+            matches!(&target.calls[0], DrawCall2D::Circles { instances } if instances.len() == 2)
+        );
+    }
+
+    #[test]
+    fn test_sprites_are_batched_per_texture() {
+        let mut queue = DrawQueue2D::empty();
+        queue.add_sprite(
+            TextureRef::from_raw(0, 0),
+            Transform2D::IDENTITY,
+            Color::WHITE,
+            None,
+        );
+        queue.add_sprite(
+            TextureRef::from_raw(0, 0),
+            Transform2D::IDENTITY,
+            Color::WHITE,
+            None,
+        );
+        queue.add_sprite(
+            TextureRef::from_raw(1, 0),
+            Transform2D::IDENTITY,
+            Color::WHITE,
+            None,
+        );
+
+        let target = draw(&mut queue);
+        assert_eq!(target.calls.len(), 2);
+
+        for call in &target.calls {
+            let DrawCall2D::Sprites {
+                texture,
+                vertices,
+                indices,
+            } = call
+            else {
+                panic!("expected a sprite batch");
+            };
+            let count = if *texture == TextureRef::from_raw(0, 0) {
+                2
+            } else {
+                1
+            };
+            assert_eq!(vertices.len(), 4 * count);
+            assert_eq!(indices.len(), 6 * count);
+        }
+    }
+
+    #[test]
+    fn test_clear_empties_queue() {
+        let mut queue = DrawQueue2D::empty();
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        queue.clear();
+
+        assert!(draw(&mut queue).calls.is_empty());
+        assert_eq!(queue.current_z(), 0.0);
+    }
+
+    #[test]
+    fn test_zero_length_line_has_no_geometry() {
+        let line = Line {
+            start: Vec2::ONE,
+            end: Vec2::ONE,
+            thickness: 2.0,
+            color: Color::WHITE,
+        };
+        let (indices, vertices) = line.points(0);
+        assert!(indices.is_empty());
+        assert!(vertices.is_empty());
+    }
+
+    #[test]
+    fn test_poly_points() {
+        let poly = Poly {
+            sides: 6,
+            radius: 2.0,
+            center: Vec2::ZERO,
+            rotation: 0.0,
+            color: Color::WHITE,
+        };
+        let points = poly.gen_points();
+        assert_eq!(points.len(), 6);
+        assert!(points.iter().all(|p| (p.length() - 2.0).abs() < 0.001));
+    }
+
+    fn test_font() -> fontdue::Font {
+        fontdue::Font::from_bytes(
+            include_bytes!("../../assets/fonts/jetbrains.ttf") as &[u8],
+            fontdue::FontSettings::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_text_layout_positions_glyphs_left_to_right() {
+        let font = test_font();
+        let layout = layout_text(&font, "abc", 16.0);
+
+        assert_eq!(layout.glyphs.len(), 3);
+        assert!(layout.glyphs[0].position.x < layout.glyphs[1].position.x);
+        assert!(layout.glyphs[1].position.x < layout.glyphs[2].position.x);
+        assert!(layout.size.y > 0.0);
+    }
+
+    #[test]
+    fn test_text_layout_width_is_sum_of_advances() {
+        let font = test_font();
+        let one = layout_text(&font, "m", 16.0);
+        let three = layout_text(&font, "mmm", 16.0);
+
+        assert!((three.size.x - one.size.x * 3.0).abs() < 0.001);
+    }
+}
+
+#[cfg(test)]
+mod skybox_tests {
+    use crate::skybox::*;
+    use bevy_math::Vec3;
+    use image::{Rgba, Rgba32FImage};
+
+    #[test]
+    fn test_cube_face_uv_centers() {
+        let cases = [
+            (Vec3::X, CubeFace::PositiveX),
+            (Vec3::NEG_X, CubeFace::NegativeX),
+            (Vec3::Y, CubeFace::PositiveY),
+            (Vec3::NEG_Y, CubeFace::NegativeY),
+            (Vec3::Z, CubeFace::PositiveZ),
+            (Vec3::NEG_Z, CubeFace::NegativeZ),
+        ];
+
+        for (direction, expected) in cases {
+            let (face, s, t) = cube_face_uv(direction);
+            assert_eq!(face, expected);
+            assert!((s - 0.5).abs() < 1e-5 && (t - 0.5).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_cube_face_uv_up_is_top_of_side_faces() {
+        let (face, _, t) = cube_face_uv(Vec3::new(0.0, 0.5, -1.0));
+        assert_eq!(face, CubeFace::NegativeZ);
+        assert!(t < 0.5);
+    }
+
+    #[test]
+    fn test_equirectangular_direction() {
+        let forward = equirectangular_direction(0.5, 0.5);
+        assert!((forward - Vec3::NEG_Z).length() < 1e-5);
+
+        let up = equirectangular_direction(0.3, 0.0);
+        assert!((up - Vec3::Y).length() < 1e-5);
+
+        let right = equirectangular_direction(0.75, 0.5);
+        assert!((right - Vec3::X).length() < 1e-5);
+    }
+
+    fn solid_face(size: u32, value: f32) -> Rgba32FImage {
+        Rgba32FImage::from_pixel(size, size, Rgba([value, value, value, 1.0]))
+    }
+
+    #[test]
+    fn test_faces_to_equirectangular() {
+        let faces: [Rgba32FImage; 6] = std::array::from_fn(|i| solid_face(4, i as f32));
+        let image = faces_to_equirectangular(&faces).unwrap();
+
+        assert_eq!(image.dimensions(), (16, 8));
+        // top row looks up, middle of the image looks down -Z
+        assert_eq!(
+            image.get_pixel(8, 0)[0],
+            CubeFace::PositiveY as usize as f32
+        );
+        assert_eq!(
+            image.get_pixel(8, 4)[0],
+            CubeFace::NegativeZ as usize as f32
+        );
+        assert_eq!(
+            image.get_pixel(8, 7)[0],
+            CubeFace::NegativeY as usize as f32
+        );
+    }
+
+    #[test]
+    fn test_faces_to_equirectangular_rejects_mismatched_faces() {
+        let mut faces: [Rgba32FImage; 6] = std::array::from_fn(|_| solid_face(4, 0.0));
+        faces[3] = solid_face(8, 0.0);
+        assert!(faces_to_equirectangular(&faces).is_err());
+
+        faces[3] = Rgba32FImage::new(4, 2);
+        assert!(faces_to_equirectangular(&faces).is_err());
+    }
+}
+
+#[cfg(test)]
+mod terrain_tests {
+    use crate::terrain::*;
+    use bevy_math::Vec3;
+
+    fn ramp(width: usize, depth: usize) -> Heightmap {
+        let heights = (0..depth)
+            .flat_map(|_| (0..width).map(move |x| x as f32 / (width - 1) as f32))
+            .collect();
+        Heightmap::new(width, depth, heights).unwrap()
+    }
+
+    #[test]
+    fn test_heightmap_validates_size() {
+        assert!(Heightmap::new(1, 4, vec![0.0; 4]).is_err());
+        assert!(Heightmap::new(3, 3, vec![0.0; 8]).is_err());
+        assert!(Heightmap::new(3, 3, vec![0.0; 9]).is_ok());
+    }
+
+    #[test]
+    fn test_heightmap_sample_is_bilinear() {
+        let heightmap = Heightmap::new(2, 2, vec![0.0, 1.0, 1.0, 2.0]).unwrap();
+
+        assert_eq!(heightmap.sample(0.0, 0.0), 0.0);
+        assert_eq!(heightmap.sample(0.5, 0.5), 1.0);
+        assert_eq!(heightmap.sample(1.0, 0.25), 1.25);
+        assert_eq!(heightmap.sample(5.0, 5.0), 2.0);
+    }
+
+    #[test]
+    fn test_height_at() {
+        let heightmap = ramp(5, 5);
+        let settings = TerrainSettings {
+            origin: Vec3::new(10.0, 1.0, 0.0),
+            spacing: 2.0,
+            height_scale: 4.0,
+            ..Default::default()
+        };
+
+        assert_eq!(height_at(&heightmap, &settings, 10.0, 0.0), Some(1.0));
+        assert_eq!(height_at(&heightmap, &settings, 18.0, 8.0), Some(5.0));
+        assert_eq!(height_at(&heightmap, &settings, 14.0, 3.0), Some(3.0));
+        assert_eq!(height_at(&heightmap, &settings, 9.0, 0.0), None);
+        assert_eq!(height_at(&heightmap, &settings, 10.0, 8.5), None);
+    }
+
+    #[test]
+    fn test_lod_for_distance() {
+        let settings = TerrainSettings {
+            lod_levels: 3,
+            lod_distance: 10.0,
+            ..Default::default()
+        };
+
+        assert_eq!(settings.lod_for_distance(5.0), 0);
+        assert_eq!(settings.lod_for_distance(15.0), 1);
+        assert_eq!(settings.lod_for_distance(25.0), 2);
+        assert_eq!(settings.lod_for_distance(1000.0), 2);
+    }
+
+    #[test]
+    fn test_chunk_counts() {
+        let settings = TerrainSettings {
+            chunk_size: 4,
+            ..Default::default()
+        };
+
+        assert_eq!(chunk_counts(&ramp(9, 9), &settings), (2, 2));
+        assert_eq!(chunk_counts(&ramp(10, 5), &settings), (3, 1));
+    }
+
+    #[test]
+    fn test_chunk_mesh_lods() {
+        let heightmap = ramp(9, 9);
+        let settings = TerrainSettings {
+            chunk_size: 8,
+            ..Default::default()
+        };
+
+        let full = chunk_mesh_data(&heightmap, &settings, 0, 0, 0);
+        let half = chunk_mesh_data(&heightmap, &settings, 0, 0, 1);
+
+        // 9x9 grid plus 4 skirts of 9
+        assert_eq!(full.vertices.len(), 81 + 36);
+        // 5x5 grid plus 4 skirts of 5
+        assert_eq!(half.vertices.len(), 25 + 20);
+        assert_eq!(full.indices.len(), 8 * 8 * 6 + 4 * 8 * 12);
+    }
+
+    #[test]
+    fn test_chunk_mesh_faces_up() {
+        let heightmap = Heightmap::new(3, 3, vec![0.0; 9]).unwrap();
+        let data = chunk_mesh_data(&heightmap, &TerrainSettings::default(), 0, 0, 0);
+
+        let position = |i: u32| Vec3::from(data.vertices[i as usize].position);
+        let [a, b, c] = [data.indices[0], data.indices[1], data.indices[2]];
+        let normal = (position(b) - position(a)).cross(position(c) - position(a));
+
+        assert!(normal.y > 0.0);
+        assert!(
+            data.vertices[..9]
+                .iter()
+                .all(|v| v.normal == [0.0, 1.0, 0.0])
+        );
+    }
+}
+
+#[cfg(test)]
+mod blend_mode_tests {
+    use crate::materials::BlendMode;
+
+    #[test]
+    fn test_only_opaque_writes_depth() {
+        assert!(!BlendMode::Opaque.is_transparent());
+        assert!(BlendMode::Alpha.is_transparent());
+        assert!(BlendMode::Additive.is_transparent());
+        assert!(BlendMode::Multiply.is_transparent());
+        assert_eq!(BlendMode::default(), BlendMode::Opaque);
+    }
+
+    #[test]
+    fn test_opaque_isnt_blended() {
+        assert_eq!(BlendMode::Opaque.to_glium(), glium::Blend::default());
+    }
+
+    #[test]
+    fn test_blend_modes_differ() {
+        let modes = [BlendMode::Alpha, BlendMode::Additive, BlendMode::Multiply];
+        for (i, a) in modes.iter().enumerate() {
+            for b in &modes[i + 1..] {
+                assert_ne!(a.to_glium(), b.to_glium());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod blend_mode_2d_tests {
+    use crate::color::Color;
+    use crate::draw_queue_2d::{DrawQueue2D, Stencil2D, draw_parameters_2d};
+    use crate::materials::BlendMode;
+    use crate::testing::{DrawCall2D, MockDrawTarget2D};
+    use bevy_math::{Mat4, Vec2};
+    use glium::Blend;
+
+    #[test]
+    fn test_blend_modes_get_their_own_batches() {
+        let mut queue = DrawQueue2D::empty();
+        queue.set_blend_mode(BlendMode::Additive);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        queue.set_blend_mode(BlendMode::Alpha);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        queue.set_blend_mode(BlendMode::Multiply);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        queue.set_blend_mode(BlendMode::Opaque);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+
+        let mut target = MockDrawTarget2D::default();
+        queue.draw_to(&mut target, &Mat4::IDENTITY);
+
+        // opaque first, then alpha blended over it, then additive and multiply over both
+        assert_eq!(
+            target.blend_modes,
+            [
+                BlendMode::Opaque,
+                BlendMode::Alpha,
+                BlendMode::Additive,
+                BlendMode::Multiply
+            ]
+        );
+    }
+
+    #[test]
+    fn test_additive_is_drawn_later_but_stays_below_later_draws() {
+        let mut queue = DrawQueue2D::empty();
+        queue.set_blend_mode(BlendMode::Additive);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        queue.set_blend_mode(BlendMode::Alpha);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+
+        let mut target = MockDrawTarget2D::default();
+        queue.draw_to(&mut target, &Mat4::IDENTITY);
+
+        let zs: Vec<f32> = target
+            .calls
+            .iter()
+            .map(|call| {
+                let DrawCall2D::Circles { instances } = call else {
+                    panic!("expected circles");
+                };
+                instances[0].center[2]
+            })
+            .collect();
+
+        // the batches swap the call order, but the alpha circle is still in front
+        assert_eq!(target.blend_modes, [BlendMode::Alpha, BlendMode::Additive]);
+        assert!(zs[0] > zs[1]);
+    }
+
+    #[test]
+    fn test_opaque_replaces_what_is_under_it() {
+        let params = draw_parameters_2d(BlendMode::Opaque, Stencil2D::Off);
+        assert_eq!(params.blend, Blend::default());
+        assert!(params.depth.write);
+
+        let params = draw_parameters_2d(BlendMode::Alpha, Stencil2D::Off);
+        assert_ne!(params.blend, Blend::default());
+        assert!(params.depth.write);
+
+        let params = draw_parameters_2d(BlendMode::Additive, Stencil2D::Off);
+        assert!(!params.depth.write);
+    }
+
+    #[test]
+    fn test_blend_mode_keeps_draw_order_in_z() {
+        let mut queue = DrawQueue2D::empty();
+        let first = queue.current_z();
+        queue.set_blend_mode(BlendMode::Additive);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+
+        assert!(queue.current_z() > first);
+        assert_eq!(queue.blend_mode(), BlendMode::Additive);
+    }
+}
+
+#[cfg(test)]
+mod layer_tests {
+    use crate::color::Color;
+    use crate::draw_queue_2d::DrawQueue2D;
+    use crate::testing::{DrawCall2D, MockDrawTarget2D};
+    use bevy_math::{Mat4, Vec2};
+
+    fn circle_zs(queue: &mut DrawQueue2D) -> Vec<f32> {
+        let mut target = MockDrawTarget2D::default();
+        queue.draw_to(&mut target, &Mat4::IDENTITY);
+
+        let DrawCall2D::Circles { instances } = &target.calls[0] else {
+            panic!("expected circles");
+        };
+        instances
+            .iter()
+            .map(|instance| instance.center[2])
+            .collect()
+    }
+
+    #[test]
+    fn test_higher_layer_draws_on_top() {
+        let mut queue = DrawQueue2D::empty();
+        queue.set_layer(1.0);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        queue.set_layer(0.0);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+
+        let zs = circle_zs(&mut queue);
+        assert!(zs[0] > zs[1]);
+    }
+
+    #[test]
+    fn test_same_layer_keeps_call_order() {
+        let mut queue = DrawQueue2D::empty();
+        queue.set_layer(-2.0);
+        for _ in 0..3 {
+            queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        }
+
+        let zs = circle_zs(&mut queue);
+        assert!(zs[0] < zs[1] && zs[1] < zs[2]);
+    }
+
+    #[test]
+    fn test_layers_sort_again_after_more_draws() {
+        let mut queue = DrawQueue2D::empty();
+        queue.set_layer(1.0);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        circle_zs(&mut queue);
+
+        queue.set_layer(0.5);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+
+        let zs = circle_zs(&mut queue);
+        assert!(zs[0] > zs[1]);
+    }
+
+    #[test]
+    fn test_no_layers_leaves_z_alone() {
+        let mut queue = DrawQueue2D::empty();
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        let z = queue.current_z();
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+
+        assert_eq!(circle_zs(&mut queue)[1], z);
+    }
+}
+
+#[cfg(test)]
+mod mask_tests {
+    use crate::color::Color;
+    use crate::draw_queue_2d::{DrawQueue2D, Stencil2D};
+    use crate::shapes_2d::Rect;
+    use crate::testing::MockDrawTarget2D;
+    use bevy_math::{Mat4, Vec2};
+
+    fn mask() -> Rect {
+        Rect {
+            top_left: Vec2::ZERO,
+            size: Vec2::splat(10.0),
+            color: Color::WHITE,
+        }
+    }
+
+    fn draw(queue: &mut DrawQueue2D) -> MockDrawTarget2D {
+        let mut target = MockDrawTarget2D::default();
+        queue.draw_to(&mut target, &Mat4::IDENTITY);
+        target
+    }
+
+    #[test]
+    fn test_unmasked_draws_skip_the_stencil() {
+        let mut queue = DrawQueue2D::empty();
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+
+        let target = draw(&mut queue);
+        assert_eq!(target.stencils, [Stencil2D::Off]);
+        assert_eq!(target.stencil_clears, 0);
+    }
+
+    #[test]
+    fn test_mask_is_written_before_its_contents() {
+        let mut queue = DrawQueue2D::empty();
+        queue.push_mask(&mask());
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        queue.pop_mask();
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+
+        let target = draw(&mut queue);
+        assert_eq!(
+            target.stencils,
+            [
+                Stencil2D::Off,
+                Stencil2D::WriteMask(0),
+                Stencil2D::Inside(1)
+            ]
+        );
+        assert_eq!(target.stencil_clears, 1);
+    }
+
+    #[test]
+    fn test_nested_masks_intersect() {
+        let mut queue = DrawQueue2D::empty();
+        queue.push_mask(&mask());
+        queue.push_mask(&mask());
+        assert_eq!(queue.mask_depth(), 2);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        queue.pop_mask();
+        queue.pop_mask();
+
+        let target = draw(&mut queue);
+        assert_eq!(
+            target.stencils,
+            [
+                Stencil2D::WriteMask(0),
+                Stencil2D::WriteMask(1),
+                Stencil2D::Inside(2)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_masks_are_skipped() {
+        let mut queue = DrawQueue2D::empty();
+        queue.push_mask(&mask());
+        queue.pop_mask();
+
+        let target = draw(&mut queue);
+        assert!(target.calls.is_empty());
+        assert_eq!(target.stencil_clears, 0);
+    }
+}
+
+#[cfg(test)]
+mod clip_rect_tests {
+    use crate::color::Color;
+    use crate::draw_queue_2d::{DrawQueue2D, Stencil2D, clip_to_ndc, ndc_to_scissor};
+    use crate::shapes_2d::Rect as RectShape;
+    use crate::testing::MockDrawTarget2D;
+    use bevy_math::{Mat4, Rect, Vec2};
+
+    #[test]
+    fn test_nested_clip_rects_intersect() {
+        let mut queue = DrawQueue2D::empty();
+        queue.push_clip_rect(Rect::new(0.0, 0.0, 100.0, 100.0));
+        queue.push_clip_rect(Rect::new(50.0, 50.0, 200.0, 200.0));
+        assert_eq!(queue.clip_rect(), Some(Rect::new(50.0, 50.0, 100.0, 100.0)));
+
+        queue.pop_clip_rect();
+        assert_eq!(queue.clip_rect(), Some(Rect::new(0.0, 0.0, 100.0, 100.0)));
+        queue.pop_clip_rect();
+        assert_eq!(queue.clip_rect(), None);
+    }
+
+    #[test]
+    fn test_clipped_draws_come_after_unclipped_ones() {
+        let mut queue = DrawQueue2D::empty();
+        let clip = Rect::new(-0.5, -0.5, 0.5, 0.5);
+        queue.push_clip_rect(clip);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+        queue.pop_clip_rect();
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+
+        let mut target = MockDrawTarget2D::default();
+        queue.draw_to(&mut target, &Mat4::IDENTITY);
+
+        assert_eq!(target.clips, [None, Some(clip)]);
+        // no mask, so the stencil isn't touched
+        assert_eq!(target.stencils, [Stencil2D::Off, Stencil2D::Off]);
+        assert_eq!(target.stencil_clears, 0);
+    }
+
+    #[test]
+    fn test_masks_inside_clip_rects_are_drawn_unclipped() {
+        let mut queue = DrawQueue2D::empty();
+        queue.push_clip_rect(Rect::new(-0.5, -0.5, 0.5, 0.5));
+        queue.push_mask(&RectShape {
+            top_left: Vec2::ZERO,
+            size: Vec2::ONE,
+            color: Color::WHITE,
+        });
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::WHITE);
+
+        let mut target = MockDrawTarget2D::default();
+        queue.draw_to(&mut target, &Mat4::IDENTITY);
+
+        assert_eq!(target.clips[0], None);
+        assert!(target.clips[1].is_some());
+        assert_eq!(target.stencils[1], Stencil2D::Inside(1));
+    }
+
+    #[test]
+    fn test_clip_to_scissor_flips_y() {
+        // top left quarter of an 800x600 window
+        let projection = Mat4::orthographic_rh_gl(0.0, 800.0, 600.0, 0.0, -1.0, 1.0);
+        let ndc = clip_to_ndc(Rect::new(0.0, 0.0, 400.0, 300.0), &projection);
+        let scissor = ndc_to_scissor(ndc, (800, 600));
+
+        assert_eq!(
+            scissor,
+            glium::Rect {
+                left: 0,
+                bottom: 300,
+                width: 400,
+                height: 300
+            }
+        );
+    }
+
+    #[test]
+    fn test_scissor_is_clamped_to_the_target() {
+        let ndc = Rect::new(-2.0, 0.0, 0.0, 3.0);
+        let scissor = ndc_to_scissor(ndc, (100, 100));
+
+        assert_eq!(
+            scissor,
+            glium::Rect {
+                left: 0,
+                bottom: 50,
+                width: 50,
+                height: 50
+            }
+        );
+    }
+}
+
+#[cfg(test)]
+mod nine_slice_tests {
+    use crate::nine_slice::{NineSliceMargins, nine_slice_patches};
+    use bevy_math::{Rect, Vec2};
+
+    #[test]
+    fn test_corners_keep_their_size() {
+        let patches = nine_slice_patches(
+            Rect::new(0.0, 0.0, 200.0, 100.0),
+            Vec2::splat(32.0),
+            NineSliceMargins::uniform(8.0),
+        );
+        assert_eq!(patches.len(), 9);
+
+        let (top_left, top_left_source) = patches[0];
+        assert_eq!(top_left, Rect::new(0.0, 0.0, 8.0, 8.0));
+        assert_eq!(top_left_source, Rect::new(0.0, 0.0, 8.0, 8.0));
+
+        let (center, center_source) = patches[4];
+        assert_eq!(center, Rect::new(8.0, 8.0, 192.0, 92.0));
+        assert_eq!(center_source, Rect::new(8.0, 8.0, 24.0, 24.0));
+
+        let (bottom_right, bottom_right_source) = patches[8];
+        assert_eq!(bottom_right, Rect::new(192.0, 92.0, 200.0, 100.0));
+        assert_eq!(bottom_right_source, Rect::new(24.0, 24.0, 32.0, 32.0));
+    }
+
+    #[test]
+    fn test_small_rects_shrink_the_corners() {
+        let patches = nine_slice_patches(
+            Rect::new(0.0, 0.0, 10.0, 40.0),
+            Vec2::splat(32.0),
+            NineSliceMargins::new(10.0, 10.0, 4.0, 4.0),
+        );
+
+        // the middle column has no width left, so only corners and side edges remain
+        assert_eq!(patches.len(), 6);
+        assert_eq!(patches[0].0, Rect::new(0.0, 0.0, 5.0, 4.0));
+        assert_eq!(patches[1].0, Rect::new(5.0, 0.0, 10.0, 4.0));
+    }
+
+    #[test]
+    fn test_zero_margins_are_one_stretched_sprite() {
+        let patches = nine_slice_patches(
+            Rect::new(0.0, 0.0, 50.0, 50.0),
+            Vec2::splat(16.0),
+            NineSliceMargins::default(),
+        );
+
+        assert_eq!(
+            patches,
+            [(
+                Rect::new(0.0, 0.0, 50.0, 50.0),
+                Rect::new(0.0, 0.0, 16.0, 16.0)
+            )]
+        );
+    }
+}
+
+#[cfg(test)]
+mod rounded_shape_tests {
+    use crate::collisions::HasBounds2D;
+    use crate::color::Color;
+    use crate::shapes_2d::*;
+    use bevy_math::Vec2;
+
+    fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+        let direction = end - start;
+        let t = ((point - start).dot(direction) / direction.length_squared()).clamp(0.0, 1.0);
+        point.distance(start + direction * t)
+    }
+
+    #[test]
+    fn test_rounded_rect_stays_inside_its_bounds() {
+        let rect = RoundedRect {
+            top_left: Vec2::new(10.0, 20.0),
+            size: Vec2::new(100.0, 50.0),
+            radius: 12.0,
+            color: Color::WHITE,
+        };
+        let bounds = rect.bounds();
+
+        for point in rect.gen_points() {
+            assert!(point.x >= bounds.min.x - 1e-4 && point.x <= bounds.max.x + 1e-4);
+            assert!(point.y >= bounds.min.y - 1e-4 && point.y <= bounds.max.y + 1e-4);
+        }
+
+        let (indices, vertices) = rect.points(0);
+        assert!(!indices.is_empty());
+        assert!(indices.iter().all(|&i| (i as usize) < vertices.len()));
+    }
+
+    #[test]
+    fn test_rounded_rect_radius_is_clamped() {
+        let pill = RoundedRect {
+            top_left: Vec2::ZERO,
+            size: Vec2::new(100.0, 20.0),
+            radius: 1000.0,
+            color: Color::WHITE,
+        };
+
+        // with a radius of half the height, the left end is a semicircle around (10, 10)
+        for point in pill.gen_points().into_iter().filter(|p| p.x < 10.0) {
+            assert!((point.distance(Vec2::new(10.0, 10.0)) - 10.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_capsule_points_are_radius_from_the_segment() {
+        let capsule = Capsule2D {
+            start: Vec2::new(-5.0, 3.0),
+            end: Vec2::new(20.0, -8.0),
+            radius: 4.0,
+            color: Color::WHITE,
+        };
+
+        for point in capsule.gen_points() {
+            let distance = distance_to_segment(point, capsule.start, capsule.end);
+            assert!((distance - 4.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_outlines_are_closed_rings() {
+        let outline = Capsule2DOutline {
+            start: Vec2::ZERO,
+            end: Vec2::new(10.0, 0.0),
+            radius: 5.0,
+            thickness: 2.0,
+            color: Color::WHITE,
+        };
+        let (indices, vertices) = outline.points(7);
+
+        // two triangles for every pair of points, wrapping around at the end
+        assert_eq!(indices.len(), vertices.len() * 3);
+        assert!(
+            indices
+                .iter()
+                .all(|&i| i >= 7 && ((i - 7) as usize) < vertices.len())
+        );
+
+        let rect_outline = RoundedRectOutline {
+            top_left: Vec2::ZERO,
+            size: Vec2::splat(10.0),
+            radius: 1.0,
+            thickness: 4.0,
+            color: Color::WHITE,
+        };
+        let (indices, vertices) = rect_outline.points(0);
+        assert_eq!(indices.len(), vertices.len() * 3);
+    }
+}
+
+#[cfg(test)]
+mod arc_shape_tests {
+    use crate::collisions::HasBounds2D;
+    use crate::color::Color;
+    use crate::shapes_2d::*;
+    use bevy_math::Vec2;
+    use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(a.distance(b) < 1e-4, "{a} != {b}");
+    }
+
+    #[test]
+    fn test_quarter_sector_bounds() {
+        let sector = Sector {
+            center: Vec2::ZERO,
+            radius: 10.0,
+            start_angle: 0.0,
+            end_angle: FRAC_PI_2,
+            color: Color::WHITE,
+        };
+        let bounds = sector.bounds();
+
+        assert_close(bounds.min, Vec2::ZERO);
+        assert_close(bounds.max, Vec2::new(10.0, 10.0));
+    }
+
+    #[test]
+    fn test_arc_bounds_include_crossed_extremes() {
+        // from just above the right side, around through the bottom, to just past the left
+        let arc = Arc {
+            center: Vec2::ZERO,
+            radius: 10.0,
+            start_angle: -0.1,
+            end_angle: PI + 0.1,
+            thickness: 2.0,
+            color: Color::WHITE,
+        };
+        let bounds = arc.bounds();
+
+        assert!((bounds.max.x - 11.0).abs() < 1e-4);
+        assert!((bounds.min.x + 11.0).abs() < 1e-4);
+        assert!((bounds.max.y - 11.0).abs() < 1e-4);
+        // doesn't reach the top of the circle
+        assert!(bounds.min.y > -3.0 && bounds.min.y < 0.0);
+    }
+
+    #[test]
+    fn test_reversed_angles_give_the_same_bounds() {
+        let forwards = Sector {
+            center: Vec2::ONE,
+            radius: 5.0,
+            start_angle: 0.3,
+            end_angle: 2.0,
+            color: Color::WHITE,
+        };
+        let backwards = Sector {
+            start_angle: 2.0,
+            end_angle: 0.3,
+            ..forwards
+        };
+
+        assert_close(forwards.bounds().min, backwards.bounds().min);
+        assert_close(forwards.bounds().max, backwards.bounds().max);
+    }
+
+    #[test]
+    fn test_ring_and_arc_meshes() {
+        let ring = Ring {
+            center: Vec2::ZERO,
+            radius: 10.0,
+            thickness: 2.0,
+            color: Color::WHITE,
+        };
+        let (indices, vertices) = ring.points(0);
+        assert_eq!(indices.len(), vertices.len() * 3);
+
+        let arc = Arc {
+            center: Vec2::ZERO,
+            radius: 10.0,
+            start_angle: 0.0,
+            end_angle: TAU * 3.0,
+            thickness: 2.0,
+            color: Color::WHITE,
+        };
+        let (indices, vertices) = arc.points(0);
+        // open, so one less segment than there are point pairs
+        assert_eq!(indices.len(), (vertices.len() / 2 - 1) * 6);
+        // more than a full turn is clamped to one
+        assert_eq!(vertices.len(), ring.points(0).1.len() + 2);
+    }
+}
+
+#[cfg(test)]
+mod polyline_tests {
+    use crate::collisions::HasBounds2D;
+    use crate::color::Color;
+    use crate::shapes_2d::*;
+    use bevy_math::Vec2;
+
+    #[test]
+    fn test_polyline_needs_two_points() {
+        let line = Polyline::new(vec![Vec2::ONE], 2.0, Color::WHITE);
+        assert!(line.points(0).0.is_empty());
+
+        let line = Polyline::new(vec![Vec2::ZERO, Vec2::new(10.0, 0.0)], 2.0, Color::WHITE);
+        let (indices, vertices) = line.points(3);
+        assert!(!indices.is_empty());
+        assert!(
+            indices
+                .iter()
+                .all(|&i| i >= 3 && ((i - 3) as usize) < vertices.len())
+        );
+        for vertex in &vertices {
+            assert!(vertex.position[1].abs() <= 1.0 + 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_round_joins_add_geometry() {
+        let points = vec![Vec2::ZERO, Vec2::new(10.0, 0.0), Vec2::new(10.0, 10.0)];
+        let bevel = Polyline::new(points.clone(), 4.0, Color::WHITE).with_join(LineJoin::Bevel);
+        let round = Polyline::new(points, 4.0, Color::WHITE).with_join(LineJoin::Round);
+
+        assert!(round.points(0).1.len() > bevel.points(0).1.len());
+    }
+
+    #[test]
+    fn test_miter_bounds_leave_room_for_the_corner() {
+        let line = Polyline::new(vec![Vec2::ZERO, Vec2::new(10.0, 0.0)], 2.0, Color::WHITE);
+        let bounds = line.bounds();
+        let (_, vertices) = line.points(0);
+
+        for vertex in vertices {
+            let point = Vec2::from(vertex.position);
+            assert!(point.cmpge(bounds.min).all() && point.cmple(bounds.max).all());
+        }
+    }
+
+    #[test]
+    fn test_bezier_ends_at_its_end_points() {
+        let curve = CubicBezier::new(
+            Vec2::ZERO,
+            Vec2::new(0.0, 10.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(10.0, 0.0),
+        );
+
+        assert_eq!(curve.point_at(0.0), curve.start);
+        assert_eq!(curve.point_at(1.0), curve.end);
+        assert_eq!(curve.point_at(0.5), Vec2::new(5.0, 7.5));
+    }
+
+    #[test]
+    fn test_bezier_subdivides_more_where_it_bends() {
+        let straight = Bezier {
+            curve: CubicBezier::new(
+                Vec2::ZERO,
+                Vec2::new(30.0, 0.0),
+                Vec2::new(60.0, 0.0),
+                Vec2::new(100.0, 0.0),
+            ),
+            thickness: 2.0,
+            color: Color::WHITE,
+            cap: LineCap::Butt,
+        };
+        let curved = Bezier {
+            curve: CubicBezier::new(
+                Vec2::ZERO,
+                Vec2::new(0.0, 100.0),
+                Vec2::new(100.0, 100.0),
+                Vec2::new(100.0, 0.0),
+            ),
+            ..straight
+        };
+
+        assert!(curved.points(0).1.len() > straight.points(0).1.len() * 2);
+    }
+}
+
+#[cfg(test)]
+mod textured_shape_tests {
+    use crate::color::Color;
+    use crate::draw_queue_2d::DrawQueue2D;
+    use crate::shapes_2d::{Shape2D, TexturedShape, bounding_box_uvs};
+    use crate::testing::{DrawCall2D, MockDrawTarget2D};
+    use crate::textures::TextureRef;
+    use bevy_math::{Mat4, Vec2};
+
+    fn l_shape() -> Vec<Vec2> {
+        vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(10.0, 0.0),
+            Vec2::new(10.0, 5.0),
+            Vec2::new(5.0, 5.0),
+            Vec2::new(5.0, 20.0),
+            Vec2::new(0.0, 20.0),
+        ]
+    }
+
+    #[test]
+    fn test_bounding_box_uvs() {
+        let uvs = bounding_box_uvs(&l_shape());
+
+        assert_eq!(uvs[0], Vec2::ZERO);
+        assert_eq!(uvs[2], Vec2::new(1.0, 0.25));
+        assert_eq!(uvs[4], Vec2::new(0.5, 1.0));
+    }
+
+    #[test]
+    fn test_mesh_keeps_each_points_uv() {
+        let shape = TexturedShape::projected(TextureRef::from_raw(0, 0), l_shape());
+        let (vertices, indices) = shape.gen_mesh();
+
+        assert_eq!(indices.len(), 4 * 3);
+        for (position, uv) in vertices {
+            let index = shape.points.iter().position(|p| *p == position).unwrap();
+            assert_eq!(uv, shape.uvs[index]);
+        }
+    }
+
+    #[test]
+    fn test_textured_shapes_are_batched_with_sprites() {
+        let mut queue = DrawQueue2D::empty();
+        TexturedShape::projected(TextureRef::from_raw(3, 0), l_shape())
+            .with_color(Color::RED_500)
+            .add_to_draw_queue(&mut queue);
+
+        let mut target = MockDrawTarget2D::default();
+        queue.draw_to(&mut target, &Mat4::IDENTITY);
+
+        let [
+            DrawCall2D::Sprites {
+                texture,
+                vertices,
+                indices,
+            },
+        ] = target.calls.as_slice()
+        else {
+            panic!("expected one sprite batch");
+        };
+        assert_eq!(*texture, TextureRef::from_raw(3, 0));
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices.len(), 12);
+        assert_eq!(vertices[0].color, Color::RED_500.for_gpu());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_uvs_must_match_points() {
+        TexturedShape::new(TextureRef::from_raw(0, 0), l_shape(), vec![Vec2::ZERO]);
+    }
+}
+
+#[cfg(all(test, feature = "svg"))]
+mod svg_tests {
+    use crate::collisions::HasBounds2D;
+    use crate::shapes_2d::Shape2D;
+    use crate::svg::Svg;
+    use bevy_math::Vec2;
+    use std::str::FromStr;
+
+    const SQUARE: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10">
+        <rect x="0" y="0" width="10" height="10" fill="#ff0000"/>
+    </svg>"##;
+
+    #[test]
+    fn test_fill_is_triangulated() {
+        let svg = Svg::from_str(SQUARE).unwrap();
+        assert_eq!(svg.size(), Vec2::new(20.0, 10.0));
+        assert_eq!(svg.triangle_count(), 2);
+
+        let (_, vertices) = svg.points(0);
+        for vertex in &vertices {
+            assert_eq!(vertex.color, [1.0, 0.0, 0.0, 1.0]);
+            assert!(vertex.position[0] <= 10.0 && vertex.position[1] <= 10.0);
+        }
+    }
+
+    #[test]
+    fn test_placement_and_scale() {
+        let svg = Svg::from_str(SQUARE)
+            .unwrap()
+            .at(Vec2::new(100.0, 50.0))
+            .with_scale(2.0);
+
+        let bounds = svg.bounds();
+        assert_eq!(bounds.min, Vec2::new(100.0, 50.0));
+        assert_eq!(bounds.max, Vec2::new(140.0, 70.0));
+
+        let (_, vertices) = svg.points(0);
+        let max = vertices
+            .iter()
+            .fold(Vec2::MIN, |max, v| max.max(Vec2::from(v.position)));
+        assert_eq!(max, Vec2::new(120.0, 70.0));
+    }
+
+    #[test]
+    fn test_strokes_and_group_opacity() {
+        let svg = Svg::from_str(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="10">
+                <g opacity="0.5">
+                    <path d="M 1 1 L 9 9" stroke="#00ff00" stroke-width="2" fill="none"/>
+                </g>
+            </svg>"##,
+        )
+        .unwrap();
+
+        let (indices, vertices) = svg.points(0);
+        assert!(!indices.is_empty());
+        assert!(vertices.iter().all(|v| v.color == [0.0, 1.0, 0.0, 0.5]));
+    }
+
+    #[test]
+    fn test_invalid_svg_is_an_error() {
+        assert!(Svg::from_str("not an svg").is_err());
+    }
+}
+
+#[cfg(test)]
+mod polygon_boolean_tests {
+    use crate::collisions::Polygon;
+    use crate::collisions::boolean::{BooleanOps, PolygonWithHoles};
+    use crate::color::Color;
+    use crate::shapes_2d::{Shape2D, ShapeWithHoles};
+    use bevy_math::Vec2;
+
+    fn square(min: Vec2, size: f32) -> Polygon {
+        Polygon {
+            vertices: vec![
+                min,
+                min + Vec2::new(size, 0.0),
+                min + Vec2::splat(size),
+                min + Vec2::new(0.0, size),
+            ],
+        }
+    }
+
+    fn total_area(polygons: &[PolygonWithHoles]) -> f32 {
+        polygons.iter().map(PolygonWithHoles::area).sum()
+    }
+
+    #[test]
+    fn test_union_and_intersection() {
+        let a = square(Vec2::ZERO, 10.0);
+        let b = square(Vec2::splat(5.0), 10.0);
+
+        let union = a.union(&b);
+        assert_eq!(union.len(), 1);
+        assert!((total_area(&union) - 175.0).abs() < 0.01);
+
+        let intersection = a.intersection(&b);
+        assert_eq!(intersection.len(), 1);
+        assert!((total_area(&intersection) - 25.0).abs() < 0.01);
+
+        let apart = a.intersection(&square(Vec2::splat(20.0), 1.0));
+        assert!(apart.is_empty());
+    }
+
+    #[test]
+    fn test_difference_makes_holes() {
+        let ground = square(Vec2::ZERO, 10.0);
+        let crater = square(Vec2::splat(4.0), 2.0);
+
+        let carved = ground.difference(&crater);
+        assert_eq!(carved.len(), 1);
+        assert_eq!(carved[0].holes.len(), 1);
+        assert!((carved[0].area() - 96.0).abs() < 0.01);
+        assert!(carved[0].contains_point(Vec2::splat(1.0)));
+        assert!(!carved[0].contains_point(Vec2::splat(5.0)));
+
+        // carving the result again keeps the first hole
+        let carved = carved.difference(&square(Vec2::new(0.0, 0.0), 2.0));
+        assert!((total_area(&carved) - 92.0).abs() < 0.01);
+        assert!(!carved[0].contains_point(Vec2::splat(5.0)));
+    }
+
+    #[test]
+    fn test_difference_can_split() {
+        let bar = Polygon {
+            vertices: vec![
+                Vec2::ZERO,
+                Vec2::new(30.0, 0.0),
+                Vec2::new(30.0, 10.0),
+                Vec2::new(0.0, 10.0),
+            ],
+        };
+        let cut = Polygon {
+            vertices: vec![
+                Vec2::new(10.0, -1.0),
+                Vec2::new(20.0, -1.0),
+                Vec2::new(20.0, 11.0),
+                Vec2::new(10.0, 11.0),
+            ],
+        };
+
+        assert_eq!(bar.difference(&cut).len(), 2);
+    }
+
+    #[test]
+    fn test_shape_with_holes_triangulation() {
+        let carved = square(Vec2::ZERO, 10.0).difference(&square(Vec2::splat(4.0), 2.0));
+        let shape = ShapeWithHoles::from_polygon(&carved[0], Color::WHITE);
+
+        let (indices, vertices) = shape.points(0);
+        assert_eq!(indices.len() % 3, 0);
+
+        let area: f32 = indices
+            .chunks(3)
+            .map(|tri| {
+                let [a, b, c] = [0, 1, 2].map(|i| Vec2::from(vertices[tri[i] as usize].position));
+                (b - a).perp_dot(c - a).abs() * 0.5
+            })
+            .sum();
+        assert!((area - 96.0).abs() < 0.01);
+
+        let centre_covered = indices.chunks(3).any(|tri| {
+            let [a, b, c] = [0, 1, 2].map(|i| Vec2::from(vertices[tri[i] as usize].position));
+            Polygon {
+                vertices: vec![a, b, c],
+            }
+            .contains_point(Vec2::splat(5.0))
+        });
+        assert!(!centre_covered);
+    }
+}
+
+#[cfg(test)]
+mod contact_tests {
+    use crate::collisions::contact::CollideWith;
+    use crate::collisions::{Circle, Point, Polygon, Square};
+    use bevy_math::Vec2;
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        a.distance(b) < 1e-4
+    }
+
+    #[test]
+    fn test_circle_circle() {
+        let a = Circle {
+            center: Vec2::ZERO,
+            radius: 2.0,
+        };
+        let b = Circle {
+            center: Vec2::new(3.0, 0.0),
+            radius: 2.0,
+        };
+
+        let collision = a.collide_with(&b).unwrap();
+        assert!(close(collision.normal, Vec2::X));
+        assert!((collision.depth - 1.0).abs() < 1e-5);
+        assert!(close(collision.contact_points[0], Vec2::new(1.5, 0.0)));
+
+        let far = Circle {
+            center: Vec2::new(5.0, 0.0),
+            radius: 2.0,
+        };
+        assert!(a.collide_with(&far).is_none());
+    }
+
+    #[test]
+    fn test_squares_have_two_contacts() {
+        let a = Square {
+            center: Vec2::ZERO,
+            half_size: 1.0,
+        };
+        let b = Square {
+            center: Vec2::new(1.5, 0.5),
+            half_size: 1.0,
+        };
+
+        let collision = a.collide_with(&b).unwrap();
+        assert!(close(collision.normal, Vec2::X));
+        assert!((collision.depth - 0.5).abs() < 1e-5);
+        assert_eq!(collision.contact_points.len(), 2);
+        for point in &collision.contact_points {
+            assert!(point.y >= -0.5 - 1e-4 && point.y <= 1.0 + 1e-4);
+            assert!(point.x >= 0.5 - 1e-4 && point.x <= 1.0 + 1e-4);
+        }
+
+        // pushing b out along the normal separates them
+        let moved = Square {
+            center: b.center + collision.normal * (collision.depth + 0.01),
+            ..b
+        };
+        assert!(a.collide_with(&moved).is_none());
+    }
+
+    #[test]
+    fn test_flipped_pairs_agree() {
+        let circle = Circle {
+            center: Vec2::new(0.0, 1.5),
+            radius: 1.0,
+        };
+        let square = Square {
+            center: Vec2::ZERO,
+            half_size: 1.0,
+        };
+
+        let from_circle = circle.collide_with(&square).unwrap();
+        let from_square = square.collide_with(&circle).unwrap();
+        assert!(close(from_circle.normal, -Vec2::Y));
+        assert!(close(from_square.normal, Vec2::Y));
+        assert!((from_circle.depth - 0.5).abs() < 1e-5);
+        assert!(close(from_circle.contact_points[0], Vec2::new(0.0, 1.0)));
+    }
+
+    #[test]
+    fn test_circle_inside_polygon() {
+        let polygon = Polygon {
+            vertices: vec![
+                Vec2::ZERO,
+                Vec2::new(10.0, 0.0),
+                Vec2::new(10.0, 10.0),
+                Vec2::new(0.0, 10.0),
+            ],
+        };
+        let circle = Circle {
+            center: Vec2::new(9.0, 5.0),
+            radius: 0.5,
+        };
+
+        let collision = circle.collide_with(&polygon).unwrap();
+        // the circle gets out through the nearest edge
+        assert!(close(-collision.normal, Vec2::X));
+        assert!((collision.depth - 1.5).abs() < 1e-5);
+
+        let point = Point::new(Vec2::new(1.0, 5.0));
+        let collision = polygon.collide_with(&point).unwrap();
+        assert!(close(collision.normal, -Vec2::X));
+        assert!((collision.depth - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_triangle_corner_into_square() {
+        let square = Square {
+            center: Vec2::ZERO,
+            half_size: 1.0,
+        };
+        let triangle = Polygon {
+            vertices: vec![
+                Vec2::new(0.0, 0.8),
+                Vec2::new(1.0, 3.0),
+                Vec2::new(-1.0, 3.0),
+            ],
+        };
+
+        let collision = square.collide_with(&triangle).unwrap();
+        assert!(close(collision.normal, Vec2::Y));
+        assert!((collision.depth - 0.2).abs() < 1e-4);
+        assert_eq!(collision.contact_points.len(), 1);
+        assert!(close(collision.contact_points[0], Vec2::new(0.0, 0.8)));
+    }
+}
+
+#[cfg(test)]
+mod sweep_tests {
+    use crate::collisions::sweep::Sweep;
+    use crate::collisions::{AABB2D, Capsule, Circle, Polygon, Square};
+    use bevy_math::Vec2;
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        a.distance(b) < 1e-3
+    }
+
+    fn thin_wall() -> AABB2D {
+        AABB2D::new(Vec2::new(10.0, -5.0), Vec2::new(10.1, 5.0))
+    }
+
+    #[test]
+    fn test_fast_circle_does_not_tunnel() {
+        let bullet = Circle {
+            center: Vec2::ZERO,
+            radius: 0.5,
+        };
+
+        // would end up far past the wall in one step
+        let hit = bullet
+            .sweep(&thin_wall(), Vec2::new(1000.0, 0.0), 1.0 / 30.0)
+            .unwrap();
+        assert!((hit.time - 9.5 / 1000.0).abs() < 1e-5);
+        assert!(close(hit.normal, -Vec2::X));
+        assert!(close(hit.point, Vec2::new(10.0, 0.0)));
+
+        let too_slow = bullet.sweep(&thin_wall(), Vec2::new(100.0, 0.0), 0.05);
+        assert!(too_slow.is_none());
+
+        let missing = bullet.sweep(&thin_wall(), Vec2::new(0.0, 1000.0), 1.0);
+        assert!(missing.is_none());
+    }
+
+    #[test]
+    fn test_circle_against_circle_and_corner() {
+        let moving = Circle {
+            center: Vec2::ZERO,
+            radius: 1.0,
+        };
+        let target = Circle {
+            center: Vec2::new(5.0, 0.0),
+            radius: 1.0,
+        };
+        let hit = moving.sweep(&target, Vec2::new(6.0, 0.0), 1.0).unwrap();
+        assert!((hit.time - 0.5).abs() < 1e-5);
+        assert!(close(hit.point, Vec2::new(4.0, 0.0)));
+
+        // clips the corner of the square rather than a side
+        let square = Square {
+            center: Vec2::new(5.0, 2.0),
+            half_size: 1.0,
+        };
+        let hit = moving.sweep(&square, Vec2::new(10.0, 0.0), 1.0).unwrap();
+        assert!(close(hit.point, Vec2::new(4.0, 1.0)));
+        assert!(close(
+            hit.normal,
+            (Vec2::new(hit.time * 10.0, 0.0) - hit.point).normalize()
+        ));
+    }
+
+    #[test]
+    fn test_falling_box_lands_on_polygon() {
+        let crate_box = AABB2D::new(Vec2::new(-1.0, 0.0), Vec2::new(1.0, 2.0));
+        let ramp = Polygon {
+            vertices: vec![
+                Vec2::new(-5.0, 10.0),
+                Vec2::new(5.0, 10.0),
+                Vec2::new(5.0, 12.0),
+                Vec2::new(-5.0, 12.0),
+            ],
+        };
+
+        let hit = crate_box.sweep(&ramp, Vec2::new(0.0, 80.0), 1.0).unwrap();
+        assert!((hit.time - 0.1).abs() < 1e-5);
+        assert!(close(hit.normal, -Vec2::Y));
+    }
+
+    #[test]
+    fn test_capsule_side_hits_point_like_corner() {
+        let body = Capsule {
+            start: Vec2::new(0.0, 0.0),
+            end: Vec2::new(0.0, 4.0),
+            radius: 0.5,
+        };
+        let spike = Polygon {
+            vertices: vec![
+                Vec2::new(3.0, 2.0),
+                Vec2::new(5.0, 1.0),
+                Vec2::new(5.0, 3.0),
+            ],
+        };
+
+        let hit = body.sweep(&spike, Vec2::new(5.0, 0.0), 1.0).unwrap();
+        assert!((hit.time - 0.5).abs() < 1e-5);
+        assert!(close(hit.point, Vec2::new(3.0, 2.0)));
+        assert!(close(hit.normal, -Vec2::X));
+    }
+
+    #[test]
+    fn test_overlapping_shapes() {
+        let circle = Circle {
+            center: Vec2::new(10.0, 0.0),
+            radius: 0.5,
+        };
+
+        // moving further in is stopped right away
+        let hit = circle
+            .sweep(&thin_wall(), Vec2::new(1.0, 0.0), 1.0)
+            .unwrap();
+        assert_eq!(hit.time, 0.0);
+
+        let resting = Circle {
+            center: Vec2::new(9.5, 0.0),
+            radius: 0.5,
+        };
+        assert!(
+            resting
+                .sweep(&thin_wall(), Vec2::new(0.0, 1.0), 1.0)
+                .is_none()
+        );
+        assert!(
+            resting
+                .sweep(&thin_wall(), Vec2::new(-1.0, 0.0), 1.0)
+                .is_none()
+        );
+    }
+}
+
+#[cfg(test)]
+mod spatial_grid_tests {
+    use crate::collisions::AABB2D;
+    use crate::collisions::ray::Ray;
+    use crate::collisions::spatial_grid::SpatialGrid;
+    use bevy_math::Vec2;
+
+    fn boxed(x: f32, y: f32, size: f32) -> AABB2D {
+        AABB2D::new(Vec2::new(x, y), Vec2::new(x + size, y + size))
+    }
+
+    fn sorted(mut keys: Vec<usize>) -> Vec<usize> {
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_insert_query_remove() {
+        let mut grid = SpatialGrid::new(10.0);
+        grid.insert(0, boxed(1.0, 1.0, 2.0));
+        grid.insert(1, boxed(15.0, 1.0, 2.0));
+        // spans four cells
+        grid.insert(2, boxed(8.0, 8.0, 5.0));
+
+        assert_eq!(grid.len(), 3);
+        assert_eq!(sorted(grid.query_aabb(&boxed(0.0, 0.0, 9.0))), vec![0, 2]);
+        assert_eq!(grid.query_point(Vec2::new(12.0, 12.0)), vec![2]);
+        assert!(grid.query_point(Vec2::new(5.0, 5.0)).is_empty());
+
+        assert!(grid.remove(2));
+        assert!(!grid.remove(2));
+        assert_eq!(grid.query_aabb(&boxed(0.0, 0.0, 9.0)), vec![0]);
+        assert!(grid.query_point(Vec2::new(12.0, 12.0)).is_empty());
+    }
+
+    #[test]
+    fn test_update_moves_between_cells() {
+        let mut grid = SpatialGrid::new(10.0);
+        grid.insert("player", boxed(1.0, 1.0, 1.0));
+
+        grid.update("player", boxed(2.0, 2.0, 1.0));
+        assert_eq!(grid.query_point(Vec2::new(2.5, 2.5)), vec!["player"]);
+
+        grid.update("player", boxed(52.0, -31.0, 1.0));
+        assert!(grid.query_point(Vec2::new(2.5, 2.5)).is_empty());
+        assert_eq!(grid.query_point(Vec2::new(52.5, -30.5)), vec!["player"]);
+        assert_eq!(grid.len(), 1);
+    }
+
+    #[test]
+    fn test_pairs_are_reported_once() {
+        let mut grid = SpatialGrid::new(4.0);
+        // both cover lots of the same cells
+        grid.insert(0, boxed(0.0, 0.0, 20.0));
+        grid.insert(1, boxed(5.0, 5.0, 20.0));
+        grid.insert(2, boxed(100.0, 100.0, 1.0));
+        grid.insert(3, boxed(24.0, 24.0, 1.0));
+
+        let mut pairs: Vec<(usize, usize)> = grid
+            .pairs()
+            .into_iter()
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 1), (1, 3)]);
+    }
+
+    #[test]
+    fn test_query_ray_in_order() {
+        let mut grid = SpatialGrid::new(10.0);
+        grid.insert(0, boxed(50.0, -1.0, 2.0));
+        grid.insert(1, boxed(20.0, -1.0, 2.0));
+        grid.insert(2, boxed(35.0, 10.0, 2.0));
+        grid.insert(3, boxed(-30.0, -1.0, 2.0));
+
+        let ray = Ray::new(Vec2::ZERO, Vec2::X);
+        assert_eq!(grid.query_ray(&ray, f32::INFINITY), vec![1, 0]);
+        assert_eq!(grid.query_ray(&ray, 30.0), vec![1]);
+
+        let from_far = Ray::new(Vec2::new(-10_000.0, 0.0), Vec2::X);
+        assert_eq!(grid.query_ray(&from_far, f32::INFINITY), vec![3, 1, 0]);
+
+        let diagonal = Ray::new(Vec2::ZERO, Vec2::new(3.6, 1.1));
+        assert_eq!(grid.query_ray(&diagonal, 100.0), vec![2]);
+    }
+
+    #[test]
+    fn test_huge_and_infinite_bounds_are_kept_out_of_cells() {
+        let mut grid = SpatialGrid::new(1.0);
+        let everywhere = AABB2D::new(Vec2::splat(f32::NEG_INFINITY), Vec2::splat(f32::INFINITY));
+        let huge = AABB2D::new(Vec2::splat(-1e30), Vec2::splat(1e30));
+        grid.insert(0, everywhere);
+        grid.insert(1, huge);
+        grid.insert(2, boxed(5.0, 5.0, 0.5));
+
+        assert_eq!(sorted(grid.query_point(Vec2::new(5.2, 5.2))), vec![0, 1, 2]);
+        assert_eq!(sorted(grid.query_point(Vec2::new(-3.0, 9.0))), vec![0, 1]);
+        assert_eq!(sorted(grid.query_aabb(&huge)), vec![0, 1, 2]);
+        assert_eq!(sorted(grid.query_aabb(&everywhere)), vec![0, 1, 2]);
+
+        let ray = Ray::new(Vec2::new(0.0, 5.2), Vec2::X);
+        assert_eq!(sorted(grid.query_ray(&ray, f32::INFINITY)), vec![0, 1, 2]);
+
+        let mut pairs: Vec<_> = grid
+            .pairs()
+            .into_iter()
+            .map(|(a, b)| (a.min(b), a.max(b)))
+            .collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 1), (0, 2), (1, 2)]);
+
+        grid.update(1, boxed(5.0, 5.0, 0.5));
+        assert_eq!(sorted(grid.query_point(Vec2::new(-3.0, 9.0))), vec![0]);
+        assert!(grid.remove(0));
+        assert_eq!(sorted(grid.query_point(Vec2::new(5.2, 5.2))), vec![1, 2]);
+        assert_eq!(grid.query_aabb(&everywhere).len(), 2);
+    }
+
+    #[test]
+    fn test_nan_bounds_dont_panic() {
+        let mut grid = SpatialGrid::new(1.0);
+        grid.insert(0, AABB2D::new(Vec2::NAN, Vec2::NAN));
+        grid.insert(1, boxed(0.0, 0.0, 1.0));
+
+        assert_eq!(grid.query_point(Vec2::new(0.5, 0.5)), vec![1]);
+        assert!(grid.remove(0));
+        assert_eq!(grid.len(), 1);
+    }
+}
+
+#[cfg(test)]
+mod world_query_tests {
+    use crate::collisions::ray::Ray;
+    use crate::physics::*;
+    use bevy_math::Vec2;
+
+    fn wall(world: &mut PhysicsWorld, x: f32, groups: Group) -> ColliderHandle {
+        world.insert_collider(
+            ColliderBuilder::cuboid(0.5, 5.0)
+                .translation(vector![x, 0.0])
+                .collision_groups(InteractionGroups::new(groups, Group::ALL))
+                .build(),
+        )
+    }
+
+    fn world_with_walls() -> (PhysicsWorld, [ColliderHandle; 3]) {
+        let mut world = PhysicsWorld::new().with_no_gravity();
+        let far = wall(&mut world, 20.0, Group::GROUP_1);
+        let near = wall(&mut world, 5.0, Group::GROUP_2);
+        let middle = wall(&mut world, 10.0, Group::GROUP_1);
+        // queries see the world as of the last step
+        world.step();
+        (world, [near, middle, far])
+    }
+
+    #[test]
+    fn test_raycast_all_sorted() {
+        let (world, [near, middle, far]) = world_with_walls();
+        let ray = Ray::new(Vec2::ZERO, Vec2::X);
+
+        let first = world.raycast(&ray, 100.0).unwrap();
+        assert_eq!(first.collider, near);
+        assert!((first.distance - 4.5).abs() < 1e-4);
+        assert!(first.point.distance(Vec2::new(4.5, 0.0)) < 1e-4);
+        assert!(first.normal.distance(-Vec2::X) < 1e-4);
+
+        let all: Vec<_> = world
+            .raycast_all(&ray, 100.0)
+            .iter()
+            .map(|hit| hit.collider)
+            .collect();
+        assert_eq!(all, vec![near, middle, far]);
+
+        let short: Vec<_> = world
+            .raycast_all(&ray, 12.0)
+            .iter()
+            .map(|hit| hit.collider)
+            .collect();
+        assert_eq!(short, vec![near, middle]);
+    }
+
+    #[test]
+    fn test_raycast_filtered() {
+        let (world, [_, middle, far]) = world_with_walls();
+        let ray = Ray::new(Vec2::ZERO, Vec2::X);
+
+        let hits: Vec<_> = world
+            .raycast_filtered(&ray, 100.0, Group::GROUP_1)
+            .iter()
+            .map(|hit| hit.collider)
+            .collect();
+        assert_eq!(hits, vec![middle, far]);
+        assert!(
+            world
+                .raycast_filtered(&ray, 100.0, Group::GROUP_3)
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_shapecast() {
+        let (world, [near, ..]) = world_with_walls();
+
+        let hit = world
+            .shapecast(&Ball::new(1.0), Vec2::ZERO, Vec2::X, 100.0)
+            .unwrap();
+        assert_eq!(hit.collider, near);
+        assert!((hit.distance - 3.5).abs() < 1e-3);
+        assert!(hit.point.distance(Vec2::new(4.5, 0.0)) < 1e-3);
+
+        assert!(
+            world
+                .shapecast(&Ball::new(1.0), Vec2::ZERO, Vec2::X, 2.0)
+                .is_none()
+        );
+        assert!(
+            world
+                .shapecast(&Ball::new(1.0), Vec2::ZERO, -Vec2::X, 100.0)
+                .is_none()
+        );
+    }
+}
+
+#[cfg(test)]
+mod sensor_tests {
+    use crate::physics::*;
+
+    #[test]
+    fn test_sensor_enter_and_exit() {
+        let mut world = PhysicsWorld::new().with_no_gravity();
+        let pickup = world.insert_sensor(
+            ColliderBuilder::ball(1.0)
+                .translation(vector![5.0, 0.0])
+                .build(),
+        );
+        let (player_body, player) = world.insert_rigid_body_with_collider(
+            RigidBodyBuilder::kinematic_position_based().build(),
+            ColliderBuilder::ball(0.5).build(),
+        );
+
+        world.step();
+        assert!(world.collision_events().is_empty());
+
+        let move_player = |world: &mut PhysicsWorld, x: f32| {
+            world
+                .get_rigid_body_mut(player_body)
+                .unwrap()
+                .set_next_kinematic_translation(vector![x, 0.0]);
+            // contacts are found at the start of a step, before bodies move
+            world.step();
+            world.step();
+        };
+
+        move_player(&mut world, 5.0);
+        assert_eq!(world.on_enter(pickup).collect::<Vec<_>>(), vec![player]);
+        assert_eq!(world.on_enter(player).collect::<Vec<_>>(), vec![pickup]);
+        assert_eq!(world.on_exit(pickup).count(), 0);
+
+        // still inside, so nothing new
+        move_player(&mut world, 5.2);
+        assert!(world.collision_events().is_empty());
+
+        move_player(&mut world, 20.0);
+        assert_eq!(world.on_exit(pickup).collect::<Vec<_>>(), vec![player]);
+        assert!(world.collision_events()[0].sensor());
+    }
+}
+
+#[cfg(test)]
+mod character_controller_tests {
+    use crate::physics::*;
+    use bevy_math::Vec2;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    fn world_with_ground() -> PhysicsWorld {
+        let mut world = PhysicsWorld::new();
+        // top at y = 0
+        world.insert_collider(
+            ColliderBuilder::cuboid(50.0, 1.0)
+                .translation(vector![0.0, -1.0])
+                .build(),
+        );
+        world
+    }
+
+    fn run(
+        world: &mut PhysicsWorld,
+        character: &mut CharacterController2D,
+        velocity: Vec2,
+        frames: usize,
+    ) {
+        for _ in 0..frames {
+            character.move_and_slide(world, velocity, DT);
+            world.step();
+        }
+    }
+
+    #[test]
+    fn test_falls_and_lands() {
+        let mut world = world_with_ground();
+        world.step();
+        let mut character = CharacterController2D::new(
+            &mut world,
+            Vec2::new(0.0, 3.0),
+            ColliderBuilder::cuboid(0.5, 0.5).build(),
+        );
+
+        run(&mut world, &mut character, Vec2::new(0.0, -10.0), 60);
+        assert!(character.is_grounded());
+        assert!((character.position(&world).y - 0.5).abs() < 0.05);
+        assert!(character.velocity().y.abs() < 0.1);
+    }
+
+    #[test]
+    fn test_walls_and_ceilings() {
+        let mut world = world_with_ground();
+        world.insert_collider(
+            ColliderBuilder::cuboid(0.5, 10.0)
+                .translation(vector![5.0, 10.0])
+                .build(),
+        );
+        world.insert_collider(
+            ColliderBuilder::cuboid(2.0, 0.5)
+                .translation(vector![-5.0, 3.0])
+                .build(),
+        );
+        world.step();
+
+        let mut character = CharacterController2D::new(
+            &mut world,
+            Vec2::new(0.0, 0.6),
+            ColliderBuilder::cuboid(0.5, 0.5).build(),
+        );
+        run(&mut world, &mut character, Vec2::new(10.0, -1.0), 60);
+        assert!(character.is_on_wall());
+        assert!(character.position(&world).x < 4.0 + 0.05);
+
+        let mut jumper = CharacterController2D::new(
+            &mut world,
+            Vec2::new(-5.0, 0.6),
+            ColliderBuilder::cuboid(0.5, 0.5).build(),
+        );
+        run(&mut world, &mut jumper, Vec2::new(0.0, 10.0), 30);
+        assert!(jumper.is_on_ceiling());
+        assert!(jumper.position(&world).y < 2.0 + 0.05);
+    }
+
+    #[test]
+    fn test_one_way_platform() {
+        let mut world = world_with_ground();
+        // top at y = 3
+        let platform = world.insert_collider(
+            ColliderBuilder::cuboid(3.0, 0.25)
+                .translation(vector![0.0, 2.75])
+                .build(),
+        );
+        world.step();
+
+        let mut character = CharacterController2D::new(
+            &mut world,
+            Vec2::new(0.0, 0.6),
+            ColliderBuilder::cuboid(0.5, 0.5).build(),
+        );
+        character.add_one_way_platform(platform);
+
+        // jumps up through it
+        run(&mut world, &mut character, Vec2::new(0.0, 10.0), 30);
+        assert!(character.position(&world).y > 4.0);
+        assert!(!character.is_on_ceiling());
+
+        // and lands on top
+        run(&mut world, &mut character, Vec2::new(0.0, -10.0), 60);
+        assert!(character.is_grounded());
+        assert!((character.position(&world).y - 3.5).abs() < 0.05);
+    }
+
+    fn world_with_step() -> PhysicsWorld {
+        let mut world = world_with_ground();
+        // a small step up to y = 0.3
+        world.insert_collider(
+            ColliderBuilder::cuboid(10.0, 0.15)
+                .translation(vector![12.0, 0.15])
+                .build(),
+        );
+        world.step();
+        world
+    }
+
+    #[test]
+    fn test_step_height() {
+        let mut world = world_with_step();
+        let mut blocked = CharacterController2D::new(
+            &mut world,
+            Vec2::new(0.0, 0.6),
+            ColliderBuilder::cuboid(0.5, 0.5).build(),
+        );
+        run(&mut world, &mut blocked, Vec2::new(5.0, -5.0), 60);
+        assert!(blocked.position(&world).x < 1.6);
+
+        let mut world = world_with_step();
+        let mut climber = CharacterController2D::new(
+            &mut world,
+            Vec2::new(0.0, 0.6),
+            ColliderBuilder::cuboid(0.5, 0.5).build(),
+        )
+        .with_step_height(0.4);
+        run(&mut world, &mut climber, Vec2::new(5.0, -5.0), 60);
+        assert!(climber.position(&world).x > 3.0);
+        assert!((climber.position(&world).y - 0.8).abs() < 0.05);
+    }
+}
+
+#[cfg(test)]
+mod video_mode_tests {
+    use bevy_math::UVec2;
+
+    use crate::window::{VideoMode, pick_video_mode};
+
+    fn mode(width: u32, height: u32, refresh_rate_hz: f32) -> VideoMode {
+        VideoMode {
+            size: UVec2::new(width, height),
+            bit_depth: 32,
+            refresh_rate_hz,
+        }
+    }
+
+    #[test]
+    fn modes_sort_largest_then_fastest_first() {
+        let mut modes = vec![
+            mode(1280, 720, 144.0),
+            mode(1920, 1080, 60.0),
+            mode(1920, 1080, 144.0),
+            mode(800, 600, 60.0),
+        ];
+        modes.sort_by(VideoMode::best_first);
+        assert_eq!(
+            modes,
+            [
+                mode(1920, 1080, 144.0),
+                mode(1920, 1080, 60.0),
+                mode(1280, 720, 144.0),
+                mode(800, 600, 60.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn picks_the_best_mode_or_the_one_asked_for() {
+        let modes = [mode(1920, 1080, 60.0), mode(1280, 720, 60.0)];
+        assert_eq!(pick_video_mode(&modes, None), Some(0));
+        assert_eq!(pick_video_mode(&modes, Some(modes[1])), Some(1));
+        assert_eq!(pick_video_mode(&modes, Some(mode(640, 480, 60.0))), None);
+        assert_eq!(pick_video_mode(&[], None), None);
+    }
+}
+
+#[cfg(test)]
+mod cursor_grab_tests {
+    use crate::window::{CursorGrabMode, grab_with_fallback};
+
+    /// A platform that can only do the grabs in `supported`, noting down what was tried.
+    fn platform(
+        supported: &[CursorGrabMode],
+        tried: &mut Vec<CursorGrabMode>,
+    ) -> impl FnMut(CursorGrabMode) -> Result<(), ()> {
+        move |mode| {
+            tried.push(mode);
+            if supported.contains(&mode) {
+                Ok(())
+            } else {
+                Err(())
+            }
+        }
+    }
+
+    #[test]
+    fn supported_grabs_dont_fall_back() {
+        let mut tried = Vec::new();
+        let all = [CursorGrabMode::Confined, CursorGrabMode::Locked];
+        let grabbed = grab_with_fallback(CursorGrabMode::Locked, platform(&all, &mut tried));
+        assert_eq!(grabbed, Ok(false));
+        assert_eq!(tried, [CursorGrabMode::Locked]);
+    }
+
+    #[test]
+    fn lock_falls_back_to_confined_and_recentering() {
+        let mut tried = Vec::new();
+        let confined = [CursorGrabMode::Confined];
+        let grabbed = grab_with_fallback(CursorGrabMode::Locked, platform(&confined, &mut tried));
+        assert_eq!(grabbed, Ok(true));
+        assert_eq!(tried, [CursorGrabMode::Locked, CursorGrabMode::Confined]);
+    }
+
+    #[test]
+    fn confined_falls_back_to_locked() {
+        let mut tried = Vec::new();
+        let locked = [CursorGrabMode::Locked];
+        let grabbed = grab_with_fallback(CursorGrabMode::Confined, platform(&locked, &mut tried));
+        assert_eq!(grabbed, Ok(false));
+    }
+
+    #[test]
+    fn nothing_supported_is_an_error() {
+        let mut tried = Vec::new();
+        let grabbed = grab_with_fallback(CursorGrabMode::Locked, platform(&[], &mut tried));
+        assert_eq!(grabbed, Err(()));
+    }
+}
+
+#[cfg(test)]
+mod egui_texture_tests {
+    use egui_glium::egui_winit::egui::TextureOptions;
+    use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
+
+    use crate::egui_textures::filter_options;
+
+    #[test]
+    fn pixel_art_stays_crisp_in_egui() {
+        assert_eq!(
+            filter_options(MagnifySamplerFilter::Nearest, MinifySamplerFilter::Nearest),
+            TextureOptions::NEAREST
+        );
+        assert_eq!(
+            filter_options(
+                MagnifySamplerFilter::Nearest,
+                MinifySamplerFilter::NearestMipmapNearest
+            ),
+            TextureOptions::NEAREST
+        );
+    }
+
+    #[test]
+    fn anything_smooth_is_linear() {
+        assert_eq!(
+            filter_options(
+                MagnifySamplerFilter::Nearest,
+                MinifySamplerFilter::LinearMipmapLinear
+            ),
+            TextureOptions::LINEAR
+        );
+        assert_eq!(
+            filter_options(MagnifySamplerFilter::Linear, MinifySamplerFilter::Nearest),
+            TextureOptions::LINEAR
+        );
+    }
+}
+
+#[cfg(test)]
+mod frame_limiter_tests {
+    use std::time::Duration;
+
+    use crate::frame_rate_wait;
+
+    #[test]
+    fn waits_out_the_rest_of_the_frame() {
+        let wait = frame_rate_wait(Some(50.0), Duration::from_millis(5));
+        assert_eq!(wait, Duration::from_millis(15));
+    }
+
+    #[test]
+    fn slow_frames_dont_wait() {
+        assert_eq!(
+            frame_rate_wait(Some(60.0), Duration::from_millis(40)),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn no_target_is_no_limit() {
+        assert_eq!(frame_rate_wait(None, Duration::ZERO), Duration::ZERO);
+        assert_eq!(frame_rate_wait(Some(0.0), Duration::ZERO), Duration::ZERO);
+        assert_eq!(frame_rate_wait(Some(-30.0), Duration::ZERO), Duration::ZERO);
+    }
+}
+
+#[cfg(test)]
+mod time_scale_tests {
+    use crate::physics::*;
+
+    fn falling_ball() -> (PhysicsWorld, RigidBodyHandle) {
+        let mut world = PhysicsWorld::new();
+        let (ball, _) = world.insert_rigid_body_with_collider(
+            RigidBodyBuilder::dynamic().build(),
+            ColliderBuilder::ball(0.5).build(),
+        );
+        (world, ball)
+    }
+
+    fn fallen(time_scale: f32) -> f32 {
+        let (mut world, ball) = falling_ball();
+        for _ in 0..10 {
+            world.step_scaled(time_scale);
+        }
+        -world.get_rigid_body(ball).unwrap().translation().y
+    }
+
+    #[test]
+    fn frozen_time_doesnt_step() {
+        assert_eq!(fallen(0.0), 0.0);
+    }
+
+    #[test]
+    fn slow_motion_falls_less() {
+        let full = fallen(1.0);
+        let half = fallen(0.5);
+        assert!(full > 0.0);
+        // distance fallen goes with the square of the time
+        assert!((half / full - 0.25).abs() < 0.05);
+    }
+}
+
+#[cfg(test)]
+mod joint_tests {
+    use crate::physics::*;
+    use bevy_math::Vec2;
+
+    fn body_position(world: &PhysicsWorld, handle: RigidBodyHandle) -> Vec2 {
+        let translation = world.get_rigid_body(handle).unwrap().translation();
+        Vec2::new(translation.x, translation.y)
+    }
+
+    fn anchor_and_ball(world: &mut PhysicsWorld, at: Vec2) -> (RigidBodyHandle, RigidBodyHandle) {
+        let anchor = world.insert_rigid_body(RigidBodyBuilder::fixed().build());
+        let (ball, _) = world.insert_rigid_body_with_collider(
+            RigidBodyBuilder::dynamic().translation(at.into()).build(),
+            ColliderBuilder::ball(0.25).build(),
+        );
+        (anchor, ball)
+    }
+
+    #[test]
+    fn test_revolute_pendulum_keeps_length() {
+        let mut world = PhysicsWorld::new();
+        let (anchor, ball) = anchor_and_ball(&mut world, Vec2::new(2.0, 0.0));
+        world.add_revolute_joint(anchor, ball, Vec2::ZERO, Vec2::new(-2.0, 0.0));
+
+        for _ in 0..120 {
+            world.step();
+        }
+
+        let position = body_position(&world, ball);
+        assert!((position.length() - 2.0).abs() < 0.05);
+        // it swung down
+        assert!(position.y < -0.5);
+    }
+
+    #[test]
+    fn test_distance_and_rope_joints() {
+        let mut world = PhysicsWorld::new();
+        let (anchor, rod) = anchor_and_ball(&mut world, Vec2::new(3.0, 0.0));
+        world.add_distance_joint(anchor, rod, Vec2::ZERO, Vec2::ZERO, 3.0);
+        let (anchor, rope) = anchor_and_ball(&mut world, Vec2::new(10.0, 1.0));
+        world.add_rope_joint(anchor, rope, Vec2::new(10.0, 0.0), Vec2::ZERO, 3.0);
+
+        // a rope can go slack, so this one falls freely at first
+        world.step();
+        let after_one_step = body_position(&world, rope);
+        assert!(after_one_step.y < 1.0);
+
+        for _ in 0..120 {
+            world.step();
+        }
+
+        assert!((body_position(&world, rod).length() - 3.0).abs() < 0.05);
+        let hanging = body_position(&world, rope) - Vec2::new(10.0, 0.0);
+        assert!((hanging.length() - 3.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_prismatic_joint_slides_along_axis() {
+        let mut world = PhysicsWorld::new();
+        let (anchor, slider) = anchor_and_ball(&mut world, Vec2::ZERO);
+        world.add_prismatic_joint(
+            anchor,
+            slider,
+            Vec2::ZERO,
+            Vec2::ZERO,
+            Vec2::new(1.0, -1.0),
+            Some([-2.0, 2.0]),
+        );
+
+        for _ in 0..120 {
+            world.step();
+        }
+
+        let position = body_position(&world, slider);
+        // slid down the diagonal until the limit
+        assert!((position.x + position.y).abs() < 0.05);
+        assert!((position.length() - 2.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_spring_settles_at_rest_length() {
+        let mut world = PhysicsWorld::new().with_no_gravity();
+        let (anchor, weight) = anchor_and_ball(&mut world, Vec2::new(5.0, 0.0));
+        let joint = world.add_spring_joint(anchor, weight, Vec2::ZERO, Vec2::ZERO, 2.0, 20.0, 2.0);
+
+        for _ in 0..600 {
+            world.step();
+        }
+        assert!((body_position(&world, weight).x - 2.0).abs() < 0.05);
+
+        assert!(world.remove_joint(joint).is_some());
+        assert!(world.remove_joint(joint).is_none());
+    }
+}
+
+#[cfg(test)]
+mod verlet_tests {
+    use crate::collisions::Polygon;
+    use crate::physics::{ColliderBuilder, PhysicsWorld, Rope, SoftBody2D};
+    use bevy_math::Vec2;
+
+    #[test]
+    fn test_rope_hangs_straight_down_from_pin() {
+        let mut rope = Rope::new(Vec2::ZERO, Vec2::new(100.0, 0.0), 10);
+        rope.body.damping = 0.9;
+        assert_eq!(rope.length(), 100.0);
+
+        for _ in 0..600 {
+            rope.step(1.0 / 60.0);
+        }
+
+        assert_eq!(rope.start(), Vec2::ZERO);
+        let end = rope.end();
+        assert!(end.x.abs() < 1.0);
+        assert!((end.y + 100.0).abs() < 2.0);
+    }
+
+    #[test]
+    fn test_rope_pinned_at_both_ends_keeps_its_ends() {
+        let mut rope = Rope::new(Vec2::ZERO, Vec2::new(50.0, 0.0), 5).pinned_at_both_ends();
+        for _ in 0..60 {
+            rope.step(1.0 / 60.0);
+        }
+
+        assert_eq!(rope.end(), Vec2::new(50.0, 0.0));
+        // sags in the middle
+        assert!(rope.body.points[2].position.y < 0.0);
+    }
+
+    #[test]
+    fn test_cloth_constraints() {
+        let cloth = SoftBody2D::cloth(Vec2::ZERO, 4, 3, 10.0);
+        assert_eq!(cloth.points.len(), 12);
+        // 3 across per row, 4 down per row after the first
+        assert_eq!(cloth.constraints.len(), 3 * 3 + 4 * 2);
+        assert_eq!(cloth.points.iter().filter(|point| point.pinned).count(), 4);
+    }
+
+    #[test]
+    fn test_soft_body_rests_on_polygon() {
+        let ground = Polygon {
+            vertices: vec![
+                Vec2::new(-100.0, -50.0),
+                Vec2::new(100.0, -50.0),
+                Vec2::new(100.0, -20.0),
+                Vec2::new(-100.0, -20.0),
+            ],
+        };
+        let mut rope = Rope::new(Vec2::new(-10.0, 0.0), Vec2::new(10.0, 0.0), 4);
+        rope.body.unpin(0);
+
+        for _ in 0..120 {
+            rope.step(1.0 / 60.0);
+            rope.collide(&ground);
+        }
+
+        for point in &rope.body.points {
+            assert!((point.position.y - (-20.0 + rope.body.radius)).abs() < 0.5);
+        }
+    }
+
+    #[test]
+    fn test_soft_body_rests_on_world_colliders() {
+        let mut world = PhysicsWorld::new();
+        world.insert_collider(
+            ColliderBuilder::cuboid(100.0, 10.0)
+                .translation(Vec2::new(0.0, -30.0).into())
+                .build(),
+        );
+        world.step();
+
+        let mut body = SoftBody2D::new();
+        body.add_point(Vec2::ZERO);
+        for _ in 0..120 {
+            body.step(1.0 / 60.0);
+            body.collide_with_world(&world);
+        }
+
+        assert!((body.points[0].position.y - (-20.0 + body.radius)).abs() < 0.5);
+    }
+}
+
+#[cfg(test)]
+mod collisions3d_tests {
+    use crate::collisions3d::*;
+    use bevy_math::{Mat4, Quat, Vec3};
+    use std::f32::consts::FRAC_PI_4;
+
+    fn unit_box() -> AABB3D {
+        AABB3D::new(Vec3::splat(-1.0), Vec3::splat(1.0))
+    }
+
+    #[test]
+    fn test_sphere_intersections() {
+        let sphere = Sphere::new(Vec3::new(2.0, 0.0, 0.0), 1.0);
+        assert!(sphere.intersects_with(&unit_box()));
+        assert!(!Sphere::new(Vec3::new(2.0, 2.0, 0.0), 1.0).intersects_with(&unit_box()));
+        assert!(sphere.intersects_with(&Sphere::new(Vec3::new(3.5, 0.0, 0.0), 0.5)));
+        assert!(
+            sphere.intersects_with(&Plane::from_point_normal(Vec3::new(2.5, 0.0, 0.0), Vec3::X))
+        );
+        assert!(!sphere.intersects_with(&Plane::new(Vec3::Y, 2.0)));
+    }
+
+    #[test]
+    fn test_obb_rotated_corner() {
+        // rotated 45 degrees, the corners reach out to sqrt(2)
+        let obb = OBB::new(Vec3::ZERO, Vec3::ONE, Quat::from_rotation_z(FRAC_PI_4));
+        let nearby = AABB3D::new(Vec3::new(1.2, -0.1, -0.1), Vec3::new(1.3, 0.1, 0.1));
+        assert!(obb.intersects_with(&nearby));
+        assert!(!unit_box().intersects_with(&nearby));
+
+        let far = OBB::new(Vec3::new(3.0, 0.0, 0.0), Vec3::ONE, Quat::IDENTITY);
+        assert!(!obb.intersects_with(&far));
+        assert!((obb.bounds().max.x - 2.0_f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_capsule_intersections() {
+        let capsule = Capsule3D::new(Vec3::new(-5.0, 2.0, 0.0), Vec3::new(5.0, 2.0, 0.0), 0.5);
+        assert!(!capsule.intersects_with(&unit_box()));
+        assert!(
+            Capsule3D {
+                radius: 1.0,
+                ..capsule
+            }
+            .intersects_with(&unit_box())
+        );
+
+        let crossing = Capsule3D::new(Vec3::new(0.0, 3.0, -5.0), Vec3::new(0.0, 3.0, 5.0), 0.6);
+        assert!(capsule.intersects_with(&crossing));
+        assert!(capsule.intersects_with(&Plane::new(Vec3::Y, 2.4)));
+        assert!(!capsule.intersects_with(&Plane::new(Vec3::Y, 3.0)));
+    }
+
+    #[test]
+    fn test_raycasts() {
+        let ray = Ray3D::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::X);
+
+        let hit = Sphere::new(Vec3::ZERO, 1.0).intersect_ray(&ray).unwrap();
+        assert!((hit.distance - 4.0).abs() < 1e-5);
+        assert!(hit.normal.abs_diff_eq(Vec3::NEG_X, 1e-5));
+
+        let hit = unit_box().intersect_ray(&ray).unwrap();
+        assert!((hit.distance - 4.0).abs() < 1e-5);
+        assert_eq!(hit.normal, Vec3::NEG_X);
+
+        let obb = OBB::new(Vec3::ZERO, Vec3::ONE, Quat::from_rotation_z(FRAC_PI_4));
+        let hit = obb.intersect_ray(&ray).unwrap();
+        assert!((hit.distance - (5.0 - 2.0_f32.sqrt())).abs() < 1e-4);
+
+        let capsule = Capsule3D::new(Vec3::new(0.0, -2.0, 0.0), Vec3::new(0.0, 2.0, 0.0), 1.0);
+        let hit = capsule.intersect_ray(&ray).unwrap();
+        assert!((hit.distance - 4.0).abs() < 1e-5);
+        let down = Ray3D::new(Vec3::new(0.0, 10.0, 0.0), Vec3::NEG_Y);
+        assert!((capsule.intersect_ray(&down).unwrap().distance - 7.0).abs() < 1e-5);
+
+        let hit = Plane::new(Vec3::X, 1.0).intersect_ray(&ray).unwrap();
+        assert!((hit.distance - 6.0).abs() < 1e-5);
+        assert_eq!(hit.normal, Vec3::NEG_X);
+
+        assert!(
+            Sphere::new(Vec3::new(0.0, 3.0, 0.0), 1.0)
+                .intersect_ray(&ray)
+                .is_none()
+        );
+        assert!(
+            Sphere::new(Vec3::ZERO, 1.0)
+                .intersect_ray_max(&ray, 3.0)
+                .is_none()
+        );
+        assert_eq!(
+            Sphere::new(ray.origin, 1.0)
+                .intersect_ray(&ray)
+                .unwrap()
+                .distance,
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_frustum_culling() {
+        let view = Mat4::look_at_rh(Vec3::ZERO, Vec3::NEG_Z, Vec3::Y);
+        let projection = Mat4::perspective_rh(90.0_f32.to_radians(), 1.0, 0.1, 100.0);
+        let frustum = Frustum::from_view_proj(projection * view);
+
+        assert!(frustum.contains_point(Vec3::new(0.0, 0.0, -10.0)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 10.0)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -200.0)));
+        assert!(!frustum.contains_point(Vec3::new(20.0, 0.0, -10.0)));
+
+        // just outside the right edge, but big enough to poke in
+        assert!(frustum.intersects_with(&Sphere::new(Vec3::new(11.0, 0.0, -10.0), 2.0)));
+        assert!(!frustum.intersects_with(&Sphere::new(Vec3::new(15.0, 0.0, -10.0), 2.0)));
+        assert!(frustum.intersects_with(&AABB3D::from_center_size(
+            Vec3::new(11.0, 0.0, -10.0),
+            Vec3::splat(4.0)
+        )));
+        assert!(!frustum.intersects_with(&AABB3D::from_center_size(
+            Vec3::new(0.0, 0.0, 10.0),
+            Vec3::splat(4.0)
+        )));
+    }
+}
+
+#[cfg(test)]
+mod ecs_tests {
+    use crate::ecs::{Entities, Entity};
+    use crate::transform::Transform2D;
+    use bevy_math::Vec2;
+
+    struct Velocity(Vec2);
+    struct Health(u32);
+
+    #[test]
+    fn test_query_updates_matching_entities() {
+        let mut entities = Entities::new();
+        let moving = entities.spawn();
+        entities.insert(moving, Transform2D::from_translation(Vec2::ZERO));
+        entities.insert(moving, Velocity(Vec2::new(1.0, 2.0)));
+        let still = entities.spawn();
+        entities.insert(still, Transform2D::from_translation(Vec2::ZERO));
+
+        for (transform, velocity) in entities.query::<(&mut Transform2D, &Velocity)>() {
+            transform.translate_by(velocity.0);
+        }
+
+        assert_eq!(
+            entities.get::<Transform2D>(moving).unwrap().translation(),
+            Vec2::new(1.0, 2.0)
+        );
+        assert_eq!(
+            entities.get::<Transform2D>(still).unwrap().translation(),
+            Vec2::ZERO
+        );
+        assert_eq!(entities.query::<&Transform2D>().count(), 2);
+        assert_eq!(
+            entities
+                .query::<(Entity, &Velocity)>()
+                .map(|(e, _)| e)
+                .collect::<Vec<_>>(),
+            vec![moving]
+        );
+    }
+
+    #[test]
+    fn test_despawned_ids_are_not_reused() {
+        let mut entities = Entities::new();
+        let first = entities.spawn();
+        entities.insert(first, Health(3));
+        assert!(entities.despawn(first));
+        assert!(!entities.despawn(first));
+
+        let second = entities.spawn();
+        assert_eq!(first.index(), second.index());
+        assert!(!entities.is_alive(first));
+        assert!(entities.get::<Health>(first).is_none());
+        assert!(!entities.has::<Health>(second));
+        assert_eq!(entities.len(), 1);
+    }
+
+    #[test]
+    fn test_despawn_later_waits_for_flush() {
+        let mut entities = Entities::new();
+        for i in 0..4 {
+            let entity = entities.spawn();
+            entities.insert(entity, Health(i));
+        }
+
+        let dead: Vec<Entity> = entities
+            .query::<(Entity, &Health)>()
+            .filter(|(_, health)| health.0 % 2 == 0)
+            .map(|(entity, _)| entity)
+            .collect();
+        for entity in dead {
+            entities.despawn_later(entity);
+        }
+        assert_eq!(entities.len(), 4);
+
+        entities.flush();
+        assert_eq!(entities.len(), 2);
+        let mut remaining: Vec<u32> = entities.query::<&Health>().map(|h| h.0).collect();
+        remaining.sort();
+        assert_eq!(remaining, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_remove_and_replace_components() {
+        let mut entities = Entities::new();
+        let entity = entities.spawn();
+        entities.insert(entity, Health(1));
+        entities.insert(entity, Health(5));
+        assert_eq!(entities.get::<Health>(entity).unwrap().0, 5);
+
+        entities.get_mut::<Health>(entity).unwrap().0 -= 1;
+        assert_eq!(entities.remove::<Health>(entity).unwrap().0, 4);
+        assert!(entities.remove::<Health>(entity).is_none());
+        assert!(entities.is_alive(entity));
+    }
+
+    #[test]
+    fn test_query_items_can_be_held_together() {
+        let mut entities = Entities::new();
+        for i in 0..3 {
+            let entity = entities.spawn();
+            entities.insert(entity, Health(i));
+            entities.insert(entity, Velocity(Vec2::ZERO));
+        }
+
+        // under Miri, this catches fetches that borrow the whole storage
+        let mut items: Vec<(&mut Health, &Velocity)> =
+            entities.query::<(&mut Health, &Velocity)>().collect();
+        let (first, rest) = items.split_first_mut().unwrap();
+        first.0.0 += 10;
+        rest[1].0.0 += 20;
+        assert_eq!(first.0.0 + rest[0].0.0 + rest[1].0.0, 33);
+
+        let healths: Vec<u32> = entities.query::<&Health>().map(|h| h.0).collect();
+        assert_eq!(healths, vec![10, 1, 22]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_query_rejects_aliasing() {
+        let mut entities = Entities::new();
+        let _ = entities.query::<(&mut Health, &Health)>();
+    }
+}
+
+#[cfg(test)]
+mod scene_graph_tests {
+    use crate::scene_graph::SceneGraph;
+    use crate::transform::{Transform2D, Transform3D};
+    use bevy_math::{Quat, Vec2, Vec3};
+    use std::f32::consts::FRAC_PI_2;
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        a.abs_diff_eq(b, 1e-4)
+    }
+
+    #[test]
+    fn test_child_follows_parent() {
+        let mut scene = SceneGraph::new();
+        let tank = scene.add(Transform2D::from_translation(Vec2::new(10.0, 0.0)));
+        let turret = scene.add_child(tank, Transform2D::from_translation(Vec2::new(1.0, 0.0)));
+
+        assert!(close(
+            scene.world(turret).unwrap().translation(),
+            Vec2::new(11.0, 0.0)
+        ));
+
+        scene.local_mut(tank).unwrap().set_rotation(FRAC_PI_2);
+        let world = scene.world(turret).unwrap();
+        assert!(close(world.translation(), Vec2::new(10.0, 1.0)));
+        assert!((world.rotation() - FRAC_PI_2).abs() < 1e-4);
+        assert_eq!(scene.parent(turret), Some(tank));
+        assert_eq!(scene.children(tank), &[turret]);
+    }
+
+    #[test]
+    fn test_attach_in_place_and_detach_keep_world_position() {
+        let mut scene = SceneGraph::new();
+        let mut hand = Transform2D::from_translation(Vec2::new(5.0, 5.0));
+        hand.set_scale(Vec2::splat(2.0));
+        let hand = scene.add(hand);
+        let sword = scene.add(Transform2D::from_translation(Vec2::new(7.0, 5.0)));
+
+        scene.attach_in_place(sword, hand);
+        assert!(close(
+            scene.world(sword).unwrap().translation(),
+            Vec2::new(7.0, 5.0)
+        ));
+        assert!(close(
+            scene.local(sword).unwrap().translation(),
+            Vec2::new(1.0, 0.0)
+        ));
+
+        scene
+            .local_mut(hand)
+            .unwrap()
+            .translate_by(Vec2::new(0.0, 10.0));
+        scene.update();
+        assert!(close(
+            scene.world(sword).unwrap().translation(),
+            Vec2::new(7.0, 15.0)
+        ));
+
+        scene.detach(sword);
+        assert_eq!(scene.parent(sword), None);
+        assert!(close(
+            scene.local(sword).unwrap().translation(),
+            Vec2::new(7.0, 15.0)
+        ));
+    }
+
+    #[test]
+    fn test_remove_takes_children_with_it() {
+        let mut scene = SceneGraph::new();
+        let root = scene.add(Transform3D::IDENTITY);
+        let child = scene.add_child(root, Transform3D::IDENTITY);
+        let grandchild = scene.add_child(child, Transform3D::IDENTITY);
+        let other = scene.add(Transform3D::IDENTITY);
+
+        assert!(scene.remove(child));
+        assert!(!scene.contains(child));
+        assert!(!scene.contains(grandchild));
+        assert!(scene.children(root).is_empty());
+        assert_eq!(scene.len(), 2);
+
+        // reused slots don't bring old ids back to life
+        let new = scene.add(Transform3D::IDENTITY);
+        assert!(!scene.contains(child) && !scene.contains(grandchild));
+        assert_eq!(scene.roots().count(), 3);
+        assert!(scene.contains(new) && scene.contains(other));
+    }
+
+    #[test]
+    fn test_3d_propagation() {
+        let mut scene = SceneGraph::new();
+        let mut arm = Transform3D::IDENTITY;
+        arm.set_rotation(Quat::from_rotation_y(FRAC_PI_2));
+        let arm = scene.add(arm);
+        let mut hand = Transform3D::IDENTITY;
+        hand.set_translation(Vec3::new(0.0, 0.0, -2.0));
+        let hand = scene.add_child(arm, hand);
+
+        let world = scene.world(hand).unwrap().translation();
+        assert!(world.abs_diff_eq(Vec3::new(-2.0, 0.0, 0.0), 1e-4));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_attach_rejects_loops() {
+        let mut scene = SceneGraph::new();
+        let parent = scene.add(Transform2D::IDENTITY);
+        let child = scene.add_child(parent, Transform2D::IDENTITY);
+        scene.attach(parent, child);
+    }
+}
+
+#[cfg(test)]
+mod scheduler_tests {
+    use crate::scheduler::{Scheduler, next_update, wait, wait_until};
+    use std::{cell::RefCell, rc::Rc};
+
+    fn log() -> Rc<RefCell<Vec<&'static str>>> {
+        Rc::new(RefCell::new(Vec::new()))
+    }
+
+    #[test]
+    fn test_after_runs_once_when_due() {
+        let mut scheduler = Scheduler::new();
+        let events = log();
+        let e = events.clone();
+        let handle = scheduler.after(1.0, move || e.borrow_mut().push("boom"));
+
+        scheduler.update(0.6);
+        assert!(events.borrow().is_empty());
+        assert!(scheduler.is_scheduled(handle));
+
+        scheduler.update(0.6);
+        scheduler.update(5.0);
+        assert_eq!(*events.borrow(), vec!["boom"]);
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_every_catches_up_and_cancels() {
+        let mut scheduler = Scheduler::new();
+        let count = Rc::new(RefCell::new(0));
+        let c = count.clone();
+        let handle = scheduler.every(0.25, move || *c.borrow_mut() += 1);
+
+        scheduler.update(0.1);
+        assert_eq!(*count.borrow(), 0);
+        scheduler.update(0.9);
+        assert_eq!(*count.borrow(), 4);
+
+        assert!(scheduler.cancel(handle));
+        assert!(!scheduler.cancel(handle));
+        scheduler.update(1.0);
+        assert_eq!(*count.borrow(), 4);
+    }
+
+    #[test]
+    fn test_routine_resumes_after_waiting() {
+        let mut scheduler = Scheduler::new();
+        let events = log();
+        let e = events.clone();
+        let ready = Rc::new(RefCell::new(false));
+        let r = ready.clone();
+        scheduler.start_routine(async move {
+            e.borrow_mut().push("start");
+            wait(1.0).await;
+            e.borrow_mut().push("waited");
+            next_update().await;
+            e.borrow_mut().push("next");
+            wait_until(|| *r.borrow()).await;
+            e.borrow_mut().push("done");
+        });
+
+        scheduler.update(0.0);
+        assert_eq!(*events.borrow(), vec!["start"]);
+        scheduler.update(0.5);
+        assert_eq!(events.borrow().len(), 1);
+        scheduler.update(0.5);
+        assert_eq!(*events.borrow(), vec!["start", "waited"]);
+        scheduler.update(0.1);
+        scheduler.update(0.1);
+        assert_eq!(events.borrow().len(), 3);
+
+        *ready.borrow_mut() = true;
+        scheduler.update(0.1);
+        assert_eq!(events.borrow().last(), Some(&"done"));
+        assert!(scheduler.is_empty());
+    }
+
+    #[test]
+    fn test_merge_keeps_relative_times_and_cancels() {
+        let mut running = Scheduler::new();
+        running.update(10.0);
+        let count = Rc::new(RefCell::new(0));
+        let c = count.clone();
+        let repeating = running.every(1.0, move || *c.borrow_mut() += 1);
+
+        // things scheduled and cancelled while `running` was busy
+        let mut added = Scheduler::new();
+        let c = count.clone();
+        added.after(0.5, move || *c.borrow_mut() += 100);
+        added.cancel(repeating);
+
+        running.merge(added);
+        assert!(!running.is_scheduled(repeating));
+        running.update(0.4);
+        assert_eq!(*count.borrow(), 0);
+        running.update(0.2);
+        assert_eq!(*count.borrow(), 100);
+    }
+}
+
+#[cfg(test)]
+mod tween_tests {
+    use crate::animation::{Ease, EasingFunction};
+    use crate::tween::{Tween, Tweens};
+    use bevy_math::Vec2;
+    use std::{cell::Cell, rc::Rc};
+
+    #[test]
+    fn test_ease_enum_matches_endpoints() {
+        for ease in [
+            Ease::Linear,
+            Ease::OutCubic,
+            Ease::InOutSine,
+            Ease::OutBounce,
+        ] {
+            assert!(ease.progress(0.0).abs() < 1e-5);
+            assert!((ease.progress(1.0) - 1.0).abs() < 1e-5);
+        }
+        assert!(Ease::OutCubic.progress(0.5) > 0.5);
+    }
+
+    #[test]
+    fn test_tween_values_over_time() {
+        let mut tweens = Tweens::new();
+        let tween = tweens.tween(0.0_f32, 10.0, 2.0, Ease::Linear);
+
+        tweens.update(0.5);
+        assert!((tween.value() - 2.5).abs() < 1e-5);
+        tweens.update(2.0);
+        assert_eq!(tween.value(), 10.0);
+        assert!(tween.is_complete());
+        assert!(tweens.is_empty());
+    }
+
+    #[test]
+    fn test_chained_tween_and_callbacks() {
+        let mut tweens = Tweens::new();
+        let last = Rc::new(Cell::new(Vec2::ZERO));
+        let done = Rc::new(Cell::new(0));
+        let (l, d) = (last.clone(), done.clone());
+
+        let tween = tweens.add(
+            Tween::new(Vec2::ZERO, Vec2::X, 1.0, Ease::Linear)
+                .then(Vec2::new(1.0, 1.0), 1.0, Ease::Linear)
+                .on_update(move |value| l.set(value))
+                .on_complete(move || d.set(d.get() + 1)),
+        );
+
+        tweens.update(1.5);
+        assert!(last.get().abs_diff_eq(Vec2::new(1.0, 0.5), 1e-5));
+        assert_eq!(done.get(), 0);
+
+        tweens.update(1.0);
+        assert_eq!(last.get(), Vec2::new(1.0, 1.0));
+        assert_eq!(done.get(), 1);
+        tweens.update(1.0);
+        assert_eq!(done.get(), 1);
+        assert!(tween.is_complete());
+    }
+
+    #[test]
+    fn test_pause_and_stop() {
+        let mut tweens = Tweens::new();
+        let done = Rc::new(Cell::new(false));
+        let d = done.clone();
+        let tween = tweens
+            .tween(0.0_f32, 1.0, 1.0, Ease::Linear)
+            .on_complete(move || d.set(true));
+
+        tween.pause();
+        tweens.update(0.5);
+        assert_eq!(tween.value(), 0.0);
+
+        tween.resume();
+        tweens.update(0.5);
+        tween.stop();
+        tweens.update(1.0);
+        assert!((tween.value() - 0.5).abs() < 1e-5);
+        assert!(!done.get());
+        assert!(tweens.is_empty());
+    }
+}
+
+#[cfg(test)]
+mod event_tests {
+    use crate::events::EventBus;
+
+    #[derive(Debug, PartialEq)]
+    struct Hit(u32);
+
+    #[test]
+    fn test_events_readable_for_one_update() {
+        let mut bus = EventBus::new();
+        bus.emit(Hit(1));
+        bus.emit(Hit(2));
+        assert_eq!(bus.read::<Hit>().count(), 0);
+
+        bus.update();
+        assert_eq!(bus.read::<Hit>().collect::<Vec<_>>(), [&Hit(1), &Hit(2)]);
+        assert_eq!(bus.read::<Hit>().count(), 2);
+
+        bus.update();
+        assert_eq!(bus.read::<Hit>().count(), 0);
+    }
+
+    #[test]
+    fn test_events_are_per_type() {
+        let mut bus = EventBus::new();
+        bus.emit(Hit(1));
+        bus.emit(5_u8);
+        bus.update();
+        assert_eq!(bus.read::<u8>().copied().collect::<Vec<_>>(), [5]);
+        assert_eq!(bus.read::<Hit>().count(), 1);
+        assert_eq!(bus.read::<String>().count(), 0);
+    }
+}
+
+#[cfg(test)]
+mod console_tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::debugging::{Console, ConsoleLineKind};
+
+    #[test]
+    fn test_execute_passes_args_and_reports_errors() {
+        let mut console = Console::new();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let s = seen.clone();
+        console.register("spawn", "", move |args| {
+            let count: usize = args.first().copied().unwrap_or("1").parse()?;
+            s.borrow_mut().push(count);
+            Ok(())
+        });
+
+        console.execute("spawn  3");
+        console.execute("spawn");
+        console.execute("spawn lots");
+        console.execute("nope");
+
+        assert_eq!(*seen.borrow(), [3, 1]);
+        let errors = console
+            .output()
+            .iter()
+            .filter(|line| line.kind == ConsoleLineKind::Error)
+            .count();
+        assert_eq!(errors, 2);
+        assert!(console.has_command("spawn"));
+    }
+
+    #[test]
+    fn test_history_navigation() {
+        let mut console = Console::new();
+        console.execute("help");
+        console.execute("clear");
+        console.execute("clear");
+        assert_eq!(console.history(), ["help", "clear"]);
+
+        console.history_up();
+        assert_eq!(console.input(), "clear");
+        console.history_up();
+        console.history_up();
+        assert_eq!(console.input(), "help");
+        console.history_down();
+        assert_eq!(console.input(), "clear");
+        console.history_down();
+        assert_eq!(console.input(), "");
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut console = Console::new();
+        console.register("spawn_enemy", "", |_| Ok(()));
+        console.register("spawn_item", "", |_| Ok(()));
+
+        console.set_input("sp");
+        console.complete();
+        assert_eq!(console.input(), "spawn_");
+        console.complete();
+        assert_eq!(
+            console.output().last().unwrap().text,
+            "spawn_enemy  spawn_item"
+        );
+
+        console.set_input("spawn_i");
+        console.complete();
+        assert_eq!(console.input(), "spawn_item ");
+
+        console.set_input("he");
+        console.complete();
+        assert_eq!(console.input(), "help ");
+    }
+}
+
+#[cfg(test)]
+mod profiler_tests {
+    use std::time::{Duration, Instant};
+
+    use crate::debugging::Profiler;
+
+    #[test]
+    fn test_scopes_are_filed_under_frames_with_depth() {
+        let mut profiler = Profiler::new();
+        let start = Instant::now();
+
+        assert!(profiler.begin_scope());
+        let outer = Instant::now();
+        assert!(profiler.begin_scope());
+        profiler.end_scope("inner", Instant::now());
+        profiler.end_scope("outer", outer);
+        profiler.next_frame(start + Duration::from_millis(16));
+
+        let frame = profiler.last_frame().unwrap();
+        assert_eq!(frame.scopes.len(), 2);
+        assert_eq!(frame.scopes[0].name, "outer");
+        assert_eq!(frame.scopes[0].depth, 0);
+        assert_eq!(frame.scopes[1].depth, 1);
+
+        profiler.next_frame(start + Duration::from_millis(32));
+        assert!(profiler.last_frame().unwrap().scopes.is_empty());
+        assert_eq!(profiler.frames().count(), 2);
+        assert_eq!(profiler.totals()[0].0, "outer");
+    }
+
+    #[test]
+    fn test_disabled_profiler_records_nothing() {
+        let mut profiler = Profiler::new();
+        profiler.set_enabled(false);
+        assert!(!profiler.begin_scope());
+    }
+
+    #[test]
+    fn test_chrome_trace_json() {
+        let mut profiler = Profiler::new();
+        profiler.begin_scope();
+        profiler.end_scope("say \"hi\"", Instant::now());
+        profiler.next_frame(Instant::now());
+
+        let json = profiler.chrome_trace_json();
+        assert!(json.starts_with("{\"traceEvents\":[{\"name\":\"frame\",\"ph\":\"X\""));
+        assert!(json.contains("\"name\":\"say \\\"hi\\\"\""));
+        assert!(json.ends_with("}]}"));
+    }
+}
+
+#[cfg(test)]
+mod gizmo_tests {
+    use bevy_math::{Mat4, Vec2, Vec3};
+
+    use crate::collisions::AABB2D;
+    use crate::color::Color;
+    use crate::gizmos::{GizmoSpace, Gizmos, project_line};
+    use crate::shapes_3d::AABB3D;
+
+    #[test]
+    fn test_disabled_gizmos_queue_nothing() {
+        let mut gizmos = Gizmos::new();
+        gizmos.line(Vec2::ZERO, Vec2::ONE, Color::WHITE, GizmoSpace::Screen);
+        gizmos.set_enabled(false);
+        assert!(gizmos.queued().is_empty());
+
+        gizmos.line(Vec2::ZERO, Vec2::ONE, Color::WHITE, GizmoSpace::World);
+        assert!(gizmos.queued().is_empty());
+    }
+
+    #[test]
+    fn test_outlines_are_split_into_lines() {
+        let mut gizmos = Gizmos::new();
+        let aabb = AABB2D {
+            min: Vec2::ZERO,
+            max: Vec2::ONE,
+        };
+        gizmos.aabb(aabb, Color::WHITE, GizmoSpace::World);
+        assert_eq!(gizmos.queued().len(), 4);
+
+        gizmos.clear();
+        gizmos.aabb_3d(AABB3D::new(Vec3::ZERO, Vec3::ONE), Color::WHITE);
+        assert_eq!(gizmos.queued().len(), 12);
+    }
+
+    #[test]
+    fn test_project_line() {
+        let size = Vec2::new(200.0, 100.0);
+        let (start, end) = project_line(
+            Vec3::new(-1.0, 1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            &Mat4::IDENTITY,
+            size,
+        )
+        .unwrap();
+        assert_eq!(start, Vec2::ZERO);
+        assert_eq!(end, size);
+
+        // entirely behind the camera
+        let behind = Mat4::from_cols_array(&[
+            1.0, 0.0, 0.0, 0.0, //
+            0.0, 1.0, 0.0, 0.0, //
+            0.0, 0.0, 1.0, 0.0, //
+            0.0, 0.0, 0.0, -1.0,
+        ]);
+        assert!(project_line(Vec3::ZERO, Vec3::ONE, &behind, size).is_none());
+    }
+}
+
+#[cfg(test)]
+mod storage_tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use crate::storage::{SaveOptions, decode_save, encode_save, format};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Item {
+        Sword { damage: u32 },
+        Potion(f32),
+        Key,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Progress {
+        name: String,
+        level: u8,
+        position: (f32, f32),
+        checkpoint: Option<u64>,
+        inventory: Vec<Item>,
+        flags: BTreeMap<String, bool>,
+    }
+
+    fn progress() -> Progress {
+        Progress {
+            name: "ferris 🦀".to_string(),
+            level: 3,
+            position: (1.5, -2.0),
+            checkpoint: None,
+            inventory: vec![Item::Sword { damage: 12 }, Item::Potion(0.5), Item::Key],
+            flags: BTreeMap::from([("met_wizard".to_string(), true)]),
+        }
+    }
+
+    #[test]
+    fn test_format_round_trip() {
+        let bytes = format::to_bytes(&progress()).unwrap();
+        assert_eq!(format::from_bytes::<Progress>(&bytes).unwrap(), progress());
+
+        assert!(format::from_bytes::<Progress>(&bytes[..bytes.len() - 1]).is_err());
+        assert!(format::from_bytes::<u8>(&[1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_save_file_round_trip() {
+        let payload = format::to_bytes(&progress()).unwrap();
+        let options = SaveOptions {
+            version: 7,
+            encryption_key: Some([42; 32]),
+            ..Default::default()
+        };
+
+        let file = encode_save(&payload, &options).unwrap();
+        let decoded = decode_save(&file, &options).unwrap();
+        assert_eq!(decoded.version, 7);
+        assert_eq!(decoded.payload, payload);
+
+        let wrong_key = SaveOptions {
+            encryption_key: Some([0; 32]),
+            ..options.clone()
+        };
+        assert!(decode_save(&file, &wrong_key).is_err());
+        assert!(decode_save(&file, &SaveOptions::default()).is_err());
+
+        let plain = SaveOptions {
+            compress: false,
+            ..Default::default()
+        };
+        let mut file = encode_save(&payload, &plain).unwrap();
+        assert!(file.ends_with(&payload));
+        *file.last_mut().unwrap() ^= 1;
+        assert!(decode_save(&file, &plain).is_err());
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use bevy_math::UVec2;
+    use glium::winit::{event::MouseButton, keyboard::KeyCode};
+
+    use crate::color::Color;
+    use crate::config::EngineConfig;
+    use crate::input_handling::{Button, parse_key};
+
+    #[test]
+    fn test_key_names_with_spaces_round_trip() {
+        let mut config = EngineConfig::default();
+        config
+            .key_binds
+            .insert("move left".to_string(), Button::Keyboard(KeyCode::KeyA));
+        config.key_binds.insert(
+            "say \"hi\" = wave".to_string(),
+            Button::Mouse(MouseButton::Middle),
+        );
+        config.target_fps = None;
+
+        let mut reread = EngineConfig {
+            target_fps: Some(60.0),
+            ..Default::default()
+        };
+        reread.read_toml(&config.to_toml()).unwrap();
+        assert_eq!(reread.key_binds, config.key_binds);
+        assert_eq!(reread.target_fps, None);
+    }
+
+    #[test]
+    fn test_config_only_changes_what_it_mentions() {
+        let mut config = EngineConfig {
+            music_volume: 0.3,
+            ..Default::default()
+        };
+        config
+            .read_toml("[audio]\nmaster_volume = 0.5\nvolume = 2\n[extra]\nthing = 1")
+            .unwrap();
+        assert_eq!(config.master_volume, 0.5);
+        assert_eq!(config.music_volume, 0.3);
+
+        assert!(config.read_toml("[window").is_err());
+        assert!(
+            config
+                .read_toml("[graphics]\nclear_color = \"#ff00\"")
+                .is_err()
+        );
+        assert!(
+            config
+                .read_toml("[graphics]\nclear_color = [1, 0, 0]")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let mut config = EngineConfig::default();
+        config
+            .read_toml(
+                r##"
+                [window]
+                size = [800, 600]
+                target_fps = 30
+                [graphics]
+                clear_color = "#ff0000"
+                [audio]
+                master_volume = 0.5
+                music_volume = 0.1
+                [keys]
+                jump = "space"
+                shoot = "MouseLeft"
+                "##,
+            )
+            .unwrap();
+
+        assert_eq!(config.window_size, Some(UVec2::new(800, 600)));
+        assert_eq!(config.target_fps, Some(30.0));
+        assert_eq!(config.clear_color, Color::hex(0xff0000));
+        assert_eq!(config.music_volume(), 0.05);
+        assert_eq!(config.key_binds["jump"], Button::Keyboard(KeyCode::Space));
+
+        let text = config.to_toml();
+        assert!(text.contains("music_volume = 0.1\n"));
+        let mut reread = EngineConfig::default();
+        reread.read_toml(&text).unwrap();
+        assert_eq!(reread.to_toml(), text);
+
+        assert!(config.read_toml("[window]\nvsync = 1").is_err());
+    }
+
+    #[test]
+    fn test_button_names() {
+        assert_eq!(parse_key("a"), Some(KeyCode::KeyA));
+        assert_eq!(parse_key("7"), Some(KeyCode::Digit7));
+        assert_eq!(parse_key("arrowup"), Some(KeyCode::ArrowUp));
+        assert_eq!(parse_key("nope"), None);
+
+        for button in [
+            Button::Keyboard(KeyCode::F12),
+            Button::Mouse(MouseButton::Right),
+            Button::Mouse(MouseButton::Other(5)),
+        ] {
+            assert_eq!(button.to_string().parse::<Button>().unwrap(), button);
+        }
+    }
+}
+
+#[cfg(test)]
+mod drag_drop_tests {
+    use std::path::PathBuf;
+
+    use glium::winit::event::WindowEvent;
+
+    use crate::input_handling::Input;
+
+    #[test]
+    fn drops_last_one_step_and_stop_hovering() {
+        let mut input = Input::new();
+        let a = PathBuf::from("a.png");
+        let b = PathBuf::from("b.png");
+
+        input.process_window_event(&WindowEvent::HoveredFile(a.clone()));
+        input.process_window_event(&WindowEvent::HoveredFile(b.clone()));
+        assert_eq!(input.hovered_files(), [a.clone(), b.clone()]);
+
+        input.process_window_event(&WindowEvent::DroppedFile(a.clone()));
+        input.process_window_event(&WindowEvent::DroppedFile(b.clone()));
+        assert_eq!(input.dropped_files(), [a, b]);
+        assert!(input.hovered_files().is_empty());
+
+        input.step();
+        assert!(input.dropped_files().is_empty());
+    }
+
+    #[test]
+    fn cancelling_a_hover_clears_it() {
+        let mut input = Input::new();
+        input.process_window_event(&WindowEvent::HoveredFile("level.map".into()));
+        input.process_window_event(&WindowEvent::HoveredFileCancelled);
+        assert!(input.hovered_files().is_empty());
+        assert!(input.dropped_files().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod text_field_tests {
+    use crate::input_handling::TextField;
+
+    #[test]
+    fn typing_and_deleting_multibyte_chars() {
+        let mut field = TextField::new();
+        field.insert("héllo\n");
+        assert_eq!(field.text(), "héllo");
+        field.move_left(false);
+        field.move_left(false);
+        field.move_left(false);
+        field.backspace();
+        assert_eq!(field.text(), "hllo");
+        field.delete();
+        assert_eq!(field.text(), "hlo");
+        assert_eq!(field.cursor(), 1);
+    }
+
+    #[test]
+    fn typing_replaces_the_selection() {
+        let mut field = TextField::with_text("hello world");
+        field.move_word_left(true);
+        assert_eq!(field.selected_text(), "world");
+        field.insert("there");
+        assert_eq!(field.text(), "hello there");
+        assert_eq!(field.selection(), None);
+
+        field.select_all();
+        field.backspace();
+        assert!(field.is_empty());
+    }
+
+    #[test]
+    fn moving_without_shift_collapses_the_selection() {
+        let mut field = TextField::with_text("abc");
+        field.move_home(true);
+        field.move_right(false);
+        assert_eq!(field.selection(), None);
+        assert_eq!(field.cursor(), 3);
+    }
+
+    #[test]
+    fn words() {
+        let mut field = TextField::with_text("one two  three");
+        field.move_home(false);
+        field.move_word_right(false);
+        assert_eq!(field.cursor(), 3);
+        field.move_word_right(false);
+        assert_eq!(field.cursor(), 7);
+        field.move_end(false);
+        field.backspace_word();
+        assert_eq!(field.text(), "one two  ");
+    }
+
+    #[test]
+    fn max_len_is_in_chars() {
+        let mut field = TextField::with_text("日本語です").with_max_len(4);
+        assert_eq!(field.text(), "日本語で");
+        field.insert("x");
+        assert_eq!(field.text(), "日本語で");
+        field.backspace();
+        field.insert("xyz");
+        assert_eq!(field.text(), "日本語x");
+    }
+}
+
+#[cfg(test)]
+mod touch_tests {
+    use bevy_math::Vec2;
+    use glium::winit::event::TouchPhase;
+
+    use crate::input_handling::{Gesture, touch::TouchState};
+
+    #[test]
+    fn quick_touch_is_a_tap() {
+        let mut touch = TouchState::default();
+        touch.process(0, TouchPhase::Started, Vec2::new(5.0, 5.0), None, 0.0);
+        touch.process(0, TouchPhase::Moved, Vec2::new(7.0, 5.0), None, 0.1);
+        touch.process(0, TouchPhase::Ended, Vec2::new(7.0, 5.0), None, 0.2);
+        assert_eq!(
+            touch.gestures(),
+            [Gesture::Tap {
+                position: Vec2::new(7.0, 5.0)
+            }]
+        );
+        assert_eq!(touch.ended().len(), 1);
+        assert!(touch.touches().is_empty());
+
+        touch.step(0.3);
+        assert!(touch.gestures().is_empty());
+    }
+
+    #[test]
+    fn held_touch_is_a_long_press_once() {
+        let mut touch = TouchState::default();
+        touch.process(0, TouchPhase::Started, Vec2::ZERO, None, 0.0);
+        touch.step(0.3);
+        assert!(touch.long_presses().is_empty());
+        touch.step(0.6);
+        assert_eq!(touch.long_presses(), [Vec2::ZERO]);
+        touch.step(0.9);
+        assert!(touch.long_presses().is_empty());
+
+        touch.process(0, TouchPhase::Ended, Vec2::ZERO, None, 1.0);
+        assert!(touch.taps().is_empty());
+    }
+
+    #[test]
+    fn dragging_pans() {
+        let mut touch = TouchState::default();
+        touch.process(0, TouchPhase::Started, Vec2::ZERO, None, 0.0);
+        touch.process(0, TouchPhase::Moved, Vec2::new(20.0, 0.0), None, 0.1);
+        touch.process(0, TouchPhase::Moved, Vec2::new(30.0, 5.0), None, 0.1);
+        assert_eq!(touch.pan(), Vec2::new(30.0, 5.0));
+
+        touch.process(0, TouchPhase::Ended, Vec2::new(30.0, 5.0), None, 0.2);
+        assert!(touch.taps().is_empty());
+    }
+
+    #[test]
+    fn spreading_two_fingers_zooms_in_around_their_middle() {
+        let mut touch = TouchState::default();
+        touch.process(0, TouchPhase::Started, Vec2::new(-10.0, 0.0), None, 0.0);
+        touch.process(1, TouchPhase::Started, Vec2::new(10.0, 0.0), None, 0.0);
+        touch.process(0, TouchPhase::Moved, Vec2::new(-20.0, 0.0), None, 0.1);
+        touch.process(1, TouchPhase::Moved, Vec2::new(20.0, 0.0), None, 0.1);
+
+        let (center, scale) = touch.pinch().unwrap();
+        assert_eq!(center, Vec2::ZERO);
+        assert!((scale - 2.0).abs() < 1e-5);
+        assert_eq!(touch.pan(), Vec2::ZERO);
+
+        touch.process(0, TouchPhase::Ended, Vec2::new(-20.0, 0.0), None, 0.2);
+        touch.process(1, TouchPhase::Ended, Vec2::new(20.0, 0.0), None, 0.2);
+        assert!(touch.taps().is_empty());
+    }
+}
+
+#[cfg(test)]
+mod input_action_tests {
+    use glium::winit::keyboard::KeyCode;
+
+    use crate::input_handling::{
+        Action, Axis, AxisBinding, AxisSource, Button, GamepadAxis, InputContext,
+        actions::{context_allows_action, context_allows_axis},
+    };
+
+    const JUMP: Action = Action::new(0);
+    const CONFIRM: Action = Action::new(1);
+    const DEBUG: Action = Action::new(2);
+
+    fn buttons(action: Action) -> Vec<Button> {
+        match action {
+            JUMP | CONFIRM => vec![KeyCode::Space.into()],
+            _ => vec![KeyCode::F3.into()],
+        }
+    }
+
+    #[test]
+    fn buttons_and_sticks_are_clamped_and_deadzoned() {
+        let binding = AxisBinding::buttons(KeyCode::KeyA, KeyCode::KeyD)
+            .with_source(AxisSource::Gamepad(GamepadAxis::LeftStickX))
+            .with_deadzone(0.2);
+
+        assert_eq!(binding.combine([1.0, 0.8]), 1.0);
+        assert_eq!(binding.combine([0.0, 0.1]), 0.0);
+        assert!((binding.combine([0.0, -0.6]) + 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mouse_isnt_clamped_and_sensitivity_scales_everything() {
+        let binding =
+            AxisBinding::new([AxisSource::MouseX, AxisSource::ScrollX]).with_sensitivity(0.5);
+        assert_eq!(binding.combine([30.0, 2.0]), 16.0);
+    }
+
+    #[test]
+    fn contexts_on_top_take_shared_buttons() {
+        let gameplay = InputContext::new().with_actions([JUMP]);
+        let menu = InputContext::new().with_actions([CONFIRM]);
+
+        let stack = vec![("gameplay".to_string(), gameplay.clone())];
+        assert!(context_allows_action(&stack, JUMP, buttons));
+
+        let stack = vec![
+            ("gameplay".to_string(), gameplay),
+            ("menu".to_string(), menu),
+        ];
+        assert!(!context_allows_action(&stack, JUMP, buttons));
+        assert!(context_allows_action(&stack, CONFIRM, buttons));
+        // not in any context
+        assert!(context_allows_action(&stack, DEBUG, buttons));
+    }
+
+    #[test]
+    fn blocking_contexts_stop_everything_below() {
+        let stack = vec![
+            (
+                "gameplay".to_string(),
+                InputContext::new()
+                    .with_actions([DEBUG])
+                    .with_axes([Axis::new(0)]),
+            ),
+            ("pause".to_string(), InputContext::new().blocking()),
+        ];
+        assert!(!context_allows_action(&stack, DEBUG, buttons));
+        assert!(!context_allows_axis(&stack, Axis::new(0)));
+        assert!(context_allows_axis(&stack, Axis::new(1)));
+    }
+}
+
+#[cfg(test)]
+mod rebinding_tests {
+    use glium::winit::{
+        event::{DeviceId, ElementState, MouseButton, WindowEvent},
+        keyboard::KeyCode,
+    };
+
+    use crate::input_handling::{Action, Button, Input};
+
+    const JUMP: Action = Action::new(0);
+    const SAVE: Action = Action::new(12);
+
+    fn click(button: MouseButton) -> WindowEvent {
+        WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state: ElementState::Pressed,
+            button,
+        }
+    }
+
+    #[test]
+    fn next_button_is_bound() {
+        let mut input = Input::new();
+        input.bind(JUMP, KeyCode::Space);
+        input.start_rebind(JUMP);
+        assert_eq!(input.rebinding(), Some(JUMP));
+
+        input.process_window_event(&click(MouseButton::Right));
+        assert_eq!(input.rebinding(), None);
+        assert_eq!(
+            input.get_button(JUMP),
+            Some(&Button::Mouse(MouseButton::Right))
+        );
+        assert_eq!(
+            input.rebound(),
+            Some((JUMP, Button::Mouse(MouseButton::Right)))
+        );
+        // the click that rebound it isn't a press
+        assert!(!input.action_pressed(JUMP));
+
+        input.step();
+        assert_eq!(input.rebound(), None);
+    }
+
+    #[test]
+    fn binds_round_trip() {
+        let mut input = Input::new();
+        input.bind(JUMP, KeyCode::Space);
+        input.bind_chord(
+            SAVE,
+            vec![KeyCode::ControlLeft.into(), KeyCode::KeyS.into()],
+        );
+        let text = input.serialize_binds();
+
+        let mut loaded = Input::new();
+        loaded.load_binds(&text).unwrap();
+        assert_eq!(loaded.get_button(JUMP), Some(&KeyCode::Space.into()));
+        assert_eq!(
+            loaded.get_chord(SAVE),
+            Some([KeyCode::ControlLeft.into(), KeyCode::KeyS.into()].as_slice())
+        );
+    }
+
+    #[test]
+    fn bad_binds_dont_load_at_all() {
+        let mut input = Input::new();
+        assert!(
+            input
+                .load_binds("[binds]\n0 = \"Space\"\n1 = \"NotAKey\"")
+                .is_err()
+        );
+        assert_eq!(input.get_button(JUMP), None);
+    }
+}
+
+#[cfg(test)]
+mod input_recording_tests {
+    use glium::winit::{
+        event::{DeviceId, ElementState, MouseButton, WindowEvent},
+        keyboard::KeyCode,
+    };
+
+    use crate::input_handling::{Button, Input, InputRecording, key_names};
+
+    fn mouse(state: ElementState) -> WindowEvent {
+        WindowEvent::MouseInput {
+            device_id: DeviceId::dummy(),
+            state,
+            button: MouseButton::Left,
+        }
+    }
+
+    fn record() -> InputRecording {
+        let mut input = Input::new();
+        input.start_recording(7);
+        input.process_window_event(&mouse(ElementState::Pressed));
+        input.end_frame(0.1);
+        input.end_frame(0.2);
+        input.process_window_event(&mouse(ElementState::Released));
+        input.end_frame(0.3);
+        input.stop_recording().unwrap()
+    }
+
+    #[test]
+    fn recording_plays_back_frame_by_frame() {
+        let recording = record();
+        assert_eq!(recording.frame_count(), 3);
+        assert!((recording.duration() - 0.6).abs() < 1e-6);
+
+        let mut input = Input::new();
+        input.start_playback(recording);
+
+        assert_eq!(input.end_frame(1.0), 0.1);
+        assert!(input.mouse_pressed(MouseButton::Left));
+        assert!(input.mouse_held(MouseButton::Left));
+
+        assert_eq!(input.end_frame(1.0), 0.2);
+        assert!(!input.mouse_pressed(MouseButton::Left));
+        assert!(input.mouse_held(MouseButton::Left));
+
+        assert_eq!(input.end_frame(1.0), 0.3);
+        assert!(input.mouse_released(MouseButton::Left));
+        assert!(!input.mouse_held(MouseButton::Left));
+
+        // out of frames, so back to the real input
+        assert_eq!(input.end_frame(1.0), 1.0);
+        assert!(!input.is_playing_back());
+    }
+
+    #[test]
+    fn recordings_round_trip_through_bytes() {
+        let recording = record();
+        let bytes = recording.to_bytes().unwrap();
+        assert_eq!(InputRecording::from_bytes(&bytes).unwrap(), recording);
+        assert!(InputRecording::from_bytes(&bytes[..bytes.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn button_codes_round_trip() {
+        for button in [
+            Button::Keyboard(KeyCode::KeyA),
+            Button::Keyboard(KeyCode::F35),
+            Button::Mouse(MouseButton::Forward),
+            Button::Mouse(MouseButton::Other(9)),
+        ] {
+            let code = key_names::button_code(button).unwrap();
+            assert_eq!(key_names::button_from_code(code), Some(button));
+        }
+    }
+}
+
+#[cfg(test)]
+mod music_tests {
+    use crate::audio::{
+        MusicLoop, MusicTrack,
+        music::{Fade, LoopFile},
+    };
+
+    #[test]
+    fn fades_move_linearly_and_stop_at_the_end() {
+        let mut fade = Fade::new(0.0, 1.0, 2.0);
+        assert_eq!(fade.value(), 0.0);
+        fade.advance(0.5);
+        assert_eq!(fade.value(), 0.25);
+        assert!(!fade.is_done());
+        fade.advance(5.0);
+        assert_eq!(fade.value(), 1.0);
+        assert!(fade.is_done());
+    }
+
+    #[test]
+    fn instant_fades_are_already_done() {
+        let fade = Fade::new(1.0, 0.0, 0.0);
+        assert_eq!(fade.value(), 0.0);
+        assert!(fade.is_done());
+    }
+
+    #[test]
+    fn tracks_loop_the_whole_file_by_default() {
+        let track = MusicTrack::new("song.ogg");
+        assert_eq!(track.looping, MusicLoop::Whole);
+        assert_eq!(track.volume, 1.0);
+
+        let track = track.with_loop_points(4.0, 60.0).with_volume(0.5);
+        assert_eq!(
+            track.looping,
+            MusicLoop::Points {
+                start: 4.0,
+                end: 60.0
+            }
+        );
+        assert_eq!(track.once().looping, MusicLoop::Once);
+    }
+
+    #[test]
+    fn loop_files_are_deleted_when_dropped() {
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!(
+            "engine_4_loop_file_test_{}.wav",
+            std::process::id()
+        ));
+        std::fs::write(&path, b"RIFF").unwrap();
+        drop(LoopFile(path.clone()));
+        assert!(!path.exists());
+
+        // even when nothing's waiting for the thread that made it
+        let path = dir.join(format!(
+            "engine_4_loop_thread_test_{}.wav",
+            std::process::id()
+        ));
+        let written = path.clone();
+        let thread = std::thread::spawn(move || {
+            let file = LoopFile(written);
+            std::fs::write(&file.0, b"RIFF").unwrap();
+            file
+        });
+        while !thread.is_finished() {
+            std::thread::yield_now();
+        }
+        assert!(path.exists());
+        drop(thread);
+        assert!(!path.exists());
+    }
+}
+
+#[cfg(test)]
+mod spatial_audio_tests {
+    use bevy_math::Vec2;
+
+    use crate::audio::SpatialFalloff;
+
+    #[test]
+    fn volume_falls_off_between_the_distances() {
+        let falloff = SpatialFalloff {
+            min_distance: 100.0,
+            max_distance: 300.0,
+            pan_distance: 200.0,
+        };
+        assert_eq!(falloff.mix(Vec2::new(0.0, 50.0)).0, 1.0);
+        assert_eq!(falloff.mix(Vec2::new(0.0, 200.0)).0, 0.5);
+        assert_eq!(falloff.mix(Vec2::new(0.0, 400.0)).0, 0.0);
+    }
+
+    #[test]
+    fn pans_toward_the_side_its_on() {
+        let falloff = SpatialFalloff {
+            pan_distance: 200.0,
+            ..Default::default()
+        };
+        assert_eq!(falloff.mix(Vec2::ZERO).1, 0.0);
+        assert_eq!(falloff.mix(Vec2::new(-100.0, 0.0)).1, -0.5);
+        assert_eq!(falloff.mix(Vec2::new(1000.0, 0.0)).1, 1.0);
+        // straight above or below is in the middle
+        assert_eq!(falloff.mix(Vec2::new(0.0, -150.0)).1, 0.0);
+    }
+}
+
+#[cfg(test)]
+mod audio_bus_tests {
+    use crate::audio::{AudioBus, buses::BusState};
+
+    #[test]
+    fn buses_start_at_full_volume() {
+        let buses = BusState::default();
+        for bus in AudioBus::ALL {
+            assert_eq!(buses.gain(bus), 1.0);
+        }
+    }
+
+    #[test]
+    fn master_volume_scales_every_bus() {
+        let mut buses = BusState::default();
+        buses.set_master_volume(0.5);
+        buses.set_volume(AudioBus::Music, 0.5);
+        assert_eq!(buses.gain(AudioBus::Music), 0.25);
+        assert_eq!(buses.gain(AudioBus::Sfx), 0.5);
+        assert_eq!(buses.gain(AudioBus::Ui), 0.5);
+    }
+
+    #[test]
+    fn volumes_dont_go_below_zero() {
+        let mut buses = BusState::default();
+        buses.set_volume(AudioBus::Ui, -1.0);
+        assert_eq!(buses.gain(AudioBus::Ui), 0.0);
+        buses.set_master_volume(-1.0);
+        assert_eq!(buses.gain(AudioBus::Sfx), 0.0);
+    }
+}
+
+#[cfg(test)]
+mod sound_handle_tests {
+    use crate::audio::{
+        AudioBus, SoundHandle,
+        sounds::{PlayingSound, forget_finished, still_playing},
+    };
+    use crate::events::{EventBus, SoundFinished};
+
+    #[test]
+    fn sounds_get_a_moment_to_start() {
+        assert!(still_playing(false, false, 0.0));
+        assert!(still_playing(false, false, 0.9));
+        assert!(!still_playing(false, false, 1.5));
+    }
+
+    #[test]
+    fn sounds_are_done_once_they_stop_after_playing() {
+        assert!(still_playing(true, true, 10.0));
+        assert!(!still_playing(false, true, 0.1));
+    }
+
+    #[test]
+    fn seeking_a_watched_sound_doesnt_finish_it() {
+        let handle = SoundHandle(7);
+        let mut sound = PlayingSound::new(handle, 1, "boom.wav", AudioBus::Sfx, None);
+        sound.watched = true;
+        let mut sounds = vec![sound];
+        let mut events = EventBus::new();
+
+        forget_finished(&mut sounds, |id| id == 1, &mut events);
+        // seeking stops the old sound and starts a new one, which takes a moment to play
+        sounds[0].restart(2, 3.0);
+        forget_finished(&mut sounds, |_| false, &mut events);
+        events.update();
+        assert_eq!(sounds.len(), 1);
+        assert_eq!(events.read::<SoundFinished>().count(), 0);
+
+        forget_finished(&mut sounds, |id| id == 2, &mut events);
+        forget_finished(&mut sounds, |_| false, &mut events);
+        events.update();
+        assert!(sounds.is_empty());
+        assert_eq!(
+            events.read::<SoundFinished>().collect::<Vec<_>>(),
+            [&SoundFinished { handle }]
+        );
+    }
+}
+
+#[cfg(test)]
+mod audio_format_tests {
+    use crate::audio::AudioFormat;
+
+    #[test]
+    fn formats_come_from_extensions() {
+        assert_eq!(AudioFormat::from_extension("OGG"), Some(AudioFormat::Ogg));
+        assert_eq!(AudioFormat::from_extension("flac"), Some(AudioFormat::Flac));
+        assert_eq!(AudioFormat::from_extension("png"), None);
+    }
+
+    #[test]
+    fn formats_are_recognised_by_their_bytes() {
+        let mp3 = include_bytes!("../../assets/sounds/vine-boom.mp3");
+        assert!(AudioFormat::Mp3.matches(mp3));
+        assert!(!AudioFormat::Ogg.matches(mp3));
+
+        assert!(AudioFormat::Wav.matches(b"RIFF\0\0\0\0WAVEfmt "));
+        assert!(AudioFormat::Flac.matches(b"fLaC\0\0\0\x22"));
+        assert!(!AudioFormat::Wav.matches(b"RIFF"));
+    }
+}
+
+#[cfg(test)]
+mod music_beat_tests {
+    use crate::audio::{beat_at, music::beats_crossed};
+
+    #[test]
+    fn beats_count_from_the_offset() {
+        assert_eq!(beat_at(0.5, 120.0, 0.5), 0.0);
+        assert_eq!(beat_at(1.75, 120.0, 0.5), 2.5);
+        assert!(beat_at(0.0, 120.0, 0.5) < 0.0);
+    }
+
+    #[test]
+    fn crossed_beats_catch_up_and_restart_on_loops() {
+        assert_eq!(beats_crossed(None, 0.2), 0..=0);
+        // started listening part way through, so wait for the next one
+        assert!(beats_crossed(None, 3.5).is_empty());
+        assert!(beats_crossed(Some(3), 3.9).is_empty());
+        assert_eq!(beats_crossed(Some(3), 6.1), 4..=6);
+        // looped back to the start
+        assert_eq!(beats_crossed(Some(15), 0.1), 0..=0);
+    }
+}
+
+#[cfg(test)]
+mod microphone_tests {
+    use std::f32::consts::TAU;
+
+    use crate::audio::{detect_pitch, rms};
+
+    fn sine(frequency: f32, sample_rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| (TAU * frequency * i as f32 / sample_rate as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn rms_of_a_sine_is_its_peak_over_root_two() {
+        assert!((rms(&sine(440.0, 44100, 4410)) - 0.5f32.sqrt()).abs() < 0.01);
+        assert_eq!(rms(&[]), 0.0);
+    }
+
+    #[test]
+    fn pitch_is_found_without_octave_errors() {
+        for frequency in [82.0, 220.0, 440.0, 880.0] {
+            let pitch = detect_pitch(&sine(frequency, 44100, 4096), 44100).unwrap();
+            assert!(
+                (pitch - frequency).abs() < frequency * 0.01,
+                "{pitch} for {frequency}"
+            );
+        }
+    }
+
+    #[test]
+    fn silence_and_noise_have_no_pitch() {
+        assert_eq!(detect_pitch(&[0.0; 4096], 44100), None);
+        let mut state = 0x2545_f491u32;
+        let noise: Vec<f32> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as f32 / u32::MAX as f32 * 2.0 - 1.0
+            })
+            .collect();
+        assert_eq!(detect_pitch(&noise, 44100), None);
+    }
+}
+
+#[cfg(test)]
+mod font_fallback_tests {
+    use crate::text_rendering::*;
+
+    fn load(bytes: &[u8]) -> fontdue::Font {
+        fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default()).unwrap()
+    }
+
+    #[test]
+    fn test_missing_glyphs_come_from_fallback() {
+        let inter = load(include_bytes!("../../assets/fonts/inter.ttf"));
+        let jetbrains = load(include_bytes!("../../assets/fonts/jetbrains.ttf"));
+        assert!(!inter.has_glyph('∀'));
+
+        let layout = layout_text_with_fallbacks(&[&inter, &jetbrains], "a ∀ b", 16.0);
+        let fonts: Vec<usize> = layout.glyphs.iter().map(|glyph| glyph.font).collect();
+
+        assert_eq!(fonts, [0, 0, 1, 1, 0]);
+        assert!(layout.glyphs[1].position.x < layout.glyphs[2].position.x);
+        assert!(layout.glyphs[2].position.x < layout.glyphs[4].position.x);
+    }
+
+    #[test]
+    fn test_glyphs_no_font_has_use_the_primary() {
+        let inter = load(include_bytes!("../../assets/fonts/inter.ttf"));
+        let jetbrains = load(include_bytes!("../../assets/fonts/jetbrains.ttf"));
+
+        let layout = layout_text_with_fallbacks(&[&inter, &jetbrains], "漢", 16.0);
+
+        assert_eq!(layout.glyphs.len(), 1);
+        assert_eq!(layout.glyphs[0].font, 0);
+    }
+}
+
+#[cfg(test)]
+mod text_box_tests {
+    use crate::text_rendering::*;
+    use bevy_math::{Vec2, vec2};
+
+    fn font() -> fontdue::Font {
+        fontdue::Font::from_bytes(
+            include_bytes!("../../assets/fonts/jetbrains.ttf") as &[u8],
+            fontdue::FontSettings::default(),
+        )
+        .unwrap()
+    }
+
+    fn layout(text: &str, size: Vec2, style: TextStyle) -> TextLayout {
+        layout_text_boxed(&[&font()], text, 16.0, size, &style)
+    }
+
+    fn char_width() -> f32 {
+        layout_text(&font(), "a", 16.0).size.x
+    }
+
+    /// The visible characters on each line.
+    fn rows(layout: &TextLayout) -> Vec<String> {
+        let mut rows: Vec<(f32, String)> = Vec::new();
+        for glyph in layout
+            .glyphs
+            .iter()
+            .filter(|g| !g.character.is_whitespace())
+        {
+            let top = glyph.position.y.round();
+            match rows.iter_mut().find(|(y, _)| (*y - top).abs() < 8.0) {
+                Some((_, row)) => row.push(glyph.character),
+                None => rows.push((top, glyph.character.to_string())),
+            }
+        }
+        rows.into_iter().map(|(_, row)| row).collect()
+    }
+
+    #[test]
+    fn test_text_wraps_between_words() {
+        let width = char_width() * 8.5;
+        let layout = layout("aaa bbb ccc", vec2(width, 1000.0), TextStyle::default());
+
+        assert_eq!(rows(&layout), ["aaabbb", "ccc"]);
+        assert!(layout.size.x <= width);
+    }
+
+    #[test]
+    fn test_text_breaks_at_newlines_without_wrapping() {
+        let style = TextStyle {
+            wrap: false,
+            ..Default::default()
+        };
+        let layout = layout("aaa bbb\nccc", vec2(10.0, 1000.0), style);
+
+        assert_eq!(rows(&layout), ["aaabbb", "ccc"]);
+    }
+
+    #[test]
+    fn test_right_aligned_text_ends_at_box_edge() {
+        let style = TextStyle {
+            align: TextAlign::Right,
+            ..Default::default()
+        };
+        let layout = layout("ab", vec2(200.0, 100.0), style);
+        let last = layout.glyphs.last().unwrap();
+
+        assert!((last.position.x + last.advance - 200.0).abs() < char_width());
+    }
+
+    #[test]
+    fn test_justified_lines_fill_box_except_last() {
+        let style = TextStyle {
+            align: TextAlign::Justify,
+            ..Default::default()
+        };
+        let width = char_width() * 12.0;
+        let layout = layout("aa bb cc dd ee", vec2(width, 1000.0), style);
+
+        assert_eq!(rows(&layout).len(), 2);
+        assert!((layout.size.x - width).abs() < 0.01);
+        let last = layout.glyphs.last().unwrap();
+        assert!(last.position.x + last.advance < width - char_width());
+    }
+
+    #[test]
+    fn test_bottom_aligned_text_sits_at_box_bottom() {
+        let style = TextStyle {
+            vertical_align: VerticalAlign::Bottom,
+            ..Default::default()
+        };
+        let layout = layout("a", vec2(100.0, 300.0), style);
+
+        assert!(layout.glyphs[0].position.y > 250.0);
+    }
+
+    #[test]
+    fn test_ellipsis_cuts_off_lines_that_dont_fit() {
+        let style = TextStyle {
+            ellipsis: true,
+            ..Default::default()
+        };
+        let width = char_width() * 8.5;
+        let layout = layout("aaa bbb ccc ddd", vec2(width, 20.0), style);
+        let rows = rows(&layout);
+
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].ends_with('…'));
+        assert!(layout.size.x <= width);
+    }
+}
+
+#[cfg(test)]
+mod rich_text_tests {
+    use crate::color::Color;
+    use crate::rich_text::*;
+
+    #[test]
+    fn test_markup_splits_into_styled_spans() {
+        let spans = parse_rich_text("Find the [color=#ff0000][b]key[/b][/color] now");
+
+        assert_eq!(spans.len(), 3);
+        assert_eq!(spans[0], TextSpan::new("Find the "));
+        assert_eq!(
+            spans[1],
+            TextSpan::new("key").with_color(Color::hex(0xff0000)).bold()
+        );
+        assert_eq!(spans[2], TextSpan::new(" now"));
+    }
+
+    #[test]
+    fn test_markup_nests_sizes_and_icons() {
+        let spans = parse_rich_text("[size=30]big [i][size=10]small[/size][/i] [icon=coin][/size]");
+
+        assert_eq!(spans[0], TextSpan::new("big ").with_size(30));
+        assert_eq!(spans[1], TextSpan::new("small").with_size(10).italic());
+        assert_eq!(spans[2], TextSpan::new(" ").with_size(30));
+        assert_eq!(spans[3], TextSpan::icon("coin").with_size(30));
+    }
+
+    #[test]
+    fn test_unknown_tags_and_escapes_stay_as_text() {
+        let spans = parse_rich_text("[[b] [wave]hi[/wave] [color=#12]");
+
+        assert_eq!(spans, [TextSpan::new("[b] [wave]hi[/wave] [color=#12]")]);
+    }
+}
+
+#[cfg(test)]
+mod sdf_text_tests {
+    use crate::color::Color;
+    use crate::draw_queue_2d::{DrawQueue2D, SdfLook};
+    use crate::testing::{DrawCall2D, MockDrawTarget2D};
+    use crate::text_rendering::signed_distance_field;
+    use crate::textures::TextureRef;
+    use bevy_math::{Mat4, Rect, Vec2};
+
+    #[test]
+    fn test_distance_field_crosses_half_at_edge() {
+        let coverage = vec![255; 10 * 10];
+        let field = signed_distance_field(10, 10, &coverage, 4);
+        let width = 18;
+        assert_eq!(field.len(), width * width);
+
+        let row: Vec<u8> = field[9 * width..10 * width].to_vec();
+        // padding, then the square starting at 4
+        assert!(row[0] < 32);
+        assert!(row[3] < 128 && row[4] > 128);
+        assert_eq!(row[9], 255);
+        assert!(row[..9].windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_empty_glyph_is_all_outside() {
+        let field = signed_distance_field(3, 3, &[0; 9], 2);
+
+        assert!(field.iter().all(|&distance| distance == 0));
+    }
+
+    #[test]
+    fn test_sdf_glyphs_batch_by_texture() {
+        let look = SdfLook {
+            color: Color::WHITE,
+            outline_width: 0.1,
+            outline_color: Color::BLACK,
+            shadow_offset: Vec2::ZERO,
+            shadow_softness: 0.0,
+            shadow_color: Color::TRANSPARENT,
+        };
+        let uv = Rect::new(0.0, 0.0, 0.5, 0.5);
+
+        let mut queue = DrawQueue2D::empty();
+        for _ in 0..3 {
+            queue.add_sdf_glyph(
+                TextureRef::from_raw(0, 0),
+                Vec2::ZERO,
+                Vec2::splat(10.0),
+                uv,
+                look,
+            );
+        }
+        let mut target = MockDrawTarget2D::default();
+        queue.draw_to(&mut target, &Mat4::IDENTITY);
+
+        let [
+            DrawCall2D::SdfGlyphs {
+                texture,
+                vertices,
+                indices,
+            },
+        ] = target.calls.as_slice()
+        else {
+            panic!("expected one distance field batch");
+        };
+        assert_eq!(*texture, TextureRef::from_raw(0, 0));
+        assert_eq!(vertices.len(), 12);
+        assert_eq!(indices.len(), 18);
+        assert_eq!(vertices[2].tex_coords, [0.5, 0.5]);
+        assert_eq!(vertices[0].effects[0], 0.1);
+    }
+}
+
+#[cfg(test)]
+mod text_animation_tests {
+    use crate::color::Color;
+    use crate::text_animation::*;
+    use crate::text_rendering::layout_text;
+    use bevy_math::Vec2;
+
+    fn glyph(index: usize) -> AnimatedGlyph {
+        AnimatedGlyph::new('a', index, Vec2::ZERO, Color::WHITE)
+    }
+
+    #[test]
+    fn test_layout_has_a_glyph_per_character() {
+        let font = fontdue::Font::from_bytes(
+            include_bytes!("../../assets/fonts/jetbrains.ttf") as &[u8],
+            fontdue::FontSettings::default(),
+        )
+        .unwrap();
+        let text = "Hi, you\nthere";
+        let layout = layout_text(&font, text, 16.0);
+
+        let characters: String = layout.glyphs.iter().map(|glyph| glyph.character).collect();
+        assert_eq!(characters, text);
+    }
+
+    #[test]
+    fn test_typewriter_reveals_at_speed_and_pauses_on_punctuation() {
+        let mut typewriter = TypewriterText::new("ab, cd", 10.0);
+        typewriter.punctuation_pause = 0.5;
+
+        assert_eq!(typewriter.advance(0.2), 2);
+        assert_eq!(typewriter.advance(0.1), 1);
+        // waiting after the comma
+        assert_eq!(typewriter.advance(0.4), 0);
+        assert_eq!(typewriter.advance(0.2), 1);
+        assert!(!typewriter.is_finished());
+
+        typewriter.skip();
+        assert!(typewriter.is_finished());
+        assert_eq!(typewriter.visible_chars(), 6);
+    }
+
+    #[test]
+    fn test_typewriter_hides_unrevealed_glyphs() {
+        let mut typewriter = TypewriterText::new("abcd", 10.0);
+        typewriter.advance(0.15);
+
+        let alphas: Vec<f32> = (0..4)
+            .map(|index| {
+                let mut glyph = glyph(index);
+                typewriter.apply(&mut glyph);
+                glyph.color.a
+            })
+            .collect();
+
+        assert_eq!(alphas[0], 1.0);
+        assert!((alphas[1] - 0.5).abs() < 0.01);
+        assert_eq!(alphas[2..], [0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_shake_is_repeatable_within_a_step() {
+        let shake = TextEffect::Shake {
+            amount: 2.0,
+            rate: 10.0,
+        };
+        let (mut a, mut b, mut c) = (glyph(3), glyph(3), glyph(3));
+        shake.apply(&mut a, 1.01);
+        shake.apply(&mut b, 1.05);
+        shake.apply(&mut c, 1.15);
+
+        assert_eq!(a.offset, b.offset);
+        assert_ne!(a.offset, c.offset);
+        assert!(a.offset.abs().max_element() <= 2.0);
+    }
+}
+
+#[cfg(test)]
+mod glyph_atlas_tests {
+    use crate::image::Image;
+    use crate::textures::glyph_atlas::{GlyphAtlas, PAGE_SIZE, ShelfPacker};
+    use bevy_math::USizeVec2;
+
+    // a glyph big enough that only one fits on a page
+    fn big_glyph() -> Image {
+        Image::empty(PAGE_SIZE - 8, PAGE_SIZE - 8)
+    }
+
+    #[test]
+    fn test_shelf_packer_reuses_shelves() {
+        let mut packer = ShelfPacker::new(64, 64);
+        let a = packer.pack(USizeVec2::new(10, 10)).unwrap();
+        let b = packer.pack(USizeVec2::new(10, 9)).unwrap();
+        let c = packer.pack(USizeVec2::new(10, 30)).unwrap();
+
+        assert_eq!(a, USizeVec2::new(0, 0));
+        assert_eq!(b.y, 0);
+        assert!(b.x > 10);
+        assert!(c.y > 10);
+        assert!(packer.pack(USizeVec2::new(10, 40)).is_none());
+    }
+
+    #[test]
+    fn test_glyph_atlas_evicts_least_recently_used_page() {
+        let mut atlas = GlyphAtlas::new(2);
+        atlas.insert('a', &big_glyph(), 1);
+        atlas.insert('b', &big_glyph(), 2);
+        // touching `a` makes `b` the oldest
+        atlas.get('a', 3);
+        atlas.insert('c', &big_glyph(), 4);
+
+        assert_eq!(atlas.page_count(), 2);
+        assert!(atlas.contains('a'));
+        assert!(!atlas.contains('b'));
+        assert!(atlas.contains('c'));
+    }
+
+    #[test]
+    fn test_glyph_atlas_keeps_pages_used_this_frame() {
+        let mut atlas = GlyphAtlas::new(1);
+        atlas.insert('a', &big_glyph(), 5);
+        atlas.insert('b', &big_glyph(), 5);
+
+        assert_eq!(atlas.page_count(), 2);
+        assert_eq!(atlas.len(), 2);
+
+        // the next frame, going over the limit clears a page again
+        atlas.insert('c', &big_glyph(), 6);
+        assert_eq!(atlas.page_count(), 2);
+        assert_eq!(atlas.len(), 2);
+    }
+}
+
+#[cfg(test)]
+mod ui_tests {
+    use crate::ui::{Anchor, slider_fraction, slider_value};
+    use bevy_math::{Rect, Vec2, vec2};
+
+    #[test]
+    fn test_anchor_place_uses_offset_as_margin() {
+        let screen = Rect::from_corners(Vec2::ZERO, vec2(800.0, 600.0));
+        let size = vec2(100.0, 50.0);
+        let margin = vec2(10.0, 20.0);
+
+        assert_eq!(
+            Anchor::TopLeft.place(screen, size, margin),
+            vec2(10.0, 20.0)
+        );
+        assert_eq!(
+            Anchor::BottomRight.place(screen, size, margin),
+            vec2(690.0, 530.0)
+        );
+        assert_eq!(
+            Anchor::Center.place(screen, size, Vec2::ZERO),
+            vec2(350.0, 275.0)
+        );
+    }
+
+    #[test]
+    fn test_slider_value_clamps_to_range() {
+        let rect = Rect::from_corners(vec2(100.0, 0.0), vec2(300.0, 20.0));
+
+        assert_eq!(slider_value(200.0, rect, 0.0..=10.0), 5.0);
+        assert_eq!(slider_value(0.0, rect, 0.0..=10.0), 0.0);
+        assert_eq!(slider_value(999.0, rect, -1.0..=1.0), 1.0);
+        assert_eq!(slider_fraction(7.5, 5.0..=10.0), 0.5);
+        assert_eq!(slider_fraction(3.0, 3.0..=3.0), 0.0);
+    }
+}
+
+#[cfg(test)]
+mod ui_layout_tests {
+    use crate::ui::Anchor;
+    use crate::ui_layout::{SafeArea, UiSize, layout_in};
+    use bevy_math::{Rect, Vec2, vec2};
+
+    #[test]
+    fn test_layout_mixes_percent_and_pixels() {
+        let area = Rect::from_corners(Vec2::ZERO, vec2(1000.0, 500.0));
+        let size = UiSize::percent(50.0, 10.0) - UiSize::px(20.0, 0.0);
+        let rect = layout_in(area, Anchor::BottomRight, size, UiSize::px(10.0, 10.0), 2.0);
+
+        assert_eq!(rect.size(), vec2(460.0, 50.0));
+        assert_eq!(rect.max, vec2(980.0, 480.0));
+    }
+
+    #[test]
+    fn test_safe_area_shrinks_area() {
+        let area = Rect::from_corners(Vec2::ZERO, vec2(100.0, 100.0));
+        let safe = SafeArea::new(10.0, 20.0, 5.0, 0.0).apply(area);
+        assert_eq!(safe, Rect::from_corners(vec2(10.0, 5.0), vec2(80.0, 100.0)));
+
+        // insets bigger than the area leave nothing instead of flipping it
+        let empty = SafeArea::uniform(80.0).apply(area);
+        assert_eq!(empty.size(), Vec2::ZERO);
+    }
+}
+
+#[cfg(test)]
+mod image_edit_tests {
+    use crate::color::u8::Pixel;
+    use crate::image::{Image, ResizeFilter};
+    use crate::utils::usize_rect::USizeRect;
+    use bevy_math::IVec2;
+
+    // a 3x2 image with a different gray in every pixel
+    fn numbered() -> Image {
+        Image::new(3, 2, (0..6).map(|n| Pixel::splat(n * 10)).collect())
+    }
+
+    fn grays(image: &Image) -> Vec<u8> {
+        image.pixels().iter().map(|pixel| pixel.r()).collect()
+    }
+
+    #[test]
+    fn test_rotate_and_flip() {
+        let image = numbered();
+
+        let rotated = image.rotate_90();
+        assert_eq!(rotated.dimensions().to_array(), [2, 3]);
+        assert_eq!(grays(&rotated), [30, 0, 40, 10, 50, 20]);
+        assert_eq!(grays(&rotated.rotate_270()), grays(&image));
+        assert_eq!(grays(&image.rotate_180()), [50, 40, 30, 20, 10, 0]);
+
+        let mut flipped = image.clone();
+        flipped.flip_vertical();
+        assert_eq!(grays(&flipped), [30, 40, 50, 0, 10, 20]);
+        flipped.flip_horizontal();
+        assert_eq!(grays(&flipped), grays(&image.rotate_180()));
+    }
+
+    #[test]
+    fn test_resize_and_crop() {
+        let image = numbered();
+
+        let doubled = image.resize(6, 4, ResizeFilter::Nearest);
+        assert_eq!(grays(&doubled)[..6], [0, 0, 10, 10, 20, 20]);
+
+        let blended = Image::new(2, 1, vec![Pixel::splat(0), Pixel::splat(200)]).resize(
+            4,
+            1,
+            ResizeFilter::Bilinear,
+        );
+        assert_eq!(grays(&blended), [0, 50, 150, 200]);
+
+        let cropped = image.crop(USizeRect::new(1, 1, 10, 10));
+        assert_eq!(grays(&cropped), [40, 50]);
+    }
+
+    #[test]
+    fn test_blit_clips_and_draws_lines() {
+        let mut image = Image::gen_color(4, 4, Pixel::WHITE);
+        let square = Image::gen_color(2, 2, Pixel::BLACK);
+        image.blit(&square, IVec2::new(-1, 3));
+        let black = image
+            .pixels()
+            .iter()
+            .filter(|pixel| **pixel == Pixel::BLACK)
+            .count();
+        assert_eq!(black, 1);
+        assert!(*image.get_pixel(0, 3).unwrap() == Pixel::BLACK);
+
+        let mut image = Image::empty(5, 5);
+        image.draw_line(IVec2::new(0, 0), IVec2::new(4, 2), Pixel::WHITE);
+        let drawn: Vec<(usize, usize)> = image
+            .iter()
+            .filter(|(_, _, pixel)| *pixel == Pixel::WHITE)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        assert_eq!(drawn.len(), 5);
+        assert!(drawn.contains(&(0, 0)) && drawn.contains(&(4, 2)));
+    }
+}
+
+#[cfg(test)]
+mod image_save_tests {
+    use crate::color::u8::Pixel;
+    use crate::image::Image;
+    use image::ImageFormat;
+
+    #[test]
+    fn test_png_round_trip() {
+        let mut image = Image::gen_color(3, 2, Pixel::SKY_400);
+        image.set(2, 1, Pixel::RED_600.with_alpha(100));
+
+        let path = std::env::temp_dir().join("engine_4_image_save_test.png");
+        image.save(&path, ImageFormat::Png).unwrap();
+        let loaded = Image::load(&std::fs::read(&path).unwrap(), ImageFormat::Png).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(loaded.dimensions(), image.dimensions());
+        assert!(loaded.pixels() == image.pixels());
+    }
+}
+
+#[cfg(test)]
+mod compressed_texture_tests {
+    use crate::color::u8::Pixel;
+    use crate::textures::compressed::{BlockFormat, CompressedImage, decode_blocks};
+
+    /// A KTX2 file with one level and no supercompression.
+    fn ktx2(vk_format: u32, width: u32, height: u32, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![
+            0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+        ];
+        for value in [vk_format, 1, width, height, 0, 0, 1, 1, 0] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend([0u8; 32]);
+        let offset = bytes.len() as u64 + 24;
+        for value in [offset, data.len() as u64, data.len() as u64] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(data);
+        bytes
+    }
+
+    #[test]
+    fn test_parse_and_decode_bc1() {
+        // red and blue, with the top row all red and the rest all blue
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0x00, 0x55, 0x55, 0x55];
+        let image = CompressedImage::parse_ktx2(&ktx2(131, 4, 4, &block)).unwrap();
+        assert_eq!(image.format, BlockFormat::Bc1);
+        assert_eq!((image.width, image.height, image.levels.len()), (4, 4, 1));
+
+        let decoded = image.decode().unwrap();
+        assert!(*decoded.get_pixel(3, 0).unwrap() == Pixel::from_rgb(255, 0, 0));
+        assert!(*decoded.get_pixel(0, 3).unwrap() == Pixel::from_rgb(0, 0, 255));
+
+        assert!(CompressedImage::parse_ktx2(&ktx2(0, 4, 4, &block)).is_err());
+        assert!(CompressedImage::parse_ktx2(&ktx2(131, 8, 8, &block)).is_err());
+    }
+
+    #[test]
+    fn test_decode_bc3_alpha_and_partial_blocks() {
+        // alpha endpoints 255 and 0 with every pixel on the first, white color
+        let mut block = [0u8; 16];
+        block[0] = 255;
+        block[8..10].copy_from_slice(&0xFFFFu16.to_le_bytes());
+        // the last pixel uses alpha index 1
+        block[7] = 0b0010_0000;
+
+        let image = decode_blocks(BlockFormat::Bc3, &block, 4, 4).unwrap();
+        assert!(*image.get_pixel(0, 0).unwrap() == Pixel::WHITE);
+        assert_eq!(image.get_pixel(3, 3).unwrap().a(), 0);
+
+        let cropped = decode_blocks(BlockFormat::Bc3, &block, 3, 2).unwrap();
+        assert_eq!(cropped.dimensions().to_array(), [3, 2]);
+    }
+
+    /// Packs values into a block or bitstream, lowest bit first.
+    #[derive(Default)]
+    struct Bits {
+        bytes: Vec<u8>,
+        len: usize,
+    }
+
+    impl Bits {
+        fn put(&mut self, value: u64, count: usize) -> &mut Self {
+            for i in 0..count {
+                if self.len.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                self.bytes[self.len / 8] |= ((value >> i) as u8 & 1) << (self.len % 8);
+                self.len += 1;
+            }
+            self
+        }
+
+        /// A Basis Huffman table where each of `symbols` (one or two, in order) has a 1 bit
+        /// code, so the first is sent as a 0 and the second as a 1.
+        fn table(&mut self, symbols: &[u32]) -> &mut Self {
+            let count = symbols.last().map_or(0, |last| last + 1);
+            self.put(count as u64, 14);
+            if count == 0 {
+                return self;
+            }
+            // code lengths 0 and 1 get 1 bit codes, which are 5th and 19th in the sent order
+            self.put(19, 5);
+            for symbol in 0..19 {
+                self.put((symbol == 4 || symbol == 18) as u64, 3);
+            }
+            for symbol in 0..count {
+                self.put(symbols.contains(&symbol) as u64, 1);
+            }
+            self
+        }
+    }
+
+    #[test]
+    fn test_decode_bc7() {
+        // mode 6: one subset, 7 bit endpoints with a p-bit each, 4 bit indices
+        let mut bits = Bits::default();
+        bits.put(1 << 6, 7);
+        for (first, second) in [(127, 0), (0, 0), (127, 0), (127, 0)] {
+            bits.put(first, 7).put(second, 7);
+        }
+        bits.put(1, 1).put(0, 1);
+        // the first pixel's index has 3 bits, as the anchor
+        bits.put(0, 3).put(8, 4);
+        for _ in 2..15 {
+            bits.put(0, 4);
+        }
+        bits.put(15, 4);
+
+        let image = decode_blocks(BlockFormat::Bc7, &bits.bytes, 4, 4).unwrap();
+        assert!(*image.get_pixel(0, 0).unwrap() == Pixel::from_rgba(255, 1, 255, 255));
+        assert!(*image.get_pixel(1, 0).unwrap() == Pixel::from_rgba(120, 0, 120, 120));
+        assert!(*image.get_pixel(3, 3).unwrap() == Pixel::from_rgba(0, 0, 0, 0));
+
+        // mode 1 with partition 13: the top two rows are the first subset, the rest the second
+        let mut bits = Bits::default();
+        bits.put(0b10, 2).put(13, 6);
+        for channel in [[63, 63, 0, 0], [0; 4], [0, 0, 63, 63]] {
+            for value in channel {
+                bits.put(value, 6);
+            }
+        }
+        bits.put(0, 2).put(0, 46);
+
+        let image = decode_blocks(BlockFormat::Bc7, &bits.bytes, 4, 4).unwrap();
+        assert!(*image.get_pixel(3, 1).unwrap() == Pixel::from_rgb(253, 0, 0));
+        assert!(*image.get_pixel(0, 2).unwrap() == Pixel::from_rgb(0, 0, 253));
+
+        // no mode bit at all
+        let image = decode_blocks(BlockFormat::Bc7, &[0; 16], 4, 4).unwrap();
+        assert!(*image.get_pixel(2, 2).unwrap() == Pixel::TRANSPARENT);
+    }
+
+    #[test]
+    fn test_transcode_basis_etc1s() {
+        // a red endpoint and a blue one, both with the smallest intensity
+        let mut endpoints = Bits::default();
+        endpoints
+            .table(&[0, 31])
+            .table(&[15, 16])
+            .table(&[1])
+            .table(&[0])
+            .put(0, 1);
+        // red: +15, +16 and +16 from 16, then blue: +1 from 31, +0 and +31 from 0
+        endpoints.put(0, 1).put(0b110, 3);
+        endpoints.put(0, 1).put(0b100, 3);
+
+        // raw selectors, the brightest everywhere then the darkest everywhere
+        let mut selectors = Bits::default();
+        selectors.put(0b100, 3);
+        for row in [0xFF; 4].into_iter().chain([0x00; 4]) {
+            selectors.put(row, 8);
+        }
+
+        // every block's endpoint is a delta, and there's no selector history
+        let mut tables = Bits::default();
+        tables
+            .table(&[0b1111])
+            .table(&[0, 1])
+            .table(&[0, 1])
+            .table(&[])
+            .put(0, 13);
+
+        // first block: prediction, delta 0, selector 0. Second: delta 1, selector 1
+        let mut slice = Bits::default();
+        slice.put(0, 3).put(0b11, 2);
+
+        let mut global = Vec::new();
+        global.extend(2u16.to_le_bytes());
+        global.extend(2u16.to_le_bytes());
+        for len in [
+            endpoints.bytes.len(),
+            selectors.bytes.len(),
+            tables.bytes.len(),
+            0,
+        ] {
+            global.extend((len as u32).to_le_bytes());
+        }
+        for value in [0, 0, slice.bytes.len() as u32, 0, 0] {
+            global.extend(value.to_le_bytes());
+        }
+        global.extend(&endpoints.bytes);
+        global.extend(&selectors.bytes);
+        global.extend(&tables.bytes);
+
+        let mut bytes = vec![
+            0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+        ];
+        for value in [0u32, 1, 8, 4, 0, 0, 1, 1, 1, 0, 0, 0, 0] {
+            bytes.extend(value.to_le_bytes());
+        }
+        let global_offset = 104u64;
+        let slice_offset = global_offset + global.len() as u64;
+        let slice_len = slice.bytes.len() as u64;
+        for value in [
+            global_offset,
+            global.len() as u64,
+            slice_offset,
+            slice_len,
+            0,
+        ] {
+            bytes.extend(value.to_le_bytes());
+        }
+        bytes.extend(&global);
+        bytes.extend(&slice.bytes);
+
+        let image = CompressedImage::parse_ktx2(&bytes).unwrap();
+        assert_eq!(image.format, BlockFormat::Bc1);
+        assert_eq!(image.levels[0].len(), 16);
+
+        let decoded = image.decode().unwrap();
+        assert!(*decoded.get_pixel(1, 1).unwrap() == Pixel::from_rgb(255, 8, 8));
+        assert!(*decoded.get_pixel(6, 3).unwrap() == Pixel::from_rgb(0, 0, 247));
+
+        // a level that runs past the end of the file
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(CompressedImage::parse_ktx2(truncated).is_err());
+    }
+
+    #[test]
+    fn test_broken_headers_are_errors() {
+        let block = [0u8; 8];
+        let mut bytes = ktx2(131, 4, 4, &block);
+
+        // far more levels than the file has room for, or a 4x4 texture can have
+        bytes[40..44].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(CompressedImage::parse_ktx2(&bytes).is_err());
+        bytes[40..44].copy_from_slice(&4u32.to_le_bytes());
+        assert!(CompressedImage::parse_ktx2(&bytes).is_err());
+
+        let mut bytes = ktx2(131, 4, 4, &block);
+        bytes[20..24].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(CompressedImage::parse_ktx2(&bytes).is_err());
+        bytes[20..24].copy_from_slice(&0u32.to_le_bytes());
+        assert!(CompressedImage::parse_ktx2(&bytes).is_err());
+
+        assert!(CompressedImage::parse_ktx2(&bytes[..60]).is_err());
+    }
+}
+
+#[cfg(test)]
+mod atlas_builder_tests {
+    use crate::textures::atlas_builder::{SkylinePacker, pack_sizes};
+    use bevy_math::USizeVec2;
+
+    #[test]
+    fn test_skyline_packs_without_overlapping() {
+        let mut packer = SkylinePacker::new(64, 64);
+        let sizes = [(30, 20), (30, 10), (20, 20), (40, 12), (10, 30), (64, 4)];
+        let mut rects = Vec::new();
+        for (w, h) in sizes {
+            let position = packer.pack(USizeVec2::new(w, h)).unwrap();
+            assert!(position.x + w <= 64 && position.y + h <= 64);
+            rects.push((position, USizeVec2::new(w, h)));
+        }
+
+        for (i, (a, a_size)) in rects.iter().enumerate() {
+            for (b, b_size) in &rects[i + 1..] {
+                let apart = a.x + a_size.x <= b.x
+                    || b.x + b_size.x <= a.x
+                    || a.y + a_size.y <= b.y
+                    || b.y + b_size.y <= a.y;
+                assert!(apart, "{a:?} {a_size:?} overlaps {b:?} {b_size:?}");
+            }
+        }
+        assert!(packer.pack(USizeVec2::new(64, 64)).is_none());
+    }
+
+    #[test]
+    fn test_pack_sizes_pages() {
+        let sizes = vec![USizeVec2::new(20, 20); 5];
+        let layout = pack_sizes(&sizes, 32, 1).unwrap();
+        assert_eq!(layout.page_sizes.len(), 5);
+        assert!(
+            layout
+                .page_sizes
+                .iter()
+                .all(|size| *size == USizeVec2::new(32, 32))
+        );
+
+        let layout = pack_sizes(&sizes, 128, 1).unwrap();
+        assert_eq!(layout.page_sizes, vec![USizeVec2::new(128, 32)]);
+        assert!(
+            layout
+                .placements
+                .iter()
+                .all(|placement| placement.page == 0)
+        );
+
+        assert!(pack_sizes(&[USizeVec2::new(200, 10)], 128, 1).is_err());
+    }
+}
+
+#[cfg(test)]
+mod picking_tests {
+    use crate::draw_queue_2d::DrawQueue2D;
+    use crate::picking::{PickId, pick_color, pick_from_color};
+    use crate::shapes_2d::Rect;
+    use crate::testing::{DrawCall2D, MockDrawTarget2D};
+    use crate::textures::TextureRef;
+    use crate::{color::Color, prelude::Transform2D};
+    use bevy_math::{Mat4, Vec2};
+
+    fn read_back(color: [f32; 4]) -> Option<PickId> {
+        pick_from_color(color.map(|channel| (channel * 255.0).round() as u8))
+    }
+
+    #[test]
+    fn test_pick_ids_survive_being_colors() {
+        for id in [0, 1, 255, 256, 70_000, (1 << 24) - 2] {
+            assert_eq!(read_back(pick_color(Some(PickId(id)))), Some(PickId(id)));
+        }
+        assert_eq!(read_back(pick_color(None)), None);
+        assert_eq!(pick_from_color([0, 0, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_for_picking_colors_each_draw_by_its_id() {
+        let mut queue = DrawQueue2D::empty();
+        queue.set_pick_id(Some(PickId(7)));
+        queue.add_shape(&Rect {
+            top_left: Vec2::ZERO,
+            size: Vec2::ONE,
+            color: Color::RED_500,
+        });
+        queue.add_sprite(
+            TextureRef::from_raw(0, 0),
+            Transform2D::IDENTITY,
+            Color::RED_500,
+            None,
+        );
+        queue.set_pick_id(None);
+        queue.add_circle(Vec2::ZERO, Vec2::ONE, Color::RED_500);
+
+        let mut target = MockDrawTarget2D::default();
+        queue.for_picking().draw_to(&mut target, &Mat4::IDENTITY);
+
+        for call in &target.calls {
+            match call {
+                DrawCall2D::Shapes { vertices, .. } => {
+                    assert!(
+                        vertices
+                            .iter()
+                            .all(|v| read_back(v.color) == Some(PickId(7)))
+                    );
+                }
+                DrawCall2D::Sprites { vertices, .. } => {
+                    assert!(
+                        vertices
+                            .iter()
+                            .all(|v| read_back(v.color) == Some(PickId(7)))
+                    );
+                }
+                DrawCall2D::Circles { instances } => {
+                    assert_eq!(read_back(instances[0].fill_color), None);
+                }
+                _ => {}
+            }
+        }
+        assert_eq!(target.calls.len(), 3);
+    }
+}
+
+#[cfg(test)]
+mod pbr_material_tests {
+    use crate::materials::{Material, UniformData};
+    use crate::programs::PBR_3D_PROGRAM;
+    use crate::textures::TextureRef;
+
+    #[test]
+    fn texture_slots_turn_on_their_flag() {
+        let material = Material::pbr().with_metallic(0.8).with_roughness(0.3);
+        assert!(material.program == PBR_3D_PROGRAM);
+        assert!(material.get_uniform("metallic") == Some(UniformData::Float(0.8)));
+        assert!(material.get_uniform("roughness") == Some(UniformData::Float(0.3)));
+        assert!(material.get_uniform("use_normal_map") == Some(UniformData::Float(0.0)));
+
+        let material = material.with_normal_map(TextureRef::from_raw(0, 0));
+        assert!(material.get_uniform("use_normal_map") == Some(UniformData::Float(1.0)));
+        assert!(
+            material.get_uniform("normal_map")
+                == Some(UniformData::Texture(TextureRef::from_raw(0, 0)))
+        );
+    }
+}
+
+#[cfg(test)]
+mod normal_mapping_tests {
+    use crate::draw_queue_2d::MaterialVertex3D;
+    use crate::materials::Material;
+    use crate::object_3d::generate_tangents;
+    use crate::programs::GOURAUD_3D_PROGRAM;
+    use crate::textures::TextureRef;
+
+    fn quad(flip_v: bool) -> Vec<MaterialVertex3D> {
+        [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]
+            .into_iter()
+            .map(|[x, y]| MaterialVertex3D {
+                position: [x, y, 0.0],
+                normal: [0.0, 0.0, 1.0],
+                tex_coords: [x, if flip_v { y } else { 1.0 - y }],
+                tangent: [0.0; 4],
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tangents_follow_u_and_bitangents_point_up_the_image() {
+        let indices = [0, 1, 2, 0, 2, 3];
+
+        // the usual layout: the top of the image is at +Y
+        let mut vertices = quad(false);
+        generate_tangents(&mut vertices, &indices);
+        for vertex in &vertices {
+            assert_eq!(vertex.tangent, [1.0, 0.0, 0.0, 1.0]);
+        }
+
+        // mirrored vertically, so the bitangent has to flip to keep pointing up the image
+        let mut vertices = quad(true);
+        generate_tangents(&mut vertices, &indices);
+        for vertex in &vertices {
+            assert_eq!(vertex.tangent, [1.0, 0.0, 0.0, -1.0]);
+        }
+    }
+
+    #[test]
+    fn normal_mapping_can_be_toggled_without_losing_the_map() {
+        let mut material =
+            Material::new(GOURAUD_3D_PROGRAM).with_normal_map(TextureRef::from_raw(3, 0));
+        assert!(material.normal_mapping());
+
+        material.set_normal_scale(0.5);
+        material.set_normal_mapping(false);
+        assert!(!material.normal_mapping());
+        material.set_normal_mapping(true);
+        assert!(material.normal_mapping());
+        assert!(
+            material.get_uniform("normal_scale") == Some(crate::materials::UniformData::Float(0.5))
+        );
+        assert!(material.get_uniform("normal_map").is_some());
+    }
+}
+
+#[cfg(test)]
+mod particle_tests {
+    use bevy_math::Vec3;
+
+    use crate::animation::Ease;
+    use crate::particles::{Curve, ParticleConfig};
+    use crate::particles_3d::{EmissionShape3D, ParticleEmitter3D};
+
+    #[test]
+    fn curves_pass_through_their_keys() {
+        let curve = Curve::linear(0.0, 1.0).with_key(0.5, 4.0, Ease::Linear);
+        assert_eq!(curve.sample(0.0), 0.0);
+        assert_eq!(curve.sample(0.25), 2.0);
+        assert_eq!(curve.sample(0.5), 4.0);
+        assert_eq!(curve.sample(0.75), 2.5);
+        assert_eq!(curve.sample(2.0), 1.0);
+        assert_eq!(Curve::constant(3.0).sample(0.7), 3.0);
+    }
+
+    #[test]
+    fn emitters_spawn_at_their_rate_and_particles_die_of_old_age() {
+        let config = ParticleConfig::default().with_lifetime(1.0, 1.0);
+        let mut emitter = ParticleEmitter3D::new(EmissionShape3D::Point)
+            .with_rate(10.0)
+            .with_config(config)
+            .with_seed(1);
+
+        for _ in 0..5 {
+            emitter.update(0.1);
+        }
+        assert_eq!(emitter.particle_count(), 5);
+
+        emitter.emitting = false;
+        emitter.update(0.95);
+        assert_eq!(emitter.particle_count(), 1);
+        emitter.update(0.1);
+        assert!(emitter.is_finished());
+
+        emitter.max_particles = 3;
+        emitter.burst(10);
+        assert_eq!(emitter.particle_count(), 3);
+    }
+
+    #[test]
+    fn cones_emit_within_their_angle() {
+        let direction = Vec3::new(1.0, 1.0, 0.0).normalize();
+        let angle = 0.3;
+        let config = ParticleConfig::default().with_speed(1.0, 1.0);
+        let mut emitter = ParticleEmitter3D::new(EmissionShape3D::Cone {
+            direction,
+            angle,
+            radius: 0.0,
+        })
+        .with_config(config)
+        .with_rate(0.0)
+        .with_seed(2);
+        emitter.burst(200);
+        emitter.update(1.0);
+
+        for instance in emitter.instances() {
+            let moved = Vec3::from(instance.center);
+            assert!(moved.angle_between(direction) <= angle + 1e-3);
+        }
+    }
+}
+
+#[cfg(test)]
+mod fog_tests {
+    use crate::atmosphere::Fog;
+    use crate::color::Color;
+
+    #[test]
+    fn fog_thickens_with_distance() {
+        let fog = Fog::linear(Color::WHITE, 10.0, 20.0);
+        assert_eq!(fog.amount(5.0), 0.0);
+        assert_eq!(fog.amount(15.0), 0.5);
+        assert_eq!(fog.amount(100.0), 1.0);
+
+        let fog = Fog::exponential(Color::WHITE.with_alpha(0.5), 0.1);
+        assert_eq!(fog.amount(0.0), 0.0);
+        assert!(fog.amount(10.0) < fog.amount(20.0));
+        assert!((fog.amount(1000.0) - 0.5).abs() < 1e-4);
+
+        // squared fog stays clearer up close, then catches up
+        let squared = Fog::exponential_squared(Color::WHITE, 0.1);
+        let plain = Fog::exponential(Color::WHITE, 0.1);
+        assert!(squared.amount(2.0) < plain.amount(2.0));
+        assert!(squared.amount(20.0) > plain.amount(20.0));
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "debugging")]
+mod debug_render_mode_tests {
+    use crate::debugging::DebugRenderMode;
+    use crate::debugging::render_mode::DebugRenderModes;
+    use crate::object_3d::Object3DRef;
+
+    #[test]
+    fn objects_follow_the_global_mode_unless_overridden() {
+        let mut modes = DebugRenderModes::default();
+        let (a, b) = (Object3DRef::from_raw(0, 0), Object3DRef::from_raw(1, 0));
+
+        modes.global = DebugRenderMode::Wireframe;
+        modes.set(a, Some(DebugRenderMode::Normals));
+        assert_eq!(modes.mode_for(a), DebugRenderMode::Normals);
+        assert_eq!(modes.mode_for(b), DebugRenderMode::Wireframe);
+
+        modes.set(a, None);
+        assert_eq!(modes.mode_for(a), DebugRenderMode::Wireframe);
+    }
+}
+
+#[cfg(test)]
+mod stream_buffer_tests {
+    use crate::buffers::Placement;
+
+    #[test]
+    fn writes_append_then_wrap_then_grow() {
+        assert_eq!(
+            Placement::plan(0, 0, 10),
+            Placement::Grow {
+                capacity: 4096,
+                range: 0..10
+            }
+        );
+        assert_eq!(Placement::plan(4096, 10, 20), Placement::Append(10..30));
+        assert_eq!(Placement::plan(4096, 4090, 20), Placement::Orphan(0..20));
+        assert_eq!(
+            Placement::plan(4096, 100, 5000),
+            Placement::Grow {
+                capacity: 8192,
+                range: 0..5000
+            }
+        );
+    }
+}
+
+#[cfg(test)]
+mod frame_arena_tests {
+    use crate::color::Color;
+    use crate::shapes_2d::*;
+    use bevy_math::Vec2;
+    use bumpalo::Bump;
+
+    #[test]
+    fn arena_points_match_heap_points() {
+        let arena = Bump::new();
+        let line = Line {
+            start: Vec2::ZERO,
+            end: Vec2::new(10.0, 5.0),
+            thickness: 2.0,
+            color: Color::WHITE,
+        };
+        let (indices, vertices) = line.points(4);
+        let (arena_indices, arena_vertices) = line.points_in(4, &arena);
+        assert_eq!(indices, &arena_indices[..]);
+        assert_eq!(vertices.len(), arena_vertices.len());
+        for (a, b) in vertices.iter().zip(arena_vertices.iter()) {
+            assert_eq!(a.position, b.position);
+        }
+
+        // shapes without their own version fall back to copying `points`
+        let poly = Poly {
+            sides: 5,
+            radius: 3.0,
+            center: Vec2::ZERO,
+            rotation: 0.0,
+            color: Color::WHITE,
+        };
+        assert_eq!(poly.points(0).0, &poly.points_in(0, &arena).0[..]);
+    }
+}
+
+#[cfg(test)]
+mod draw_commands_tests {
+    use crate::color::Color;
+    use crate::draw_queue_2d::{DrawCommands2D, DrawQueue2D};
+    use crate::shapes_2d::Rect;
+    use crate::testing::{DrawCall2D, MockDrawTarget2D};
+    use bevy_math::{Mat4, Vec2};
+
+    fn rect(x: f32) -> Rect {
+        Rect {
+            top_left: Vec2::new(x, 0.0),
+            size: Vec2::ONE,
+            color: Color::WHITE,
+        }
+    }
+
+    #[test]
+    fn appended_commands_draw_on_top_in_order() {
+        let mut queue = DrawQueue2D::empty();
+        queue.add_shape(&rect(0.0));
+
+        let commands = DrawCommands2D::record_parallel(&[1.0, 2.0, 3.0], |&x, commands| {
+            commands.draw(&rect(x));
+        });
+        for commands in commands {
+            queue.append(commands.into_queue());
+        }
+        queue.add_shape(&rect(4.0));
+
+        let mut target = MockDrawTarget2D::default();
+        queue.draw_to(&mut target, &Mat4::IDENTITY);
+
+        let DrawCall2D::Shapes { vertices, indices } = &target.calls[0] else {
+            panic!("expected shapes");
+        };
+        assert_eq!(vertices.len(), 20);
+        assert_eq!(*indices.iter().max().unwrap(), 19);
+
+        // each rect is further along in x and z than the one before it
+        for pair in vertices.chunks(4).collect::<Vec<_>>().windows(2) {
+            assert!(pair[0][0].position[0] < pair[1][0].position[0]);
+            assert!(pair[0][0].position[2] < pair[1][0].position[2]);
+        }
+    }
+
+    #[test]
+    fn commands_go_inside_the_current_clip_rect() {
+        let mut queue = DrawQueue2D::empty();
+        let clip = bevy_math::Rect::new(0.0, 0.0, 10.0, 10.0);
+        queue.push_clip_rect(clip);
+
+        let mut commands = DrawCommands2D::new();
+        commands.draw(&rect(1.0));
+        queue.append(commands.into_queue());
+        queue.pop_clip_rect();
+
+        let mut target = MockDrawTarget2D::default();
+        queue.draw_to(&mut target, &Mat4::IDENTITY);
+        assert_eq!(target.calls.len(), 1);
+        assert_eq!(target.clips, vec![Some(clip)]);
+    }
+}
+
+#[cfg(test)]
+mod engine_error_tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::error::{
+        EngineError, OrReport, report_error, reset_error_handler, set_error_handler,
+    };
+    use crate::image::Image;
+
+    #[test]
+    fn wrong_sized_images_say_how_big_they_should_be() {
+        let error = Image::from_bytes(2, 2, vec![0; 12]).err().unwrap();
+        assert!(matches!(
+            error,
+            EngineError::ImageSize {
+                expected: 16,
+                actual: 12
+            }
+        ));
+
+        // still works with `?` in a function returning `anyhow::Result`
+        let error: anyhow::Error = error.into();
+        assert_eq!(error.to_string(), "image should be 16 bytes, but it's 12");
+    }
+
+    #[test]
+    fn loading_without_the_engine_is_an_error() {
+        // another test might have started the engine on its own thread
+        let result = crate::textures::load_texture(&[], ::image::ImageFormat::Png);
+        assert!(matches!(
+            result,
+            Err(EngineError::NotInitialized | EngineError::WrongThread)
+        ));
+        let result = crate::text_rendering::load_font(&[]);
+        assert!(matches!(
+            result,
+            Err(EngineError::NotInitialized | EngineError::WrongThread)
+        ));
+    }
+
+    // one test, since the handler is global and tests run in parallel
+    #[test]
+    fn reported_errors_go_to_the_handler_or_the_log() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let handler_reported = reported.clone();
+        set_error_handler(move |error| {
+            // other tests can report errors while this one's handler is set
+            if !matches!(
+                error,
+                EngineError::Font(_) | EngineError::StaleRef("TextureRef")
+            ) {
+                return;
+            }
+            handler_reported.lock().unwrap().push(error.to_string());
+            // reporting from inside the handler doesn't deadlock
+            if let EngineError::Font(_) = error {
+                report_error(EngineError::StaleRef("TextureRef"));
+            }
+        });
+
+        let result: Result<(), EngineError> = Err(EngineError::Font("bad bytes".to_string()));
+        assert_eq!(result.or_report(), None);
+        reset_error_handler();
+
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![
+                "couldn't load font: bad bytes".to_string(),
+                "TextureRef points to something that's been removed".to_string(),
+            ]
+        );
+
+        // without a handler it's only logged
+        let result: Result<(), EngineError> = Err(EngineError::NotInitialized);
+        assert_eq!(result.or_report(), None);
+    }
+}
+
+#[cfg(test)]
+mod slots_tests {
+    use crate::utils::slots::Slots;
+
+    #[test]
+    fn removed_values_cant_be_reached_through_old_handles() {
+        let mut slots = Slots::new();
+        let (index, generation) = slots.insert("guy");
+
+        assert_eq!(slots.remove(index, generation), Some("guy"));
+        assert_eq!(slots.get(index, generation), None);
+        assert_eq!(slots.remove(index, generation), None);
+
+        // the slot is reused, but the old handle still doesn't see it
+        let (new_index, new_generation) = slots.insert("pasta");
+        assert_eq!(new_index, index);
+        assert_ne!(new_generation, generation);
+        assert_eq!(slots.get(index, generation), None);
+        assert_eq!(slots.get(new_index, new_generation), Some(&"pasta"));
+        assert_eq!(slots.len(), 1);
+    }
+
+    #[test]
+    fn iterating_skips_removed_values() {
+        let mut slots = Slots::new();
+        let a = slots.insert(1);
+        let b = slots.insert(2);
+        slots.insert(3);
+        slots.remove(b.0, b.1);
+
+        let values: Vec<_> = slots.iter_mut().map(|(_, _, value)| *value).collect();
+        assert_eq!(values, vec![1, 3]);
+        assert!(slots.contains(a.0, a.1));
+        assert!(!slots.contains(b.0, b.1));
+        assert!(!slots.contains(10, 0));
+    }
+
+    #[test]
+    fn builtins_are_the_values_in_when_marked() {
+        let mut slots = Slots::new();
+        let builtin = slots.insert("default font");
+        slots.mark_builtin();
+        let added = slots.insert("game font");
+
+        assert!(slots.is_builtin(builtin.0));
+        assert!(!slots.is_builtin(added.0));
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_tests {
+    use bevy_math::{BVec2, Vec2};
+    use glium::{
+        uniforms::{MagnifySamplerFilter, MinifySamplerFilter},
+        winit::keyboard::KeyCode,
+    };
+    use serde::{Serialize, de::DeserializeOwned};
+
+    use crate::{
+        color::Color,
+        config::EngineConfig,
+        input_handling::Button,
+        shapes_2d::{LineCap, Polyline},
+        storage::format,
+        transform::Transform2D,
+    };
+
+    fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+        format::from_bytes(&format::to_bytes(value).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn transforms_come_back_with_a_working_matrix() {
+        let mut transform = Transform2D::from_translation(Vec2::new(3.0, 4.0))
+            .with_rotation(1.0)
+            .with_scale(Vec2::splat(2.0))
+            .with_mirror(BVec2::new(true, false));
+
+        let mut loaded = round_trip(&transform);
+        assert_eq!(loaded.translation(), transform.translation());
+        assert_eq!(loaded.mirror(), transform.mirror());
+        assert_eq!(loaded.matrix(), transform.matrix());
+    }
+
+    #[test]
+    fn shapes_keep_their_colors_and_styles() {
+        let line = Polyline::new(vec![Vec2::ZERO, Vec2::X, Vec2::ONE], 2.0, Color::RED_500)
+            .with_cap(LineCap::Round);
+
+        let loaded = round_trip(&line);
+        assert_eq!(loaded.points, line.points);
+        assert_eq!(loaded.color, Color::RED_500);
+        assert_eq!(loaded.cap, LineCap::Round);
+    }
+
+    #[test]
+    fn config_keeps_filters_and_key_binds() {
+        let mut config = EngineConfig {
+            default_magnify_filter: MagnifySamplerFilter::Linear,
+            default_minify_filter: MinifySamplerFilter::NearestMipmapLinear,
+            ..Default::default()
+        };
+        config
+            .key_binds
+            .insert("jump".to_string(), Button::Keyboard(KeyCode::Space));
+
+        let loaded = round_trip(&config);
+        assert_eq!(loaded.default_magnify_filter, MagnifySamplerFilter::Linear);
+        assert_eq!(
+            loaded.default_minify_filter,
+            MinifySamplerFilter::NearestMipmapLinear
+        );
+        assert_eq!(loaded.key_binds, config.key_binds);
+    }
+}
+
+#[cfg(test)]
+mod net_tests {
+    use std::{
+        net::UdpSocket,
+        time::{Duration, Instant},
+    };
+
+    use crate::net::{
+        Channel, Lockstep, MAX_MESSAGE_SIZE, NetEvent, NetSocket, PeerId, Snapshot,
+        SnapshotReceiver, SnapshotSender,
+        channel::{ChannelState, RECEIVE_WINDOW},
+        packet::Packet,
+    };
+
+    #[test]
+    fn ordered_messages_wait_for_the_gap_to_fill() {
+        let mut channel = ChannelState::new(Channel::ReliableOrdered);
+
+        assert_eq!(channel.receive(1, vec![1]), Some(vec![]));
+        assert_eq!(channel.receive(2, vec![2]), Some(vec![]));
+        assert_eq!(
+            channel.receive(0, vec![0]),
+            Some(vec![vec![0], vec![1], vec![2]])
+        );
+        // a resend of something already delivered
+        assert_eq!(channel.receive(1, vec![1]), Some(vec![]));
+    }
+
+    #[test]
+    fn sequenced_messages_drop_anything_older() {
+        let mut channel = ChannelState::new(Channel::Sequenced);
+
+        assert_eq!(channel.receive(3, vec![3]), Some(vec![vec![3]]));
+        assert_eq!(channel.receive(1, vec![1]), Some(vec![]));
+        assert_eq!(channel.receive(4, vec![4]), Some(vec![vec![4]]));
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        let mut channel = ChannelState::starting_at(Channel::Sequenced, u32::MAX - 1);
+        assert_eq!(channel.receive(u32::MAX, vec![1]), Some(vec![vec![1]]));
+        assert_eq!(channel.receive(0, vec![2]), Some(vec![vec![2]]));
+        assert_eq!(channel.receive(u32::MAX, vec![1]), Some(vec![]));
+
+        let mut channel = ChannelState::starting_at(Channel::ReliableOrdered, u32::MAX);
+        assert_eq!(channel.receive(0, vec![2]), Some(vec![]));
+        assert_eq!(
+            channel.receive(u32::MAX, vec![1]),
+            Some(vec![vec![1], vec![2]])
+        );
+        assert_eq!(channel.receive(1, vec![3]), Some(vec![vec![3]]));
+
+        let mut channel = ChannelState::starting_at(Channel::Reliable, u32::MAX);
+        let sent = channel.send(b"a", Instant::now());
+        assert_eq!(sent, u32::MAX);
+        assert_eq!(channel.send(b"b", Instant::now()), 0);
+    }
+
+    #[test]
+    fn hostile_sequence_numbers_are_ignored() {
+        let mut channel = ChannelState::new(Channel::Sequenced);
+        // would overflow `next_expected`, and is behind it anyway
+        assert_eq!(channel.receive(u32::MAX, vec![1]), Some(vec![]));
+        assert_eq!(channel.receive(RECEIVE_WINDOW, vec![1]), None);
+        assert_eq!(channel.receive(u32::MAX / 2, vec![1]), None);
+        assert_eq!(channel.receive(0, vec![0]), Some(vec![vec![0]]));
+
+        // only a window's worth is held on to while waiting for the gap
+        let mut channel = ChannelState::new(Channel::ReliableOrdered);
+        for sequence in 1..RECEIVE_WINDOW {
+            assert_eq!(channel.receive(sequence, vec![]), Some(vec![]));
+        }
+        for sequence in RECEIVE_WINDOW..RECEIVE_WINDOW * 4 {
+            assert_eq!(channel.receive(sequence, vec![]), None);
+        }
+        assert_eq!(
+            channel.receive(0, vec![]).map(|delivered| delivered.len()),
+            Some(RECEIVE_WINDOW as usize)
+        );
+        // and the window moves along with what's been delivered
+        assert_eq!(channel.receive(RECEIVE_WINDOW, vec![]), Some(vec![vec![]]));
+    }
+
+    #[test]
+    fn reliable_messages_are_resent_until_acked() {
+        let mut channel = ChannelState::new(Channel::Reliable);
+        let start = Instant::now();
+        let resend_after = Duration::from_millis(200);
+
+        let first = channel.send(b"a", start);
+        let second = channel.send(b"b", start);
+        assert!(channel.due_for_resend(start, resend_after).is_empty());
+
+        channel.ack(first);
+        let later = start + resend_after;
+        assert_eq!(
+            channel.due_for_resend(later, resend_after),
+            vec![(second, b"b".to_vec())]
+        );
+        // and not again straight away
+        assert!(channel.due_for_resend(later, resend_after).is_empty());
+    }
+
+    #[test]
+    fn packets_survive_encoding_and_strangers_are_ignored() {
+        let packet = Packet::Message {
+            channel: Channel::ReliableOrdered,
+            sequence: 70_000,
+            payload: b"hello".to_vec(),
+        };
+        assert_eq!(Packet::decode(&packet.encode()), Some(packet));
+        assert_eq!(Packet::decode(b"GET / HTTP/1.1"), None);
+        assert_eq!(
+            Packet::decode(
+                &Packet::Ack {
+                    channel: Channel::Reliable,
+                    sequence: 1
+                }
+                .encode()[..6]
+            ),
+            None
+        );
+    }
+
+    /// Polls both until `done` says so, or gives up after a second.
+    fn poll_until(
+        a: &mut NetSocket,
+        b: &mut NetSocket,
+        mut done: impl FnMut(&[NetEvent], &[NetEvent]) -> bool,
+    ) {
+        let start = Instant::now();
+        let (mut a_events, mut b_events) = (vec![], vec![]);
+        while start.elapsed() < Duration::from_secs(1) {
+            a_events.extend(a.poll_messages());
+            b_events.extend(b.poll_messages());
+            if done(&a_events, &b_events) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        panic!("gave up waiting: {a_events:?} {b_events:?}");
+    }
+
+    fn connected(events: &[NetEvent]) -> Option<PeerId> {
+        events.iter().find_map(|event| match event {
+            NetEvent::Connected(peer) => Some(*peer),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn clients_connect_and_exchange_messages() {
+        let Ok(mut host) = NetSocket::host("127.0.0.1:0") else {
+            // no loopback to test on
+            return;
+        };
+        let mut client = NetSocket::connect(host.local_addr().unwrap()).unwrap();
+
+        let (mut host_side, mut client_side) = (None, None);
+        poll_until(&mut host, &mut client, |h, c| {
+            host_side = connected(h);
+            client_side = connected(c);
+            host_side.is_some() && client_side.is_some()
+        });
+        let (host_side, client_side) = (host_side.unwrap(), client_side.unwrap());
+
+        for i in 0..5u32 {
+            client
+                .send_value(client_side, Channel::ReliableOrdered, &i)
+                .unwrap();
+        }
+
+        let mut received = vec![];
+        poll_until(&mut host, &mut client, |h, _| {
+            received = h.iter().filter_map(|e| e.read::<u32>()?.ok()).collect();
+            received.len() == 5
+        });
+        assert_eq!(received, vec![0, 1, 2, 3, 4]);
+
+        host.disconnect(host_side);
+        poll_until(&mut host, &mut client, |_, c| {
+            c.contains(&NetEvent::Disconnected(client_side))
+        });
+        assert_eq!(client.peers().count(), 0);
+    }
+
+    #[test]
+    fn oversized_and_far_ahead_datagrams_are_rejected() {
+        let Ok(mut host) = NetSocket::host("127.0.0.1:0") else {
+            return;
+        };
+        let addr = host.local_addr().unwrap();
+        let raw = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let send = |packet: Packet| raw.send_to(&packet.encode(), addr).unwrap();
+        let message = |channel, sequence, payload| Packet::Message {
+            channel,
+            sequence,
+            payload,
+        };
+
+        let mut events = vec![];
+        let mut poll_until = |done: &dyn Fn(&[NetEvent]) -> bool| {
+            let start = Instant::now();
+            while !done(&events) && start.elapsed() < Duration::from_secs(1) {
+                events.extend(host.poll_messages());
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            assert!(done(&events), "gave up waiting: {events:?}");
+        };
+
+        send(Packet::Connect);
+        poll_until(&|events| connected(events).is_some());
+
+        send(message(
+            Channel::Unreliable,
+            0,
+            vec![0; MAX_MESSAGE_SIZE + 1],
+        ));
+        send(message(Channel::Unreliable, 1, b"ok".to_vec()));
+        poll_until(&|events| events.iter().any(|e| matches!(e, NetEvent::Message { .. })));
+
+        send(message(Channel::Sequenced, u32::MAX / 2, vec![]));
+        poll_until(&|events| {
+            events
+                .iter()
+                .any(|e| matches!(e, NetEvent::Disconnected(_)))
+        });
+
+        let messages: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                NetEvent::Message { bytes, .. } => Some(bytes.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(messages, vec![b"ok".to_vec()]);
+    }
+
+    #[test]
+    fn lockstep_ignores_input_for_ticks_nobody_could_have_reached() {
+        let lockstep = Lockstep::<u32>::new(0, 2).with_input_delay(3);
+        assert!(lockstep.accepts_tick(0));
+        // a peer at most 4 ticks ahead, sending 3 ticks past that
+        assert!(lockstep.accepts_tick(7));
+        assert!(!lockstep.accepts_tick(8));
+        assert!(!lockstep.accepts_tick(u32::MAX));
+
+        let lockstep = Lockstep::<u32>::new(0, 2).with_input_delay(u32::MAX);
+        assert!(lockstep.accepts_tick(u32::MAX));
+    }
+
+    #[test]
+    fn snapshot_deltas_only_carry_what_changed() {
+        let mut base = Snapshot::new(1);
+        base.set("score", &10u32).unwrap();
+        base.set("positions", &vec![1.0f32; 64]).unwrap();
+        base.set("gone", "soon").unwrap();
+
+        let mut next = base.clone();
+        next.tick = 2;
+        let mut positions = vec![1.0f32; 64];
+        positions[20] = 2.0;
+        next.set("positions", &positions).unwrap();
+        next.set("new", &true).unwrap();
+        next.remove("gone");
+
+        let delta = next.delta_from(Some(&base));
+        assert_eq!(delta.base(), Some(1));
+        assert_eq!(delta.apply(Some(&base)), Some(next.clone()));
+        assert_eq!(delta.apply(None), None);
+
+        let full = next.delta_from(None);
+        let size = |delta| crate::storage::format::to_bytes(&delta).unwrap().len();
+        assert!(size(delta) < size(full.clone()) / 2);
+        assert_eq!(full.apply(None), Some(next));
+    }
+
+    #[test]
+    fn snapshots_and_lockstep_inputs_reach_the_other_side() {
+        let Ok(mut host) = NetSocket::host("127.0.0.1:0") else {
+            return;
+        };
+        let mut client = NetSocket::connect(host.local_addr().unwrap()).unwrap();
+        poll_until(&mut host, &mut client, |h, c| {
+            connected(h).is_some() && connected(c).is_some()
+        });
+
+        let mut sender = SnapshotSender::new();
+        let mut receiver = SnapshotReceiver::new();
+        let mut host_lockstep = Lockstep::<u32>::new(0, 2);
+        let mut client_lockstep = Lockstep::<u32>::new(1, 2);
+        host_lockstep.set_input(7);
+        client_lockstep.set_input(9);
+
+        let (mut host_ticks, mut client_ticks) = (vec![], vec![]);
+        let start = Instant::now();
+        while (host_ticks.len() < 10 || client_ticks.len() < 10 || receiver.latest().is_none())
+            && start.elapsed() < Duration::from_secs(2)
+        {
+            for event in host.poll_messages() {
+                sender.receive(&event);
+                host_lockstep.receive(&mut host, &event);
+            }
+            for event in client.poll_messages() {
+                receiver.receive(&mut client, &event);
+                client_lockstep.receive(&mut client, &event);
+            }
+
+            let length = host_lockstep.tick_length();
+            host_ticks.extend(host_lockstep.advance(&mut host, length).unwrap());
+            client_ticks.extend(client_lockstep.advance(&mut client, length).unwrap());
+
+            let mut snapshot = Snapshot::new(host_lockstep.tick());
+            snapshot.set("tick", &host_lockstep.tick()).unwrap();
+            sender.send(&mut host, snapshot).unwrap();
+
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let latest = receiver.latest().unwrap();
+        assert_eq!(latest.get::<u32>("tick").unwrap().unwrap(), latest.tick);
+
+        for ticks in [&host_ticks, &client_ticks] {
+            for tick in &ticks[..10] {
+                let expected = if tick.tick < 3 {
+                    vec![0, 0]
+                } else {
+                    vec![7, 9]
+                };
+                assert_eq!(tick.inputs, expected);
+            }
+        }
+        assert_eq!(host_ticks[..10], client_ticks[..10]);
+    }
+}
+
+#[cfg(test)]
+mod pathfinding_tests {
+    use bevy_math::{UVec2, Vec2};
+
+    use crate::collisions::{AABB2D, Polygon, boolean::BooleanOps};
+    use crate::pathfinding::{NavGrid, NavMesh, Neighborhood};
+    use crate::physics::{ColliderBuilder, PhysicsWorld, vector};
+
+    fn square(center: Vec2, half_size: f32) -> Polygon {
+        Polygon {
+            vertices: vec![
+                center + Vec2::new(-half_size, -half_size),
+                center + Vec2::new(half_size, -half_size),
+                center + Vec2::new(half_size, half_size),
+                center + Vec2::new(-half_size, half_size),
+            ],
+        }
+    }
+
+    /// Whether the segment crosses into the square, checked by stepping along it.
+    fn crosses(a: Vec2, b: Vec2, center: Vec2, half_size: f32) -> bool {
+        (0..=100).any(|i| {
+            let point = a.lerp(b, i as f32 / 100.0) - center;
+            point.x.abs() < half_size - 0.01 && point.y.abs() < half_size - 0.01
+        })
+    }
+
+    #[test]
+    fn open_grids_give_a_straight_line() {
+        let grid = NavGrid::new(UVec2::new(10, 10), 1.0);
+        let path = grid
+            .find_path(Vec2::new(0.5, 0.5), Vec2::new(9.5, 6.5))
+            .unwrap();
+        assert_eq!(path, vec![Vec2::new(0.5, 0.5), Vec2::new(9.5, 6.5)]);
+    }
+
+    #[test]
+    fn grid_paths_go_around_walls() {
+        let mut grid = NavGrid::new(UVec2::new(10, 10), 1.0);
+        for y in 0..9 {
+            grid.set_blocked(UVec2::new(5, y));
+        }
+
+        let search = grid.search(Vec2::new(1.5, 1.5), Vec2::new(8.5, 1.5));
+        let path = search.path.unwrap();
+        assert!(path.iter().any(|point| point.y > 9.0));
+        for pair in path.windows(2) {
+            assert!(!crosses(pair[0], pair[1], Vec2::new(5.5, 4.5), 0.5));
+        }
+        assert!(!search.explored.is_empty());
+
+        grid.set_blocked(UVec2::new(5, 9));
+        assert_eq!(
+            grid.find_path(Vec2::new(1.5, 1.5), Vec2::new(8.5, 1.5)),
+            None
+        );
+        assert_eq!(
+            grid.find_path(Vec2::new(1.5, 1.5), Vec2::new(5.5, 1.5)),
+            None
+        );
+        assert_eq!(
+            grid.find_path(Vec2::new(-1.0, 1.5), Vec2::new(2.5, 1.5)),
+            None
+        );
+    }
+
+    #[test]
+    fn expensive_tiles_are_avoided_unless_worth_it() {
+        // a swamp down the middle, with a dry way round at the top
+        let swamp = |cost| {
+            NavGrid::from_fn(UVec2::new(9, 5), 1.0, move |cell| {
+                Some(if cell.x == 4 && cell.y < 4 { cost } else { 1.0 })
+            })
+            .with_neighborhood(Neighborhood::Four)
+        };
+
+        let around = swamp(10.0)
+            .find_path(Vec2::new(0.5, 0.5), Vec2::new(8.5, 0.5))
+            .unwrap();
+        assert!(around.iter().any(|point| point.y > 4.0));
+
+        let through = swamp(1.5)
+            .find_path(Vec2::new(0.5, 0.5), Vec2::new(8.5, 0.5))
+            .unwrap();
+        assert_eq!(through, vec![Vec2::new(0.5, 0.5), Vec2::new(8.5, 0.5)]);
+    }
+
+    #[test]
+    fn diagonals_dont_cut_blocked_corners() {
+        let mut grid = NavGrid::new(UVec2::new(2, 2), 1.0);
+        grid.set_blocked(UVec2::new(1, 0));
+        grid.set_blocked(UVec2::new(0, 1));
+        assert_eq!(
+            grid.find_path(Vec2::new(0.5, 0.5), Vec2::new(1.5, 1.5)),
+            None
+        );
+    }
+
+    #[test]
+    fn navmesh_paths_turn_at_the_obstacles_corners() {
+        let level = square(Vec2::ZERO, 10.0);
+        let walkable = level.difference(&square(Vec2::ZERO, 2.0));
+        let mesh = NavMesh::from_walkable(&walkable);
+        assert!(!mesh.contains_point(Vec2::ZERO));
+        assert!(mesh.contains_point(Vec2::new(5.0, 5.0)));
+
+        let start = Vec2::new(-8.0, 0.5);
+        let goal = Vec2::new(8.0, 0.5);
+        let path = mesh.find_path(start, goal).unwrap();
+
+        assert_eq!(path.len(), 4, "{path:?}");
+        assert_eq!(path[0], start);
+        assert_eq!(path[3], goal);
+        // around the top two corners
+        assert!(path[1].abs_diff_eq(Vec2::new(-2.0, 2.0), 1e-3));
+        assert!(path[2].abs_diff_eq(Vec2::new(2.0, 2.0), 1e-3));
+    }
+
+    #[test]
+    fn navmeshes_come_from_fixed_colliders() {
+        let mut world = PhysicsWorld::new();
+        world.insert_collider(
+            ColliderBuilder::cuboid(1.0, 3.0)
+                .translation(vector![0.0, 0.0])
+                .build(),
+        );
+        let bounds = AABB2D::new(Vec2::splat(-5.0), Vec2::splat(5.0));
+        let mesh = NavMesh::from_physics_world(&world, bounds, 0.5);
+
+        assert!(!mesh.contains_point(Vec2::new(1.2, 0.0)));
+        assert!(mesh.contains_point(Vec2::new(1.7, 0.0)));
+
+        let path = mesh
+            .find_path(Vec2::new(-4.0, 0.0), Vec2::new(4.0, 0.0))
+            .unwrap();
+        for pair in path.windows(2) {
+            assert!(!crosses(pair[0], pair[1], Vec2::ZERO, 1.5));
+        }
+        // off the mesh, so it starts from the nearest edge instead
+        let from_inside = mesh
+            .find_path(Vec2::new(0.5, 0.0), Vec2::new(4.0, 0.0))
+            .unwrap();
+        assert!(from_inside[0].abs_diff_eq(Vec2::new(1.5, 0.0), 1e-3));
+    }
+}
+
+#[cfg(test)]
+mod steering_tests {
+    use bevy_math::Vec2;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::steering::{self, Agent, PathFollower, Wander};
+
+    const DT: f32 = 1.0 / 60.0;
+
+    #[test]
+    fn agents_keep_to_their_limits() {
+        let mut agent = Agent::new(Vec2::ZERO, 5.0).with_max_force(10.0);
+        agent.apply(Vec2::new(1000.0, 0.0), 1.0);
+        assert_eq!(agent.velocity, Vec2::new(5.0, 0.0));
+
+        let mut agent = Agent::new(Vec2::ZERO, 5.0).with_max_force(10.0);
+        agent.apply(Vec2::new(1000.0, 0.0), 0.1);
+        assert!((agent.velocity.x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn seek_and_flee_point_opposite_ways() {
+        let agent = Agent::new(Vec2::ZERO, 2.0);
+        let target = Vec2::new(3.0, 4.0);
+        assert!(steering::seek(&agent, target).abs_diff_eq(Vec2::new(1.2, 1.6), 1e-5));
+        assert!(steering::flee(&agent, target).abs_diff_eq(Vec2::new(-1.2, -1.6), 1e-5));
+    }
+
+    #[test]
+    fn arriving_agents_stop_on_the_target() {
+        let target = Vec2::new(10.0, 0.0);
+        let mut agent = Agent::new(Vec2::ZERO, 5.0);
+        for _ in 0..600 {
+            agent.apply(steering::arrive(&agent, target, 3.0), DT);
+        }
+        assert!(agent.position.distance(target) < 0.05);
+        assert!(agent.velocity.length() < 0.05);
+    }
+
+    #[test]
+    fn wandering_is_the_same_for_the_same_seed() {
+        let run = || {
+            let mut rng = ChaCha8Rng::seed_from_u64(7);
+            let mut wander = Wander::default();
+            let mut agent = Agent::new(Vec2::ZERO, 3.0);
+            for _ in 0..120 {
+                agent.apply(wander.steer_with_rng(&agent, &mut rng), DT);
+            }
+            agent
+        };
+        let agent = run();
+        assert_eq!(agent, run());
+        assert!(agent.position.length() > 1.0);
+    }
+
+    #[test]
+    fn flocks_spread_out_and_line_up() {
+        let flock = [
+            Agent::new(Vec2::new(0.0, 0.0), 2.0).with_velocity(Vec2::new(1.0, 0.0)),
+            Agent::new(Vec2::new(0.5, 0.0), 2.0).with_velocity(Vec2::new(0.0, 1.0)),
+            Agent::new(Vec2::new(4.0, 0.0), 2.0).with_velocity(Vec2::new(0.0, 1.0)),
+        ];
+
+        // only the close one pushes, and itself is ignored
+        let away = steering::separation(&flock[0], &flock, 1.0);
+        assert!(away.x < 0.0);
+        assert_eq!(steering::separation(&flock[2], &flock, 1.0), Vec2::ZERO);
+
+        assert!(steering::cohesion(&flock[0], &flock, 10.0).x > 0.0);
+        let aligned = flock[0].velocity + steering::alignment(&flock[0], &flock, 10.0);
+        assert!(aligned.abs_diff_eq(Vec2::new(0.0, 2.0), 1e-5));
+    }
+
+    #[test]
+    fn path_followers_visit_each_point_then_stop() {
+        let path = vec![
+            Vec2::new(5.0, 0.0),
+            Vec2::new(5.0, 5.0),
+            Vec2::new(0.0, 5.0),
+        ];
+        let mut follower = PathFollower::new(path, 0.5);
+        let mut agent = Agent::new(Vec2::ZERO, 4.0);
+
+        let mut reached_corner = false;
+        for _ in 0..1200 {
+            agent.apply(follower.steer(&agent), DT);
+            reached_corner |= agent.position.distance(Vec2::new(5.0, 5.0)) < 0.6;
+        }
+
+        assert!(reached_corner);
+        assert!(follower.is_finished(&agent));
+        assert!(agent.position.distance(Vec2::new(0.0, 5.0)) < 0.1);
+        assert!(agent.velocity.length() < 0.1);
+    }
+}
+
+#[cfg(all(test, feature = "editor"))]
+mod editor_tests {
+    use crate::editor::{Scene, SceneObject, SceneShape};
+    use bevy_math::Vec2;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn scene() -> Scene {
+        let mut scene = Scene::new();
+        scene.add(
+            SceneObject::new(
+                "floor",
+                SceneShape::Rect {
+                    size: Vec2::new(10.0, 1.0),
+                },
+            )
+            .with_property("solid", "true"),
+        );
+        scene.add(
+            SceneObject::new("coin", SceneShape::Circle { radius: 0.5 })
+                .with_position(Vec2::new(2.0, 0.0)),
+        );
+        scene.add(
+            SceneObject::new("spawn", SceneShape::Marker)
+                .with_position(Vec2::new(2.0, 0.0))
+                .with_property("spawn", "player"),
+        );
+        scene
+    }
+
+    #[test]
+    fn scene_round_trips_through_bytes() {
+        let scene = scene();
+        let bytes = scene.to_bytes().unwrap();
+        assert_eq!(Scene::from_bytes(&bytes).unwrap(), scene);
+
+        assert!(Scene::from_bytes(b"nope").is_err());
+        let mut newer = bytes.clone();
+        newer[4] = 99;
+        assert!(Scene::from_bytes(&newer).is_err());
+    }
+
+    #[test]
+    fn contains_point_follows_rotation_and_scale() {
+        let object = SceneObject::new(
+            "wall",
+            SceneShape::Rect {
+                size: Vec2::new(4.0, 1.0),
+            },
+        )
+        .with_position(Vec2::new(10.0, 0.0))
+        .with_rotation(FRAC_PI_2)
+        .with_scale(Vec2::new(2.0, 1.0));
+
+        // 8 tall and 1 wide once turned on its side
+        assert!(object.contains_point(Vec2::new(10.0, 3.5)));
+        assert!(!object.contains_point(Vec2::new(13.5, 0.0)));
+        assert!(!object.contains_point(Vec2::new(10.0, 4.5)));
+        assert_eq!(object.outline().len(), 4);
+    }
+
+    #[test]
+    fn object_at_finds_the_topmost_shape() {
+        let scene = scene();
+        assert_eq!(scene.object_at(Vec2::new(2.0, 0.0)), Some(1));
+        assert_eq!(scene.object_at(Vec2::new(-4.0, 0.0)), Some(0));
+        assert_eq!(scene.object_at(Vec2::new(0.0, 5.0)), None);
+    }
+
+    #[test]
+    fn objects_are_found_by_name_and_property() {
+        let scene = scene();
+        assert_eq!(scene.find("coin").unwrap().position, Vec2::new(2.0, 0.0));
+        assert!(scene.find("door").is_none());
+
+        let spawns: Vec<_> = scene.with_property("spawn").collect();
+        assert_eq!(spawns.len(), 1);
+        assert_eq!(spawns[0].property("spawn"), Some("player"));
+    }
+}
+
+#[cfg(test)]
+mod flipbook_tests {
+    use crate::animation::{Flipbook, FlipbookClip, FlipbookPlayer};
+    use bevy_math::{Rect, UVec2, Vec2};
+
+    const FILE: &str = r#"
+image = "player.png"
+frame_size = [16, 16]
+
+[walk]
+frames = [4, 5, 6, 7]
+durations = [0.1, 0.2, 0.1, 0.2]
+
+[walk.events]
+footstep = [1, 3]
+
+[attack]
+frames = [8, 9]
+frame_time = 0.05
+looping = false
+"#;
+
+    #[test]
+    fn parses_clips_and_events() {
+        let flipbook = Flipbook::parse(FILE).unwrap();
+        assert_eq!(flipbook.image.as_deref(), Some("player.png"));
+        assert_eq!(flipbook.frame_size, UVec2::splat(16));
+
+        let walk = flipbook.clip("walk").unwrap();
+        assert!(walk.looping);
+        assert_eq!(walk.frames[2].cell, 6);
+        assert!((walk.duration() - 0.6).abs() < 1e-6);
+        assert_eq!(walk.frames[3].events, vec!["footstep".to_string()]);
+        assert!(walk.frames[0].events.is_empty());
+        assert_eq!(walk.frame_at(0.15), 1);
+        assert_eq!(walk.frame_at(0.65), 0);
+
+        let attack = flipbook.clip("attack").unwrap();
+        assert!(!attack.looping);
+        assert_eq!(attack.frames[1].duration, 0.05);
+        assert_eq!(attack.frame_at(10.0), 1);
+    }
+
+    #[test]
+    fn bad_files_are_rejected() {
+        assert!(Flipbook::parse("[walk]\nframes = [0]").is_err());
+        assert!(Flipbook::parse("frame_size = [8, 8]\n[walk]\nlooping = true").is_err());
+        assert!(
+            Flipbook::parse("frame_size = [8, 8]\n[walk]\nframes = [0, 1]\ndurations = [0.1]")
+                .is_err()
+        );
+        assert!(
+            Flipbook::parse("frame_size = [8, 8]\n[walk]\nframes = [0]\n[walk.events]\nstep = [3]")
+                .is_err()
+        );
+        assert!(Flipbook::parse("frame_size = [8, 8]\n[run.events]\nstep = [0]").is_err());
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let flipbook = Flipbook::parse(FILE).unwrap();
+        assert_eq!(Flipbook::parse(&flipbook.to_toml()).unwrap(), flipbook);
+
+        let built = Flipbook::new(UVec2::new(32, 16)).with_clip(
+            "idle",
+            FlipbookClip::new([0, 1, 2], 0.25).with_event(2, "blink"),
+        );
+        assert_eq!(Flipbook::parse(&built.to_toml()).unwrap(), built);
+    }
+
+    #[test]
+    fn player_sends_events_as_frames_come_up() {
+        let flipbook = Flipbook::parse(FILE).unwrap();
+        let mut player = FlipbookPlayer::new("walk");
+
+        assert!(player.advance(&flipbook, 0.05).is_empty());
+        assert_eq!(player.cell(&flipbook), Some(4));
+
+        let events = player.advance(&flipbook, 0.1);
+        assert_eq!(player.frame(), 1);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name, "footstep");
+        assert_eq!(events[0].frame, 1);
+
+        // past frames 2 and 3 and around to 0 in one go
+        let events = player.advance(&flipbook, 0.5);
+        assert_eq!(player.frame(), 0);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].frame, 3);
+
+        player.play("attack");
+        player.advance(&flipbook, 1.0);
+        assert!(player.is_finished());
+        assert_eq!(player.cell(&flipbook), Some(9));
+
+        player.play("attack");
+        assert!(player.is_finished());
+        player.play("missing");
+        assert!(player.advance(&flipbook, 1.0).is_empty());
+        assert_eq!(player.cell(&flipbook), None);
+    }
+
+    #[test]
+    fn regions_count_across_then_down() {
+        let flipbook = Flipbook::new(UVec2::splat(16));
+        let sheet = UVec2::new(64, 32);
+        assert_eq!(
+            flipbook.region(5, sheet),
+            Rect::from_corners(Vec2::new(16.0, 16.0), Vec2::new(32.0, 32.0))
+        );
+    }
+}
+
+#[cfg(test)]
+mod accessibility_tests {
+    use crate::color::Color;
+    use crate::config::EngineConfig;
+    use crate::post_processing::{ColorBlindness, ColorFilter};
+
+    #[test]
+    fn color_filters_parse_and_display() {
+        for blindness in ColorBlindness::ALL {
+            for filter in [
+                ColorFilter::Simulate(blindness),
+                ColorFilter::Correct(blindness),
+            ] {
+                assert_eq!(filter.to_string().parse::<ColorFilter>().unwrap(), filter);
+            }
+        }
+        assert_eq!(
+            "Correct-Protanopia".parse::<ColorFilter>().unwrap(),
+            ColorFilter::Correct(ColorBlindness::Protanopia)
+        );
+        assert!("simulate-blue".parse::<ColorFilter>().is_err());
+        assert!("deuteranopia".parse::<ColorFilter>().is_err());
+    }
+
+    #[test]
+    fn simulation_merges_red_and_green() {
+        let filter = ColorFilter::Simulate(ColorBlindness::Deuteranopia);
+        let gray = filter.apply(Color::from_rgba(0.5, 0.5, 0.5, 0.3));
+        assert!((gray.r - 0.5).abs() < 0.01 && (gray.g - 0.5).abs() < 0.01);
+        assert_eq!(gray.a, 0.3);
+
+        let red = filter.apply(Color::from_rgba(1.0, 0.0, 0.0, 1.0));
+        let green = filter.apply(Color::from_rgba(0.0, 1.0, 0.0, 1.0));
+        // both come out yellowish, with nearly the same amount of red and green
+        assert!((red.r - red.g).abs() < 0.15);
+        assert!((green.r - green.g).abs() < 0.15);
+    }
+
+    #[test]
+    fn correction_keeps_red_and_green_apart() {
+        let simulate = ColorFilter::Simulate(ColorBlindness::Protanopia);
+        let correct = ColorFilter::Correct(ColorBlindness::Protanopia);
+        let red = Color::from_rgba(0.8, 0.2, 0.1, 1.0);
+        let green = Color::from_rgba(0.3, 0.6, 0.1, 1.0);
+
+        let distance = |a: Color, b: Color| (a.to_vec4() - b.to_vec4()).length();
+        let uncorrected = distance(simulate.apply(red), simulate.apply(green));
+        let corrected = distance(
+            simulate.apply(correct.apply(red)),
+            simulate.apply(correct.apply(green)),
+        );
+        assert!(corrected > uncorrected);
+    }
+
+    #[test]
+    fn accessibility_settings_round_trip_through_config() {
+        let mut config = EngineConfig::default();
+        config
+            .read_toml("[accessibility]\nui_scale = 1.5\ncolor_filter = \"correct-tritanopia\"")
+            .unwrap();
+        assert_eq!(config.ui_scale, 1.5);
+        assert_eq!(
+            config.color_filter,
+            Some(ColorFilter::Correct(ColorBlindness::Tritanopia))
+        );
+
+        let mut reread = EngineConfig::default();
+        reread.read_toml(&config.to_toml()).unwrap();
+        assert_eq!(reread.ui_scale, 1.5);
+        assert_eq!(reread.color_filter, config.color_filter);
+
+        reread
+            .read_toml("[accessibility]\ncolor_filter = \"none\"")
+            .unwrap();
+        assert_eq!(reread.color_filter, None);
+        assert!(config.read_toml("[accessibility]\nui_scale = 0").is_err());
+    }
+}
+
+#[cfg(test)]
+mod trail_tests {
+    use crate::collisions::HasBounds2D;
+    use crate::particles::Curve;
+    use crate::shapes_2d::Shape2D;
+    use crate::trail::Trail;
+    use bevy_math::Vec2;
+
+    #[test]
+    fn adds_points_as_it_moves_and_drops_old_ones() {
+        let mut trail = Trail::new(0.5, 1.0).with_min_distance(1.0);
+        trail.update(Vec2::ZERO, 0.1);
+        trail.update(Vec2::new(0.5, 0.0), 0.1);
+        // not far enough yet, so the head just follows
+        assert_eq!(trail.len(), 2);
+        trail.update(Vec2::new(0.9, 0.0), 0.1);
+        assert_eq!(trail.len(), 2);
+        trail.update(Vec2::new(1.5, 0.0), 0.1);
+        assert_eq!(trail.len(), 3);
+        assert_eq!(trail.positions().next(), Some(Vec2::new(1.5, 0.0)));
+
+        trail.emitting = false;
+        trail.update(Vec2::new(5.0, 0.0), 0.35);
+        assert_eq!(trail.len(), 2);
+        trail.update(Vec2::new(5.0, 0.0), 1.0);
+        assert!(trail.is_finished());
+    }
+
+    #[test]
+    fn max_points_caps_the_length() {
+        let mut trail = Trail::new(10.0, 1.0).with_max_points(4);
+        for i in 0..10 {
+            trail.update(Vec2::new(i as f32, 0.0), 0.01);
+        }
+        assert_eq!(trail.len(), 4);
+        assert_eq!(trail.positions().last(), Some(Vec2::new(6.0, 0.0)));
+    }
+
+    #[test]
+    fn ribbon_tapers_from_head_to_tail() {
+        let mut trail = Trail::new(10.0, 2.0).with_min_distance(0.0);
+        for i in 0..3 {
+            trail.update(Vec2::new(i as f32, 0.0), 0.01);
+        }
+
+        let (indices, vertices) = trail.points(0);
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(indices.len(), 12);
+        // the head is full width across the line, and the tail has none
+        assert_eq!(vertices[0].position[0], 2.0);
+        assert!((vertices[0].position[1] - vertices[1].position[1]).abs() - 2.0 < 1e-5);
+        assert!((vertices[4].position[1] - vertices[5].position[1]).abs() < 1e-5);
+        assert!(vertices[0].color[3] > vertices[4].color[3]);
+
+        let bounds = trail.bounds();
+        assert_eq!(bounds.min, Vec2::new(-1.0, -1.0));
+        assert_eq!(bounds.max, Vec2::new(3.0, 1.0));
+    }
+
+    #[test]
+    fn width_curve_is_followed() {
+        let mut trail = Trail::new(10.0, 1.0)
+            .with_min_distance(0.0)
+            .with_width(Curve::constant(0.5));
+        trail.update(Vec2::ZERO, 0.01);
+        trail.update(Vec2::new(0.0, 4.0), 0.01);
+
+        let (_, vertices) = trail.points(0);
+        let width = (vertices[2].position[0] - vertices[3].position[0]).abs();
+        assert!((width - 0.5).abs() < 1e-5);
+    }
+}
+
+#[cfg(test)]
+mod render_pass_tests {
+    use crate::camera::shake::CameraShake;
+    use crate::render_pipeline::{PassSettings, RenderPass};
+    use bevy_math::Vec2;
+
+    #[test]
+    fn only_the_world_is_post_processed_and_shaken_by_default() {
+        let world = RenderPass::World.default_settings();
+        assert!(world.post_processing && world.camera_shake);
+        for pass in [RenderPass::Ui, RenderPass::Overlay] {
+            assert_eq!(
+                pass.default_settings(),
+                PassSettings {
+                    post_processing: false,
+                    camera_shake: false,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn passes_index_in_draw_order() {
+        for (i, pass) in RenderPass::ALL.into_iter().enumerate() {
+            assert_eq!(pass as usize, i);
+        }
+    }
+
+    #[test]
+    fn trauma_is_clamped_and_wears_off() {
+        let mut shake = CameraShake::default();
+        shake.add_trauma(0.7);
+        shake.add_trauma(0.7);
+        assert_eq!(shake.trauma, 1.0);
+
+        shake.update(0.5);
+        assert!((shake.trauma - 0.25).abs() < 1e-5);
+        shake.update(1.0);
+        assert_eq!(shake.trauma, 0.0);
+        assert_eq!(shake.offset(3.0), Vec2::ZERO);
+    }
+
+    #[test]
+    fn shake_stays_within_max_offset() {
+        let mut shake = CameraShake::default();
+        shake.add_trauma(1.0);
+        let offsets: Vec<_> = (0..200).map(|i| shake.offset(i as f32 * 0.013)).collect();
+        assert!(
+            offsets
+                .iter()
+                .all(|offset| offset.x.abs() <= shake.max_offset
+                    && offset.y.abs() <= shake.max_offset)
+        );
+        assert!(
+            offsets
+                .iter()
+                .any(|offset| offset.length() > shake.max_offset * 0.25)
+        );
+
+        // trauma squared, so half the trauma is a quarter of the movement
+        let full = shake.offset(1.23);
+        shake.trauma = 0.5;
+        assert!((shake.offset(1.23) - full * 0.25).length() < 1e-4);
+    }
+}
+
+#[cfg(test)]
+mod tonemapping_tests {
+    use crate::color::Color;
+    use crate::post_processing::{TonemapOperator, Tonemapping};
+
+    #[test]
+    fn operators_squeeze_everything_into_range() {
+        for operator in [TonemapOperator::Reinhard, TonemapOperator::Aces] {
+            assert_eq!(operator.map(0.0), 0.0);
+            assert_eq!(operator.map(-3.0), 0.0);
+
+            let mut previous = 0.0;
+            for i in 1..200 {
+                let mapped = operator.map(i as f32 * 0.25);
+                assert!(mapped >= previous && mapped <= 1.0, "{operator:?}");
+                previous = mapped;
+            }
+            assert!(operator.map(1000.0) > 0.99);
+        }
+    }
+
+    #[test]
+    fn reinhard_halves_white() {
+        assert_eq!(TonemapOperator::Reinhard.map(1.0), 0.5);
+    }
+
+    #[test]
+    fn exposure_brightens_and_alpha_is_kept() {
+        let color = Color::from_rgba(0.4, 0.2, 0.1, 0.5);
+        let dim = Tonemapping::aces(1.0).apply(color);
+        let bright = Tonemapping::aces(4.0).apply(color);
+        assert!(bright.r > dim.r && bright.g > dim.g && bright.b > dim.b);
+        assert_eq!(dim.a, 0.5);
+        assert_eq!(Tonemapping::default(), Tonemapping::aces(1.0));
+    }
+
+    #[test]
+    fn hdr_colors_are_told_apart() {
+        // clipping would make these both white
+        let tonemapping = Tonemapping::reinhard(1.0);
+        let bright = tonemapping.apply(Color::from_rgba(2.0, 2.0, 2.0, 1.0));
+        let brighter = tonemapping.apply(Color::from_rgba(4.0, 4.0, 4.0, 1.0));
+        assert!(bright.r < brighter.r && brighter.r < 1.0);
+    }
+}
+
+#[cfg(test)]
+mod outline_tests {
+    use crate::color::Color;
+    use crate::draw_queue_2d::DrawQueue2D;
+    use crate::shapes_2d::Rect;
+    use crate::testing::{DrawCall2D, MockDrawTarget2D};
+    use bevy_math::{Mat4, Vec2};
+
+    fn rect(x: f32) -> Rect {
+        Rect {
+            top_left: Vec2::new(x, 0.0),
+            size: Vec2::ONE,
+            color: Color::WHITE,
+        }
+    }
+
+    #[test]
+    fn only_outlined_things_are_in_the_mask() {
+        let mut queue = DrawQueue2D::empty();
+        queue.add_shape(&rect(0.0));
+        queue.set_outline(Some(Color::YELLOW_400));
+        queue.add_shape(&rect(5.0));
+        queue.set_outline(None);
+        queue.add_circle(Vec2::ZERO, Vec2::splat(2.0), Color::WHITE);
+        assert!(queue.has_outlines());
+
+        let mut target = MockDrawTarget2D::default();
+        queue.for_outlining().draw_to(&mut target, &Mat4::IDENTITY);
+
+        let shapes = target.calls.iter().find_map(|call| match call {
+            DrawCall2D::Shapes { vertices, .. } => Some(vertices),
+            _ => None,
+        });
+        let vertices = shapes.unwrap();
+        assert!(vertices[..4].iter().all(|v| v.position == [0.0; 3]));
+        assert!(
+            vertices[4..]
+                .iter()
+                .all(|v| v.color == Color::YELLOW_400.for_gpu())
+        );
+
+        let circles = target.calls.iter().find_map(|call| match call {
+            DrawCall2D::Circles { instances } => Some(instances),
+            _ => None,
+        });
+        assert_eq!(circles.unwrap()[0].radius, [0.0; 2]);
+    }
+
+    #[test]
+    fn appended_queues_take_the_current_outline() {
+        let mut inner = DrawQueue2D::empty();
+        inner.add_shape(&rect(0.0));
+
+        let mut queue = DrawQueue2D::empty();
+        assert!(!queue.has_outlines());
+        queue.set_outline(Some(Color::RED_500));
+        queue.append(inner);
+        assert!(queue.has_outlines());
+    }
+
+    #[test]
+    fn nothing_outlined_by_default() {
+        let mut queue = DrawQueue2D::empty();
+        queue.add_shape(&rect(0.0));
+        assert!(!queue.has_outlines());
+        assert_eq!(queue.outline(), None);
+    }
+}
+
+#[cfg(test)]
+mod crt_tests {
+    use crate::post_processing::{PostProcessingEffect, crt_distort};
+    use bevy_math::Vec2;
+
+    #[test]
+    fn flat_screens_are_left_alone() {
+        for uv in [Vec2::ZERO, Vec2::new(0.3, 0.8), Vec2::ONE] {
+            assert_eq!(crt_distort(uv, 0.0), Some(uv));
+        }
+    }
+
+    #[test]
+    fn curves_push_out_from_the_middle() {
+        assert_eq!(crt_distort(Vec2::splat(0.5), 0.3), Some(Vec2::splat(0.5)));
+
+        // straight across the middle only the other axis bends it, so it stays put
+        assert_eq!(
+            crt_distort(Vec2::new(0.9, 0.5), 0.3),
+            Some(Vec2::new(0.9, 0.5))
+        );
+
+        let uv = crt_distort(Vec2::new(0.7, 0.7), 0.3).unwrap();
+        assert!(uv.x > 0.7 && uv.y > 0.7);
+        assert_eq!(crt_distort(Vec2::new(0.98, 0.98), 0.3), None);
+    }
+
+    #[test]
+    fn presets_get_stronger() {
+        let curvature = |effect| match effect {
+            PostProcessingEffect::Crt { curvature, .. } => curvature,
+            _ => unreachable!(),
+        };
+        assert!(
+            curvature(PostProcessingEffect::crt_subtle())
+                < curvature(PostProcessingEffect::crt_arcade())
+        );
+        assert!(
+            curvature(PostProcessingEffect::crt_arcade())
+                < curvature(PostProcessingEffect::crt_old_tv())
+        );
+    }
+}
+
+#[cfg(test)]
+mod palette_tests {
+    use crate::color::Color;
+    use crate::palette::{dither_offset, dithered, nearest_index};
+    use bevy_math::UVec2;
+
+    fn gameboy() -> Vec<Color> {
+        [15, 48, 139, 155]
+            .map(|v| Color::from_rgba_u8(v, v + 20, v, 255))
+            .to_vec()
+    }
+
+    #[test]
+    fn colors_go_to_the_closest_entry() {
+        let colors = gameboy();
+        assert_eq!(nearest_index(&colors, Color::BLACK), 0);
+        assert_eq!(nearest_index(&colors, Color::WHITE), 3);
+        assert_eq!(nearest_index(&colors, colors[2]), 2);
+        assert_eq!(
+            nearest_index(&colors, Color::from_rgba(0.21, 0.28, 0.2, 1.0)),
+            1
+        );
+    }
+
+    #[test]
+    fn dithering_averages_out() {
+        let offsets: Vec<f32> = (0..4)
+            .flat_map(|y| (0..4).map(move |x| dither_offset(UVec2::new(x, y))))
+            .collect();
+        let mean = offsets.iter().sum::<f32>() / offsets.len() as f32;
+        assert!(mean.abs() < 1e-6);
+        assert!(offsets.iter().all(|offset| offset.abs() < 0.5));
+        // the pattern repeats every four pixels
+        assert_eq!(
+            dither_offset(UVec2::new(1, 2)),
+            dither_offset(UVec2::new(5, 6))
+        );
+    }
+
+    #[test]
+    fn dithering_mixes_colors_between_entries() {
+        let colors = vec![Color::BLACK, Color::WHITE];
+        let grey = Color::from_rgba(0.5, 0.5, 0.5, 1.0);
+        let picked: Vec<usize> = (0..4)
+            .flat_map(|y| (0..4).map(move |x| UVec2::new(x, y)))
+            .map(|pixel| nearest_index(&colors, dithered(grey, pixel, 1.0, colors.len())))
+            .collect();
+        let whites = picked.iter().filter(|&&index| index == 1).count();
+        assert_eq!(whites, 8);
+
+        // without dithering it's one flat color
+        let flat = nearest_index(&colors, dithered(grey, UVec2::ZERO, 0.0, colors.len()));
+        assert!((0..16).all(|i| {
+            let pixel = UVec2::new(i % 4, i / 4);
+            nearest_index(&colors, dithered(grey, pixel, 0.0, colors.len())) == flat
+        }));
+    }
+}
+
+#[cfg(test)]
+mod async_texture_tests {
+    use crate::{
+        color::u8::Pixel,
+        image::Image,
+        textures::async_loading::{loaded_image, placeholder_image, read_image},
+    };
+
+    #[test]
+    fn placeholder_is_a_magenta_and_black_checkerboard() {
+        let image = placeholder_image();
+        assert_eq!((image.width(), image.height()), (2, 2));
+        let magenta = Pixel::from_rgb(255, 0, 255);
+        assert_eq!(image.get_pixel(0, 0), Some(&magenta));
+        assert_eq!(image.get_pixel(1, 1), Some(&magenta));
+        assert_eq!(image.get_pixel(1, 0), Some(&Pixel::BLACK));
+        assert_eq!(image.get_pixel(0, 1), Some(&Pixel::BLACK));
+    }
+
+    #[test]
+    fn decoded_images_are_ready_to_upload() {
+        let Ok(image) = loaded_image(Ok(Ok(Image::empty(3, 2))), false) else {
+            panic!("the image should be ready");
+        };
+        assert_eq!((image.width(), image.height()), (3, 2));
+    }
+
+    #[test]
+    fn headless_loads_fail_instead_of_uploading() {
+        let error = loaded_image(Ok(Ok(Image::empty(1, 1))), true)
+            .err()
+            .unwrap();
+        assert!(error.contains("headless"));
+    }
+
+    #[test]
+    fn decode_errors_are_kept() {
+        let error = loaded_image(Ok(Err(anyhow::anyhow!("bad header"))), false)
+            .err()
+            .unwrap();
+        assert_eq!(error, "bad header");
+    }
+
+    #[test]
+    fn panicking_threads_fail() {
+        let joined = std::thread::spawn(|| -> anyhow::Result<Image> { panic!("oops") }).join();
+        let error = loaded_image(joined, false).err().unwrap();
+        assert_eq!(error, "the loading thread panicked");
+    }
+
+    #[test]
+    fn missing_files_name_the_path() {
+        let error = read_image("does/not/exist.png".as_ref()).err().unwrap();
+        assert!(error.to_string().contains("does/not/exist.png"));
+    }
+}
+
+#[cfg(test)]
+mod prelude_tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn test_physics_still_has_rapiers_prelude() {
+        let mut bodies = physics::RigidBodySet::new();
+        let mut colliders = physics::ColliderSet::new();
+        let body = bodies
+            .insert(physics::RigidBodyBuilder::dynamic().translation(physics::vector![1.0, 2.0]));
+        colliders.insert_with_parent(physics::ColliderBuilder::ball(0.5), body, &mut bodies);
+
+        assert_eq!(colliders.len(), 1);
+        assert_eq!(bodies[body].translation(), &physics::vector![1.0, 2.0]);
+    }
+}
+
+#[cfg(test)]
+mod derive_uniforms_tests {
+    use crate::color::Color;
+    use engine_4_macros::Uniforms;
+    use glium::uniforms::{UniformValue, Uniforms};
+
+    #[derive(Uniforms)]
+    #[uniform(rename_all = "camelCase")]
+    struct Params {
+        light_color: Color,
+        #[uniform(name = "u_time")]
+        elapsed_time: f32,
+        flip_y: bool,
+        #[uniform(skip)]
+        #[allow(dead_code)]
+        label: String,
+    }
+
+    #[test]
+    fn test_derived_uniforms_are_renamed_and_skipped() {
+        let params = Params {
+            light_color: Color::WHITE,
+            elapsed_time: 2.5,
+            flip_y: true,
+            label: "not a uniform".to_string(),
+        };
+
+        let mut names = Vec::new();
+        params.visit_values(|name, value| {
+            match name {
+                "lightColor" => {
+                    assert!(matches!(value, UniformValue::Vec4(v) if v == Color::WHITE.for_gpu()))
+                }
+                "u_time" => assert!(matches!(value, UniformValue::Float(2.5))),
+                "flipY" => assert!(matches!(value, UniformValue::Bool(true))),
+                other => panic!("unexpected uniform `{other}`"),
+            }
+            names.push(name.to_string());
+        });
+
+        assert_eq!(names, ["lightColor", "u_time", "flipY"]);
+    }
+}
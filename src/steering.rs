@@ -0,0 +1,314 @@
+//! Steering behaviours for AI movement. Each behaviour looks at an [`Agent`] and gives back a
+//! force that turns it toward what it wants to do, and forces can be weighted and added up to
+//! mix behaviours, like following a path while keeping away from the rest of a crowd:
+//!
+//! ```ignore
+//! let force = follower.steer(&agent) + steering::separation(&agent, &others, 2.0) * 1.5;
+//! agent.update(force);
+//! ```
+
+use std::f32::consts::TAU;
+
+use bevy_math::Vec2;
+use rand::Rng;
+
+use crate::api::{delta_time, random_range};
+
+/// Something that moves around under steering. Doesn't have to be the thing that's drawn, so
+/// copy `position` and `velocity` in and out of whatever is, like a physics body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Agent {
+    pub position: Vec2,
+    pub velocity: Vec2,
+    pub max_speed: f32,
+    /// How hard it can turn or speed up, per second. Low values make it feel heavy.
+    pub max_force: f32,
+}
+
+impl Agent {
+    /// Standing still, able to reach full speed in a quarter of a second.
+    pub fn new(position: Vec2, max_speed: f32) -> Self {
+        Self {
+            position,
+            velocity: Vec2::ZERO,
+            max_speed,
+            max_force: max_speed * 4.0,
+        }
+    }
+
+    pub fn with_velocity(mut self, velocity: Vec2) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn with_max_force(mut self, max_force: f32) -> Self {
+        self.max_force = max_force;
+        self
+    }
+
+    /// Which way it's going, or zero if it's stopped.
+    pub fn heading(&self) -> Vec2 {
+        self.velocity.normalize_or_zero()
+    }
+
+    /// Applies `force` for this frame and moves.
+    pub fn update(&mut self, force: Vec2) {
+        self.apply(force, delta_time());
+    }
+
+    /// Like [`update`](Self::update), for `delta_time` seconds. Forces are the change in
+    /// velocity wanted, and get as much of it as `max_force` allows.
+    pub fn apply(&mut self, force: Vec2, delta_time: f32) {
+        self.velocity += force.clamp_length_max(self.max_force * delta_time);
+        self.velocity = self.velocity.clamp_length_max(self.max_speed);
+        self.position += self.velocity * delta_time;
+    }
+
+    /// The force to go from the current velocity to `desired`.
+    fn steer_towards(&self, desired: Vec2) -> Vec2 {
+        desired - self.velocity
+    }
+}
+
+/// Straight at `target` at full speed. Overshoots and circles back, see [`arrive`] for
+/// stopping there.
+pub fn seek(agent: &Agent, target: Vec2) -> Vec2 {
+    let desired = (target - agent.position).normalize_or_zero() * agent.max_speed;
+    agent.steer_towards(desired)
+}
+
+/// Straight away from `threat` at full speed.
+pub fn flee(agent: &Agent, threat: Vec2) -> Vec2 {
+    let desired = (agent.position - threat).normalize_or_zero() * agent.max_speed;
+    agent.steer_towards(desired)
+}
+
+/// Like [`seek`], but slows down within `slowing_radius` to stop on `target`.
+pub fn arrive(agent: &Agent, target: Vec2, slowing_radius: f32) -> Vec2 {
+    let offset = target - agent.position;
+    let distance = offset.length();
+    if distance <= f32::EPSILON {
+        return -agent.velocity;
+    }
+
+    let speed = if distance < slowing_radius {
+        agent.max_speed * distance / slowing_radius
+    } else {
+        agent.max_speed
+    };
+    agent.steer_towards(offset / distance * speed)
+}
+
+/// Aimless but smooth wandering. A point on a circle in front of the agent drifts a little
+/// each frame, and the agent heads for it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wander {
+    /// Size of the circle. Bigger turns harder.
+    pub radius: f32,
+    /// How far ahead the circle is. Further makes turns gentler.
+    pub distance: f32,
+    /// How far the point can drift around the circle each frame, in radians.
+    pub jitter: f32,
+    angle: f32,
+}
+
+impl Wander {
+    pub fn new(radius: f32, distance: f32, jitter: f32) -> Self {
+        Self {
+            radius,
+            distance,
+            jitter,
+            angle: 0.0,
+        }
+    }
+
+    pub fn steer(&mut self, agent: &Agent) -> Vec2 {
+        self.angle += random_range(-1.0..=1.0) * self.jitter;
+        self.steer_to_angle(agent)
+    }
+
+    /// Like [`steer`](Self::steer), with randomness from `rng` instead of the engine's.
+    pub fn steer_with_rng(&mut self, agent: &Agent, rng: &mut impl Rng) -> Vec2 {
+        self.angle += rng.random_range(-1.0..=1.0) * self.jitter;
+        self.steer_to_angle(agent)
+    }
+
+    fn steer_to_angle(&mut self, agent: &Agent) -> Vec2 {
+        self.angle %= TAU;
+        let heading = match agent.heading() {
+            Vec2::ZERO => Vec2::X,
+            heading => heading,
+        };
+        let circle = agent.position + heading * self.distance;
+        let target = circle + heading.rotate(Vec2::from_angle(self.angle)) * self.radius;
+        seek(agent, target)
+    }
+}
+
+impl Default for Wander {
+    fn default() -> Self {
+        Self::new(1.0, 2.0, 0.3)
+    }
+}
+
+/// The agents in `others` within `radius`, apart from `agent` itself, so the whole flock can be
+/// passed in.
+fn neighbours<'a>(
+    agent: &'a Agent,
+    others: &'a [Agent],
+    radius: f32,
+) -> impl Iterator<Item = &'a Agent> {
+    others.iter().filter(move |other| {
+        !std::ptr::eq(*other, agent)
+            && other.position.distance_squared(agent.position) < radius * radius
+    })
+}
+
+/// Away from anything in `others` within `radius`, harder the closer it is. Stops crowds
+/// bunching up.
+pub fn separation(agent: &Agent, others: &[Agent], radius: f32) -> Vec2 {
+    let away: Vec2 = neighbours(agent, others, radius)
+        .map(|other| {
+            let offset = agent.position - other.position;
+            let distance = offset.length();
+            if distance <= f32::EPSILON {
+                // right on top of each other, so any way out will do
+                return Vec2::X;
+            }
+            offset / distance * (1.0 - distance / radius)
+        })
+        .sum();
+
+    if away == Vec2::ZERO {
+        return Vec2::ZERO;
+    }
+    agent.steer_towards(away.normalize() * agent.max_speed)
+}
+
+/// Toward the middle of everything in `others` within `radius`.
+pub fn cohesion(agent: &Agent, others: &[Agent], radius: f32) -> Vec2 {
+    let (sum, count) = neighbours(agent, others, radius)
+        .fold((Vec2::ZERO, 0), |(sum, count), other| {
+            (sum + other.position, count + 1)
+        });
+
+    if count == 0 {
+        return Vec2::ZERO;
+    }
+    seek(agent, sum / count as f32)
+}
+
+/// Toward the average heading of everything in `others` within `radius`.
+pub fn alignment(agent: &Agent, others: &[Agent], radius: f32) -> Vec2 {
+    let heading: Vec2 = neighbours(agent, others, radius).map(Agent::heading).sum();
+
+    if heading == Vec2::ZERO {
+        return Vec2::ZERO;
+    }
+    agent.steer_towards(heading.normalize() * agent.max_speed)
+}
+
+/// How much each part of [`flock`] counts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flocking {
+    /// How far away others are noticed.
+    pub radius: f32,
+    /// Others closer than this are pushed away from.
+    pub separation_radius: f32,
+    pub separation: f32,
+    pub cohesion: f32,
+    pub alignment: f32,
+}
+
+impl Default for Flocking {
+    fn default() -> Self {
+        Self {
+            radius: 5.0,
+            separation_radius: 2.0,
+            separation: 1.5,
+            cohesion: 1.0,
+            alignment: 1.0,
+        }
+    }
+}
+
+/// [`separation`], [`cohesion`] and [`alignment`] together, for birds, fish and crowds.
+pub fn flock(agent: &Agent, others: &[Agent], flocking: Flocking) -> Vec2 {
+    separation(agent, others, flocking.separation_radius) * flocking.separation
+        + cohesion(agent, others, flocking.radius) * flocking.cohesion
+        + alignment(agent, others, flocking.radius) * flocking.alignment
+}
+
+/// Goes through a list of points in order, like one from
+/// [`pathfinding`](crate::pathfinding), and stops at the last.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathFollower {
+    pub path: Vec<Vec2>,
+    /// How close counts as reaching a point, to move on to the next.
+    pub radius: f32,
+    /// Goes back to the start after the end, instead of stopping there.
+    pub looping: bool,
+    current: usize,
+}
+
+impl PathFollower {
+    pub fn new(path: Vec<Vec2>, radius: f32) -> Self {
+        Self {
+            path,
+            radius,
+            looping: false,
+            current: 0,
+        }
+    }
+
+    pub fn looping(mut self) -> Self {
+        self.looping = true;
+        self
+    }
+
+    /// The point it's heading for.
+    pub fn target(&self) -> Option<Vec2> {
+        self.path.get(self.current).copied()
+    }
+
+    /// Whether it's got to the last point. Never true when looping.
+    pub fn is_finished(&self, agent: &Agent) -> bool {
+        !self.looping
+            && self.current + 1 >= self.path.len()
+            && self
+                .target()
+                .is_none_or(|target| agent.position.distance(target) <= self.radius)
+    }
+
+    /// Starts over with a new path.
+    pub fn set_path(&mut self, path: Vec<Vec2>) {
+        self.path = path;
+        self.current = 0;
+    }
+
+    pub fn steer(&mut self, agent: &Agent) -> Vec2 {
+        let Some(mut target) = self.target() else {
+            return -agent.velocity;
+        };
+
+        if agent.position.distance(target) <= self.radius {
+            if self.current + 1 < self.path.len() {
+                self.current += 1;
+            } else if self.looping {
+                self.current = 0;
+            }
+            target = self.path[self.current];
+        }
+
+        let last = !self.looping && self.current + 1 == self.path.len();
+        if last {
+            // slowing down over about the distance it takes to stop
+            let stopping_distance = agent.max_speed * agent.max_speed / agent.max_force;
+            arrive(agent, target, stopping_distance.max(self.radius))
+        } else {
+            seek(agent, target)
+        }
+    }
+}
@@ -0,0 +1,385 @@
+//! Save games and anything else that should still be there next time the game runs.
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct Progress {
+//!     level: u32,
+//!     coins: u64,
+//! }
+//!
+//! storage::save("slot1", &Progress { level: 3, coins: 120 })?;
+//! let progress: Progress = storage::load("slot1")?;
+//! ```
+//!
+//! Saves go in a folder under the platform's data directory, named after the game (see
+//! [`SaveOptions`]). Each one remembers the [`SaveOptions::version`] it was written with, so
+//! old saves can be upgraded with [`migrate_save`] when the saved types change.
+
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+};
+
+use anyhow::{Context, bail, ensure};
+use flate2::{Compression, Crc, read::DeflateDecoder, write::DeflateEncoder};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{get_state, try_get_state};
+
+pub(crate) mod format;
+
+pub use format::FormatError;
+
+const MAGIC: &[u8; 4] = b"E4SV";
+/// Bumped when the layout of the file itself changes, not the game's data.
+const FILE_FORMAT: u8 = 1;
+const EXTENSION: &str = "sav";
+
+const COMPRESSED: u8 = 1 << 0;
+const ENCRYPTED: u8 = 1 << 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SaveOptions {
+    /// The folder saves go in under the platform's data directory. Defaults to the executable's
+    /// name.
+    pub game_name: Option<String>,
+    /// Puts saves here instead of the platform's data directory.
+    pub directory: Option<PathBuf>,
+    /// Written into every save. Bump it when the saved types change, and use [`migrate_save`] to
+    /// bring older saves up to date.
+    pub version: u32,
+    pub compress: bool,
+    /// Encrypts saves with ChaCha20. The key ships inside the game, so this only stops players
+    /// from casually editing their saves; it isn't real security.
+    pub encryption_key: Option<[u8; 32]>,
+}
+
+impl Default for SaveOptions {
+    fn default() -> Self {
+        Self {
+            game_name: None,
+            directory: None,
+            version: 0,
+            compress: true,
+            encryption_key: None,
+        }
+    }
+}
+
+impl SaveOptions {
+    /// Where saves go with these options.
+    pub fn save_directory(&self) -> PathBuf {
+        if let Some(directory) = &self.directory {
+            return directory.clone();
+        }
+
+        let game_name = self.game_name.clone().unwrap_or_else(|| {
+            std::env::current_exe()
+                .ok()
+                .and_then(|exe| {
+                    exe.file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                })
+                .unwrap_or_else(|| "engine_4".to_string())
+        });
+
+        match platform_data_directory() {
+            Some(directory) => directory.join(game_name),
+            None => PathBuf::from("saves").join(game_name),
+        }
+    }
+}
+
+fn platform_data_directory() -> Option<PathBuf> {
+    let env = |name| std::env::var_os(name).filter(|value| !value.is_empty());
+
+    if cfg!(target_os = "windows") {
+        env("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        env("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+    }
+}
+
+/// A save that's been read and decrypted, but not deserialized yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SaveFile {
+    pub version: u32,
+    pub payload: Vec<u8>,
+}
+
+/// Lays a save out as: magic, file format, flags, version, checksum of the payload, nonce if
+/// encrypted, then the payload, compressed and then encrypted.
+pub(crate) fn encode_save(payload: &[u8], options: &SaveOptions) -> anyhow::Result<Vec<u8>> {
+    let mut crc = Crc::new();
+    crc.update(payload);
+
+    let mut flags = 0;
+    let mut body = if options.compress {
+        flags |= COMPRESSED;
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(payload)?;
+        encoder.finish()?
+    } else {
+        payload.to_vec()
+    };
+
+    let nonce = options.encryption_key.map(|key| {
+        flags |= ENCRYPTED;
+        let nonce = rand::random::<u64>();
+        apply_keystream(&mut body, &key, nonce);
+        nonce
+    });
+
+    let mut file = Vec::with_capacity(body.len() + 22);
+    file.extend_from_slice(MAGIC);
+    file.push(FILE_FORMAT);
+    file.push(flags);
+    file.extend_from_slice(&options.version.to_le_bytes());
+    file.extend_from_slice(&crc.sum().to_le_bytes());
+    if let Some(nonce) = nonce {
+        file.extend_from_slice(&nonce.to_le_bytes());
+    }
+    file.extend_from_slice(&body);
+
+    Ok(file)
+}
+
+pub(crate) fn decode_save(file: &[u8], options: &SaveOptions) -> anyhow::Result<SaveFile> {
+    let mut reader = file;
+    let mut take = |len: usize| -> anyhow::Result<&[u8]> {
+        ensure!(reader.len() >= len, "save file is cut short");
+        let (taken, rest) = reader.split_at(len);
+        reader = rest;
+        Ok(taken)
+    };
+
+    ensure!(take(4)? == MAGIC, "not a save file");
+    let file_format = take(1)?[0];
+    ensure!(
+        file_format == FILE_FORMAT,
+        "save file format {file_format} isn't supported"
+    );
+    let flags = take(1)?[0];
+    let version = u32::from_le_bytes(take(4)?.try_into().unwrap());
+    let checksum = u32::from_le_bytes(take(4)?.try_into().unwrap());
+
+    let mut body = if flags & ENCRYPTED != 0 {
+        let Some(key) = &options.encryption_key else {
+            bail!("save is encrypted, but there's no encryption key set");
+        };
+        let nonce = u64::from_le_bytes(take(8)?.try_into().unwrap());
+        let mut body = reader.to_vec();
+        apply_keystream(&mut body, key, nonce);
+        body
+    } else {
+        reader.to_vec()
+    };
+
+    if flags & COMPRESSED != 0 {
+        let mut payload = Vec::new();
+        DeflateDecoder::new(body.as_slice())
+            .read_to_end(&mut payload)
+            .context("couldn't decompress save, it's corrupt or the key is wrong")?;
+        body = payload;
+    }
+
+    let mut crc = Crc::new();
+    crc.update(&body);
+    ensure!(
+        crc.sum() == checksum,
+        "save doesn't match its checksum, it's corrupt or the key is wrong"
+    );
+
+    Ok(SaveFile {
+        version,
+        payload: body,
+    })
+}
+
+fn apply_keystream(data: &mut [u8], key: &[u8; 32], nonce: u64) {
+    let mut cipher = ChaCha20Rng::from_seed(*key);
+    cipher.set_stream(nonce);
+
+    let mut keystream = vec![0; data.len()];
+    cipher.fill_bytes(&mut keystream);
+    for (byte, key_byte) in data.iter_mut().zip(keystream) {
+        *byte ^= key_byte;
+    }
+}
+
+/// Slots become file names, so they're kept to characters that are safe on every platform.
+fn check_slot(slot: &str) -> anyhow::Result<()> {
+    ensure!(!slot.is_empty(), "save slot name can't be empty");
+    ensure!(
+        slot.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ' ')),
+        "save slot `{slot}` can only use letters, numbers, spaces, `-` and `_`"
+    );
+    Ok(())
+}
+
+fn options() -> SaveOptions {
+    try_get_state()
+        .map(|state| state.config.saves.clone())
+        .unwrap_or_default()
+}
+
+fn slot_path(slot: &str, options: &SaveOptions) -> anyhow::Result<PathBuf> {
+    check_slot(slot)?;
+    Ok(options
+        .save_directory()
+        .join(slot)
+        .with_extension(EXTENSION))
+}
+
+fn read_slot(slot: &str, options: &SaveOptions) -> anyhow::Result<SaveFile> {
+    let path = slot_path(slot, options)?;
+    let file = std::fs::read(&path)
+        .with_context(|| format!("couldn't read save `{slot}` at {}", path.display()))?;
+    decode_save(&file, options).with_context(|| format!("couldn't load save `{slot}`"))
+}
+
+fn write_slot<T: Serialize + ?Sized>(
+    slot: &str,
+    value: &T,
+    options: &SaveOptions,
+) -> anyhow::Result<()> {
+    let path = slot_path(slot, options)?;
+    let payload = format::to_bytes(value)?;
+    let file = encode_save(&payload, options)?;
+
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)?;
+    }
+    // written next to it first, so a crash halfway through doesn't wipe the old save
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, file)?;
+    std::fs::rename(&temp_path, &path)
+        .with_context(|| format!("couldn't write save `{slot}` to {}", path.display()))?;
+
+    Ok(())
+}
+
+pub fn save<T: Serialize + ?Sized>(slot: &str, value: &T) -> anyhow::Result<()> {
+    write_slot(slot, value, &options())
+}
+
+/// Fails if the save is from a different [`SaveOptions::version`]; see [`migrate_save`].
+pub fn load<T: DeserializeOwned>(slot: &str) -> anyhow::Result<T> {
+    let options = options();
+    let file = read_slot(slot, &options)?;
+
+    if file.version > options.version {
+        bail!(
+            "save `{slot}` is from version {}, which is newer than this game's version {}",
+            file.version,
+            options.version
+        );
+    } else if file.version < options.version {
+        bail!(
+            "save `{slot}` is from version {}, migrate it to version {} first",
+            file.version,
+            options.version
+        );
+    }
+
+    Ok(format::from_bytes(&file.payload)?)
+}
+
+/// Like [`load`], but gives `T::default()` if there's no save in the slot yet.
+pub fn load_or_default<T: DeserializeOwned + Default>(slot: &str) -> anyhow::Result<T> {
+    if save_exists(slot) {
+        load(slot)
+    } else {
+        Ok(T::default())
+    }
+}
+
+/// If the save in `slot` is from version `from`, reads it as an `Old`, turns it into a `New` with
+/// `migrate` and saves that with the current version. Returns whether it did anything.
+///
+/// Call one for each old version, oldest first:
+///
+/// ```ignore
+/// storage::migrate_save("slot1", 0, |old: ProgressV0| ProgressV1::from(old))?;
+/// storage::migrate_save("slot1", 1, |old: ProgressV1| Progress::from(old))?;
+/// let progress: Progress = storage::load("slot1")?;
+/// ```
+pub fn migrate_save<Old: DeserializeOwned, New: Serialize>(
+    slot: &str,
+    from: u32,
+    migrate: impl FnOnce(Old) -> New,
+) -> anyhow::Result<bool> {
+    let options = options();
+    if !save_exists(slot) {
+        return Ok(false);
+    }
+
+    let file = read_slot(slot, &options)?;
+    if file.version != from {
+        return Ok(false);
+    }
+
+    let old = format::from_bytes(&file.payload)
+        .with_context(|| format!("save `{slot}` isn't a valid version {from} save"))?;
+    write_slot(slot, &migrate(old), &options)?;
+
+    Ok(true)
+}
+
+/// The [`SaveOptions::version`] the save in `slot` was written with.
+pub fn saved_version(slot: &str) -> anyhow::Result<u32> {
+    Ok(read_slot(slot, &options())?.version)
+}
+
+pub fn save_exists(slot: &str) -> bool {
+    slot_path(slot, &options()).is_ok_and(|path| path.is_file())
+}
+
+pub fn delete_save(slot: &str) -> anyhow::Result<()> {
+    let path = slot_path(slot, &options())?;
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Every slot with a save in it, sorted by name.
+pub fn list_saves() -> anyhow::Result<Vec<String>> {
+    let directory = options().save_directory();
+    if !directory.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut slots = Vec::new();
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == EXTENSION)
+            && let Some(stem) = path.file_stem()
+        {
+            slots.push(stem.to_string_lossy().into_owned());
+        }
+    }
+    slots.sort();
+
+    Ok(slots)
+}
+
+pub fn save_directory() -> PathBuf {
+    options().save_directory()
+}
+
+/// Changes the save options after [`init`](crate::init). They can also be set at init with
+/// [`EngineConfig::saves`](crate::config::EngineConfig::saves).
+pub fn set_save_options(options: SaveOptions) {
+    get_state().config.saves = options;
+}
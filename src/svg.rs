@@ -0,0 +1,289 @@
+use std::str::FromStr;
+
+use bevy_math::Vec2;
+use lyon::tessellation::{
+    BuffersBuilder, FillOptions, FillTessellator, FillVertex, FillVertexConstructor, StrokeOptions,
+    StrokeTessellator, StrokeVertex, StrokeVertexConstructor, VertexBuffers, path::Path,
+};
+use usvg::tiny_skia_path::PathSegment;
+
+use crate::{
+    collisions::{AABB2D, HasBounds2D},
+    color::Color,
+    draw_queue_2d::{DrawQueue2D, Vertex2D},
+    shapes_2d::Shape2D,
+};
+
+/// Vector art loaded from an SVG file. Fills and strokes are turned into triangles once when
+/// loading, so drawing it costs about the same as a `CustomShape` with the same detail.
+///
+/// Gradients and patterns are drawn as a single color (their first stop), and images, text,
+/// filters, clip paths and masks are skipped.
+#[derive(Clone, Debug)]
+pub struct Svg {
+    vertices: Vec<Vertex2D>,
+    indices: Vec<u32>,
+    size: Vec2,
+    pub top_left: Vec2,
+    /// Multiplies the size the SVG says it is.
+    pub scale: Vec2,
+}
+
+impl Svg {
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let tree = usvg::Tree::from_data(bytes, &usvg::Options::default())?;
+        Ok(Self::from_tree(&tree))
+    }
+
+    fn from_tree(tree: &usvg::Tree) -> Self {
+        let mut svg = Self {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            size: Vec2::new(tree.size().width(), tree.size().height()),
+            top_left: Vec2::ZERO,
+            scale: Vec2::ONE,
+        };
+        svg.add_group(tree.root(), 1.0);
+        svg
+    }
+
+    pub fn at(mut self, top_left: Vec2) -> Self {
+        self.top_left = top_left;
+        self
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = Vec2::splat(scale);
+        self
+    }
+
+    /// Scales it to exactly `size`, which might stretch it.
+    pub fn with_size(mut self, size: Vec2) -> Self {
+        self.scale = size / self.size;
+        self
+    }
+
+    /// Size it's drawn at, after scaling.
+    pub fn size(&self) -> Vec2 {
+        self.size * self.scale
+    }
+
+    /// Number of triangles it's made of.
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn add_group(&mut self, group: &usvg::Group, opacity: f32) {
+        let opacity = opacity * group.opacity().get();
+
+        for node in group.children() {
+            match node {
+                usvg::Node::Group(group) => self.add_group(group, opacity),
+                usvg::Node::Path(path) if path.is_visible() => self.add_path(path, opacity),
+                _ => {}
+            }
+        }
+    }
+
+    fn add_path(&mut self, path: &usvg::Path, opacity: f32) {
+        let lyon_path = to_lyon_path(path.data(), path.abs_transform());
+
+        let fill = path.fill().map(|fill| {
+            let options = FillOptions::default().with_fill_rule(match fill.rule() {
+                usvg::FillRule::NonZero => lyon::tessellation::FillRule::NonZero,
+                usvg::FillRule::EvenOdd => lyon::tessellation::FillRule::EvenOdd,
+            });
+            let color = paint_color(fill.paint(), fill.opacity().get() * opacity);
+            (options, color)
+        });
+
+        let stroke = path.stroke().map(|stroke| {
+            // strokes get scaled along with the path
+            let transform = path.abs_transform();
+            let scale = (transform.sx * transform.sy - transform.kx * transform.ky)
+                .abs()
+                .sqrt();
+
+            let options = StrokeOptions::default()
+                .with_line_width(stroke.width().get() * scale)
+                .with_miter_limit(stroke.miterlimit().get().max(1.0))
+                .with_line_cap(match stroke.linecap() {
+                    usvg::LineCap::Butt => lyon::tessellation::LineCap::Butt,
+                    usvg::LineCap::Round => lyon::tessellation::LineCap::Round,
+                    usvg::LineCap::Square => lyon::tessellation::LineCap::Square,
+                })
+                .with_line_join(match stroke.linejoin() {
+                    usvg::LineJoin::Miter => lyon::tessellation::LineJoin::Miter,
+                    usvg::LineJoin::MiterClip => lyon::tessellation::LineJoin::MiterClip,
+                    usvg::LineJoin::Round => lyon::tessellation::LineJoin::Round,
+                    usvg::LineJoin::Bevel => lyon::tessellation::LineJoin::Bevel,
+                });
+            let color = paint_color(stroke.paint(), stroke.opacity().get() * opacity);
+            (options, color)
+        });
+
+        let fill_first = path.paint_order() == usvg::PaintOrder::FillAndStroke;
+        if fill_first && let Some((options, color)) = fill {
+            self.fill(&lyon_path, &options, color);
+        }
+        if let Some((options, color)) = stroke {
+            self.stroke(&lyon_path, &options, color);
+        }
+        if !fill_first && let Some((options, color)) = fill {
+            self.fill(&lyon_path, &options, color);
+        }
+    }
+
+    fn fill(&mut self, path: &Path, options: &FillOptions, color: Color) {
+        let mut buffers = VertexBuffers::new();
+        let result = FillTessellator::new().tessellate_path(
+            path,
+            options,
+            &mut BuffersBuilder::new(&mut buffers, VertexConstructor { color }),
+        );
+
+        if result.is_ok() {
+            self.append(buffers);
+        }
+    }
+
+    fn stroke(&mut self, path: &Path, options: &StrokeOptions, color: Color) {
+        let mut buffers = VertexBuffers::new();
+        let result = StrokeTessellator::new().tessellate_path(
+            path,
+            options,
+            &mut BuffersBuilder::new(&mut buffers, VertexConstructor { color }),
+        );
+
+        if result.is_ok() {
+            self.append(buffers);
+        }
+    }
+
+    fn append(&mut self, buffers: VertexBuffers<Vertex2D, u32>) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(buffers.vertices);
+        self.indices
+            .extend(buffers.indices.into_iter().map(|index| index + offset));
+    }
+}
+
+impl FromStr for Svg {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> anyhow::Result<Self> {
+        Self::from_bytes(text.as_bytes())
+    }
+}
+
+impl HasBounds2D for Svg {
+    fn bounds(&self) -> AABB2D {
+        AABB2D::new(self.top_left, self.top_left + self.size())
+    }
+}
+
+impl Shape2D for Svg {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let vertices = self
+            .vertices
+            .iter()
+            .map(|vertex| {
+                let position = Vec2::from(vertex.position) * self.scale + self.top_left;
+                Vertex2D {
+                    position: position.into(),
+                    color: vertex.color,
+                }
+            })
+            .collect();
+        let indices = self.indices.iter().map(|n| n + starting_index).collect();
+        (indices, vertices)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        draw_queue.add_shape(self);
+    }
+}
+
+struct VertexConstructor {
+    color: Color,
+}
+
+impl FillVertexConstructor<Vertex2D> for VertexConstructor {
+    fn new_vertex(&mut self, vertex: FillVertex) -> Vertex2D {
+        let pos = vertex.position();
+        Vertex2D::new(pos.x, pos.y, self.color)
+    }
+}
+
+impl StrokeVertexConstructor<Vertex2D> for VertexConstructor {
+    fn new_vertex(&mut self, vertex: StrokeVertex) -> Vertex2D {
+        let pos = vertex.position();
+        Vertex2D::new(pos.x, pos.y, self.color)
+    }
+}
+
+fn to_lyon_path(data: &usvg::tiny_skia_path::Path, transform: usvg::Transform) -> Path {
+    let point = |mut point: usvg::tiny_skia_path::Point| {
+        transform.map_point(&mut point);
+        lyon::math::point(point.x, point.y)
+    };
+
+    let mut builder = Path::builder();
+    let mut open = false;
+
+    for segment in data.segments() {
+        match segment {
+            PathSegment::MoveTo(to) => {
+                if open {
+                    builder.end(false);
+                }
+                builder.begin(point(to));
+                open = true;
+            }
+            PathSegment::LineTo(to) => {
+                builder.line_to(point(to));
+            }
+            PathSegment::QuadTo(control, to) => {
+                builder.quadratic_bezier_to(point(control), point(to));
+            }
+            PathSegment::CubicTo(control_1, control_2, to) => {
+                builder.cubic_bezier_to(point(control_1), point(control_2), point(to));
+            }
+            PathSegment::Close => {
+                if open {
+                    builder.end(true);
+                    open = false;
+                }
+            }
+        }
+    }
+
+    if open {
+        builder.end(false);
+    }
+
+    builder.build()
+}
+
+/// Flat color for a paint. Gradients and patterns don't have one, so they get their first stop
+/// (or black for patterns).
+fn paint_color(paint: &usvg::Paint, opacity: f32) -> Color {
+    let (color, stop_opacity) = match paint {
+        usvg::Paint::Color(color) => (*color, 1.0),
+        usvg::Paint::LinearGradient(gradient) => gradient
+            .stops()
+            .first()
+            .map_or((usvg::Color::black(), 1.0), |stop| {
+                (stop.color(), stop.opacity().get())
+            }),
+        usvg::Paint::RadialGradient(gradient) => gradient
+            .stops()
+            .first()
+            .map_or((usvg::Color::black(), 1.0), |stop| {
+                (stop.color(), stop.opacity().get())
+            }),
+        usvg::Paint::Pattern(_) => (usvg::Color::black(), 1.0),
+    };
+
+    Color::from_rgba_u8(color.red, color.green, color.blue, 255).with_alpha(opacity * stop_opacity)
+}
@@ -0,0 +1,390 @@
+use bevy_math::{Vec2, Vec3};
+
+use crate::{
+    draw_queue_2d::MaterialVertex3D,
+    get_state,
+    image::Image,
+    materials::{Material, MaterialRef},
+    object_3d::{Object3D, Object3DRef},
+    programs::TERRAIN_3D_PROGRAM,
+    shapes_3d::MeshData,
+    textures::TextureRef,
+    utils::EngineCreate,
+};
+
+/// Grid of heights from 0 to 1. Rows go along +Z, columns along +X.
+#[derive(Clone, Debug)]
+pub struct Heightmap {
+    width: usize,
+    depth: usize,
+    heights: Vec<f32>,
+}
+
+impl Heightmap {
+    pub fn new(width: usize, depth: usize, heights: Vec<f32>) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            width >= 2 && depth >= 2,
+            "Heightmaps need at least 2x2 samples, got {width}x{depth}"
+        );
+        anyhow::ensure!(
+            heights.len() == width * depth,
+            "Heightmap is {width}x{depth} but has {} samples",
+            heights.len()
+        );
+
+        Ok(Self {
+            width,
+            depth,
+            heights,
+        })
+    }
+
+    /// Uses the red channel of the image, so any greyscale image works.
+    pub fn from_image(image: &Image) -> anyhow::Result<Self> {
+        let (width, depth) = (image.width(), image.height());
+        let heights = (0..depth)
+            .flat_map(|z| (0..width).map(move |x| image.get_pixel(x, z).unwrap().r_f32()))
+            .collect();
+
+        Self::new(width, depth, heights)
+    }
+
+    /// Loads an image file. 16 bit greyscale PNGs keep their full precision.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let image = image::load_from_memory(bytes)?.to_luma16();
+        let (width, depth) = image.dimensions();
+        let heights = image
+            .into_raw()
+            .into_iter()
+            .map(|h| h as f32 / u16::MAX as f32)
+            .collect();
+
+        Self::new(width as usize, depth as usize, heights)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Height of a single sample, clamped to the edges.
+    pub fn get(&self, x: usize, z: usize) -> f32 {
+        let x = x.min(self.width - 1);
+        let z = z.min(self.depth - 1);
+        self.heights[z * self.width + x]
+    }
+
+    /// Bilinearly interpolated height, in samples. Clamped to the edges.
+    pub fn sample(&self, x: f32, z: f32) -> f32 {
+        let x = x.clamp(0.0, (self.width - 1) as f32);
+        let z = z.clamp(0.0, (self.depth - 1) as f32);
+
+        let (x0, z0) = (x.floor() as usize, z.floor() as usize);
+        let (fx, fz) = (x.fract(), z.fract());
+
+        let top = lerp(self.get(x0, z0), self.get(x0 + 1, z0), fx);
+        let bottom = lerp(self.get(x0, z0 + 1), self.get(x0 + 1, z0 + 1), fx);
+        lerp(top, bottom, fz)
+    }
+
+    fn normal(&self, x: usize, z: usize, settings: &TerrainSettings) -> Vec3 {
+        let left = self.get(x.saturating_sub(1), z);
+        let right = self.get(x + 1, z);
+        let back = self.get(x, z.saturating_sub(1));
+        let front = self.get(x, z + 1);
+
+        Vec3::new(
+            (left - right) * settings.height_scale,
+            2.0 * settings.spacing,
+            (back - front) * settings.height_scale,
+        )
+        .normalize()
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TerrainSettings {
+    /// World position of the first heightmap sample. The terrain extends along +X and +Z.
+    pub origin: Vec3,
+    /// World distance between two heightmap samples.
+    pub spacing: f32,
+    /// World height of a heightmap value of 1.
+    pub height_scale: f32,
+    /// Samples along each side of a chunk at full detail.
+    pub chunk_size: usize,
+    /// How many levels of detail to build. Each level halves the resolution of the last.
+    pub lod_levels: usize,
+    /// Chunks closer than this use full detail. Every doubling of the distance after that
+    /// drops a level.
+    pub lod_distance: f32,
+}
+
+impl Default for TerrainSettings {
+    fn default() -> Self {
+        Self {
+            origin: Vec3::ZERO,
+            spacing: 1.0,
+            height_scale: 10.0,
+            chunk_size: 32,
+            lod_levels: 4,
+            lod_distance: 64.0,
+        }
+    }
+}
+
+impl TerrainSettings {
+    /// Level of detail to use for a chunk this far from the camera.
+    pub fn lod_for_distance(&self, distance: f32) -> usize {
+        if distance <= self.lod_distance {
+            return 0;
+        }
+
+        let level = (distance / self.lod_distance).log2().floor() as usize + 1;
+        level.min(self.lod_levels.saturating_sub(1))
+    }
+}
+
+struct TerrainChunk {
+    center: Vec3,
+    lods: Vec<Object3DRef>,
+}
+
+/// A heightmap turned into a grid of chunk meshes, each with a few levels of detail.
+pub struct Terrain {
+    heightmap: Heightmap,
+    settings: TerrainSettings,
+    chunks: Vec<TerrainChunk>,
+}
+
+impl Terrain {
+    pub fn new(
+        heightmap: Heightmap,
+        settings: TerrainSettings,
+        material: MaterialRef,
+    ) -> anyhow::Result<Self> {
+        let (chunks_x, chunks_z) = chunk_counts(&heightmap, &settings);
+        let mut chunks = Vec::with_capacity(chunks_x * chunks_z);
+
+        for chunk_z in 0..chunks_z {
+            for chunk_x in 0..chunks_x {
+                let lods = (0..settings.lod_levels.max(1))
+                    .map(|lod| {
+                        let mesh = chunk_mesh_data(&heightmap, &settings, chunk_x, chunk_z, lod)
+                            .build()?
+                            .create();
+                        Ok(Object3D::from_mesh_and_material(mesh, material))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+
+                let bounds = lods[0].mesh.bounds;
+                chunks.push(TerrainChunk {
+                    center: (bounds.min + bounds.max) * 0.5,
+                    lods,
+                });
+            }
+        }
+
+        Ok(Self {
+            heightmap,
+            settings,
+            chunks,
+        })
+    }
+
+    pub fn heightmap(&self) -> &Heightmap {
+        &self.heightmap
+    }
+
+    pub fn settings(&self) -> &TerrainSettings {
+        &self.settings
+    }
+
+    /// Size of the terrain on the X and Z axes, in world units.
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(
+            (self.heightmap.width - 1) as f32,
+            (self.heightmap.depth - 1) as f32,
+        ) * self.settings.spacing
+    }
+
+    /// World height of the ground at the given position, or None if it's outside the terrain.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        height_at(&self.heightmap, &self.settings, x, z)
+    }
+
+    /// Draws every chunk, picking its level of detail from the distance to the 3D camera.
+    pub fn draw(&self) {
+        let eye = get_state().camera_3d.eye;
+
+        for chunk in &self.chunks {
+            let lod = self.settings.lod_for_distance(chunk.center.distance(eye));
+            chunk.lods[lod.min(chunk.lods.len() - 1)].draw();
+        }
+    }
+}
+
+/// How many chunks the terrain is split into along X and Z.
+pub fn chunk_counts(heightmap: &Heightmap, settings: &TerrainSettings) -> (usize, usize) {
+    let chunk_size = settings.chunk_size.max(1);
+    (
+        (heightmap.width - 1).div_ceil(chunk_size),
+        (heightmap.depth - 1).div_ceil(chunk_size),
+    )
+}
+
+pub fn height_at(heightmap: &Heightmap, settings: &TerrainSettings, x: f32, z: f32) -> Option<f32> {
+    let local =
+        (Vec2::new(x, z) - Vec2::new(settings.origin.x, settings.origin.z)) / settings.spacing;
+
+    if local.x < 0.0
+        || local.y < 0.0
+        || local.x > (heightmap.width - 1) as f32
+        || local.y > (heightmap.depth - 1) as f32
+    {
+        return None;
+    }
+
+    Some(settings.origin.y + heightmap.sample(local.x, local.y) * settings.height_scale)
+}
+
+/// Sample indices along one side of a chunk at a level of detail. Always includes both ends,
+/// so neighbouring chunks share their edge vertices.
+fn chunk_samples(chunk: usize, chunk_size: usize, len: usize, lod: usize) -> Vec<usize> {
+    let start = chunk * chunk_size;
+    let end = ((chunk + 1) * chunk_size).min(len - 1);
+    let step = 1 << lod;
+
+    let mut samples: Vec<usize> = (start..end).step_by(step).collect();
+    samples.push(end);
+    samples
+}
+
+/// Mesh for one chunk, in world space. Edges get a skirt hanging down to the lowest point of the
+/// chunk, which hides the cracks between neighbouring chunks at different levels of detail.
+pub fn chunk_mesh_data(
+    heightmap: &Heightmap,
+    settings: &TerrainSettings,
+    chunk_x: usize,
+    chunk_z: usize,
+    lod: usize,
+) -> MeshData {
+    let chunk_size = settings.chunk_size.max(1);
+    let xs = chunk_samples(chunk_x, chunk_size, heightmap.width, lod);
+    let zs = chunk_samples(chunk_z, chunk_size, heightmap.depth, lod);
+    let uv_scale = Vec2::new(
+        1.0 / (heightmap.width - 1) as f32,
+        1.0 / (heightmap.depth - 1) as f32,
+    );
+
+    let mut data = MeshData::default();
+    let mut min_height = f32::INFINITY;
+
+    for &z in &zs {
+        for &x in &xs {
+            let height = settings.origin.y + heightmap.get(x, z) * settings.height_scale;
+            min_height = min_height.min(height);
+
+            let position = settings.origin
+                + Vec3::new(
+                    x as f32 * settings.spacing,
+                    0.0,
+                    z as f32 * settings.spacing,
+                );
+            data.vertices.push(MaterialVertex3D {
+                position: [position.x, height, position.z],
+                normal: heightmap.normal(x, z, settings).into(),
+                tex_coords: (Vec2::new(x as f32, z as f32) * uv_scale).into(),
+                tangent: [0.0; 4],
+            });
+        }
+    }
+
+    let columns = xs.len() as u32;
+    for row in 0..zs.len() as u32 - 1 {
+        for column in 0..columns - 1 {
+            let a = row * columns + column;
+            let b = a + columns;
+            data.indices
+                .extend_from_slice(&[a, b, a + 1, a + 1, b, b + 1]);
+        }
+    }
+
+    let rows = zs.len() as u32;
+    let edges: [Vec<u32>; 4] = [
+        (0..columns).collect(),
+        (0..columns).map(|c| (rows - 1) * columns + c).collect(),
+        (0..rows).map(|r| r * columns).collect(),
+        (0..rows).map(|r| r * columns + columns - 1).collect(),
+    ];
+    let skirt_bottom = min_height - settings.spacing * (1 << lod) as f32;
+
+    for edge in edges {
+        let first = data.vertices.len() as u32;
+        for &i in &edge {
+            let mut vertex = data.vertices[i as usize];
+            vertex.position[1] = skirt_bottom;
+            data.vertices.push(vertex);
+        }
+
+        for (n, pair) in edge.windows(2).enumerate() {
+            let (top_a, top_b) = (pair[0], pair[1]);
+            let (bottom_a, bottom_b) = (first + n as u32, first + n as u32 + 1);
+
+            // which way a skirt faces depends on the edge, so just give it both sides
+            data.indices.extend_from_slice(&[
+                top_a, bottom_a, top_b, top_b, bottom_a, bottom_b, top_a, top_b, bottom_a, top_b,
+                bottom_b, bottom_a,
+            ]);
+        }
+    }
+
+    data
+}
+
+/// Material for terrain textured from a splat map. `base` covers everything, then the red,
+/// green and blue channels of the splat map blend in `layers[0]`, `layers[1]` and `layers[2]`
+/// on top of it. `tiling` is how many times the layer textures repeat across the terrain.
+pub fn create_terrain_material(
+    splat_map: TextureRef,
+    base: TextureRef,
+    layers: [TextureRef; 3],
+    tiling: f32,
+) -> MaterialRef {
+    Material::new(TERRAIN_3D_PROGRAM)
+        .with_texture("splat_map", splat_map)
+        .with_texture("base_layer", base)
+        .with_texture("layer_r", layers[0])
+        .with_texture("layer_g", layers[1])
+        .with_texture("layer_b", layers[2])
+        .with_float("tiling", tiling)
+        .with_vec3("light_dir", Vec3::new(0.4, 1.0, 0.3))
+        .create()
+}
+
+/// Sets the terrain used by `draw_terrain` and `terrain_height_at`.
+pub fn set_terrain(terrain: Terrain) {
+    get_state().terrain = Some(terrain);
+}
+
+pub fn terrain() -> Option<&'static Terrain> {
+    get_state().terrain.as_ref()
+}
+
+pub fn draw_terrain() {
+    if let Some(terrain) = terrain() {
+        terrain.draw();
+    }
+}
+
+/// World height of the current terrain at the given position, or None if there's no terrain
+/// there.
+pub fn terrain_height_at(x: f32, z: f32) -> Option<f32> {
+    terrain()?.height_at(x, z)
+}
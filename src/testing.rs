@@ -0,0 +1,129 @@
+//! Stand-ins for the GPU side of the engine, so tests can run on machines without one.
+
+use bevy_math::{Mat4, Rect};
+
+use crate::{
+    draw_queue_2d::{CircleInstance, DrawTarget2D, SdfVertex, SpriteVertex, Stencil2D, Vertex3D},
+    materials::BlendMode,
+    textures::TextureRef,
+};
+
+#[derive(Clone, Debug)]
+pub(crate) enum DrawCall2D {
+    Shapes {
+        vertices: Vec<Vertex3D>,
+        indices: Vec<u32>,
+    },
+    Circles {
+        instances: Vec<CircleInstance>,
+    },
+    Sprites {
+        texture: TextureRef,
+        vertices: Vec<SpriteVertex>,
+        indices: Vec<u32>,
+    },
+    SdfGlyphs {
+        texture: TextureRef,
+        vertices: Vec<SdfVertex>,
+        indices: Vec<u32>,
+    },
+}
+
+/// Records everything a draw queue tries to draw instead of sending it to the GPU.
+#[derive(Default)]
+pub(crate) struct MockDrawTarget2D {
+    pub calls: Vec<DrawCall2D>,
+    pub projections: Vec<Mat4>,
+    pub blend_modes: Vec<BlendMode>,
+    /// Stencil state of each call.
+    pub stencils: Vec<Stencil2D>,
+    pub stencil_clears: usize,
+    /// Clip rect of each call.
+    pub clips: Vec<Option<Rect>>,
+    stencil: Stencil2D,
+    clip: Option<Rect>,
+}
+
+impl DrawTarget2D for MockDrawTarget2D {
+    fn draw_shapes(
+        &mut self,
+        vertices: &[Vertex3D],
+        indices: &[u32],
+        projection: &Mat4,
+        blend_mode: BlendMode,
+    ) {
+        self.projections.push(*projection);
+        self.blend_modes.push(blend_mode);
+        self.stencils.push(self.stencil);
+        self.clips.push(self.clip);
+        self.calls.push(DrawCall2D::Shapes {
+            vertices: vertices.to_vec(),
+            indices: indices.to_vec(),
+        });
+    }
+
+    fn draw_circles(
+        &mut self,
+        instances: &[CircleInstance],
+        projection: &Mat4,
+        blend_mode: BlendMode,
+    ) {
+        self.projections.push(*projection);
+        self.blend_modes.push(blend_mode);
+        self.stencils.push(self.stencil);
+        self.clips.push(self.clip);
+        self.calls.push(DrawCall2D::Circles {
+            instances: instances.to_vec(),
+        });
+    }
+
+    fn draw_sprites(
+        &mut self,
+        texture: TextureRef,
+        vertices: &[SpriteVertex],
+        indices: &[u32],
+        projection: &Mat4,
+        blend_mode: BlendMode,
+    ) {
+        self.projections.push(*projection);
+        self.blend_modes.push(blend_mode);
+        self.stencils.push(self.stencil);
+        self.clips.push(self.clip);
+        self.calls.push(DrawCall2D::Sprites {
+            texture,
+            vertices: vertices.to_vec(),
+            indices: indices.to_vec(),
+        });
+    }
+
+    fn draw_sdf_glyphs(
+        &mut self,
+        texture: TextureRef,
+        vertices: &[SdfVertex],
+        indices: &[u32],
+        projection: &Mat4,
+        blend_mode: BlendMode,
+    ) {
+        self.projections.push(*projection);
+        self.blend_modes.push(blend_mode);
+        self.stencils.push(self.stencil);
+        self.clips.push(self.clip);
+        self.calls.push(DrawCall2D::SdfGlyphs {
+            texture,
+            vertices: vertices.to_vec(),
+            indices: indices.to_vec(),
+        });
+    }
+
+    fn set_stencil(&mut self, stencil: Stencil2D) {
+        self.stencil = stencil;
+    }
+
+    fn clear_stencil(&mut self) {
+        self.stencil_clears += 1;
+    }
+
+    fn set_clip(&mut self, clip: Option<Rect>) {
+        self.clip = clip;
+    }
+}
@@ -0,0 +1,337 @@
+//! Text whose glyphs move, fade and change color on their own, like typewriter reveals, wavy
+//! text and shaking text in dialogue.
+
+use bevy_math::{Vec2, vec2};
+
+use crate::{
+    api::{default_font, delta_time, time, ui_scaling},
+    color::Color,
+    draw_queue_2d::DrawQueue2D,
+    get_state,
+    text_rendering::{
+        TextDimensions, TextDrawParams, chain_fonts, draw_glyph, layout_text_with_fallbacks,
+    },
+};
+
+/// One glyph of animated text, for effects to change before it's drawn.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnimatedGlyph {
+    pub character: char,
+    /// Where it is in the text, counting every character from 0.
+    pub index: usize,
+    /// Where it would normally be drawn, relative to the text's position.
+    pub position: Vec2,
+    /// Moves the glyph from `position`.
+    pub offset: Vec2,
+    /// Starts as the text's color. Set the alpha to 0 to hide the glyph.
+    pub color: Color,
+    /// Grows or shrinks the glyph around its centre.
+    pub scale: f32,
+}
+
+impl AnimatedGlyph {
+    pub fn new(character: char, index: usize, position: Vec2, color: Color) -> Self {
+        Self {
+            character,
+            index,
+            position,
+            offset: Vec2::ZERO,
+            color,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Ready made effects for [`draw_text_animated`] and [`TypewriterText`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextEffect {
+    /// Bobs glyphs up and down in a wave travelling along the text.
+    Wave {
+        /// How far glyphs move, in pixels.
+        amplitude: f32,
+        /// Waves per second.
+        speed: f32,
+        /// How many glyphs one wave covers.
+        wavelength: f32,
+    },
+    /// Jitters glyphs around, for shouting or fear.
+    Shake {
+        /// How far glyphs move, in pixels.
+        amount: f32,
+        /// How many times a second they jump to a new spot.
+        rate: f32,
+    },
+    /// Cycles the glyphs through every hue.
+    Rainbow {
+        /// Trips around the color wheel per second.
+        speed: f32,
+    },
+}
+
+impl TextEffect {
+    pub fn wave() -> Self {
+        Self::Wave {
+            amplitude: 4.0,
+            speed: 1.0,
+            wavelength: 8.0,
+        }
+    }
+
+    pub fn shake() -> Self {
+        Self::Shake {
+            amount: 1.5,
+            rate: 20.0,
+        }
+    }
+
+    pub fn rainbow() -> Self {
+        Self::Rainbow { speed: 0.5 }
+    }
+
+    /// Changes `glyph` for how the effect looks `time` seconds in.
+    pub fn apply(self, glyph: &mut AnimatedGlyph, time: f32) {
+        let index = glyph.index as f32;
+
+        match self {
+            Self::Wave {
+                amplitude,
+                speed,
+                wavelength,
+            } => {
+                let phase = (time * speed - index / wavelength) * std::f32::consts::TAU;
+                glyph.offset.y += phase.sin() * amplitude;
+            }
+            Self::Shake { amount, rate } => {
+                let step = (time * rate).floor() as u64;
+                let seed = (glyph.index as u64) << 32 ^ step;
+                glyph.offset += vec2(jitter(seed), jitter(seed ^ 0x9e37_79b9)) * amount;
+            }
+            Self::Rainbow { speed } => {
+                let hue = (time * speed * 360.0 + index * 20.0).rem_euclid(360.0);
+                glyph.color = Color::hsl(hue, 0.8, 0.65).with_alpha(glyph.color.a);
+            }
+        }
+    }
+}
+
+/// A repeatable random number from -1 to 1 for `seed`.
+fn jitter(seed: u64) -> f32 {
+    let mut x = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+    (x >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+}
+
+pub(crate) fn draw_text_animated_to(
+    text: impl AsRef<str>,
+    params: TextDrawParams,
+    mut effect: impl FnMut(&mut AnimatedGlyph),
+    draw_queue: &mut DrawQueue2D,
+) -> TextDimensions {
+    let text = text.as_ref();
+    if text.is_empty() || get_state().window_context.is_none() {
+        return TextDimensions::default();
+    }
+
+    let dpi_scaling = if params.do_dpi_scaling {
+        ui_scaling()
+    } else {
+        1.0
+    };
+    let font_size = (params.font_size as f32 * dpi_scaling).ceil();
+    let chain = params.font.unwrap_or(default_font()).chain();
+    let layout = layout_text_with_fallbacks(&chain_fonts(&chain), text, font_size);
+
+    for (index, laid_out) in layout.glyphs.iter().enumerate() {
+        let mut glyph =
+            AnimatedGlyph::new(laid_out.character, index, laid_out.position, params.color);
+        effect(&mut glyph);
+
+        if glyph.color.a <= 0.0 || laid_out.size == Vec2::ZERO {
+            continue;
+        }
+
+        let size = laid_out.size * glyph.scale;
+        let top_left = glyph.position + glyph.offset + (laid_out.size - size) / 2.0;
+        draw_glyph(
+            &chain,
+            laid_out,
+            font_size,
+            params.position + top_left,
+            size,
+            glyph.color,
+            draw_queue,
+        );
+    }
+
+    TextDimensions { size: layout.size }
+}
+
+/// Draws text on screen, letting `effect` move, scale and recolor each glyph first.
+///
+/// ```ignore
+/// draw_text_animated("Wheee", params, |glyph| TextEffect::wave().apply(glyph, time()));
+/// ```
+pub fn draw_text_animated(
+    text: impl AsRef<str>,
+    params: TextDrawParams,
+    effect: impl FnMut(&mut AnimatedGlyph),
+) -> TextDimensions {
+    draw_text_animated_to(text, params, effect, get_state().draw_queue_2d())
+}
+
+pub fn draw_text_animated_world(
+    text: impl AsRef<str>,
+    params: TextDrawParams,
+    effect: impl FnMut(&mut AnimatedGlyph),
+) -> TextDimensions {
+    draw_text_animated_to(text, params, effect, get_state().world_draw_queue_2d())
+}
+
+/// Text that appears a character at a time, like dialogue in an RPG. Call
+/// [`update`](Self::update) every frame and [`draw`](Self::draw) it.
+#[derive(Clone, Debug)]
+pub struct TypewriterText {
+    text: String,
+    /// Characters shown per second.
+    pub speed: f32,
+    /// Extra seconds to wait after `.`, `!`, `?` and `,`, so sentences have a rhythm.
+    pub punctuation_pause: f32,
+    /// Applied to every glyph on top of the reveal.
+    pub effects: Vec<TextEffect>,
+    /// How many characters are showing. The one after the last whole one fades in.
+    revealed: f32,
+    /// Time left before carrying on after punctuation.
+    pause: f32,
+    /// For effects, counting from when the text was set.
+    elapsed: f32,
+}
+
+impl TypewriterText {
+    pub fn new(text: impl Into<String>, speed: f32) -> Self {
+        Self {
+            text: text.into(),
+            speed,
+            punctuation_pause: 0.25,
+            effects: Vec::new(),
+            revealed: 0.0,
+            pause: 0.0,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn with_effect(mut self, effect: TextEffect) -> Self {
+        self.effects.push(effect);
+        self
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Swaps the text and starts revealing it from the beginning.
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.text = text.into();
+        self.restart();
+    }
+
+    pub fn restart(&mut self) {
+        self.revealed = 0.0;
+        self.pause = 0.0;
+        self.elapsed = 0.0;
+    }
+
+    /// Shows everything straight away, for when the player presses a button to skip.
+    pub fn skip(&mut self) {
+        self.revealed = self.len() as f32;
+        self.pause = 0.0;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.revealed >= self.len() as f32
+    }
+
+    /// How many characters are fully showing.
+    pub fn visible_chars(&self) -> usize {
+        self.revealed as usize
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    /// Moves on by this frame's [`delta_time`]. Returns how many characters appeared, which is
+    /// handy for playing a blip per character.
+    pub fn update(&mut self) -> usize {
+        self.advance(delta_time())
+    }
+
+    /// Moves on by `dt` seconds. Returns how many characters appeared.
+    pub fn advance(&mut self, dt: f32) -> usize {
+        self.elapsed += dt;
+        let before = self.visible_chars();
+        let len = self.len() as f32;
+        let mut dt = dt;
+
+        while dt > 0.0 && self.revealed < len {
+            if self.pause > 0.0 {
+                let waited = self.pause.min(dt);
+                self.pause -= waited;
+                dt -= waited;
+                continue;
+            }
+
+            // reveal up to the end of the current character, then see if it needs a pause
+            let next = self.revealed.floor() + 1.0;
+            let step = ((next - self.revealed) / self.speed).min(dt);
+            self.revealed += step * self.speed;
+            dt -= step;
+
+            if self.revealed >= next - 1e-4 {
+                self.revealed = next;
+                let character = self.text.chars().nth(next as usize - 1);
+                if matches!(character, Some('.' | '!' | '?' | ',')) {
+                    self.pause = self.punctuation_pause;
+                }
+            }
+        }
+
+        self.revealed = self.revealed.min(len);
+        self.visible_chars() - before
+    }
+
+    /// Fades in the glyph being revealed, hides the rest and applies [`Self::effects`].
+    pub fn apply(&self, glyph: &mut AnimatedGlyph) {
+        let shown = (self.revealed - glyph.index as f32).clamp(0.0, 1.0);
+        glyph.color.a *= shown;
+        glyph.scale *= 0.6 + 0.4 * shown;
+
+        for effect in &self.effects {
+            effect.apply(glyph, self.elapsed);
+        }
+    }
+
+    pub fn draw(&self, params: TextDrawParams) -> TextDimensions {
+        draw_text_animated(&self.text, params, |glyph| self.apply(glyph))
+    }
+
+    pub fn draw_world(&self, params: TextDrawParams) -> TextDimensions {
+        draw_text_animated_world(&self.text, params, |glyph| self.apply(glyph))
+    }
+}
+
+/// Like [`draw_text_animated`] with [`time`] passed to each effect, for text that's always
+/// animating, like a wavy title.
+pub fn draw_text_with_effects(
+    text: impl AsRef<str>,
+    params: TextDrawParams,
+    effects: &[TextEffect],
+) -> TextDimensions {
+    let time = time();
+    draw_text_animated(text, params, |glyph| {
+        for effect in effects {
+            effect.apply(glyph, time);
+        }
+    })
+}
@@ -1,34 +1,52 @@
-use std::{collections::HashMap, hash::Hash};
+use std::hash::Hash;
 
 use crate::utils::EngineCreate;
-use bevy_math::{IVec2, Rect, Vec2};
+use bevy_math::{Rect, Vec2, vec2};
+use bumpalo::Bump;
+use bumpalo::collections::Vec as BumpVec;
 use engine_4_macros::gen_ref_type;
-use fontdue::{Metrics, layout::TextStyle};
+use fontdue::{
+    Metrics,
+    layout::{CoordinateSystem, Layout, TextStyle as FontdueStyle},
+};
 use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter};
 
 use crate::{
-    api::{default_font, dpi_scaling},
+    api::{default_font, ui_scaling},
+    checked_state,
     color::{Color, u8::Pixel},
-    draw_queue_2d::DrawQueue2D,
+    draw_queue_2d::{DrawQueue2D, SdfLook},
+    error::{EngineError, EngineResult},
     get_state,
     image::Image,
-    prelude::{SpriteKey, TextureAtlas, Transform2D},
-    textures::TextureRef,
+    prelude::Transform2D,
+    textures::{
+        TextureRef,
+        glyph_atlas::{GlyphAtlas, MAX_PAGES},
+    },
+    with_frame_arena,
 };
 
 pub struct EngineFont {
     font: fontdue::Font,
-    atlas: TextureAtlas,
-    characters: HashMap<Glyph, CharacterInfo>,
+    glyphs: GlyphAtlas<Glyph>,
+    /// Tried in order for characters this font doesn't have.
+    fallbacks: Vec<FontRef>,
+    bold: Option<FontRef>,
+    italic: Option<FontRef>,
+    bold_italic: Option<FontRef>,
+    /// Filled in as the font is drawn with [`draw_text_sdf`].
+    sdf_glyphs: GlyphAtlas<char>,
 }
 
-#[derive(Clone, Copy, Debug)]
-pub(crate) struct CharacterInfo {
-    #[allow(unused)]
-    pub offset: IVec2,
-    pub advance: f32,
-    pub sprite: SpriteKey,
-}
+/// The size glyphs are rasterized at for distance fields, which are scaled from this to
+/// whatever size they're drawn at.
+const SDF_SIZE: f32 = 48.0;
+/// How far distance fields reach outside each glyph, in pixels at [`SDF_SIZE`]. Outlines and
+/// shadows can't reach further than this.
+const SDF_SPREAD: usize = 8;
+/// What distance fields start from, standing in for infinity without turning into NaNs.
+const SDF_FAR: f32 = 1e20;
 
 #[derive(Default)]
 pub struct TextDimensions {
@@ -46,6 +64,79 @@ gen_ref_type!(EngineFont, FontRef, fonts);
 
 #[allow(unused)]
 impl FontRef {
+    /// Uses `fallback` for characters this font doesn't have, like CJK or emoji, instead of
+    /// drawing boxes. Fallbacks are tried in the order they're added, and their own fallbacks
+    /// are tried after them.
+    pub fn add_fallback(&self, fallback: FontRef) {
+        if fallback != *self {
+            self.get_mut().fallbacks.push(fallback);
+        }
+    }
+
+    pub fn set_fallbacks(&self, fallbacks: impl IntoIterator<Item = FontRef>) {
+        self.get_mut().fallbacks.clear();
+        for fallback in fallbacks {
+            self.add_fallback(fallback);
+        }
+    }
+
+    pub fn fallbacks(&self) -> &'static [FontRef] {
+        &self.get().fallbacks
+    }
+
+    /// This font then all its fallbacks, in the order they're tried, without repeats. Fonts
+    /// that have been removed are reported and left out, and if that's this one the default
+    /// font is used instead.
+    pub fn chain(&self) -> Vec<FontRef> {
+        let first = match self.get_or_report() {
+            Some(_) => *self,
+            None => default_font(),
+        };
+        let mut chain = vec![first];
+        let mut next = 0;
+        while next < chain.len() {
+            for &fallback in chain[next].fallbacks() {
+                if !chain.contains(&fallback) && fallback.get_or_report().is_some() {
+                    chain.push(fallback);
+                }
+            }
+            next += 1;
+        }
+        chain
+    }
+
+    /// The font used for bold text drawn with this one, like in
+    /// [`draw_rich_text`](crate::gfx::draw_rich_text).
+    pub fn set_bold(&self, bold: FontRef) {
+        self.get_mut().bold = Some(bold);
+    }
+
+    pub fn set_italic(&self, italic: FontRef) {
+        self.get_mut().italic = Some(italic);
+    }
+
+    pub fn set_bold_italic(&self, bold_italic: FontRef) {
+        self.get_mut().bold_italic = Some(bold_italic);
+    }
+
+    /// The closest face of this font that's been set. Bold italic falls back to bold then
+    /// italic, and anything missing falls back to this font.
+    pub fn face(&self, bold: bool, italic: bool) -> FontRef {
+        let font = self.get();
+        let face = match (bold, italic) {
+            (true, true) => font.bold_italic.or(font.bold).or(font.italic),
+            (true, false) => font.bold,
+            (false, true) => font.italic,
+            (false, false) => None,
+        };
+        face.unwrap_or(*self)
+    }
+
+    /// Whether this font has its own glyph for `character`, not counting fallbacks.
+    pub fn has_glyph(&self, character: char) -> bool {
+        self.font.has_glyph(character)
+    }
+
     pub fn draw_text(&self, text: impl AsRef<str>, position: Vec2, size: usize) -> TextDimensions {
         draw_text_ex(
             text,
@@ -117,19 +208,16 @@ impl FontRef {
 }
 
 impl EngineFont {
-    pub(crate) fn load_from_bytes(bytes: &[u8]) -> anyhow::Result<EngineFont> {
-        Self::load_from_bytes_with_atlas(TextureAtlas::new()?, bytes)
-    }
-
-    pub(crate) fn load_from_bytes_with_atlas(
-        atlas: TextureAtlas,
-        bytes: &[u8],
-    ) -> anyhow::Result<Self> {
+    pub(crate) fn load_from_bytes(bytes: &[u8]) -> EngineResult<EngineFont> {
         Ok(Self {
             font: fontdue::Font::from_bytes(bytes, fontdue::FontSettings::default())
-                .map_err(|e| anyhow::anyhow!(e))?,
-            characters: HashMap::new(),
-            atlas,
+                .map_err(|error| EngineError::Font(error.to_string()))?,
+            glyphs: GlyphAtlas::new(MAX_PAGES),
+            fallbacks: Vec::new(),
+            bold: None,
+            italic: None,
+            bold_italic: None,
+            sdf_glyphs: GlyphAtlas::new(MAX_PAGES),
         })
     }
 
@@ -151,65 +239,49 @@ impl EngineFont {
         }
 
         let (metrics, bitmap) = self.rasterize_glyph(glyph);
-        let sprite = self.atlas.cache_sprite(&Image::new(
+        let image = Image::new(
             metrics.width,
             metrics.height,
             bitmap
                 .iter()
                 .map(|coverage| Pixel::from_rgba(255, 255, 255, *coverage))
                 .collect(),
-        ));
-        let advance = metrics.advance_width;
-        let offset = (metrics.xmin, metrics.ymin).into();
+        );
+        self.glyphs.insert(glyph, &image, current_frame());
+    }
 
-        let character_info = CharacterInfo {
-            advance,
-            offset,
-            sprite,
-        };
+    fn cache_sdf_glyph(&mut self, character: char) {
+        if self.sdf_glyphs.get(character, current_frame()).is_some() {
+            return;
+        }
 
-        self.characters.insert(glyph, character_info);
+        let (metrics, bitmap) = self.font.rasterize(character, SDF_SIZE);
+        let field = signed_distance_field(metrics.width, metrics.height, &bitmap, SDF_SPREAD);
+        let padding = SDF_SPREAD * 2;
+        let image = Image::new(
+            metrics.width + padding,
+            metrics.height + padding,
+            field
+                .iter()
+                .map(|distance| Pixel::from_rgba(255, 255, 255, *distance))
+                .collect(),
+        );
+        self.sdf_glyphs.insert(character, &image, current_frame());
     }
 
     pub(crate) fn contains(&self, glyph: Glyph) -> bool {
-        self.characters.contains_key(&glyph)
+        self.glyphs.contains(glyph)
     }
 
-    pub(crate) fn measure_text(
-        &mut self,
-        text: impl AsRef<str>,
-        font_size: f32,
-        do_dpi_scaling: bool,
-    ) -> TextDimensions {
-        let text = text.as_ref();
-
-        if text.is_empty() {
-            return TextDimensions::default();
-        }
-
-        let dpi_scaling = if do_dpi_scaling { dpi_scaling() } else { 1.0 };
-        let font_size = (font_size * dpi_scaling).ceil();
-        let mut layout =
-            fontdue::layout::Layout::new(fontdue::layout::CoordinateSystem::PositiveYDown);
-        layout.append(&[&self.font], &TextStyle::new(text, font_size, 0));
-
-        let mut width = 0.0;
-
-        for position in layout.glyphs() {
-            let glyph = Glyph {
-                character: position.parent,
-                size: font_size as usize,
-            };
-            if !self.contains(glyph) {
-                self.cache_glyph(glyph);
-            }
-            let char_info = self.characters[&glyph];
-            width += char_info.advance;
-        }
+    /// How many glyphs are cached for normal text, which goes down again when old pages are
+    /// cleared out.
+    pub fn cached_glyph_count(&self) -> usize {
+        self.glyphs.len()
+    }
 
-        TextDimensions {
-            size: Vec2::new(width, layout.height()),
-        }
+    /// How many textures the glyph cache is spread over.
+    pub fn glyph_page_count(&self) -> usize {
+        self.glyphs.page_count()
     }
 
     pub fn ascii_character_list() -> Vec<char> {
@@ -232,27 +304,36 @@ impl EngineFont {
     }
 
     pub fn set_minify_filter(&mut self, filter_mode: MinifySamplerFilter) {
-        self.atlas.set_minify_filter(filter_mode);
+        self.glyphs.set_minify_filter(filter_mode);
     }
 
     pub fn set_magnify_filter(&mut self, filter_mode: MagnifySamplerFilter) {
-        self.atlas.set_magnify_filter(filter_mode);
+        self.glyphs.set_magnify_filter(filter_mode);
     }
 
     pub fn use_linear_filtering(&mut self) {
-        self.atlas.use_linear_filtering();
+        self.set_magnify_filter(MagnifySamplerFilter::Linear);
+        self.set_minify_filter(MinifySamplerFilter::Linear);
     }
 
     pub fn use_nearest_filtering(&mut self) {
-        self.atlas.use_nearest_filtering();
+        self.set_magnify_filter(MagnifySamplerFilter::Nearest);
+        self.set_minify_filter(MinifySamplerFilter::Nearest);
     }
 
+    /// The first page of the glyph cache, handy for seeing what's in it.
     pub fn texture(&mut self) -> TextureRef {
-        self.atlas.texture().unwrap()
+        self.texture_page(0)
+    }
+
+    /// A page of the glyph cache. See [`glyph_page_count`](Self::glyph_page_count).
+    pub fn texture_page(&mut self, page: usize) -> TextureRef {
+        self.glyphs.texture(page)
     }
 }
 
-pub fn create_ttf_font(bytes: &[u8]) -> anyhow::Result<FontRef> {
+pub fn create_ttf_font(bytes: &[u8]) -> EngineResult<FontRef> {
+    checked_state()?;
     EngineFont::load_from_bytes(bytes).map(|f| f.create())
 }
 
@@ -262,7 +343,7 @@ pub struct TextDrawParams {
     pub font_size: usize,
     pub color: Color,
     pub position: Vec2,
-    /// scale the font size by the DPI scaling of your monitor
+    /// scale the font size by the DPI scaling of your monitor, and the player's `ui_scale`
     pub do_dpi_scaling: bool,
 }
 
@@ -280,9 +361,10 @@ impl Default for TextDrawParams {
 
 pub(crate) fn init_fonts() {
     let _ = load_font(include_bytes!("../assets/fonts/jetbrains.ttf"));
+    get_state().storage.fonts.mark_builtin();
 }
 
-fn draw_text_to(
+pub(crate) fn draw_text_to(
     text: impl AsRef<str>,
     params: TextDrawParams,
     draw_queue: &mut DrawQueue2D,
@@ -296,48 +378,694 @@ fn draw_text_to(
         do_dpi_scaling,
     } = params;
 
-    if text.is_empty() {
+    // glyphs are only rasterized into the atlas's textures when drawn, and there's nothing to
+    // draw on in headless mode
+    if text.is_empty() || get_state().window_context.is_none() {
         return TextDimensions::default();
     }
 
-    let dpi_scaling = if do_dpi_scaling { dpi_scaling() } else { 1.0 };
+    let dpi_scaling = if do_dpi_scaling { ui_scaling() } else { 1.0 };
     let font_size = (font_size as f32 * dpi_scaling).ceil();
-    let mut font = font.unwrap_or(default_font());
-    let mut layout = fontdue::layout::Layout::new(fontdue::layout::CoordinateSystem::PositiveYDown);
-    layout.append(&[&font.font], &TextStyle::new(text, font_size, 0));
+    let chain = font.unwrap_or(default_font()).chain();
+    with_frame_arena(|arena| {
+        let (glyphs, size) = layout_glyphs_in(&chain_fonts(&chain), text, font_size, arena);
+        draw_layout(&chain, &glyphs, font_size, pos, color, draw_queue);
+        TextDimensions { size }
+    })
+}
 
-    let mut width = 0.0;
+/// A coverage bitmap turned into a signed distance field, padded by `spread` on every side.
+/// Each byte is 128 on the glyph's edge, rising to 255 `spread` pixels inside it and falling
+/// to 0 `spread` pixels outside it.
+pub(crate) fn signed_distance_field(
+    width: usize,
+    height: usize,
+    coverage: &[u8],
+    spread: usize,
+) -> Vec<u8> {
+    let (padded_width, padded_height) = (width + spread * 2, height + spread * 2);
+    let mut to_inside = vec![SDF_FAR; padded_width * padded_height];
+    let mut to_outside = vec![SDF_FAR; padded_width * padded_height];
 
-    for glyph_position in layout.glyphs() {
-        let glyph = Glyph {
-            character: glyph_position.parent,
-            size: font_size as usize,
-        };
+    for y in 0..height {
+        for x in 0..width {
+            if coverage[y * width + x] >= 128 {
+                to_inside[(y + spread) * padded_width + x + spread] = 0.0;
+            }
+        }
+    }
+    for (to_outside, to_inside) in to_outside.iter_mut().zip(&to_inside) {
+        if *to_inside != 0.0 {
+            *to_outside = 0.0;
+        }
+    }
+
+    distance_transform(&mut to_inside, padded_width, padded_height);
+    distance_transform(&mut to_outside, padded_width, padded_height);
 
-        if !font.contains(glyph) {
+    to_inside
+        .iter()
+        .zip(&to_outside)
+        .map(|(&to_inside, &to_outside)| {
+            // distances are between pixel centres, and the edge is half way between them
+            let distance = if to_inside == 0.0 {
+                to_outside.sqrt() - 0.5
+            } else {
+                0.5 - to_inside.sqrt()
+            };
+            let value = 0.5 + distance / (spread * 2) as f32;
+            (value.clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect()
+}
+
+/// Replaces every cell of `grid` with its squared distance to the nearest cell that's 0. Cells
+/// start as either 0 or [`SDF_FAR`]. This is Felzenszwalb and Huttenlocher's exact distance
+/// transform, done down every column then along every row.
+fn distance_transform(grid: &mut [f32], width: usize, height: usize) {
+    let longest = width.max(height);
+    let mut line = vec![0.0; longest];
+    let mut distances = vec![0.0; longest];
+    let mut parabolas = vec![0; longest];
+    let mut boundaries = vec![0.0; longest + 1];
+
+    for x in 0..width {
+        for y in 0..height {
+            line[y] = grid[y * width + x];
+        }
+        distance_transform_1d(
+            &line[..height],
+            &mut distances,
+            &mut parabolas,
+            &mut boundaries,
+        );
+        for y in 0..height {
+            grid[y * width + x] = distances[y];
+        }
+    }
+
+    for y in 0..height {
+        let row = &mut grid[y * width..(y + 1) * width];
+        distance_transform_1d(row, &mut distances, &mut parabolas, &mut boundaries);
+        row.copy_from_slice(&distances[..width]);
+    }
+}
+
+/// The lower envelope of a parabola rooted at each cell, which gives each cell's squared
+/// distance to the nearest cell, plus that cell's own value.
+fn distance_transform_1d(
+    values: &[f32],
+    distances: &mut [f32],
+    parabolas: &mut [usize],
+    boundaries: &mut [f32],
+) {
+    let intersection = |q: usize, p: usize| {
+        let (qf, pf) = (q as f32, p as f32);
+        ((values[q] + qf * qf) - (values[p] + pf * pf)) / (2.0 * qf - 2.0 * pf)
+    };
+
+    let mut k = 0;
+    parabolas[0] = 0;
+    boundaries[0] = f32::NEG_INFINITY;
+    boundaries[1] = f32::INFINITY;
+
+    for q in 1..values.len() {
+        let mut s = intersection(q, parabolas[k]);
+        while s <= boundaries[k] {
+            k -= 1;
+            s = intersection(q, parabolas[k]);
+        }
+        k += 1;
+        parabolas[k] = q;
+        boundaries[k] = s;
+        boundaries[k + 1] = f32::INFINITY;
+    }
+
+    k = 0;
+    for (q, distance) in distances.iter_mut().enumerate().take(values.len()) {
+        while boundaries[k + 1] < q as f32 {
+            k += 1;
+        }
+        let offset = q as f32 - parabolas[k] as f32;
+        *distance = offset * offset + values[parabolas[k]];
+    }
+}
+
+/// Draws glyphs laid out from the fonts in `chain`, with `position` as the top left.
+pub(crate) fn draw_layout(
+    chain: &[FontRef],
+    glyphs: &[LaidOutGlyph],
+    font_size: f32,
+    position: Vec2,
+    color: Color,
+    draw_queue: &mut DrawQueue2D,
+) {
+    for laid_out in glyphs {
+        draw_glyph(
+            chain,
+            laid_out,
+            font_size,
+            laid_out.position + position,
+            laid_out.size,
+            color,
+            draw_queue,
+        );
+    }
+}
+
+/// Draws one glyph from a layout made with `chain`, cached at `font_size`, stretched over
+/// `top_left` and `size`.
+pub(crate) fn draw_glyph(
+    chain: &[FontRef],
+    laid_out: &LaidOutGlyph,
+    font_size: f32,
+    top_left: Vec2,
+    size: Vec2,
+    color: Color,
+    draw_queue: &mut DrawQueue2D,
+) {
+    let font = chain[laid_out.font].get_mut();
+    let glyph = Glyph {
+        character: laid_out.character,
+        size: font_size as usize,
+    };
+
+    let frame = current_frame();
+    let entry = match font.glyphs.get(glyph, frame) {
+        Some(entry) => entry,
+        None => {
             font.cache_glyph(glyph);
+            font.glyphs.get(glyph, frame).unwrap()
         }
+    };
+
+    let transform = Transform2D::from_scale_translation(size, top_left);
+    let texture = font.glyphs.texture(entry.page);
+
+    draw_queue.add_sprite(texture, transform, color, Some(entry.rect.into()));
+}
+
+/// For keeping track of which glyph pages are in use, so ones that are about to be drawn
+/// aren't cleared out.
+fn current_frame() -> u64 {
+    get_state().frame_count as u64
+}
+
+pub(crate) fn chain_fonts(chain: &[FontRef]) -> Vec<&'static fontdue::Font> {
+    chain.iter().map(|font| &font.get().font).collect()
+}
 
-        let char_info = font.characters[&glyph];
-        let sprite = font.atlas.get(char_info.sprite).unwrap();
-        let rect = sprite.rect;
-        let rectf: Rect = rect.into();
+#[derive(Clone, Copy, Debug)]
+pub struct LaidOutGlyph {
+    pub character: char,
+    /// Which of the fonts it's from, for text laid out with fallbacks.
+    pub font: usize,
+    /// Top left of the glyph's bitmap, relative to where the text is drawn.
+    pub position: Vec2,
+    pub size: Vec2,
+    pub advance: f32,
+}
 
-        width += char_info.advance;
+#[derive(Clone, Debug, Default)]
+pub struct TextLayout {
+    pub glyphs: Vec<LaidOutGlyph>,
+    pub size: Vec2,
+}
 
-        let transform = Transform2D::from_scale_translation(
-            Vec2::new(glyph_position.width as f32, glyph_position.height as f32),
-            Vec2::new(glyph_position.x + pos.x, glyph_position.y + pos.y),
+/// Positions every glyph in `text` without touching the GPU.
+pub fn layout_text(font: &fontdue::Font, text: &str, font_size: f32) -> TextLayout {
+    layout_text_with_fallbacks(&[font], text, font_size)
+}
+
+/// Like [`layout_text`], but each character comes from the first of `fonts` that has it, or
+/// the first font if none of them do. Drawing and measuring text both go through this.
+pub fn layout_text_with_fallbacks(
+    fonts: &[&fontdue::Font],
+    text: &str,
+    font_size: f32,
+) -> TextLayout {
+    let mut glyphs = Vec::new();
+    let size = layout_glyphs(fonts, text, font_size, &mut glyphs);
+    TextLayout { glyphs, size }
+}
+
+/// Like [`layout_text_with_fallbacks`], but the glyphs go in `arena`. For drawing, where the
+/// layout is thrown away straight after.
+pub(crate) fn layout_glyphs_in<'a>(
+    fonts: &[&fontdue::Font],
+    text: &str,
+    font_size: f32,
+    arena: &'a Bump,
+) -> (BumpVec<'a, LaidOutGlyph>, Vec2) {
+    let mut glyphs = BumpVec::new_in(arena);
+    let size = layout_glyphs(fonts, text, font_size, &mut glyphs);
+    (glyphs, size)
+}
+
+/// Adds every glyph of `text` to `glyphs`, and returns the size of the whole thing.
+fn layout_glyphs(
+    fonts: &[&fontdue::Font],
+    text: &str,
+    font_size: f32,
+    glyphs: &mut impl Extend<LaidOutGlyph>,
+) -> Vec2 {
+    let mut layout = Layout::new(CoordinateSystem::PositiveYDown);
+    for (range, font_index) in font_runs(fonts, text) {
+        layout.append(
+            fonts,
+            &FontdueStyle::new(&text[range], font_size, font_index),
         );
+    }
+
+    let mut width = 0.0;
+    glyphs.extend(layout.glyphs().iter().map(|glyph| {
+        let advance = fonts[glyph.font_index]
+            .metrics(glyph.parent, font_size)
+            .advance_width;
+        width += advance;
+
+        LaidOutGlyph {
+            character: glyph.parent,
+            font: glyph.font_index,
+            position: Vec2::new(glyph.x, glyph.y),
+            size: Vec2::new(glyph.width as f32, glyph.height as f32),
+            advance,
+        }
+    }));
+
+    Vec2::new(width, layout.height())
+}
+
+/// Splits `text` into runs that each come from one font, as byte ranges and indices into
+/// `fonts`. Whitespace stays with the run before it, so spaces don't break runs up.
+fn font_runs(fonts: &[&fontdue::Font], text: &str) -> Vec<(std::ops::Range<usize>, usize)> {
+    let mut runs: Vec<(std::ops::Range<usize>, usize)> = Vec::new();
+    for (start, character) in text.char_indices() {
+        let end = start + character.len_utf8();
+        let font = match runs.last() {
+            Some(&(_, previous)) if character.is_whitespace() => previous,
+            _ => fonts
+                .iter()
+                .position(|font| font.has_glyph(character))
+                .unwrap_or(0),
+        };
+
+        match runs.last_mut() {
+            Some((range, previous)) if *previous == font => range.end = end,
+            _ => runs.push((start..end, font)),
+        }
+    }
+    runs
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+    /// Stretches the spaces in wrapped lines so they fill the box's width. The last line of
+    /// each paragraph stays left aligned.
+    Justify,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerticalAlign {
+    #[default]
+    Top,
+    Middle,
+    Bottom,
+}
 
-        draw_queue.add_sprite(font.atlas.texture().unwrap(), transform, color, Some(rectf));
+/// How text is drawn in a box, for [`draw_text_boxed`] and [`measure_text`].
+#[derive(Clone, Copy)]
+pub struct TextStyle {
+    pub font: Option<FontRef>,
+    pub font_size: usize,
+    pub color: Color,
+    /// scale the font size by the DPI scaling of your monitor, and the player's `ui_scale`
+    pub do_dpi_scaling: bool,
+    pub align: TextAlign,
+    pub vertical_align: VerticalAlign,
+    /// Breaks lines between words so they fit the box's width. Lines always break at `\n`.
+    /// Words longer than a whole line aren't broken up.
+    pub wrap: bool,
+    /// Cuts off text that doesn't fit in the box with `…`, instead of letting it spill out.
+    pub ellipsis: bool,
+    /// Multiplies the font's line height.
+    pub line_spacing: f32,
+}
+
+impl Default for TextStyle {
+    fn default() -> Self {
+        Self {
+            font: None,
+            font_size: 16,
+            color: Color::WHITE,
+            do_dpi_scaling: false,
+            align: TextAlign::Left,
+            vertical_align: VerticalAlign::Top,
+            wrap: true,
+            ellipsis: false,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+struct BoxedLine {
+    text: String,
+    /// Whether the line was wrapped, so should be stretched when justifying.
+    justify: bool,
+}
+
+/// Lays out `text` to fit in a box of `size` without touching the GPU, with glyph positions
+/// relative to the box's top left. An infinite width never wraps, and infinite sizes line text
+/// up against its own bounds instead.
+pub fn layout_text_boxed(
+    fonts: &[&fontdue::Font],
+    text: &str,
+    font_size: f32,
+    size: Vec2,
+    style: &TextStyle,
+) -> TextLayout {
+    if text.is_empty() {
+        return TextLayout::default();
+    }
+
+    let width = |line: &str| layout_text_with_fallbacks(fonts, line, font_size).size.x;
+    let wrap_width = if style.wrap { size.x } else { f32::INFINITY };
+    let mut lines = wrap_lines(text, wrap_width, width);
+
+    let line_height = fonts[0]
+        .horizontal_line_metrics(font_size)
+        .map_or(font_size, |metrics| metrics.new_line_size)
+        * style.line_spacing;
+
+    if style.ellipsis {
+        let max_lines = ((size.y / line_height).floor() as usize).max(1);
+        let cut = lines.len() > max_lines;
+        lines.truncate(max_lines);
+
+        let last = lines.len() - 1;
+        for (i, line) in lines.iter_mut().enumerate() {
+            if (cut && i == last) || width(&line.text) > size.x {
+                line.text = ellipsize(&line.text, size.x, width);
+                line.justify = false;
+            }
+        }
+    }
+
+    let laid_out: Vec<TextLayout> = lines
+        .iter()
+        .map(|line| layout_text_with_fallbacks(fonts, &line.text, font_size))
+        .collect();
+    let text_width = laid_out
+        .iter()
+        .map(|layout| layout.size.x)
+        .fold(0.0, f32::max);
+    let text_height = line_height * lines.len() as f32;
+    let box_size = vec2(
+        if size.x.is_finite() {
+            size.x
+        } else {
+            text_width
+        },
+        if size.y.is_finite() {
+            size.y
+        } else {
+            text_height
+        },
+    );
+
+    let top = match style.vertical_align {
+        VerticalAlign::Top => 0.0,
+        VerticalAlign::Middle => (box_size.y - text_height) / 2.0,
+        VerticalAlign::Bottom => box_size.y - text_height,
+    };
+
+    let mut glyphs = Vec::new();
+    let mut widest = 0.0_f32;
+    for (i, (line, layout)) in lines.iter().zip(laid_out).enumerate() {
+        let free = box_size.x - layout.size.x;
+        let gaps = line.text.chars().filter(|c| c.is_whitespace()).count();
+        let (left, gap) = match style.align {
+            TextAlign::Left => (0.0, 0.0),
+            TextAlign::Center => (free / 2.0, 0.0),
+            TextAlign::Right => (free, 0.0),
+            TextAlign::Justify if line.justify && gaps > 0 && free > 0.0 => {
+                (0.0, free / gaps as f32)
+            }
+            TextAlign::Justify => (0.0, 0.0),
+        };
+        widest = widest.max(layout.size.x + gap * gaps as f32);
+
+        let y = top + line_height * i as f32;
+        let mut spaces = 0;
+        for mut glyph in layout.glyphs {
+            if glyph.character.is_whitespace() {
+                spaces += 1;
+            }
+            glyph.position += vec2(left + gap * spaces as f32, y);
+            glyphs.push(glyph);
+        }
+    }
+
+    TextLayout {
+        glyphs,
+        size: vec2(widest, text_height),
+    }
+}
+
+/// Splits `text` into lines at `\n`, and between words wherever a line would be wider than
+/// `max_width`.
+fn wrap_lines(text: &str, max_width: f32, width: impl Fn(&str) -> f32) -> Vec<BoxedLine> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+        let mut start = 0;
+        let mut end = None;
+
+        for word in paragraph.split_whitespace() {
+            let word_start = word.as_ptr() as usize - paragraph.as_ptr() as usize;
+            let word_end = word_start + word.len();
+
+            if let Some(line_end) = end
+                && width(&paragraph[start..word_end]) > max_width
+            {
+                lines.push(BoxedLine {
+                    text: paragraph[start..line_end].to_string(),
+                    justify: true,
+                });
+                start = word_start;
+            }
+            end = Some(word_end);
+        }
+
+        lines.push(BoxedLine {
+            text: paragraph[start..].trim_end().to_string(),
+            justify: false,
+        });
+    }
+
+    lines
+}
+
+/// Cuts characters off the end of `line` until it fits in `max_width` with `…` after it.
+fn ellipsize(line: &str, max_width: f32, width: impl Fn(&str) -> f32) -> String {
+    let mut end = line.len();
+    loop {
+        let candidate = format!("{}…", line[..end].trim_end());
+        if end == 0 || width(&candidate) <= max_width {
+            return candidate;
+        }
+        end = line[..end].char_indices().next_back().map_or(0, |(i, _)| i);
+    }
+}
+
+pub(crate) fn draw_text_boxed_to(
+    text: impl AsRef<str>,
+    rect: Rect,
+    style: TextStyle,
+    draw_queue: &mut DrawQueue2D,
+) -> TextDimensions {
+    let text = text.as_ref();
+
+    if text.is_empty() || get_state().window_context.is_none() {
+        return TextDimensions::default();
+    }
+
+    let dpi_scaling = if style.do_dpi_scaling {
+        ui_scaling()
+    } else {
+        1.0
+    };
+    let font_size = (style.font_size as f32 * dpi_scaling).ceil();
+    let chain = style.font.unwrap_or(default_font()).chain();
+    let layout = layout_text_boxed(&chain_fonts(&chain), text, font_size, rect.size(), &style);
+    draw_layout(
+        &chain,
+        &layout.glyphs,
+        font_size,
+        rect.min,
+        style.color,
+        draw_queue,
+    );
+
+    TextDimensions { size: layout.size }
+}
+
+/// Draws `text` inside `rect` on screen, wrapped and lined up as `style` says. Text can still
+/// spill out of the box unless [`TextStyle::ellipsis`] is on.
+pub fn draw_text_boxed(text: impl AsRef<str>, rect: Rect, style: TextStyle) -> TextDimensions {
+    draw_text_boxed_to(text, rect, style, get_state().draw_queue_2d())
+}
+
+pub fn draw_text_boxed_world(
+    text: impl AsRef<str>,
+    rect: Rect,
+    style: TextStyle,
+) -> TextDimensions {
+    draw_text_boxed_to(text, rect, style, get_state().world_draw_queue_2d())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextOutline {
+    /// In pixels at the text's size, or world units for world text. Can't be wider than about
+    /// a sixth of the font size.
+    pub width: f32,
+    pub color: Color,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TextShadow {
+    /// Which way the shadow falls, in the same units as the font size. Like outlines, it can't
+    /// go much further than a sixth of the font size.
+    pub offset: Vec2,
+    pub color: Color,
+    /// How blurry the shadow's edge is, in the same units.
+    pub softness: f32,
+}
+
+/// Text drawn with [`draw_text_sdf`], which stays sharp at any size or zoom.
+#[derive(Clone, Copy)]
+pub struct SdfTextParams {
+    pub font: Option<FontRef>,
+    /// Doesn't have to be a whole number, unlike normal text.
+    pub font_size: f32,
+    pub color: Color,
+    pub position: Vec2,
+    /// scale the font size by the DPI scaling of your monitor, and the player's `ui_scale`
+    pub do_dpi_scaling: bool,
+    pub outline: Option<TextOutline>,
+    pub shadow: Option<TextShadow>,
+}
+
+impl Default for SdfTextParams {
+    fn default() -> Self {
+        Self {
+            font: None,
+            font_size: 16.0,
+            color: Color::WHITE,
+            position: Vec2::ZERO,
+            do_dpi_scaling: false,
+            outline: None,
+            shadow: None,
+        }
+    }
+}
+
+pub(crate) fn draw_text_sdf_to(
+    text: impl AsRef<str>,
+    params: SdfTextParams,
+    draw_queue: &mut DrawQueue2D,
+) -> TextDimensions {
+    let text = text.as_ref();
+    if text.is_empty() || get_state().window_context.is_none() {
+        return TextDimensions::default();
+    }
+
+    with_frame_arena(|arena| draw_text_sdf_in(text, params, draw_queue, arena))
+}
+
+/// [`draw_text_sdf_to`], with the glyphs laid out in `arena`.
+fn draw_text_sdf_in(
+    text: &str,
+    params: SdfTextParams,
+    draw_queue: &mut DrawQueue2D,
+    arena: &Bump,
+) -> TextDimensions {
+    let dpi_scaling = if params.do_dpi_scaling {
+        ui_scaling()
+    } else {
+        1.0
+    };
+    let scale = params.font_size * dpi_scaling / SDF_SIZE;
+    let chain = params.font.unwrap_or(default_font()).chain();
+    let (glyphs, layout_size) = layout_glyphs_in(&chain_fonts(&chain), text, SDF_SIZE, arena);
+    let visible = || glyphs.iter().filter(|glyph| glyph.size != Vec2::ZERO);
+
+    // pages are written when their texture is asked for, so everything gets cached first to
+    // only write each page once
+    for glyph in visible() {
+        chain[glyph.font].get_mut().cache_sdf_glyph(glyph.character);
+    }
+
+    // from units at the drawn size to distance field units
+    let to_field = |length: f32| length / scale / (SDF_SPREAD * 2) as f32;
+    let outline = params.outline.unwrap_or(TextOutline {
+        width: 0.0,
+        color: Color::TRANSPARENT,
+    });
+    let shadow = params.shadow.unwrap_or(TextShadow {
+        offset: Vec2::ZERO,
+        color: Color::TRANSPARENT,
+        softness: 0.0,
+    });
+
+    let padding = SDF_SPREAD as f32;
+    for glyph in visible() {
+        let sdf_glyphs = &mut chain[glyph.font].get_mut().sdf_glyphs;
+        let entry = sdf_glyphs.get(glyph.character, current_frame()).unwrap();
+        let texture = sdf_glyphs.texture(entry.page);
+        let tex_size = texture.dimensions().as_vec2();
+        let region: Rect = entry.rect.into();
+
+        draw_queue.add_sdf_glyph(
+            texture,
+            params.position + (glyph.position - padding) * scale,
+            (glyph.size + padding * 2.0) * scale,
+            Rect {
+                min: region.min / tex_size,
+                max: region.max / tex_size,
+            },
+            SdfLook {
+                color: params.color,
+                outline_width: to_field(outline.width).min(0.45),
+                outline_color: outline.color,
+                shadow_offset: shadow.offset / scale / tex_size,
+                shadow_softness: to_field(shadow.softness),
+                shadow_color: shadow.color,
+            },
+        );
     }
 
     TextDimensions {
-        size: Vec2::new(width, layout.height()),
+        size: layout_size * scale,
     }
 }
 
+/// Draws text from a signed distance field instead of a bitmap at one size, so it stays sharp
+/// however big it's drawn and can have an outline and a drop shadow.
+pub fn draw_text_sdf(text: impl AsRef<str>, params: SdfTextParams) -> TextDimensions {
+    draw_text_sdf_to(text, params, get_state().draw_queue_2d())
+}
+
+/// [`draw_text_sdf`] in the world, where it stays sharp when the camera zooms in.
+pub fn draw_text_sdf_world(text: impl AsRef<str>, params: SdfTextParams) -> TextDimensions {
+    draw_text_sdf_to(text, params, get_state().world_draw_queue_2d())
+}
+
 pub fn draw_text_ex(text: impl AsRef<str>, params: TextDrawParams) -> TextDimensions {
     draw_text_to(text, params, get_state().draw_queue_2d())
 }
@@ -392,12 +1120,29 @@ pub fn draw_text_size_world(text: impl AsRef<str>, position: Vec2, size: usize)
     )
 }
 
-pub fn load_font(bytes: &[u8]) -> Result<FontRef, anyhow::Error> {
+pub fn load_font(bytes: &[u8]) -> EngineResult<FontRef> {
+    checked_state()?;
     EngineFont::load_from_bytes(bytes).map(|f| f.create())
 }
 
-pub fn measure_text(text: impl AsRef<str>) -> TextDimensions {
-    measure_text_ex(text, TextDrawParams::default())
+/// The size of `text` drawn with `style`, only breaking lines at `\n`.
+pub fn measure_text(text: impl AsRef<str>, style: TextStyle) -> Vec2 {
+    measure_text_boxed(text, f32::INFINITY, style)
+}
+
+/// The size of `text` drawn with `style` in a box `width` wide, for working out how tall a
+/// box needs to be.
+pub fn measure_text_boxed(text: impl AsRef<str>, width: f32, style: TextStyle) -> Vec2 {
+    let dpi_scaling = if style.do_dpi_scaling {
+        ui_scaling()
+    } else {
+        1.0
+    };
+    let font_size = (style.font_size as f32 * dpi_scaling).ceil();
+    let chain = style.font.unwrap_or(default_font()).chain();
+    let size = vec2(width, f32::INFINITY);
+
+    layout_text_boxed(&chain_fonts(&chain), text.as_ref(), font_size, size, &style).size
 }
 
 /// Does not take translatation, rotation or colour into account. Feel free to exclude them.
@@ -409,6 +1154,16 @@ pub fn measure_text_ex(text: impl AsRef<str>, params: TextDrawParams) -> TextDim
         ..
     } = params;
 
-    font.unwrap_or(default_font())
-        .measure_text(text, font_size as f32, do_dpi_scaling)
+    let text = text.as_ref();
+    if text.is_empty() {
+        return TextDimensions::default();
+    }
+
+    let dpi_scaling = if do_dpi_scaling { ui_scaling() } else { 1.0 };
+    let font_size = (font_size as f32 * dpi_scaling).ceil();
+    let chain = font.unwrap_or(default_font()).chain();
+
+    TextDimensions {
+        size: layout_text_with_fallbacks(&chain_fonts(&chain), text, font_size).size,
+    }
 }
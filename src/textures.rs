@@ -4,15 +4,23 @@ use bevy_math::{UVec2, Vec2};
 use engine_4_macros::gen_ref_type;
 use glium::{
     Texture2d, implement_vertex,
-    texture::{RawImage2d, TextureCreationError},
-    uniforms::{MagnifySamplerFilter, MinifySamplerFilter},
+    texture::{CompressedTexture2d, RawImage2d, TextureCreationError},
+    uniforms::{
+        AsUniformValue, MagnifySamplerFilter, MinifySamplerFilter, SamplerBehavior, UniformValue,
+    },
 };
 use image::ImageFormat;
 
+use crate::error::{EngineResult, OrReport};
+use crate::textures::compressed::{BlockFormat, decode_blocks};
 use crate::utils::EngineCreate;
-use crate::{EngineDisplay, EngineStorage, get_state, image::Image};
+use crate::{EngineDisplay, EngineStorage, checked_state, get_state, image::Image};
 
+pub(crate) mod async_loading;
 pub mod atlas;
+pub mod atlas_builder;
+pub(crate) mod compressed;
+pub(crate) mod glyph_atlas;
 
 // pub const DUMMY_TEXTURE: TextureRef = TextureRef(0);
 
@@ -61,16 +69,20 @@ pub fn init_textures(_storage: &mut EngineStorage, _display: &EngineDisplay) {
     // storage.textures.push(dummy);
 }
 
-pub fn load_texture(bytes: &[u8], format: ImageFormat) -> anyhow::Result<TextureRef> {
+pub fn load_texture(bytes: &[u8], format: ImageFormat) -> EngineResult<TextureRef> {
+    checked_state()?;
     Ok(EngineTexture::load_from_bytes(bytes, format)?.create())
 }
 
 pub struct EngineTexture {
     pub dimensions: UVec2,
     pub normalized_dimensions: Vec2,
+    /// For textures that are still compressed on the GPU, this is a 1x1 placeholder, since
+    /// they can only be sampled. [`sampled`](Self::sampled) works for both.
     pub gl_texture: Texture2d,
     pub magnify_filter: MagnifySamplerFilter,
     pub minify_filter: MinifySamplerFilter,
+    pub(crate) compressed: Option<(CompressedTexture2d, BlockFormat)>,
 }
 
 impl EngineTexture {
@@ -78,7 +90,7 @@ impl EngineTexture {
         &self.gl_texture
     }
 
-    pub fn load_from_bytes(bytes: &[u8], format: ImageFormat) -> anyhow::Result<Self> {
+    pub fn load_from_bytes(bytes: &[u8], format: ImageFormat) -> EngineResult<Self> {
         let image = image::load(Cursor::new(bytes), format)?.to_rgba8();
         let image_dimensions = image.dimensions();
         let image = RawImage2d::from_raw_rgba(image.into_raw(), image_dimensions);
@@ -94,9 +106,41 @@ impl EngineTexture {
             normalized_dimensions: Self::create_normalized_dimensions(dimensions.x, dimensions.y),
             magnify_filter: state.config.default_magnify_filter,
             minify_filter: state.config.default_minify_filter,
+            compressed: None,
         }
     }
 
+    pub(crate) fn from_compressed(
+        texture: CompressedTexture2d,
+        format: BlockFormat,
+    ) -> Result<Self, TextureCreationError> {
+        let dimensions = UVec2::new(texture.width(), texture.height());
+        let mut engine_texture = Self::empty(1, 1)?;
+        engine_texture.dimensions = dimensions;
+        engine_texture.normalized_dimensions =
+            Self::create_normalized_dimensions(dimensions.x, dimensions.y);
+        engine_texture.compressed = Some((texture, format));
+        Ok(engine_texture)
+    }
+
+    /// The texture as a uniform, sampled with `behaviour`. Unlike `gl_texture`, this works for
+    /// compressed textures too.
+    pub(crate) fn uniform(&self, behaviour: SamplerBehavior) -> TextureUniform<'_> {
+        TextureUniform(match &self.compressed {
+            Some((texture, _)) => UniformValue::CompressedTexture2d(texture, Some(behaviour)),
+            None => UniformValue::Texture2d(&self.gl_texture, Some(behaviour)),
+        })
+    }
+
+    /// [`uniform`](Self::uniform) with the texture's own filters.
+    pub(crate) fn sampled(&self) -> TextureUniform<'_> {
+        self.uniform(SamplerBehavior {
+            magnify_filter: self.magnify_filter,
+            minify_filter: self.minify_filter,
+            ..Default::default()
+        })
+    }
+
     pub(crate) fn create_normalized_dimensions(width: u32, height: u32) -> Vec2 {
         let mut v = Vec2::new(width as f32, height as f32);
         let max = v.x.max(v.y);
@@ -107,7 +151,7 @@ impl EngineTexture {
 
     pub fn empty(width: u32, height: u32) -> Result<Self, TextureCreationError> {
         let state = get_state();
-        Ok(Self::new(Texture2d::empty(&state.display, width, height)?))
+        Ok(Self::new(Texture2d::empty(state.display(), width, height)?))
     }
 
     pub fn from_engine_image(image: Image) -> Result<Self, TextureCreationError> {
@@ -119,7 +163,7 @@ impl EngineTexture {
     pub fn from_raw(raw: RawImage2d<'_, u8>) -> Result<Self, TextureCreationError> {
         let state = get_state();
         let texture = Texture2d::with_format(
-            &state.display,
+            state.display(),
             raw,
             glium::texture::UncompressedFloatFormat::U8U8U8U8,
             if state.config.use_mipmaps {
@@ -133,6 +177,16 @@ impl EngineTexture {
     }
 }
 
+/// A texture bound for drawing, see [`EngineTexture::uniform`].
+#[derive(Clone, Copy)]
+pub(crate) struct TextureUniform<'a>(pub(crate) UniformValue<'a>);
+
+impl AsUniformValue for TextureUniform<'_> {
+    fn as_uniform_value(&self) -> UniformValue<'_> {
+        self.0
+    }
+}
+
 gen_ref_type!(EngineTexture, TextureRef, textures);
 
 impl TextureRef {
@@ -143,4 +197,26 @@ impl TextureRef {
     pub fn normalized_dimensions(&self) -> Vec2 {
         self.get().normalized_dimensions
     }
+
+    /// Reads the texture back from the GPU, for saving procedural textures or photos taken in
+    /// game. It waits for the GPU to finish with the texture, so it's slow to do every frame.
+    pub fn download(&self) -> Image {
+        if let Some((texture, format)) = &self.compressed {
+            let (width, height) = (self.dimensions.x as usize, self.dimensions.y as usize);
+            return texture
+                .read_compressed_data()
+                .ok_or_else(|| anyhow::anyhow!("the driver couldn't read the texture back").into())
+                .and_then(|(_, data)| decode_blocks(*format, &data, width, height))
+                .or_report()
+                .unwrap_or_else(|| Image::empty(width, height));
+        }
+
+        let raw: RawImage2d<'_, u8> = self.gl_texture.read();
+        Image::from_bytes(
+            raw.width as usize,
+            raw.height as usize,
+            raw.data.into_owned(),
+        )
+        .unwrap()
+    }
 }
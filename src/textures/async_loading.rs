@@ -0,0 +1,177 @@
+//! Loading textures without freezing the game. Images are decoded on their own threads, then
+//! uploaded at the end of the frame they finish in, since only the main thread can use the GPU.
+
+use std::{path::PathBuf, thread::JoinHandle};
+
+use log::warn;
+
+use super::TextureRef;
+use crate::{color::u8::Pixel, get_state, image::Image};
+
+#[derive(Default)]
+pub(crate) struct AsyncTextures {
+    textures: Vec<AsyncTexture>,
+    /// Shown in place of textures that aren't ready, made the first time it's needed.
+    placeholder: Option<TextureRef>,
+}
+
+enum AsyncTexture {
+    Loading(JoinHandle<anyhow::Result<Image>>),
+    Ready(TextureRef),
+    Failed(String),
+}
+
+/// A texture that's being loaded in the background by [`load_texture_async`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(usize);
+
+impl TextureHandle {
+    fn get(&self) -> &'static AsyncTexture {
+        &get_state().async_textures.textures[self.0]
+    }
+
+    pub fn is_ready(&self) -> bool {
+        matches!(self.get(), AsyncTexture::Ready(_))
+    }
+
+    /// Whether it's still being loaded, as opposed to being ready or having failed.
+    pub fn is_loading(&self) -> bool {
+        matches!(self.get(), AsyncTexture::Loading(_))
+    }
+
+    /// Why it couldn't be loaded, if it couldn't.
+    pub fn error(&self) -> Option<&'static str> {
+        match self.get() {
+            AsyncTexture::Failed(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// The texture, once it's ready.
+    pub fn texture(&self) -> Option<TextureRef> {
+        match self.get() {
+            AsyncTexture::Ready(texture) => Some(*texture),
+            _ => None,
+        }
+    }
+
+    /// The texture, or `placeholder` until it's ready.
+    pub fn texture_or(&self, placeholder: TextureRef) -> TextureRef {
+        self.texture().unwrap_or(placeholder)
+    }
+
+    /// The texture, or a checkerboard until it's ready (or forever, if it failed).
+    pub fn texture_or_placeholder(&self) -> TextureRef {
+        self.texture().unwrap_or_else(placeholder_texture)
+    }
+}
+
+/// Starts decoding the image at `path` on another thread. The format is guessed from the
+/// file. Draw it with [`TextureHandle::texture_or_placeholder`] while it loads, or wait for
+/// [`textures_loading`] to hit 0 behind a loading screen.
+pub fn load_texture_async(path: impl Into<PathBuf>) -> TextureHandle {
+    let path = path.into();
+    spawn_load(move || read_image(&path))
+}
+
+pub(crate) fn read_image(path: &std::path::Path) -> anyhow::Result<Image> {
+    let image = image::open(path)
+        .map_err(|e| anyhow::anyhow!("couldn't load {}: {e}", path.display()))?
+        .to_rgba8();
+    let (width, height) = image.dimensions();
+    Ok(Image::from_bytes(
+        width as usize,
+        height as usize,
+        image.into_raw(),
+    )?)
+}
+
+/// Like [`load_texture_async`], for images that are already in memory, like ones from
+/// `include_bytes!`.
+pub fn load_texture_bytes_async(bytes: Vec<u8>, format: image::ImageFormat) -> TextureHandle {
+    spawn_load(move || Ok(Image::load(&bytes, format)?))
+}
+
+fn spawn_load(load: impl FnOnce() -> anyhow::Result<Image> + Send + 'static) -> TextureHandle {
+    let textures = &mut get_state().async_textures.textures;
+    textures.push(AsyncTexture::Loading(std::thread::spawn(load)));
+    TextureHandle(textures.len() - 1)
+}
+
+/// How many textures from [`load_texture_async`] haven't finished yet.
+pub fn textures_loading() -> usize {
+    get_state()
+        .async_textures
+        .textures
+        .iter()
+        .filter(|texture| matches!(texture, AsyncTexture::Loading(_)))
+        .count()
+}
+
+/// A small magenta and black checkerboard, the usual "missing texture" look.
+pub fn placeholder_texture() -> TextureRef {
+    let state = get_state();
+    *state.async_textures.placeholder.get_or_insert_with(|| {
+        let mut texture = placeholder_image().to_texture().unwrap();
+        texture.magnify_filter = glium::uniforms::MagnifySamplerFilter::Nearest;
+        texture
+    })
+}
+
+pub(crate) fn placeholder_image() -> Image {
+    let mut image = Image::empty(2, 2);
+    image.map_pixels(|x, y, _| {
+        if (x + y) % 2 == 0 {
+            Pixel::from_rgb(255, 0, 255)
+        } else {
+            Pixel::BLACK
+        }
+    });
+    image
+}
+
+/// Uploads images that finished decoding.
+pub(crate) fn update_async_textures() {
+    let state = get_state();
+    let headless = state.window_context.is_none();
+
+    for texture in &mut state.async_textures.textures {
+        let AsyncTexture::Loading(handle) = texture else {
+            continue;
+        };
+        if !handle.is_finished() {
+            continue;
+        }
+
+        let AsyncTexture::Loading(handle) =
+            std::mem::replace(texture, AsyncTexture::Failed(String::new()))
+        else {
+            unreachable!()
+        };
+
+        *texture = match loaded_image(handle.join(), headless) {
+            Ok(image) => match image.to_texture() {
+                Ok(uploaded) => AsyncTexture::Ready(uploaded),
+                Err(e) => AsyncTexture::Failed(e.to_string()),
+            },
+            Err(error) => AsyncTexture::Failed(error),
+        };
+
+        if let AsyncTexture::Failed(error) = texture {
+            warn!("Couldn't load texture: {error}");
+        }
+    }
+}
+
+/// The image a finished loading thread left, ready to upload, or why there isn't one.
+pub(crate) fn loaded_image(
+    joined: std::thread::Result<anyhow::Result<Image>>,
+    headless: bool,
+) -> Result<Image, String> {
+    match joined {
+        Ok(Ok(_)) if headless => Err("there's no GPU to upload to in headless mode".to_string()),
+        Ok(Ok(image)) => Ok(image),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("the loading thread panicked".to_string()),
+    }
+}
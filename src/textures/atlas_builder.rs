@@ -0,0 +1,380 @@
+//! Packing lots of small images into a few big textures at once, so sprites that are made at
+//! runtime or loaded from loose files can be drawn without switching textures.
+//!
+//! Unlike [`TextureAtlas`](super::atlas::TextureAtlas), which adds sprites one at a time, the
+//! builder sees every image before packing, so it can sort them and waste less space.
+
+use std::path::Path;
+
+use bevy_math::{Rect, USizeVec2, Vec2};
+use image::ImageFormat;
+
+use super::{EngineTexture, TextureRef};
+use crate::{
+    api::{draw_texture_ex, draw_texture_world_ex},
+    color::Color,
+    error::EngineResult,
+    image::Image,
+    prelude::Transform2D,
+};
+
+/// Which image passed to [`AtlasBuilder::add`] a region came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AtlasRegionId(usize);
+
+/// Collects images, then packs them into as few pages as fit.
+///
+/// ```ignore
+/// let mut builder = AtlasBuilder::new();
+/// let player = builder.add_file("assets/player.png")?;
+/// let coin = builder.add(make_coin_image());
+/// let atlas = builder.build()?;
+///
+/// atlas.region(player).draw(position, 100.0);
+/// ```
+pub struct AtlasBuilder {
+    max_size: usize,
+    gap: usize,
+    images: Vec<Image>,
+}
+
+impl Default for AtlasBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AtlasBuilder {
+    const DEFAULT_MAX_SIZE: usize = 2048;
+    const DEFAULT_GAP: usize = 1;
+
+    pub fn new() -> Self {
+        Self {
+            max_size: Self::DEFAULT_MAX_SIZE,
+            gap: Self::DEFAULT_GAP,
+            images: Vec::new(),
+        }
+    }
+
+    /// The biggest a page can be on each side. Images bigger than this can't be packed.
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Transparent pixels between images, so they don't bleed into each other with linear
+    /// filtering.
+    pub fn with_gap(mut self, gap: usize) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn add(&mut self, image: Image) -> AtlasRegionId {
+        self.images.push(image);
+        AtlasRegionId(self.images.len() - 1)
+    }
+
+    pub fn add_bytes(
+        &mut self,
+        bytes: &[u8],
+        format: ImageFormat,
+    ) -> anyhow::Result<AtlasRegionId> {
+        Ok(self.add(Image::load(bytes, format)?))
+    }
+
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> anyhow::Result<AtlasRegionId> {
+        let path = path.as_ref();
+        let image = image::open(path)
+            .map_err(|e| anyhow::anyhow!("couldn't load {}: {e}", path.display()))?
+            .to_rgba8();
+        let (width, height) = image.dimensions();
+        Ok(self.add(Image::from_bytes(
+            width as usize,
+            height as usize,
+            image.into_raw(),
+        )?))
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Works out where every image goes without making any textures.
+    pub fn pack(&self) -> anyhow::Result<AtlasLayout> {
+        let sizes: Vec<USizeVec2> = self.images.iter().map(Image::dimensions).collect();
+        pack_sizes(&sizes, self.max_size, self.gap)
+    }
+
+    /// Packs the images and uploads each page to a texture.
+    pub fn build(self) -> anyhow::Result<BuiltAtlas> {
+        let layout = self.pack()?;
+
+        let mut pages: Vec<Image> = layout
+            .page_sizes
+            .iter()
+            .map(|size| Image::empty(size.x, size.y))
+            .collect();
+        for (image, placement) in self.images.iter().zip(&layout.placements) {
+            pages[placement.page].copy_from(image, placement.position.as_ivec2());
+        }
+
+        let textures = pages
+            .into_iter()
+            .map(|page| page.to_texture())
+            .collect::<EngineResult<Vec<_>>>()?;
+
+        let regions = self
+            .images
+            .iter()
+            .zip(&layout.placements)
+            .map(|(image, placement)| {
+                let min = placement.position.as_vec2();
+                let size = image.dimensions();
+                AtlasRegion {
+                    texture: textures[placement.page],
+                    rect: Rect::from_corners(min, min + size.as_vec2()),
+                    normalized_dimensions: EngineTexture::create_normalized_dimensions(
+                        size.x as u32,
+                        size.y as u32,
+                    ),
+                }
+            })
+            .collect();
+
+        Ok(BuiltAtlas {
+            pages: textures,
+            regions,
+        })
+    }
+}
+
+/// Where [`AtlasBuilder::pack`] put things.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtlasLayout {
+    pub page_sizes: Vec<USizeVec2>,
+    /// In the order the images were added.
+    pub placements: Vec<AtlasPlacement>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AtlasPlacement {
+    pub page: usize,
+    /// Top left, in pixels.
+    pub position: USizeVec2,
+}
+
+/// Packs images of `sizes` into pages no bigger than `max_size`, biggest first. Pages are
+/// trimmed to a power of two that fits what's on them.
+pub(crate) fn pack_sizes(
+    sizes: &[USizeVec2],
+    max_size: usize,
+    gap: usize,
+) -> anyhow::Result<AtlasLayout> {
+    if let Some(size) = sizes
+        .iter()
+        .find(|size| size.x > max_size || size.y > max_size)
+    {
+        anyhow::bail!(
+            "a {}x{} image doesn't fit in a {max_size}x{max_size} atlas",
+            size.x,
+            size.y
+        );
+    }
+
+    // tall images first, since the skyline packs rows of similar heights best
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse((sizes[i].y, sizes[i].x)));
+
+    let mut packers: Vec<SkylinePacker> = Vec::new();
+    let mut placements = vec![
+        AtlasPlacement {
+            page: 0,
+            position: USizeVec2::ZERO,
+        };
+        sizes.len()
+    ];
+
+    for i in order {
+        // the gap only goes on the right and bottom, so images as big as a page still fit
+        let padded = (sizes[i] + gap).min(USizeVec2::splat(max_size));
+
+        let placed = packers
+            .iter_mut()
+            .enumerate()
+            .find_map(|(page, packer)| packer.pack(padded).map(|position| (page, position)));
+        let (page, position) = match placed {
+            Some(placed) => placed,
+            None => {
+                let mut packer = SkylinePacker::new(max_size, max_size);
+                let position = packer.pack(padded).unwrap();
+                packers.push(packer);
+                (packers.len() - 1, position)
+            }
+        };
+        placements[i] = AtlasPlacement { page, position };
+    }
+
+    let page_sizes = packers
+        .iter()
+        .map(|packer| {
+            let used = packer.used_size();
+            USizeVec2::new(
+                used.x.next_power_of_two().min(max_size),
+                used.y.next_power_of_two().min(max_size),
+            )
+        })
+        .collect();
+
+    Ok(AtlasLayout {
+        page_sizes,
+        placements,
+    })
+}
+
+/// Bottom left skyline packing, upside down since y goes down. It keeps track of the lowest
+/// free y for each stretch of x, and puts each rect wherever it ends up highest.
+pub(crate) struct SkylinePacker {
+    width: usize,
+    height: usize,
+    /// `(x, y, width)` of each stretch, left to right.
+    skyline: Vec<(usize, usize, usize)>,
+    used: USizeVec2,
+}
+
+impl SkylinePacker {
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            skyline: vec![(0, 0, width)],
+            used: USizeVec2::ZERO,
+        }
+    }
+
+    /// Where a `size` rect goes, or `None` if there's no room left.
+    pub(crate) fn pack(&mut self, size: USizeVec2) -> Option<USizeVec2> {
+        let (index, y) = (0..self.skyline.len())
+            .filter_map(|i| self.fits(i, size).map(|y| (i, y)))
+            .min_by_key(|&(i, y)| (y + size.y, self.skyline[i].0))?;
+
+        let x = self.skyline[index].0;
+        self.skyline.insert(index, (x, y + size.y, size.x));
+
+        // shorten or remove the stretches the new one covers
+        let right = x + size.x;
+        let i = index + 1;
+        while i < self.skyline.len() {
+            let (start, node_y, width) = self.skyline[i];
+            if start >= right {
+                break;
+            }
+            let end = start + width;
+            if end <= right {
+                self.skyline.remove(i);
+            } else {
+                self.skyline[i] = (right, node_y, end - right);
+                break;
+            }
+        }
+
+        // join neighbours at the same height
+        self.skyline.dedup_by(|next, previous| {
+            if previous.1 == next.1 {
+                previous.2 += next.2;
+                true
+            } else {
+                false
+            }
+        });
+
+        self.used = self.used.max(USizeVec2::new(right, y + size.y));
+        Some(USizeVec2::new(x, y))
+    }
+
+    /// The y a rect would go at if its left edge was at the start of stretch `index`.
+    fn fits(&self, index: usize, size: USizeVec2) -> Option<usize> {
+        let x = self.skyline[index].0;
+        if x + size.x > self.width {
+            return None;
+        }
+
+        let mut y = 0;
+        let mut covered = 0;
+        for &(_, node_y, width) in &self.skyline[index..] {
+            y = y.max(node_y);
+            covered += width;
+            if covered >= size.x {
+                break;
+            }
+        }
+
+        (y + size.y <= self.height).then_some(y)
+    }
+
+    /// How much of the page has been used, from the top left.
+    pub(crate) fn used_size(&self) -> USizeVec2 {
+        self.used
+    }
+}
+
+/// The textures an [`AtlasBuilder`] made, and where each image ended up on them.
+pub struct BuiltAtlas {
+    pub pages: Vec<TextureRef>,
+    regions: Vec<AtlasRegion>,
+}
+
+impl BuiltAtlas {
+    pub fn region(&self, id: AtlasRegionId) -> AtlasRegion {
+        self.regions[id.0]
+    }
+
+    /// Every region, in the order the images were added.
+    pub fn regions(&self) -> &[AtlasRegion] {
+        &self.regions
+    }
+}
+
+/// Part of an atlas page with one image on it. Draws like a texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub texture: TextureRef,
+    /// In pixels of the page, from the top left.
+    pub rect: Rect,
+    pub normalized_dimensions: Vec2,
+}
+
+impl AtlasRegion {
+    pub fn size(&self) -> Vec2 {
+        self.rect.size()
+    }
+
+    pub fn draw(&self, position: Vec2, scale: f32) {
+        self.draw_scaled(position, self.normalized_dimensions * scale);
+    }
+
+    pub fn draw_scaled(&self, position: Vec2, scale: Vec2) {
+        self.draw_ex(
+            Transform2D::from_scale_translation(scale, position),
+            Color::WHITE,
+        );
+    }
+
+    pub fn draw_ex(&self, transform: Transform2D, color: Color) {
+        draw_texture_ex(self.texture, transform, color, Some(self.rect));
+    }
+
+    pub fn draw_world(&self, position: Vec2, scale: f32) {
+        self.draw_world_ex(
+            Transform2D::from_scale_translation(self.normalized_dimensions * scale, position),
+            Color::WHITE,
+        );
+    }
+
+    pub fn draw_world_ex(&self, transform: Transform2D, color: Color) {
+        draw_texture_world_ex(self.texture, transform, color, Some(self.rect));
+    }
+}
@@ -0,0 +1,444 @@
+//! Pre-compressed textures from KTX2 files. BCn data is uploaded to the GPU as-is, so it takes
+//! a quarter to an eighth of the memory and skips decoding PNGs at load time. When the GPU
+//! can't sample a format, it's decompressed on the CPU instead.
+//!
+//! Basis Universal ETC1S files are transcoded to BC1, or BC3 with alpha. UASTC ones have to be
+//! transcoded to BCn first, e.g. with `ktx transcode --target bc7`.
+
+use std::io::Read;
+
+use glium::texture::{CompressedFormat, CompressedMipmapsOption, CompressedTexture2d};
+use log::warn;
+
+use super::{EngineTexture, TextureRef};
+use crate::{
+    checked_state,
+    color::u8::Pixel,
+    error::{EngineError, EngineResult},
+    image::Image,
+    utils::EngineCreate,
+};
+
+mod basis;
+mod bc7;
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+/// Bigger than any GPU can make a texture. Stops a broken header from asking for gigabytes.
+const MAX_SIZE: usize = 16384;
+
+const SUPERCOMPRESSION_NONE: u32 = 0;
+const SUPERCOMPRESSION_BASIS_LZ: u32 = 1;
+const SUPERCOMPRESSION_ZLIB: u32 = 3;
+
+/// How the pixels in a [`CompressedImage`] are stored. sRGB and UNORM variants are treated the
+/// same, like every other texture in the engine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockFormat {
+    /// Uncompressed, 4 bytes a pixel.
+    Rgba8,
+    /// BC1/DXT1 without alpha.
+    Bc1,
+    /// BC1/DXT1 with 1 bit alpha.
+    Bc1Alpha,
+    /// BC2/DXT3.
+    Bc2,
+    /// BC3/DXT5.
+    Bc3,
+    /// BC4, one channel, ends up in red.
+    Bc4,
+    /// BC5, two channels, end up in red and green. Usually normal maps.
+    Bc5,
+    /// BC7.
+    Bc7,
+}
+
+impl BlockFormat {
+    fn from_vk_format(vk_format: u32) -> EngineResult<Self> {
+        Ok(match vk_format {
+            37 | 43 => Self::Rgba8,
+            131 | 132 => Self::Bc1,
+            133 | 134 => Self::Bc1Alpha,
+            135 | 136 => Self::Bc2,
+            137 | 138 => Self::Bc3,
+            139 => Self::Bc4,
+            141 => Self::Bc5,
+            145 | 146 => Self::Bc7,
+            _ => {
+                return Err(ktx2_error(format!(
+                    "unsupported format (VkFormat {vk_format})"
+                )));
+            }
+        })
+    }
+
+    /// How many bytes each 4x4 block takes, or each pixel for [`Rgba8`](Self::Rgba8).
+    pub fn block_bytes(self) -> usize {
+        match self {
+            Self::Rgba8 => 4,
+            Self::Bc1 | Self::Bc1Alpha | Self::Bc4 => 8,
+            Self::Bc2 | Self::Bc3 | Self::Bc5 | Self::Bc7 => 16,
+        }
+    }
+
+    fn block_size(self) -> usize {
+        match self {
+            Self::Rgba8 => 1,
+            _ => 4,
+        }
+    }
+
+    /// How many bytes a `width` by `height` image takes in this format.
+    pub fn data_len(self, width: usize, height: usize) -> usize {
+        let block = self.block_size();
+        width.div_ceil(block) * height.div_ceil(block) * self.block_bytes()
+    }
+
+    fn gl_format(self) -> Option<CompressedFormat> {
+        match self {
+            Self::Rgba8 => None,
+            Self::Bc1 => Some(CompressedFormat::S3tcDxt1NoAlpha),
+            Self::Bc1Alpha => Some(CompressedFormat::S3tcDxt1Alpha),
+            Self::Bc2 => Some(CompressedFormat::S3tcDxt3Alpha),
+            Self::Bc3 => Some(CompressedFormat::S3tcDxt5Alpha),
+            Self::Bc4 => Some(CompressedFormat::RgtcFormatU),
+            Self::Bc5 => Some(CompressedFormat::RgtcFormatUU),
+            Self::Bc7 => Some(CompressedFormat::BptcUnorm4),
+        }
+    }
+}
+
+/// A texture that's still compressed, with its mipmaps, as read from a KTX2 file.
+#[derive(Clone, Debug)]
+pub struct CompressedImage {
+    pub format: BlockFormat,
+    pub width: usize,
+    pub height: usize,
+    /// The full size image first, then each mipmap.
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+    /// Reads a KTX2 file. Only plain 2D textures are supported, not arrays, cubemaps or 3D.
+    pub fn parse_ktx2(bytes: &[u8]) -> EngineResult<Self> {
+        if bytes.len() < HEADER_SIZE || bytes[..12] != KTX2_IDENTIFIER {
+            return Err(ktx2_error("not a KTX2 file"));
+        }
+
+        let vk_format = read_u32(bytes, 12)?;
+        let width = read_u32(bytes, 20)? as usize;
+        let height = read_u32(bytes, 24)? as usize;
+        let depth = read_u32(bytes, 28)?;
+        let layers = read_u32(bytes, 32)?;
+        let faces = read_u32(bytes, 36)?;
+        let level_count = read_u32(bytes, 40)?.max(1) as usize;
+        let supercompression = read_u32(bytes, 44)?;
+
+        if depth > 1 || layers > 1 || faces != 1 || width == 0 || height == 0 {
+            return Err(ktx2_error("only 2D KTX2 textures are supported"));
+        }
+        if width > MAX_SIZE || height > MAX_SIZE {
+            return Err(ktx2_error(format!(
+                "{width}x{height} is bigger than the biggest texture, {MAX_SIZE}x{MAX_SIZE}"
+            )));
+        }
+        // each level is half the size of the last, down to 1x1
+        let max_levels = (usize::BITS - width.max(height).leading_zeros()) as usize;
+        if level_count > max_levels {
+            return Err(ktx2_error(format!(
+                "a {width}x{height} texture can't have {level_count} levels"
+            )));
+        }
+        if HEADER_SIZE + level_count * LEVEL_INDEX_ENTRY_SIZE > bytes.len() {
+            return Err(ktx2_error("the level index is cut off"));
+        }
+
+        let level_data = (0..level_count)
+            .map(|level| {
+                let entry = HEADER_SIZE + level * LEVEL_INDEX_ENTRY_SIZE;
+                let offset = read_u64(bytes, entry)? as usize;
+                let length = read_u64(bytes, entry + 8)? as usize;
+                bytes
+                    .get(offset..offset.saturating_add(length))
+                    .ok_or_else(|| ktx2_error(format!("level {level} is out of bounds")))
+            })
+            .collect::<EngineResult<Vec<_>>>()?;
+
+        // Basis Universal textures don't have a format until they're transcoded
+        if vk_format == 0 {
+            if supercompression != SUPERCOMPRESSION_BASIS_LZ {
+                return Err(ktx2_error(
+                    "UASTC Basis textures aren't supported, transcode them to BCn first (`ktx transcode --target bc7`)",
+                ));
+            }
+            let global_offset = read_u64(bytes, 64)? as usize;
+            let global_length = read_u64(bytes, 72)? as usize;
+            let global = bytes
+                .get(global_offset..global_offset.saturating_add(global_length))
+                .ok_or_else(|| ktx2_error("the supercompression data is out of bounds"))?;
+
+            let (format, levels) = basis::transcode(global, &level_data, width, height)?;
+            return Ok(Self {
+                format,
+                width,
+                height,
+                levels,
+            });
+        }
+
+        let format = BlockFormat::from_vk_format(vk_format)?;
+        let mut levels = Vec::with_capacity(level_count);
+        for (level, data) in level_data.into_iter().enumerate() {
+            let data = match supercompression {
+                SUPERCOMPRESSION_NONE => data.to_vec(),
+                SUPERCOMPRESSION_ZLIB => {
+                    let mut inflated = Vec::new();
+                    flate2::read::ZlibDecoder::new(data).read_to_end(&mut inflated)?;
+                    inflated
+                }
+                scheme => {
+                    return Err(ktx2_error(format!(
+                        "unsupported supercompression (scheme {scheme})"
+                    )));
+                }
+            };
+
+            let (level_width, level_height) = level_size(width, height, level);
+            if data.len() < format.data_len(level_width, level_height) {
+                return Err(ktx2_error(format!("level {level} is too short")));
+            }
+            levels.push(data);
+        }
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    /// Decompresses the full size image on the CPU.
+    pub fn decode(&self) -> EngineResult<Image> {
+        decode_blocks(self.format, &self.levels[0], self.width, self.height)
+    }
+
+    /// Whether the GPU can sample this texture without it being decompressed first. Always
+    /// false before the engine is initialized.
+    pub fn gpu_supported(&self) -> bool {
+        checked_state().is_ok_and(|state| {
+            self.format
+                .gl_format()
+                .is_some_and(|format| format.is_supported(state.display()))
+        })
+    }
+
+    /// Uploads the texture still compressed if the GPU supports its format, and decompressed
+    /// otherwise.
+    ///
+    /// Compressed textures can only be sampled, so they can't be drawn to or shown in egui.
+    /// Their mipmaps come from the file, so if it has none, the texture won't have any either.
+    pub fn to_texture(&self) -> EngineResult<TextureRef> {
+        let state = checked_state()?;
+        let Some(gl_format) = self.format.gl_format().filter(|_| self.gpu_supported()) else {
+            if self.format != BlockFormat::Rgba8 {
+                warn!(
+                    "The GPU doesn't support {:?} textures, decompressing instead",
+                    self.format
+                );
+            }
+            return self.decode()?.to_texture();
+        };
+
+        let mip_levels = self.levels.len() as u32 - 1;
+        let compressed = CompressedTexture2d::with_compressed_data(
+            state.display(),
+            &self.levels[0],
+            self.width as u32,
+            self.height as u32,
+            gl_format,
+            if mip_levels == 0 {
+                CompressedMipmapsOption::NoMipmap
+            } else {
+                CompressedMipmapsOption::EmptyMipmapsMax(mip_levels)
+            },
+        )?;
+
+        for (level, data) in self.levels.iter().enumerate().skip(1) {
+            let (width, height) = level_size(self.width, self.height, level);
+            let rect = glium::Rect {
+                left: 0,
+                bottom: 0,
+                width: width as u32,
+                height: height as u32,
+            };
+            compressed
+                .mipmap(level as u32)
+                .ok_or_else(|| ktx2_error(format!("couldn't make mipmap {level}")))?
+                .write_compressed_data(rect, data, width as u32, height as u32, gl_format)
+                .map_err(|_| ktx2_error(format!("couldn't upload mipmap {level}")))?;
+        }
+
+        Ok(EngineTexture::from_compressed(compressed, self.format)?.create())
+    }
+}
+
+/// Loads a KTX2 texture, keeping it compressed on the GPU when possible. See
+/// [`CompressedImage::to_texture`].
+pub fn load_compressed_texture(bytes: &[u8]) -> EngineResult<TextureRef> {
+    CompressedImage::parse_ktx2(bytes)?.to_texture()
+}
+
+fn ktx2_error(message: impl Into<String>) -> EngineError {
+    EngineError::Ktx2(message.into())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> EngineResult<u32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| ktx2_error("the file is cut off"))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> EngineResult<u64> {
+    bytes
+        .get(offset..offset + 8)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| ktx2_error("the file is cut off"))
+}
+
+fn level_size(width: usize, height: usize, level: usize) -> (usize, usize) {
+    ((width >> level).max(1), (height >> level).max(1))
+}
+
+/// Decompresses `data` into an image.
+pub(crate) fn decode_blocks(
+    format: BlockFormat,
+    data: &[u8],
+    width: usize,
+    height: usize,
+) -> EngineResult<Image> {
+    if data.len() < format.data_len(width, height) {
+        return Err(ktx2_error(format!(
+            "not enough data for a {width}x{height} {format:?} image"
+        )));
+    }
+
+    if format == BlockFormat::Rgba8 {
+        return Image::from_bytes(width, height, data[..width * height * 4].to_vec());
+    }
+
+    let mut image = Image::empty(width, height);
+    let blocks_wide = width.div_ceil(4);
+    let block_bytes = format.block_bytes();
+
+    for (i, block) in data
+        .chunks_exact(block_bytes)
+        .take(blocks_wide * height.div_ceil(4))
+        .enumerate()
+    {
+        let pixels = decode_block(format, block);
+        let (block_x, block_y) = (i % blocks_wide * 4, i / blocks_wide * 4);
+        for (j, pixel) in pixels.into_iter().enumerate() {
+            // blocks on the right and bottom edges can hang off the image
+            image.set(block_x + j % 4, block_y + j / 4, pixel);
+        }
+    }
+
+    Ok(image)
+}
+
+fn decode_block(format: BlockFormat, block: &[u8]) -> [Pixel; 16] {
+    match format {
+        BlockFormat::Bc1 => decode_color_block(block, Some(Pixel::BLACK)),
+        BlockFormat::Bc1Alpha => decode_color_block(block, Some(Pixel::TRANSPARENT)),
+        BlockFormat::Bc2 => {
+            let mut pixels = decode_color_block(&block[8..], None);
+            let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+            for (i, pixel) in pixels.iter_mut().enumerate() {
+                let a = (alpha >> (i * 4)) as u8 & 0xF;
+                *pixel = pixel.with_alpha(a << 4 | a);
+            }
+            pixels
+        }
+        BlockFormat::Bc3 => {
+            let mut pixels = decode_color_block(&block[8..], None);
+            for (pixel, a) in pixels.iter_mut().zip(decode_channel_block(&block[..8])) {
+                *pixel = pixel.with_alpha(a);
+            }
+            pixels
+        }
+        BlockFormat::Bc4 => decode_channel_block(block).map(|r| Pixel::from_rgba(r, 0, 0, 255)),
+        BlockFormat::Bc5 => {
+            let red = decode_channel_block(&block[..8]);
+            let green = decode_channel_block(&block[8..]);
+            std::array::from_fn(|i| Pixel::from_rgba(red[i], green[i], 0, 255))
+        }
+        BlockFormat::Bc7 => bc7::decode_block(block),
+        BlockFormat::Rgba8 => unreachable!(),
+    }
+}
+
+/// The BC1 color part, also used by BC2 and BC3. BC1 switches to 3 colors plus `fourth` when
+/// the first color isn't bigger than the second, BC2 and BC3 (`None`) always use 4.
+fn decode_color_block(block: &[u8], fourth: Option<Pixel>) -> [Pixel; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes(block[4..8].try_into().unwrap());
+
+    let [r0, g0, b0] = rgb565(c0);
+    let [r1, g1, b1] = rgb565(c1);
+    let mix = |a: u8, b: u8, wa: u32, wb: u32| ((a as u32 * wa + b as u32 * wb) / (wa + wb)) as u8;
+
+    let palette = if let Some(fourth) = fourth
+        && c0 <= c1
+    {
+        [
+            Pixel::from_rgb(r0, g0, b0),
+            Pixel::from_rgb(r1, g1, b1),
+            Pixel::from_rgb(mix(r0, r1, 1, 1), mix(g0, g1, 1, 1), mix(b0, b1, 1, 1)),
+            fourth,
+        ]
+    } else {
+        [
+            Pixel::from_rgb(r0, g0, b0),
+            Pixel::from_rgb(r1, g1, b1),
+            Pixel::from_rgb(mix(r0, r1, 2, 1), mix(g0, g1, 2, 1), mix(b0, b1, 2, 1)),
+            Pixel::from_rgb(mix(r0, r1, 1, 2), mix(g0, g1, 1, 2), mix(b0, b1, 1, 2)),
+        ]
+    };
+
+    std::array::from_fn(|i| palette[(indices >> (i * 2)) as usize & 0b11])
+}
+
+/// A BC4 block, also used for BC3's alpha and both of BC5's channels.
+fn decode_channel_block(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+
+    let palette: [u8; 8] = std::array::from_fn(|i| {
+        let i = i as u32;
+        match i {
+            0 => a0 as u8,
+            1 => a1 as u8,
+            _ if a0 > a1 => (((8 - i) * a0 + (i - 1) * a1) / 7) as u8,
+            6 => 0,
+            7 => 255,
+            _ => (((6 - i) * a0 + (i - 1) * a1) / 5) as u8,
+        }
+    });
+
+    std::array::from_fn(|i| palette[(indices >> (i * 3)) as usize & 0b111])
+}
+
+fn rgb565(color: u16) -> [u8; 3] {
+    let r = (color >> 11) as u8 & 0x1F;
+    let g = (color >> 5) as u8 & 0x3F;
+    let b = color as u8 & 0x1F;
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
@@ -0,0 +1,631 @@
+//! Basis Universal ETC1S textures, the kind stored in KTX2 files with BasisLZ supercompression.
+//! Each block is an ETC1S endpoint and selectors from codebooks shared by the whole file, which
+//! are transcoded here to BC1, or BC3 if the texture has alpha.
+
+use super::{BlockFormat, ktx2_error, level_size, read_u32};
+use crate::error::EngineResult;
+
+/// `-3` to `+3` brightness steps that each ETC1S intensity adds to the base color.
+const INTENSITIES: [[i32; 4]; 8] = [
+    [-8, -2, 2, 8],
+    [-17, -5, 5, 17],
+    [-29, -9, 9, 29],
+    [-42, -13, 13, 42],
+    [-60, -18, 18, 60],
+    [-80, -24, 24, 80],
+    [-106, -33, 33, 106],
+    [-183, -47, 47, 183],
+];
+
+/// The order code length code sizes are stored in, most likely to be used first.
+const CODE_LENGTH_ORDER: [usize; 21] = [
+    17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16,
+];
+
+/// The endpoint prediction symbol that repeats the last one, at least this many times.
+const REPEAT_LAST_PREDICTION: u32 = 256;
+const MIN_PREDICTION_REPEATS: u32 = 3;
+/// Selector history runs of this length or more are sent with a VLC.
+const LONG_SELECTOR_RUN: u32 = 63;
+const MIN_SELECTOR_RUN: u32 = 3;
+
+const IMAGE_DESC_SIZE: usize = 20;
+/// Set on video frames that only store the difference from the last frame.
+const P_FRAME_FLAG: u32 = 2;
+
+/// Transcodes each level of a BasisLZ texture. `global` is the file's supercompression global
+/// data, and `levels` the bytes of each level, full size first.
+pub(super) fn transcode(
+    global: &[u8],
+    levels: &[&[u8]],
+    width: usize,
+    height: usize,
+) -> EngineResult<(BlockFormat, Vec<Vec<u8>>)> {
+    let endpoint_count = read_u16(global, 0)? as usize;
+    let selector_count = read_u16(global, 2)? as usize;
+    let endpoints_len = read_u32(global, 4)? as usize;
+    let selectors_len = read_u32(global, 8)? as usize;
+    let tables_len = read_u32(global, 12)? as usize;
+
+    let descs_start = 20;
+    let codebooks_start = descs_start + levels.len() * IMAGE_DESC_SIZE;
+    let section = |start: usize, len: usize| {
+        global
+            .get(start..start.saturating_add(len))
+            .ok_or_else(|| ktx2_error("BasisLZ global data is cut off"))
+    };
+    let endpoints = section(codebooks_start, endpoints_len)?;
+    let selectors = section(codebooks_start + endpoints_len, selectors_len)?;
+    let tables = section(codebooks_start + endpoints_len + selectors_len, tables_len)?;
+
+    let codebooks = Codebooks {
+        endpoints: read_endpoints(endpoints, endpoint_count)?,
+        selectors: read_selectors(selectors, selector_count)?,
+        tables: Tables::read(tables)?,
+    };
+
+    let descs = (0..levels.len())
+        .map(|level| ImageDesc::read(global, descs_start + level * IMAGE_DESC_SIZE))
+        .collect::<EngineResult<Vec<_>>>()?;
+    let has_alpha = descs.first().is_some_and(|desc| desc.alpha.1 > 0);
+    let format = if has_alpha {
+        BlockFormat::Bc3
+    } else {
+        BlockFormat::Bc1
+    };
+
+    let mut transcoded = Vec::with_capacity(levels.len());
+    for (level, (data, desc)) in levels.iter().zip(&descs).enumerate() {
+        if desc.flags & P_FRAME_FLAG != 0 {
+            return Err(ktx2_error("Basis video textures aren't supported"));
+        }
+
+        let (level_width, level_height) = level_size(width, height, level);
+        let (blocks_wide, blocks_high) = (level_width.div_ceil(4), level_height.div_ceil(4));
+        let slice = |(offset, len): (usize, usize)| {
+            let bytes = data
+                .get(offset..offset.saturating_add(len))
+                .ok_or_else(|| ktx2_error(format!("Basis level {level} is out of bounds")))?;
+            codebooks.decode_slice(bytes, blocks_wide, blocks_high)
+        };
+
+        let color = slice(desc.color)?;
+        let mut bytes = Vec::with_capacity(format.data_len(level_width, level_height));
+        if has_alpha {
+            let alpha = slice(desc.alpha)?;
+            for (color, alpha) in color.iter().zip(&alpha) {
+                bytes.extend(codebooks.alpha_block(*alpha));
+                bytes.extend(codebooks.color_block(*color));
+            }
+        } else {
+            for color in &color {
+                bytes.extend(codebooks.color_block(*color));
+            }
+        }
+        transcoded.push(bytes);
+    }
+
+    Ok((format, transcoded))
+}
+
+struct ImageDesc {
+    flags: u32,
+    /// Offset and length of the color slice in the level.
+    color: (usize, usize),
+    /// Same for the alpha slice, which is empty when there's no alpha.
+    alpha: (usize, usize),
+}
+
+impl ImageDesc {
+    fn read(global: &[u8], offset: usize) -> EngineResult<Self> {
+        let field = |i: usize| read_u32(global, offset + i * 4).map(|value| value as usize);
+        Ok(Self {
+            flags: field(0)? as u32,
+            color: (field(1)?, field(2)?),
+            alpha: (field(3)?, field(4)?),
+        })
+    }
+}
+
+/// A base color with 5 bits a channel, and how far its four shades are spread.
+#[derive(Clone, Copy)]
+struct Endpoint {
+    color: [u8; 3],
+    intensity: u8,
+}
+
+impl Endpoint {
+    /// The colors the four selectors pick, darkest first.
+    fn colors(self) -> [[u8; 3]; 4] {
+        let base = self.color.map(|c| (c << 3 | c >> 2) as i32);
+        INTENSITIES[self.intensity as usize]
+            .map(|offset| base.map(|c| (c + offset).clamp(0, 255) as u8))
+    }
+}
+
+/// A 4x4 block of 2 bit selectors, a byte a row with the leftmost pixel in the lowest bits.
+type Selectors = [u8; 4];
+
+struct Codebooks {
+    endpoints: Vec<Endpoint>,
+    selectors: Vec<Selectors>,
+    tables: Tables,
+}
+
+/// An endpoint and selectors, as indices into the codebooks.
+type Block = (usize, usize);
+
+impl Codebooks {
+    fn decode_slice(
+        &self,
+        bytes: &[u8],
+        blocks_wide: usize,
+        blocks_high: usize,
+    ) -> EngineResult<Vec<Block>> {
+        let tables = &self.tables;
+        let mut bits = BitReader::new(bytes);
+        let total_blocks = blocks_wide * blocks_high;
+        let mut blocks: Vec<Block> = Vec::with_capacity(total_blocks);
+
+        // selectors are sent as a codebook index, a spot in the history, or a run of the last one
+        let mut history = History::new(tables.history_size);
+        let first_history_symbol = self.selectors.len();
+        let run_symbol = first_history_symbol + tables.history_size;
+        let mut selector_run = 0;
+
+        // endpoints are predicted from the left, above, or above left, or sent as a delta. The
+        // predictions come for 2x2 groups of blocks at a time, 2 bits each
+        let mut prediction = 0;
+        let mut last_prediction = 0;
+        let mut prediction_repeats = 0;
+        let mut lower_predictions = vec![0; blocks_wide];
+        let mut last_endpoint = 0;
+
+        for y in 0..blocks_high {
+            for x in 0..blocks_wide {
+                if x % 2 == 0 {
+                    if y % 2 == 0 {
+                        if prediction_repeats > 0 {
+                            prediction_repeats -= 1;
+                            prediction = last_prediction;
+                        } else {
+                            prediction = tables.endpoint_prediction.decode(&mut bits)?;
+                            if prediction == REPEAT_LAST_PREDICTION {
+                                // this block is one of the repeats
+                                prediction_repeats = bits.read_vlc(4)? + MIN_PREDICTION_REPEATS - 1;
+                                prediction = last_prediction;
+                            } else {
+                                last_prediction = prediction;
+                            }
+                        }
+                        lower_predictions[x] = prediction >> 4;
+                    } else {
+                        prediction = lower_predictions[x];
+                    }
+                }
+
+                let endpoint = match prediction & 3 {
+                    0 if x > 0 => last_endpoint,
+                    1 if y > 0 => blocks[(y - 1) * blocks_wide + x].0,
+                    2 if x > 0 && y > 0 => blocks[(y - 1) * blocks_wide + x - 1].0,
+                    3 => {
+                        let delta = tables.endpoint_delta.decode(&mut bits)? as usize;
+                        let endpoint = last_endpoint + delta;
+                        if endpoint >= self.endpoints.len() {
+                            endpoint - self.endpoints.len()
+                        } else {
+                            endpoint
+                        }
+                    }
+                    _ => return Err(ktx2_error("Basis block predicted from outside the image")),
+                };
+                if endpoint >= self.endpoints.len() {
+                    return Err(ktx2_error("Basis endpoint is out of range"));
+                }
+                prediction >>= 2;
+                last_endpoint = endpoint;
+
+                let selector = if selector_run > 0 {
+                    selector_run -= 1;
+                    history.get(0)?
+                } else {
+                    let symbol = tables.selector.decode(&mut bits)? as usize;
+                    if symbol == run_symbol {
+                        let run = tables.selector_run.decode(&mut bits)?;
+                        selector_run = if run == LONG_SELECTOR_RUN {
+                            bits.read_vlc(7)? + MIN_SELECTOR_RUN
+                        } else {
+                            run + MIN_SELECTOR_RUN
+                        } as usize;
+                        if selector_run > total_blocks {
+                            return Err(ktx2_error("Basis selector run is too long"));
+                        }
+                        selector_run -= 1;
+                        history.get(0)?
+                    } else if symbol >= first_history_symbol {
+                        let index = symbol - first_history_symbol;
+                        let selector = history.get(index)?;
+                        history.promote(index);
+                        selector
+                    } else {
+                        history.add(symbol);
+                        symbol
+                    }
+                };
+                if selector >= self.selectors.len() {
+                    return Err(ktx2_error("Basis selector is out of range"));
+                }
+
+                blocks.push((endpoint, selector));
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// The nearest BC1 block. BC1's endpoints are the darkest and brightest ETC1S colors, and
+    /// each selector goes to whichever of the four BC1 colors is closest to its own.
+    fn color_block(&self, (endpoint, selectors): Block) -> [u8; 8] {
+        let colors = self.endpoints[endpoint].colors();
+        let (high, low) = (rgb565(colors[3]), rgb565(colors[0]));
+
+        let palette = {
+            let [h, l] = [high, low].map(expand565);
+            let mix = |a: u8, b: u8| ((2 * a as u32 + b as u32) / 3) as u8;
+            [
+                h,
+                l,
+                std::array::from_fn(|c| mix(h[c], l[c])),
+                std::array::from_fn(|c| mix(l[c], h[c])),
+            ]
+        };
+        let index = |selector: usize| {
+            if high == low {
+                return 0;
+            }
+            nearest(&palette, |entry| distance(*entry, colors[selector]))
+        };
+        let mapping: [u32; 4] = std::array::from_fn(index);
+
+        let mut block = [0; 8];
+        block[..2].copy_from_slice(&high.to_le_bytes());
+        block[2..4].copy_from_slice(&low.to_le_bytes());
+        let indices = remap(self.selectors[selectors], |s| mapping[s], 2);
+        block[4..].copy_from_slice(&(indices as u32).to_le_bytes());
+        block
+    }
+
+    /// The nearest BC3 alpha block. Alpha slices are grayscale, so alpha comes from green.
+    fn alpha_block(&self, (endpoint, selectors): Block) -> [u8; 8] {
+        let alphas = self.endpoints[endpoint].colors().map(|color| color[1]);
+        let (high, low) = (alphas[3], alphas[0]);
+
+        let palette: [u8; 8] = std::array::from_fn(|i| {
+            let (i, high, low) = (i as u32, high as u32, low as u32);
+            match i {
+                0 => high as u8,
+                1 => low as u8,
+                _ => (((8 - i) * high + (i - 1) * low) / 7) as u8,
+            }
+        });
+        let index = |selector: usize| {
+            if high == low {
+                return 0;
+            }
+            nearest(&palette, |entry| entry.abs_diff(alphas[selector]) as u32)
+        };
+        let mapping: [u32; 4] = std::array::from_fn(index);
+
+        let mut block = [0; 8];
+        block[0] = high;
+        block[1] = low;
+        let indices = remap(self.selectors[selectors], |s| mapping[s], 3);
+        block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+        block
+    }
+}
+
+/// Packs a block's indices, `bits` each with the first pixel lowest, after mapping each of its
+/// selectors through `map`.
+fn remap(selectors: Selectors, map: impl Fn(usize) -> u32, bits: usize) -> u64 {
+    let mut packed = 0;
+    for (y, row) in selectors.iter().enumerate() {
+        for x in 0..4 {
+            let selector = (row >> (x * 2)) as usize & 3;
+            packed |= (map(selector) as u64) << ((y * 4 + x) * bits);
+        }
+    }
+    packed
+}
+
+fn nearest<T>(palette: &[T], distance: impl Fn(&T) -> u32) -> u32 {
+    (0..palette.len())
+        .min_by_key(|i| distance(&palette[*i]))
+        .unwrap_or(0) as u32
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    (0..3).map(|c| (a[c].abs_diff(b[c]) as u32).pow(2)).sum()
+}
+
+fn rgb565([r, g, b]: [u8; 3]) -> u16 {
+    let quantize = |value: u8, max: u32| ((value as u32 * max + 127) / 255) as u16;
+    quantize(r, 31) << 11 | quantize(g, 63) << 5 | quantize(b, 31)
+}
+
+fn expand565(color: u16) -> [u8; 3] {
+    let r = (color >> 11) as u8 & 0x1F;
+    let g = (color >> 5) as u8 & 0x3F;
+    let b = color as u8 & 0x1F;
+    [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+}
+
+fn read_endpoints(bytes: &[u8], count: usize) -> EngineResult<Vec<Endpoint>> {
+    let mut bits = BitReader::new(bytes);
+    // each channel's delta comes from one of three models, picked by the last value of the
+    // channel, since deltas from near 0 or 31 can only go one way
+    let color_deltas = [
+        Huffman::read(&mut bits)?,
+        Huffman::read(&mut bits)?,
+        Huffman::read(&mut bits)?,
+    ];
+    let intensity_delta = Huffman::read(&mut bits)?;
+    let grayscale = bits.read(1) == 1;
+
+    let mut color = [16; 3];
+    let mut intensity = 0;
+    let mut endpoints = Vec::with_capacity(count);
+    for _ in 0..count {
+        intensity = (intensity + intensity_delta.decode(&mut bits)?) & 7;
+        let channels = if grayscale { 1 } else { 3 };
+        for channel in &mut color[..channels] {
+            let model = match *channel {
+                0..=9 => &color_deltas[0],
+                10..=21 => &color_deltas[1],
+                _ => &color_deltas[2],
+            };
+            *channel = (*channel + model.decode(&mut bits)?) & 31;
+        }
+
+        let color = if grayscale { [color[0]; 3] } else { color };
+        endpoints.push(Endpoint {
+            color: color.map(|c| c as u8),
+            intensity: intensity as u8,
+        });
+    }
+
+    Ok(endpoints)
+}
+
+fn read_selectors(bytes: &[u8], count: usize) -> EngineResult<Vec<Selectors>> {
+    let mut bits = BitReader::new(bytes);
+    if bits.read(1) == 1 || bits.read(1) == 1 {
+        return Err(ktx2_error(
+            "this Basis texture uses a global selector codebook, which isn't supported",
+        ));
+    }
+
+    let mut selectors = Vec::with_capacity(count);
+    if bits.read(1) == 1 {
+        for _ in 0..count {
+            selectors.push(std::array::from_fn(|_| bits.read(8) as u8));
+        }
+        return Ok(selectors);
+    }
+
+    // each row is xored with the same row of the last one
+    let delta = Huffman::read(&mut bits)?;
+    let mut last = [0; 4];
+    for i in 0..count {
+        for row in &mut last {
+            *row = if i == 0 {
+                bits.read(8) as u8
+            } else {
+                delta.decode(&mut bits)? as u8 ^ *row
+            };
+        }
+        selectors.push(last);
+    }
+
+    Ok(selectors)
+}
+
+struct Tables {
+    endpoint_prediction: Huffman,
+    endpoint_delta: Huffman,
+    selector: Huffman,
+    selector_run: Huffman,
+    history_size: usize,
+}
+
+impl Tables {
+    fn read(bytes: &[u8]) -> EngineResult<Self> {
+        let mut bits = BitReader::new(bytes);
+        Ok(Self {
+            endpoint_prediction: Huffman::read(&mut bits)?,
+            endpoint_delta: Huffman::read(&mut bits)?,
+            selector: Huffman::read(&mut bits)?,
+            selector_run: Huffman::read(&mut bits)?,
+            history_size: bits.read(13) as usize,
+        })
+    }
+}
+
+/// Recently used selectors. Used ones move halfway to the front, so common ones get short
+/// codes without reshuffling the whole list.
+struct History {
+    selectors: Vec<usize>,
+    next: usize,
+}
+
+impl History {
+    fn new(size: usize) -> Self {
+        Self {
+            selectors: vec![0; size],
+            next: size / 2,
+        }
+    }
+
+    fn get(&self, index: usize) -> EngineResult<usize> {
+        self.selectors
+            .get(index)
+            .copied()
+            .ok_or_else(|| ktx2_error("Basis selector history index is out of range"))
+    }
+
+    fn add(&mut self, selector: usize) {
+        if self.selectors.is_empty() {
+            return;
+        }
+        self.selectors[self.next] = selector;
+        self.next += 1;
+        if self.next == self.selectors.len() {
+            self.next = self.selectors.len() / 2;
+        }
+    }
+
+    fn promote(&mut self, index: usize) {
+        self.selectors.swap(index / 2, index);
+    }
+}
+
+/// Reads bits from the lowest bit of each byte up. Past the end it reads zeros, like the
+/// reference decoder.
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    fn bit(&mut self) -> u32 {
+        let byte = self.bytes.get(self.position / 8).copied().unwrap_or(0);
+        self.position += 1;
+        (byte >> ((self.position - 1) % 8)) as u32 & 1
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        (0..count).fold(0, |value, i| value | self.bit() << i)
+    }
+
+    /// A number sent `chunk_bits` at a time, each chunk followed by a bit saying whether there's
+    /// another.
+    fn read_vlc(&mut self, chunk_bits: u32) -> EngineResult<u32> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let chunk = self.read(chunk_bits + 1);
+            value |= (chunk & ((1 << chunk_bits) - 1)) << shift;
+            shift += chunk_bits;
+            if chunk >> chunk_bits == 0 {
+                return Ok(value);
+            }
+            if shift >= 32 {
+                return Err(ktx2_error("Basis VLC is too long"));
+            }
+        }
+    }
+}
+
+/// A canonical Huffman code, the same as deflate's.
+#[derive(Default)]
+struct Huffman {
+    /// How many codes there are of each length.
+    counts: [u32; 17],
+    /// Symbols sorted by code length, then value.
+    symbols: Vec<u32>,
+}
+
+impl Huffman {
+    fn new(sizes: &[u8]) -> Self {
+        let mut counts = [0; 17];
+        for size in sizes {
+            counts[*size as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut symbols: Vec<u32> = (0..sizes.len() as u32)
+            .filter(|symbol| sizes[*symbol as usize] > 0)
+            .collect();
+        symbols.sort_by_key(|symbol| sizes[*symbol as usize]);
+
+        Self { counts, symbols }
+    }
+
+    /// Code sizes are sent as codes themselves, with runs of zeros and repeats.
+    fn read(bits: &mut BitReader) -> EngineResult<Self> {
+        let symbol_count = bits.read(14) as usize;
+        if symbol_count == 0 {
+            return Ok(Self::default());
+        }
+
+        let mut code_length_sizes = [0; 21];
+        let code_length_count = bits.read(5) as usize;
+        if code_length_count > CODE_LENGTH_ORDER.len() {
+            return Err(ktx2_error("Basis Huffman table is broken"));
+        }
+        for symbol in &CODE_LENGTH_ORDER[..code_length_count] {
+            code_length_sizes[*symbol] = bits.read(3) as u8;
+        }
+        let code_lengths = Self::new(&code_length_sizes);
+
+        let mut sizes = vec![0; symbol_count];
+        let mut i = 0;
+        while i < symbol_count {
+            let (size, run) = match code_lengths.decode(bits)? {
+                size @ 0..=16 => (size as u8, 1),
+                17 => (0, bits.read(3) + 3),
+                18 => (0, bits.read(7) + 11),
+                code => {
+                    let run = if code == 19 {
+                        bits.read(2) + 3
+                    } else {
+                        bits.read(6) + 7
+                    };
+                    match i.checked_sub(1).map(|last| sizes[last]) {
+                        Some(last) if last > 0 => (last, run),
+                        _ => return Err(ktx2_error("Basis Huffman table is broken")),
+                    }
+                }
+            };
+            let end = i + run as usize;
+            if end > symbol_count {
+                return Err(ktx2_error("Basis Huffman table is broken"));
+            }
+            sizes[i..end].fill(size);
+            i = end;
+        }
+
+        Ok(Self::new(&sizes))
+    }
+
+    fn decode(&self, bits: &mut BitReader) -> EngineResult<u32> {
+        // codes are stored most significant bit first, so they can be read a bit at a time
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for count in &self.counts[1..] {
+            code |= bits.bit();
+            if code < first + count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        Err(ktx2_error(
+            "Basis data has a Huffman code that isn't in its table",
+        ))
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> EngineResult<u16> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| ktx2_error("BasisLZ global data is cut off"))
+}
@@ -0,0 +1,309 @@
+//! Where a font keeps its rasterized glyphs. Glyphs are packed onto fixed size pages, more
+//! pages are added as they fill up, and once there are too many the least recently used page
+//! is cleared out to make room, so big character sets and lots of sizes don't use up memory
+//! forever.
+
+use std::{collections::HashMap, hash::Hash, ops::Range};
+
+use bevy_math::USizeVec2;
+use glium::{
+    texture::RawImage2d,
+    uniforms::{MagnifySamplerFilter, MinifySamplerFilter},
+};
+
+use super::{EngineTexture, TextureRef};
+use crate::{get_state, image::Image, utils::EngineCreate, utils::usize_rect::USizeRect};
+
+/// The width and height of a page, unless a glyph is bigger than this.
+pub(crate) const PAGE_SIZE: usize = 512;
+/// How many pages a font can have before old ones start getting cleared out.
+pub(crate) const MAX_PAGES: usize = 8;
+/// Space left around each glyph, so linear filtering doesn't bleed the neighbours in.
+const GAP: usize = 1;
+/// Shelves are rounded up to this, so glyphs of slightly different heights share them.
+const SHELF_STEP: usize = 4;
+
+/// Packs rectangles in rows, like books on shelves. Glyphs of one font and size are all
+/// about as tall, so hardly any space is wasted.
+#[derive(Clone, Debug)]
+pub(crate) struct ShelfPacker {
+    width: usize,
+    height: usize,
+    shelves: Vec<Shelf>,
+}
+
+#[derive(Clone, Debug)]
+struct Shelf {
+    y: usize,
+    height: usize,
+    /// Where the next rectangle goes.
+    x: usize,
+}
+
+impl ShelfPacker {
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            shelves: Vec::new(),
+        }
+    }
+
+    /// Finds a spot for a rectangle of `size`, or `None` if it's full.
+    pub(crate) fn pack(&mut self, size: USizeVec2) -> Option<USizeVec2> {
+        let (width, height) = (size.x + GAP, size.y + GAP);
+        if width > self.width {
+            return None;
+        }
+
+        // the shelf that wastes the least height
+        let shelf = self
+            .shelves
+            .iter_mut()
+            .filter(|shelf| shelf.height >= height && shelf.x + width <= self.width)
+            .min_by_key(|shelf| shelf.height - height);
+        if let Some(shelf) = shelf {
+            let position = USizeVec2::new(shelf.x, shelf.y);
+            shelf.x += width;
+            return Some(position);
+        }
+
+        let y = self
+            .shelves
+            .last()
+            .map_or(0, |shelf| shelf.y + shelf.height);
+        let shelf_height = height.next_multiple_of(SHELF_STEP).max(height);
+        if y + height > self.height {
+            return None;
+        }
+
+        self.shelves.push(Shelf {
+            y,
+            height: shelf_height.min(self.height - y),
+            x: width,
+        });
+        Some(USizeVec2::new(0, y))
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.shelves.clear();
+    }
+}
+
+/// Where a glyph ended up.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct GlyphEntry {
+    pub page: usize,
+    /// In pixels on the page.
+    pub rect: USizeRect,
+}
+
+struct GlyphPage {
+    packer: ShelfPacker,
+    image: Image,
+    /// Made the first time the page is drawn from.
+    texture: Option<TextureRef>,
+    /// Rows of `image` that changed since the texture was last written.
+    dirty_rows: Option<Range<usize>>,
+    /// The last frame a glyph on this page was looked up or added.
+    last_used: u64,
+}
+
+impl GlyphPage {
+    fn new(width: usize, height: usize, frame: u64) -> Self {
+        Self {
+            packer: ShelfPacker::new(width, height),
+            image: Image::empty(width, height),
+            texture: None,
+            dirty_rows: None,
+            last_used: frame,
+        }
+    }
+
+    fn mark_dirty(&mut self, rows: Range<usize>) {
+        self.dirty_rows = Some(match self.dirty_rows.take() {
+            Some(dirty) => dirty.start.min(rows.start)..dirty.end.max(rows.end),
+            None => rows,
+        });
+    }
+}
+
+/// Glyph bitmaps keyed by `K`, spread over as many pages as they need. The GPU is only
+/// touched by [`texture`](Self::texture), so everything else works in headless mode.
+pub(crate) struct GlyphAtlas<K> {
+    pages: Vec<GlyphPage>,
+    entries: HashMap<K, GlyphEntry>,
+    max_pages: usize,
+    /// Set on every page's texture. `None` leaves the config's default.
+    minify_filter: Option<MinifySamplerFilter>,
+    magnify_filter: Option<MagnifySamplerFilter>,
+}
+
+impl<K: Hash + Eq + Copy> GlyphAtlas<K> {
+    pub(crate) fn new(max_pages: usize) -> Self {
+        Self {
+            pages: Vec::new(),
+            entries: HashMap::new(),
+            max_pages,
+            minify_filter: None,
+            magnify_filter: None,
+        }
+    }
+
+    pub(crate) fn contains(&self, key: K) -> bool {
+        self.entries.contains_key(&key)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub(crate) fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Finds a glyph, and counts its page as used in `frame` so it won't be cleared out from
+    /// under anything drawn this frame.
+    pub(crate) fn get(&mut self, key: K, frame: u64) -> Option<GlyphEntry> {
+        let entry = *self.entries.get(&key)?;
+        self.pages[entry.page].last_used = frame;
+        Some(entry)
+    }
+
+    /// Adds a glyph. If every page is full and there are already the most allowed, the page
+    /// that's gone unused the longest is cleared for it. Pages used this frame are never
+    /// cleared, since things drawn from them haven't reached the screen yet, so a frame with
+    /// more glyphs than fit gets extra pages instead.
+    pub(crate) fn insert(&mut self, key: K, glyph: &Image, frame: u64) -> GlyphEntry {
+        if let Some(entry) = self.get(key, frame) {
+            return entry;
+        }
+
+        let size = glyph.dimensions();
+        let (page, position) = self.find_space(size, frame);
+
+        let page_data = &mut self.pages[page];
+        for y in 0..size.y {
+            for x in 0..size.x {
+                page_data.image.set(
+                    position.x + x,
+                    position.y + y,
+                    *glyph.get_pixel(x, y).unwrap(),
+                );
+            }
+        }
+        page_data.mark_dirty(position.y..position.y + size.y);
+        page_data.last_used = frame;
+
+        let entry = GlyphEntry {
+            page,
+            rect: USizeRect::new(
+                position.x,
+                position.y,
+                position.x + size.x,
+                position.y + size.y,
+            ),
+        };
+        self.entries.insert(key, entry);
+        entry
+    }
+
+    fn find_space(&mut self, size: USizeVec2, frame: u64) -> (usize, USizeVec2) {
+        for (index, page) in self.pages.iter_mut().enumerate() {
+            if let Some(position) = page.packer.pack(size) {
+                return (index, position);
+            }
+        }
+
+        let oldest = self
+            .pages
+            .iter()
+            .enumerate()
+            .filter(|(_, page)| page.last_used < frame)
+            .min_by_key(|(_, page)| page.last_used)
+            .map(|(index, _)| index);
+
+        if self.pages.len() >= self.max_pages
+            && let Some(index) = oldest
+        {
+            self.clear_page(index);
+            if let Some(position) = self.pages[index].packer.pack(size) {
+                return (index, position);
+            }
+        }
+
+        let width = PAGE_SIZE.max((size.x + GAP).next_power_of_two());
+        let height = PAGE_SIZE.max((size.y + GAP).next_power_of_two());
+        let mut page = GlyphPage::new(width, height, frame);
+        let position = page.packer.pack(size).unwrap();
+        self.pages.push(page);
+        (self.pages.len() - 1, position)
+    }
+
+    fn clear_page(&mut self, index: usize) {
+        self.entries.retain(|_, entry| entry.page != index);
+
+        let page = &mut self.pages[index];
+        page.packer.clear();
+        let (width, height) = (page.image.width(), page.image.height());
+        page.image = Image::empty(width, height);
+        page.mark_dirty(0..height);
+    }
+
+    /// The page's texture, with anything added since last time written to it.
+    pub(crate) fn texture(&mut self, page: usize) -> TextureRef {
+        while self.pages.len() <= page {
+            self.pages.push(GlyphPage::new(PAGE_SIZE, PAGE_SIZE, 0));
+        }
+        let (minify_filter, magnify_filter) = (self.minify_filter, self.magnify_filter);
+        let page = &mut self.pages[page];
+
+        let texture = *page.texture.get_or_insert_with(|| {
+            page.dirty_rows = None;
+            let mut texture = EngineTexture::from_engine_image(page.image.clone())
+                .unwrap()
+                .create();
+            if let Some(filter) = minify_filter {
+                texture.minify_filter = filter;
+            }
+            if let Some(filter) = magnify_filter {
+                texture.magnify_filter = filter;
+            }
+            texture
+        });
+
+        if let Some(rows) = page.dirty_rows.take() {
+            let width = page.image.width();
+            let changed = page
+                .image
+                .sub_image(USizeRect::new(0, rows.start, width, rows.end));
+            let raw =
+                RawImage2d::from_raw_rgba(changed.into_bytes(), (width as u32, rows.len() as u32));
+            get_state().storage[texture].gl_texture.write(
+                glium::Rect {
+                    left: 0,
+                    bottom: rows.start as u32,
+                    width: width as u32,
+                    height: rows.len() as u32,
+                },
+                raw,
+            );
+        }
+
+        texture
+    }
+
+    pub(crate) fn set_minify_filter(&mut self, filter: MinifySamplerFilter) {
+        self.minify_filter = Some(filter);
+        for mut texture in self.pages.iter().filter_map(|page| page.texture) {
+            texture.minify_filter = filter;
+        }
+    }
+
+    pub(crate) fn set_magnify_filter(&mut self, filter: MagnifySamplerFilter) {
+        self.magnify_filter = Some(filter);
+        for mut texture in self.pages.iter().filter_map(|page| page.texture) {
+            texture.magnify_filter = filter;
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Ribbons that follow something as it moves, for sword swipes, projectiles and dashes.
+//!
+//! ```ignore
+//! let mut trail =
+//!     Trail::new(0.3, 0.5).with_color(Curve::linear(Color::SKY_400, Color::TRANSPARENT));
+//! loop {
+//!     trail.update(sword_tip, delta_time());
+//!     trail.draw_world();
+//!     next_frame();
+//! }
+//! ```
+//!
+//! It's a plain struct, so it can also be an [`ecs`](crate::ecs) component updated from a
+//! query.
+
+use std::collections::VecDeque;
+
+use bevy_math::Vec2;
+
+use crate::{
+    collisions::{AABB2D, HasBounds2D},
+    color::Color,
+    draw_queue_2d::{DrawQueue2D, Vertex2D},
+    particles::Curve,
+    shapes_2d::Shape2D,
+    textures::TextureRef,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TrailPoint {
+    position: Vec2,
+    age: f32,
+}
+
+pub struct Trail {
+    /// Seconds each point stays in the trail.
+    pub lifetime: f32,
+    /// How far it has to move before another point is added. Smaller is smoother.
+    pub min_distance: f32,
+    /// From the head (0) to the tail (1), by distance along the trail.
+    pub width: Curve<f32>,
+    /// From the head (0) to the tail (1). Multiplies the texture, if there is one.
+    pub color: Curve<Color>,
+    /// Stretched along the whole trail, with its top edge down one side.
+    pub texture: Option<TextureRef>,
+    /// While this is off no new points are added, and the trail shrinks away.
+    pub emitting: bool,
+    /// The oldest points are dropped past this many.
+    pub max_points: usize,
+    /// Newest first. The first one follows the position until it's moved far enough to add
+    /// another.
+    points: VecDeque<TrailPoint>,
+}
+
+impl Trail {
+    /// Tapering from `width` to nothing, and fading out.
+    pub fn new(lifetime: f32, width: f32) -> Self {
+        Self {
+            lifetime,
+            min_distance: 0.05,
+            width: Curve::linear(width, 0.0),
+            color: Curve::linear(Color::WHITE, Color::WHITE.with_alpha(0.0)),
+            texture: None,
+            emitting: true,
+            max_points: 256,
+            points: VecDeque::new(),
+        }
+    }
+
+    pub fn with_min_distance(mut self, min_distance: f32) -> Self {
+        self.min_distance = min_distance;
+        self
+    }
+
+    pub fn with_width(mut self, width: Curve<f32>) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn with_color(mut self, color: Curve<Color>) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_texture(mut self, texture: TextureRef) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    pub fn with_max_points(mut self, max_points: usize) -> Self {
+        self.max_points = max_points;
+        self
+    }
+
+    /// Ages the trail and, if it's emitting, follows it to `position`. Usually called every frame
+    /// with [`delta_time`](crate::prelude::delta_time).
+    pub fn update(&mut self, position: Vec2, delta_time: f32) {
+        for point in &mut self.points {
+            point.age += delta_time;
+        }
+        while self
+            .points
+            .back()
+            .is_some_and(|point| point.age > self.lifetime)
+        {
+            self.points.pop_back();
+        }
+
+        if !self.emitting {
+            return;
+        }
+
+        let head = TrailPoint { position, age: 0.0 };
+        // the newest point that's stayed put, which the head is measured from
+        match self.points.get(1) {
+            Some(anchor) if anchor.position.distance(position) < self.min_distance => {
+                self.points[0] = head;
+            }
+            _ => self.points.push_front(head),
+        }
+        self.points.truncate(self.max_points.max(2));
+    }
+
+    /// Drops every point, for when what it follows teleports.
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// Where each point is, newest first.
+    pub fn positions(&self) -> impl Iterator<Item = Vec2> + '_ {
+        self.points.iter().map(|point| point.position)
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Whether it's stopped emitting and every point has gone.
+    pub fn is_finished(&self) -> bool {
+        !self.emitting && self.points.is_empty()
+    }
+
+    /// How far along the trail each point is, from 0 at the head to 1 at the tail.
+    fn fractions(&self) -> Vec<f32> {
+        let mut distance = 0.0;
+        let mut distances = Vec::with_capacity(self.points.len());
+        for (i, point) in self.points.iter().enumerate() {
+            if i > 0 {
+                distance += self.points[i - 1].position.distance(point.position);
+            }
+            distances.push(distance);
+        }
+
+        if distance <= f32::EPSILON {
+            return vec![0.0; distances.len()];
+        }
+        distances.iter().map(|d| d / distance).collect()
+    }
+
+    /// Two vertices per point, one each side, with where along the trail it is and its color.
+    fn edges(&self) -> Vec<(Vec2, Vec2, f32, Color)> {
+        if self.points.len() < 2 {
+            return Vec::new();
+        }
+
+        let fractions = self.fractions();
+        let last = self.points.len() - 1;
+        self.points
+            .iter()
+            .enumerate()
+            .map(|(i, point)| {
+                // along the line through its neighbours, so corners are shared by both sides
+                let before = self.points[i.saturating_sub(1)].position;
+                let after = self.points[(i + 1).min(last)].position;
+                let normal = (before - after).normalize_or_zero().perp();
+
+                let t = fractions[i];
+                let half_width = self.width.sample(t) * 0.5;
+                (
+                    point.position + normal * half_width,
+                    point.position - normal * half_width,
+                    t,
+                    self.color.sample(t),
+                )
+            })
+            .collect()
+    }
+
+    /// Two triangles between each pair of points.
+    fn indices(&self, starting_index: u32) -> Vec<u32> {
+        (0..self.points.len().saturating_sub(1) as u32)
+            .flat_map(|i| {
+                let a = starting_index + i * 2;
+                [a, a + 1, a + 2, a + 1, a + 3, a + 2]
+            })
+            .collect()
+    }
+}
+
+impl HasBounds2D for Trail {
+    fn bounds(&self) -> AABB2D {
+        let Some(first) = self.points.front() else {
+            return AABB2D::new(Vec2::ZERO, Vec2::ZERO);
+        };
+        let (min, max) = self
+            .points
+            .iter()
+            .fold((first.position, first.position), |(min, max), point| {
+                (min.min(point.position), max.max(point.position))
+            });
+
+        let half_width = self
+            .fractions()
+            .into_iter()
+            .map(|t| self.width.sample(t) * 0.5)
+            .fold(0.0, f32::max);
+        let padding = Vec2::splat(half_width);
+        AABB2D::new(min - padding, max + padding)
+    }
+}
+
+impl Shape2D for Trail {
+    fn points(&self, starting_index: u32) -> (Vec<u32>, Vec<Vertex2D>) {
+        let vertices = self
+            .edges()
+            .into_iter()
+            .flat_map(|(left, right, _, color)| {
+                [
+                    Vertex2D::new(left.x, left.y, color),
+                    Vertex2D::new(right.x, right.y, color),
+                ]
+            })
+            .collect();
+        (self.indices(starting_index), vertices)
+    }
+
+    fn add_to_draw_queue(&self, draw_queue: &mut DrawQueue2D) {
+        if self.points.len() < 2 {
+            return;
+        }
+
+        match self.texture {
+            Some(texture) => {
+                let vertices: Vec<_> = self
+                    .edges()
+                    .into_iter()
+                    .flat_map(|(left, right, t, color)| {
+                        [
+                            (left, Vec2::new(t, 0.0), color),
+                            (right, Vec2::new(t, 1.0), color),
+                        ]
+                    })
+                    .collect();
+                draw_queue.add_colored_textured_mesh(texture, &vertices, &self.indices(0));
+            }
+            None => draw_queue.add_shape(self),
+        }
+    }
+}
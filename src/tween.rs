@@ -0,0 +1,242 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use crate::{
+    animation::{Animatable, EasingFunction},
+    get_state,
+};
+
+struct Segment<T> {
+    end: T,
+    duration: f32,
+    ease: Box<dyn EasingFunction>,
+}
+
+struct TweenState<T> {
+    /// Where the current segment started.
+    start: T,
+    segments: VecDeque<Segment<T>>,
+    /// Time into the current segment.
+    elapsed: f32,
+    value: T,
+    paused: bool,
+    stopped: bool,
+    on_update: Vec<Box<dyn FnMut(T)>>,
+    on_complete: Vec<Box<dyn FnOnce()>>,
+}
+
+/// A value moving from one thing to another over time. Cheap to clone: clones are handles to
+/// the same tween.
+///
+/// Poll it with [`value`](Self::value), or have it write into something every update with
+/// [`on_update`](Self::on_update). Works with anything [`Animatable`]: numbers, vectors,
+/// colors, transforms and more.
+pub struct Tween<T>(Rc<RefCell<TweenState<T>>>);
+
+impl<T> Clone for Tween<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Animatable + Copy + 'static> Tween<T> {
+    /// A tween that isn't running yet. [`Tweens::add`] or [`tween`] start it.
+    pub fn new(start: T, end: T, duration: f32, ease: impl EasingFunction + 'static) -> Self {
+        Self(Rc::new(RefCell::new(TweenState {
+            start,
+            segments: VecDeque::from([Segment {
+                end,
+                duration,
+                ease: Box::new(ease),
+            }]),
+            elapsed: 0.0,
+            value: start,
+            paused: false,
+            stopped: false,
+            on_update: Vec::new(),
+            on_complete: Vec::new(),
+        })))
+    }
+
+    /// After getting to the end, carries on to `end`.
+    pub fn then(self, end: T, duration: f32, ease: impl EasingFunction + 'static) -> Self {
+        self.0.borrow_mut().segments.push_back(Segment {
+            end,
+            duration,
+            ease: Box::new(ease),
+        });
+        self
+    }
+
+    /// Called with the new value every update, including the last.
+    pub fn on_update(self, callback: impl FnMut(T) + 'static) -> Self {
+        self.0.borrow_mut().on_update.push(Box::new(callback));
+        self
+    }
+
+    /// Called once when the whole chain finishes. Not called if it's stopped.
+    pub fn on_complete(self, callback: impl FnOnce() + 'static) -> Self {
+        self.0.borrow_mut().on_complete.push(Box::new(callback));
+        self
+    }
+
+    pub fn value(&self) -> T {
+        self.0.borrow().value
+    }
+
+    /// Finished the whole chain, or was stopped.
+    pub fn is_complete(&self) -> bool {
+        let state = self.0.borrow();
+        state.stopped || state.segments.is_empty()
+    }
+
+    pub fn pause(&self) {
+        self.0.borrow_mut().paused = true;
+    }
+
+    pub fn resume(&self) {
+        self.0.borrow_mut().paused = false;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.0.borrow().paused
+    }
+
+    /// Stops where it is, without calling the on-complete callbacks.
+    pub fn stop(&self) {
+        self.0.borrow_mut().stopped = true;
+    }
+
+    /// Skips to the end of the chain, calling the callbacks as if it got there normally.
+    pub fn finish(&self) {
+        self.advance(f32::INFINITY);
+    }
+
+    /// Moves on by `delta_time`. Returns whether it's still going.
+    fn advance(&self, delta_time: f32) -> bool {
+        let mut guard = self.0.borrow_mut();
+        let state = &mut *guard;
+        if state.stopped {
+            return false;
+        }
+        if state.paused {
+            return true;
+        }
+
+        state.elapsed += delta_time;
+        while let Some(segment) = state.segments.front()
+            && state.elapsed >= segment.duration
+        {
+            state.elapsed -= segment.duration;
+            state.start = segment.end;
+            state.segments.pop_front();
+        }
+
+        state.value = match state.segments.front() {
+            Some(segment) => {
+                let progress = segment.ease.progress(state.elapsed / segment.duration);
+                T::lerp(state.start, segment.end, progress)
+            }
+            None => state.start,
+        };
+
+        let value = state.value;
+        let running = !state.segments.is_empty();
+        let mut on_update = std::mem::take(&mut state.on_update);
+        let on_complete = if running {
+            Vec::new()
+        } else {
+            std::mem::take(&mut state.on_complete)
+        };
+        // callbacks might look at the tween, so it can't be borrowed while they run
+        drop(guard);
+
+        for callback in &mut on_update {
+            callback(value);
+        }
+        for callback in on_complete {
+            callback();
+        }
+
+        let mut state = self.0.borrow_mut();
+        on_update.append(&mut state.on_update);
+        state.on_update = on_update;
+        running && !state.stopped
+    }
+}
+
+trait AnyTween {
+    fn advance(&self, delta_time: f32) -> bool;
+}
+
+impl<T: Animatable + Copy + 'static> AnyTween for Tween<T> {
+    fn advance(&self, delta_time: f32) -> bool {
+        Tween::advance(self, delta_time)
+    }
+}
+
+/// Keeps a set of [`Tween`]s moving. The engine has one, updated every frame, that [`tween`]
+/// adds to.
+#[derive(Default)]
+pub struct Tweens {
+    active: Vec<Box<dyn AnyTween>>,
+}
+
+impl Tweens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<T: Animatable + Copy + 'static>(&mut self, tween: Tween<T>) -> Tween<T> {
+        self.active.push(Box::new(tween.clone()));
+        tween
+    }
+
+    pub fn tween<T: Animatable + Copy + 'static>(
+        &mut self,
+        start: T,
+        end: T,
+        duration: f32,
+        ease: impl EasingFunction + 'static,
+    ) -> Tween<T> {
+        self.add(Tween::new(start, end, duration, ease))
+    }
+
+    /// How many tweens are still going.
+    pub fn len(&self) -> usize {
+        self.active.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.active.is_empty()
+    }
+
+    /// Moves every tween on, and forgets the ones that have finished.
+    pub fn update(&mut self, delta_time: f32) {
+        self.active.retain(|tween| tween.advance(delta_time));
+    }
+}
+
+/// Starts moving from `start` to `end` over `duration` seconds of engine time:
+///
+/// ```ignore
+/// let fade = tween(Color::WHITE, Color::TRANSPARENT, 0.5, Ease::OutCubic)
+///     .on_complete(|| log::info!("gone"));
+/// ```
+pub fn tween<T: Animatable + Copy + 'static>(
+    start: T,
+    end: T,
+    duration: f32,
+    ease: impl EasingFunction + 'static,
+) -> Tween<T> {
+    get_state().tweens.tween(start, end, duration, ease)
+}
+
+pub(crate) fn update_tweens(delta_time: f32) {
+    let state = get_state();
+
+    // callbacks can start new tweens, which shouldn't land in the list being updated
+    let mut running = std::mem::take(&mut state.tweens);
+    running.update(delta_time);
+    let added = std::mem::replace(&mut state.tweens, running);
+    state.tweens.active.extend(added.active);
+}
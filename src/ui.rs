@@ -0,0 +1,520 @@
+//! Simple widgets drawn with the 2D draw queue, for HUDs and menus that should look like the
+//! rest of the game instead of like egui.
+//!
+//! ```ignore
+//! let mut ui = Ui::new();
+//! loop {
+//!     ui.panel(Anchor::TopRight, vec2(10.0, 10.0), |ui| {
+//!         ui.label("Paused");
+//!         if ui.button("Quit") {
+//!             quit = true;
+//!         }
+//!         ui.slider("Volume", &mut volume, 0.0..=1.0);
+//!     });
+//!     next_frame();
+//! }
+//! ```
+
+use std::ops::RangeInclusive;
+
+use bevy_math::{Rect, Vec2, vec2};
+use glium::winit::event::MouseButton;
+
+use crate::{
+    api::{cursor_pos, frame_count, ui_scaling},
+    color::{Color, theme::Theme, theme::theme},
+    input_handling::{mouse_held, mouse_pressed, mouse_released},
+    shapes_2d::{draw_rounded_rect, draw_rounded_rect_outline},
+    text_rendering::{FontRef, TextDrawParams, draw_text_ex, measure_text_ex},
+    ui_layout::safe_area,
+};
+
+/// Where on the screen something is lined up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// How far across and down the anchor is, from 0 to 1. It's also the point on the
+    /// anchored thing that's lined up with it, so `Center` centres things.
+    pub fn fraction(self) -> Vec2 {
+        let x = match self {
+            Self::TopLeft | Self::Left | Self::BottomLeft => 0.0,
+            Self::Top | Self::Center | Self::Bottom => 0.5,
+            Self::TopRight | Self::Right | Self::BottomRight => 1.0,
+        };
+        let y = match self {
+            Self::TopLeft | Self::Top | Self::TopRight => 0.0,
+            Self::Left | Self::Center | Self::Right => 0.5,
+            Self::BottomLeft | Self::Bottom | Self::BottomRight => 1.0,
+        };
+        vec2(x, y)
+    }
+
+    /// The top left of something `size` big anchored inside of `area`. `offset` pushes it away
+    /// from the edges it's anchored to, so it works as a margin for every anchor.
+    pub fn place(self, area: Rect, size: Vec2, offset: Vec2) -> Vec2 {
+        let fraction = self.fraction();
+        let direction = Vec2::select(fraction.cmpgt(Vec2::splat(0.5)), -Vec2::ONE, Vec2::ONE);
+        area.min + (area.size() - size) * fraction + offset * direction
+    }
+}
+
+/// How [`Ui`] widgets look. Sizes are in pixels before [`Ui::scale`] and DPI scaling.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UiStyle {
+    pub font: Option<FontRef>,
+    pub font_size: f32,
+    /// Space between a panel's edge and what's in it, and around text in buttons.
+    pub padding: f32,
+    /// Space between widgets.
+    pub spacing: f32,
+    pub corner_radius: f32,
+    pub border_width: f32,
+    /// How wide sliders and progress bars are.
+    pub widget_width: f32,
+
+    pub panel: Color,
+    pub border: Color,
+    pub widget: Color,
+    pub hovered: Color,
+    pub pressed: Color,
+    /// Slider and progress bar fills, and checkbox ticks.
+    pub fill: Color,
+    pub text: Color,
+}
+
+impl UiStyle {
+    pub fn from_theme(theme: &Theme) -> Self {
+        Self {
+            font: None,
+            font_size: 20.0,
+            padding: 8.0,
+            spacing: 6.0,
+            corner_radius: 4.0,
+            border_width: 1.0,
+            widget_width: 200.0,
+
+            panel: theme.surface.with_alpha(0.9),
+            border: theme.border,
+            widget: theme.surface_alt,
+            hovered: theme.border,
+            pressed: theme.primary,
+            fill: theme.primary,
+            text: theme.text,
+        }
+    }
+}
+
+/// Where the next widget goes.
+#[derive(Clone, Copy, Debug)]
+struct Layout {
+    top_left: Vec2,
+    /// The size of everything added so far.
+    size: Vec2,
+    /// Panels are hidden the first frame, before they know how big they are.
+    visible: bool,
+}
+
+impl Layout {
+    fn new(top_left: Vec2, visible: bool) -> Self {
+        Self {
+            top_left,
+            size: Vec2::ZERO,
+            visible,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Interaction {
+    hovered: bool,
+    /// Held down on this widget, even if the cursor has moved off of it since.
+    held: bool,
+    /// Pressed and released on this widget.
+    clicked: bool,
+}
+
+/// Immediate mode widgets: call them every frame you want them shown, and they return
+/// whether they were used. Keep one around between frames, so it can remember things like
+/// which slider is being dragged.
+///
+/// Widgets stack top to bottom inside [`panel`](Self::panel)s, or from the top left of the
+/// screen outside of one.
+#[derive(Clone, Debug)]
+pub struct Ui {
+    pub style: UiStyle,
+    /// Multiplies every size, on top of DPI scaling.
+    pub scale: f32,
+    /// Whether DPI scaling and the player's `ui_scale` are applied, so the UI is the same
+    /// physical size on every screen.
+    pub do_dpi_scaling: bool,
+    layout: Layout,
+    frame: Option<usize>,
+    /// How big each panel was last frame, by the order they were made in.
+    panel_sizes: Vec<Option<Vec2>>,
+    next_panel: usize,
+    next_widget: usize,
+    /// The widget the mouse was pressed on, until it's released.
+    held: Option<usize>,
+    mouse_over: bool,
+    mouse_over_next: bool,
+}
+
+impl Ui {
+    /// Styled from the current [`theme`].
+    pub fn new() -> Self {
+        Self::with_style(UiStyle::from_theme(theme()))
+    }
+
+    pub fn with_style(style: UiStyle) -> Self {
+        Self {
+            style,
+            scale: 1.0,
+            do_dpi_scaling: true,
+            layout: Layout::new(Vec2::ZERO, true),
+            frame: None,
+            panel_sizes: Vec::new(),
+            next_panel: 0,
+            next_widget: 0,
+            held: None,
+            mouse_over: false,
+            mouse_over_next: false,
+        }
+    }
+
+    /// Whether the cursor is over a panel or a widget is being dragged, so clicks shouldn't
+    /// go through to the game.
+    pub fn wants_mouse(&self) -> bool {
+        self.mouse_over || self.held.is_some()
+    }
+
+    /// The full scale sizes get multiplied by.
+    pub fn pixel_scale(&self) -> f32 {
+        let dpi_scaling = if self.do_dpi_scaling {
+            ui_scaling()
+        } else {
+            1.0
+        };
+        self.scale * dpi_scaling
+    }
+
+    /// Resets things the first time the UI is used each frame.
+    fn begin_frame(&mut self) {
+        let frame = frame_count();
+        if self.frame == Some(frame) {
+            return;
+        }
+        self.frame = Some(frame);
+
+        let padding = self.style.padding * self.pixel_scale();
+        self.layout = Layout::new(safe_area().min + Vec2::splat(padding), true);
+        self.next_panel = 0;
+        self.next_widget = 0;
+        self.mouse_over = self.mouse_over_next;
+        self.mouse_over_next = false;
+
+        // the widget that was held wasn't shown last frame, so never saw the release
+        if !mouse_held(MouseButton::Left) && !mouse_released(MouseButton::Left) {
+            self.held = None;
+        }
+    }
+
+    /// A box of widgets anchored to the [`safe_area`], with `offset` as the margin from the edges it's
+    /// anchored to. It's sized to fit the widgets added in `f`.
+    pub fn panel<T>(&mut self, anchor: Anchor, offset: Vec2, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.begin_frame();
+
+        let index = self.next_panel;
+        self.next_panel += 1;
+        if self.panel_sizes.len() <= index {
+            self.panel_sizes.resize(index + 1, None);
+        }
+
+        // panels only know their size once their widgets are added, so this goes off of last
+        // frame's
+        let scale = self.pixel_scale();
+        let known_size = self.panel_sizes[index];
+        let size = known_size.unwrap_or(Vec2::ZERO);
+        let top_left = anchor.place(safe_area(), size, offset * scale);
+        let visible = self.layout.visible && known_size.is_some();
+
+        if visible {
+            let radius = self.style.corner_radius * scale;
+            draw_rounded_rect(top_left, size, radius, self.style.panel);
+            if self.style.border_width > 0.0 {
+                let width = self.style.border_width * scale;
+                draw_rounded_rect_outline(top_left, size, radius, width, self.style.border);
+            }
+
+            if Rect::from_corners(top_left, top_left + size).contains(cursor_pos()) {
+                self.mouse_over_next = true;
+            }
+        }
+
+        let padding = self.style.padding * scale;
+        let outer = std::mem::replace(
+            &mut self.layout,
+            Layout::new(top_left + Vec2::splat(padding), visible),
+        );
+        let result = f(self);
+        let inner = std::mem::replace(&mut self.layout, outer);
+
+        self.panel_sizes[index] = Some(inner.size + Vec2::splat(padding * 2.0));
+        result
+    }
+
+    /// Empty space between widgets.
+    pub fn space(&mut self, amount: f32) {
+        self.begin_frame();
+        self.layout.size.y += amount * self.pixel_scale();
+    }
+
+    pub fn label(&mut self, text: impl AsRef<str>) {
+        self.begin_frame();
+        let text = text.as_ref();
+        let rect = self.allocate(self.text_size(text));
+        if self.layout.visible {
+            self.draw_text(text, rect.min, self.style.text);
+        }
+    }
+
+    /// Returns true the frame it's clicked.
+    pub fn button(&mut self, text: impl AsRef<str>) -> bool {
+        self.begin_frame();
+        let text = text.as_ref();
+        let padding = self.style.padding * self.pixel_scale();
+        let rect = self.allocate(self.text_size(text) + Vec2::splat(padding * 2.0));
+        let interaction = self.interact(rect);
+
+        if self.layout.visible {
+            self.draw_frame(rect, self.widget_color(interaction));
+            self.draw_text_centered(text, rect, self.style.text);
+        }
+
+        interaction.clicked
+    }
+
+    /// A box that's ticked and unticked by clicking it or its label. Returns true if it changed.
+    pub fn checkbox(&mut self, text: impl AsRef<str>, checked: &mut bool) -> bool {
+        self.begin_frame();
+        let text = text.as_ref();
+        let scale = self.pixel_scale();
+        let text_size = self.text_size(text);
+        let box_size = text_size.y;
+        let gap = self.style.spacing * scale;
+        let rect = self.allocate(vec2(box_size + gap + text_size.x, box_size));
+        let interaction = self.interact(rect);
+
+        if interaction.clicked {
+            *checked = !*checked;
+        }
+
+        if self.layout.visible {
+            let check_box = Rect::from_corners(rect.min, rect.min + Vec2::splat(box_size));
+            self.draw_frame(check_box, self.widget_color(interaction));
+            if *checked {
+                let inset = box_size * 0.25;
+                let radius = self.style.corner_radius * scale * 0.5;
+                draw_rounded_rect(
+                    check_box.min + inset,
+                    check_box.size() - inset * 2.0,
+                    radius,
+                    self.style.fill,
+                );
+            }
+            self.draw_text(text, rect.min + vec2(box_size + gap, 0.0), self.style.text);
+        }
+
+        interaction.clicked
+    }
+
+    /// Drags `value` between the ends of `range`. Returns true if it changed.
+    pub fn slider(
+        &mut self,
+        text: impl AsRef<str>,
+        value: &mut f32,
+        range: RangeInclusive<f32>,
+    ) -> bool {
+        self.begin_frame();
+        let rect = self.allocate(self.bar_size());
+        let interaction = self.interact(rect);
+
+        let previous = *value;
+        if interaction.held {
+            *value = slider_value(cursor_pos().x, rect, range.clone());
+        }
+
+        if self.layout.visible {
+            let fraction = slider_fraction(*value, range);
+            self.draw_bar(rect, fraction, self.widget_color(interaction));
+            let text = format!("{}: {}", text.as_ref(), format_value(*value));
+            self.draw_text_centered(&text, rect, self.style.text);
+        }
+
+        *value != previous
+    }
+
+    /// A bar filled `fraction` of the way, from 0 to 1.
+    pub fn progress_bar(&mut self, fraction: f32) {
+        self.progress_bar_with_text(fraction, "");
+    }
+
+    /// A progress bar with text over it, like `"12 / 20 HP"`.
+    pub fn progress_bar_with_text(&mut self, fraction: f32, text: impl AsRef<str>) {
+        self.begin_frame();
+        let rect = self.allocate(self.bar_size());
+
+        if self.layout.visible {
+            self.draw_bar(rect, fraction.clamp(0.0, 1.0), self.style.widget);
+            self.draw_text_centered(text.as_ref(), rect, self.style.text);
+        }
+    }
+
+    /// Takes the next spot in the layout.
+    fn allocate(&mut self, size: Vec2) -> Rect {
+        let spacing = self.style.spacing * self.pixel_scale();
+        let layout = &mut self.layout;
+        if layout.size.y > 0.0 {
+            layout.size.y += spacing;
+        }
+
+        let top_left = layout.top_left + vec2(0.0, layout.size.y);
+        layout.size.x = layout.size.x.max(size.x);
+        layout.size.y += size.y;
+        Rect::from_corners(top_left, top_left + size)
+    }
+
+    fn interact(&mut self, rect: Rect) -> Interaction {
+        let id = self.next_widget;
+        self.next_widget += 1;
+        if !self.layout.visible {
+            return Interaction::default();
+        }
+
+        let hovered = rect.contains(cursor_pos());
+        if hovered && self.held.is_none() && mouse_pressed(MouseButton::Left) {
+            self.held = Some(id);
+        }
+
+        let held = self.held == Some(id);
+        let clicked = held && hovered && mouse_released(MouseButton::Left);
+        if held && !mouse_held(MouseButton::Left) {
+            self.held = None;
+        }
+
+        Interaction {
+            hovered,
+            held,
+            clicked,
+        }
+    }
+
+    fn widget_color(&self, interaction: Interaction) -> Color {
+        if interaction.held {
+            self.style.pressed
+        } else if interaction.hovered {
+            self.style.hovered
+        } else {
+            self.style.widget
+        }
+    }
+
+    fn bar_size(&self) -> Vec2 {
+        let scale = self.pixel_scale();
+        let height = self.text_size("").y + self.style.padding * scale;
+        vec2(self.style.widget_width * scale, height)
+    }
+
+    fn text_params(&self, position: Vec2, color: Color) -> TextDrawParams {
+        TextDrawParams {
+            font: self.style.font,
+            font_size: (self.style.font_size * self.scale).round() as usize,
+            color,
+            position,
+            do_dpi_scaling: self.do_dpi_scaling,
+        }
+    }
+
+    fn text_size(&self, text: &str) -> Vec2 {
+        let size = measure_text_ex(text, self.text_params(Vec2::ZERO, self.style.text)).size;
+        // empty text still takes up a line, so widgets don't collapse
+        let line_height = self.style.font_size * self.pixel_scale();
+        vec2(size.x, size.y.max(line_height))
+    }
+
+    fn draw_text(&self, text: &str, position: Vec2, color: Color) {
+        draw_text_ex(text, self.text_params(position, color));
+    }
+
+    fn draw_text_centered(&self, text: &str, rect: Rect, color: Color) {
+        if text.is_empty() {
+            return;
+        }
+        let size = self.text_size(text);
+        self.draw_text(text, rect.center() - size / 2.0, color);
+    }
+
+    fn draw_frame(&self, rect: Rect, color: Color) {
+        let scale = self.pixel_scale();
+        let radius = self.style.corner_radius * scale;
+        draw_rounded_rect(rect.min, rect.size(), radius, color);
+        if self.style.border_width > 0.0 {
+            let width = self.style.border_width * scale;
+            draw_rounded_rect_outline(rect.min, rect.size(), radius, width, self.style.border);
+        }
+    }
+
+    fn draw_bar(&self, rect: Rect, fraction: f32, background: Color) {
+        self.draw_frame(rect, background);
+        if fraction > 0.0 {
+            let radius = self.style.corner_radius * self.pixel_scale();
+            let size = vec2(rect.width() * fraction, rect.height());
+            draw_rounded_rect(rect.min, size, radius, self.style.fill);
+        }
+    }
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The value a slider spanning `rect` has with the cursor at `cursor_x`.
+pub(crate) fn slider_value(cursor_x: f32, rect: Rect, range: RangeInclusive<f32>) -> f32 {
+    let fraction = if rect.width() > 0.0 {
+        ((cursor_x - rect.min.x) / rect.width()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    range.start() + (range.end() - range.start()) * fraction
+}
+
+/// How far along `range` `value` is, from 0 to 1.
+pub(crate) fn slider_fraction(value: f32, range: RangeInclusive<f32>) -> f32 {
+    let length = range.end() - range.start();
+    if length == 0.0 {
+        return 0.0;
+    }
+    ((value - range.start()) / length).clamp(0.0, 1.0)
+}
+
+/// Short enough for a slider, without hiding small numbers.
+fn format_value(value: f32) -> String {
+    if value.abs() >= 100.0 {
+        format!("{value:.0}")
+    } else {
+        format!("{value:.2}")
+    }
+}
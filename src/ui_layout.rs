@@ -0,0 +1,188 @@
+//! Working out where HUD elements go from anchors, margins and sizes relative to the screen,
+//! so they stay in the right place when the window is resized.
+
+use std::ops::{Add, Sub};
+
+use bevy_math::{Rect, Vec2, vec2};
+
+use crate::{
+    api::{ui_scaling, window_size},
+    get_state,
+    ui::Anchor,
+};
+
+/// A size or margin made of a part in pixels and a part relative to the area it's in, like
+/// `50% - 20px`. The pixel part is DPI scaled.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct UiSize {
+    pub pixels: Vec2,
+    /// Percent of the area, from 0 to 100.
+    pub percent: Vec2,
+}
+
+impl UiSize {
+    pub const ZERO: Self = Self {
+        pixels: Vec2::ZERO,
+        percent: Vec2::ZERO,
+    };
+
+    pub const fn px(x: f32, y: f32) -> Self {
+        Self {
+            pixels: vec2(x, y),
+            percent: Vec2::ZERO,
+        }
+    }
+
+    pub const fn percent(x: f32, y: f32) -> Self {
+        Self {
+            pixels: Vec2::ZERO,
+            percent: vec2(x, y),
+        }
+    }
+
+    /// The size in pixels inside of an area `area` big.
+    pub fn resolve(self, area: Vec2, scale: f32) -> Vec2 {
+        self.pixels * scale + self.percent / 100.0 * area
+    }
+}
+
+impl From<Vec2> for UiSize {
+    fn from(pixels: Vec2) -> Self {
+        Self::px(pixels.x, pixels.y)
+    }
+}
+
+impl Add for UiSize {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            pixels: self.pixels + other.pixels,
+            percent: self.percent + other.percent,
+        }
+    }
+}
+
+impl Sub for UiSize {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self {
+            pixels: self.pixels - other.pixels,
+            percent: self.percent - other.percent,
+        }
+    }
+}
+
+/// How much of each edge of the window to keep HUDs out of, in pixels. For TVs that cut off
+/// the edges of the picture, or screens with notches.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SafeArea {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl SafeArea {
+    pub fn new(left: f32, right: f32, top: f32, bottom: f32) -> Self {
+        Self {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+
+    pub fn uniform(inset: f32) -> Self {
+        Self::new(inset, inset, inset, inset)
+    }
+
+    /// What's left of `area` after the insets.
+    pub fn apply(self, area: Rect) -> Rect {
+        let min = area.min + vec2(self.left, self.top);
+        let max = area.max - vec2(self.right, self.bottom);
+        Rect::from_corners(min, max.max(min))
+    }
+}
+
+pub fn set_safe_area(safe_area: SafeArea) {
+    get_state().safe_area = safe_area;
+}
+
+pub fn safe_area_insets() -> SafeArea {
+    get_state().safe_area
+}
+
+/// The part of the window inside of the [`SafeArea`], in pixels.
+pub fn safe_area() -> Rect {
+    let screen = Rect::from_corners(Vec2::ZERO, window_size());
+    get_state().safe_area.apply(screen)
+}
+
+/// Where something goes in `area`, given where it's anchored, its size, and its margin from the
+/// edges it's anchored to. Percentages are of `area`, and pixels are multiplied by `scale`.
+pub fn layout_in(area: Rect, anchor: Anchor, size: UiSize, margin: UiSize, scale: f32) -> Rect {
+    let size = size.resolve(area.size(), scale);
+    let margin = margin.resolve(area.size(), scale);
+    let top_left = anchor.place(area, size, margin);
+    Rect::from_corners(top_left, top_left + size)
+}
+
+/// [`layout_in`] the [`safe_area`] with [`ui_scaling`](crate::prelude::ui_scaling), for this
+/// frame's window size.
+pub fn ui_layout(anchor: Anchor, size: UiSize, margin: UiSize) -> Rect {
+    layout_in(safe_area(), anchor, size, margin, ui_scaling())
+}
+
+/// A HUD element's place on screen, worked out again every time it's used so it follows the
+/// window size.
+///
+/// ```ignore
+/// let minimap = Anchored::new(Anchor::TopRight, UiSize::percent(20.0, 20.0))
+///     .with_margin(UiSize::px(16.0, 16.0));
+/// minimap.draw(|rect| draw_texture_scaled(map, rect.min, rect.size()));
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Anchored {
+    pub anchor: Anchor,
+    pub size: UiSize,
+    pub margin: UiSize,
+    /// Lays out inside of the whole window instead, for things like full screen overlays.
+    pub ignore_safe_area: bool,
+}
+
+impl Anchored {
+    pub fn new(anchor: Anchor, size: impl Into<UiSize>) -> Self {
+        Self {
+            anchor,
+            size: size.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_margin(mut self, margin: impl Into<UiSize>) -> Self {
+        self.margin = margin.into();
+        self
+    }
+
+    pub fn ignoring_safe_area(mut self) -> Self {
+        self.ignore_safe_area = true;
+        self
+    }
+
+    /// Where it is this frame, in pixels.
+    pub fn rect(&self) -> Rect {
+        let area = if self.ignore_safe_area {
+            Rect::from_corners(Vec2::ZERO, window_size())
+        } else {
+            safe_area()
+        };
+        layout_in(area, self.anchor, self.size, self.margin, ui_scaling())
+    }
+
+    /// Calls `f` with where it is this frame, for it to draw into.
+    pub fn draw<T>(&self, f: impl FnOnce(Rect) -> T) -> T {
+        f(self.rect())
+    }
+}
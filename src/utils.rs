@@ -1,3 +1,4 @@
+pub mod slots;
 pub mod usize_rect;
 
 pub trait EngineCreate<R> {
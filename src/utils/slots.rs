@@ -0,0 +1,99 @@
+/// Storage for things handed out by reference, like textures and fonts. Each slot has a
+/// generation that goes up when its value is removed, so a reference made before that doesn't
+/// find whatever gets put there next.
+#[derive(Debug, Clone)]
+pub(crate) struct Slots<T> {
+    values: Vec<Option<T>>,
+    generations: Vec<u32>,
+    free: Vec<u32>,
+    /// The first this many belong to the engine, and can't be removed.
+    builtin: u32,
+}
+
+impl<T> Default for Slots<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Slots<T> {
+    pub fn new() -> Self {
+        Self {
+            values: Vec::new(),
+            generations: Vec::new(),
+            free: Vec::new(),
+            builtin: 0,
+        }
+    }
+
+    /// Marks everything in so far as the engine's own, like the default font, which handles
+    /// to are made with `from_raw` and expected to always work.
+    pub fn mark_builtin(&mut self) {
+        self.builtin = self.values.len() as u32;
+    }
+
+    pub fn is_builtin(&self, index: u32) -> bool {
+        index < self.builtin
+    }
+
+    /// Returns the index and generation of the slot it went in.
+    pub fn insert(&mut self, value: T) -> (u32, u32) {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.values.push(None);
+                self.generations.push(0);
+                self.values.len() as u32 - 1
+            }
+        };
+
+        self.values[index as usize] = Some(value);
+        (index, self.generations[index as usize])
+    }
+
+    pub fn get(&self, index: u32, generation: u32) -> Option<&T> {
+        if *self.generations.get(index as usize)? != generation {
+            return None;
+        }
+        self.values[index as usize].as_ref()
+    }
+
+    pub fn get_mut(&mut self, index: u32, generation: u32) -> Option<&mut T> {
+        if *self.generations.get(index as usize)? != generation {
+            return None;
+        }
+        self.values[index as usize].as_mut()
+    }
+
+    pub fn remove(&mut self, index: u32, generation: u32) -> Option<T> {
+        if *self.generations.get(index as usize)? != generation {
+            return None;
+        }
+
+        let value = self.values[index as usize].take()?;
+        // wraps instead of panicking, and a handle would have to outlive 4 billion reuses of its
+        // slot to be fooled by that
+        self.generations[index as usize] = self.generations[index as usize].wrapping_add(1);
+        self.free.push(index);
+        Some(value)
+    }
+
+    pub fn contains(&self, index: u32, generation: u32) -> bool {
+        self.get(index, generation).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len() - self.free.len()
+    }
+
+    /// Every value, with the index and generation it can be found at.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (u32, u32, &mut T)> {
+        self.values
+            .iter_mut()
+            .zip(&self.generations)
+            .enumerate()
+            .filter_map(|(index, (value, generation))| {
+                Some((index as u32, *generation, value.as_mut()?))
+            })
+    }
+}
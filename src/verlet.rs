@@ -0,0 +1,366 @@
+use bevy_math::Vec2;
+use rapier2d::prelude::{EventHandler, PhysicsHooks, QueryFilter};
+
+use crate::{
+    collisions::{Circle, contact::CollideWith},
+    color::Color,
+    physics_world::World,
+    shapes_2d::{Line, Polyline, Shape2D},
+};
+
+/// A point in a [`SoftBody2D`]. Its velocity is however far it moved last step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VerletPoint {
+    pub position: Vec2,
+    pub previous: Vec2,
+    /// Pinned points stay where they are, holding up everything attached to them.
+    pub pinned: bool,
+}
+
+impl VerletPoint {
+    pub fn new(position: Vec2) -> Self {
+        Self {
+            position,
+            previous: position,
+            pinned: false,
+        }
+    }
+
+    pub fn velocity(&self, dt: f32) -> Vec2 {
+        if dt > 0.0 {
+            (self.position - self.previous) / dt
+        } else {
+            Vec2::ZERO
+        }
+    }
+}
+
+/// Keeps two points of a [`SoftBody2D`] `length` apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DistanceConstraint {
+    pub a: usize,
+    pub b: usize,
+    pub length: f32,
+}
+
+/// Points joined by sticks, simulated with verlet integration: cheap, stable, and floppy in a
+/// way that looks right for chains, vines, cloth and jelly. Nothing here is a rapier body, so
+/// the rest of the physics world doesn't get pushed around by it.
+///
+/// Call [`step`](Self::step) every frame, then [`collide`](Self::collide) or
+/// [`collide_with_world`](Self::collide_with_world) for whatever it should rest on.
+#[derive(Debug, Clone)]
+pub struct SoftBody2D {
+    pub points: Vec<VerletPoint>,
+    pub constraints: Vec<DistanceConstraint>,
+    pub gravity: Vec2,
+    /// How much velocity is kept each step, from 0 to 1. A bit under 1 stops it wobbling
+    /// forever.
+    pub damping: f32,
+    /// How many times per step the constraints are solved. More is stiffer but slower.
+    pub iterations: usize,
+    /// How thick the points are when colliding.
+    pub radius: f32,
+}
+
+impl Default for SoftBody2D {
+    fn default() -> Self {
+        Self {
+            points: Vec::new(),
+            constraints: Vec::new(),
+            gravity: Vec2::new(0.0, -980.0),
+            damping: 0.99,
+            iterations: 8,
+            radius: 2.0,
+        }
+    }
+}
+
+impl SoftBody2D {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A strip of cloth `columns` by `rows` points, hanging down from `top_left` with its top
+    /// row pinned.
+    pub fn cloth(top_left: Vec2, columns: usize, rows: usize, spacing: f32) -> Self {
+        let mut body = Self::new();
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let index = body.add_point(
+                    top_left + Vec2::new(column as f32 * spacing, -(row as f32) * spacing),
+                );
+                if row == 0 {
+                    body.pin(index);
+                }
+                if column > 0 {
+                    body.add_constraint(index - 1, index);
+                }
+                if row > 0 {
+                    body.add_constraint(index - columns, index);
+                }
+            }
+        }
+
+        body
+    }
+
+    pub fn with_gravity(mut self, gravity: Vec2) -> Self {
+        self.gravity = gravity;
+        self
+    }
+
+    pub fn with_damping(mut self, damping: f32) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    /// Returns the index of the new point.
+    pub fn add_point(&mut self, position: Vec2) -> usize {
+        self.points.push(VerletPoint::new(position));
+        self.points.len() - 1
+    }
+
+    /// Joins two points, keeping them as far apart as they are now.
+    pub fn add_constraint(&mut self, a: usize, b: usize) {
+        let length = self.points[a].position.distance(self.points[b].position);
+        self.add_constraint_with_length(a, b, length);
+    }
+
+    pub fn add_constraint_with_length(&mut self, a: usize, b: usize, length: f32) {
+        self.constraints.push(DistanceConstraint { a, b, length });
+    }
+
+    pub fn pin(&mut self, index: usize) {
+        self.points[index].pinned = true;
+    }
+
+    pub fn unpin(&mut self, index: usize) {
+        self.points[index].pinned = false;
+    }
+
+    /// Teleports a point, without giving it any velocity. Useful for dragging pinned points
+    /// around.
+    pub fn set_position(&mut self, index: usize, position: Vec2) {
+        let point = &mut self.points[index];
+        point.position = position;
+        point.previous = position;
+    }
+
+    /// Moves a point, keeping its old position so it flings things around when it's moved fast.
+    pub fn move_to(&mut self, index: usize, position: Vec2) {
+        self.points[index].position = position;
+    }
+
+    pub fn positions(&self) -> Vec<Vec2> {
+        self.points.iter().map(|point| point.position).collect()
+    }
+
+    /// Moves every point along and pulls them back together.
+    pub fn step(&mut self, dt: f32) {
+        let acceleration = self.gravity * dt * dt;
+        for point in self.points.iter_mut().filter(|point| !point.pinned) {
+            let velocity = (point.position - point.previous) * self.damping;
+            point.previous = point.position;
+            point.position += velocity + acceleration;
+        }
+
+        for _ in 0..self.iterations {
+            self.solve_constraints();
+        }
+    }
+
+    fn solve_constraints(&mut self) {
+        for constraint in &self.constraints {
+            let a = self.points[constraint.a];
+            let b = self.points[constraint.b];
+            if a.pinned && b.pinned {
+                continue;
+            }
+
+            let offset = b.position - a.position;
+            let distance = offset.length();
+            if distance == 0.0 {
+                continue;
+            }
+            let correction = offset * ((distance - constraint.length) / distance);
+
+            // pinned points don't move, so the other one goes all the way
+            let share_a = match (a.pinned, b.pinned) {
+                (true, _) => 0.0,
+                (_, true) => 1.0,
+                _ => 0.5,
+            };
+            self.points[constraint.a].position += correction * share_a;
+            self.points[constraint.b].position -= correction * (1.0 - share_a);
+        }
+    }
+
+    /// Pushes every point out of `shape`. Works with any collider a circle can
+    /// [collide with](CollideWith).
+    pub fn collide<T>(&mut self, shape: &T)
+    where
+        Circle: CollideWith<T>,
+    {
+        let radius = self.radius;
+        for point in self.points.iter_mut().filter(|point| !point.pinned) {
+            let circle = Circle {
+                center: point.position,
+                radius,
+            };
+            if let Some(collision) = circle.collide_with(shape) {
+                point.position -= collision.normal * collision.depth;
+            }
+        }
+    }
+
+    /// Pushes every point out of the fixed colliders in `world`. Moving bodies are ignored, as
+    /// they wouldn't be pushed back.
+    pub fn collide_with_world<H: PhysicsHooks, E: EventHandler>(&mut self, world: &World<H, E>) {
+        let query = world.query_pipeline(QueryFilter::only_fixed().exclude_sensors());
+        let radius = self.radius;
+
+        for point in self.points.iter_mut().filter(|point| !point.pinned) {
+            let Some((_, projection)) =
+                query.project_point(&point.position.into(), f32::MAX, false)
+            else {
+                continue;
+            };
+
+            let surface = Vec2::new(projection.point.x, projection.point.y);
+            let offset = point.position - surface;
+            let distance = offset.length();
+            if !projection.is_inside && distance >= radius {
+                continue;
+            }
+
+            let outwards = if projection.is_inside {
+                -offset
+            } else {
+                offset
+            };
+            if let Some(outwards) = outwards.try_normalize() {
+                point.position = surface + outwards * radius;
+            }
+        }
+    }
+
+    /// Draws every constraint as a line.
+    pub fn draw(&self, thickness: f32, color: Color) {
+        for line in self.lines(thickness, color) {
+            line.draw();
+        }
+    }
+
+    pub fn draw_world(&self, thickness: f32, color: Color) {
+        for line in self.lines(thickness, color) {
+            line.draw_world();
+        }
+    }
+
+    fn lines(&self, thickness: f32, color: Color) -> impl Iterator<Item = Line> + '_ {
+        self.constraints.iter().map(move |constraint| Line {
+            start: self.points[constraint.a].position,
+            end: self.points[constraint.b].position,
+            thickness,
+            color,
+        })
+    }
+}
+
+/// A chain of points, for ropes, chains and vines. It's a [`SoftBody2D`] where each point is
+/// only joined to the next, drawn as one smooth [`Polyline`].
+#[derive(Debug, Clone)]
+pub struct Rope {
+    pub body: SoftBody2D,
+}
+
+impl Rope {
+    /// A rope from `start` to `end` made of `segments` pieces, pinned at the start.
+    pub fn new(start: Vec2, end: Vec2, segments: usize) -> Self {
+        let segments = segments.max(1);
+        let mut body = SoftBody2D::new();
+
+        for i in 0..=segments {
+            let index = body.add_point(start.lerp(end, i as f32 / segments as f32));
+            if i > 0 {
+                body.add_constraint(index - 1, index);
+            }
+        }
+        body.pin(0);
+
+        Self { body }
+    }
+
+    /// Pins the last point too, so it hangs between both ends.
+    pub fn pinned_at_both_ends(mut self) -> Self {
+        let last = self.body.points.len() - 1;
+        self.body.pin(last);
+        self
+    }
+
+    pub fn start(&self) -> Vec2 {
+        self.body.points[0].position
+    }
+
+    pub fn end(&self) -> Vec2 {
+        self.body.points[self.body.points.len() - 1].position
+    }
+
+    /// Moves the first point, like a hand holding the rope.
+    pub fn set_start(&mut self, position: Vec2) {
+        self.body.move_to(0, position);
+    }
+
+    pub fn set_end(&mut self, position: Vec2) {
+        let last = self.body.points.len() - 1;
+        self.body.move_to(last, position);
+    }
+
+    /// Length when not stretched.
+    pub fn length(&self) -> f32 {
+        self.body
+            .constraints
+            .iter()
+            .map(|constraint| constraint.length)
+            .sum()
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        self.body.step(dt);
+    }
+
+    pub fn collide<T>(&mut self, shape: &T)
+    where
+        Circle: CollideWith<T>,
+    {
+        self.body.collide(shape);
+    }
+
+    pub fn collide_with_world<H: PhysicsHooks, E: EventHandler>(&mut self, world: &World<H, E>) {
+        self.body.collide_with_world(world);
+    }
+
+    pub fn polyline(&self, thickness: f32, color: Color) -> Polyline {
+        Polyline::new(self.body.positions(), thickness, color)
+    }
+
+    pub fn draw(&self, thickness: f32, color: Color) {
+        self.polyline(thickness, color).draw();
+    }
+
+    pub fn draw_world(&self, thickness: f32, color: Color) {
+        self.polyline(thickness, color).draw_world();
+    }
+}
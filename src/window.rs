@@ -0,0 +1,410 @@
+//! Controls for the game's window: fullscreen, size limits, icon, title, visibility and the
+//! cursor, plus the monitors it could go on.
+//!
+//! Everything here does nothing in headless mode, and the queries give empty or default
+//! answers.
+
+use std::cmp::Ordering;
+
+use bevy_math::{IVec2, UVec2, Vec2};
+use glium::winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    monitor::{MonitorHandle, VideoModeHandle},
+    window::{CustomCursor, Fullscreen, Icon, Window},
+};
+
+pub use glium::winit::window::{CursorGrabMode, CursorIcon};
+
+use crate::{WindowContext, get_state, image::Image};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowMode {
+    Windowed,
+    /// A window the size of the monitor with no border, which is quick to switch in and out of.
+    BorderlessFullscreen,
+    /// Takes over the monitor, changing its resolution to `mode`, or keeping the monitor's
+    /// largest and fastest mode if that's `None`.
+    ExclusiveFullscreen(Option<VideoMode>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VideoMode {
+    pub size: UVec2,
+    pub bit_depth: u16,
+    pub refresh_rate_hz: f32,
+}
+
+impl VideoMode {
+    fn from_handle(mode: &VideoModeHandle) -> Self {
+        Self {
+            size: UVec2::new(mode.size().width, mode.size().height),
+            bit_depth: mode.bit_depth(),
+            refresh_rate_hz: mode.refresh_rate_millihertz() as f32 / 1000.0,
+        }
+    }
+
+    /// Puts larger modes first, then faster, then deeper.
+    pub(crate) fn best_first(&self, other: &Self) -> Ordering {
+        let area = |mode: &Self| mode.size.x as u64 * mode.size.y as u64;
+        area(other)
+            .cmp(&area(self))
+            .then(other.refresh_rate_hz.total_cmp(&self.refresh_rate_hz))
+            .then(other.bit_depth.cmp(&self.bit_depth))
+    }
+}
+
+/// Which of `modes`, sorted best first, exclusive fullscreen should use: the best one, or
+/// exactly the one `wanted`.
+pub(crate) fn pick_video_mode(modes: &[VideoMode], wanted: Option<VideoMode>) -> Option<usize> {
+    match wanted {
+        None => (!modes.is_empty()).then_some(0),
+        Some(wanted) => modes.iter().position(|mode| *mode == wanted),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    pub name: Option<String>,
+    /// Of the top left corner, in pixels, on the desktop.
+    pub position: IVec2,
+    pub size: UVec2,
+    pub scale_factor: f32,
+    pub refresh_rate_hz: Option<f32>,
+    /// What exclusive fullscreen can use on this monitor, largest and fastest first.
+    pub video_modes: Vec<VideoMode>,
+    pub is_primary: bool,
+    /// Whether the window is on this monitor.
+    pub is_current: bool,
+}
+
+impl Monitor {
+    fn from_handle(monitor: &MonitorHandle, window: &Window) -> Self {
+        Self {
+            name: monitor.name(),
+            position: IVec2::new(monitor.position().x, monitor.position().y),
+            size: UVec2::new(monitor.size().width, monitor.size().height),
+            scale_factor: monitor.scale_factor() as f32,
+            refresh_rate_hz: monitor
+                .refresh_rate_millihertz()
+                .map(|millihertz| millihertz as f32 / 1000.0),
+            video_modes: sorted_video_modes(monitor)
+                .iter()
+                .map(VideoMode::from_handle)
+                .collect(),
+            is_primary: window.primary_monitor().as_ref() == Some(monitor),
+            is_current: window.current_monitor().as_ref() == Some(monitor),
+        }
+    }
+}
+
+fn sorted_video_modes(monitor: &MonitorHandle) -> Vec<VideoModeHandle> {
+    let mut modes: Vec<_> = monitor.video_modes().collect();
+    modes.sort_by(|a, b| VideoMode::from_handle(a).best_first(&VideoMode::from_handle(b)));
+    modes
+}
+
+fn context() -> Option<&'static mut WindowContext> {
+    get_state().window_context.as_mut()
+}
+
+fn window() -> Option<&'static Window> {
+    context().map(|context| &context.window)
+}
+
+pub fn set_window_mode(mode: WindowMode) {
+    let Some(window) = window() else {
+        return;
+    };
+
+    let fullscreen = match mode {
+        WindowMode::Windowed => None,
+        WindowMode::BorderlessFullscreen => Some(Fullscreen::Borderless(None)),
+        WindowMode::ExclusiveFullscreen(wanted) => {
+            let Some(monitor) = window
+                .current_monitor()
+                .or_else(|| window.primary_monitor())
+            else {
+                log::warn!("no monitor to go fullscreen on");
+                return;
+            };
+            let mut handles = sorted_video_modes(&monitor);
+            let modes: Vec<_> = handles.iter().map(VideoMode::from_handle).collect();
+            match pick_video_mode(&modes, wanted) {
+                Some(index) => Some(Fullscreen::Exclusive(handles.swap_remove(index))),
+                None => {
+                    log::warn!("the monitor doesn't support the video mode {wanted:?}");
+                    return;
+                }
+            }
+        }
+    };
+
+    window.set_fullscreen(fullscreen);
+}
+
+pub fn window_mode() -> WindowMode {
+    match window().and_then(|window| window.fullscreen()) {
+        None => WindowMode::Windowed,
+        Some(Fullscreen::Borderless(_)) => WindowMode::BorderlessFullscreen,
+        Some(Fullscreen::Exclusive(handle)) => {
+            WindowMode::ExclusiveFullscreen(Some(VideoMode::from_handle(&handle)))
+        }
+    }
+}
+
+pub fn is_fullscreen() -> bool {
+    window_mode() != WindowMode::Windowed
+}
+
+/// Switches between windowed and borderless fullscreen.
+pub fn toggle_fullscreen() {
+    set_window_mode(if is_fullscreen() {
+        WindowMode::Windowed
+    } else {
+        WindowMode::BorderlessFullscreen
+    });
+}
+
+/// Asks for the window to be `size` pixels. The window manager might not allow it; a
+/// [`WindowResized`](crate::events::WindowResized) is sent if it does.
+pub fn set_window_size(size: UVec2) {
+    if let Some(window) = window() {
+        let _ = window.request_inner_size(PhysicalSize::new(size.x, size.y));
+    }
+}
+
+/// `None` for no limit.
+pub fn set_window_min_size(size: Option<UVec2>) {
+    if let Some(window) = window() {
+        window.set_min_inner_size(size.map(|size| PhysicalSize::new(size.x, size.y)));
+    }
+}
+
+/// `None` for no limit.
+pub fn set_window_max_size(size: Option<UVec2>) {
+    if let Some(window) = window() {
+        window.set_max_inner_size(size.map(|size| PhysicalSize::new(size.x, size.y)));
+    }
+}
+
+pub fn set_window_resizable(resizable: bool) {
+    if let Some(window) = window() {
+        window.set_resizable(resizable);
+    }
+}
+
+pub fn set_window_title(title: &str) {
+    if let Some(window) = window() {
+        window.set_title(title);
+    }
+}
+
+pub fn window_title() -> String {
+    window().map(|window| window.title()).unwrap_or_default()
+}
+
+/// `None` goes back to the platform's default icon. Not every platform shows one.
+pub fn set_window_icon(icon: Option<&Image>) -> anyhow::Result<()> {
+    let Some(window) = window() else {
+        return Ok(());
+    };
+
+    let icon = match icon {
+        Some(image) => {
+            let size = image.dimensions_u32();
+            Some(Icon::from_rgba(image.clone().into_bytes(), size.x, size.y)?)
+        }
+        None => None,
+    };
+    window.set_window_icon(icon);
+
+    Ok(())
+}
+
+pub fn set_window_visible(visible: bool) {
+    if let Some(window) = window() {
+        window.set_visible(visible);
+    }
+}
+
+pub fn show_window() {
+    set_window_visible(true);
+}
+
+pub fn hide_window() {
+    set_window_visible(false);
+}
+
+pub fn set_cursor_visible(visible: bool) {
+    if let Some(window) = window() {
+        window.set_cursor_visible(visible);
+    }
+}
+
+pub fn show_cursor() {
+    set_cursor_visible(true);
+}
+
+pub fn hide_cursor() {
+    set_cursor_visible(false);
+}
+
+pub fn set_cursor_icon(icon: CursorIcon) {
+    if let Some(window) = window() {
+        window.set_cursor(icon);
+    }
+}
+
+/// The grab asked for with [`set_cursor_grab`], kept so it can be put back when the window
+/// gets focus again.
+pub(crate) struct CursorGrab {
+    mode: CursorGrabMode,
+    /// The platform can't lock the cursor, so it's confined and moved back to the middle of the
+    /// window each frame instead.
+    recenter: bool,
+}
+
+impl Default for CursorGrab {
+    fn default() -> Self {
+        Self {
+            mode: CursorGrabMode::None,
+            recenter: false,
+        }
+    }
+}
+
+/// Keeps the cursor in the window. [`CursorGrabMode::Locked`] also stops it moving at all, for
+/// first-person cameras, and [`CursorGrabMode::None`] lets it go again.
+///
+/// [`mouse_diff`](crate::input::mouse_diff) keeps giving how far the mouse moved while the
+/// cursor is grabbed, even when it's stuck against the edge. Platforms that can't do one kind
+/// of grab get the other, with the cursor kept in the middle of the window if it couldn't be
+/// locked. Pair it with [`hide_cursor`].
+pub fn set_cursor_grab(mode: CursorGrabMode) {
+    let state = get_state();
+    state.cursor_grab.mode = mode;
+    if let Some(context) = &state.window_context {
+        apply_cursor_grab(&context.window);
+    }
+}
+
+pub fn cursor_grab() -> CursorGrabMode {
+    get_state().cursor_grab.mode
+}
+
+/// Locks and hides the cursor, for mouse look.
+pub fn lock_cursor() {
+    set_cursor_grab(CursorGrabMode::Locked);
+    hide_cursor();
+}
+
+/// Undoes [`lock_cursor`].
+pub fn unlock_cursor() {
+    set_cursor_grab(CursorGrabMode::None);
+    show_cursor();
+}
+
+pub(crate) fn apply_cursor_grab(window: &Window) {
+    let grab = &mut get_state().cursor_grab;
+    match grab_with_fallback(grab.mode, |mode| window.set_cursor_grab(mode)) {
+        Ok(recenter) => grab.recenter = recenter,
+        Err(e) => {
+            grab.recenter = false;
+            log::warn!("couldn't grab the cursor: {e}");
+        }
+    }
+}
+
+/// Grabs the cursor with `grab`, trying the other kind if the platform can't do `mode`.
+/// Returns whether the cursor has to be kept in the middle of the window by hand, because it
+/// was meant to be locked but could only be confined, or the first error if neither worked.
+pub(crate) fn grab_with_fallback<E>(
+    mode: CursorGrabMode,
+    mut grab: impl FnMut(CursorGrabMode) -> Result<(), E>,
+) -> Result<bool, E> {
+    let fallback = match mode {
+        CursorGrabMode::None => CursorGrabMode::None,
+        CursorGrabMode::Confined => CursorGrabMode::Locked,
+        CursorGrabMode::Locked => CursorGrabMode::Confined,
+    };
+
+    match grab(mode) {
+        Ok(()) => Ok(false),
+        Err(e) => match grab(fallback) {
+            Ok(()) => Ok(fallback == CursorGrabMode::Confined),
+            Err(_) => Err(e),
+        },
+    }
+}
+
+/// Moves the cursor back to the middle of the window if it's meant to be locked but can't be.
+pub(crate) fn update_cursor_grab() {
+    let state = get_state();
+    if let Some(context) = &state.window_context
+        && state.cursor_grab.recenter
+        && context.window.has_focus()
+    {
+        let size = context.window.inner_size();
+        let center = PhysicalPosition::new(size.width / 2, size.height / 2);
+        let _ = context.window.set_cursor_position(center);
+    }
+}
+
+/// Uses `image` as the cursor, with the pixel at `hotspot` being where it points.
+pub fn set_cursor_image(image: &Image, hotspot: UVec2) -> anyhow::Result<()> {
+    let Some(context) = context() else {
+        return Ok(());
+    };
+
+    let size = image.dimensions_u32();
+    let source = CustomCursor::from_rgba(
+        image.clone().into_bytes(),
+        u16::try_from(size.x)?,
+        u16::try_from(size.y)?,
+        u16::try_from(hotspot.x)?,
+        u16::try_from(hotspot.y)?,
+    )?;
+    let cursor = context.event_loop.create_custom_cursor(source);
+    context.window.set_cursor(cursor);
+
+    Ok(())
+}
+
+/// Lets the platform's IME be used for typing, which is needed for languages like Chinese or
+/// Japanese. Turn it on while a text box has focus and off again afterwards, as it can swallow
+/// key presses meant for the game.
+pub fn set_ime_allowed(allowed: bool) {
+    if let Some(window) = window() {
+        window.set_ime_allowed(allowed);
+    }
+}
+
+/// Where the text cursor is on screen, so the IME's candidate box can be put next to it.
+pub fn set_ime_cursor_area(top_left: Vec2, size: Vec2) {
+    if let Some(window) = window() {
+        window.set_ime_cursor_area(
+            PhysicalPosition::new(top_left.x, top_left.y),
+            PhysicalSize::new(size.x, size.y),
+        );
+    }
+}
+
+/// Every monitor connected, in the order the platform lists them.
+pub fn monitors() -> Vec<Monitor> {
+    let Some(window) = window() else {
+        return Vec::new();
+    };
+
+    window
+        .available_monitors()
+        .map(|monitor| Monitor::from_handle(&monitor, window))
+        .collect()
+}
+
+/// The monitor the window is on.
+pub fn current_monitor() -> Option<Monitor> {
+    let window = window()?;
+    window
+        .current_monitor()
+        .map(|monitor| Monitor::from_handle(&monitor, window))
+}
@@ -0,0 +1,46 @@
+//! Runs the engine for a few frames with no window, GL context or audio, the way CI and
+//! dedicated servers do. The engine can only be started once per process, so it's all one test.
+
+use engine_4::prelude::*;
+
+#[test]
+fn runs_frames_headless() {
+    init_headless_with_config(EngineConfig {
+        window_size: Some(UVec2::new(640, 480)),
+        ..Default::default()
+    })
+    .unwrap();
+
+    let entity = spawn();
+    insert_component(entity, 0u32);
+
+    for frame in 1..=5 {
+        for counter in query::<&mut u32>() {
+            *counter += 1;
+        }
+
+        // draw calls are ignored, text included
+        draw_circle(Vec2::ZERO, 5.0, Color::RED_500);
+        draw_rect_outline(Vec2::ZERO, Vec2::splat(10.0), 1.0, Color::WHITE);
+        draw_fps();
+        assert_eq!(draw_text("hello", Vec2::ZERO).size, Vec2::ZERO);
+        assert_eq!(
+            draw_rich_text("[b]hello[/b]", TextDrawParams::default()).size,
+            Vec2::ZERO
+        );
+        draw_text_boxed(
+            "hello there",
+            bevy_math::Rect::new(0.0, 0.0, 50.0, 50.0),
+            TextStyle::default(),
+        );
+
+        // but text can still be measured, for laying things out
+        assert!(measure_text("hello", TextStyle::default()).x > 0.0);
+
+        next_frame();
+        assert_eq!(frame_count(), frame);
+    }
+
+    assert_eq!(get_component::<u32>(entity), Some(&5));
+    assert_eq!(window_size(), vec2(640.0, 480.0));
+}